@@ -0,0 +1,149 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use funcall::{Arg, Convention, Func, Signature};
+
+extern "C" fn mixed(a: i32, b: f64, c: *const u8, d: u64) -> f64 {
+    a as f64 + b + c as usize as f64 + d as f64
+}
+
+#[cfg(all(target_arch = "x86_64", any(target_os = "linux", windows)))]
+extern "C" fn six(a: i64, b: i64, c: i64, d: i64, e: i64, f: i64) -> i64 {
+    a + b + c + d + e + f
+}
+
+#[allow(clippy::too_many_arguments)]
+extern "C" fn eight(a: i64, b: i64, c: i64, d: i64, e: i64, f: i64, g: i64, h: i64) -> i64 {
+    a + b + c + d + e + f + g + h
+}
+
+fn call(c: &mut Criterion) {
+    let target = mixed as *const fn();
+    let sig = Signature::parse("double mixed(int, double, const char*, uint64_t)").unwrap();
+    let args = [Arg::I32(1), Arg::F64(2.0), Arg::Ptr(0), Arg::U64(4)];
+
+    let mut group = c.benchmark_group("call");
+    group.bench_function("push", |b| {
+        b.iter(|| {
+            let mut func = Func::from_raw(target);
+            func.push(black_box(1i32));
+            func.push(black_box(2.0f64));
+            func.push(black_box(std::ptr::null::<u8>()));
+            func.push(black_box(4u64));
+            unsafe { func.cdecl() };
+            func.ret_as_f64()
+        })
+    });
+    group.bench_function("push_only", |b| {
+        b.iter(|| {
+            let mut func = Func::from_raw(target);
+            func.push(black_box(1i32))
+                .push(black_box(2i64))
+                .push(black_box(3u8))
+                .push(black_box(4usize))
+                .push(black_box(5u128));
+            func.stack_bytes()
+        })
+    });
+    group.bench_function("push_checked", |b| {
+        b.iter(|| {
+            let mut func = Func::from_raw(target);
+            for (i, arg) in black_box(&args).iter().enumerate() {
+                sig.push_checked(&mut func, i, arg.clone()).unwrap();
+            }
+            unsafe { func.cdecl() };
+            func.ret_as_f64()
+        })
+    });
+    let mut func = Func::from_raw(target);
+    func.push(1i32)
+        .push(2.0f64)
+        .push(std::ptr::null::<u8>())
+        .push(4u64);
+    let mut prepared = func.prepare(Convention::Cdecl).unwrap();
+    group.bench_function("prepared", |b| {
+        b.iter(|| {
+            prepared.replace_arg(0, black_box(1i32)).unwrap();
+            unsafe { prepared.invoke().as_f64() }
+        })
+    });
+    // 六个参数只用寄存器, 八个参数时有两个需要通过栈传递
+    let mut func = Func::from_raw(eight as *const fn());
+    func.push_args((1i64, 2i64, 3i64, 4i64, 5i64, 6i64, 7i64, 8i64));
+    let mut prepared = func.prepare(Convention::Cdecl).unwrap();
+    group.bench_function("prepared_stack", |b| {
+        b.iter(|| {
+            prepared.replace_arg(0, black_box(1i64)).unwrap();
+            unsafe { prepared.invoke().as_i64() }
+        })
+    });
+    #[cfg(all(target_arch = "x86_64", target_os = "linux"))]
+    {
+        let mut func = Func::from_raw(target);
+        func.push(1i32)
+            .push(2.0f64)
+            .push(std::ptr::null::<u8>())
+            .push(4u64);
+        let mut prepared = func
+            .prepare_with(Convention::Cdecl, funcall::Backend::Jit)
+            .unwrap();
+        group.bench_function("prepared_jit", |b| {
+            b.iter(|| {
+                prepared.replace_arg(0, black_box(1i32)).unwrap();
+                unsafe { prepared.invoke().as_f64() }
+            })
+        });
+    }
+    // 不经过 `Func` 的下限, 只有整数参数
+    #[cfg(all(target_arch = "x86_64", any(target_os = "linux", windows)))]
+    group.bench_function("raw6", |b| {
+        let target = six as *const fn();
+        b.iter(|| unsafe { funcall::call_raw6(target, black_box([1, 2, 3, 4, 5, 6]), 6).as_i64() })
+    });
+    let compiled = sig.compile(Convention::Cdecl);
+    group.bench_function("compiled", |b| {
+        b.iter(|| unsafe { compiled.invoke(target, black_box(&args)).unwrap().as_f64() })
+    });
+    #[cfg(all(target_arch = "x86_64", target_os = "linux"))]
+    {
+        let jit = sig.compile_with(Convention::Cdecl, funcall::Backend::Jit);
+        group.bench_function("jit", |b| {
+            b.iter(|| unsafe { jit.invoke(target, black_box(&args)).unwrap().as_f64() })
+        });
+    }
+    group.finish();
+}
+
+fn batch(c: &mut Criterion) {
+    let target = mixed as *const fn();
+    let rows: Vec<Vec<Arg>> = (0..1000)
+        .map(|i| vec![Arg::I32(i), Arg::F64(2.0), Arg::Ptr(0), Arg::U64(4)])
+        .collect();
+    let rows: Vec<&[Arg]> = rows.iter().map(Vec::as_slice).collect();
+
+    let mut group = c.benchmark_group("batch");
+    group.bench_function("push", |b| {
+        b.iter(|| {
+            let mut out = Vec::with_capacity(rows.len());
+            for row in black_box(&rows) {
+                let mut func = Func::from_raw(target);
+                for arg in row.iter() {
+                    func.push_arg(arg.clone());
+                }
+                unsafe { func.cdecl() };
+                out.push(func.ret());
+            }
+            out
+        })
+    });
+    group.bench_function("call_batch", |b| {
+        let mut func = Func::from_raw(target);
+        b.iter(|| {
+            let mut out = Vec::with_capacity(rows.len());
+            unsafe { func.call_batch(Convention::Cdecl, black_box(&rows), &mut out) }.unwrap();
+            out
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, call, batch);
+criterion_main!(benches);
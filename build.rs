@@ -0,0 +1,21 @@
+//! 记录编译目标, 启用 `protected` feature 时编译捕获硬件异常的 C 代码
+
+fn main() {
+    // 供 `CallError::unsupported_target` 使用
+    for (name, cfg) in [
+        ("FUNCALL_TARGET_ARCH", "CARGO_CFG_TARGET_ARCH"),
+        ("FUNCALL_TARGET_OS", "CARGO_CFG_TARGET_OS"),
+    ] {
+        let value = std::env::var(cfg).unwrap_or_default();
+        println!("cargo:rustc-env={}={}", name, value);
+    }
+    #[cfg(feature = "protected")]
+    {
+        let src = match std::env::var("CARGO_CFG_TARGET_FAMILY").as_deref() {
+            Ok("windows") => "csrc/protect_windows.c",
+            _ => "csrc/protect_unix.c",
+        };
+        println!("cargo:rerun-if-changed={}", src);
+        cc::Build::new().file(src).compile("funcall_protect");
+    }
+}
@@ -0,0 +1,138 @@
+/* 在单独的线程中调用, 捕获调用期间的 SIGSEGV, SIGBUS, SIGFPE 与 SIGILL.
+ * 出错的线程报告信号后被永久挂起, 不会通过 siglongjmp 跳过 Rust 的调用帧 */
+
+#include <errno.h>
+#include <pthread.h>
+#include <signal.h>
+#include <stdlib.h>
+#include <string.h>
+#include <unistd.h>
+
+static const int SIGNALS[] = {SIGSEGV, SIGBUS, SIGFPE, SIGILL};
+#define NSIGNALS (sizeof(SIGNALS) / sizeof(SIGNALS[0]))
+
+/* 安装前的处理函数, 只在持有 Rust 一侧的锁时修改 */
+static struct sigaction previous[NSIGNALS];
+
+/* 一次受保护的调用, 调用结束或出错时向 done 写入一个字节 */
+struct call {
+    void (*callback)(void *);
+    void *data;
+    int done;
+    int signal;
+    void *addr;
+};
+
+/* 本线程正在进行的受保护调用 */
+static __thread struct call *current;
+
+/* 不是受保护的调用引起的信号交给之前的处理函数 */
+static void chain(int sig, siginfo_t *info, void *context) {
+    size_t i;
+    for (i = 0; i < NSIGNALS; i++) {
+        if (SIGNALS[i] != sig) {
+            continue;
+        }
+        if (previous[i].sa_flags & SA_SIGINFO) {
+            previous[i].sa_sigaction(sig, info, context);
+        } else if (previous[i].sa_handler == SIG_DFL) {
+            /* 恢复默认行为, 返回后会再次触发同一个异常 */
+            signal(sig, SIG_DFL);
+        } else if (previous[i].sa_handler != SIG_IGN) {
+            previous[i].sa_handler(sig);
+        }
+    }
+}
+
+static void notify(int fd) {
+    char byte = 0;
+    while (write(fd, &byte, 1) < 0 && errno == EINTR) {
+    }
+}
+
+static void handler(int sig, siginfo_t *info, void *context) {
+    struct call *call = current;
+    if (call == NULL) {
+        chain(sig, info, context);
+        return;
+    }
+    current = NULL;
+    call->signal = sig;
+    call->addr = info->si_addr;
+    notify(call->done);
+    /* 被调用者的栈帧无法安全地离开, 挂起本线程, 它的栈与持有的资源都会泄漏 */
+    for (;;) {
+        pause();
+    }
+}
+
+static void *run(void *arg) {
+    struct call *call = arg;
+    /* 栈溢出时只能在备用栈上处理 */
+    stack_t stack;
+    memset(&stack, 0, sizeof(stack));
+    stack.ss_size = SIGSTKSZ > 65536 ? SIGSTKSZ : 65536;
+    stack.ss_sp = malloc(stack.ss_size);
+    if (stack.ss_sp != NULL) {
+        sigaltstack(&stack, NULL);
+    }
+    current = call;
+    call->callback(call->data);
+    current = NULL;
+    if (stack.ss_sp != NULL) {
+        stack.ss_flags = SS_DISABLE;
+        sigaltstack(&stack, NULL);
+        free(stack.ss_sp);
+    }
+    notify(call->done);
+    return NULL;
+}
+
+/* 在新线程中调用 callback(data) 并等待其结束, 捕获到信号时返回 1 并写入信号与地址, 无法创建线程时返回 -1 */
+int funcall_protected_call(void (*callback)(void *), void *data, int *sig, void **addr) {
+    struct sigaction action;
+    struct call call;
+    pthread_t thread;
+    int fds[2];
+    char byte;
+    int faulted = -1;
+    size_t i;
+
+    if (pipe(fds) != 0) {
+        return -1;
+    }
+    memset(&call, 0, sizeof(call));
+    call.callback = callback;
+    call.data = data;
+    call.done = fds[1];
+
+    memset(&action, 0, sizeof(action));
+    action.sa_sigaction = handler;
+    action.sa_flags = SA_SIGINFO | SA_ONSTACK;
+    sigemptyset(&action.sa_mask);
+    for (i = 0; i < NSIGNALS; i++) {
+        sigaction(SIGNALS[i], &action, &previous[i]);
+    }
+
+    if (pthread_create(&thread, NULL, run, &call) == 0) {
+        while (read(fds[0], &byte, 1) < 0 && errno == EINTR) {
+        }
+        if (call.signal == 0) {
+            pthread_join(thread, NULL);
+            faulted = 0;
+        } else {
+            pthread_detach(thread);
+            faulted = 1;
+            *sig = call.signal;
+            *addr = call.addr;
+        }
+    }
+
+    for (i = 0; i < NSIGNALS; i++) {
+        sigaction(SIGNALS[i], &previous[i], NULL);
+    }
+    close(fds[0]);
+    /* 挂起的线程不会再写入, 写端可以关闭 */
+    close(fds[1]);
+    return faulted;
+}
@@ -0,0 +1,60 @@
+/* 在调用期间通过 SEH 捕获访问违例等硬件异常 */
+
+#include <malloc.h>
+#include <windows.h>
+
+static int filter(EXCEPTION_POINTERS *info, DWORD *code, void **addr) {
+    EXCEPTION_RECORD *record = info->ExceptionRecord;
+    switch (record->ExceptionCode) {
+    case EXCEPTION_ACCESS_VIOLATION:
+    case EXCEPTION_IN_PAGE_ERROR:
+        /* 第二个参数为访问的地址 */
+        *addr = (void *)record->ExceptionInformation[1];
+        break;
+    case EXCEPTION_INT_DIVIDE_BY_ZERO:
+    case EXCEPTION_INT_OVERFLOW:
+    case EXCEPTION_FLT_DIVIDE_BY_ZERO:
+    case EXCEPTION_FLT_INVALID_OPERATION:
+    case EXCEPTION_FLT_OVERFLOW:
+    case EXCEPTION_FLT_UNDERFLOW:
+    case EXCEPTION_FLT_INEXACT_RESULT:
+    case EXCEPTION_FLT_DENORMAL_OPERAND:
+    case EXCEPTION_FLT_STACK_CHECK:
+    case EXCEPTION_ILLEGAL_INSTRUCTION:
+    case EXCEPTION_PRIV_INSTRUCTION:
+    case EXCEPTION_STACK_OVERFLOW:
+        *addr = record->ExceptionAddress;
+        break;
+    default:
+        /* C++ 异常等软件异常继续向外传递 */
+        return EXCEPTION_CONTINUE_SEARCH;
+    }
+    *code = record->ExceptionCode;
+    return EXCEPTION_EXECUTE_HANDLER;
+}
+
+/* 调用 callback(data), 捕获到异常时返回 1 并写入异常代码与地址 */
+int funcall_protected_call(void (*callback)(void *), void *data, DWORD *code, void **addr) {
+    __try {
+        callback(data);
+    } __except (filter(GetExceptionInformation(), code, addr)) {
+        if (*code == EXCEPTION_STACK_OVERFLOW) {
+            _resetstkoflw();
+        }
+        return 1;
+    }
+    return 0;
+}
+
+/* 调用 callback(data), 捕获到包括 C++ 异常在内的任何异常时返回 1 并写入异常代码 */
+int funcall_catching_call(void (*callback)(void *), void *data, DWORD *code) {
+    __try {
+        callback(data);
+    } __except (*code = GetExceptionCode(), EXCEPTION_EXECUTE_HANDLER) {
+        if (*code == EXCEPTION_STACK_OVERFLOW) {
+            _resetstkoflw();
+        }
+        return 1;
+    }
+    return 0;
+}
@@ -0,0 +1,173 @@
+//! funcall 的过程宏, 通过 funcall 的 `macros` feature 使用, 参见 `funcall::dynamic_extern`
+
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::{format_ident, quote};
+use syn::{
+    parse_macro_input, Abi, Error, Expr, FnArg, ForeignItem, ForeignItemFn, Ident, ItemForeignMod,
+    LitStr, Pat, ReturnType,
+};
+
+/// 查找失败时的处理方式
+#[derive(Clone, Copy, PartialEq)]
+enum OnError {
+    Result,
+    Panic,
+}
+
+struct Options {
+    lib: Expr,
+    on_error: OnError,
+}
+
+/// 由 extern 块生成首次调用时才查找函数的包装函数, 参见 `funcall::dynamic_extern`
+#[proc_macro_attribute]
+pub fn dynamic_extern(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let mut lib = None;
+    let mut on_error = OnError::Result;
+    let parser = syn::meta::parser(|meta| {
+        if meta.path.is_ident("lib") {
+            lib = Some(meta.value()?.parse::<Expr>()?);
+        } else if meta.path.is_ident("on_error") {
+            let value: LitStr = meta.value()?.parse()?;
+            on_error = match value.value().as_str() {
+                "result" => OnError::Result,
+                "panic" => OnError::Panic,
+                _ => return Err(Error::new(value.span(), "expected `result` or `panic`")),
+            };
+        } else {
+            return Err(meta.error("expected `lib` or `on_error`"));
+        }
+        Ok(())
+    });
+    parse_macro_input!(attr with parser);
+    let lib = match lib {
+        Some(lib) => lib,
+        None => {
+            return Error::new(Span::call_site(), "missing `lib = \"...\"`")
+                .to_compile_error()
+                .into()
+        }
+    };
+    let block = parse_macro_input!(item as ItemForeignMod);
+    let options = Options { lib, on_error };
+    let items = block.items.iter().map(|item| match item {
+        ForeignItem::Fn(func) => expand_fn(&options, &block.abi, func),
+        _ => Err(Error::new_spanned(
+            item,
+            "only functions are supported in `dynamic_extern`",
+        )),
+    });
+    items
+        .map(|item| item.unwrap_or_else(Error::into_compile_error))
+        .collect::<TokenStream2>()
+        .into()
+}
+
+fn expand_fn(options: &Options, abi: &Abi, func: &ForeignItemFn) -> syn::Result<TokenStream2> {
+    let sig = &func.sig;
+    let name = &sig.ident;
+    let vis = &func.vis;
+    let lib = &options.lib;
+
+    // `#[link_name]` 指定实际查找的符号, 其他属性原样保留
+    let mut symbol = LitStr::new(&name.to_string(), name.span());
+    let mut attrs = Vec::new();
+    for attr in &func.attrs {
+        if attr.path().is_ident("link_name") {
+            symbol = match &attr.meta.require_name_value()?.value {
+                Expr::Lit(syn::ExprLit {
+                    lit: syn::Lit::Str(s),
+                    ..
+                }) => s.clone(),
+                value => return Err(Error::new_spanned(value, "expected a string literal")),
+            };
+        } else {
+            attrs.push(attr);
+        }
+    }
+
+    // 外部块中的参数可以是 `_`, 生成的函数需要为它们命名
+    let mut names = Vec::new();
+    let mut types = Vec::new();
+    for (i, input) in sig.inputs.iter().enumerate() {
+        let arg = match input {
+            FnArg::Typed(arg) => arg,
+            FnArg::Receiver(recv) => return Err(Error::new_spanned(recv, "unexpected `self`")),
+        };
+        names.push(match &*arg.pat {
+            Pat::Ident(pat) => pat.ident.clone(),
+            _ => format_ident!("__arg{}", i),
+        });
+        types.push(&arg.ty);
+    }
+    let ret = match &sig.output {
+        ReturnType::Default => quote!(()),
+        ReturnType::Type(_, ty) => quote!(#ty),
+    };
+
+    let resolve = match options.on_error {
+        OnError::Result => quote!(FUNC.try_get()?),
+        OnError::Panic => quote!(FUNC.try_get().unwrap_or_else(|e| panic!("{}", e))),
+    };
+    let (rest, call) = match &sig.variadic {
+        // 可变参数部分的类型在编译时未知, 改为通过 `Func` 压入
+        Some(variadic) => {
+            if abi.name.as_ref().is_some_and(|abi| abi.value() != "C") {
+                return Err(Error::new_spanned(
+                    abi,
+                    "variadic functions must use the \"C\" calling convention",
+                ));
+            }
+            let rest = variadic
+                .pat
+                .as_ref()
+                .and_then(|(pat, _)| match &**pat {
+                    Pat::Ident(pat) => Some(pat.ident.clone()),
+                    _ => None,
+                })
+                .unwrap_or_else(|| Ident::new("args", Span::call_site()));
+            let fixed = names.len();
+            let checked = match options.on_error {
+                OnError::Result => quote!(func.try_call(::funcall::Convention::Cdecl)?),
+                OnError::Panic => quote!(func
+                    .try_call(::funcall::Convention::Cdecl)
+                    .unwrap_or_else(|e| panic!("failed to call `{}`: {}", #symbol, e))),
+            };
+            let call = quote! {
+                let mut func = #resolve.func();
+                func.set_arity(#fixed, true);
+                #(func.push(#names);)*
+                for arg in #rest {
+                    func.push_arg(::core::clone::Clone::clone(arg));
+                }
+                #checked;
+                func.ret().get::<#ret>()
+            };
+            (Some(quote!(#rest: &[::funcall::Arg])), call)
+        }
+        None => {
+            let call = quote! {
+                let ptr: unsafe #abi fn(#(#types),*) -> #ret =
+                    ::core::mem::transmute::<*const fn(), _>(#resolve.ptr());
+                ptr(#(#names),*)
+            };
+            (None, call)
+        }
+    };
+    let (output, body) = match options.on_error {
+        OnError::Result => (
+            quote!(::funcall::Result<#ret>),
+            quote!(::core::result::Result::Ok({ #call })),
+        ),
+        OnError::Panic => (ret, call),
+    };
+    Ok(quote! {
+        #(#attrs)*
+        #[allow(non_snake_case, clippy::missing_safety_doc)]
+        #vis unsafe fn #name(#(#names: #types,)* #rest) -> #output {
+            static FUNC: ::funcall::LazyFunc = ::funcall::LazyFunc::new(#lib, #symbol);
+            #body
+        }
+    })
+}
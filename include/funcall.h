@@ -0,0 +1,66 @@
+/*
+ * funcall 的 C 接口, 需要以 `capi` feature 编译, 参见 src/capi.rs
+ *
+ * 出错的函数返回非零的错误码 (funcall_new 返回 NULL), 错误码与错误信息
+ * 记录在当前线程中, 通过 funcall_last_error 取得.
+ */
+#ifndef FUNCALL_H
+#define FUNCALL_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* 错误码 */
+#define FUNCALL_OK 0
+#define FUNCALL_ERR_INVALID 1 /* 句柄或指针参数为空, 或字符串不是合法的 UTF-8 */
+#define FUNCALL_ERR_LOAD 2    /* 无法加载库或找不到符号 */
+#define FUNCALL_ERR_CALL 3    /* 调用前的检查未通过 */
+#define FUNCALL_ERR_PANIC 4   /* 调用过程中发生了 panic */
+
+/* funcall_call 的调用约定 */
+#define FUNCALL_CDECL 0
+#define FUNCALL_STDCALL 1 /* 只在 32 位 x86 下可用 */
+#define FUNCALL_SYSTEM 2  /* 同 extern "system", 32 位 Windows 下为 stdcall, 其他平台上为 cdecl */
+#define FUNCALL_THISCALL 3 /* 只在 32 位 x86 下可用, 第一个参数为 this */
+#define FUNCALL_FASTCALL 4 /* 只在 32 位 x86 下可用 */
+#define FUNCALL_AAPCS_SOFTFLOAT 5 /* 只在 ARM32 下可用, 浮点数通过整数寄存器传递 */
+#define FUNCALL_AAPCS_VFP 6 /* 只在硬浮点的 ARM32 下可用, 浮点数通过 VFP 寄存器传递 */
+
+/* 待调用的函数及其参数, 由 funcall_free 释放 */
+typedef struct funcall_func funcall_func;
+
+/* 加载动态库 lib 并查找其中的符号 sym, 失败时返回 NULL */
+funcall_func *funcall_new(const char *lib, const char *sym);
+/* 以已知的函数地址创建句柄 */
+funcall_func *funcall_from_raw(const void *ptr);
+
+int funcall_push_i32(funcall_func *func, int32_t value);
+/* 32 位平台上占用两个参数位置 */
+int funcall_push_i64(funcall_func *func, int64_t value);
+int funcall_push_f64(funcall_func *func, double value);
+int funcall_push_ptr(funcall_func *func, const void *value);
+/* 复制 len 个字节并压入指向副本的指针, 副本在句柄释放前一直有效 */
+int funcall_push_bytes(funcall_func *func, const uint8_t *data, size_t len);
+
+/* 以已压入的参数调用函数, 同一个句柄可以反复调用 */
+int funcall_call(funcall_func *func, int convention);
+/* 上一次调用的返回值, func 为 NULL 时记录 FUNCALL_ERR_INVALID 并返回 0 */
+int64_t funcall_ret_i64(const funcall_func *func);
+double funcall_ret_f64(const funcall_func *func);
+
+/* 当前线程中最后一次出错的错误码, message 不为 NULL 时写入错误信息,
+ * 在当前线程再次出错前一直有效 */
+int funcall_last_error(const char **message);
+
+/* 释放句柄, 传入 NULL 时什么也不做 */
+void funcall_free(funcall_func *func);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* FUNCALL_H */
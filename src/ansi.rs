@@ -0,0 +1,114 @@
+//! 以 ANSI 代码页压入字符串, 参见 `Func::push_ansi_str`
+
+use alloc::format;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::ptr;
+
+use crate::{Error, Func, Result};
+
+const CP_ACP: u32 = 0;
+const CP_UTF8: u32 = 65001;
+const WC_NO_BEST_FIT_CHARS: u32 = 0x400;
+
+#[link(name = "kernel32")]
+extern "system" {
+    fn GetACP() -> u32;
+    fn WideCharToMultiByte(
+        code_page: u32,
+        flags: u32,
+        wide: *const u16,
+        wide_len: i32,
+        out: *mut u8,
+        out_len: i32,
+        default_char: *const u8,
+        used_default: *mut i32,
+    ) -> i32;
+}
+
+impl Func {
+    /// 将字符串转换为当前 ANSI 代码页 (`CP_ACP`) 中的编码, 在末尾补上 '\0' 后压入, 用于以 A 结尾的 Windows API
+    ///
+    /// 转换后的字符串由 `Func` 持有, 与 `Arg::Str` 相同. strict 为 `true` 时,
+    /// 代码页中没有对应字符的字符串会返回 `Error::InvalidInput` 而不压入任何参数;
+    /// 否则与系统的默认行为相同, 这样的字符会被替换为相近的字符或 '?'
+    ///
+    /// ```
+    /// use funcall::{Convention, Func};
+    /// use std::ffi::CStr;
+    /// use std::os::raw::c_char;
+    ///
+    /// extern "C" fn len(s: *const c_char) -> usize {
+    ///     unsafe { CStr::from_ptr(s) }.to_bytes().len()
+    /// }
+    ///
+    /// let mut func = Func::from_raw(len as *const fn());
+    /// func.push_ansi_str("hello", true).unwrap();
+    /// unsafe { func.try_call(Convention::Cdecl).unwrap() };
+    /// assert_eq!(func.ret_as_usize(), 5);
+    /// ```
+    #[cfg_attr(feature = "provenance", track_caller)]
+    pub fn push_ansi_str(&mut self, s: &str, strict: bool) -> Result<&mut Self> {
+        let bytes = to_ansi(s, strict)?;
+        let start = self.slots.len();
+        self.push_owned(bytes);
+        Ok(self.mark_origin(start))
+    }
+}
+
+/// 将 s 经 UTF-16 转换为当前 ANSI 代码页中的字节, 末尾带有 '\0'
+fn to_ansi(s: &str, strict: bool) -> Result<Vec<u8>> {
+    let acp = unsafe { GetACP() };
+    // 代码页为 UTF-8 时不需要转换, 且 WideCharToMultiByte 此时不接受 used_default
+    if acp == CP_UTF8 {
+        let mut bytes = s.as_bytes().to_vec();
+        bytes.push(0);
+        return Ok(bytes);
+    }
+    // 包括结尾的 '\0', 以便 WideCharToMultiByte 一并写出
+    let wide: Vec<u16> = s.encode_utf16().chain(Some(0)).collect();
+    let flags = if strict { WC_NO_BEST_FIT_CHARS } else { 0 };
+    let mut used_default = 0;
+    let used_default_ptr = if strict {
+        &mut used_default as *mut i32
+    } else {
+        ptr::null_mut()
+    };
+    unsafe {
+        let len = WideCharToMultiByte(
+            CP_ACP,
+            flags,
+            wide.as_ptr(),
+            wide.len() as i32,
+            ptr::null_mut(),
+            0,
+            ptr::null(),
+            ptr::null_mut(),
+        );
+        let mut bytes = vec![0u8; len.max(0) as usize];
+        let written = WideCharToMultiByte(
+            CP_ACP,
+            flags,
+            wide.as_ptr(),
+            wide.len() as i32,
+            bytes.as_mut_ptr(),
+            len,
+            ptr::null(),
+            used_default_ptr,
+        );
+        if written <= 0 {
+            return Err(Error::InvalidInput(format!(
+                "failed to convert {:?} to code page {}",
+                s, acp
+            )));
+        }
+        if used_default != 0 {
+            return Err(Error::InvalidInput(format!(
+                "{:?} cannot be represented in code page {}",
+                s, acp
+            )));
+        }
+        bytes.truncate(written as usize);
+        Ok(bytes)
+    }
+}
@@ -0,0 +1,83 @@
+//! 运行时才确定类型的参数
+
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::Func;
+
+/// 运行时才确定类型的参数, 可通过 `Func::push_arg` 压入
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Arg {
+    I8(i8),
+    U8(u8),
+    I16(i16),
+    U16(u16),
+    I32(i32),
+    U32(u32),
+    I64(i64),
+    U64(u64),
+    I128(i128),
+    U128(u128),
+    Isize(isize),
+    Usize(usize),
+    F32(f32),
+    F64(f64),
+    /// 指针, 以地址的形式保存
+    ///
+    /// 序列化时只会得到一个不透明的整数, 反序列化后的地址在其他进程中没有意义
+    Ptr(usize),
+    /// 一段字节, 压入时复制一份由 `Func` 持有, 实际传递的是指向它的指针
+    Bytes(Vec<u8>),
+    /// 字符串, 压入时会在末尾补上 '\0', 实际传递的是指向它的指针
+    Str(String),
+}
+
+impl Func {
+    /// 压入运行时才确定类型的参数
+    ///
+    /// `Arg::Bytes` 与 `Arg::Str` 的内容由 `Func` 持有, 在它被 drop 前一直有效; 克隆 `Func` 时会各自复制一份
+    #[cfg_attr(feature = "provenance", track_caller)]
+    pub fn push_arg(&mut self, arg: Arg) -> &mut Self {
+        let start = self.slots.len();
+        if self.signature.is_some() {
+            self.push_declared(|func| func.try_push_arg(arg).map(drop));
+            return self.mark_origin(start);
+        }
+        match arg {
+            Arg::I8(v) => self.push(v),
+            Arg::U8(v) => self.push(v),
+            Arg::I16(v) => self.push(v),
+            Arg::U16(v) => self.push(v),
+            Arg::I32(v) => self.push(v),
+            Arg::U32(v) => self.push(v),
+            Arg::I64(v) => self.push(v),
+            Arg::U64(v) => self.push(v),
+            Arg::I128(v) => self.push(v),
+            Arg::U128(v) => self.push(v),
+            Arg::Isize(v) => self.push(v),
+            Arg::Usize(v) => self.push(v),
+            Arg::F32(v) => self.push(v),
+            Arg::F64(v) => self.push(v),
+            Arg::Ptr(v) => self.push(v as *const u8),
+            Arg::Bytes(v) => self.push_owned(v),
+            Arg::Str(v) => {
+                let mut v = v.into_bytes();
+                v.push(0);
+                self.push_owned(v)
+            }
+        };
+        self.mark_origin(start)
+    }
+
+    pub(crate) fn push_owned(&mut self, bytes: Vec<u8>) -> &mut Self {
+        let bytes: Arc<[u8]> = bytes.into();
+        self.push(bytes.as_ptr());
+        self.owned.push(bytes);
+        self
+    }
+}
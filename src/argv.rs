@@ -0,0 +1,77 @@
+//! 以空指针结尾的字符串指针数组, 参见 `Func::push_cstring_array`
+
+use alloc::format;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::ffi::CStr;
+
+use crate::{Error, Func, Result};
+
+impl Func {
+    /// 复制 items 中的每个字符串并在末尾补上 '\0', 压入指向以空指针结尾的字符串指针数组的指针,
+    /// 用于 `execvp` 的 argv, `posix_spawn` 的 envp 等参数
+    ///
+    /// 字符串与数组都由 `Func` 持有, 与 `Arg::Str` 相同; 克隆 `Func` 时各自复制一份, 副本中的数组指向复制出的字符串.
+    /// 字符串中含有 '\0' 时返回 `Error::InvalidInput`, 不压入任何参数
+    ///
+    /// ```
+    /// use funcall::{Convention, Func};
+    /// use std::ffi::CStr;
+    /// use std::os::raw::c_char;
+    ///
+    /// extern "C" fn count(argv: *const *const c_char) -> usize {
+    ///     let mut n = 0;
+    ///     while !unsafe { *argv.add(n) }.is_null() {
+    ///         n += 1;
+    ///     }
+    ///     n
+    /// }
+    ///
+    /// let mut func = Func::from_raw(count as *const fn());
+    /// func.push_cstring_array(&["ls", "-l", "/"]).unwrap();
+    /// unsafe { func.try_call(Convention::Cdecl).unwrap() };
+    /// assert_eq!(func.ret_as_usize(), 3);
+    /// ```
+    #[cfg_attr(feature = "provenance", track_caller)]
+    pub fn push_cstring_array(&mut self, items: &[&str]) -> Result<&mut Self> {
+        if let Some(i) = items.iter().position(|item| item.contains('\0')) {
+            return Err(Error::InvalidInput(format!(
+                "string {} of the array contains a nul byte",
+                i
+            )));
+        }
+        let ptrs = items
+            .iter()
+            .map(|item| {
+                let mut bytes = Vec::with_capacity(item.len() + 1);
+                bytes.extend_from_slice(item.as_bytes());
+                bytes.push(0);
+                let bytes: Arc<[u8]> = bytes.into();
+                let ptr = bytes.as_ptr() as usize;
+                self.owned.push(bytes);
+                ptr
+            })
+            .collect::<Vec<_>>();
+        self.tables.push(self.owned.len());
+        let start = self.slots.len();
+        self.push_ptr_array(ptrs);
+        Ok(self.mark_origin(start))
+    }
+
+    /// 同 `push_cstring_array`, 但不复制字符串, 只有指针数组由 `Func` 持有
+    ///
+    /// items 中的字符串需要在调用结束前一直有效
+    #[cfg_attr(feature = "provenance", track_caller)]
+    pub fn push_cstr_array(&mut self, items: &[&CStr]) -> &mut Self {
+        let start = self.slots.len();
+        self.push_ptr_array(items.iter().map(|item| item.as_ptr() as usize).collect());
+        self.mark_origin(start)
+    }
+
+    /// 在 ptrs 末尾补上空指针, 压入指向它的指针
+    fn push_ptr_array(&mut self, mut ptrs: Vec<usize>) -> &mut Self {
+        ptrs.push(0);
+        let bytes = ptrs.iter().flat_map(|ptr| ptr.to_ne_bytes()).collect();
+        self.push_owned(bytes)
+    }
+}
@@ -0,0 +1,235 @@
+//! 在命令行中按 C 函数原型调用动态库中的函数, 用于调试
+//!
+//! ```text
+//! funcall [--lib <path>] [--conv <convention>] [--errno] <prototype> [args...]
+//! funcall --lib libc.so.6 --conv cdecl 'int strlen(const char*)' hello
+//! ```
+//!
+//! 参数按原型中声明的类型转换: 整数可以是十进制或 0x 开头的十六进制, `char *` 参数直接传递字符串,
+//! 其他指针参数传递整数地址或 `NULL`; 可变参数部分按字面量推断为 int, double 或字符串.
+//! 未指定 `--lib` 时在进程已加载的 C 运行库中查找. 调用后输出按返回值类型格式化的返回值,
+//! 指定 `--errno` 时再输出一行调用刚结束时的 errno
+
+use std::convert::TryFrom;
+use std::env;
+use std::ffi::CStr;
+use std::os::raw::c_char;
+use std::process;
+
+use funcall::{libc_func, Arg, CType, Convention, Func, Library, Signature};
+
+const USAGE: &str =
+    "usage: funcall [--lib <path>] [--conv <convention>] [--errno] <prototype> [args...]";
+
+/// 命令行选项
+struct Options {
+    lib: Option<String>,
+    conv: Convention,
+    errno: bool,
+    proto: String,
+    args: Vec<String>,
+}
+
+fn parse_options(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let (mut lib, mut conv, mut errno) = (None, Convention::Cdecl, false);
+    let proto = loop {
+        match args.next().as_deref() {
+            Some("--lib") => lib = Some(args.next().ok_or("--lib requires a path")?),
+            Some("--conv") => conv = convention(&args.next().ok_or("--conv requires a name")?)?,
+            Some("--errno") => errno = true,
+            Some(flag) if flag.starts_with("--") => {
+                return Err(format!("unknown option `{}`", flag))
+            }
+            Some(proto) => break proto.to_owned(),
+            None => return Err("missing prototype".to_owned()),
+        }
+    };
+    Ok(Options {
+        lib,
+        conv,
+        errno,
+        proto,
+        args: args.collect(),
+    })
+}
+
+fn convention(name: &str) -> Result<Convention, String> {
+    Ok(match name {
+        "cdecl" => Convention::Cdecl,
+        "stdcall" => Convention::Stdcall,
+        "system" => Convention::System,
+        "thiscall" => Convention::Thiscall,
+        "fastcall" => Convention::Fastcall,
+        "aapcs-softfloat" => Convention::AapcsSoftFloat,
+        "aapcs-vfp" => Convention::AapcsVfp,
+        _ => return Err(format!("unknown convention `{}`", name)),
+    })
+}
+
+/// 去掉 const 并展开类型别名
+fn base(ty: &CType) -> CType {
+    match ty {
+        CType::Const(ty) => base(ty),
+        ty => ty.resolve_alias(),
+    }
+}
+
+fn is_char(ty: &CType) -> bool {
+    matches!(base(ty), CType::Char | CType::SChar | CType::UChar)
+}
+
+fn is_unsigned(ty: &CType) -> bool {
+    matches!(
+        base(ty),
+        CType::Bool | CType::UChar | CType::UShort | CType::UInt | CType::ULong | CType::ULongLong
+    )
+}
+
+fn parse_int(text: &str) -> Option<i128> {
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, text),
+    };
+    let value = match digits
+        .strip_prefix("0x")
+        .or_else(|| digits.strip_prefix("0X"))
+    {
+        Some(hex) => i128::from_str_radix(hex, 16).ok()?,
+        None => digits.parse().ok()?,
+    };
+    Some(if negative { -value } else { value })
+}
+
+fn int_arg(value: i128) -> Arg {
+    match i64::try_from(value) {
+        Ok(value) => Arg::I64(value),
+        Err(_) => Arg::U64(value as u64),
+    }
+}
+
+/// 将命令行中的第 index 个参数转换为 ty 类型, ty 为 `None` 时处于可变参数部分
+fn to_arg(ty: Option<&CType>, index: usize, text: &str) -> Result<Arg, String> {
+    let invalid = |what: &str| format!("argument {}: `{}` is not {}", index, text, what);
+    let ty = match ty {
+        Some(ty) => base(ty),
+        None => {
+            return Ok(match parse_int(text) {
+                Some(value) => match i32::try_from(value) {
+                    Ok(value) => Arg::I32(value),
+                    Err(_) => int_arg(value),
+                },
+                None => match text.parse::<f64>() {
+                    Ok(value) => Arg::F64(value),
+                    Err(_) => Arg::Str(text.to_owned()),
+                },
+            })
+        }
+    };
+    Ok(match ty {
+        CType::Ptr(_) if text == "NULL" => Arg::Ptr(0),
+        CType::Ptr(pointee) if is_char(&pointee) => Arg::Str(text.to_owned()),
+        CType::Ptr(_) => Arg::Ptr(parse_int(text).ok_or_else(|| invalid("an address"))? as usize),
+        CType::Float | CType::Double => Arg::F64(text.parse().map_err(|_| invalid("a number"))?),
+        CType::Bool => match text {
+            "true" => Arg::I32(1),
+            "false" => Arg::I32(0),
+            _ => int_arg(parse_int(text).ok_or_else(|| invalid("a boolean"))?),
+        },
+        CType::Void => return Err(format!("argument {}: parameter has type void", index)),
+        ty => match parse_int(text) {
+            Some(value) => int_arg(value),
+            // 单个字符传给 char 参数时取其编码
+            None if is_char(&ty) && text.len() == 1 => Arg::I64(i64::from(text.as_bytes()[0])),
+            None => return Err(invalid("an integer")),
+        },
+    })
+}
+
+/// 按返回值类型格式化, 没有返回值时为 `None`
+fn format_ret(ty: &CType, func: &Func) -> Option<String> {
+    let ret = func.ret();
+    Some(match base(ty) {
+        CType::Void => return None,
+        CType::Bool => (ret.as_u8() != 0).to_string(),
+        CType::Float => ret.as_f32().to_string(),
+        CType::Double => ret.as_f64().to_string(),
+        CType::Ptr(_) if ret.as_usize() == 0 => "NULL".to_owned(),
+        CType::Ptr(pointee) if is_char(&pointee) => {
+            let s = unsafe { CStr::from_ptr(ret.as_usize() as *const c_char) };
+            format!("{:#x} {:?}", ret.as_usize(), s.to_string_lossy())
+        }
+        CType::Ptr(_) => format!("{:#x}", ret.as_usize()),
+        ty if is_unsigned(&ty) => ret.as_u64().to_string(),
+        _ => ret.as_i64().to_string(),
+    })
+}
+
+fn run(opts: Options) -> Result<(), String> {
+    let sig = Signature::parse(&opts.proto).map_err(|e| e.to_string())?;
+    let mut func = match &opts.lib {
+        Some(lib) => Library::new(lib).and_then(|lib| lib.get(&sig.name)),
+        None => libc_func(&sig.name),
+    }
+    .map_err(|e| format!("{}: {}", sig.name, e))?;
+    func.set_signature(sig.clone());
+    for (index, text) in opts.args.iter().enumerate() {
+        let arg = to_arg(sig.params.get(index), index, text)?;
+        func.try_push_arg(arg).map_err(|e| e.to_string())?;
+    }
+    errno::clear();
+    unsafe { func.try_call(opts.conv) }.map_err(|e| e.to_string())?;
+    let errno = errno::get();
+    if let Some(ret) = format_ret(&sig.ret, &func) {
+        println!("{}", ret);
+    }
+    if opts.errno {
+        println!("errno: {}", errno);
+    }
+    Ok(())
+}
+
+fn main() {
+    let opts = match parse_options(env::args().skip(1)) {
+        Ok(opts) => opts,
+        Err(e) => {
+            eprintln!("error: {}\n{}", e, USAGE);
+            process::exit(2);
+        }
+    };
+    if let Err(e) = run(opts) {
+        eprintln!("error: {}", e);
+        process::exit(1);
+    }
+}
+
+mod errno {
+    /// 调用前清零, 避免输出之前残留的值
+    pub fn clear() {
+        #[cfg(target_os = "linux")]
+        unsafe {
+            extern "C" {
+                fn __errno_location() -> *mut std::os::raw::c_int;
+            }
+            *__errno_location() = 0;
+        }
+        #[cfg(target_vendor = "apple")]
+        unsafe {
+            extern "C" {
+                fn __error() -> *mut std::os::raw::c_int;
+            }
+            *__error() = 0;
+        }
+        #[cfg(windows)]
+        unsafe {
+            extern "system" {
+                fn SetLastError(code: u32);
+            }
+            SetLastError(0);
+        }
+    }
+
+    /// Windows 下为 `GetLastError` 的值
+    pub fn get() -> i32 {
+        std::io::Error::last_os_error().raw_os_error().unwrap_or(0)
+    }
+}
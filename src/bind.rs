@@ -0,0 +1,72 @@
+//! 部分应用: 预先绑定前面的参数
+
+use crate::{Arg, CallError, Convention, Func, RetValues};
+
+/// 由 `Func::bind` 得到的调用, 已压入的参数被固定为前缀
+///
+/// 每次调用时在前缀之后压入其余的参数, 调用结束后只丢弃这部分参数.
+/// 前缀中复制的字符串等缓冲区会一直保留到本值被 drop 为止
+///
+/// ```
+/// use funcall::{Arg, Func};
+/// extern "C" fn sub(a: i32, b: i32) -> i32 {
+///     a - b
+/// }
+///
+/// let mut func = Func::from_raw(sub as *const fn());
+/// func.push(10i32);
+/// let mut sub10 = func.bind();
+/// for i in 0..3 {
+///     let ret = unsafe { sub10.call(&[Arg::I32(i)]) };
+///     assert_eq!(ret.unwrap().as_i32(), 10 - i);
+/// }
+/// ```
+#[derive(Debug)]
+pub struct BoundCall {
+    func: Func,
+    conv: Convention,
+    /// 前缀在 func 中占用的状态, 见 `Func::checkpoint`
+    prefix: [usize; 4],
+}
+
+impl Func {
+    /// 将已压入的参数固定为前缀, 得到可以反复以不同的剩余参数调用的 `BoundCall`
+    ///
+    /// 默认使用 cdecl 调用约定, 可以通过 `BoundCall::with_convention` 修改
+    pub fn bind(self) -> BoundCall {
+        BoundCall {
+            prefix: self.checkpoint(),
+            conv: Convention::Cdecl,
+            func: self,
+        }
+    }
+}
+
+impl BoundCall {
+    /// 调用时使用的调用约定
+    pub fn with_convention(mut self, conv: Convention) -> Self {
+        self.conv = conv;
+        self
+    }
+
+    /// 前缀之后压入 rest 并调用函数, 返回各返回值寄存器的值
+    ///
+    /// 参数个数的检查同 `Func::try_call`, 计入前缀的参数
+    ///
+    /// # Safety
+    ///
+    /// 同 `Func::try_call`
+    pub unsafe fn call(&mut self, rest: &[Arg]) -> Result<RetValues, CallError> {
+        for arg in rest {
+            self.func.push_arg(arg.clone());
+        }
+        let ret = self.func.try_call(self.conv);
+        self.func.rollback(self.prefix);
+        ret.map(|()| self.func.ret())
+    }
+
+    /// 被绑定的函数
+    pub fn func(&self) -> &Func {
+        &self.func
+    }
+}
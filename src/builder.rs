@@ -0,0 +1,76 @@
+//! 链式构造参数并调用函数
+
+use core::ffi::CStr;
+use core::marker::PhantomData;
+
+use crate::{CallError, Convention, Func, IntoArg, RetValues};
+
+/// 以链式调用的方式压入参数并调用函数, 被借用的 `Func` 本身不会被修改
+///
+/// # 示例
+///
+/// ```
+/// use funcall::Func;
+/// extern "C" fn add(a: i32, b: i32) -> i32 {
+///     a + b
+/// }
+///
+/// let func = Func::from_raw(add as *const fn());
+/// let ret = unsafe { func.builder().arg(1i32).arg(2i32).call_cdecl() }.unwrap();
+/// assert_eq!(ret.as_i32(), 3);
+///
+/// // func 可以被再次使用
+/// let ret = unsafe { func.builder().arg(3i32).arg(4i32).call_cdecl() }.unwrap();
+/// assert_eq!(ret.as_i32(), 7);
+/// ```
+#[derive(Debug)]
+pub struct CallBuilder<'a> {
+    /// 仅复制了目标地址, 用于储存本次调用的参数
+    call: Func,
+    _func: PhantomData<&'a Func>,
+}
+
+impl<'a> CallBuilder<'a> {
+    pub fn new(func: &'a Func) -> Self {
+        Self {
+            call: Func::from_raw(func.func),
+            _func: PhantomData,
+        }
+    }
+
+    /// 压入参数
+    pub fn arg<T: IntoArg>(mut self, arg: T) -> Self {
+        self.call.push(arg);
+        self
+    }
+
+    /// 压入 C 字符串的指针, 字符串需要在调用完成前保持有效
+    pub fn arg_cstr(self, s: &'a CStr) -> Self {
+        self.arg(s.as_ptr())
+    }
+
+    /// 以 cdecl 调用约定调用函数, 与 `Func::invoke` 相同在调用前进行检查, 不支持时返回错误
+    ///
+    /// # Safety
+    ///
+    /// 压入的参数必须与函数的实际签名一致
+    pub unsafe fn call_cdecl(mut self) -> Result<RetValues, CallError> {
+        self.call.invoke(Convention::Cdecl)
+    }
+
+    /// 以 stdcall 调用约定调用函数, 同 `call_cdecl`
+    ///
+    /// # Safety
+    ///
+    /// 压入的参数必须与函数的实际签名一致
+    pub unsafe fn call_stdcall(mut self) -> Result<RetValues, CallError> {
+        self.call.invoke(Convention::Stdcall)
+    }
+}
+
+impl Func {
+    /// 以借用的方式创建一个 `CallBuilder`
+    pub fn builder(&self) -> CallBuilder<'_> {
+        CallBuilder::new(self)
+    }
+}
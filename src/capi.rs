@@ -0,0 +1,251 @@
+//! 供其他语言使用的 C 接口, 声明见仓库中的 `include/funcall.h`
+//!
+//! `Func` 以不透明指针的形式交给调用者, 由 `funcall_free` 释放. 出错的函数返回非零的错误码 (或空指针),
+//! 错误码与错误信息同时记录在当前线程中, 可以通过 `funcall_last_error` 取得.
+//! 需要将本 crate 编译为动态库, 例如在 `crate-type = ["cdylib"]` 的包装 crate 中 `pub use funcall::capi::*;`
+
+use std::cell::RefCell;
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_int, c_void};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::slice;
+
+use crate::{Arg, Convention, Func};
+
+/// 成功
+pub const FUNCALL_OK: c_int = 0;
+/// 句柄或指针参数为空, 或字符串不是合法的 UTF-8
+pub const FUNCALL_ERR_INVALID: c_int = 1;
+/// 无法加载库或找不到符号
+pub const FUNCALL_ERR_LOAD: c_int = 2;
+/// 调用前的检查未通过, 参见 `CallError`
+pub const FUNCALL_ERR_CALL: c_int = 3;
+/// 调用过程中发生了 panic
+pub const FUNCALL_ERR_PANIC: c_int = 4;
+
+/// `funcall_call` 的调用约定, 分别对应 `Convention::Cdecl`, `Convention::Stdcall`, `Convention::System`, `Convention::Thiscall`,
+/// `Convention::Fastcall`, `Convention::AapcsSoftFloat` 与 `Convention::AapcsVfp`
+pub const FUNCALL_CDECL: c_int = 0;
+pub const FUNCALL_STDCALL: c_int = 1;
+pub const FUNCALL_SYSTEM: c_int = 2;
+pub const FUNCALL_THISCALL: c_int = 3;
+pub const FUNCALL_FASTCALL: c_int = 4;
+pub const FUNCALL_AAPCS_SOFTFLOAT: c_int = 5;
+pub const FUNCALL_AAPCS_VFP: c_int = 6;
+
+thread_local! {
+    static LAST_ERROR: RefCell<(c_int, CString)> = RefCell::new((FUNCALL_OK, CString::default()));
+}
+
+/// 记录错误并返回错误码
+fn fail(code: c_int, message: impl ToString) -> c_int {
+    // 错误信息中的 `\0` 会截断 C 字符串, 直接去掉
+    let message = message.to_string().replace('\0', "");
+    LAST_ERROR.with(|last| *last.borrow_mut() = (code, CString::new(message).unwrap()));
+    code
+}
+
+unsafe fn str_arg<'a>(s: *const c_char, what: &str) -> Result<&'a str, c_int> {
+    if s.is_null() {
+        return Err(fail(FUNCALL_ERR_INVALID, format!("{} is null", what)));
+    }
+    CStr::from_ptr(s)
+        .to_str()
+        .map_err(|e| fail(FUNCALL_ERR_INVALID, format!("{} is not UTF-8: {}", what, e)))
+}
+
+unsafe fn handle<'a>(func: *mut Func) -> Result<&'a mut Func, c_int> {
+    func.as_mut()
+        .ok_or_else(|| fail(FUNCALL_ERR_INVALID, "handle is null"))
+}
+
+fn status(result: Result<(), c_int>) -> c_int {
+    result.err().unwrap_or(FUNCALL_OK)
+}
+
+/// 加载动态库 lib 并查找其中的符号 sym, 失败时返回空指针
+///
+/// # Safety
+///
+/// lib 与 sym 必须为空指针或以 `\0` 结尾的字符串
+#[no_mangle]
+pub unsafe extern "C" fn funcall_new(lib: *const c_char, sym: *const c_char) -> *mut Func {
+    let load = || -> Result<Func, c_int> {
+        let lib = str_arg(lib, "lib")?;
+        let sym = str_arg(sym, "sym")?;
+        crate::Library::new(lib)
+            .and_then(|lib| lib.get(sym))
+            .map_err(|e| fail(FUNCALL_ERR_LOAD, e))
+    };
+    match load() {
+        Ok(func) => Box::into_raw(Box::new(func)),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+/// 以已知的函数地址创建句柄
+#[no_mangle]
+pub extern "C" fn funcall_from_raw(ptr: *const c_void) -> *mut Func {
+    Box::into_raw(Box::new(Func::from_raw(ptr as *const fn())))
+}
+
+/// 压入 32 位整数
+///
+/// # Safety
+///
+/// func 必须为空指针或未释放的句柄
+#[no_mangle]
+pub unsafe extern "C" fn funcall_push_i32(func: *mut Func, value: i32) -> c_int {
+    status(handle(func).map(|func| {
+        func.push(value);
+    }))
+}
+
+/// 压入 64 位整数, 32 位平台上占用两个参数位置
+///
+/// # Safety
+///
+/// 同 `funcall_push_i32`
+#[no_mangle]
+pub unsafe extern "C" fn funcall_push_i64(func: *mut Func, value: i64) -> c_int {
+    status(handle(func).map(|func| {
+        func.push(value);
+    }))
+}
+
+/// 压入 double
+///
+/// # Safety
+///
+/// 同 `funcall_push_i32`
+#[no_mangle]
+pub unsafe extern "C" fn funcall_push_f64(func: *mut Func, value: f64) -> c_int {
+    status(handle(func).map(|func| {
+        func.push(value);
+    }))
+}
+
+/// 压入指针
+///
+/// # Safety
+///
+/// 同 `funcall_push_i32`
+#[no_mangle]
+pub unsafe extern "C" fn funcall_push_ptr(func: *mut Func, value: *const c_void) -> c_int {
+    status(handle(func).map(|func| {
+        func.push(value);
+    }))
+}
+
+/// 复制 data 开始的 len 个字节, 压入指向副本的指针, 副本在句柄释放前一直有效
+///
+/// # Safety
+///
+/// 同 `funcall_push_i32`, len 不为 0 时 data 必须指向至少 len 个可读的字节
+#[no_mangle]
+pub unsafe extern "C" fn funcall_push_bytes(func: *mut Func, data: *const u8, len: usize) -> c_int {
+    let push = || {
+        let func = handle(func)?;
+        let bytes = match len {
+            0 => Vec::new(),
+            _ if data.is_null() => return Err(fail(FUNCALL_ERR_INVALID, "data is null")),
+            _ => slice::from_raw_parts(data, len).to_vec(),
+        };
+        func.push_arg(Arg::Bytes(bytes));
+        Ok(())
+    };
+    status(push())
+}
+
+/// 以 convention 指定的调用约定调用函数, 返回值通过 `funcall_ret_i64` 等取得
+///
+/// # Safety
+///
+/// 同 `funcall_push_i32`, 压入的参数必须与函数的实际签名一致
+#[no_mangle]
+pub unsafe extern "C" fn funcall_call(func: *mut Func, convention: c_int) -> c_int {
+    let call = || {
+        let func = handle(func)?;
+        let conv = match convention {
+            FUNCALL_CDECL => Convention::Cdecl,
+            FUNCALL_STDCALL => Convention::Stdcall,
+            FUNCALL_SYSTEM => Convention::System,
+            FUNCALL_THISCALL => Convention::Thiscall,
+            FUNCALL_FASTCALL => Convention::Fastcall,
+            FUNCALL_AAPCS_SOFTFLOAT => Convention::AapcsSoftFloat,
+            FUNCALL_AAPCS_VFP => Convention::AapcsVfp,
+            _ => {
+                let message = format!("unknown convention {}", convention);
+                return Err(fail(FUNCALL_ERR_INVALID, message));
+            }
+        };
+        match panic::catch_unwind(AssertUnwindSafe(|| func.try_call(conv))) {
+            Ok(result) => result.map_err(|e| fail(FUNCALL_ERR_CALL, e)),
+            Err(_) => Err(fail(FUNCALL_ERR_PANIC, "the call panicked")),
+        }
+    };
+    status(call())
+}
+
+/// 上一次调用的返回值, 作为 64 位整数. func 为空指针时记录 `FUNCALL_ERR_INVALID` 并返回 0
+///
+/// # Safety
+///
+/// func 必须为空指针或未释放的句柄
+#[no_mangle]
+pub unsafe extern "C" fn funcall_ret_i64(func: *const Func) -> i64 {
+    match func.as_ref() {
+        Some(func) => func.ret().as_i64(),
+        None => {
+            fail(FUNCALL_ERR_INVALID, "handle is null");
+            0
+        }
+    }
+}
+
+/// 上一次调用的返回值, 作为 double. func 为空指针时记录 `FUNCALL_ERR_INVALID` 并返回 0
+///
+/// # Safety
+///
+/// 同 `funcall_ret_i64`
+#[no_mangle]
+pub unsafe extern "C" fn funcall_ret_f64(func: *const Func) -> f64 {
+    match func.as_ref() {
+        Some(func) => func.ret().as_f64(),
+        None => {
+            fail(FUNCALL_ERR_INVALID, "handle is null");
+            0.0
+        }
+    }
+}
+
+/// 当前线程中最后一次出错的错误码, 没有出错时为 `FUNCALL_OK`
+///
+/// message 不为空时写入错误信息, 在当前线程再次出错前一直有效
+///
+/// # Safety
+///
+/// message 必须为空指针或可写的指针
+#[no_mangle]
+pub unsafe extern "C" fn funcall_last_error(message: *mut *const c_char) -> c_int {
+    LAST_ERROR.with(|last| {
+        let last = last.borrow();
+        if !message.is_null() {
+            *message = last.1.as_ptr();
+        }
+        last.0
+    })
+}
+
+/// 释放句柄, 传入空指针时什么也不做
+///
+/// # Safety
+///
+/// func 必须为空指针或未释放的句柄
+#[no_mangle]
+pub unsafe extern "C" fn funcall_free(func: *mut Func) {
+    if !func.is_null() {
+        drop(Box::from_raw(func));
+    }
+}
@@ -0,0 +1,310 @@
+//! 预先确定参数布局的调用
+
+#[cfg(all(feature = "std", target_arch = "x86_64", target_os = "linux"))]
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::cell::RefCell;
+use core::ffi::{c_char, c_long};
+use core::mem;
+
+#[cfg(all(feature = "std", target_arch = "x86_64", target_os = "linux"))]
+use crate::jit::Trampoline;
+use crate::signature::{int_value, promote, push_as};
+use crate::{
+    Arg, ArgKind, ArgLocation, ArgSlot, Backend, CType, CallError, Convention, Func, RetValues,
+    Signature,
+};
+
+/// 由 `Signature::compile` 得到的调用, 每个参数的储存位置都已预先确定
+///
+/// 调用时只需将参数的值复制到可复用的帧中, 不再逐个判断参数该放在哪里.
+/// 帧只在同一个线程中复用, 因此 `CompiledCall` 不是 `Sync` 的; 需要在多个线程中使用时各自克隆一份
+///
+/// ```
+/// use funcall::{Arg, Convention, Signature};
+/// extern "C" fn add(a: i32, b: f64) -> f64 {
+///     a as f64 + b
+/// }
+///
+/// let call = Signature::parse("double add(int, double)")
+///     .unwrap()
+///     .compile(Convention::Cdecl);
+/// for i in 0..3 {
+///     let ret = unsafe { call.invoke(add as *const fn(), &[Arg::I32(i), Arg::F64(0.5)]) };
+///     assert_eq!(ret.unwrap().as_f64(), i as f64 + 0.5);
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct CompiledCall {
+    conv: Convention,
+    backend: Backend,
+    /// 固定参数的布局对应的跳板, 只在使用 `Backend::Jit` 时生成
+    #[cfg(all(feature = "std", target_arch = "x86_64", target_os = "linux"))]
+    trampoline: Option<Arc<Trampoline>>,
+    params: Vec<Param>,
+    variadic: bool,
+    ret_kind: Option<ArgKind>,
+    /// 固定参数在帧中占用的状态, 见 `Func::checkpoint`
+    fixed: [usize; 4],
+    frame: RefCell<Func>,
+}
+
+/// 一个固定参数的类型与储存位置
+#[derive(Debug, Clone)]
+struct Param {
+    ty: CType,
+    conv: Conv,
+    slot: ArgSlot,
+}
+
+/// 参数值的转换方式
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Conv {
+    Int {
+        size: usize,
+        signed: bool,
+    },
+    Bool,
+    Float,
+    Double,
+    Ptr,
+    /// void 等无法传递的类型
+    Invalid,
+}
+
+impl Signature {
+    /// 预先确定每个固定参数的储存位置, 得到可以反复调用的 `CompiledCall`
+    pub fn compile(&self, conv: Convention) -> CompiledCall {
+        self.compile_with(conv, conv.backend().unwrap_or(Backend::Asm))
+    }
+
+    /// 同 `compile`, 但指定调用时使用的后端
+    ///
+    /// 使用 `Backend::Jit` 时会为固定参数的布局生成调用跳板, 布局相同的签名共用同一个跳板.
+    /// 传入可变参数时布局会发生变化, 此时改用与之对应的跳板
+    pub fn compile_with(&self, conv: Convention, backend: Backend) -> CompiledCall {
+        // 以零值压入一遍参数, 布局与逐个压入时完全一致
+        let mut frame = Func::from_raw(core::ptr::null());
+        self.prepare(&mut frame);
+        let params = self
+            .params
+            .iter()
+            .enumerate()
+            .map(|(index, ty)| {
+                let conv = classify(ty);
+                let zero = match conv {
+                    Conv::Float => Arg::F32(0.0),
+                    Conv::Double => Arg::F64(0.0),
+                    Conv::Ptr => Arg::Ptr(0),
+                    _ => Arg::I8(0),
+                };
+                // 无法传递的参数在调用时报错, 这里先占一个字
+                if push_as(&mut frame, ty, index, zero).is_err() {
+                    frame.push(0usize);
+                }
+                Param {
+                    ty: ty.clone(),
+                    conv,
+                    slot: frame.slots[index],
+                }
+            })
+            .collect();
+        #[cfg(all(feature = "std", target_arch = "x86_64", target_os = "linux"))]
+        let trampoline = if backend == Backend::Jit && backend.supports(conv) {
+            Trampoline::for_func(&frame).ok()
+        } else {
+            None
+        };
+        CompiledCall {
+            conv,
+            backend,
+            #[cfg(all(feature = "std", target_arch = "x86_64", target_os = "linux"))]
+            trampoline,
+            params,
+            variadic: self.variadic,
+            ret_kind: frame.ret_kind,
+            fixed: frame.checkpoint(),
+            frame: RefCell::new(frame),
+        }
+    }
+}
+
+impl CompiledCall {
+    /// 以 args 为参数调用 target, 返回各返回值寄存器的值
+    ///
+    /// 参数的转换规则与 `Signature::push_checked` 相同, 可变参数部分会进行默认参数提升
+    ///
+    /// # Safety
+    ///
+    /// target 的签名与调用约定必须与编译时使用的一致
+    pub unsafe fn invoke(&self, target: *const fn(), args: &[Arg]) -> Result<RetValues, CallError> {
+        if target.is_null() {
+            return Err(CallError::NullTarget);
+        }
+        if !self.backend.supports(self.conv) {
+            return Err(CallError::unsupported(self.conv));
+        }
+        let expected = self.params.len();
+        if args.len() < expected || (!self.variadic && args.len() != expected) {
+            return Err(CallError::ArgCountMismatch {
+                expected,
+                variadic: self.variadic,
+                got: args.len(),
+            });
+        }
+
+        // 被调用的函数可能再次调用自己, 此时帧已被占用, 只能临时创建一个
+        let mut fresh;
+        let mut borrowed;
+        let frame: &mut Func = match self.frame.try_borrow_mut() {
+            Ok(frame) => {
+                borrowed = frame;
+                &mut borrowed
+            }
+            Err(_) => {
+                fresh = self.frame_template();
+                &mut fresh
+            }
+        };
+        frame.func = target;
+        // 同时丢弃上一次调用的可变参数与复制的字符串
+        frame.rollback(self.fixed);
+
+        for (index, (param, arg)) in self.params.iter().zip(args).enumerate() {
+            self.write(frame, param, index, arg)?;
+        }
+        for arg in &args[expected..] {
+            promote(frame, arg.clone());
+        }
+        #[cfg(all(feature = "std", target_arch = "x86_64", target_os = "linux"))]
+        {
+            if let Some(trampoline) = &self.trampoline {
+                if args.len() == expected {
+                    trampoline.call(frame, self.conv);
+                    return Ok(frame.ret);
+                }
+            }
+        }
+        frame.call_backend(self.conv, self.backend);
+        Ok(frame.ret)
+    }
+
+    /// 使用 `Backend::Jit` 时为固定参数生成的跳板
+    #[cfg(all(feature = "std", target_arch = "x86_64", target_os = "linux"))]
+    pub fn trampoline(&self) -> Option<&Arc<Trampoline>> {
+        self.trampoline.as_ref()
+    }
+
+    /// 每个固定参数所处的位置
+    pub fn locations(&self) -> Vec<ArgLocation> {
+        self.params
+            .iter()
+            .map(|param| param.slot.location(0))
+            .collect()
+    }
+
+    fn frame_template(&self) -> Func {
+        let mut frame = Func::from_raw(core::ptr::null());
+        frame.args = vec![0; self.fixed[0]].into();
+        frame.fargs = vec![0.0; self.fixed[1]].into();
+        frame.slots = self.params.iter().map(|param| param.slot).collect();
+        frame.set_arity(self.params.len(), self.variadic);
+        frame.ret_kind = self.ret_kind;
+        frame
+    }
+
+    /// 将参数的值写入预先确定的位置
+    fn write(
+        &self,
+        frame: &mut Func,
+        param: &Param,
+        index: usize,
+        arg: &Arg,
+    ) -> Result<(), CallError> {
+        let mismatch = || CallError::ArgTypeMismatch {
+            index,
+            expected: param.ty.clone(),
+            got: format!("{:?}", arg),
+        };
+        let bits = match (param.conv, arg) {
+            (Conv::Int { size, signed }, _) => {
+                let v = int_value(arg).ok_or_else(mismatch)?;
+                let bits = size * 8;
+                let (min, max) = if signed {
+                    (-(1i128 << (bits - 1)), (1i128 << (bits - 1)) - 1)
+                } else {
+                    (0, (1i128 << bits) - 1)
+                };
+                if v < min || v > max {
+                    return Err(mismatch());
+                }
+                v as u64
+            }
+            (Conv::Bool, _) => match int_value(arg) {
+                Some(v @ 0..=1) => v as u64,
+                _ => return Err(mismatch()),
+            },
+            (Conv::Float, Arg::F32(v)) => u64::from(v.to_bits()),
+            (Conv::Float, Arg::F64(v)) => u64::from((*v as f32).to_bits()),
+            (Conv::Float, _) => u64::from((int_value(arg).ok_or_else(mismatch)? as f32).to_bits()),
+            (Conv::Double, Arg::F32(v)) => f64::from(*v).to_bits(),
+            (Conv::Double, Arg::F64(v)) => v.to_bits(),
+            (Conv::Double, _) => (int_value(arg).ok_or_else(mismatch)? as f64).to_bits(),
+            (Conv::Ptr, Arg::Ptr(v)) => *v as u64,
+            // 参数在调用期间一直被借用, 可以直接传递其地址
+            (Conv::Ptr, Arg::Bytes(v)) => v.as_ptr() as u64,
+            (Conv::Ptr, Arg::Str(v)) => {
+                let mut bytes = Vec::with_capacity(v.len() + 1);
+                bytes.extend_from_slice(v.as_bytes());
+                bytes.push(0);
+                let bytes: alloc::sync::Arc<[u8]> = bytes.into();
+                let ptr = bytes.as_ptr() as u64;
+                frame.owned.push(bytes);
+                ptr
+            }
+            (Conv::Ptr, _) | (Conv::Invalid, _) => return Err(mismatch()),
+        };
+
+        let slot = param.slot;
+        if slot.float {
+            frame.fargs[slot.index] = f64::from_bits(bits);
+        } else {
+            // 32 位下 64 位的参数占两个字
+            for (i, word) in frame.args[slot.index..slot.index + slot.len]
+                .iter_mut()
+                .enumerate()
+            {
+                *word = (bits >> (i * mem::size_of::<usize>() * 8)) as usize;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 根据参数类型确定转换方式
+fn classify(ty: &CType) -> Conv {
+    let ty = match ty {
+        CType::Const(ty) => ty.resolve_alias(),
+        ty => ty.resolve_alias(),
+    };
+    let int = |size, signed| Conv::Int { size, signed };
+    match ty {
+        CType::Bool => Conv::Bool,
+        // char 的符号随平台而定, 如 x86 下有符号, ARM 与 aarch64 Linux 下无符号
+        CType::Char => int(1, c_char::MIN != 0),
+        CType::SChar => int(1, true),
+        CType::UChar => int(1, false),
+        CType::Short => int(2, true),
+        CType::UShort => int(2, false),
+        CType::Int => int(4, true),
+        CType::UInt => int(4, false),
+        CType::Long => int(mem::size_of::<c_long>(), true),
+        CType::ULong => int(mem::size_of::<c_long>(), false),
+        CType::LongLong => int(8, true),
+        CType::ULongLong => int(8, false),
+        CType::Float => Conv::Float,
+        CType::Double => Conv::Double,
+        CType::Ptr(_) => Conv::Ptr,
+        _ => Conv::Invalid,
+    }
+}
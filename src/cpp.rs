@@ -0,0 +1,153 @@
+//! C++ 符号名相关的辅助函数
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::{CType, Error, Result};
+
+/// 从 MSVC 修饰名中取出函数的限定名, 如 `?add@math@@YAHHH@Z` -> `math::add`
+///
+/// 只解析名字部分, 不关心参数类型; 运算符, 构造函数, 模板等特殊名字返回 `None`
+pub fn msvc_demangle_name(symbol: &str) -> Option<String> {
+    let symbol = symbol.strip_prefix('?')?;
+    // 形如 `??0Foo@@` 的特殊名字与形如 `?$foo@H@@` 的模板
+    if symbol.starts_with(['?', '$']) {
+        return None;
+    }
+    let end = symbol.find("@@")?;
+    let mut parts = symbol[..end].split('@').collect::<Vec<_>>();
+    // 含有回引用 (数字) 或嵌套修饰的名字不在支持范围内
+    if parts
+        .iter()
+        .any(|p| p.is_empty() || !p.bytes().all(|c| c.is_ascii_alphanumeric() || c == b'_'))
+        || parts.iter().any(|p| p.as_bytes()[0].is_ascii_digit())
+    {
+        return None;
+    }
+    // MSVC 中的作用域是由内向外排列的
+    parts.reverse();
+    Some(parts.join("::"))
+}
+
+/// 生成 Itanium C++ ABI (GCC / Clang) 下自由函数的修饰名, 如 `foo(int, double)` -> `_Z3fooid`
+///
+/// 仅支持基本类型, 指针与 const, 函数名可以带有简单的命名空间 (如 `ns::foo`)
+pub fn itanium_mangle(name: &str, params: &[CType]) -> Result<String> {
+    let parts = name.split("::").collect::<Vec<_>>();
+    if !parts.iter().all(|p| is_identifier(p)) {
+        return Err(unsupported(format!("unsupported function name `{}`", name)));
+    }
+
+    let mut out = String::from("_Z");
+    let mut subs = Substitutions::default();
+    if let [name] = parts[..] {
+        out += &format!("{}{}", name.len(), name);
+    } else {
+        out.push('N');
+        for part in &parts {
+            out += &format!("{}{}", part.len(), part);
+        }
+        out.push('E');
+        // 命名空间前缀也会占用替换表的位置
+        subs.prefixes = parts.len() - 1;
+    }
+
+    if params.is_empty() {
+        out.push('v');
+    }
+    for param in params {
+        // 参数的顶层 const 不参与修饰
+        let param = match param {
+            CType::Const(inner) => inner,
+            param => param,
+        };
+        if *param == CType::Void {
+            return Err(unsupported("`void` is not a valid parameter type".into()));
+        }
+        subs.mangle(&canonical(param), &mut out);
+    }
+    Ok(out)
+}
+
+/// 修饰名中重复出现的复合类型会被替换为 `S_`, `S0_`, `S1_` ...
+#[derive(Default)]
+struct Substitutions {
+    prefixes: usize,
+    types: Vec<CType>,
+}
+
+impl Substitutions {
+    fn mangle(&mut self, ty: &CType, out: &mut String) {
+        let (code, inner) = match ty {
+            CType::Ptr(inner) => ('P', inner),
+            CType::Const(inner) => ('K', inner),
+            ty => return out.push(builtin_code(ty)),
+        };
+        if let Some(i) = self.types.iter().position(|t| t == ty) {
+            match self.prefixes + i {
+                0 => out.push_str("S_"),
+                i => out.push_str(&format!("S{}_", base36(i - 1))),
+            }
+            return;
+        }
+        out.push(code);
+        self.mangle(inner, out);
+        self.types.push(ty.clone());
+    }
+}
+
+/// 展开所有类型别名, 别名与原类型在 C++ 中是同一类型
+fn canonical(ty: &CType) -> CType {
+    match ty {
+        CType::Ptr(inner) => CType::ptr(canonical(inner)),
+        CType::Const(inner) => CType::constant(canonical(inner)),
+        ty => ty.resolve_alias(),
+    }
+}
+
+fn builtin_code(ty: &CType) -> char {
+    match ty {
+        CType::Void => 'v',
+        CType::Bool => 'b',
+        CType::Char => 'c',
+        CType::SChar => 'a',
+        CType::UChar => 'h',
+        CType::Short => 's',
+        CType::UShort => 't',
+        CType::Int => 'i',
+        CType::UInt => 'j',
+        CType::Long => 'l',
+        CType::ULong => 'm',
+        CType::LongLong => 'x',
+        CType::ULongLong => 'y',
+        CType::Float => 'f',
+        CType::Double => 'd',
+        ty => unreachable!("{:?} should have been resolved", ty),
+    }
+}
+
+fn base36(mut n: usize) -> String {
+    let mut digits = Vec::new();
+    loop {
+        digits.push(
+            core::char::from_digit((n % 36) as u32, 36)
+                .unwrap()
+                .to_ascii_uppercase(),
+        );
+        n /= 36;
+        if n == 0 {
+            break;
+        }
+    }
+    digits.iter().rev().collect()
+}
+
+fn is_identifier(s: &str) -> bool {
+    !s.is_empty()
+        && !s.as_bytes()[0].is_ascii_digit()
+        && s.bytes().all(|c| c.is_ascii_alphanumeric() || c == b'_')
+}
+
+fn unsupported(msg: String) -> Error {
+    Error::InvalidInput(msg)
+}
@@ -0,0 +1,82 @@
+//! C 语言类型的描述
+
+use alloc::boxed::Box;
+
+/// C 语言中的类型
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Hash)]
+pub enum CType {
+    Void,
+    Bool,
+    Char,
+    SChar,
+    UChar,
+    Short,
+    UShort,
+    Int,
+    UInt,
+    Long,
+    ULong,
+    LongLong,
+    ULongLong,
+    Float,
+    Double,
+    Int8,
+    UInt8,
+    Int16,
+    UInt16,
+    Int32,
+    UInt32,
+    Int64,
+    UInt64,
+    SizeT,
+    SSizeT,
+    IntPtr,
+    UIntPtr,
+    /// 指向某类型的指针
+    Ptr(Box<CType>),
+    /// const 修饰的类型
+    Const(Box<CType>),
+}
+
+impl CType {
+    /// 指向 ty 的指针
+    pub fn ptr(ty: CType) -> Self {
+        CType::Ptr(Box::new(ty))
+    }
+
+    /// const 修饰的 ty
+    pub fn constant(ty: CType) -> Self {
+        CType::Const(Box::new(ty))
+    }
+
+    /// 将 size_t, int64_t 等类型别名展开为当前平台上对应的基本类型
+    pub fn resolve_alias(&self) -> CType {
+        // int64_t 在 LP64 的 Linux 下是 long, 在 Windows 与 macOS 下是 long long
+        let lp64 = cfg!(all(
+            target_pointer_width = "64",
+            not(windows),
+            not(target_vendor = "apple")
+        ));
+        // size_t 在 64 位 Windows 下是 unsigned long long, 其他平台下与指针等宽的 long
+        let llp64 = cfg!(all(target_pointer_width = "64", windows));
+        match self {
+            CType::Int8 => CType::SChar,
+            CType::UInt8 => CType::UChar,
+            CType::Int16 => CType::Short,
+            CType::UInt16 => CType::UShort,
+            CType::Int32 => CType::Int,
+            CType::UInt32 => CType::UInt,
+            CType::Int64 if lp64 => CType::Long,
+            CType::Int64 => CType::LongLong,
+            CType::UInt64 if lp64 => CType::ULong,
+            CType::UInt64 => CType::ULongLong,
+            CType::SizeT | CType::UIntPtr if llp64 => CType::ULongLong,
+            CType::SizeT | CType::UIntPtr if cfg!(target_pointer_width = "32") => CType::UInt,
+            CType::SizeT | CType::UIntPtr => CType::ULong,
+            CType::SSizeT | CType::IntPtr if llp64 => CType::LongLong,
+            CType::SSizeT | CType::IntPtr if cfg!(target_pointer_width = "32") => CType::Int,
+            CType::SSizeT | CType::IntPtr => CType::Long,
+            ty => ty.clone(),
+        }
+    }
+}
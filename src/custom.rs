@@ -0,0 +1,586 @@
+//! 以数据描述的调用约定, 参见 `Func::call_custom`
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::arch::asm;
+use core::mem::{self, offset_of};
+
+use crate::{fpstate, ArgOrder, CallError, Func};
+
+/// 可以用于传递参数与返回值的通用寄存器, rsp 与 rbp 除外
+#[cfg(target_arch = "x86_64")]
+const GPRS: [&str; 14] = [
+    "rax", "rbx", "rcx", "rdx", "rsi", "rdi", "r8", "r9", "r10", "r11", "r12", "r13", "r14", "r15",
+];
+#[cfg(target_arch = "x86")]
+const GPRS: [&str; 6] = ["eax", "ebx", "ecx", "edx", "esi", "edi"];
+
+/// xmm 寄存器的个数
+#[cfg(target_arch = "x86_64")]
+const XMMS: usize = 16;
+#[cfg(target_arch = "x86")]
+const XMMS: usize = 8;
+
+/// 一个参数的去向
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ArgDest {
+    /// 按名字指定的通用寄存器或 xmm 寄存器, 如 `ecx`, `r10`, `xmm2`
+    Reg(String),
+    /// 按 `Func::set_arg_order` 的顺序放在栈上
+    Stack,
+}
+
+/// 由谁清理栈上的参数
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Cleanup {
+    /// 调用者, 如 cdecl
+    #[default]
+    Caller,
+    /// 被调用者, 如 stdcall
+    Callee,
+}
+
+/// 调用约定的描述, 用于编译器特有的约定 (如 IDA 中的 `__usercall`), 参见 `Func::call_custom`
+///
+/// 默认所有参数都在栈上, 由调用者清理, 返回值在 rax / eax, rdx / edx 以及 xmm0 / st0 中
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ConvDesc {
+    /// 依次为每个参数的去向
+    pub args: Vec<ArgDest>,
+    /// 由谁清理栈上的参数
+    pub cleanup: Cleanup,
+    /// 在栈上参数之前保留的字节数, 如 64 位 Windows 下的 32 字节 shadow space
+    pub shadow_space: usize,
+    /// 保存返回值低位的通用寄存器
+    pub ret_low: String,
+    /// 保存返回值高位的通用寄存器
+    pub ret_high: String,
+    /// 保存浮点返回值的寄存器, xmm 寄存器或 32 位下的 `st0`
+    pub ret_float: String,
+}
+
+impl Default for ConvDesc {
+    fn default() -> Self {
+        let float = if cfg!(target_arch = "x86") {
+            "st0"
+        } else {
+            "xmm0"
+        };
+        Self {
+            args: Vec::new(),
+            cleanup: Cleanup::Caller,
+            shadow_space: 0,
+            ret_low: GPRS[0].to_string(),
+            ret_high: GPRS[3].to_string(),
+            ret_float: float.to_string(),
+        }
+    }
+}
+
+impl ConvDesc {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 下一个参数通过寄存器 name 传递
+    pub fn reg(mut self, name: &str) -> Self {
+        self.args.push(ArgDest::Reg(name.to_string()));
+        self
+    }
+
+    /// 下一个参数通过栈传递
+    pub fn stack(mut self) -> Self {
+        self.args.push(ArgDest::Stack);
+        self
+    }
+
+    /// 设置由谁清理栈上的参数
+    pub fn cleanup(mut self, cleanup: Cleanup) -> Self {
+        self.cleanup = cleanup;
+        self
+    }
+
+    /// 设置在栈上参数之前保留的字节数
+    pub fn shadow_space(mut self, bytes: usize) -> Self {
+        self.shadow_space = bytes;
+        self
+    }
+
+    /// 设置保存返回值的寄存器, high 为返回值高位所在的寄存器
+    pub fn ret(mut self, low: &str, high: &str) -> Self {
+        self.ret_low = low.to_string();
+        self.ret_high = high.to_string();
+        self
+    }
+
+    /// 设置保存浮点返回值的寄存器
+    pub fn ret_float(mut self, name: &str) -> Self {
+        self.ret_float = name.to_string();
+        self
+    }
+
+    /// 检查寄存器名是否有效, 以及是否有寄存器被多个参数同时使用
+    pub fn validate(&self) -> Result<(), CallError> {
+        let mut used = Vec::new();
+        for dest in &self.args {
+            if let ArgDest::Reg(name) = dest {
+                let reg = parse(name)?;
+                if reg == Reg::St0 {
+                    return Err(invalid("`st0` cannot be used to pass arguments"));
+                }
+                if used.contains(&reg) {
+                    return Err(invalid(&format!(
+                        "register `{}` is used by more than one argument",
+                        name
+                    )));
+                }
+                used.push(reg);
+            }
+        }
+        for name in [&self.ret_low, &self.ret_high] {
+            if !matches!(parse(name)?, Reg::Gpr(_)) {
+                return Err(invalid(&format!(
+                    "`{}` is not a general purpose register",
+                    name
+                )));
+            }
+        }
+        if matches!(parse(&self.ret_float)?, Reg::Gpr(_)) {
+            return Err(invalid(&format!(
+                "`{}` cannot hold a floating point return value",
+                self.ret_float
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// 寄存器在上下文中的位置
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Reg {
+    Gpr(usize),
+    Xmm(usize),
+    St0,
+}
+
+fn parse(name: &str) -> Result<Reg, CallError> {
+    let lower = name.to_ascii_lowercase();
+    if let Some(i) = GPRS.iter().position(|reg| *reg == lower) {
+        return Ok(Reg::Gpr(i));
+    }
+    if let Some(n) = lower.strip_prefix("xmm") {
+        match n.parse::<usize>() {
+            Ok(i) if i < XMMS && i.to_string() == n => return Ok(Reg::Xmm(i)),
+            _ => {}
+        }
+    }
+    if lower == "st0" && cfg!(target_arch = "x86") {
+        return Ok(Reg::St0);
+    }
+    Err(invalid(&match lower.as_str() {
+        "rsp" | "rbp" | "esp" | "ebp" => format!("`{}` cannot be used", name),
+        _ => format!("unknown register `{}`", name),
+    }))
+}
+
+fn invalid(reason: &str) -> CallError {
+    CallError::InvalidDescriptor(reason.to_string())
+}
+
+/// 跳板读写的寄存器文件, 偏移量以 const 操作数传给汇编
+#[repr(C)]
+#[derive(Default)]
+struct Context {
+    target: usize,
+    /// 已排好顺序的栈上参数与其字数
+    stack: usize,
+    words: usize,
+    shadow: usize,
+    gprs: [usize; GPRS.len()],
+    xmms: [u64; XMMS],
+    out_gprs: [usize; GPRS.len()],
+    out_xmms: [u64; XMMS],
+    /// 按 double 与 float 分别读出的 st0, 32 位下 x87 栈为空时为 0
+    st0: f64,
+    st0_single: f32,
+    /// 被调用者弹出的字节数
+    popped: usize,
+}
+
+impl Func {
+    /// 以 desc 描述的调用约定调用函数
+    ///
+    /// 每个参数按 desc 送入指定的寄存器或放到栈上, 占多个字的参数只能放在栈上, 浮点参数也可以送入通用寄存器.
+    /// 被调用者需要像平台的默认约定那样保留 rbx / ebx, rbp / ebp 等寄存器. 本方法不经过 `set_observer` 设置的观察者,
+    /// 开启 `set_paranoid` 时会检查被调用者弹出的字节数是否与 `desc.cleanup` 相符
+    ///
+    /// ```
+    /// use funcall::{ConvDesc, Func};
+    /// extern "C" fn sub(a: i32, b: i32) -> i32 {
+    ///     a - b
+    /// }
+    ///
+    /// # #[cfg(all(target_arch = "x86_64", not(windows)))]
+    /// # {
+    /// let desc = ConvDesc::new().reg("rdi").reg("rsi");
+    /// let mut func = Func::from_raw(sub as *const fn());
+    /// func.push(5i32).push(3i32);
+    /// unsafe { func.call_custom(&desc) }.unwrap();
+    /// assert_eq!(func.ret_as_i32(), 2);
+    /// # }
+    /// ```
+    ///
+    /// # Safety
+    ///
+    /// desc 与压入的参数必须与函数的实际约定和签名一致
+    pub unsafe fn call_custom(&mut self, desc: &ConvDesc) -> Result<(), CallError> {
+        #[cfg(feature = "loader")]
+        self.resolve()?;
+        if self.func.is_null() {
+            return Err(CallError::NullTarget);
+        }
+        #[cfg(feature = "std")]
+        if self.is_poisoned() {
+            return Err(CallError::Poisoned);
+        }
+        if self.paranoid {
+            self.validate_ptr().map_err(CallError::InvalidTarget)?;
+        }
+        if let Some((_, e)) = &self.rejected {
+            return Err(e.clone());
+        }
+        desc.validate()?;
+        if desc.args.len() != self.slots.len() {
+            return Err(CallError::ArgCountMismatch {
+                expected: desc.args.len(),
+                variadic: false,
+                got: self.slots.len(),
+            });
+        }
+
+        let mut ctx = Context {
+            target: self.func as usize,
+            shadow: desc.shadow_space,
+            ..Context::default()
+        };
+        let mut stack: Vec<Vec<usize>> = Vec::new();
+        for (index, (slot, dest)) in self.slots.iter().zip(&desc.args).enumerate() {
+            let words = if slot.float {
+                vec![self.fargs[slot.index].to_bits() as usize]
+            } else {
+                self.args[slot.index..slot.index + slot.len].to_vec()
+            };
+            let name = match dest {
+                ArgDest::Stack => {
+                    stack.push(words);
+                    continue;
+                }
+                ArgDest::Reg(name) => name,
+            };
+            let bytes = words.len() * mem::size_of::<usize>();
+            match parse(name)? {
+                Reg::Gpr(i) if words.len() == 1 => ctx.gprs[i] = words[0],
+                Reg::Xmm(i) if bytes <= 8 => {
+                    // 32 位下的 f64 与 u64 占两个字, 低位在前
+                    ctx.xmms[i] = words.iter().enumerate().fold(0u64, |acc, (i, word)| {
+                        acc | (*word as u64) << (i as u32 * usize::BITS)
+                    })
+                }
+                _ => {
+                    return Err(invalid(&format!(
+                        "argument {} takes {} bytes and cannot be passed in `{}`",
+                        index, bytes, name
+                    )))
+                }
+            }
+        }
+        if self.order == ArgOrder::LeftToRight {
+            stack.reverse();
+        }
+        let stack = stack.concat();
+        let bytes = stack.len() * mem::size_of::<usize>();
+        if bytes > self.max_stack {
+            return Err(CallError::StackTooLarge {
+                bytes,
+                limit: self.max_stack,
+            });
+        }
+        ctx.stack = stack.as_ptr() as usize;
+        ctx.words = stack.len();
+
+        self.called = true;
+        self.ret = Default::default();
+        let fp = fpstate::save(self);
+        trampoline(&mut ctx);
+        fpstate::restore(fp);
+
+        let gpr = |name: &str| match parse(name) {
+            Ok(Reg::Gpr(i)) => ctx.out_gprs[i],
+            _ => unreachable!(),
+        };
+        self.ret.low = gpr(&desc.ret_low);
+        self.ret.high = gpr(&desc.ret_high);
+        self.ret.float = match parse(&desc.ret_float) {
+            Ok(Reg::Xmm(i)) => {
+                let bits = ctx.out_xmms[i];
+                self.float_ret(f64::from_bits(bits), f32::from_bits(bits as u32))
+            }
+            _ => self.float_ret(ctx.st0, ctx.st0_single),
+        };
+        if self.paranoid {
+            let expected = match desc.cleanup {
+                Cleanup::Caller => 0,
+                Cleanup::Callee => bytes,
+            };
+            if ctx.popped != expected {
+                panic!(
+                    "callee consumed {} bytes of stack instead of {} — convention mismatch?",
+                    ctx.popped, expected
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 从上下文中读出所有寄存器与栈上的参数后调用, 再将所有寄存器写回上下文
+///
+/// rbx / ebx, rbp / ebp 以及 32 位下的 esi 不能作为操作数, 在跳板中自行保存和恢复.
+/// rbp / ebp 作为帧指针, 调用前后通过它找到上下文与目标函数
+#[cfg(target_arch = "x86_64")]
+unsafe fn trampoline(ctx: &mut Context) {
+    asm!(
+        // 越过 red zone
+        "sub rsp, 128",
+        "push rbp",
+        "push rbx",
+        "mov rbp, rsp",
+        // [rbp - 8] 为上下文, [rbp - 16] 为目标函数, [rbp - 24] 为 call 时的 rsp, [rbp - 32] 为临时空间
+        "push rax",
+        "push qword ptr [rax + {target}]",
+        "sub rsp, 16",
+        "mov rcx, qword ptr [rax + {words}]",
+        "lea rdx, [rcx * 8]",
+        "add rdx, qword ptr [rax + {shadow}]",
+        // 超过一页时逐页下移 rsp 并访问新的一页, 不会跳过 guard page
+        "2:",
+        "cmp rdx, 4096",
+        "jb 3f",
+        "sub rsp, 4096",
+        "or qword ptr [rsp], 0",
+        "sub rdx, 4096",
+        "jmp 2b",
+        "3:",
+        "sub rsp, rdx",
+        "and rsp, -16",
+        // 栈上的参数复制到 shadow space 之上
+        "mov rsi, qword ptr [rax + {stack}]",
+        "mov rdi, rsp",
+        "add rdi, qword ptr [rax + {shadow}]",
+        "cld",
+        "rep movsq",
+        "mov qword ptr [rbp - 24], rsp",
+        "movq xmm0, qword ptr [rax + {xmms}]",
+        "movq xmm1, qword ptr [rax + {xmms} + 8]",
+        "movq xmm2, qword ptr [rax + {xmms} + 16]",
+        "movq xmm3, qword ptr [rax + {xmms} + 24]",
+        "movq xmm4, qword ptr [rax + {xmms} + 32]",
+        "movq xmm5, qword ptr [rax + {xmms} + 40]",
+        "movq xmm6, qword ptr [rax + {xmms} + 48]",
+        "movq xmm7, qword ptr [rax + {xmms} + 56]",
+        "movq xmm8, qword ptr [rax + {xmms} + 64]",
+        "movq xmm9, qword ptr [rax + {xmms} + 72]",
+        "movq xmm10, qword ptr [rax + {xmms} + 80]",
+        "movq xmm11, qword ptr [rax + {xmms} + 88]",
+        "movq xmm12, qword ptr [rax + {xmms} + 96]",
+        "movq xmm13, qword ptr [rax + {xmms} + 104]",
+        "movq xmm14, qword ptr [rax + {xmms} + 112]",
+        "movq xmm15, qword ptr [rax + {xmms} + 120]",
+        // rax 最后送入
+        "mov rbx, qword ptr [rax + {gprs} + 8]",
+        "mov rcx, qword ptr [rax + {gprs} + 16]",
+        "mov rdx, qword ptr [rax + {gprs} + 24]",
+        "mov rsi, qword ptr [rax + {gprs} + 32]",
+        "mov rdi, qword ptr [rax + {gprs} + 40]",
+        "mov r8, qword ptr [rax + {gprs} + 48]",
+        "mov r9, qword ptr [rax + {gprs} + 56]",
+        "mov r10, qword ptr [rax + {gprs} + 64]",
+        "mov r11, qword ptr [rax + {gprs} + 72]",
+        "mov r12, qword ptr [rax + {gprs} + 80]",
+        "mov r13, qword ptr [rax + {gprs} + 88]",
+        "mov r14, qword ptr [rax + {gprs} + 96]",
+        "mov r15, qword ptr [rax + {gprs} + 104]",
+        "mov rax, qword ptr [rax + {gprs}]",
+        "call qword ptr [rbp - 16]",
+        // 先暂存 rax, 再算出被调用者弹出的字节数
+        "push rax",
+        "lea rax, [rsp + 8]",
+        "sub rax, qword ptr [rbp - 24]",
+        "mov qword ptr [rbp - 32], rax",
+        "mov rax, qword ptr [rbp - 8]",
+        "mov qword ptr [rax + {out_gprs} + 8], rbx",
+        "mov qword ptr [rax + {out_gprs} + 16], rcx",
+        "mov qword ptr [rax + {out_gprs} + 24], rdx",
+        "mov qword ptr [rax + {out_gprs} + 32], rsi",
+        "mov qword ptr [rax + {out_gprs} + 40], rdi",
+        "mov qword ptr [rax + {out_gprs} + 48], r8",
+        "mov qword ptr [rax + {out_gprs} + 56], r9",
+        "mov qword ptr [rax + {out_gprs} + 64], r10",
+        "mov qword ptr [rax + {out_gprs} + 72], r11",
+        "mov qword ptr [rax + {out_gprs} + 80], r12",
+        "mov qword ptr [rax + {out_gprs} + 88], r13",
+        "mov qword ptr [rax + {out_gprs} + 96], r14",
+        "mov qword ptr [rax + {out_gprs} + 104], r15",
+        "mov rcx, qword ptr [rbp - 32]",
+        "mov qword ptr [rax + {popped}], rcx",
+        "pop rcx",
+        "mov qword ptr [rax + {out_gprs}], rcx",
+        "movq qword ptr [rax + {out_xmms}], xmm0",
+        "movq qword ptr [rax + {out_xmms} + 8], xmm1",
+        "movq qword ptr [rax + {out_xmms} + 16], xmm2",
+        "movq qword ptr [rax + {out_xmms} + 24], xmm3",
+        "movq qword ptr [rax + {out_xmms} + 32], xmm4",
+        "movq qword ptr [rax + {out_xmms} + 40], xmm5",
+        "movq qword ptr [rax + {out_xmms} + 48], xmm6",
+        "movq qword ptr [rax + {out_xmms} + 56], xmm7",
+        "movq qword ptr [rax + {out_xmms} + 64], xmm8",
+        "movq qword ptr [rax + {out_xmms} + 72], xmm9",
+        "movq qword ptr [rax + {out_xmms} + 80], xmm10",
+        "movq qword ptr [rax + {out_xmms} + 88], xmm11",
+        "movq qword ptr [rax + {out_xmms} + 96], xmm12",
+        "movq qword ptr [rax + {out_xmms} + 104], xmm13",
+        "movq qword ptr [rax + {out_xmms} + 112], xmm14",
+        "movq qword ptr [rax + {out_xmms} + 120], xmm15",
+        "mov rsp, rbp",
+        "pop rbx",
+        "pop rbp",
+        "add rsp, 128",
+        target = const offset_of!(Context, target),
+        stack = const offset_of!(Context, stack),
+        words = const offset_of!(Context, words),
+        shadow = const offset_of!(Context, shadow),
+        gprs = const offset_of!(Context, gprs),
+        xmms = const offset_of!(Context, xmms),
+        out_gprs = const offset_of!(Context, out_gprs),
+        out_xmms = const offset_of!(Context, out_xmms),
+        popped = const offset_of!(Context, popped),
+        inout("rax") ctx as *mut Context => _,
+        out("r12") _,
+        out("r13") _,
+        out("r14") _,
+        out("r15") _,
+        out("xmm6") _,
+        out("xmm7") _,
+        out("xmm8") _,
+        out("xmm9") _,
+        out("xmm10") _,
+        out("xmm11") _,
+        out("xmm12") _,
+        out("xmm13") _,
+        out("xmm14") _,
+        out("xmm15") _,
+        clobber_abi("C"),
+    );
+}
+
+/// 从上下文中读出所有寄存器与栈上的参数后调用, 再将所有寄存器写回上下文
+///
+/// rbx / ebx, rbp / ebp 以及 32 位下的 esi 不能作为操作数, 在跳板中自行保存和恢复.
+/// rbp / ebp 作为帧指针, 调用前后通过它找到上下文与目标函数
+#[cfg(target_arch = "x86")]
+unsafe fn trampoline(ctx: &mut Context) {
+    asm!(
+        "push ebp",
+        "push ebx",
+        "push esi",
+        "mov ebp, esp",
+        // [ebp - 4] 为上下文, [ebp - 8] 为目标函数, [ebp - 12] 为 call 时的 esp, [ebp - 16] 为临时空间
+        "push eax",
+        "push dword ptr [eax + {target}]",
+        "sub esp, 8",
+        "mov ecx, dword ptr [eax + {words}]",
+        "lea edx, [ecx * 4]",
+        "add edx, dword ptr [eax + {shadow}]",
+        "2:",
+        "cmp edx, 4096",
+        "jb 3f",
+        "sub esp, 4096",
+        "or dword ptr [esp], 0",
+        "sub edx, 4096",
+        "jmp 2b",
+        "3:",
+        "sub esp, edx",
+        "and esp, -16",
+        "mov esi, dword ptr [eax + {stack}]",
+        "mov edi, esp",
+        "add edi, dword ptr [eax + {shadow}]",
+        "cld",
+        "rep movsd",
+        "mov dword ptr [ebp - 12], esp",
+        "movq xmm0, qword ptr [eax + {xmms}]",
+        "movq xmm1, qword ptr [eax + {xmms} + 8]",
+        "movq xmm2, qword ptr [eax + {xmms} + 16]",
+        "movq xmm3, qword ptr [eax + {xmms} + 24]",
+        "movq xmm4, qword ptr [eax + {xmms} + 32]",
+        "movq xmm5, qword ptr [eax + {xmms} + 40]",
+        "movq xmm6, qword ptr [eax + {xmms} + 48]",
+        "movq xmm7, qword ptr [eax + {xmms} + 56]",
+        "mov ebx, dword ptr [eax + {gprs} + 4]",
+        "mov ecx, dword ptr [eax + {gprs} + 8]",
+        "mov edx, dword ptr [eax + {gprs} + 12]",
+        "mov esi, dword ptr [eax + {gprs} + 16]",
+        "mov edi, dword ptr [eax + {gprs} + 20]",
+        "mov eax, dword ptr [eax + {gprs}]",
+        "call dword ptr [ebp - 8]",
+        "push eax",
+        "lea eax, [esp + 4]",
+        "sub eax, dword ptr [ebp - 12]",
+        "mov dword ptr [ebp - 16], eax",
+        "mov eax, dword ptr [ebp - 4]",
+        "mov dword ptr [eax + {out_gprs} + 4], ebx",
+        "mov dword ptr [eax + {out_gprs} + 8], ecx",
+        "mov dword ptr [eax + {out_gprs} + 12], edx",
+        "mov dword ptr [eax + {out_gprs} + 16], esi",
+        "mov dword ptr [eax + {out_gprs} + 20], edi",
+        "mov ecx, dword ptr [ebp - 16]",
+        "mov dword ptr [eax + {popped}], ecx",
+        "pop ecx",
+        "mov dword ptr [eax + {out_gprs}], ecx",
+        "movq qword ptr [eax + {out_xmms}], xmm0",
+        "movq qword ptr [eax + {out_xmms} + 8], xmm1",
+        "movq qword ptr [eax + {out_xmms} + 16], xmm2",
+        "movq qword ptr [eax + {out_xmms} + 24], xmm3",
+        "movq qword ptr [eax + {out_xmms} + 32], xmm4",
+        "movq qword ptr [eax + {out_xmms} + 40], xmm5",
+        "movq qword ptr [eax + {out_xmms} + 48], xmm6",
+        "movq qword ptr [eax + {out_xmms} + 56], xmm7",
+        // 与 cdecl 相同, x87 栈不为空时才弹出 st0. fnstsw 会改写 ax, 因此上下文改放在 edx 中
+        "mov edx, eax",
+        "fxam",
+        "fnstsw ax",
+        "and ah, 0x45",
+        "cmp ah, 0x41",
+        "je 4f",
+        "fst dword ptr [edx + {st0_single}]",
+        "fstp qword ptr [edx + {st0}]",
+        "4:",
+        "mov esp, ebp",
+        "pop esi",
+        "pop ebx",
+        "pop ebp",
+        target = const offset_of!(Context, target),
+        stack = const offset_of!(Context, stack),
+        words = const offset_of!(Context, words),
+        shadow = const offset_of!(Context, shadow),
+        gprs = const offset_of!(Context, gprs),
+        xmms = const offset_of!(Context, xmms),
+        out_gprs = const offset_of!(Context, out_gprs),
+        out_xmms = const offset_of!(Context, out_xmms),
+        st0 = const offset_of!(Context, st0),
+        st0_single = const offset_of!(Context, st0_single),
+        popped = const offset_of!(Context, popped),
+        inout("eax") ctx as *mut Context => _,
+        out("edi") _,
+        clobber_abi("C"),
+    );
+}
@@ -0,0 +1,30 @@
+//! 读取与清零调用后的 errno, Windows 下为 `GetLastError` 的值
+
+/// 调用前清零, 避免返回之前残留的值
+pub(crate) fn clear() {
+    #[cfg(target_os = "linux")]
+    unsafe {
+        extern "C" {
+            fn __errno_location() -> *mut core::ffi::c_int;
+        }
+        *__errno_location() = 0;
+    }
+    #[cfg(target_vendor = "apple")]
+    unsafe {
+        extern "C" {
+            fn __error() -> *mut core::ffi::c_int;
+        }
+        *__error() = 0;
+    }
+    #[cfg(windows)]
+    unsafe {
+        extern "system" {
+            fn SetLastError(code: u32);
+        }
+        SetLastError(0);
+    }
+}
+
+pub(crate) fn get() -> i32 {
+    std::io::Error::last_os_error().raw_os_error().unwrap_or(0)
+}
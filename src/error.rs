@@ -0,0 +1,301 @@
+//! 加载与调用函数时可能出现的错误
+
+use alloc::string::{String, ToString};
+use core::error::Error as StdError;
+use core::fmt;
+
+use crate::{ArgKind, Backend, CType, Convention, PtrError};
+
+/// `Func::try_call` 在调用前检查出的错误
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd)]
+#[non_exhaustive]
+pub enum CallError {
+    /// 函数指针为空
+    NullTarget,
+    /// 开启 `Func::set_paranoid` 后检查出函数指针无效
+    InvalidTarget(PtrError),
+    /// `Func::call_with_timeout` 超时后遗留的调用仍在运行
+    Poisoned,
+    /// 当前平台不支持该调用约定
+    UnsupportedConvention(Convention),
+    /// 当前平台上没有任何可用的后端, 不支持任何调用约定, 参见 `supported_conventions`
+    UnsupportedTarget {
+        /// 编译目标的架构, 如 `riscv64`
+        arch: &'static str,
+        /// 编译目标的操作系统, 如 `macos`
+        os: &'static str,
+    },
+    /// 压入的参数个数与声明的不符
+    ArgCountMismatch {
+        /// 声明的固定参数个数
+        expected: usize,
+        /// 是否为可变参数函数
+        variadic: bool,
+        /// 实际压入的参数个数
+        got: usize,
+    },
+    /// 参数的值不能作为声明的类型传递
+    ArgTypeMismatch {
+        /// 参数的序号
+        index: usize,
+        /// 声明的类型
+        expected: CType,
+        /// 实际传入的值
+        got: String,
+    },
+    /// 栈上参数所占的字节数超过了 `Func::set_max_stack_bytes` 设置的上限
+    StackTooLarge {
+        /// 栈上参数所占的字节数
+        bytes: usize,
+        /// 上限
+        limit: usize,
+    },
+    /// 实际压入的参数字节数与 32 位 Windows 修饰名 `_name@N` 中的 N 不符, 被调用者返回时会弹出错误的字节数
+    ArgSizeMismatch {
+        /// 修饰名中的参数字节数
+        expected: usize,
+        /// 实际压入的参数字节数
+        got: usize,
+    },
+    /// `Func::push_fmt` 的格式串中有无法识别的字符
+    InvalidFormat {
+        /// 字符在格式串中的位置
+        index: usize,
+        /// 无法识别的字符
+        spec: char,
+    },
+    /// `PreparedCall::replace_arg` 替换的参数与原先压入的类型不同
+    ArgKindMismatch {
+        /// 参数的序号
+        index: usize,
+        /// 原先压入的参数类型
+        expected: ArgKind,
+        /// 替换的参数类型
+        got: ArgKind,
+    },
+    /// 该后端只支持从右往左排列栈上的参数, 参见 `Func::set_arg_order`
+    UnsupportedArgOrder(Backend),
+    /// 该后端不支持 `Func::set_layout_override` 改变的参数布局
+    UnsupportedLayout(Backend),
+    /// 该后端取不回 `Func::set_return` 声明的通过浮点寄存器返回的结构体, 如 64 位 Linux 下需要 xmm1 的结构体
+    UnsupportedReturn(Backend),
+    /// 该后端不支持 `Func::set_unwind_policy` 设置的展开策略
+    UnsupportedUnwind(Backend),
+    /// 当前平台不支持传递或返回该类型, 如 x86_64 与 aarch64 以外的 `ArgKind::F16`
+    UnsupportedKind(ArgKind),
+    /// `Func::call_custom` 的调用约定描述无效
+    InvalidDescriptor(String),
+    /// `Func::try_call_catching` 捕获到被调用者抛出的 SEH 异常或 C++ 异常
+    ForeignException {
+        /// 异常代码, MSVC 的 C++ 异常为 0xE06D7363
+        code: u32,
+    },
+    /// 通过 `Func::new_lazy` 创建的函数在首次调用时未能加载库或找到符号
+    ResolveFailed {
+        /// 查找的符号名
+        symbol: String,
+        /// 加载或查找失败的原因
+        reason: String,
+    },
+}
+
+impl fmt::Display for CallError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CallError::NullTarget => write!(f, "the target function pointer is null"),
+            CallError::InvalidTarget(e) => write!(f, "invalid target: {}", e),
+            CallError::Poisoned => write!(f, "a previous call timed out and is still running"),
+            CallError::UnsupportedConvention(conv) => {
+                write!(
+                    f,
+                    "calling convention {:?} is not supported on this target",
+                    conv
+                )
+            }
+            CallError::UnsupportedTarget { arch, os } => write!(
+                f,
+                "no calling convention is supported on {}-{}, try enabling the `libffi` feature",
+                arch, os
+            ),
+            CallError::ArgCountMismatch {
+                expected,
+                variadic,
+                got,
+            } => write!(
+                f,
+                "expected {}{} argument(s), got {}",
+                if *variadic { "at least " } else { "" },
+                expected,
+                got
+            ),
+            CallError::ArgTypeMismatch {
+                index,
+                expected,
+                got,
+            } => write!(
+                f,
+                "argument {} of type {:?} cannot accept {}",
+                index, expected, got
+            ),
+            CallError::StackTooLarge { bytes, limit } => write!(
+                f,
+                "arguments need {} bytes of stack, more than the limit of {}",
+                bytes, limit
+            ),
+            CallError::ArgSizeMismatch { expected, got } => write!(
+                f,
+                "the decorated symbol expects {} bytes of arguments, got {}",
+                expected, got
+            ),
+            CallError::InvalidFormat { index, spec } => {
+                write!(f, "unknown format character `{}` at {}", spec, index)
+            }
+            CallError::ArgKindMismatch {
+                index,
+                expected,
+                got,
+            } => {
+                write!(
+                    f,
+                    "argument {} was pushed as {:?} and cannot be replaced by {:?}",
+                    index, expected, got
+                )
+            }
+            CallError::UnsupportedArgOrder(backend) => write!(
+                f,
+                "backend {:?} only supports right-to-left stack arguments",
+                backend
+            ),
+            CallError::UnsupportedLayout(backend) => write!(
+                f,
+                "backend {:?} does not support overriding the argument layout",
+                backend
+            ),
+            CallError::UnsupportedReturn(backend) => write!(
+                f,
+                "backend {:?} cannot retrieve the declared floating-point aggregate return value",
+                backend
+            ),
+            CallError::UnsupportedUnwind(backend) => write!(
+                f,
+                "backend {:?} cannot let unwinding pass through the call frame on this target",
+                backend
+            ),
+            CallError::UnsupportedKind(kind) => {
+                write!(f, "{:?} arguments are not supported on this target", kind)
+            }
+            CallError::InvalidDescriptor(reason) => {
+                write!(f, "invalid convention descriptor: {}", reason)
+            }
+            CallError::ForeignException { code } => {
+                write!(f, "callee raised foreign exception {:#010x}", code)
+            }
+            CallError::ResolveFailed { symbol, reason } => {
+                write!(f, "failed to resolve `{}`: {}", symbol, reason)
+            }
+        }
+    }
+}
+
+impl CallError {
+    /// 当前编译目标的 `CallError::UnsupportedTarget`
+    pub fn unsupported_target() -> Self {
+        CallError::UnsupportedTarget {
+            arch: env!("FUNCALL_TARGET_ARCH"),
+            os: env!("FUNCALL_TARGET_OS"),
+        }
+    }
+
+    /// 不支持以 conv 调用时的错误, 当前平台不支持任何调用约定时为 `UnsupportedTarget`
+    pub(crate) fn unsupported(conv: Convention) -> Self {
+        if crate::supported_conventions().is_empty() {
+            Self::unsupported_target()
+        } else {
+            CallError::UnsupportedConvention(conv)
+        }
+    }
+}
+
+impl StdError for CallError {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            CallError::InvalidTarget(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// 加载函数, 解析原型等不涉及调用本身的操作出现的错误
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// 原型或名字无法解析, 或含有不支持的类型
+    InvalidInput(String),
+    /// 找不到指定的函数
+    NotFound(String),
+    /// 读取的数据格式不正确
+    InvalidData(String),
+    /// 加载库或读写文件时的系统错误
+    #[cfg(feature = "std")]
+    Io(std::io::Error),
+}
+
+#[cfg(feature = "std")]
+impl Error {
+    /// 对应的 `std::io::ErrorKind`
+    pub fn kind(&self) -> std::io::ErrorKind {
+        use std::io::ErrorKind;
+        match self {
+            Error::InvalidInput(_) => ErrorKind::InvalidInput,
+            Error::NotFound(_) => ErrorKind::NotFound,
+            Error::InvalidData(_) => ErrorKind::InvalidData,
+            Error::Io(e) => e.kind(),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::InvalidInput(msg) | Error::NotFound(msg) | Error::InvalidData(msg) => {
+                f.write_str(msg)
+            }
+            #[cfg(feature = "std")]
+            Error::Io(e) => e.fmt(f),
+        }
+    }
+}
+
+impl StdError for Error {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            #[cfg(feature = "std")]
+            Error::Io(e) => e.source(),
+            _ => None,
+        }
+    }
+}
+
+/// 调用前的检查未通过, 作为 `Error::InvalidInput`
+impl From<CallError> for Error {
+    fn from(e: CallError) -> Self {
+        Error::InvalidInput(e.to_string())
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<Error> for std::io::Error {
+    fn from(e: Error) -> Self {
+        match e {
+            Error::Io(e) => e,
+            e => std::io::Error::new(e.kind(), e),
+        }
+    }
+}
@@ -0,0 +1,161 @@
+//! 半精度浮点数, 即 C 语言的 `_Float16` / `__fp16`, 参见 `F16`
+
+use core::fmt;
+
+use crate::{ArgKind, ArgSink, FromRet, IntoArg, RetValues};
+
+/// 当前平台上是否能以浮点寄存器的低 16 位传递半精度浮点数
+pub(crate) const SUPPORTED: bool = cfg!(any(target_arch = "x86_64", target_arch = "aarch64"));
+
+/// 以原始的位保存的半精度浮点数
+///
+/// 压入时与 C 的 `_Float16` 一样放入浮点寄存器的低 16 位, 不会提升为 float 或 double;
+/// 返回值通过 `Func::ret_as_f16` 从浮点返回值寄存器的低 16 位取出. 比较时按位比较.
+/// 目前只支持 x86_64 与 aarch64, 其他平台上调用时返回 `CallError::UnsupportedKind`.
+/// 开启 `half` feature 后可以与 `half::f16` 相互转换, `half::f16` 也可以直接压入
+///
+/// ```
+/// use funcall::F16;
+///
+/// let half = F16::from_f32(1.5);
+/// assert_eq!(half.to_bits(), 0x3e00);
+/// assert_eq!(half.to_f32(), 1.5);
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[repr(transparent)]
+pub struct F16(u16);
+
+impl F16 {
+    /// 由 IEEE 754 binary16 的位构造
+    pub const fn from_bits(bits: u16) -> Self {
+        F16(bits)
+    }
+
+    /// IEEE 754 binary16 的位
+    pub const fn to_bits(self) -> u16 {
+        self.0
+    }
+
+    /// 舍入到最接近的半精度浮点数, 距离相等时取偶数, 超出范围时为无穷大
+    pub fn from_f32(value: f32) -> Self {
+        let x = value.to_bits();
+        let sign = ((x >> 16) & 0x8000) as u16;
+        let exp = ((x >> 23) & 0xff) as i32;
+        let man = x & 0x7f_ffff;
+        if exp == 0xff {
+            // 无穷大与 NaN, NaN 保留尾数的高位并置为 quiet NaN
+            let nan = if man == 0 {
+                0
+            } else {
+                0x200 | (man >> 13) as u16
+            };
+            return F16(sign | 0x7c00 | nan);
+        }
+        let exp = exp - 127 + 15;
+        if exp >= 0x1f {
+            return F16(sign | 0x7c00);
+        }
+        if exp <= 0 {
+            // 非规格化数, 舍入后可能进位为最小的规格化数
+            if exp < -10 {
+                return F16(sign);
+            }
+            return F16(sign | round_shift(man | 0x80_0000, (14 - exp) as u32) as u16);
+        }
+        // 尾数舍入后的进位会直接进入指数, 最大的有限值之上进位为无穷大
+        F16(sign | round_shift(((exp as u32) << 23) | man, 13) as u16)
+    }
+
+    /// 转换为 f32, 没有精度损失
+    pub fn to_f32(self) -> f32 {
+        let sign = u32::from(self.0 & 0x8000) << 16;
+        let exp = u32::from((self.0 >> 10) & 0x1f);
+        let man = u32::from(self.0 & 0x3ff);
+        let bits = match exp {
+            0 if man == 0 => sign,
+            // 非规格化数, 将最高的 1 移到隐含位上
+            0 => {
+                let shift = man.leading_zeros() - 21;
+                sign | ((113 - shift) << 23) | (((man << shift) & 0x3ff) << 13)
+            }
+            0x1f => sign | 0x7f80_0000 | (man << 13),
+            _ => sign | ((exp + 112) << 23) | (man << 13),
+        };
+        f32::from_bits(bits)
+    }
+}
+
+/// 右移 shift 位, 按舍入到最接近的值, 距离相等时取偶数
+fn round_shift(value: u32, shift: u32) -> u32 {
+    let quotient = value >> shift;
+    let rest = value & ((1 << shift) - 1);
+    let half = 1 << (shift - 1);
+    if rest > half || (rest == half && quotient & 1 == 1) {
+        quotient + 1
+    } else {
+        quotient
+    }
+}
+
+impl From<F16> for f32 {
+    fn from(value: F16) -> Self {
+        value.to_f32()
+    }
+}
+
+impl From<F16> for f64 {
+    fn from(value: F16) -> Self {
+        f64::from(value.to_f32())
+    }
+}
+
+impl fmt::Display for F16 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.to_f32().fmt(f)
+    }
+}
+
+// 浮点寄存器的低 16 位即为 _Float16, 高位置零
+impl IntoArg for F16 {
+    const KIND: ArgKind = ArgKind::F16;
+
+    fn push_into(self, out: &mut ArgSink) {
+        out.push_word(self.0 as usize);
+    }
+}
+
+impl FromRet for F16 {
+    fn from_ret(ret: &RetValues) -> Self {
+        ret.as_f16()
+    }
+}
+
+#[cfg(feature = "half")]
+impl From<half::f16> for F16 {
+    fn from(value: half::f16) -> Self {
+        F16(value.to_bits())
+    }
+}
+
+#[cfg(feature = "half")]
+impl From<F16> for half::f16 {
+    fn from(value: F16) -> Self {
+        half::f16::from_bits(value.0)
+    }
+}
+
+#[cfg(feature = "half")]
+impl IntoArg for half::f16 {
+    const KIND: ArgKind = ArgKind::F16;
+
+    fn push_into(self, out: &mut ArgSink) {
+        F16::from(self).push_into(out);
+    }
+}
+
+#[cfg(feature = "half")]
+impl FromRet for half::f16 {
+    fn from_ret(ret: &RetValues) -> Self {
+        ret.as_f16().into()
+    }
+}
@@ -0,0 +1,156 @@
+//! 调试输出
+
+use core::fmt;
+#[cfg(feature = "provenance")]
+use core::panic::Location;
+
+#[cfg(feature = "provenance")]
+use crate::Origin;
+use crate::{ArgKind, ArgSlot, Func, RetValues, F16};
+
+/// 一个已压入参数的只读视图
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ArgView {
+    kind: ArgKind,
+    bits: u128,
+    #[cfg(feature = "provenance")]
+    origin: Origin,
+}
+
+impl ArgView {
+    /// 参数的类型
+    pub fn kind(&self) -> ArgKind {
+        self.kind
+    }
+
+    /// 参数的原始数据, 小于 128 位的参数只占用低位
+    ///
+    /// 通过 `push` 压入的 f32 已被提升为 f64, 因此 `ArgKind::F32` 的数据是 f64 的位
+    pub fn bits(&self) -> u128 {
+        self.bits
+    }
+
+    /// 压入参数的代码位置, 即调用 `push` 等方法的那一行
+    ///
+    /// 由 crate 在调用前插入的隐藏参数, 如 this 与返回结构体的缓冲区指针, 没有记录位置
+    #[cfg(feature = "provenance")]
+    pub fn origin(&self) -> Option<&'static Location<'static>> {
+        self.origin.0
+    }
+}
+
+/// 以 `kind value` 的形式输出, 如 `i32 -1`, `ptr 0x2000`
+impl fmt::Display for ArgView {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let bits = self.bits;
+        match self.kind {
+            ArgKind::I8 => write!(f, "i8 {}", bits as i8),
+            ArgKind::U8 => write!(f, "u8 {}", bits as u8),
+            ArgKind::I16 => write!(f, "i16 {}", bits as i16),
+            ArgKind::U16 => write!(f, "u16 {}", bits as u16),
+            ArgKind::I32 => write!(f, "i32 {}", bits as i32),
+            ArgKind::U32 => write!(f, "u32 {}", bits as u32),
+            ArgKind::I64 => write!(f, "i64 {}", bits as i64),
+            ArgKind::U64 => write!(f, "u64 {}", bits as u64),
+            ArgKind::I128 => write!(f, "i128 {}", bits as i128),
+            ArgKind::U128 => write!(f, "u128 {}", bits),
+            ArgKind::Isize => write!(f, "isize {}", bits as isize),
+            ArgKind::Usize => write!(f, "usize {}", bits as usize),
+            // f32 在压入时已经被提升为 f64
+            ArgKind::F32 => write!(f, "f32 {}", f64::from_bits(bits as u64) as f32),
+            ArgKind::F64 => write!(f, "f64 {}", f64::from_bits(bits as u64)),
+            ArgKind::CFloat => write!(f, "float {}", f32::from_bits(bits as u32)),
+            ArgKind::F16 => write!(f, "f16 {}", F16::from_bits(bits as u16)),
+            ArgKind::Ptr => write!(f, "ptr {:#x}", bits as usize),
+            ArgKind::Other => write!(f, "? {:#x}", bits),
+        }
+    }
+}
+
+impl Func {
+    /// 依次返回所有已压入参数的视图
+    pub fn arg_views(&self) -> impl Iterator<Item = ArgView> + '_ {
+        self.slots.iter().map(move |slot| self.view(slot))
+    }
+
+    fn view(&self, slot: &ArgSlot) -> ArgView {
+        let bits = if slot.float {
+            u128::from(self.fargs[slot.index].to_bits())
+        } else {
+            self.args[slot.index..slot.index + slot.len]
+                .iter()
+                .rev()
+                .fold(0u128, |bits, &word| {
+                    bits.checked_shl(usize::BITS).unwrap_or(0) | word as u128
+                })
+        };
+        ArgView {
+            kind: slot.kind,
+            bits,
+            #[cfg(feature = "provenance")]
+            origin: slot.origin,
+        }
+    }
+}
+
+/// 以 `symbol(kind value, ...)` 的形式输出将要进行的调用, 调用过后还会输出返回值
+impl fmt::Display for Func {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.symbol {
+            Some(symbol) => write!(f, "{}(", symbol)?,
+            None => write!(f, "{:p}(", self.func)?,
+        }
+        for (i, arg) in self.arg_views().enumerate() {
+            if i != 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", arg)?;
+        }
+        write!(f, ")")?;
+        if self.called {
+            write!(f, " -> {}", self.ret)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Func {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        struct Args<'a>(&'a Func);
+
+        impl fmt::Debug for Args<'_> {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                let mut list = f.debug_list();
+                for arg in self.0.arg_views() {
+                    #[cfg(feature = "provenance")]
+                    if let Some(origin) = arg.origin() {
+                        list.entry(&format_args!("{} at {}", arg, origin));
+                        continue;
+                    }
+                    list.entry(&format_args!("{}", arg));
+                }
+                list.finish()
+            }
+        }
+
+        let mut s = f.debug_struct("Func");
+        s.field("func", &self.func).field("symbol", &self.symbol);
+        #[cfg(feature = "loader")]
+        s.field("lib", &self.lib);
+        s.field("args", &Args(self));
+        if self.called {
+            s.field("ret", &self.ret);
+        }
+        s.finish()
+    }
+}
+
+impl fmt::Display for RetValues {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "(low {:#x}, high {:#x}, float {})",
+            self.low, self.high, self.float
+        )
+    }
+}
@@ -0,0 +1,100 @@
+//! ctypes 风格的单字符参数类型声明
+
+use alloc::vec::Vec;
+
+use crate::signature::push_as;
+use crate::{Arg, CType, CallError, Func};
+
+impl Func {
+    /// 按照格式串 fmt 逐个检查并压入参数, 每个字符声明一个参数的 C 类型
+    ///
+    /// | 字符 | 类型 | 字符 | 类型 |
+    /// |------|------|------|------|
+    /// | `b` | signed char | `B` | unsigned char |
+    /// | `h` | short | `H` | unsigned short |
+    /// | `i` | int | `I` | unsigned int |
+    /// | `l` | long | `L` | unsigned long |
+    /// | `q` | int64_t | `Q` | uint64_t |
+    /// | `n` | ssize_t | `N` | size_t |
+    /// | `f` | float | `d` | double |
+    /// | `?` | bool | `p` | 指针 |
+    /// | `z` | 以 '\0' 结尾的字符串 | | |
+    ///
+    /// 整数会被转换为声明的宽度, 值超出范围时报错而不是截断; `f` 以 float 传递, 不会提升为 double;
+    /// `p` 接受 `Arg::Ptr`, `Arg::Bytes` 与 `Arg::Str`, `z` 只接受 `Arg::Str`.
+    /// 出错时不会压入任何参数
+    ///
+    /// ```
+    /// use funcall::{Arg, Func};
+    /// extern "C" fn add(a: i32, b: f32, c: f64) -> f64 {
+    ///     a as f64 + b as f64 + c
+    /// }
+    ///
+    /// let mut func = Func::from_raw(add as *const fn());
+    /// func.push_fmt("ifd", &[Arg::I64(1), Arg::F32(2.0), Arg::F64(3.0)])
+    ///     .unwrap();
+    /// unsafe { func.cdecl() };
+    /// assert_eq!(func.ret_as_f64(), 6.0);
+    /// ```
+    #[cfg_attr(feature = "provenance", track_caller)]
+    pub fn push_fmt(&mut self, fmt: &str, args: &[Arg]) -> Result<(), CallError> {
+        let types = fmt
+            .chars()
+            .enumerate()
+            .map(|(index, spec)| format_type(spec).ok_or(CallError::InvalidFormat { index, spec }))
+            .collect::<Result<Vec<_>, _>>()?;
+        if types.len() != args.len() {
+            return Err(CallError::ArgCountMismatch {
+                expected: types.len(),
+                variadic: false,
+                got: args.len(),
+            });
+        }
+
+        let checkpoint = self.checkpoint();
+        for (index, (ty, arg)) in types.into_iter().zip(args.iter().cloned()).enumerate() {
+            let pushed = match (ty, arg) {
+                (None, Arg::Str(s)) => {
+                    self.push_arg(Arg::Str(s));
+                    Ok(())
+                }
+                (None, arg) => Err(CallError::ArgTypeMismatch {
+                    index,
+                    expected: CType::ptr(CType::constant(CType::Char)),
+                    got: format!("{:?}", arg),
+                }),
+                (Some(ty), arg) => push_as(self, &ty, index, arg),
+            };
+            if let Err(e) = pushed {
+                self.rollback(checkpoint);
+                return Err(e);
+            }
+        }
+        self.mark_origin(checkpoint[2]);
+        Ok(())
+    }
+}
+
+/// 格式字符对应的类型, `z` 为 `Some(None)`
+fn format_type(spec: char) -> Option<Option<CType>> {
+    Some(Some(match spec {
+        'b' => CType::SChar,
+        'B' => CType::UChar,
+        'h' => CType::Short,
+        'H' => CType::UShort,
+        'i' => CType::Int,
+        'I' => CType::UInt,
+        'l' => CType::Long,
+        'L' => CType::ULong,
+        'q' => CType::Int64,
+        'Q' => CType::UInt64,
+        'n' => CType::SSizeT,
+        'N' => CType::SizeT,
+        'f' => CType::Float,
+        'd' => CType::Double,
+        '?' => CType::Bool,
+        'p' => CType::ptr(CType::Void),
+        'z' => return Some(None),
+        _ => return None,
+    }))
+}
@@ -0,0 +1,105 @@
+//! 静态已知签名的函数指针
+
+use core::mem;
+use core::ops::Deref;
+
+use crate::Func;
+#[cfg(feature = "loader")]
+use crate::Library;
+
+/// 函数指针类型, 为最多 12 个参数的 `extern "C"` 与 `extern "system"` 函数指针实现
+pub trait FnPtr: Copy {
+    /// 参数组成的元组
+    type Args;
+    /// 返回值类型
+    type Ret;
+
+    /// 将无类型的函数指针转换为本类型
+    ///
+    /// # Safety
+    ///
+    /// ptr 指向的函数的签名必须与本类型一致
+    unsafe fn from_ptr(ptr: *const fn()) -> Self;
+
+    /// 调用函数
+    ///
+    /// # Safety
+    ///
+    /// 取决于被调用的函数
+    unsafe fn call(self, args: Self::Args) -> Self::Ret;
+}
+
+macro_rules! impl_fnptr {
+    ($($arg:ident), *) => {
+        impl_fnptr!(@abi "C" $($arg),*);
+        impl_fnptr!(@abi "system" $($arg),*);
+    };
+    (@abi $abi:literal $($arg:ident), *) => {
+        impl_fnptr!(@impl (unsafe extern $abi fn($($arg),*) -> R) $($arg),*);
+        impl_fnptr!(@impl (extern $abi fn($($arg),*) -> R) $($arg),*);
+    };
+    (@impl ($ty:ty) $($arg:ident), *) => {
+        impl<R, $($arg),*> FnPtr for $ty {
+            type Args = ($($arg,)*);
+            type Ret = R;
+
+            unsafe fn from_ptr(ptr: *const fn()) -> Self {
+                mem::transmute_copy(&ptr)
+            }
+
+            #[allow(non_snake_case, clippy::unused_unit)]
+            unsafe fn call(self, ($($arg,)*): Self::Args) -> R {
+                (self)($($arg),*)
+            }
+        }
+    };
+}
+
+impl_fnptr!();
+impl_fnptr!(A);
+impl_fnptr!(A, B);
+impl_fnptr!(A, B, C);
+impl_fnptr!(A, B, C, D);
+impl_fnptr!(A, B, C, D, E);
+impl_fnptr!(A, B, C, D, E, F);
+impl_fnptr!(A, B, C, D, E, F, G);
+impl_fnptr!(A, B, C, D, E, F, G, H);
+impl_fnptr!(A, B, C, D, E, F, G, H, I);
+impl_fnptr!(A, B, C, D, E, F, G, H, I, J);
+impl_fnptr!(A, B, C, D, E, F, G, H, I, J, K);
+impl_fnptr!(A, B, C, D, E, F, G, H, I, J, K, L);
+
+/// 持有所在库的引用的函数指针, 参见 `Func::to_extern_c`
+///
+/// 可以解引用为 F, 在本值被 drop 之前函数所在的库都不会被卸载.
+/// 将解引用得到的指针传给其他代码时, 需要自行保证本值活得比它们更久
+#[derive(Debug, Clone)]
+pub struct BoundFn<F> {
+    func: F,
+    #[cfg(feature = "loader")]
+    lib: Option<Library>,
+}
+
+impl<F: FnPtr> BoundFn<F> {
+    pub(crate) unsafe fn new(func: &Func) -> Self {
+        Self {
+            func: F::from_ptr(func.func),
+            #[cfg(feature = "loader")]
+            lib: func.lib.clone(),
+        }
+    }
+
+    /// 函数所在的库, 由 `Func::from_raw` 等创建时为 `None`
+    #[cfg(feature = "loader")]
+    pub fn library(&self) -> Option<&Library> {
+        self.lib.as_ref()
+    }
+}
+
+impl<F> Deref for BoundFn<F> {
+    type Target = F;
+
+    fn deref(&self) -> &F {
+        &self.func
+    }
+}
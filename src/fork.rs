@@ -0,0 +1,206 @@
+//! 在子进程中调用, 参见 `Func::call_in_fork`
+
+use std::convert::TryInto;
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::os::raw::c_int;
+use std::panic::{self, AssertUnwindSafe};
+
+use crate::{errno, CallError, Convention, Func, RetValues};
+
+/// `Func::call_in_fork` 中子进程的结局
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+#[non_exhaustive]
+pub enum ForkOutcome {
+    /// 调用正常返回
+    Returned {
+        /// 调用的返回值, 同时也会写回 `Func`
+        ret: RetValues,
+        /// 调用刚结束时的 errno
+        errno: i32,
+    },
+    /// 子进程在调用中被信号终止, 如访问空指针时的 SIGSEGV
+    Crashed {
+        /// 终止子进程的信号
+        signal: i32,
+    },
+    /// 被调用者没有返回, 而是通过 `exit` 等直接结束了子进程
+    Exited {
+        /// 子进程的退出码
+        code: i32,
+    },
+}
+
+/// `Func::call_in_fork` 没能完成调用
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ForkError {
+    /// 调用前的检查没有通过, 没有创建子进程
+    Call(CallError),
+    /// 创建管道或子进程, 或者等待子进程时出错
+    Io(io::Error),
+}
+
+impl fmt::Display for ForkError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ForkError::Call(e) => e.fmt(f),
+            ForkError::Io(e) => write!(f, "failed to run the call in a child process: {}", e),
+        }
+    }
+}
+
+impl Error for ForkError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ForkError::Call(e) => Some(e),
+            ForkError::Io(e) => Some(e),
+        }
+    }
+}
+
+impl From<CallError> for ForkError {
+    fn from(e: CallError) -> Self {
+        ForkError::Call(e)
+    }
+}
+
+extern "C" {
+    fn fork() -> c_int;
+    fn pipe(fds: *mut c_int) -> c_int;
+    fn read(fd: c_int, buf: *mut u8, count: usize) -> isize;
+    fn write(fd: c_int, buf: *const u8, count: usize) -> isize;
+    fn close(fd: c_int) -> c_int;
+    fn waitpid(pid: c_int, status: *mut c_int, options: c_int) -> c_int;
+    fn _exit(status: c_int) -> !;
+}
+
+/// 子进程写回的返回值寄存器与 errno 的字节数
+const REPORT: usize = 8 * 3 + 4;
+
+/// 子进程中 panic 时的退出码, 与 Rust 程序 panic 后的退出码相同
+const PANICKED: c_int = 101;
+
+/// 在 EINTR 时重试, 其他错误转换为 `io::Error`
+fn retry(mut f: impl FnMut() -> isize) -> io::Result<isize> {
+    loop {
+        match f() {
+            -1 if io::Error::last_os_error().kind() == io::ErrorKind::Interrupted => {}
+            -1 => return Err(io::Error::last_os_error()),
+            n => return Ok(n),
+        }
+    }
+}
+
+impl Func {
+    /// 在 fork 出的子进程中以 conv 调用函数, 被调用者崩溃时不会影响当前进程
+    ///
+    /// 用于试探未知的函数指针等可能崩溃的调用. 子进程继承了当前进程的内存, 压入的参数与它们指向的缓冲区
+    /// 都可以直接使用; 返回值寄存器与调用刚结束时的 errno 通过管道传回, 并写回本 `Func`.
+    /// 但子进程中的一切副作用都不会反映到当前进程中: 被调用者写入指针参数的内容, 对全局变量的修改
+    /// 以及分配的内存都随子进程一同消失. 与 `try_call` 相同的检查在 fork 之前进行
+    ///
+    /// ```
+    /// use funcall::{Convention, ForkOutcome, Func};
+    /// unsafe extern "C" fn read(p: *const i32) -> i32 {
+    ///     *p
+    /// }
+    ///
+    /// let mut func = Func::from_raw(read as *const fn());
+    /// func.push(std::ptr::null::<i32>());
+    /// let outcome = unsafe { func.call_in_fork(Convention::Cdecl) }.unwrap();
+    /// assert!(matches!(outcome, ForkOutcome::Crashed { .. }));
+    /// ```
+    ///
+    /// # Safety
+    ///
+    /// 同 `try_call`. 当前进程有多个线程时, 子进程中只有调用的线程, 其他线程持有的锁永远不会被释放,
+    /// 被调用者 (以及 `set_observer` 设置的观察者) 不能依赖这样的锁
+    pub unsafe fn call_in_fork(&mut self, conv: Convention) -> Result<ForkOutcome, ForkError> {
+        let backend = conv.backend().ok_or_else(|| CallError::unsupported(conv))?;
+        self.check_call(conv, backend)?;
+
+        let mut fds = [0; 2];
+        if pipe(fds.as_mut_ptr()) == -1 {
+            return Err(ForkError::Io(io::Error::last_os_error()));
+        }
+        let [reader, writer] = fds;
+        let pid = fork();
+        if pid == -1 {
+            let e = io::Error::last_os_error();
+            close(reader);
+            close(writer);
+            return Err(ForkError::Io(e));
+        }
+        if pid == 0 {
+            close(reader);
+            let called = panic::catch_unwind(AssertUnwindSafe(|| {
+                errno::clear();
+                self.call_backend(conv, backend);
+                errno::get()
+            }));
+            let errno = match called {
+                Ok(errno) => errno,
+                Err(_) => _exit(PANICKED),
+            };
+            let mut report = [0u8; REPORT];
+            report[..8].copy_from_slice(&(self.ret.low as u64).to_ne_bytes());
+            report[8..16].copy_from_slice(&(self.ret.high as u64).to_ne_bytes());
+            report[16..24].copy_from_slice(&self.ret.float.to_bits().to_ne_bytes());
+            report[24..].copy_from_slice(&errno.to_ne_bytes());
+            // 父进程读不到完整的结果时按退出码报告, 写入失败也无法做得更好
+            let mut done = 0;
+            while done < REPORT {
+                match retry(|| write(writer, report[done..].as_ptr(), REPORT - done)) {
+                    Ok(n) if n > 0 => done += n as usize,
+                    _ => _exit(1),
+                }
+            }
+            _exit(0);
+        }
+
+        close(writer);
+        let mut report = [0u8; REPORT];
+        let mut got = 0;
+        let mut failed = None;
+        while got < REPORT {
+            match retry(|| read(reader, report[got..].as_mut_ptr(), REPORT - got)) {
+                Ok(0) => break,
+                Ok(n) => got += n as usize,
+                Err(e) => {
+                    failed = Some(e);
+                    break;
+                }
+            }
+        }
+        close(reader);
+        let mut status = 0;
+        retry(|| waitpid(pid, &mut status, 0) as isize).map_err(ForkError::Io)?;
+        if let Some(e) = failed {
+            return Err(ForkError::Io(e));
+        }
+
+        let signal = status & 0x7f;
+        if got == REPORT {
+            let word = |i: usize| u64::from_ne_bytes(report[i * 8..i * 8 + 8].try_into().unwrap());
+            let ret = RetValues {
+                low: word(0) as usize,
+                high: word(1) as usize,
+                float: f64::from_bits(word(2)),
+            };
+            self.ret = ret;
+            self.called = true;
+            Ok(ForkOutcome::Returned {
+                ret,
+                errno: i32::from_ne_bytes(report[24..].try_into().unwrap()),
+            })
+        } else if signal != 0 && signal != 0x7f {
+            Ok(ForkOutcome::Crashed { signal })
+        } else {
+            Ok(ForkOutcome::Exited {
+                code: (status >> 8) & 0xff,
+            })
+        }
+    }
+}
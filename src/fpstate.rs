@@ -0,0 +1,98 @@
+//! 调用前后保存与恢复浮点控制状态, 参见 `Func::protect_fp_state`
+
+// 不支持任何调用约定的平台上不会发出调用
+#![cfg_attr(
+    not(any(
+        target_arch = "x86",
+        all(target_arch = "x86_64", any(target_os = "linux", windows)),
+        all(target_arch = "loongarch64", target_os = "linux"),
+        feature = "libffi"
+    )),
+    allow(dead_code)
+)]
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64", target_arch = "aarch64"))]
+use core::arch::asm;
+
+use crate::Func;
+
+/// 浮点控制状态, 即 x87 控制字与 MXCSR, aarch64 下为 FPCR
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct FpState {
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    control: u16,
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    mxcsr: u32,
+    #[cfg(target_arch = "aarch64")]
+    fpcr: u64,
+}
+
+impl FpState {
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    fn current() -> Self {
+        let (mut control, mut mxcsr) = (0u16, 0u32);
+        unsafe {
+            asm!(
+                "fnstcw word ptr [{}]",
+                "stmxcsr dword ptr [{}]",
+                in(reg) &mut control,
+                in(reg) &mut mxcsr,
+                options(nostack, preserves_flags),
+            );
+        }
+        FpState { control, mxcsr }
+    }
+
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    fn apply(self) {
+        unsafe {
+            asm!(
+                "fldcw word ptr [{}]",
+                "ldmxcsr dword ptr [{}]",
+                in(reg) &self.control,
+                in(reg) &self.mxcsr,
+                options(nostack, preserves_flags, readonly),
+            );
+        }
+    }
+
+    #[cfg(target_arch = "aarch64")]
+    fn current() -> Self {
+        let fpcr: u64;
+        unsafe { asm!("mrs {}, fpcr", out(reg) fpcr, options(nomem, nostack, preserves_flags)) };
+        FpState { fpcr }
+    }
+
+    #[cfg(target_arch = "aarch64")]
+    fn apply(self) {
+        unsafe {
+            asm!("msr fpcr, {}", in(reg) self.fpcr, options(nomem, nostack, preserves_flags))
+        };
+    }
+
+    #[cfg(not(any(target_arch = "x86", target_arch = "x86_64", target_arch = "aarch64")))]
+    fn current() -> Self {
+        FpState {}
+    }
+
+    #[cfg(not(any(target_arch = "x86", target_arch = "x86_64", target_arch = "aarch64")))]
+    fn apply(self) {}
+}
+
+/// 开启了 `protect_fp_state` 时保存调用前的浮点控制状态
+#[inline]
+pub(crate) fn save(func: &Func) -> Option<FpState> {
+    if func.protect_fp {
+        Some(FpState::current())
+    } else {
+        None
+    }
+}
+
+/// 恢复 `save` 保存的浮点控制状态
+#[inline]
+pub(crate) fn restore(saved: Option<FpState>) {
+    if let Some(state) = saved {
+        state.apply();
+    }
+}
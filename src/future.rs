@@ -0,0 +1,129 @@
+//! 在后台线程中发出的调用, 参见 `Func::call_async`
+
+use std::future::Future;
+use std::panic;
+#[cfg(not(feature = "tokio"))]
+use std::panic::AssertUnwindSafe;
+use std::pin::Pin;
+#[cfg(not(feature = "tokio"))]
+use std::sync::{Arc, Mutex};
+#[cfg(not(feature = "tokio"))]
+use std::task::Waker;
+use std::task::{Context, Poll};
+#[cfg(not(feature = "tokio"))]
+use std::thread;
+
+use crate::{CallError, Convention, Func, RetValues};
+
+/// `CallFuture` 的结果, 调用成功时同时交还 `Func` 以便再次使用
+pub type CallResult = Result<(RetValues, Func), CallError>;
+
+/// 调用线程与 `CallFuture` 之间共享的状态
+#[cfg(not(feature = "tokio"))]
+#[derive(Default)]
+struct Shared {
+    /// 被调用者 panic 时保存 panic 的内容, 在 `poll` 中继续传播
+    done: Option<thread::Result<CallResult>>,
+    waker: Option<Waker>,
+}
+
+/// `Func::call_async` 返回的 `Future`
+///
+/// 调用在创建时就已经开始, drop 它不会取消调用: 调用仍会在后台线程中运行到结束, 其结果与 `Func` 随之被丢弃
+pub struct CallFuture {
+    #[cfg(not(feature = "tokio"))]
+    shared: Arc<Mutex<Shared>>,
+    #[cfg(feature = "tokio")]
+    handle: tokio::task::JoinHandle<CallResult>,
+}
+
+impl Future for CallFuture {
+    type Output = CallResult;
+
+    /// # Panics
+    ///
+    /// 被调用者 panic 时在此继续传播
+    #[cfg(not(feature = "tokio"))]
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<CallResult> {
+        let mut shared = self.shared.lock().unwrap();
+        match shared.done.take() {
+            Some(Ok(result)) => Poll::Ready(result),
+            Some(Err(payload)) => panic::resume_unwind(payload),
+            None => {
+                shared.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+
+    /// # Panics
+    ///
+    /// 被调用者 panic 时在此继续传播, 运行时在调用开始前关闭时同样 panic
+    #[cfg(feature = "tokio")]
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<CallResult> {
+        match Pin::new(&mut self.handle).poll(cx) {
+            Poll::Ready(Ok(result)) => Poll::Ready(result),
+            Poll::Ready(Err(e)) if e.is_panic() => panic::resume_unwind(e.into_panic()),
+            Poll::Ready(Err(e)) => panic!("the call did not run: {}", e),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl Func {
+    /// 将本 `Func` 移到一个新的线程中以 `try_call` 调用, 返回在调用结束时完成的 `Future`
+    ///
+    /// 用于会阻塞较长时间的函数, 不会占用异步运行时的工作线程, 也不依赖于特定的运行时.
+    /// 开启 `tokio` feature 时则通过 `tokio::task::spawn_blocking` 在 tokio 的阻塞线程池中调用, 不再每次创建线程.
+    /// 结果中的返回值与 `invoke` 的相同, 同时交还 `Func` 以便再次调用; 检查未通过时 `Func` 随错误一起被丢弃.
+    ///
+    /// 调用在本方法返回前就已开始. 取消 (drop) 返回的 `Future` 不会中断调用, 调用仍会运行到结束,
+    /// 因此指针参数指向的数据需要在调用结束前一直有效
+    ///
+    /// ```
+    /// use funcall::{CallError, Convention, Func};
+    ///
+    /// async fn sleep_twice(mut func: Func) -> Result<i32, CallError> {
+    ///     func.push(100_000u32);
+    ///     let (ret, func) = unsafe { func.call_async(Convention::Cdecl) }.await?;
+    ///     // 参数仍保留在交还的 Func 中
+    ///     let (again, _) = unsafe { func.call_async(Convention::Cdecl) }.await?;
+    ///     Ok(ret.as_i32() + again.as_i32())
+    /// }
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// 无法创建线程时 panic. 开启 `tokio` feature 时不在 tokio 运行时中调用也会 panic
+    ///
+    /// # Safety
+    ///
+    /// 同 `try_call`. 函数会在另一个线程中执行, 依赖线程局部状态的函数可能出错
+    pub unsafe fn call_async(mut self, conv: Convention) -> CallFuture {
+        #[cfg(feature = "tokio")]
+        return CallFuture {
+            handle: tokio::task::spawn_blocking(move || {
+                self.try_call(conv).map(|()| (self.ret(), self))
+            }),
+        };
+        #[cfg(not(feature = "tokio"))]
+        {
+            let shared = Arc::new(Mutex::new(Shared::default()));
+            let state = shared.clone();
+            thread::Builder::new()
+                .name("funcall-async".into())
+                .spawn(move || {
+                    let done = panic::catch_unwind(AssertUnwindSafe(|| {
+                        self.try_call(conv).map(|()| (self.ret(), self))
+                    }));
+                    let mut state = state.lock().unwrap();
+                    state.done = Some(done);
+                    if let Some(waker) = state.waker.take() {
+                        waker.wake();
+                    }
+                })
+                .expect("failed to spawn a thread for the call");
+            CallFuture { shared }
+        }
+    }
+}
@@ -0,0 +1,683 @@
+//! C 头文件的解析, 参见 `parse`
+//!
+//! 只处理函数声明, typedef 与预处理指令中可以确定结果的部分, 其余内容都会被跳过并记录在 `Header::diagnostics` 中
+
+use alloc::borrow::ToOwned;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+
+use crate::signature::{Parser, TypeDef};
+use crate::Signature;
+#[cfg(feature = "loader")]
+use crate::{Error, Func, Library, Result};
+
+/// `parse` 的结果
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Header {
+    /// 声明的函数原型, 以函数名为键
+    pub functions: BTreeMap<String, Signature>,
+    /// 被跳过的内容
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl Header {
+    /// 名为 name 的函数的原型
+    pub fn get(&self, name: &str) -> Option<&Signature> {
+        self.functions.get(name)
+    }
+
+    /// 从 lib 中查找声明过的函数 name, 并附加其原型进入检查模式, 参见 `Func::set_signature`
+    #[cfg(feature = "loader")]
+    pub fn func(&self, lib: &Library, name: &str) -> Result<Func> {
+        let sig = self
+            .get(name)
+            .ok_or_else(|| Error::NotFound(format!("`{}` is not declared in the header", name)))?;
+        let mut func = lib.get(name)?;
+        func.set_signature(sig.clone());
+        Ok(func)
+    }
+}
+
+/// 解析时被跳过的内容
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Diagnostic {
+    /// 所在的行, 从 1 开始
+    pub line: usize,
+    /// 被跳过的原因
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+/// 解析 C 头文件中的函数声明
+///
+/// 类型的支持范围同 `Signature::parse`, 此外还可以使用头文件中由 typedef 定义的类型名.
+/// 预处理时展开无参数的宏, 按当前目标平台预定义 `_WIN32`, `__linux__`, `__x86_64__` 等宏, 不定义 `__cplusplus`.
+/// 无法计算的 `#if` 会跳过其所有分支, `#include` 不会被读取.
+/// 以值传递结构体的函数, 函数定义, 全局变量以及不是 cdecl 的函数都会被跳过. 解析不会失败,
+/// 所有被跳过的内容都记录在 `Header::diagnostics` 中
+///
+/// ```
+/// let header = funcall::headers::parse(
+///     r#"
+///     #ifndef PLUGIN_H
+///     #define PLUGIN_H
+///     typedef unsigned long plugin_id;
+///     typedef struct plugin plugin;
+///     plugin *plugin_open(plugin_id id);
+///     int plugin_area(struct rect r);
+///     #endif
+///     "#,
+/// );
+/// let open = header.get("plugin_open").unwrap();
+/// assert_eq!(open.params, [funcall::CType::ULong]);
+/// assert!(header.get("plugin_area").is_none());
+/// assert_eq!(header.diagnostics[0].line, 7);
+/// ```
+pub fn parse(text: &str) -> Header {
+    let mut state = State {
+        header: Header::default(),
+        macros: predefined(),
+        typedefs: BTreeMap::new(),
+    };
+    let tokens = state.preprocess(&strip_comments(text));
+    state.declarations(&tokens);
+    state.header
+}
+
+/// `#if` 的每个分支的状态
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Branch {
+    /// 当前分支有效
+    Active,
+    /// 之前的分支都无效, 之后的 `#elif` 或 `#else` 可能有效
+    Pending,
+    /// 已经有分支有效, 外层无效或条件无法计算, 直到 `#endif` 都无效
+    Skipped,
+}
+
+struct State {
+    header: Header,
+    /// 已定义的宏, 带参数的宏不会被展开, 其值为 `None`
+    macros: BTreeMap<String, Option<String>>,
+    typedefs: BTreeMap<String, TypeDef>,
+}
+
+impl State {
+    fn diagnose(&mut self, line: usize, message: String) {
+        self.header.diagnostics.push(Diagnostic { line, message });
+    }
+
+    /// 处理预处理指令并展开宏, 返回有效部分的 token 及其所在的行
+    fn preprocess(&mut self, text: &str) -> Vec<(usize, String)> {
+        let mut tokens = Vec::new();
+        let mut branches: Vec<Branch> = Vec::new();
+        let mut lines = text.lines().enumerate();
+        let mut last = 1;
+        while let Some((index, line)) = lines.next() {
+            let line_no = index + 1;
+            last = line_no;
+            // 以 `\` 结尾的行与下一行相连
+            let mut line = line.to_owned();
+            while line.ends_with('\\') {
+                line.pop();
+                match lines.next() {
+                    Some((_, next)) => line.push_str(next),
+                    None => break,
+                }
+            }
+            let active = branches.iter().all(|b| *b == Branch::Active);
+            let directive = match line.trim_start().strip_prefix('#') {
+                Some(directive) => directive.trim(),
+                None => {
+                    if active {
+                        for token in lex(&line) {
+                            self.expand(token, line_no, &mut tokens, &mut Vec::new());
+                        }
+                    }
+                    continue;
+                }
+            };
+            let name_len = directive
+                .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                .unwrap_or(directive.len());
+            let (name, rest) = directive.split_at(name_len);
+            let rest = rest.trim();
+            match name {
+                "if" | "ifdef" | "ifndef" => {
+                    let branch = if active {
+                        self.condition(line_no, name, rest)
+                    } else {
+                        Branch::Skipped
+                    };
+                    branches.push(branch);
+                }
+                "elif" | "else" | "endif" if branches.is_empty() => {
+                    self.diagnose(line_no, format!("`#{}` without `#if`", name));
+                }
+                "elif" | "else" => {
+                    let top = branches.len() - 1;
+                    branches[top] = match branches[top] {
+                        Branch::Pending if name == "elif" => self.condition(line_no, "if", rest),
+                        Branch::Pending => Branch::Active,
+                        Branch::Active | Branch::Skipped => Branch::Skipped,
+                    };
+                }
+                "endif" => {
+                    branches.pop();
+                }
+                _ if !active => (),
+                "define" => self.define(line_no, rest),
+                "undef" => {
+                    self.macros.remove(rest);
+                }
+                "include" => self.diagnose(line_no, format!("`#include {}` is not followed", rest)),
+                "pragma" if rest == "once" => (),
+                _ => self.diagnose(line_no, format!("ignored `#{}`", directive)),
+            }
+        }
+        if !branches.is_empty() {
+            self.diagnose(last, "missing `#endif`".into());
+        }
+        tokens
+    }
+
+    /// 计算 `#if`, `#ifdef` 或 `#ifndef` 的条件
+    fn condition(&mut self, line: usize, directive: &str, expr: &str) -> Branch {
+        let value = match directive {
+            "ifdef" => Some(self.macros.contains_key(expr)),
+            "ifndef" => Some(!self.macros.contains_key(expr)),
+            _ => Expr::new(expr, &self.macros, 0).eval(),
+        };
+        match value {
+            Some(true) => Branch::Active,
+            Some(false) => Branch::Pending,
+            None => {
+                self.diagnose(
+                    line,
+                    format!(
+                        "cannot evaluate `#{} {}`, skipping the whole conditional",
+                        directive, expr
+                    ),
+                );
+                Branch::Skipped
+            }
+        }
+    }
+
+    fn define(&mut self, line: usize, rest: &str) {
+        let name_len = rest
+            .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+            .unwrap_or(rest.len());
+        let (name, body) = rest.split_at(name_len);
+        if body.starts_with('(') {
+            self.diagnose(
+                line,
+                format!("function-like macro `{}` is not expanded", name),
+            );
+            self.macros.insert(name.to_owned(), None);
+        } else {
+            self.macros
+                .insert(name.to_owned(), Some(body.trim().to_owned()));
+        }
+    }
+
+    /// 展开宏后将 token 追加到 out, expanding 为正在展开的宏, 用于防止无限递归
+    fn expand(
+        &self,
+        token: String,
+        line: usize,
+        out: &mut Vec<(usize, String)>,
+        expanding: &mut Vec<String>,
+    ) {
+        match self.macros.get(&token) {
+            Some(Some(body)) if !expanding.contains(&token) => {
+                let body = lex(body);
+                expanding.push(token);
+                for token in body {
+                    self.expand(token, line, out, expanding);
+                }
+                expanding.pop();
+            }
+            _ => out.push((line, token)),
+        }
+    }
+
+    /// 将 token 按 `;` 与顶层的 `{}` 分为语句并逐条处理
+    fn declarations(&mut self, tokens: &[(usize, String)]) {
+        let mut stmt: Vec<&str> = Vec::new();
+        let mut line = 0;
+        let mut extern_c = 0;
+        let mut i = 0;
+        while i < tokens.len() {
+            let (token_line, token) = &tokens[i];
+            if stmt.is_empty() {
+                line = *token_line;
+            }
+            match token.as_str() {
+                ";" => {
+                    self.statement(line, &stmt);
+                    stmt.clear();
+                }
+                "{" if stmt == ["extern", "\"C\""] => {
+                    extern_c += 1;
+                    stmt.clear();
+                }
+                "{" => {
+                    let mut depth = 0;
+                    while i < tokens.len() {
+                        match tokens[i].1.as_str() {
+                            "{" => depth += 1,
+                            "}" => depth -= 1,
+                            _ => (),
+                        }
+                        if depth == 0 {
+                            break;
+                        }
+                        i += 1;
+                    }
+                    let definition = match stmt.iter().find(|t| **t != "typedef") {
+                        Some(t) => ["struct", "union", "enum"].contains(t) && !stmt.contains(&"("),
+                        None => false,
+                    };
+                    if definition {
+                        // 结构体只能通过指针使用, 不需要知道其成员
+                        stmt.push("{}");
+                    } else {
+                        let message = match function_name(&stmt) {
+                            Some(name) => format!("ignored the definition of `{}`", name),
+                            None => "ignored a block".into(),
+                        };
+                        self.diagnose(line, message);
+                        stmt.clear();
+                    }
+                }
+                "}" if extern_c > 0 && stmt.is_empty() => extern_c -= 1,
+                token => stmt.push(token),
+            }
+            i += 1;
+        }
+        if !stmt.is_empty() {
+            self.diagnose(line, "missing `;` at the end of the header".into());
+        }
+    }
+
+    fn statement(&mut self, line: usize, stmt: &[&str]) {
+        let mut tokens = Vec::with_capacity(stmt.len());
+        let mut i = 0;
+        while i < stmt.len() {
+            match stmt[i] {
+                "__attribute__" | "__declspec" => {
+                    let end = group_end(stmt, i + 1);
+                    if let Some(conv) = stmt[i..end].iter().find(|t| is_convention(t)) {
+                        self.not_cdecl(line, &stmt[end..], conv);
+                        return;
+                    }
+                    i = end;
+                    continue;
+                }
+                "__cdecl" => (),
+                token if is_convention(token) => {
+                    self.not_cdecl(line, &stmt[i + 1..], token);
+                    return;
+                }
+                token => tokens.push(token),
+            }
+            i += 1;
+        }
+        // `extern "C" int foo(void);`
+        if tokens.first() == Some(&"extern") {
+            tokens.remove(0);
+            if tokens.first().is_some_and(|t| t.starts_with('"')) {
+                tokens.remove(0);
+            }
+        }
+        let first = match tokens.first() {
+            Some(first) => *first,
+            None => return,
+        };
+
+        if first == "typedef" {
+            let mut tokens = tokens[1..].to_vec();
+            if let Some(block) = tokens.iter().position(|t| *t == "{}") {
+                tokens.remove(block);
+                // 匿名的结构体或枚举
+                if block == 1 {
+                    tokens.insert(1, "__anonymous");
+                }
+            }
+            let text = tokens.join(" ");
+            match Parser::with_typedefs(&text, &self.typedefs).and_then(Parser::typedef) {
+                Ok((name, ty)) => {
+                    self.typedefs.insert(name, ty);
+                }
+                Err(e) => self.diagnose(line, format!("ignored `typedef {}`: {}", text, e)),
+            }
+        } else if ["struct", "union", "enum"].contains(&first) && !tokens.contains(&"(") {
+            // 类型的定义或前向声明, 之后还有名字的是变量
+            let rest = match tokens.iter().position(|t| *t == "{}") {
+                Some(block) => &tokens[block + 1..],
+                None => &tokens[2.min(tokens.len())..],
+            };
+            if let Some(name) = rest.iter().rev().find(|t| is_identifier(t)) {
+                self.diagnose(line, format!("ignored variable `{}`", name));
+            }
+        } else if let Some(name) = function_name(&tokens) {
+            let text = tokens.join(" ");
+            match Parser::with_typedefs(&text, &self.typedefs).and_then(Parser::signature) {
+                Ok(sig) => match self.header.functions.get(&sig.name) {
+                    Some(prev) if *prev != sig => self.diagnose(
+                        line,
+                        format!(
+                            "conflicting declaration of `{}`, keeping the first",
+                            sig.name
+                        ),
+                    ),
+                    Some(_) => (),
+                    None => {
+                        self.header.functions.insert(sig.name.clone(), sig);
+                    }
+                },
+                Err(e) => self.diagnose(line, format!("ignored `{}`: {}", name, e)),
+            }
+        } else {
+            let name = tokens
+                .iter()
+                .rev()
+                .find(|t| is_identifier(t))
+                .unwrap_or(&first);
+            self.diagnose(line, format!("ignored variable `{}`", name));
+        }
+    }
+
+    /// rest 为调用约定之后的部分
+    fn not_cdecl(&mut self, line: usize, rest: &[&str], conv: &str) {
+        let name = function_name(rest).unwrap_or_default();
+        self.diagnose(
+            line,
+            format!("ignored `{}`: calling convention `{}`", name, conv),
+        );
+    }
+}
+
+/// 按当前目标平台预定义的宏
+fn predefined() -> BTreeMap<String, Option<String>> {
+    let macros = [
+        ("__STDC__", true),
+        ("_WIN32", cfg!(windows)),
+        ("_WIN64", cfg!(all(windows, target_pointer_width = "64"))),
+        ("__linux__", cfg!(target_os = "linux")),
+        ("__APPLE__", cfg!(target_vendor = "apple")),
+        ("__unix__", cfg!(all(unix, not(target_vendor = "apple")))),
+        ("__x86_64__", cfg!(target_arch = "x86_64")),
+        ("__i386__", cfg!(target_arch = "x86")),
+        ("__aarch64__", cfg!(target_arch = "aarch64")),
+        (
+            "__LP64__",
+            cfg!(all(target_pointer_width = "64", not(windows))),
+        ),
+    ];
+    macros
+        .iter()
+        .filter(|(_, defined)| *defined)
+        .map(|(name, _)| ((*name).to_owned(), Some("1".to_owned())))
+        .collect()
+}
+
+/// 去掉注释, 保留其中的换行以便计算行号
+fn strip_comments(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '/' if chars.peek() == Some(&'/') => {
+                while chars.peek().is_some_and(|c| *c != '\n') {
+                    chars.next();
+                }
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut prev = ' ';
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        out.push('\n');
+                    }
+                    if prev == '*' && c == '/' {
+                        break;
+                    }
+                    prev = c;
+                }
+                out.push(' ');
+            }
+            '"' | '\'' => {
+                out.push(c);
+                while let Some(next) = chars.next() {
+                    out.push(next);
+                    if next == '\\' {
+                        out.extend(chars.next());
+                    } else if next == c || next == '\n' {
+                        break;
+                    }
+                }
+            }
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// 由多个字符组成的运算符
+const PUNCTS: &[&str] = &["...", "&&", "||", "==", "!=", "<=", ">=", "<<", ">>"];
+
+fn lex(line: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut rest = line;
+    while let Some(c) = rest.chars().next() {
+        let len = if c.is_whitespace() {
+            rest = &rest[c.len_utf8()..];
+            continue;
+        } else if c.is_ascii_alphanumeric() || c == '_' {
+            rest.find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                .unwrap_or(rest.len())
+        } else if c == '"' || c == '\'' {
+            let mut escaped = false;
+            rest[1..]
+                .find(|next: char| {
+                    let end = next == c && !escaped;
+                    escaped = next == '\\' && !escaped;
+                    end
+                })
+                .map_or(rest.len(), |end| end + 2)
+        } else {
+            PUNCTS
+                .iter()
+                .find(|p| rest.starts_with(**p))
+                .map_or(c.len_utf8(), |p| p.len())
+        };
+        tokens.push(rest[..len].to_owned());
+        rest = &rest[len..];
+    }
+    tokens
+}
+
+/// `(...)` 之后的位置, start 处不是 `(` 时即为 start
+fn group_end(tokens: &[&str], start: usize) -> usize {
+    let mut depth = 0;
+    for (i, token) in tokens.iter().enumerate().skip(start) {
+        match *token {
+            "(" => depth += 1,
+            ")" => depth -= 1,
+            _ if depth == 0 => return i,
+            _ => (),
+        }
+        if depth == 0 {
+            return i + 1;
+        }
+    }
+    tokens.len()
+}
+
+/// 第一个 `(` 之前的名字
+fn function_name<'a>(tokens: &[&'a str]) -> Option<&'a str> {
+    let paren = tokens.iter().position(|t| *t == "(")?;
+    Some(paren.checked_sub(1).map_or("", |i| tokens[i]))
+}
+
+fn is_identifier(token: &str) -> bool {
+    token.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+}
+
+/// 改变调用约定的关键字与属性
+fn is_convention(token: &str) -> bool {
+    matches!(
+        token.trim_matches('_'),
+        "stdcall" | "fastcall" | "thiscall" | "vectorcall" | "regparm" | "ms_abi" | "sysv_abi"
+    )
+}
+
+/// `#if` 的条件表达式, 只支持整数, `defined`, 逻辑, 比较运算与括号
+///
+/// 各方法在语法错误时返回 `None`. 无法知道编译器会预定义哪些宏, 未定义的标识符不像 C 中那样视为 0,
+/// 而是视为未知的值 (内层的 `None`), 只有 `&&` 与 `||` 的另一侧能确定结果时才不影响整个表达式
+struct Expr<'a> {
+    tokens: Vec<String>,
+    pos: usize,
+    macros: &'a BTreeMap<String, Option<String>>,
+    /// 宏的嵌套层数, 防止互相引用的宏无限展开
+    depth: usize,
+}
+
+/// 未知时为 `None`
+type Value = Option<i64>;
+
+impl<'a> Expr<'a> {
+    fn new(expr: &str, macros: &'a BTreeMap<String, Option<String>>, depth: usize) -> Self {
+        Self {
+            tokens: lex(expr),
+            pos: 0,
+            macros,
+            depth,
+        }
+    }
+
+    /// 无法计算时返回 `None`
+    fn eval(self) -> Option<bool> {
+        self.value()?.map(|value| value != 0)
+    }
+
+    fn value(mut self) -> Option<Value> {
+        let value = self.or()?;
+        (self.pos == self.tokens.len()).then_some(value)
+    }
+
+    fn peek(&self) -> Option<&str> {
+        self.tokens.get(self.pos).map(String::as_str)
+    }
+
+    fn eat(&mut self, token: &str) -> bool {
+        let eaten = self.peek() == Some(token);
+        if eaten {
+            self.pos += 1;
+        }
+        eaten
+    }
+
+    fn or(&mut self) -> Option<Value> {
+        let mut value = self.and()?;
+        while self.eat("||") {
+            let rhs = self.and()?;
+            value = match (value, rhs) {
+                (Some(a), _) if a != 0 => Some(1),
+                (_, Some(b)) if b != 0 => Some(1),
+                (Some(_), Some(_)) => Some(0),
+                _ => None,
+            };
+        }
+        Some(value)
+    }
+
+    fn and(&mut self) -> Option<Value> {
+        let mut value = self.compare()?;
+        while self.eat("&&") {
+            let rhs = self.compare()?;
+            value = match (value, rhs) {
+                (Some(0), _) | (_, Some(0)) => Some(0),
+                (Some(_), Some(_)) => Some(1),
+                _ => None,
+            };
+        }
+        Some(value)
+    }
+
+    fn compare(&mut self) -> Option<Value> {
+        let mut value = self.unary()?;
+        while let Some(op @ ("==" | "!=" | "<" | ">" | "<=" | ">=")) = self.peek() {
+            let op = op.to_owned();
+            self.pos += 1;
+            let rhs = self.unary()?;
+            value = value.zip(rhs).map(|(a, b)| {
+                i64::from(match op.as_str() {
+                    "==" => a == b,
+                    "!=" => a != b,
+                    "<" => a < b,
+                    ">" => a > b,
+                    "<=" => a <= b,
+                    _ => a >= b,
+                })
+            });
+        }
+        Some(value)
+    }
+
+    fn unary(&mut self) -> Option<Value> {
+        if self.eat("!") {
+            return Some(self.unary()?.map(|value| i64::from(value == 0)));
+        }
+        if self.eat("(") {
+            let value = self.or()?;
+            return self.eat(")").then_some(value);
+        }
+        let token = self.peek()?.to_owned();
+        self.pos += 1;
+        if token == "defined" {
+            let paren = self.eat("(");
+            let name = self.peek()?.to_owned();
+            self.pos += 1;
+            if paren && !self.eat(")") {
+                return None;
+            }
+            return Some(Some(i64::from(self.macros.contains_key(&name))));
+        }
+        if token.starts_with(|c: char| c.is_ascii_digit()) {
+            return number(&token).map(Some);
+        }
+        if !is_identifier(&token) {
+            return None;
+        }
+        match self.macros.get(&token) {
+            Some(Some(body)) if self.depth < 16 => Some(
+                Expr::new(body, self.macros, self.depth + 1)
+                    .value()
+                    .flatten(),
+            ),
+            _ => Some(None),
+        }
+    }
+}
+
+fn number(token: &str) -> Option<i64> {
+    let token = token.trim_end_matches(['u', 'U', 'l', 'L']);
+    match token
+        .strip_prefix("0x")
+        .or_else(|| token.strip_prefix("0X"))
+    {
+        Some(hex) => i64::from_str_radix(hex, 16).ok(),
+        None => token.parse().ok(),
+    }
+}
@@ -0,0 +1,152 @@
+//! 带内联存储的向量, 用于保存压入的参数
+
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+use core::fmt;
+use core::iter::FromIterator;
+use core::ops::{Deref, DerefMut};
+
+/// 不超过 N 个元素时存放在内联的数组中, 超出后整体移到堆上
+///
+/// 常见的调用只有寥寥几个参数, 这样压入参数时就不需要分配内存.
+/// 内联数组中未使用的部分始终为 `T::default()`, 参见 `padded`
+#[derive(Clone)]
+pub(crate) struct InlineVec<T, const N: usize> {
+    /// 内联存储的元素个数, 元素在堆上时不使用
+    len: usize,
+    inline: [T; N],
+    /// 不为空时所有元素都在这里
+    heap: Vec<T>,
+}
+
+impl<T: Copy + Default, const N: usize> InlineVec<T, N> {
+    pub(crate) fn new() -> Self {
+        Self {
+            len: 0,
+            inline: [T::default(); N],
+            heap: Vec::new(),
+        }
+    }
+
+    fn spilled(&self) -> bool {
+        !self.heap.is_empty()
+    }
+
+    pub(crate) fn push(&mut self, value: T) {
+        if self.spilled() {
+            self.heap.push(value);
+        } else if self.len < N {
+            self.inline[self.len] = value;
+            self.len += 1;
+        } else {
+            self.heap.reserve(N * 2);
+            self.heap.extend_from_slice(&self.inline[..self.len]);
+            self.heap.push(value);
+        }
+    }
+
+    pub(crate) fn extend_from_slice(&mut self, values: &[T]) {
+        for value in values {
+            self.push(*value);
+        }
+    }
+
+    pub(crate) fn truncate(&mut self, len: usize) {
+        if self.spilled() {
+            self.heap.truncate(len);
+            // 全部移除后回到内联存储, 其中还留着移到堆上之前的元素
+            if self.heap.is_empty() {
+                self.len = 0;
+                self.inline = [T::default(); N];
+            }
+        } else if len < self.len {
+            self.inline[len..self.len].fill(T::default());
+            self.len = len;
+        }
+    }
+
+    /// 元素都在内联数组中时返回整个数组, 有效元素之后的部分为 `T::default()`
+    #[cfg(all(target_arch = "x86_64", target_os = "linux"))]
+    #[cfg_attr(feature = "regcheck", allow(dead_code))]
+    pub(crate) fn padded(&self) -> Option<&[T; N]> {
+        if self.spilled() {
+            None
+        } else {
+            Some(&self.inline)
+        }
+    }
+
+    #[cfg(any(
+        all(target_arch = "x86_64", not(windows)),
+        target_arch = "s390x",
+        target_arch = "aarch64"
+    ))]
+    pub(crate) fn resize(&mut self, len: usize, value: T) {
+        self.truncate(len);
+        while self.len() < len {
+            self.push(value);
+        }
+    }
+}
+
+impl<T: Copy + Default, const N: usize> Default for InlineVec<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, const N: usize> Deref for InlineVec<T, N> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        if self.heap.is_empty() {
+            &self.inline[..self.len]
+        } else {
+            &self.heap
+        }
+    }
+}
+
+impl<T, const N: usize> DerefMut for InlineVec<T, N> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        if self.heap.is_empty() {
+            &mut self.inline[..self.len]
+        } else {
+            &mut self.heap
+        }
+    }
+}
+
+impl<T: Copy + Default, const N: usize> From<Vec<T>> for InlineVec<T, N> {
+    fn from(values: Vec<T>) -> Self {
+        values.into_iter().collect()
+    }
+}
+
+impl<T: Copy + Default, const N: usize> FromIterator<T> for InlineVec<T, N> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut vec = Self::new();
+        for value in iter {
+            vec.push(value);
+        }
+        vec
+    }
+}
+
+impl<T: fmt::Debug, const N: usize> fmt::Debug for InlineVec<T, N> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<T: PartialEq, const N: usize> PartialEq for InlineVec<T, N> {
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+impl<T: PartialOrd, const N: usize> PartialOrd for InlineVec<T, N> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        (**self).partial_cmp(&**other)
+    }
+}
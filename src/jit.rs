@@ -0,0 +1,243 @@
+//! 运行时生成的调用跳板
+//!
+//! 跳板从上下文中读出参数, 送入寄存器并复制栈上的参数, 调用目标函数后将返回值寄存器写回上下文.
+//! 跳板只与参数的布局有关, 即整数参数与浮点参数各有几个, 布局相同的调用共用同一个跳板
+
+use std::collections::BTreeMap;
+use std::io;
+use std::mem;
+use std::os::raw::{c_int, c_long, c_void};
+use std::ptr;
+use std::sync::{Arc, Mutex, Weak};
+
+use crate::{fpstate, observer, sanitize, Convention, Func};
+
+extern "C" {
+    fn mmap(
+        addr: *mut c_void,
+        len: usize,
+        prot: c_int,
+        flags: c_int,
+        fd: c_int,
+        offset: c_long,
+    ) -> *mut c_void;
+    fn mprotect(addr: *mut c_void, len: usize, prot: c_int) -> c_int;
+    fn munmap(addr: *mut c_void, len: usize) -> c_int;
+}
+
+const PROT_READ: c_int = 1;
+const PROT_WRITE: c_int = 2;
+const PROT_EXEC: c_int = 4;
+const MAP_PRIVATE: c_int = 2;
+const MAP_ANONYMOUS: c_int = 0x20;
+const PAGE_SIZE: usize = 4096;
+
+/// 跳板读写的上下文, 偏移量固定写在生成的代码中
+#[repr(C)]
+struct Context {
+    target: *const fn(),
+    args: *const usize,
+    fargs: *const f64,
+    low: usize,
+    high: usize,
+    float: f64,
+}
+
+/// 参数的布局
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+struct Layout {
+    /// 整数参数占用的字数, 超出 6 个的部分通过栈传递
+    ints: usize,
+    /// 浮点寄存器中的参数个数
+    floats: usize,
+}
+
+/// 只保存弱引用, 所有使用者都 drop 之后跳板即被释放
+static CACHE: Mutex<BTreeMap<Layout, Weak<Trampoline>>> = Mutex::new(BTreeMap::new());
+
+/// 为某一参数布局生成的调用跳板, 最后一个引用被 drop 时释放其内存
+#[derive(Debug)]
+pub struct Trampoline {
+    code: *mut u8,
+    size: usize,
+    layout: Layout,
+}
+
+// 跳板的内存在创建后只读, 可以在线程间共享
+unsafe impl Send for Trampoline {}
+unsafe impl Sync for Trampoline {}
+
+impl Trampoline {
+    /// 取得适用于 func 中已压入参数的跳板, 已有时复用
+    pub(crate) fn for_func(func: &Func) -> io::Result<Arc<Self>> {
+        let layout = Layout {
+            ints: func.args.len(),
+            floats: func.fargs.len(),
+        };
+        let mut cache = CACHE.lock().unwrap();
+        if let Some(trampoline) = cache.get(&layout).and_then(Weak::upgrade) {
+            return Ok(trampoline);
+        }
+        let trampoline = Arc::new(Self::new(layout)?);
+        cache.retain(|_, weak| weak.strong_count() > 0);
+        cache.insert(layout, Arc::downgrade(&trampoline));
+        Ok(trampoline)
+    }
+
+    /// 先以可写的方式映射内存写入代码, 再改为可执行
+    fn new(layout: Layout) -> io::Result<Self> {
+        let code = emit(layout);
+        let size = code.len().div_ceil(PAGE_SIZE) * PAGE_SIZE;
+        unsafe {
+            let map = mmap(
+                ptr::null_mut(),
+                size,
+                PROT_READ | PROT_WRITE,
+                MAP_PRIVATE | MAP_ANONYMOUS,
+                -1,
+                0,
+            );
+            if map as isize == -1 {
+                return Err(io::Error::last_os_error());
+            }
+            ptr::copy_nonoverlapping(code.as_ptr(), map as *mut u8, code.len());
+            if mprotect(map, size, PROT_READ | PROT_EXEC) != 0 {
+                let e = io::Error::last_os_error();
+                munmap(map, size);
+                return Err(e);
+            }
+            Ok(Self {
+                code: map as *mut u8,
+                size,
+                layout,
+            })
+        }
+    }
+
+    /// 跳板代码的起始地址
+    pub fn as_ptr(&self) -> *const u8 {
+        self.code
+    }
+
+    /// 映射的内存大小
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// 以 func 中已压入的参数调用函数, 参数的布局必须与生成跳板时的一致
+    pub(crate) unsafe fn call(&self, func: &mut Func, conv: Convention) {
+        debug_assert_eq!(func.args.len(), self.layout.ints);
+        debug_assert_eq!(func.fargs.len(), self.layout.floats);
+        let fp = fpstate::save(func);
+        let observed = observer::begin(func, conv);
+        func.called = true;
+        let mut ctx = Context {
+            target: func.func,
+            args: func.args.as_ptr(),
+            fargs: func.fargs.as_ptr(),
+            low: 0,
+            high: 0,
+            float: 0.0,
+        };
+        let entry: extern "C" fn(*mut Context) = mem::transmute(self.code);
+        entry(&mut ctx);
+        func.ret.low = ctx.low;
+        func.ret.high = ctx.high;
+        // float 返回值在 xmm0 的低 32 位
+        func.ret.float = func.float_ret(ctx.float, f32::from_bits(ctx.float.to_bits() as u32));
+        fpstate::restore(fp);
+        observer::end(observed, func, conv);
+    }
+}
+
+impl Drop for Trampoline {
+    fn drop(&mut self) {
+        unsafe {
+            munmap(self.code as *mut c_void, self.size);
+        }
+    }
+}
+
+/// 通过跳板调用函数
+pub(crate) unsafe fn call(func: &mut Func, conv: Convention) {
+    func.assert_stack_size();
+    sanitize::outgoing_args(func);
+    Trampoline::for_func(func)
+        .expect("failed to allocate a trampoline")
+        .call(func, conv);
+}
+
+/// 依次送入 rdi, rsi, rdx, rcx, r8, r9 的 `mov reg, [rax + disp32]`
+const INT_LOADS: [[u8; 3]; 6] = [
+    [0x48, 0x8b, 0xb8],
+    [0x48, 0x8b, 0xb0],
+    [0x48, 0x8b, 0x90],
+    [0x48, 0x8b, 0x88],
+    [0x4c, 0x8b, 0x80],
+    [0x4c, 0x8b, 0x88],
+];
+
+/// 生成 System V AMD64 下的跳板, 签名为 `extern "C" fn(*mut Context)`
+fn emit(layout: Layout) -> Vec<u8> {
+    let stack = layout.ints.saturating_sub(INT_LOADS.len());
+    // 入口处 rsp 模 16 余 8, 压入 rbp 与 rbx 并再减去 8 后对齐, 之后按 16 字节留出栈上参数的空间
+    let reserve = (stack * 8).div_ceil(16) * 16;
+    let disp = |n: usize| (n as u32).to_le_bytes();
+
+    let mut code = vec![
+        0x55, // push rbp
+        0x48, 0x89, 0xe5, // mov rbp, rsp
+        0x53, // push rbx
+        0x48, 0x83, 0xec, 0x08, // sub rsp, 8
+        0x48, 0x89, 0xfb, // mov rbx, rdi
+        0x48, 0x8b, 0x43, 0x08, // mov rax, [rbx + 8]
+    ];
+    // 超过一页时逐页下移 rsp 并访问新的一页, 不会跳过栈末尾的 guard page
+    for _ in 0..reserve / PAGE_SIZE {
+        // sub rsp, PAGE_SIZE
+        code.extend_from_slice(&[0x48, 0x81, 0xec]);
+        code.extend_from_slice(&disp(PAGE_SIZE));
+        code.extend_from_slice(&[0x48, 0x83, 0x0c, 0x24, 0x00]); // or qword ptr [rsp], 0
+    }
+    if !reserve.is_multiple_of(PAGE_SIZE) {
+        // sub rsp, reserve % PAGE_SIZE
+        code.extend_from_slice(&[0x48, 0x81, 0xec]);
+        code.extend_from_slice(&disp(reserve % PAGE_SIZE));
+    }
+    for i in 0..stack {
+        // mov r11, [rax + (6 + i) * 8]
+        code.extend_from_slice(&[0x4c, 0x8b, 0x98]);
+        code.extend_from_slice(&disp((INT_LOADS.len() + i) * 8));
+        // mov [rsp + i * 8], r11
+        code.extend_from_slice(&[0x4c, 0x89, 0x9c, 0x24]);
+        code.extend_from_slice(&disp(i * 8));
+    }
+    for (i, load) in INT_LOADS.iter().take(layout.ints).enumerate() {
+        code.extend_from_slice(load);
+        code.extend_from_slice(&disp(i * 8));
+    }
+    // 没有浮点参数时 xmm0 置零, 与内联汇编的行为一致
+    code.extend_from_slice(&[0x0f, 0x57, 0xc0]); // xorps xmm0, xmm0
+    if layout.floats != 0 {
+        code.extend_from_slice(&[0x4c, 0x8b, 0x53, 0x10]); // mov r10, [rbx + 16]
+        for i in 0..layout.floats {
+            // movsd xmm{i}, [r10 + i * 8]
+            code.extend_from_slice(&[0xf2, 0x41, 0x0f, 0x10, 0x82 | (i as u8) << 3]);
+            code.extend_from_slice(&disp(i * 8));
+        }
+    }
+    // mov eax, floats
+    code.push(0xb8);
+    code.extend_from_slice(&disp(layout.floats));
+    code.extend_from_slice(&[
+        0x4c, 0x8b, 0x1b, // mov r11, [rbx]
+        0x41, 0xff, 0xd3, // call r11
+        0x48, 0x89, 0x43, 0x18, // mov [rbx + 24], rax
+        0x48, 0x89, 0x53, 0x20, // mov [rbx + 32], rdx
+        0xf2, 0x0f, 0x11, 0x43, 0x28, // movsd [rbx + 40], xmm0
+        0x48, 0x8b, 0x5d, 0xf8, // mov rbx, [rbp - 8]
+        0xc9, // leave
+        0xc3, // ret
+    ]);
+    code
+}
@@ -0,0 +1,282 @@
+//! `CallSpec` 的 JSON 格式, 参见 `CallSpec::from_json`
+
+use core::convert::TryFrom;
+
+use serde_json::{json, Map, Value};
+
+use crate::{Arg, ArgKind, CallSpec, Convention, Error, Result, SpecResult};
+
+/// 参数与返回值的类型标签
+const KINDS: &[(&str, ArgKind)] = &[
+    ("i8", ArgKind::I8),
+    ("u8", ArgKind::U8),
+    ("i16", ArgKind::I16),
+    ("u16", ArgKind::U16),
+    ("i32", ArgKind::I32),
+    ("u32", ArgKind::U32),
+    ("i64", ArgKind::I64),
+    ("u64", ArgKind::U64),
+    ("isize", ArgKind::Isize),
+    ("usize", ArgKind::Usize),
+    ("f32", ArgKind::F32),
+    ("f64", ArgKind::F64),
+    ("ptr", ArgKind::Ptr),
+];
+
+const CONVENTIONS: &[(&str, Convention)] = &[
+    ("cdecl", Convention::Cdecl),
+    ("stdcall", Convention::Stdcall),
+    ("system", Convention::System),
+    ("thiscall", Convention::Thiscall),
+    ("fastcall", Convention::Fastcall),
+    ("aapcs-softfloat", Convention::AapcsSoftFloat),
+    ("aapcs-vfp", Convention::AapcsVfp),
+];
+
+impl CallSpec {
+    /// 从 JSON 中读取调用描述, 如
+    /// `{"lib": "libc.so.6", "symbol": "abs", "convention": "cdecl", "args": [{"i32": -3}], "ret": "i32"}`
+    ///
+    /// 每个参数是只有一个键的对象, 键为类型标签: `i8` 至 `u64`, `isize`, `usize`, `f32`, `f64` 与 `ptr` 的值为数字,
+    /// `cstr` 的值为字符串, 压入时补上结尾的 `\0`; `bytes` 的值为字节组成的数组.
+    /// `convention` 可以是 `cdecl`, `stdcall`, `system`, `thiscall`, `fastcall`, `aapcs-softfloat` 或 `aapcs-vfp`, 省略时为 `cdecl`;
+    /// `ret` 为除 `cstr` 与 `bytes` 外的类型标签或 `void`, 省略时为 `void`.
+    /// 出错时返回 `Error::InvalidInput`, 错误信息中带有出错的位置, 如 `$.args[1]`
+    ///
+    /// ```
+    /// use funcall::{Arg, CallSpec};
+    ///
+    /// let spec = CallSpec::from_json(
+    ///     r#"{"lib": "libc.so.6", "symbol": "abs", "args": [{"i32": -3}], "ret": "i32"}"#,
+    /// )
+    /// .unwrap();
+    /// assert_eq!(spec.args, [Arg::I32(-3)]);
+    ///
+    /// let err = CallSpec::from_json(r#"{"lib": "", "symbol": "", "args": [{"i33": 1}]}"#);
+    /// assert_eq!(err.unwrap_err().to_string(), "$.args[0]: unknown type tag `i33`");
+    /// ```
+    pub fn from_json(json: &str) -> Result<Self> {
+        let value: Value =
+            serde_json::from_str(json).map_err(|e| Error::InvalidInput(format!("$: {}", e)))?;
+        let obj = value
+            .as_object()
+            .ok_or_else(|| invalid("$", "expected an object"))?;
+        for key in obj.keys() {
+            if !["lib", "symbol", "convention", "args", "ret"].contains(&key.as_str()) {
+                return Err(invalid(&format!("$.{}", key), "unknown field"));
+            }
+        }
+        let string = |key: &str| match obj.get(key) {
+            Some(Value::String(s)) => Ok(s.clone()),
+            Some(_) => Err(invalid(&format!("$.{}", key), "expected a string")),
+            None => Err(invalid("$", &format!("missing field `{}`", key))),
+        };
+        let library = string("lib")?;
+        let symbol = string("symbol")?;
+        let convention = match obj.get("convention") {
+            None => Convention::Cdecl,
+            Some(value) => {
+                let name = value.as_str().unwrap_or_default();
+                lookup(CONVENTIONS, name).ok_or_else(|| {
+                    invalid("$.convention", &format!("unknown convention {}", value))
+                })?
+            }
+        };
+        let args = match obj.get("args") {
+            None => Vec::new(),
+            Some(Value::Array(args)) => args
+                .iter()
+                .enumerate()
+                .map(|(i, arg)| parse_arg(&format!("$.args[{}]", i), arg))
+                .collect::<Result<_>>()?,
+            Some(_) => return Err(invalid("$.args", "expected an array")),
+        };
+        let ret =
+            match obj.get("ret") {
+                None => None,
+                Some(Value::String(tag)) if tag == "void" => None,
+                Some(value) => {
+                    let tag = value.as_str().unwrap_or_default();
+                    Some(lookup(KINDS, tag).ok_or_else(|| {
+                        invalid("$.ret", &format!("unknown return type {}", value))
+                    })?)
+                }
+            };
+        Ok(CallSpec {
+            library,
+            symbol,
+            convention,
+            args,
+            ret,
+        })
+    }
+
+    /// 以 `from_json` 的格式输出
+    ///
+    /// # Panics
+    ///
+    /// 参数中有 `Arg::I128` 或 `Arg::U128` 时 panic, JSON 格式中没有它们的类型标签
+    pub fn to_json(&self) -> String {
+        let convention = CONVENTIONS
+            .iter()
+            .find(|(_, conv)| *conv == self.convention)
+            .map_or("cdecl", |(name, _)| name);
+        let args: Vec<Value> = self.args.iter().map(arg_json).collect();
+        json!({
+            "lib": self.library,
+            "symbol": self.symbol,
+            "convention": convention,
+            "args": args,
+            "ret": self.ret.map_or("void", kind_tag),
+        })
+        .to_string()
+    }
+
+    /// 将 `execute` 的结果按本描述中声明的返回值类型输出为 JSON
+    ///
+    /// 形如 `{"ret": {"i32": 3}, "errno": 0, "error": null}`, 没有返回值时 `ret` 为 `null`,
+    /// 出错时 `ret` 为 `null`, `error` 为错误信息
+    pub fn result_to_json(&self, result: &SpecResult) -> String {
+        let (ret, error) = match &result.ret {
+            Ok(ret) => {
+                let ret = self.ret.map(|kind| {
+                    let arg = match kind {
+                        ArgKind::I8 => Arg::I8(ret.as_i8()),
+                        ArgKind::U8 => Arg::U8(ret.as_u8()),
+                        ArgKind::I16 => Arg::I16(ret.as_i16()),
+                        ArgKind::U16 => Arg::U16(ret.as_u16()),
+                        ArgKind::I32 => Arg::I32(ret.as_i32()),
+                        ArgKind::U32 => Arg::U32(ret.as_u32()),
+                        ArgKind::I64 => Arg::I64(ret.as_i64()),
+                        ArgKind::U64 => Arg::U64(ret.as_u64()),
+                        ArgKind::Isize => Arg::Isize(ret.as_isize()),
+                        ArgKind::F32 | ArgKind::CFloat => Arg::F32(ret.as_f32()),
+                        ArgKind::F64 => Arg::F64(ret.as_f64()),
+                        ArgKind::Ptr => Arg::Ptr(ret.as_usize()),
+                        _ => Arg::Usize(ret.as_usize()),
+                    };
+                    arg_json(&arg)
+                });
+                (ret, None)
+            }
+            Err(e) => (None, Some(e)),
+        };
+        json!({ "ret": ret, "errno": result.errno, "error": error }).to_string()
+    }
+}
+
+fn invalid(path: &str, msg: &str) -> Error {
+    Error::InvalidInput(format!("{}: {}", path, msg))
+}
+
+fn lookup<T: Copy>(table: &[(&str, T)], name: &str) -> Option<T> {
+    table.iter().find(|(n, _)| *n == name).map(|(_, v)| *v)
+}
+
+fn kind_tag(kind: ArgKind) -> &'static str {
+    KINDS
+        .iter()
+        .find(|(_, k)| *k == kind)
+        .map_or("usize", |(tag, _)| tag)
+}
+
+fn parse_arg(path: &str, value: &Value) -> Result<Arg> {
+    let (tag, value) = match value
+        .as_object()
+        .map(Map::iter)
+        .map(|mut it| (it.next(), it.next()))
+    {
+        Some((Some(entry), None)) => entry,
+        _ => return Err(invalid(path, "expected an object with a single type tag")),
+    };
+    let path = &format!("{}.{}", path, tag);
+    let int = || -> Result<i128> {
+        let v = match value {
+            Value::Number(n) => n
+                .as_i64()
+                .map(i128::from)
+                .or_else(|| n.as_u64().map(i128::from)),
+            _ => None,
+        };
+        v.ok_or_else(|| invalid(path, &format!("expected an integer, found {}", value)))
+    };
+    let range = |v: i128| invalid(path, &format!("`{}` is out of range for {}", v, tag));
+    macro_rules! int {
+        ($variant:ident, $ty:ty) => {{
+            let v = int()?;
+            Arg::$variant(<$ty>::try_from(v).map_err(|_| range(v))?)
+        }};
+    }
+    Ok(match tag.as_str() {
+        "i8" => int!(I8, i8),
+        "u8" => int!(U8, u8),
+        "i16" => int!(I16, i16),
+        "u16" => int!(U16, u16),
+        "i32" => int!(I32, i32),
+        "u32" => int!(U32, u32),
+        "i64" => int!(I64, i64),
+        "u64" => int!(U64, u64),
+        "isize" => int!(Isize, isize),
+        "usize" => int!(Usize, usize),
+        "ptr" => int!(Ptr, usize),
+        "f32" | "f64" => {
+            let v = value
+                .as_f64()
+                .ok_or_else(|| invalid(path, &format!("expected a number, found {}", value)))?;
+            if tag == "f32" {
+                Arg::F32(v as f32)
+            } else {
+                Arg::F64(v)
+            }
+        }
+        "cstr" => match value {
+            Value::String(s) => Arg::Str(s.clone()),
+            _ => {
+                return Err(invalid(
+                    path,
+                    &format!("expected a string, found {}", value),
+                ))
+            }
+        },
+        "bytes" => {
+            let bytes = value
+                .as_array()
+                .ok_or_else(|| invalid(path, &format!("expected an array, found {}", value)))?;
+            let bytes = bytes
+                .iter()
+                .enumerate()
+                .map(|(i, b)| {
+                    b.as_u64()
+                        .and_then(|b| u8::try_from(b).ok())
+                        .ok_or_else(|| invalid(&format!("{}[{}]", path, i), "expected a byte"))
+                })
+                .collect::<Result<_>>()?;
+            Arg::Bytes(bytes)
+        }
+        _ => {
+            let path = path.rsplit_once('.').map_or(path.as_str(), |(p, _)| p);
+            return Err(invalid(path, &format!("unknown type tag `{}`", tag)));
+        }
+    })
+}
+
+fn arg_json(arg: &Arg) -> Value {
+    match arg {
+        Arg::I8(v) => json!({ "i8": v }),
+        Arg::U8(v) => json!({ "u8": v }),
+        Arg::I16(v) => json!({ "i16": v }),
+        Arg::U16(v) => json!({ "u16": v }),
+        Arg::I32(v) => json!({ "i32": v }),
+        Arg::U32(v) => json!({ "u32": v }),
+        Arg::I64(v) => json!({ "i64": v }),
+        Arg::U64(v) => json!({ "u64": v }),
+        Arg::Isize(v) => json!({ "isize": v }),
+        Arg::Usize(v) => json!({ "usize": v }),
+        Arg::F32(v) => json!({ "f32": v }),
+        Arg::F64(v) => json!({ "f64": v }),
+        Arg::Ptr(v) => json!({ "ptr": v }),
+        Arg::Bytes(v) => json!({ "bytes": v }),
+        Arg::Str(v) => json!({ "cstr": v }),
+        Arg::I128(_) | Arg::U128(_) => panic!("{:?} cannot be represented in JSON", arg),
+    }
+}
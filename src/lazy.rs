@@ -0,0 +1,182 @@
+//! 首次使用时才查找的函数
+
+use std::cmp::Ordering;
+use std::ffi::{OsStr, OsString};
+use std::sync::{Arc, OnceLock};
+
+use crate::{CallError, Error, Func, Library, Result, SharedFunc};
+
+/// 首次使用时才加载库并查找函数, 之后一直缓存查找结果, 通常通过 `lazy_func!` 声明为 static
+///
+/// 缓存的只是函数地址与库的引用, 每次调用 `func` 都会得到一个新的 `Func`,
+/// 因此多个线程可以同时通过同一个 `LazyFunc` 调用函数
+pub struct LazyFunc {
+    source: Source,
+    cell: OnceLock<Result<SharedFunc>>,
+}
+
+enum Source {
+    Symbol {
+        lib: &'static str,
+        symbol: &'static str,
+    },
+    Resolver(fn() -> Result<SharedFunc>),
+}
+
+impl LazyFunc {
+    /// 首次使用时从 lib 中查找 symbol
+    pub const fn new(lib: &'static str, symbol: &'static str) -> Self {
+        Self {
+            source: Source::Symbol { lib, symbol },
+            cell: OnceLock::new(),
+        }
+    }
+
+    /// 首次使用时调用 resolve 取得函数
+    pub const fn with_resolver(resolve: fn() -> Result<SharedFunc>) -> Self {
+        Self {
+            source: Source::Resolver(resolve),
+            cell: OnceLock::new(),
+        }
+    }
+
+    /// 取得缓存的函数, 首次调用时进行查找, 查找失败的错误同样会被缓存
+    pub fn get(&self) -> std::result::Result<&SharedFunc, &Error> {
+        self.cell
+            .get_or_init(|| match self.source {
+                Source::Symbol { lib, symbol } => Ok(Library::new(lib)?.get(symbol)?.share()),
+                Source::Resolver(resolve) => resolve(),
+            })
+            .as_ref()
+    }
+
+    /// 同 `get`, 但返回一个新的错误, 错误信息中带有库与符号名, 可以直接通过 `?` 传递
+    pub fn try_get(&self) -> Result<&SharedFunc> {
+        self.get().map_err(|e| self.error(e))
+    }
+
+    /// 以缓存的错误构造同类的错误
+    fn error(&self, e: &Error) -> Error {
+        let msg = match self.source {
+            Source::Symbol { lib, symbol } => {
+                format!("failed to resolve `{}` from `{}`: {}", symbol, lib, e)
+            }
+            Source::Resolver(_) => format!("failed to resolve function: {}", e),
+        };
+        match e {
+            Error::InvalidInput(_) => Error::InvalidInput(msg),
+            Error::NotFound(_) => Error::NotFound(msg),
+            Error::InvalidData(_) => Error::InvalidData(msg),
+            Error::Io(e) => Error::Io(std::io::Error::new(e.kind(), msg)),
+        }
+    }
+
+    /// 创建一个参数为空的 `Func`
+    pub fn func(&self) -> std::result::Result<Func, &Error> {
+        self.get().map(SharedFunc::func)
+    }
+
+    /// 同 `func`, 但查找失败时 panic
+    pub fn func_or_panic(&self) -> Func {
+        match self.func() {
+            Ok(func) => func,
+            Err(e) => panic!("{}", self.error(e)),
+        }
+    }
+
+    /// 是否已经进行过查找
+    pub fn is_resolved(&self) -> bool {
+        self.cell.get().is_some()
+    }
+}
+
+/// `Func::new_lazy` 记录的库与符号, 由克隆出的 `Func` 共享, 查找只进行一次
+#[derive(Debug)]
+pub(crate) struct Pending {
+    lib: OsString,
+    symbol: String,
+    cell: OnceLock<std::result::Result<SharedFunc, String>>,
+}
+
+/// 只比较库与符号, 不论是否已经查找过
+impl PartialEq for Pending {
+    fn eq(&self, other: &Self) -> bool {
+        (&self.lib, &self.symbol) == (&other.lib, &other.symbol)
+    }
+}
+
+impl PartialOrd for Pending {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        (&self.lib, &self.symbol).partial_cmp(&(&other.lib, &other.symbol))
+    }
+}
+
+impl Func {
+    /// 只记录库的路径与符号名, 直到第一次调用或 `resolve` 时才加载库并查找函数
+    ///
+    /// 查找失败时 `try_call`, `call_protected` 等返回 `Result` 的调用方法返回 `CallError::ResolveFailed`, 而不是在创建时报错.
+    /// 查找的结果 (包括失败) 会被缓存, 查找前克隆得到的 `Func` 共享同一次查找, 可以在不同的线程中同时进行.
+    /// `cdecl` 等直接调用的方法不会进行查找, 需要先调用 `resolve`
+    ///
+    /// ```
+    /// # #[cfg(target_os = "linux")]
+    /// # fn main() {
+    /// use funcall::{CallError, Convention, Func};
+    ///
+    /// let mut missing = Func::new_lazy("libc.so.6", "no_such_function");
+    /// assert!(matches!(
+    ///     unsafe { missing.try_call(Convention::Cdecl) },
+    ///     Err(CallError::ResolveFailed { .. })
+    /// ));
+    /// # }
+    /// # #[cfg(not(target_os = "linux"))]
+    /// # fn main() {}
+    /// ```
+    pub fn new_lazy<P: AsRef<OsStr>>(lib: P, symbol: &str) -> Self {
+        let mut func = Func::null();
+        func.symbol = Some(symbol.to_owned());
+        func.pending = Some(Arc::new(Pending {
+            lib: lib.as_ref().to_owned(),
+            symbol: symbol.to_owned(),
+            cell: OnceLock::new(),
+        }));
+        func
+    }
+
+    /// 进行 `new_lazy` 推迟的查找, 已经查找过或不是通过 `new_lazy` 创建时什么都不做
+    pub fn resolve(&mut self) -> std::result::Result<(), CallError> {
+        let pending = match &self.pending {
+            Some(pending) => pending.clone(),
+            None => return Ok(()),
+        };
+        let resolved = pending.cell.get_or_init(|| {
+            Library::new(&pending.lib)
+                .and_then(|lib| lib.get(&pending.symbol))
+                .map(|func| func.share())
+                .map_err(|e| e.to_string())
+        });
+        match resolved {
+            Ok(shared) => {
+                let func = shared.func();
+                self.func = func.func;
+                self.lib = func.lib;
+                self.symbol = func.symbol;
+                #[cfg(all(windows, target_arch = "x86"))]
+                {
+                    self.decorated_bytes = func.decorated_bytes;
+                }
+                self.pending = None;
+                Ok(())
+            }
+            Err(reason) => Err(CallError::ResolveFailed {
+                symbol: pending.symbol.clone(),
+                reason: reason.clone(),
+            }),
+        }
+    }
+
+    /// 是否是通过 `new_lazy` 创建且尚未成功查找的函数
+    pub fn is_pending(&self) -> bool {
+        self.pending.is_some()
+    }
+}
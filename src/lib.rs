@@ -9,8 +9,7 @@
 //! }
 //!
 //! let mut func = Func::from_raw(add as *const fn());
-//! func.push(1i32);
-//! func.push(1i32);
+//! func.push(1i32).push(1i32);
 //! unsafe {
 //!     func.cdecl();
 //! }
@@ -19,427 +18,3326 @@
 //! ```
 //!
 //! ```
-//! use funcall::Func;
+//! # #[cfg(feature = "loader")]
+//! # fn main() {
+//! use funcall::libc_func;
 //! use std::ffi::CStr;
 //!
-//! let mut func = Func::new("/usr/lib/libc.so.6", b"sprintf\0").unwrap();
+//! let mut func = libc_func("sprintf").unwrap();
 //! let mut buf = vec![0i8; 100];
-//! func.push(buf.as_mut_ptr());
-//! func.push(b"%d %.6f\0".as_ptr());
-//! func.push(2233i32);
-//! func.push(2233.3322f64);
+//! func.push(buf.as_mut_ptr())
+//!     .push(b"%d %.6f\0".as_ptr())
+//!     .push(2233i32)
+//!     .push(2233.3322f64);
 //! unsafe {
 //!     func.cdecl();
 //!     assert_eq!(CStr::from_ptr(buf.as_ptr()).to_str().unwrap(), "2233 2233.332200")
 //! }
-//!
+//! # }
+//! # #[cfg(not(feature = "loader"))]
+//! # fn main() {}
 //! ```
-#![feature(proc_macro_hygiene, asm)]
+//!
+//! # 线程安全
+//!
+//! - `Library` 是 `Send + Sync` 的, 克隆只会增加引用计数
+//! - `Func` 是 `Send` 的, 可以整个移动到其他线程中使用, 但压入参数与调用都需要 `&mut`,
+//!   因此同一个 `Func` 不能被多个线程同时调用
+//! - 需要在多个线程中同时调用同一个函数时, 使用 `Func::share` 得到 `SharedFunc`,
+//!   每个线程通过 `SharedFunc::func` 得到各自的 `Func` 来压入参数
+//! - 压入的指针参数只以地址的形式保存, 它们指向的数据是否能在其他线程中访问需要调用者自行保证
+//!
+//! # Features
+//!
+//! - `std` (默认开启): 观察者, 注册表, 超时与 JIT 等依赖标准库的功能.
+//!   关闭后本 crate 只依赖 `core` 与 `alloc`, 仍可以通过 `Func::from_raw` 调用已知地址的函数
+//! - `loader` (默认开启): 通过 libloading 加载动态库, 提供 `Library`, `Func::new`, `LazyFunc`, `Func::new_lazy` 与 `set_resolver` 等,
+//!   会同时开启 `std`. 只调用已知地址的函数时可以关闭它以去掉 libloading 与系统加载器相关的代码.
+//!   `recorder`, `capi`, `json` 与 `macros` 都需要加载库, 会同时开启本 feature
+//! - `serde`: 为 `Convention`, `Arg` 与 `CallSpec` 实现序列化与反序列化
+//! - `log`: 在 trace 级别输出每次调用的寄存器与栈布局, 以及调用后返回值寄存器的值
+//! - `libffi`: 通过 libffi-sys 链接系统的 libffi (MSVC 下编译其自带的源码), 在没有手写汇编的平台上通过它发出调用, 参见 `Backend`.
+//!   libffi 只按声明的类型取回返回值, 需要通过 `Func::set_ret_kind` 或 `Signature::prepare` 声明
+//! - `protected`: 提供 `Func::call_protected`, 将调用中的段错误等硬件异常转换为 `Err`,
+//!   Windows 下还提供 `Func::try_call_catching`, 将被调用者抛出的 SEH 与 C++ 异常转换为 `Err`.
+//!   需要 C 编译器, Windows 下只支持 MSVC
+//! - `recorder`: 提供 `Recorder` 与 `replay`, 将调用记录到文件中并在之后重放, 会同时开启 `serde`
+//! - `capi`: 提供 `capi` 模块, 以 C 接口导出 `Func` 供其他语言使用, 头文件为 `include/funcall.h`
+//! - `headers`: 提供 `headers` 模块, 从 C 头文件中解析函数原型
+//! - `json`: 提供 `CallSpec::from_json`, `CallSpec::to_json` 与 `CallSpec::result_to_json`, 以 JSON 描述调用并输出结果
+//! - `testing`: 提供 `testing` 模块, 以随机生成的参数对照直接调用检查 `Func`
+//! - `async`: 提供 `Func::call_async`, 在后台线程中调用函数并返回 `Future`, 不依赖于特定的异步运行时
+//! - `tokio`: `Func::call_async` 改为通过 `tokio::task::spawn_blocking` 在 tokio 的阻塞线程池中调用,
+//!   不再为每次调用创建线程, 此时需要在 tokio 运行时中调用它. 会同时开启 `async`
+//! - `macros`: 提供 `dynamic_extern` 属性宏, 由 `extern` 块生成首次调用时才查找函数的包装函数
+//! - `regcheck`: 调试用, 64 位 Linux 上 `cdecl` 调用前后比较 rbx, rbp 与 r12-r15, 被调用者破坏了其中任何一个时
+//!   panic 并列出被破坏的寄存器. 调用总是走较慢的通用路径, 不要在发布版本中开启
+//! - `provenance`: 调试用, 各个压入方法通过 `#[track_caller]` 记下每个参数是在哪一行压入的,
+//!   可以由 `ArgView::origin` 读取, 并出现在 `Func` 的 `Debug` 输出与 `log` 的 trace 输出中
+//! - `asan`: 调用前通过 `__asan_unpoison_memory_region` 将汇编构造参数的栈区域标记为可访问,
+//!   只能在以 `-Zsanitizer=address` 编译时开启, 否则链接时找不到该函数
+//! - `valgrind`: 调用前通过 Valgrind 的 client request 将同一区域标记为已初始化, 不在 Valgrind 中运行时没有效果
+//! - `unwind`: 提供 `UnwindPolicy::Catch`, 64 位 Linux 与 64 位 Windows 上允许回调中的 panic 展开经过调用帧,
+//!   作为普通的 panic 传给调用者, 会同时开启 `std`
+//! - `half`: 为 `half::f16` 实现 `IntoArg` 与 `FromRet`, 并提供它与 `F16` 之间的转换
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[macro_use]
+extern crate alloc;
 
-use std::any::{Any, TypeId};
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::ffi::{c_char, c_int, c_long, c_uint, c_ulong, c_void};
+use core::mem;
+#[cfg(feature = "provenance")]
+use core::panic::Location;
+#[cfg(feature = "loader")]
 use std::ffi::OsStr;
-use std::mem;
 
-use rusty_asm::rusty_asm;
+use inline::InlineVec;
+use native::NativeCall;
+
+#[cfg(any(target_arch = "x86", all(target_arch = "x86_64", target_os = "linux")))]
+use core::arch::asm;
+
+#[macro_use]
+mod macros;
+#[cfg(windows)]
+mod ansi;
+mod arg;
+mod argv;
+mod bind;
+mod builder;
+#[cfg(feature = "capi")]
+pub mod capi;
+mod compiled;
+pub mod cpp;
+mod ctype;
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+mod custom;
+#[cfg(any(feature = "loader", all(feature = "std", unix)))]
+mod errno;
+mod error;
+mod float16;
+mod fmt;
+mod fmtspec;
+mod fnptr;
+#[cfg(all(feature = "std", unix))]
+mod fork;
+mod fpstate;
+#[cfg(feature = "async")]
+mod future;
+#[cfg(feature = "headers")]
+pub mod headers;
+mod inline;
+#[cfg(all(feature = "std", target_arch = "x86_64", target_os = "linux"))]
+mod jit;
+#[cfg(feature = "json")]
+mod json;
+#[cfg(feature = "loader")]
+mod lazy;
+#[cfg(feature = "libffi")]
+mod libffi;
+#[cfg(feature = "loader")]
+mod library;
+#[cfg(all(target_arch = "loongarch64", target_os = "linux"))]
+mod loongarch64;
+#[cfg(feature = "std")]
+mod memo;
+mod native;
+#[cfg(any(
+    target_arch = "x86",
+    all(target_arch = "x86_64", any(target_os = "linux", windows))
+))]
+mod noreturn;
+#[cfg(target_vendor = "apple")]
+pub mod objc;
+mod observer;
+mod out;
+mod owned;
+mod pack;
+#[cfg(all(feature = "loader", windows))]
+mod pe;
+mod plan;
+mod prepared;
+#[cfg(all(feature = "protected", any(unix, all(windows, target_env = "msvc"))))]
+mod protect;
+#[cfg(all(target_arch = "x86_64", any(target_os = "linux", windows)))]
+mod raw;
+#[cfg(feature = "recorder")]
+mod recorder;
+#[cfg(all(feature = "regcheck", target_arch = "x86_64", target_os = "linux"))]
+mod regcheck;
+#[cfg(feature = "std")]
+mod registry;
+#[cfg(feature = "loader")]
+mod resolver;
+#[cfg(any(
+    target_arch = "x86",
+    all(target_arch = "x86_64", any(target_os = "linux", windows)),
+    all(target_arch = "loongarch64", target_os = "linux")
+))]
+mod sanitize;
+mod selftest;
+#[cfg(feature = "std")]
+mod shared;
+mod signature;
+mod spec;
+mod sret;
+#[cfg(feature = "std")]
+mod stack;
+#[cfg(feature = "std")]
+mod stats;
+#[cfg(feature = "testing")]
+pub mod testing;
+pub mod time;
+#[cfg(feature = "std")]
+mod timeout;
+mod unwind;
+mod validate;
+mod valist;
+#[cfg(windows)]
+mod wide;
+mod wow64;
+
+pub use arg::Arg;
+pub use bind::BoundCall;
+pub use builder::CallBuilder;
+pub use compiled::CompiledCall;
+pub use ctype::CType;
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+pub use custom::{ArgDest, Cleanup, ConvDesc};
+pub use error::{CallError, Error};
+pub use float16::F16;
+pub use fmt::ArgView;
+pub use fnptr::{BoundFn, FnPtr};
+#[cfg(all(feature = "std", unix))]
+pub use fork::{ForkError, ForkOutcome};
+/// 将 `extern` 块中声明的函数展开为同名的包装函数, 首次调用时才通过 `LazyFunc` 从 lib 中查找
+///
+/// 包装函数为 unsafe 函数, 参数与声明的相同, 直接按声明的签名调用查找到的函数;
+/// 可变参数函数的 `...` 部分改为 `&[Arg]` 类型的参数, 通过 `Func` 压入并以 cdecl 调用.
+/// `#[link_name = "..."]` 可以指定实际查找的符号, 其他属性会保留到包装函数上.
+///
+/// 默认 (`on_error = "result"`) 包装函数的返回值为 `Result<T>`, 库或符号找不到时返回错误;
+/// `on_error = "panic"` 时返回值为 `T`, 找不到时 panic. lib 可以是任何常量表达式
+///
+/// ```
+/// # #[cfg(target_os = "linux")]
+/// # fn main() -> funcall::Result<()> {
+/// use funcall::{dynamic_extern, Arg};
+/// use std::os::raw::{c_char, c_int};
+///
+/// #[dynamic_extern(lib = "libc.so.6")]
+/// extern "C" {
+///     fn abs(n: c_int) -> c_int;
+///     fn snprintf(buf: *mut c_char, len: usize, fmt: *const c_char, ...) -> c_int;
+///     #[link_name = "no_such_function"]
+///     fn missing();
+/// }
+///
+/// assert_eq!(unsafe { abs(-3) }?, 3);
+/// let mut buf = [0 as c_char; 16];
+/// let n = unsafe { snprintf(buf.as_mut_ptr(), 16, b"%d\0".as_ptr().cast(), &[Arg::I32(42)]) }?;
+/// assert_eq!(n, 2);
+/// assert!(unsafe { missing() }.is_err());
+/// # Ok(())
+/// # }
+/// # #[cfg(not(target_os = "linux"))]
+/// # fn main() {}
+/// ```
+#[cfg(feature = "macros")]
+pub use funcall_macros::dynamic_extern;
+#[cfg(feature = "async")]
+pub use future::{CallFuture, CallResult};
+#[cfg(all(feature = "std", target_arch = "x86_64", target_os = "linux"))]
+pub use jit::Trampoline;
+#[cfg(feature = "loader")]
+pub use lazy::LazyFunc;
+#[cfg(feature = "loader")]
+pub use library::{libc_func, BatchError, Library, OpenFirstError};
+#[cfg(feature = "std")]
+pub use memo::CacheStats;
+#[cfg(feature = "std")]
+pub use observer::{clear_observer, set_observer, CallInfo, CallObserver};
+pub use out::OutParam;
+pub use owned::OwnedPtr;
+pub use pack::ArgPack;
+pub use plan::{ArgLocation, ArgMove, ArgOrder, CallPlan, LayoutOverride, PlannedArg, Promotion};
+pub use prepared::{PreparedCall, RowError};
+#[cfg(all(feature = "protected", any(unix, all(windows, target_env = "msvc"))))]
+pub use protect::{Fault, ProtectedError};
+#[cfg(all(target_arch = "x86_64", any(target_os = "linux", windows)))]
+pub use raw::call_raw6;
+#[cfg(feature = "recorder")]
+pub use recorder::{
+    replay, replay_log, CallLog, Divergence, RecordedCall, Recorder, ReplayOptions, ReplayReport,
+    LOG_VERSION,
+};
+#[cfg(feature = "std")]
+pub use registry::{register, register_with, registered_signature, unregister};
+#[cfg(feature = "loader")]
+pub use resolver::{clear_resolver, set_resolver, DefaultResolver, LibraryResolver, ResolvedLib};
+pub use selftest::{self_test, self_test_with, SelfTestFailure, SelfTestReport};
+#[cfg(feature = "std")]
+pub use shared::SharedFunc;
+pub use signature::Signature;
+pub use spec::CallSpec;
+#[cfg(feature = "loader")]
+pub use spec::SpecResult;
+pub use sret::RetLayout;
+#[cfg(feature = "std")]
+pub use stats::{call_stats, reset_call_stats, CallStats};
+#[cfg(feature = "std")]
+pub use timeout::{CallOutcome, Timeout, TimeoutError};
+pub use unwind::UnwindPolicy;
+pub use validate::PtrError;
+pub use wow64::ntdll64_export;
 
-/// 将参数转换为 Vec<usize> 方便压栈
+/// 可以压入 `Func` 的参数
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` cannot be passed as an argument of a dynamic call",
+    label = "unsupported argument type",
+    note = "pass integers, floats or raw pointers; convert strings with `CString` and pass `as_ptr()`"
+)]
 pub trait IntoArg {
-    fn into_arg(self) -> Vec<usize>;
+    /// 参数的类型, 决定参数如何传递
+    ///
+    /// `F32`, `F64`, `CFloat` 与 `F16` 送入浮点寄存器, 其余的送入整数寄存器或栈; 不足一个字的整数按它扩展符号,
+    /// libffi 后端按它选择 `ffi_type`, 附加了原型时也按它检查与转换参数.
+    /// 与 `push_into` 写入的内容不符时参数会被放进错误的寄存器且不会有任何报错,
+    /// 转发给其他类型的实现应使用被转发类型的 `KIND`. 默认的 `Other` 按整数传递
+    const KIND: ArgKind = ArgKind::Other;
+
+    /// 将参数按内存中的顺序写入 out
+    fn push_into(self, out: &mut ArgSink)
+    where
+        Self: Sized;
+
+    /// 将参数转换为 Vec<usize>
+    ///
+    /// 默认通过 `push_into` 实现
+    #[deprecated(note = "implement `push_into` instead, which does not allocate")]
+    fn into_arg(self) -> Vec<usize>
+    where
+        Self: Sized,
+    {
+        let mut sink = ArgSink::new(Self::KIND);
+        self.push_into(&mut sink);
+        sink.words.to_vec()
+    }
+}
+
+/// `IntoArg::push_into` 写入参数的地方
+///
+/// 一个参数的所有字写完后才由 `Func` 按调用约定放入寄存器或栈中, 不超过四个字的参数不会分配内存
+#[derive(Debug)]
+pub struct ArgSink {
+    kind: ArgKind,
+    words: InlineVec<usize, 4>,
+}
+
+impl ArgSink {
+    fn new(kind: ArgKind) -> Self {
+        ArgSink {
+            kind,
+            words: InlineVec::new(),
+        }
+    }
+
+    /// 写入一个字
+    pub fn push_word(&mut self, word: usize) -> &mut Self {
+        self.words.push(word);
+        self
+    }
+
+    /// 依次写入多个字
+    pub fn push_words(&mut self, words: &[usize]) -> &mut Self {
+        self.words.extend_from_slice(words);
+        self
+    }
+
+    /// 按机器字长分割后写入, 最后不满一个字的部分在末尾补零
+    pub fn push_bytes(&mut self, bytes: &[u8]) -> &mut Self {
+        for chunk in bytes.chunks(mem::size_of::<usize>()) {
+            let mut word = [0; mem::size_of::<usize>()];
+            word[..chunk.len()].copy_from_slice(chunk);
+            self.words.push(usize::from_ne_bytes(word));
+        }
+        self
+    }
+
+    /// 参数的类型, 即 `IntoArg::KIND`
+    pub fn kind(&self) -> ArgKind {
+        self.kind
+    }
+
+    /// 已写入的字
+    pub fn words(&self) -> &[usize] {
+        &self.words
+    }
+
+    /// 已写入的字节数
+    pub fn size(&self) -> usize {
+        self.words.len() * mem::size_of::<usize>()
+    }
+
+    /// 按内存中的顺序拼接写入的字, 超出 16 字节的部分被忽略
+    pub(crate) fn bytes(&self) -> [u8; 16] {
+        let mut bytes = [0; 16];
+        for (chunk, word) in bytes
+            .chunks_mut(mem::size_of::<usize>())
+            .zip(self.words.iter())
+        {
+            chunk.copy_from_slice(&word.to_ne_bytes());
+        }
+        bytes
+    }
+
+    /// 写入的 double, f32 也已提升为 double
+    pub(crate) fn float(&self) -> f64 {
+        let mut bits = [0; 8];
+        bits.copy_from_slice(&self.bytes()[..8]);
+        f64::from_ne_bytes(bits)
+    }
+}
+
+/// 参数的类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ArgKind {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    I64,
+    U64,
+    I128,
+    U128,
+    Isize,
+    Usize,
+    /// 压入时被提升为 double 的 f32
+    F32,
+    F64,
+    /// 通过 `Func::push_float` 压入, 以 C float 传递的 f32
+    CFloat,
+    /// 以浮点寄存器的低 16 位传递的半精度浮点数 `F16`
+    F16,
+    Ptr,
+    /// 第三方实现的 `IntoArg`
+    Other,
+}
+
+/// addr 落在 moves 中某段 (原地址, 长度, 新地址) 内时, 返回新地址中的相同位置, 否则原样返回
+fn relocate(addr: usize, moves: &[(usize, usize, usize)]) -> usize {
+    moves
+        .iter()
+        .find_map(|&(from, len, to)| {
+            // 输出参数的指针可能指向缓冲区中间
+            let offset = addr.wrapping_sub(from);
+            (offset < len).then(|| to + offset)
+        })
+        .unwrap_or(addr)
+}
+
+/// 按 kind 将不足一个字的整数符号扩展或零扩展到整个字, 其他 kind 原样返回
+fn extend_word(kind: ArgKind, word: usize) -> usize {
+    match kind {
+        ArgKind::I8 => word as i8 as isize as usize,
+        ArgKind::U8 => word as u8 as usize,
+        ArgKind::I16 => word as i16 as isize as usize,
+        ArgKind::U16 => word as u16 as usize,
+        ArgKind::I32 => word as i32 as isize as usize,
+        ArgKind::U32 if plan::SIGN_EXTEND_U32 => word as i32 as isize as usize,
+        ArgKind::U32 => word as u32 as usize,
+        _ => word,
+    }
 }
 
 impl<T> IntoArg for *const T {
-    fn into_arg(self) -> Vec<usize> {
-        vec![self as usize]
+    const KIND: ArgKind = ArgKind::Ptr;
+
+    fn push_into(self, out: &mut ArgSink) {
+        out.push_word(self as usize);
     }
 }
 
 impl<T> IntoArg for *mut T {
-    fn into_arg(self) -> Vec<usize> {
-        vec![self as usize]
+    const KIND: ArgKind = ArgKind::Ptr;
+
+    fn push_into(self, out: &mut ArgSink) {
+        out.push_word(self as usize);
     }
 }
 
 // f32 无论 32 位 还是 64 位下都要对齐到 64 位再传参
 impl IntoArg for f32 {
-    fn into_arg(self) -> Vec<usize> {
-        (self as f64).into_arg()
+    const KIND: ArgKind = ArgKind::F32;
+
+    fn push_into(self, out: &mut ArgSink) {
+        (self as f64).push_into(out);
     }
 }
 
 macro_rules! impl_intoarg {
-    ($($ty:ty), *) => {
+    ($($ty:ty => $kind:ident), *) => {
         $(impl IntoArg for $ty {
-            fn into_arg(self) -> Vec<usize> {
-                let len = mem::size_of::<$ty>() / mem::size_of::<usize>();
-                if len <= 1 {
+            const KIND: ArgKind = ArgKind::$kind;
+
+            fn push_into(self, out: &mut ArgSink) {
+                if mem::size_of::<$ty>() <= mem::size_of::<usize>() {
                     // 小于等于机器字长的参数, 直接对齐就行了
-                    vec![self as usize]
+                    out.push_word(self as usize);
                 } else {
-                    // 大于机器字长的参数, 分割为 Vec<usize>
-                    unsafe {
-                        std::slice::from_raw_parts(&self as *const _ as *const usize, len).to_vec()
-                    }
+                    // 大于机器字长的参数, 按内存中的顺序分割
+                    out.push_bytes(&self.to_ne_bytes());
                 }
             }
         })*
     };
 }
 
-impl_intoarg!(i8, u8, i16, u16, i32, u32, i64, u64, i128, u128, isize, usize, f64);
+impl_intoarg!(
+    i8 => I8, u8 => U8, i16 => I16, u16 => U16, i32 => I32, u32 => U32, i64 => I64, u64 => U64,
+    i128 => I128, u128 => U128, isize => Isize, usize => Usize
+);
+
+// 浮点数需要按位传递, 不能直接 as usize
+impl IntoArg for f64 {
+    const KIND: ArgKind = ArgKind::F64;
+
+    fn push_into(self, out: &mut ArgSink) {
+        self.to_bits().push_into(out);
+    }
+}
+
+/// 可以一次性压入的参数元组, 元组中的每个元素都会依次通过 `Func::push` 压入
+pub trait ArgTuple {
+    fn push_into(self, func: &mut Func);
+}
+
+macro_rules! impl_argtuple {
+    ($($name:ident), *) => {
+        impl<$($name: IntoArg), *> ArgTuple for ($($name,)*) {
+            #[allow(non_snake_case)]
+            fn push_into(self, func: &mut Func) {
+                let ($($name,)*) = self;
+                $(func.push($name);)*
+            }
+        }
+    };
+}
+
+impl_argtuple!(A);
+impl_argtuple!(A, B);
+impl_argtuple!(A, B, C);
+impl_argtuple!(A, B, C, D);
+impl_argtuple!(A, B, C, D, E);
+impl_argtuple!(A, B, C, D, E, F);
+impl_argtuple!(A, B, C, D, E, F, G);
+impl_argtuple!(A, B, C, D, E, F, G, H);
+impl_argtuple!(A, B, C, D, E, F, G, H, I);
+impl_argtuple!(A, B, C, D, E, F, G, H, I, J);
+impl_argtuple!(A, B, C, D, E, F, G, H, I, J, K);
+impl_argtuple!(A, B, C, D, E, F, G, H, I, J, K, L);
+
+/// 本 crate 中加载函数与解析原型等操作的结果
+pub type Result<T> = core::result::Result<T, Error>;
+
+/// 栈上参数所占字节数的默认上限
+const DEFAULT_MAX_STACK: usize = 64 * 1024;
+
+/// 32 位 x86 下压在栈上参数之上的哨兵值
+#[cfg(target_arch = "x86")]
+const STACK_CANARY: u32 = 0x5afe_c0de;
+
+/// 调用约定
+///
+/// 每次调用时参数都会重新复制到寄存器与栈中, 被调用者清理的只是这份副本,
+/// 因此两种调用约定下同一个 `Func` 都可以反复调用, 参见 `Func::invoke`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum Convention {
+    /// C 语言默认使用的调用约定, 在 64 位 Linux 下即 System V AMD64 ABI, 在 64 位 Windows 下即 Microsoft x64 ABI
+    Cdecl,
+    /// 32 位下 WINAPI 使用的调用约定
+    Stdcall,
+    /// 与 `extern "system"` 相同的调用约定, 调用时按当前平台换成 `Convention::native_system()` 返回的具体调用约定
+    ///
+    /// 在所有平台上都可以使用, 绑定 WINAPI 的代码不需要再按平台区分调用约定
+    System,
+    /// 32 位 MSVC 下 C++ 成员函数默认使用的调用约定, 第一个压入的参数须为 this
+    ///
+    /// this 通过 ecx 传递, 其余参数与 stdcall 相同由被调用者清理. 通过 `Func::set_arity` 或
+    /// `Func::set_signature` 声明为可变参数函数时, 则与 MSVC 一样退回 cdecl: this 作为第一个栈参数, 由调用者清理.
+    /// GCC 编译的成员函数参见 `Func::set_thiscall_flavor`
+    Thiscall,
+    /// 32 位 x86 下的 fastcall, 从左到右前两个不超过 4 字节的整数或指针参数通过 ecx 与 edx 传递
+    ///
+    /// 8 字节的参数与浮点数总在栈上, 也不占用寄存器, 其后较小的参数仍会使用剩下的寄存器, 如 `(i64, i32, i32)`
+    /// 中的两个 i32 分别在 ecx 与 edx 中. 栈上的参数由被调用者清理; 与 thiscall 相同, 声明为可变参数函数时退回 cdecl
+    ///
+    /// 这是 MSVC 与 clang 的规则. GCC 与 rustc 的 `extern "fastcall"` 中宽参数虽然在栈上, 却会占用寄存器,
+    /// 宽参数之前寄存器未满时两者不兼容
+    Fastcall,
+    /// ARM32 软浮点 ABI (gnueabi), 浮点参数与返回值同整数一样通过 r0~r3 与栈传递
+    ///
+    /// 只在 ARM32 下通过 libffi 支持. 硬浮点平台上也可以用它调用 `__aeabi_*` 等运行时函数与按 `-mfloat-abi=softfp` 编译的函数
+    AapcsSoftFloat,
+    /// ARM32 硬浮点 ABI (gnueabihf), 浮点参数与返回值通过 VFP 寄存器 s0~s15 或 d0~d7 传递
+    ///
+    /// 只在硬浮点的 ARM32 平台下通过 libffi 支持, 软浮点平台上不保证存在 VFP 寄存器
+    AapcsVfp,
+}
+
+impl Convention {
+    /// 所有调用约定, 按声明的顺序排列, 包括当前平台不支持的调用约定
+    pub const ALL: &'static [Convention] = &[
+        Convention::Cdecl,
+        Convention::Stdcall,
+        Convention::System,
+        Convention::Thiscall,
+        Convention::Fastcall,
+        Convention::AapcsSoftFloat,
+        Convention::AapcsVfp,
+    ];
+
+    /// `Convention::System` 在当前平台上实际使用的调用约定, 即 32 位 Windows 下的 `Stdcall`, 其他平台上的 `Cdecl`
+    ///
+    /// COM 接口的方法都使用该调用约定. 以它与 `Convention::System` 调用的结果相同, 需要得到具体的调用约定 (如按调用约定分派) 时使用本函数
+    pub const fn native_system() -> Self {
+        if cfg!(all(target_arch = "x86", windows)) {
+            Convention::Stdcall
+        } else {
+            Convention::Cdecl
+        }
+    }
+
+    #[deprecated(note = "renamed to `Convention::native_system`")]
+    pub const fn system() -> Self {
+        Convention::native_system()
+    }
+
+    /// ARM32 下 `Cdecl` 实际使用的调用约定, 由编译目标的浮点 ABI 决定, 即 eabihf 下的 `AapcsVfp` 与其他目标下的 `AapcsSoftFloat`
+    pub const fn aapcs() -> Self {
+        if cfg!(target_abi = "eabihf") {
+            Convention::AapcsVfp
+        } else {
+            Convention::AapcsSoftFloat
+        }
+    }
+
+    /// 当前平台是否支持该调用约定
+    pub fn is_supported(self) -> bool {
+        self.backend().is_some()
+    }
+
+    /// 以该调用约定调用时使用的后端, 优先使用手写的汇编, 都不支持时返回 `None`
+    pub fn backend(self) -> Option<Backend> {
+        [Backend::Asm, Backend::Libffi]
+            .iter()
+            .copied()
+            .find(|backend| backend.supports(self))
+    }
+}
+
+/// 当前平台支持的所有调用约定, 按声明的顺序排列, 即 `Convention::is_supported` 为 `true` 的调用约定
+///
+/// 没有手写汇编也没有开启 `libffi` 的平台上为空, 此时所有调用都会返回 `CallError::UnsupportedTarget`,
+/// 跨平台的程序可以据此在运行时关闭依赖动态调用的功能
+///
+/// ```
+/// use funcall::{supported_conventions, Convention};
+///
+/// if supported_conventions().contains(&Convention::Cdecl) {
+///     // 启用插件
+/// }
+/// ```
+pub fn supported_conventions() -> &'static [Convention] {
+    use core::sync::atomic::{AtomicPtr, Ordering};
+
+    // 没有 std 时也要可用, 因此不使用 OnceLock. 并发初始化时只保留先写入的结果
+    static SUPPORTED: AtomicPtr<Vec<Convention>> = AtomicPtr::new(core::ptr::null_mut());
+    let mut ptr = SUPPORTED.load(Ordering::Acquire);
+    if ptr.is_null() {
+        let supported = Convention::ALL
+            .iter()
+            .copied()
+            .filter(|conv| conv.is_supported())
+            .collect::<Vec<_>>();
+        let new = alloc::boxed::Box::into_raw(alloc::boxed::Box::new(supported));
+        ptr = match SUPPORTED.compare_exchange(
+            core::ptr::null_mut(),
+            new,
+            Ordering::AcqRel,
+            Ordering::Acquire,
+        ) {
+            Ok(_) => new,
+            Err(current) => {
+                // SAFETY: new 来自上面的 Box::into_raw, 且没有写入 SUPPORTED
+                drop(unsafe { alloc::boxed::Box::from_raw(new) });
+                current
+            }
+        };
+    }
+    // SAFETY: 写入 SUPPORTED 的指针不会再被释放或修改
+    unsafe { &*ptr }
+}
+
+/// `Convention::Thiscall` 中 this 的传递方式与栈的清理者, 参见 `Func::set_thiscall_flavor`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Hash)]
+#[non_exhaustive]
+pub enum ThiscallFlavor {
+    /// MSVC 的 thiscall: this 通过 ecx 传递, 其余参数由被调用者清理
+    #[default]
+    Msvc,
+    /// 部分 GCC (MinGW) 版本的 thiscall: this 作为第一个栈参数, 由调用者清理, 即与 cdecl 相同
+    Gcc,
+}
+
+/// 实际发出调用的后端
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Hash)]
+#[non_exhaustive]
+pub enum Backend {
+    /// 手写的内联汇编
+    Asm,
+    /// 根据压入的参数类型构造 libffi 的 CIF, 需要启用 `libffi` feature
+    Libffi,
+    /// 运行时生成的调用跳板, 参见 `Signature::compile_with`. 不会被自动选用, 需要 `std` feature
+    Jit,
+}
 
-type Result<T> = std::io::Result<T>;
+impl Backend {
+    /// 当前平台上该后端是否支持 conv
+    pub fn supports(self, conv: Convention) -> bool {
+        match (self, conv) {
+            (backend, Convention::System) => backend.supports(Convention::native_system()),
+            (Backend::Asm, conv) => <native::Native as NativeCall>::supports(conv),
+            (Backend::Libffi, Convention::Cdecl) => cfg!(feature = "libffi"),
+            (Backend::Libffi, Convention::Stdcall) => {
+                cfg!(all(feature = "libffi", target_arch = "x86"))
+            }
+            (Backend::Jit, Convention::Cdecl) => {
+                cfg!(all(
+                    feature = "std",
+                    target_arch = "x86_64",
+                    target_os = "linux"
+                ))
+            }
+            (Backend::Jit, Convention::Stdcall) => false,
+            (Backend::Libffi, Convention::Thiscall) | (Backend::Jit, Convention::Thiscall) => false,
+            (Backend::Libffi, Convention::Fastcall) | (Backend::Jit, Convention::Fastcall) => false,
+            (Backend::Libffi, Convention::AapcsSoftFloat) => {
+                cfg!(all(feature = "libffi", target_arch = "arm"))
+            }
+            (Backend::Libffi, Convention::AapcsVfp) => cfg!(all(
+                feature = "libffi",
+                target_arch = "arm",
+                target_abi = "eabihf"
+            )),
+            (Backend::Jit, Convention::AapcsSoftFloat | Convention::AapcsVfp) => false,
+        }
+    }
+}
 
+/// 派生的 `PartialEq` 与 `PartialOrd` 比较的是全部状态, 包括已压入的参数;
+/// 只关心调用的是哪个函数时使用 `Func::target`, `Func::same_target` 或 `ByTarget`
+///
 /// # 示例
 ///
 /// ```ignore
-/// use funcall::Func;
+/// use funcall::libc_func;
 ///
-/// let mut func = Func::new("/usr/lib/libc.so.6", b"printf\0").unwrap();
-/// func.push(b"%d".as_ptr());
-/// func.push(2233);
+/// let mut func = libc_func("printf").unwrap();
+/// func.push(b"%d".as_ptr()).push(2233);
 /// unsafe {
 ///     func.cdecl();
 /// }
 /// ```
-#[derive(Debug, Clone, PartialOrd, PartialEq)]
+#[derive(PartialOrd, PartialEq)]
 pub struct Func {
     /// 被调用函数指针
     func: *const fn(),
-    /// 32位下储存所有参数, 64位下储存所有整数参数与除前八个外的浮点参数
-    args: Vec<usize>,
-    /// 64位下储存前八个浮点参数
-    fargs: Vec<f64>,
-    /// 返回值低位
-    ret_low: usize,
-    /// 返回值高位
-    ret_high: usize,
-    /// 浮点寄存器的值
-    ret_float: f64,
+    /// 32位与64位 Windows 下储存所有参数, 64位 Linux 下储存所有整数参数与除前八个外的浮点参数
+    args: InlineVec<usize, 8>,
+    /// 64位 Linux 下储存前八个浮点参数
+    fargs: InlineVec<f64, 8>,
+    /// 每个已压入的参数的类型与储存位置
+    slots: InlineVec<ArgSlot, 8>,
+    /// 声明的固定参数个数与是否为可变参数函数
+    arity: Option<(usize, bool)>,
+    /// 声明的返回值类型, 为 `None` 时视为与指针等宽的整数
+    ret_kind: Option<ArgKind>,
+    /// 通过 `set_restype` 或 `set_signature` 声明的返回值的 C 类型, `ret` 会据此转换返回值
+    restype: Option<CType>,
+    /// 上一次调用的返回值
+    ret: RetValues,
+    /// 是否已经调用过
+    called: bool,
+    /// 是否在 `try_call` 前检查函数指针是否指向可执行的内存
+    paranoid: bool,
+    /// 通过 `set_signature` 附加的原型, 附加后压入的参数都会按其检查
+    signature: Option<Arc<Signature>>,
+    /// 检查模式下第一个被拒绝的参数的位置与错误, 会在调用时报告
+    rejected: Option<(usize, CallError)>,
+    /// `call_with_timeout` 超时后遗留的调用
+    #[cfg(feature = "std")]
+    orphan: Option<timeout::Orphan>,
+    /// 栈上参数所占字节数的上限
+    max_stack: usize,
+    /// 函数所在的库, 持有它以防止库被提前卸载
+    #[cfg(feature = "loader")]
+    lib: Option<Library>,
+    /// 查找时使用的符号名
+    symbol: Option<String>,
+    /// 32 位 Windows 下修饰名 `_name@N` / `@name@N` 中的参数字节数, 调用前与实际压入的字节数比较
+    #[cfg(all(feature = "loader", windows, target_arch = "x86"))]
+    decorated_bytes: Option<usize>,
+    /// 通过 `new_lazy` 创建且尚未查找时记录的库与符号
+    #[cfg(feature = "loader")]
+    pending: Option<Arc<lazy::Pending>>,
+    /// 通过 `push_arg` 压入的字节与字符串以及输出参数, 参数中只保存了指向它们的指针
+    owned: Vec<Arc<[u8]>>,
+    /// owned 中由 `push_cstring_array` 构造的指针数组的位置, 数组中的指针指向 owned 中的其他缓冲区
+    tables: Vec<usize>,
+    /// owned 中通过 `own_aligned` 分配的缓冲区的位置与对齐, 内容从其中第一个对齐的位置开始
+    aligned: Vec<(usize, usize)>,
+    /// 调用统计, 未开启计时时为 `None`
+    #[cfg(feature = "std")]
+    stats: Option<CallStats>,
+    /// 调用结果的缓存, 未开启时为 `None`
+    #[cfg(feature = "std")]
+    memo: Option<Box<memo::Memo>>,
+    /// 栈上参数的排列顺序
+    order: ArgOrder,
+    /// 通过 `set_return` 声明的结构体返回值
+    aggregate: Option<sret::RetBuf>,
+    /// 调试用的参数布局
+    layout: LayoutOverride,
+    /// 是否在调用前后保存与恢复浮点控制状态
+    protect_fp: bool,
+    /// thiscall 中 this 的传递方式
+    thiscall_flavor: ThiscallFlavor,
+    /// 展开到达调用帧时的处理方式
+    unwind_policy: UnwindPolicy,
+}
+
+// Func 中的裸指针只是函数的地址, 库由 Library 以引用计数的方式持有, 两者都可以安全地在线程间移动.
+// 压入的参数只以数值的形式保存, 调用本身是 unsafe 的, 指针参数的跨线程有效性由调用者保证
+unsafe impl Send for Func {}
+
+/// 克隆得到的 `Func` 可以与原来的分别压入参数和调用, 互不影响
+///
+/// 所在的库以引用计数的方式共享. 通过 `push_arg` 复制的字节与字符串会再复制一份, 指向它们的参数随之改为指向新的副本,
+/// 被调用者对其中一份的修改不会出现在另一份中. 克隆的返回值与调用统计被清零,
+/// `memoize` 缓存的结果则会保留;
+/// `call_with_timeout` 超时后遗留的调用仍会使克隆报告 `CallError::Poisoned`, 因为它可能还在访问相同的指针参数
+impl Clone for Func {
+    fn clone(&self) -> Self {
+        let mut func = self.shallow_clone();
+        func.ret = RetValues::default();
+        func.called = false;
+        #[cfg(feature = "std")]
+        func.reset_stats();
+        #[cfg(feature = "std")]
+        func.reset_cache_stats();
+        func.copy_owned([0; 4]);
+        func
+    }
+}
+
+/// `Func::target` 返回的调用目标, 可以作为 `HashMap` 等容器的键
+///
+/// 由函数地址与所在库的句柄组成. 同一个库即使通过不同的 `Library` 打开, 句柄也相同;
+/// 通过 `Func::from_raw` 等创建的 `Func` 没有库, 因此与从库中查找得到的同一地址的 `Func` 不相等
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Target {
+    /// 函数地址
+    pub addr: usize,
+    /// 所在库的原始句柄, 未知时为 `None`
+    pub lib: Option<usize>,
+}
+
+/// 只按 `Func::target` 比较与哈希的 `Func`, 可以直接放入 `HashSet` 或作为 `HashMap` 的键
+///
+/// ```
+/// use funcall::{ByTarget, Func};
+/// use std::collections::HashSet;
+/// extern "C" fn add(a: i32, b: i32) -> i32 {
+///     a + b
+/// }
+///
+/// let mut a = Func::from_raw(add as *const fn());
+/// a.push(1i32);
+/// let b = Func::from_raw(add as *const fn());
+///
+/// let mut set = HashSet::new();
+/// assert!(set.insert(ByTarget(a)));
+/// assert!(!set.insert(ByTarget(b.clone())));
+/// assert!(set.contains(&ByTarget(b)));
+/// ```
+#[derive(Debug, Clone)]
+pub struct ByTarget(pub Func);
+
+impl PartialEq for ByTarget {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.same_target(&other.0)
+    }
+}
+
+impl Eq for ByTarget {}
+
+impl core::hash::Hash for ByTarget {
+    fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
+        self.0.target().hash(state);
+    }
 }
 
 impl Func {
     /// 从 lib 中加载一个函数, 注意 func 需要以 '\0' 结尾
+    #[cfg(feature = "loader")]
     pub fn new<P: AsRef<OsStr>>(lib: P, func: &[u8]) -> Result<Self> {
         // TODO: 是否需要先尝试 dlopen / GetModuleHandle 来节省时间? (待确认
-        let lib = libloading::Library::new(lib)?;
-        unsafe {
-            let func = lib.get::<fn()>(func)?;
-            Ok(Self {
-                func: *func.into_raw() as *const fn(),
-                args: Vec::new(),
-                fargs: Vec::new(),
-                ret_low: 0,
-                ret_high: 0,
-                ret_float: 0.0,
-            })
-        }
+        Library::new(lib)?.get_bytes(func)
+    }
+
+    /// 依次尝试从 libs 中的库加载函数, 返回第一个成功的结果及其库名, 参见 `Library::open_first`
+    ///
+    /// 库加载失败或其中找不到该函数时都会继续尝试下一个
+    #[cfg(feature = "loader")]
+    pub fn new_first<'a, P: AsRef<OsStr>>(
+        libs: &'a [P],
+        func: &[u8],
+    ) -> core::result::Result<(Self, &'a OsStr), OpenFirstError> {
+        library::open_first(libs, |lib| Library::new(lib)?.get_bytes(func))
+    }
+
+    /// 从 lib 中加载一个 C++ 函数, 会根据参数类型生成 Itanium ABI (GCC / Clang) 下的修饰名再查找
+    ///
+    /// 例如 `Func::new_cpp("libfoo.so", "foo", &[CType::Int, CType::Double])` 会查找 `_Z3fooid`,
+    /// 支持的类型见 `cpp::itanium_mangle`
+    #[cfg(feature = "loader")]
+    pub fn new_cpp<P: AsRef<OsStr>>(lib: P, name: &str, params: &[CType]) -> Result<Self> {
+        Library::new(lib)?.get_cpp(name, params)
+    }
+
+    /// 从已有的 dlopen / LoadLibrary 句柄中查找函数, 不获取句柄的所有权
+    ///
+    /// # Safety
+    ///
+    /// handle 必须是有效的库句柄, 且调用者需保证在返回的 `Func` 最后一次被调用之前不关闭它
+    #[cfg(feature = "loader")]
+    pub unsafe fn from_handle(handle: *mut c_void, symbol: &str) -> Result<Self> {
+        let mut func = Self::from_raw(library::lookup_in_handle(handle, symbol.as_bytes())?);
+        func.symbol = Some(symbol.to_owned());
+        Ok(func)
+    }
+
+    /// 以 `dlsym(RTLD_NEXT, symbol)` 查找函数, 即加载顺序中位于调用者所在模块之后的下一个同名定义
+    ///
+    /// 用于 LD_PRELOAD 等拦截场景中调用被覆盖的原始函数, 如在自己的 `malloc` 中调用真正的 `malloc`.
+    /// RTLD_NEXT 是相对于调用 `dlsym` 的代码所在的模块而言的, 因此 funcall 需要静态链接进拦截用的动态库,
+    /// 并在其中调用本函数; 在主程序中调用时只会跳过主程序本身
+    ///
+    /// ```
+    /// use funcall::{Convention, Func};
+    ///
+    /// // 主程序没有定义 getpid, 找到的是 C 运行库中的
+    /// let mut getpid = Func::next_symbol("getpid").unwrap();
+    /// unsafe { getpid.try_call(Convention::Cdecl).unwrap() };
+    /// assert_eq!(getpid.ret_as_u32(), std::process::id());
+    /// ```
+    #[cfg(all(feature = "loader", unix))]
+    pub fn next_symbol(symbol: &str) -> Result<Self> {
+        unsafe { Self::from_handle(library::RTLD_NEXT, symbol) }
+    }
+
+    /// 以 `dlsym(RTLD_DEFAULT, symbol)` 按加载顺序在全局范围内查找函数
+    ///
+    /// 与 `Library::libc` 的查找方式相同, 但返回的 `Func` 不持有任何库的引用
+    #[cfg(all(feature = "loader", unix))]
+    pub fn default_symbol(symbol: &str) -> Result<Self> {
+        unsafe { Self::from_handle(library::RTLD_DEFAULT, symbol) }
+    }
+
+    /// 同 `from_handle`, 但获取句柄的所有权, 返回的 `Func` 被 drop 时会关闭句柄
+    ///
+    /// 若需要从同一句柄中查找多个函数, 请使用 `Library::from_raw`
+    ///
+    /// # Safety
+    ///
+    /// handle 必须是有效的库句柄, 且此后不能再由其他人关闭
+    #[cfg(feature = "loader")]
+    pub unsafe fn from_handle_owned(handle: *mut c_void, symbol: &str) -> Result<Self> {
+        Library::from_raw(handle).get(symbol)
+    }
+
+    /// 不指向任何函数的实例, 用于表示可选的函数不存在
+    ///
+    /// 可以照常压入参数, 但 `try_call` 等会返回 `CallError::NullTarget` 而不会真的调用
+    pub fn null() -> Self {
+        Self::from_raw(core::ptr::null())
+    }
+
+    /// 函数指针是否为空, 参见 `Func::null`
+    pub fn is_null(&self) -> bool {
+        self.func.is_null()
     }
 
     /// 根据函数指针创建一个实例
     pub fn from_raw(ptr: *const fn()) -> Self {
         Self {
             func: ptr,
-            args: Vec::new(),
-            fargs: Vec::new(),
-            ret_low: 0,
-            ret_high: 0,
-            ret_float: 0.0,
-        }
-    }
-
-    /// 压入参数
-    pub fn push<T: IntoArg + Any>(&mut self, arg: T) {
-        unsafe {
-            // 64位下前八个浮点数需要用 xmm0~xmm7 传递
-            if cfg!(target_arch = "x86_64") && self.fargs.len() != 8 {
-                if arg.type_id() == TypeId::of::<f32>() {
-                    return self
-                        .fargs
-                        .push(f64::from(mem::transmute_copy::<T, f32>(&arg)));
-                } else if arg.type_id() == TypeId::of::<f64>() {
-                    return self.fargs.push(mem::transmute_copy::<T, f64>(&arg));
-                }
-            }
-            self.args.extend_from_slice(&arg.into_arg());
+            args: InlineVec::new(),
+            fargs: InlineVec::new(),
+            slots: InlineVec::new(),
+            arity: None,
+            ret_kind: None,
+            restype: None,
+            ret: RetValues::default(),
+            called: false,
+            paranoid: false,
+            signature: None,
+            rejected: None,
+            #[cfg(feature = "std")]
+            orphan: None,
+            max_stack: DEFAULT_MAX_STACK,
+            #[cfg(feature = "loader")]
+            lib: None,
+            symbol: None,
+            #[cfg(all(feature = "loader", windows, target_arch = "x86"))]
+            decorated_bytes: None,
+            #[cfg(feature = "loader")]
+            pending: None,
+            owned: Vec::new(),
+            tables: Vec::new(),
+            aligned: Vec::new(),
+            #[cfg(feature = "std")]
+            stats: None,
+            #[cfg(feature = "std")]
+            memo: None,
+            order: ArgOrder::RightToLeft,
+            aggregate: None,
+            layout: LayoutOverride::Abi,
+            protect_fp: false,
+            thiscall_flavor: ThiscallFlavor::Msvc,
+            unwind_policy: UnwindPolicy::Abort,
         }
     }
 
-    /// 以 cdecl 调用约定调用函数
-    /// 即 C 语言默认使用的调用约定
-    #[cfg(target_arch = "x86")]
-    pub unsafe fn cdecl(&mut self) {
-        rusty_asm! {
-            let mut low  : usize: out("{eax}");
-            let mut high : usize: out("{edx}");
-            let mut float: f64  : out("{st}");
-            // 参数从右往左入栈, 因此先取得最右边的地址
-            let args: in("r") = self.args.as_ptr().wrapping_offset(self.args.len() as isize - 1);
-            let len : in("m") = self.args.len();
-            let func: in("m") = self.func;
+    /// 从对象的虚函数表中取出第 index 个函数, 并将 this 作为第一个参数压入
+    ///
+    /// 对象的第一个字段须为指向虚函数表的指针, 如 COM 对象与单继承的 C++ 对象.
+    /// 之后压入的参数都排在 this 之后. COM 方法需要以 `Convention::native_system()` 调用,
+    /// 其他对象按其方法声明的调用约定调用, 32 位 MSVC 下的 C++ 成员函数默认为 `Convention::Thiscall`
+    ///
+    /// ```
+    /// use funcall::{Convention, Func};
+    /// use std::ffi::c_void;
+    ///
+    /// #[repr(C)]
+    /// struct Counter {
+    ///     vtable: *const [extern "system" fn(*mut Counter) -> i32; 1],
+    ///     count: i32,
+    /// }
+    /// extern "system" fn next(this: *mut Counter) -> i32 {
+    ///     unsafe {
+    ///         (*this).count += 1;
+    ///         (*this).count
+    ///     }
+    /// }
+    ///
+    /// let vtable = [next as extern "system" fn(*mut Counter) -> i32];
+    /// let mut counter = Counter { vtable: &vtable, count: 0 };
+    /// let mut func = unsafe { Func::from_vtable(&mut counter as *mut Counter as *mut c_void, 0) };
+    /// let ret = unsafe { func.invoke(Convention::native_system()) }.unwrap();
+    /// assert_eq!(ret.as_i32(), 1);
+    /// ```
+    ///
+    /// # Safety
+    ///
+    /// this 必须指向有效的对象, 其虚函数表中至少有 index + 1 个函数
+    pub unsafe fn from_vtable(this: *mut c_void, index: usize) -> Self {
+        Self::from_vtable_adjusted(this, 0, index, 0)
+    }
+
+    /// 同 `from_vtable`, 但用于多重继承的对象, 虚函数表指针不在对象开头
+    ///
+    /// 以 `obj + vtable_offset` 处的子对象为准: 从该处读出虚函数表并取出第 index 个函数,
+    /// 压入的 this 为子对象的地址再加上 this_displacement. vtable_offset 即基类子对象在对象中的偏移;
+    /// this_displacement 为被调用的函数所期望的 this 与子对象之间的差值, 可以为负,
+    /// 例如 MSVC 下不经过 thunk 的覆盖函数需要调回派生类的起始地址. 表中的项本身是 thunk 时, 它会自行调整 this, 此时应传入 0
+    ///
+    /// ```
+    /// use funcall::{Convention, Func};
+    /// use std::ffi::c_void;
+    ///
+    /// #[repr(C)]
+    /// struct Derived {
+    ///     vtable_a: *const [usize; 1],
+    ///     vtable_b: *const [extern "system" fn(*const Derived) -> usize; 1],
+    /// }
+    /// extern "system" fn this_addr(this: *const Derived) -> usize {
+    ///     this as usize
+    /// }
+    ///
+    /// let vtable_b = [this_addr as extern "system" fn(*const Derived) -> usize];
+    /// let obj = Derived { vtable_a: &[0], vtable_b: &vtable_b };
+    /// let this = &obj as *const Derived as *mut c_void;
+    /// let offset = std::mem::size_of::<usize>();
+    /// // 从第二个基类的虚函数表中调用, 函数期望的是整个对象的地址
+    /// let mut func = unsafe { Func::from_vtable_adjusted(this, offset, 0, -(offset as isize)) };
+    /// let ret = unsafe { func.invoke(Convention::native_system()) }.unwrap();
+    /// assert_eq!(ret.as_usize(), this as usize);
+    /// ```
+    ///
+    /// # Safety
+    ///
+    /// obj + vtable_offset 处必须是指向虚函数表的指针, 表中至少有 index + 1 个函数,
+    /// 调整后的 this 必须仍位于同一个对象中
+    pub unsafe fn from_vtable_adjusted(
+        obj: *mut c_void,
+        vtable_offset: usize,
+        index: usize,
+        this_displacement: isize,
+    ) -> Self {
+        let subobject = (obj as *mut u8).add(vtable_offset);
+        let vtable = *(subobject as *const *const *const fn());
+        let mut func = Self::from_raw(*vtable.add(index));
+        func.insert_front(subobject.offset(this_displacement) as *mut c_void);
+        func
+    }
 
-            clobber("memory");
-            clobber("esp");
-            clobber("ebx");
+    /// 根据函数指针创建一个实例, 并依次压入 args 中的参数
+    ///
+    /// ```
+    /// use funcall::{Arg, Func};
+    /// extern "C" fn add(a: i32, b: i32) -> i32 {
+    ///     a + b
+    /// }
+    ///
+    /// let mut func = Func::from_raw_with(add as *const fn(), vec![Arg::I32(1), Arg::I32(2)]);
+    /// unsafe { func.cdecl() };
+    /// assert_eq!(func.ret_as_i32(), 3);
+    /// ```
+    pub fn from_raw_with(ptr: *const fn(), args: impl IntoIterator<Item = Arg>) -> Self {
+        let mut func = Self::from_raw(ptr);
+        for arg in args {
+            func.push_arg(arg);
+        }
+        func
+    }
 
-            asm("intel") {r"
-                mov  ebx, $len  // 将 $4 个参数依次压栈
-                dec  ebx
-            .L${:uid}:          // https://github.com/rust-lang/rust/issues/27395
-                push dword ptr [$args]
-                sub  $args, 4
-                dec  ebx
-                jns  .L${:uid}
+    /// 同 `new`, 但库中没有该函数时返回 `Func::null()` 而不是报错, 库本身无法加载时仍然报错
+    ///
+    /// ```
+    /// use funcall::{CallError, Convention, Func};
+    ///
+    /// # #[cfg(target_os = "linux")]
+    /// # {
+    /// let mut func = Func::new_optional("libc.so.6", b"no_such_function\0").unwrap();
+    /// assert!(func.is_null());
+    /// assert_eq!(unsafe { func.try_call(Convention::Cdecl) }, Err(CallError::NullTarget));
+    /// # }
+    /// ```
+    #[cfg(feature = "loader")]
+    pub fn new_optional<P: AsRef<OsStr>>(lib: P, func: &[u8]) -> Result<Self> {
+        Ok(Library::new(lib)?.get_optional_bytes(func))
+    }
 
-                call $func      // 调用函数
+    /// 从 lib 中加载一个函数, 并依次压入 args 中的参数, 注意 func 需要以 '\0' 结尾
+    #[cfg(feature = "loader")]
+    pub fn new_with<P: AsRef<OsStr>>(
+        lib: P,
+        func: &[u8],
+        args: impl IntoIterator<Item = Arg>,
+    ) -> Result<Self> {
+        let mut func = Self::new(lib, func)?;
+        for arg in args {
+            func.push_arg(arg);
+        }
+        Ok(func)
+    }
 
-                mov  ebx, $len  // 恢复堆栈指针
-                lea  esp, [esp + ebx * 4]
-            "}
+    /// 获取函数指针
+    pub fn as_raw(&self) -> *const fn() {
+        self.func
+    }
 
-            self.ret_low   = low;
-            self.ret_high  = high;
-            self.ret_float = float;
+    /// 将函数指针换为 ptr, 已压入的参数 (包括 `push_arg` 复制的字节与字符串) 与声明的参数和返回值类型保持不变
+    ///
+    /// 上一次调用的返回值被清零. 查找时记下的符号名被清除, 之后 `ensure_fresh` 不会再按旧的符号重新查找;
+    /// 函数来自某个库时仍持有该库, 以免新的地址恰好位于其中时库被提前卸载
+    ///
+    /// ```
+    /// use funcall::{Convention, Func};
+    /// extern "C" fn add(a: i32, b: i32) -> i32 {
+    ///     a + b
+    /// }
+    /// extern "C" fn sub(a: i32, b: i32) -> i32 {
+    ///     a - b
+    /// }
+    ///
+    /// let mut func = Func::from_raw(add as *const fn());
+    /// func.push(5i32).push(3i32);
+    /// assert_eq!(unsafe { func.invoke(Convention::Cdecl) }.unwrap().as_i32(), 8);
+    /// func.set_target(sub as *const fn());
+    /// assert_eq!(unsafe { func.invoke(Convention::Cdecl) }.unwrap().as_i32(), 2);
+    /// ```
+    pub fn set_target(&mut self, ptr: *const fn()) {
+        self.func = ptr;
+        self.symbol = None;
+        #[cfg(all(feature = "loader", windows, target_arch = "x86"))]
+        {
+            self.decorated_bytes = None;
         }
+        self.ret = RetValues::default();
+        self.called = false;
     }
 
-    /// 64 位 Linux 默认使用的调用约定
-    #[cfg(all(target_arch = "x86_64", target_os = "linux"))]
-    pub unsafe fn cdecl(&mut self) {
-        rusty_asm! {
-            let mut low  : usize: out("{rax}");
-            let mut high : usize: out("{rdx}");
-            let mut float: f64  : out("{xmm0}"); // https://github.com/rust-lang/rust/issues/20213
-
-            let args : in("r") = self.args.as_ptr().wrapping_offset(self.args.len() as isize - 1);
-            let len  : in("r") = self.args.len();
-            let fargs: in("r") = self.fargs.as_ptr().wrapping_offset(self.fargs.len() as isize - 1);
-            let flen : in("r") = self.fargs.len();
-            let func : in("m") = self.func;
-
-            clobber("memory");
-            clobber("rsp");
-
-            clobber("rdi"); // 传参寄存器
-            clobber("rsi");
-            clobber("rdx");
-            clobber("rcx");
-            clobber("r8");
-            clobber("r9");
-
-            clobber("r10"); // 调用者保护
-            clobber("r11"); // 调用者保护
-            clobber("r12");
-
-            asm("alignstack", "intel") {r"
-                // 需要送入寄存器的浮点参数个数一定不大于 8, 因此直接查表跳转即可
-                lea    rdi, [rip + .LFLABELS${:uid}]
-                movsxd rsi, dword ptr [rdi + $flen * 4]
-                add    rsi, rdi
-                jmp    rsi
-
-            .LFLABELS${:uid}:
-                .long .LARG0${:uid}-.LFLABELS${:uid}
-                .long .LARG1${:uid}-.LFLABELS${:uid}
-                .long .LARG2${:uid}-.LFLABELS${:uid}
-                .long .LARG3${:uid}-.LFLABELS${:uid}
-                .long .LARG4${:uid}-.LFLABELS${:uid}
-                .long .LARG5${:uid}-.LFLABELS${:uid}
-                .long .LARG6${:uid}-.LFLABELS${:uid}
-                .long .LARG7${:uid}-.LFLABELS${:uid}
-                .long .LARG8${:uid}-.LFLABELS${:uid}
-
-            .LARG8${:uid}:
-                movsd xmm7, qword ptr [$fargs]
-                sub   $fargs, 8
-            .LARG7${:uid}:
-                movsd xmm6, qword ptr [$fargs]
-                sub   $fargs, 8
-            .LARG6${:uid}:
-                movsd xmm5, qword ptr [$fargs]
-                sub   $fargs, 8
-            .LARG5${:uid}:
-                movsd xmm4, qword ptr [$fargs]
-                sub   $fargs, 8
-            .LARG4${:uid}:
-                movsd xmm3, qword ptr [$fargs]
-                sub   $fargs, 8
-            .LARG3${:uid}:
-                movsd xmm2, qword ptr [$fargs]
-                sub   $fargs, 8
-            .LARG2${:uid}:
-                movsd xmm1, qword ptr [$fargs]
-                sub   $fargs, 8
-            .LARG1${:uid}:
-                movsd xmm0, qword ptr [$fargs]
-            .LARG0${:uid}:
-
-                // r12 = $len <= 6 ? 0 : ($len - 6)
-                lea    r12, [$len - 6]
-                cmp    $len, 6
-                mov    rdi, 0
-                cmovbe r12, rdi
-                jbe    .LPUSH_F6${:uid}
-
-                // 栈对齐, 暂时不知道原因不过不对齐会出错
-                test   r12, 1   // if r12 % 2 == 0
-                je     .LPUSH${:uid}
-                sub    rsp, 8
-            .LPUSH${:uid}:       // 将参数压栈, 直到参数个数小于等于 6
-                push   qword ptr [$args]
-                sub    $args, 8
-                sub    $len, 1
-                cmp    $len, 6   // if $len != 6
-                jne    .LPUSH${:uid}
-
-            .LPUSH_F6${:uid}:    // 将前六个参数送入寄存器
-                lea    rdi, [rip + .LABELS${:uid}]
-                movsxd rsi, dword ptr [rdi + $len * 4]
-                add    rsi, rdi
-                jmp    rsi
-
-            .LABELS${:uid}:
-                .long .LCALL${:uid}-.LABELS${:uid}
-                .long .L1${:uid}-.LABELS${:uid}
-                .long .L2${:uid}-.LABELS${:uid}
-                .long .L3${:uid}-.LABELS${:uid}
-                .long .L4${:uid}-.LABELS${:uid}
-                .long .L5${:uid}-.LABELS${:uid}
-                .long .L6${:uid}-.LABELS${:uid}
-
-            .L6${:uid}:
-                mov  r9, qword ptr [$args]
-                sub  $args, 8
-            .L5${:uid}:
-                mov  r8, qword ptr [$args]
-                sub  $args, 8
-            .L4${:uid}:
-                mov  rcx, qword ptr [$args]
-                sub  $args, 8
-            .L3${:uid}:
-                mov  rdx, qword ptr [$args]
-                sub  $args, 8
-            .L2${:uid}:
-                mov  rsi, qword ptr [$args]
-                sub  $args, 8
-            .L1${:uid}:
-                mov  rdi, qword ptr [$args]
-
-            .LCALL${:uid}:
-                mov  rax, $flen
-                call $func
-
-                // 清理堆栈
-                lea  rsp, [rsp + r12 * 8]
-                test r12, 1
-                je   .LNOALIGN2${:uid}
-                add  rsp, 8
-            .LNOALIGN2${:uid}:
-            "}
-
-            self.ret_low   = low;
-            self.ret_high  = high;
-            self.ret_float = float;
+    /// 同 `set_target`, 但获取并返回自身
+    pub fn with_target(mut self, ptr: *const fn()) -> Self {
+        self.set_target(ptr);
+        self
+    }
+
+    /// 调用的目标, 只由函数地址与所在的库决定
+    ///
+    /// `Func` 的 `PartialEq` 会同时比较已压入的参数与声明的类型等状态, 浮点参数还使其无法实现 `Eq`;
+    /// 需要按调用的函数去重或查找时应以本方法的返回值为键
+    ///
+    /// ```
+    /// use funcall::Func;
+    /// use std::collections::HashMap;
+    /// extern "C" fn add(a: i32, b: i32) -> i32 {
+    ///     a + b
+    /// }
+    ///
+    /// let mut a = Func::from_raw(add as *const fn());
+    /// a.push(1i32);
+    /// let b = Func::from_raw(add as *const fn());
+    /// assert!(a != b);
+    /// assert!(a.same_target(&b));
+    ///
+    /// let mut map = HashMap::new();
+    /// map.insert(a.target(), a);
+    /// map.insert(b.target(), b);
+    /// assert_eq!(map.len(), 1);
+    /// ```
+    pub fn target(&self) -> Target {
+        Target {
+            addr: self.func as usize,
+            #[cfg(feature = "loader")]
+            lib: self.lib.as_ref().map(|lib| lib.as_raw() as usize),
+            #[cfg(not(feature = "loader"))]
+            lib: None,
         }
     }
 
-    /// 以 stdcall 调用约定调用函数
-    /// 即 32 位下 WINAPI 使用的调用约定
-    #[cfg(target_arch = "x86")]
-    pub unsafe fn stdcall(&mut self) {
-        rusty_asm! {
-            let mut low  : usize: out("{eax}");
-            let mut high : usize: out("{edx}");
-            let mut float: f64  : out("{st}");
-            // 参数从右往左入栈, 因此先取得最右边的地址
-            let args: in("r") = self.args.as_ptr().wrapping_offset(self.args.len() as isize - 1);
-            let len : in("m") = self.args.len();
-            let func: in("m") = self.func;
+    /// 两者是否调用同一个函数, 即 `target` 是否相等, 不比较已压入的参数
+    pub fn same_target(&self, other: &Func) -> bool {
+        self.target() == other.target()
+    }
 
-            clobber("memory");
-            clobber("esp");
-            clobber("ebx");
+    /// 逐个字段复制, 与原来的 `Func` 共享 `push_arg` 复制的缓冲区, 并保留返回值
+    pub(crate) fn shallow_clone(&self) -> Self {
+        Self {
+            func: self.func,
+            args: self.args.clone(),
+            fargs: self.fargs.clone(),
+            slots: self.slots.clone(),
+            arity: self.arity,
+            ret_kind: self.ret_kind,
+            restype: self.restype.clone(),
+            ret: self.ret,
+            called: self.called,
+            paranoid: self.paranoid,
+            signature: self.signature.clone(),
+            rejected: self.rejected.clone(),
+            #[cfg(feature = "std")]
+            orphan: self.orphan.clone(),
+            max_stack: self.max_stack,
+            #[cfg(feature = "loader")]
+            lib: self.lib.clone(),
+            symbol: self.symbol.clone(),
+            #[cfg(all(feature = "loader", windows, target_arch = "x86"))]
+            decorated_bytes: self.decorated_bytes,
+            #[cfg(feature = "loader")]
+            pending: self.pending.clone(),
+            owned: self.owned.clone(),
+            tables: self.tables.clone(),
+            aligned: self.aligned.clone(),
+            #[cfg(feature = "std")]
+            stats: self.stats,
+            #[cfg(feature = "std")]
+            memo: self.memo.clone(),
+            order: self.order,
+            aggregate: self.aggregate,
+            layout: self.layout,
+            protect_fp: self.protect_fp,
+            thiscall_flavor: self.thiscall_flavor,
+            unwind_policy: self.unwind_policy,
+        }
+    }
 
-            asm("intel") {r"
-                mov  ebx, $len  // 将 $4 个参数依次压栈
-                dec  ebx
-            .L${:uid}:          // https://github.com/rust-lang/rust/issues/27395
-                push dword ptr [$args]
-                sub  $args, 4
-                dec  ebx
-                jns  .L${:uid}
+    /// 查找函数时实际匹配到的符号名
+    ///
+    /// 在 32 位 Windows 下可能是修饰过的名字, 如 `_Foo@12`
+    pub fn symbol_name(&self) -> Option<&str> {
+        self.symbol.as_deref()
+    }
 
-                call $func      // 调用函数
-            "}
+    /// 若函数来自通过 `Library::open_reloadable` 打开的库且库文件已发生变化,
+    /// 则重新加载库并重新查找函数, 已压入的参数保持不变
+    ///
+    /// 返回是否发生了重新加载
+    #[cfg(feature = "loader")]
+    pub fn ensure_fresh(&mut self) -> Result<bool> {
+        let lib = match self.lib.as_ref().map(Library::reloaded) {
+            Some(lib) => lib?,
+            None => None,
+        };
+        match (lib, &self.symbol) {
+            (Some(lib), Some(symbol)) => {
+                let func = lib.get(symbol)?;
+                self.func = func.func;
+                self.lib = func.lib;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
 
-            self.ret_low   = low;
-            self.ret_high  = high;
-            self.ret_float = float;
+    /// 压入参数, 返回自身以便链式调用
+    ///
+    /// f32 默认像可变参数那样提升为 double; 若已通过 `set_arity` 声明了参数个数,
+    /// 固定参数位置上的 f32 则与 `push_float` 相同, 以 C float 传递.
+    /// 通过 `set_signature` 附加了原型时按 `try_push` 检查, 被拒绝的参数不会压入, 错误在调用时由 `try_call` 报告
+    #[cfg_attr(feature = "provenance", track_caller)]
+    pub fn push<T: IntoArg>(&mut self, arg: T) -> &mut Self {
+        if self.signature.is_none() {
+            return self.push_raw(arg);
         }
+        let start = self.slots.len();
+        self.push_declared(|func| func.try_push(arg).map(drop));
+        self.mark_origin(start)
     }
-}
 
-impl Func {
-    pub fn ret_as_i8(&self) -> i8 {
-        self.ret_low as i8
+    /// 不经检查地压入参数, 即使已经附加了原型
+    #[cfg_attr(feature = "provenance", track_caller)]
+    pub fn push_raw<T: IntoArg>(&mut self, arg: T) -> &mut Self {
+        let mut sink = ArgSink::new(T::KIND);
+        arg.push_into(&mut sink);
+        let start = self.slots.len();
+        self.push_sink(sink);
+        self.mark_origin(start)
     }
 
-    pub fn ret_as_u8(&self) -> u8 {
-        self.ret_low as u8
+    /// 按 `sink.kind` 放入写好的参数
+    ///
+    /// kind 来自 `IntoArg::KIND`, 内联后以下的分支在编译期就已确定.
+    /// 浮点数只看 kind, 转发了 f32 或 f64 实现的包装类型同样通过浮点寄存器传递
+    #[inline]
+    pub(crate) fn push_sink(&mut self, sink: ArgSink) -> &mut Self {
+        let align = plan::arg_align(sink.kind);
+        self.place_sink(sink, align)
     }
 
-    pub fn ret_as_i16(&self) -> i16 {
-        self.ret_low as i16
+    /// 以至少 align 字节的对齐压入参数, 对齐不足时先放入空位
+    ///
+    /// 各类型默认已按当前平台的 ABI 对齐, 如 64 位 Linux 下栈上的 i128 与 ARM32 (AAPCS) 下的 64 位参数,
+    /// 本方法用于 ABI 要求更大对齐的其他参数. 空位与参数一样占用寄存器或栈:
+    /// ARM32 下对齐到 8 字节的参数从偶数号寄存器开始, 栈上的参数则相对于栈顶对齐.
+    /// 64 位 Linux 下只有栈上的参数需要对齐, 通过寄存器传递的参数不受影响.
+    /// 与 `push_raw` 相同, 参数不经原型检查
+    ///
+    /// # Panics
+    ///
+    /// align 不是 2 的幂时 panic
+    #[cfg_attr(feature = "provenance", track_caller)]
+    pub fn push_aligned<T: IntoArg>(&mut self, arg: T, align: usize) -> &mut Self {
+        assert!(
+            align.is_power_of_two(),
+            "alignment {} is not a power of two",
+            align
+        );
+        let mut sink = ArgSink::new(T::KIND);
+        arg.push_into(&mut sink);
+        let align = align.max(plan::arg_align(T::KIND));
+        let start = self.slots.len();
+        self.place_sink(sink, align);
+        self.mark_origin(start)
     }
 
-    pub fn ret_as_u16(&self) -> u16 {
-        self.ret_low as u16
+    #[inline]
+    fn place_sink(&mut self, mut sink: ArgSink, align: usize) -> &mut Self {
+        let kind = sink.kind;
+        let float = matches!(kind, ArgKind::F32 | ArgKind::F64 | ArgKind::F16);
+        if kind == ArgKind::F32 && self.at_fixed_param() {
+            return self.place_float(sink.float() as f32, align);
+        }
+        // 64位 Linux 下前八个浮点数需要用 xmm0~xmm7 传递, s390x 下前四个用 f0~f6 传递, LoongArch64 下前八个用 fa0~fa7 传递,
+        // aarch64 下前八个用 v0~v7 传递, Windows 下则按位置与整数参数共用前四个位置
+        if float && plan::SEPARATE_FLOATS && self.float_regs_free() {
+            self.slots.push(ArgSlot {
+                kind,
+                float: true,
+                index: self.fargs.len(),
+                len: 1,
+                align,
+                #[cfg(feature = "provenance")]
+                origin: Origin(None),
+            });
+            self.fargs.push(sink.float());
+            return self;
+        }
+        // Apple arm64 与 clang 编译的 x86_64 代码中被调用者假定小于 32 位的整数已由调用者扩展,
+        // 第三方 `IntoArg` 写入的字的高位可能是任意值, 需要按 kind 扩展到整个字
+        if let Some(word) = sink.words.first_mut() {
+            *word = extend_word(kind, *word);
+        }
+        // 走到这里的浮点数除 Windows ARM64 的可变参数部分外只能通过栈传递
+        let index = self.place_words(sink.words(), self.int_regs_usable(float), align);
+        self.slots.push(ArgSlot {
+            kind,
+            float: false,
+            index,
+            len: sink.words.len(),
+            align,
+            #[cfg(feature = "provenance")]
+            origin: Origin(None),
+        });
+        self
     }
 
-    pub fn ret_as_i32(&self) -> i32 {
-        self.ret_low as i32
+    /// 将整数参数的各个字放入 args, 返回其起始位置
+    ///
+    /// 起始位置按 align 对齐, 不足时先放入空位. ARM32 下 args 的前四个字送入 r0~r3, MIPS64 下前八个字送入 a0~a7,
+    /// 对齐的位置即是偶数号寄存器或对齐的栈位置
+    #[cfg(not(any(
+        all(target_arch = "x86_64", not(windows)),
+        target_arch = "s390x",
+        target_arch = "aarch64"
+    )))]
+    fn place_words(&mut self, words: &[usize], _in_regs: bool, align: usize) -> usize {
+        if self.layout == LayoutOverride::StackOnly && self.args.len() < plan::INT_REGS.len() {
+            self.args
+                .extend_from_slice(&[0; 8][..plan::INT_REGS.len() - self.args.len()]);
+        }
+        let step = (align / mem::size_of::<usize>()).max(1);
+        while !self.args.len().is_multiple_of(step) {
+            self.args.push(0);
+        }
+        let index = self.args.len();
+        self.args.extend_from_slice(words);
+        index
     }
 
-    pub fn ret_as_u32(&self) -> u32 {
-        self.ret_low as u32
+    /// 将整数参数的各个字放入 args, 返回其起始位置
+    ///
+    /// 64 位 Linux 下 args 的前六个字送入寄存器 (s390x 下为五个, aarch64 下为八个), 其余的在栈上. 剩余的寄存器放不下的参数与多出的浮点参数
+    /// 整个都在栈上, 这时先用空位补满寄存器的部分, 之后的整数参数仍然依次填入空出的寄存器;
+    /// AAPCS64 中则一旦有整数参数放到了栈上, 之后的整数参数也都在栈上.
+    /// 栈上的参数按 align 对齐, 如 i128 与 u128 需要 16 字节对齐, aarch64 下它们在寄存器中也从偶数号寄存器开始
+    #[cfg(any(
+        all(target_arch = "x86_64", not(windows)),
+        target_arch = "s390x",
+        target_arch = "aarch64"
+    ))]
+    fn place_words(&mut self, words: &[usize], in_regs: bool, align: usize) -> usize {
+        let regs = plan::INT_REGS.len();
+        let mut used = if self.args.len() < regs {
+            self.args.len()
+        } else {
+            self.slots
+                .iter()
+                .filter(|slot| !slot.float && slot.index < regs)
+                .map(|slot| slot.index + slot.len)
+                .max()
+                .unwrap_or(0)
+        };
+        if cfg!(target_arch = "aarch64") {
+            let float = |kind| {
+                matches!(
+                    kind,
+                    ArgKind::F32 | ArgKind::F64 | ArgKind::CFloat | ArgKind::F16
+                )
+            };
+            let spilled = self
+                .slots
+                .iter()
+                .any(|slot| !slot.float && slot.index >= regs && !float(slot.kind));
+            used = if spilled {
+                regs
+            } else {
+                used.next_multiple_of((align / mem::size_of::<usize>()).max(1))
+            };
+        }
+        if in_regs && self.layout == LayoutOverride::Abi && used + words.len() <= regs {
+            let end = used + words.len();
+            if self.args.len() < end {
+                self.args.resize(end, 0);
+            }
+            self.args[used..end].copy_from_slice(words);
+            return used;
+        }
+        if self.args.len() < regs {
+            self.args.resize(regs, 0);
+        }
+        let step = (align / mem::size_of::<usize>()).max(1);
+        while !(self.args.len() - regs).is_multiple_of(step) {
+            self.args.push(0);
+        }
+        let index = self.args.len();
+        self.args.extend_from_slice(words);
+        index
     }
 
-    pub fn ret_as_i64(&self) -> i64 {
-        self.ret_as_u64() as i64
+    /// 以 C 语言的 float 类型压入 f32
+    ///
+    /// `push` 会像可变参数那样将 f32 提升为 double, 参数声明为 float 时需要使用本方法.
+    /// 附加了原型时则与 `push` 相同, 按声明的类型转换
+    #[cfg_attr(feature = "provenance", track_caller)]
+    pub fn push_float(&mut self, arg: f32) -> &mut Self {
+        let start = self.slots.len();
+        if self.signature.is_some() {
+            self.push_declared(|func| func.try_push_arg(Arg::F32(arg)).map(drop));
+        } else {
+            self.place_float(arg, plan::arg_align(ArgKind::CFloat));
+        }
+        self.mark_origin(start)
     }
 
-    pub fn ret_as_u64(&self) -> u64 {
-        if cfg!(target_arch = "x86") {
-            (self.ret_high as u64) << 32 | self.ret_low as u64
+    fn place_float(&mut self, arg: f32, align: usize) -> &mut Self {
+        let bits = arg.to_bits();
+        if plan::SEPARATE_FLOATS && self.float_regs_free() {
+            // xmm 寄存器的低 32 位即为 float
+            self.slots.push(ArgSlot {
+                kind: ArgKind::CFloat,
+                float: true,
+                index: self.fargs.len(),
+                len: 1,
+                align,
+                #[cfg(feature = "provenance")]
+                origin: Origin(None),
+            });
+            self.fargs.push(f64::from_bits(u64::from(bits)));
         } else {
-            self.ret_low as u64
+            let index = self.place_words(&[bits as usize], self.int_regs_usable(true), align);
+            self.slots.push(ArgSlot {
+                kind: ArgKind::CFloat,
+                float: false,
+                index,
+                len: 1,
+                align,
+                #[cfg(feature = "provenance")]
+                origin: Origin(None),
+            });
         }
+        self
     }
 
-    pub fn ret_as_isize(&self) -> isize {
-        self.ret_low as isize
+    /// 以 C 语言的 char 类型压入, 其符号随平台而定
+    #[cfg_attr(feature = "provenance", track_caller)]
+    pub fn push_c_char(&mut self, arg: c_char) -> &mut Self {
+        self.push(arg)
     }
 
-    pub fn ret_as_usize(&self) -> usize {
-        self.ret_low as usize
+    /// 以 C 语言的 int 类型压入
+    #[cfg_attr(feature = "provenance", track_caller)]
+    pub fn push_c_int(&mut self, arg: c_int) -> &mut Self {
+        self.push(arg)
     }
 
-    pub fn ret_as_i128(&self) -> i128 {
-        self.ret_as_u128() as i128
+    /// 以 C 语言的 unsigned int 类型压入
+    #[cfg_attr(feature = "provenance", track_caller)]
+    pub fn push_c_uint(&mut self, arg: c_uint) -> &mut Self {
+        self.push(arg)
     }
 
-    pub fn ret_as_u128(&self) -> u128 {
-        if cfg!(target_arch = "x86_64") {
-            (self.ret_high as u128) << 64 | self.ret_low as u128
-        } else {
-            unimplemented!()
+    /// 以 C 语言的 long 类型压入
+    ///
+    /// long 在 64 位 Unix (LP64) 下为 64 位, 在 Windows (LLP64) 与 32 位平台上为 32 位
+    #[cfg_attr(feature = "provenance", track_caller)]
+    pub fn push_c_long(&mut self, arg: c_long) -> &mut Self {
+        self.push(arg)
+    }
+
+    /// 以 C 语言的 unsigned long 类型压入, 宽度同 `push_c_long`
+    #[cfg_attr(feature = "provenance", track_caller)]
+    pub fn push_c_ulong(&mut self, arg: c_ulong) -> &mut Self {
+        self.push(arg)
+    }
+
+    /// 以 C 语言的 size_t 类型压入, 与指针等宽
+    #[cfg_attr(feature = "provenance", track_caller)]
+    pub fn push_c_size_t(&mut self, arg: usize) -> &mut Self {
+        self.push(arg)
+    }
+
+    /// 以 POSIX 的 ssize_t 类型压入, 与指针等宽
+    #[cfg_attr(feature = "provenance", track_caller)]
+    pub fn push_c_ssize_t(&mut self, arg: isize) -> &mut Self {
+        self.push(arg)
+    }
+
+    /// thiscall 是否实际以 cdecl 调用, 即可变参数函数或 `ThiscallFlavor::Gcc`
+    #[cfg(target_arch = "x86")]
+    pub(crate) fn thiscall_as_cdecl(&self) -> bool {
+        matches!(self.arity, Some((_, true))) || self.thiscall_flavor == ThiscallFlavor::Gcc
+    }
+
+    /// 下一个参数是否处在声明的固定参数位置上
+    fn at_fixed_param(&self) -> bool {
+        matches!(self.arity, Some((fixed, _)) if self.slots.len() < fixed)
+    }
+
+    /// 已压入参数的状态, 用于出错时通过 `rollback` 撤销之后压入的参数
+    pub(crate) fn checkpoint(&self) -> [usize; 4] {
+        [
+            self.args.len(),
+            self.fargs.len(),
+            self.slots.len(),
+            self.owned.len(),
+        ]
+    }
+
+    /// 将第 start 个之后的参数的来源记为调用者, 返回自身
+    ///
+    /// 各个公开的压入方法在返回前调用, 嵌套时最外层的最后调用, 因此记下的总是用户代码中的位置
+    #[cfg(feature = "provenance")]
+    #[track_caller]
+    pub(crate) fn mark_origin(&mut self, start: usize) -> &mut Self {
+        let origin = Location::caller();
+        for slot in &mut self.slots[start..] {
+            slot.origin = Origin(Some(origin));
         }
+        self
     }
 
-    pub fn ret_as_f32(&self) -> f32 {
-        self.ret_float as f32
+    #[cfg(not(feature = "provenance"))]
+    #[inline(always)]
+    pub(crate) fn mark_origin(&mut self, _start: usize) -> &mut Self {
+        self
     }
 
-    pub fn ret_as_f64(&self) -> f64 {
-        self.ret_float
+    pub(crate) fn rollback(&mut self, [args, fargs, slots, owned]: [usize; 4]) {
+        if matches!(self.rejected, Some((index, _)) if index >= slots) {
+            self.rejected = None;
+        }
+        self.args.truncate(args);
+        self.fargs.truncate(fargs);
+        self.slots.truncate(slots);
+        self.truncate_owned(owned);
+    }
+
+    /// 释放第 owned 个之后持有的缓冲区, 之前的缓冲区与指向它们的 `OutParam` 不受影响
+    pub(crate) fn truncate_owned(&mut self, owned: usize) {
+        self.owned.truncate(owned);
+        self.tables.retain(|&table| table < owned);
+        self.aligned.retain(|&(index, _)| index < owned);
+    }
+
+    /// 在所有已压入的参数之前插入一个参数, 用于 this 等隐藏的参数
+    ///
+    /// 其余参数按原来的类型重新放置, 64 位下可能因此从寄存器移到栈上.
+    /// 已声明的固定参数个数随之加一, 插入的参数不经原型检查
+    pub(crate) fn insert_front<T: IntoArg>(&mut self, arg: T) -> &mut Self {
+        self.relayout(|func| {
+            func.push_raw(arg);
+        });
+        // 隐藏的参数不是由用户代码压入的, 不记录位置
+        #[cfg(feature = "provenance")]
+        {
+            self.slots[0].origin = Origin(None);
+        }
+        if let Some((index, _)) = &mut self.rejected {
+            *index += 1;
+        }
+        if let Some((fixed, _)) = &mut self.arity {
+            *fixed += 1;
+        }
+        self
+    }
+
+    /// 清空参数后先执行 front, 再按原来的类型与对齐依次重新放置已压入的参数
+    fn relayout(&mut self, front: impl FnOnce(&mut Self)) {
+        let args = mem::take(&mut self.args);
+        let fargs = mem::take(&mut self.fargs);
+        let slots = mem::take(&mut self.slots);
+        front(self);
+        for slot in slots.iter() {
+            self.place_slot(slot, &args, &fargs);
+        }
+    }
+
+    /// 按 slot 的类型与对齐重新放置另一组 args 与 fargs 中的参数
+    fn place_slot(&mut self, slot: &ArgSlot, args: &[usize], fargs: &[f64]) {
+        let float = matches!(
+            slot.kind,
+            ArgKind::F32 | ArgKind::F64 | ArgKind::CFloat | ArgKind::F16
+        );
+        // 提升为 double 的 f32 放到固定参数位置时与 `place_sink` 相同, 收窄回 float
+        if slot.kind == ArgKind::F32 && self.at_fixed_param() {
+            let value = if slot.float {
+                fargs[slot.index]
+            } else {
+                match args[slot.index..slot.index + slot.len] {
+                    // 32 位下 double 占两个字
+                    [low, high] => f64::from_bits((high as u64) << 32 | low as u64),
+                    ref words => f64::from_bits(words[0] as u64),
+                }
+            };
+            self.place_float(value as f32, slot.align);
+            #[cfg(feature = "provenance")]
+            {
+                let last = self.slots.len() - 1;
+                self.slots[last].origin = slot.origin;
+            }
+            return;
+        }
+        // 栈上的浮点数在 xmm 寄存器空出时同样移回寄存器
+        if float && plan::SEPARATE_FLOATS && self.float_regs_free() {
+            self.fargs.push(if slot.float {
+                fargs[slot.index]
+            } else {
+                f64::from_bits(args[slot.index] as u64)
+            });
+            self.slots.push(ArgSlot {
+                float: true,
+                index: self.fargs.len() - 1,
+                ..*slot
+            });
+            return;
+        }
+        let words = if slot.float {
+            vec![fargs[slot.index].to_bits() as usize]
+        } else {
+            args[slot.index..slot.index + slot.len].to_vec()
+        };
+        let index = self.place_words(&words, self.int_regs_usable(float), slot.align);
+        self.slots.push(ArgSlot {
+            float: false,
+            index,
+            len: words.len(),
+            ..*slot
+        });
+    }
+
+    /// 持有一个 len 字节, 起始地址按 align 对齐的全零缓冲区, 返回其内容
+    ///
+    /// 多分配 align - 1 字节并从对齐的位置开始使用. 复制时副本中的内容同样移到对齐的位置,
+    /// 因此内容在缓冲区中的偏移需要通过 `owned_start` 得到, 不能事先记下
+    pub(crate) fn own_aligned(&mut self, len: usize, align: usize) -> &mut [u8] {
+        self.aligned.push((self.owned.len(), align));
+        self.owned.push(vec![0u8; len + align - 1].into());
+        let buf = Arc::get_mut(self.owned.last_mut().unwrap()).unwrap();
+        let start = buf.as_ptr().align_offset(align);
+        &mut buf[start..start + len]
+    }
+
+    /// owned 中第 index 个缓冲区的内容在其中的偏移, 只有 `own_aligned` 分配的缓冲区可能不为 0
+    pub(crate) fn owned_start(&self, index: usize) -> usize {
+        self.aligned
+            .iter()
+            .find(|&&(aligned, _)| aligned == index)
+            .map_or(0, |&(_, align)| {
+                self.owned[index].as_ptr().align_offset(align)
+            })
+    }
+
+    /// 将 from (见 `checkpoint`) 之后持有的缓冲区各复制一份, 之后压入的指向它们的参数随之改为指向新的副本
+    fn copy_owned(&mut self, [_, _, slots, owned]: [usize; 4]) {
+        let mut copies = Vec::new();
+        let mut moves = Vec::new();
+        for (index, buf) in self.owned.iter().enumerate().skip(owned) {
+            let from = self.owned_start(index);
+            let align = self
+                .aligned
+                .iter()
+                .find(|&&(aligned, _)| aligned == index)
+                .map_or(1, |&(_, align)| align);
+            // 副本的地址与原缓冲区对 align 的余数可能不同, 内容移到副本中对齐的位置
+            let mut copy: Arc<[u8]> = vec![0u8; buf.len()].into();
+            let bytes = Arc::get_mut(&mut copy).unwrap();
+            let to = bytes.as_ptr().align_offset(align);
+            let len = buf.len() - (align - 1);
+            bytes[to..to + len].copy_from_slice(&buf[from..from + len]);
+            moves.push((
+                buf.as_ptr() as usize + from,
+                buf.len() - from,
+                copy.as_ptr() as usize + to,
+            ));
+            copies.push(copy);
+        }
+        // 指针数组中的元素同样改为指向副本. 原地修改副本, va_list 等指向自身的指针才会仍然有效
+        for &table in self.tables.iter().filter(|&&table| table >= owned) {
+            let words = Arc::get_mut(&mut copies[table - owned]).unwrap();
+            for word in words.chunks_exact_mut(mem::size_of::<usize>()) {
+                let mut bytes = [0; mem::size_of::<usize>()];
+                bytes.copy_from_slice(word);
+                let addr = relocate(usize::from_ne_bytes(bytes), &moves);
+                word.copy_from_slice(&addr.to_ne_bytes());
+            }
+        }
+        let ptrs = self.slots[slots..]
+            .iter()
+            .filter(|slot| slot.kind == ArgKind::Ptr && !slot.float);
+        for slot in ptrs {
+            self.args[slot.index] = relocate(self.args[slot.index], &moves);
+        }
+        for (buf, copy) in self.owned[owned..].iter_mut().zip(copies) {
+            *buf = copy;
+        }
+    }
+
+    /// 64 位 Linux, s390x, LoongArch64 与 aarch64 下是否还有空闲的浮点寄存器可以传递下一个浮点参数
+    fn float_regs_free(&self) -> bool {
+        // LoongArch64 与 Windows ARM64 下可变参数部分的浮点数按整数传递, Apple arm64 下则在栈上
+        let variadic_in_ints = cfg!(any(
+            target_arch = "loongarch64",
+            all(
+                target_arch = "aarch64",
+                any(windows, target_vendor = "apple")
+            )
+        ));
+        if variadic_in_ints && self.in_variadic_part() {
+            return false;
+        }
+        self.layout == LayoutOverride::Abi && self.fargs.len() != plan::FLOAT_REGS.len()
+    }
+
+    /// 下一个放入 args 的参数能否使用整数寄存器, float 为 true 时它是没有浮点寄存器可用的浮点数
+    fn int_regs_usable(&self, float: bool) -> bool {
+        let variadic = self.in_variadic_part();
+        if cfg!(all(target_arch = "aarch64", target_vendor = "apple")) && variadic {
+            // Apple arm64 下可变参数部分全部在栈上
+            false
+        } else if float {
+            // Windows ARM64 下可变参数部分的浮点数与整数一样使用 x0~x7
+            cfg!(all(target_arch = "aarch64", windows)) && variadic
+        } else {
+            true
+        }
+    }
+
+    /// 下一个参数是否处在可变参数部分
+    fn in_variadic_part(&self) -> bool {
+        matches!(self.arity, Some((fixed, true)) if self.slots.len() >= fixed)
+    }
+
+    /// 调试用: 改变参数的布局, 默认为 `LayoutOverride::Abi`
+    ///
+    /// `LayoutOverride::StackOnly` 使所有参数都通过栈传递, 原本用于参数的寄存器被留空,
+    /// 用于判断崩溃是来自寄存器的分配还是被调用者本身: 只从栈上读取参数的函数 (如按 32 位 cdecl 编译的垫片) 此时仍能得到正确的参数.
+    /// 已压入的参数随之重新放置, 配合 `dry_run_with` 与 `CallPlan::diff` 可以看到每个参数的去向.
+    /// 32 位下除 fastcall 外参数本来就都在栈上, thiscall 的 this 仍在 ecx 中.
+    /// 只有 `Backend::Asm` 支持, 以其他后端调用时 `try_call` 返回 `CallError::UnsupportedLayout`
+    ///
+    /// ```
+    /// use funcall::{Convention, Func, LayoutOverride};
+    ///
+    /// let mut func = Func::null();
+    /// func.push(1i32).push(2.0f64);
+    /// let abi = func.dry_run(Convention::Cdecl);
+    /// let stack = func.dry_run_with(Convention::Cdecl, LayoutOverride::StackOnly);
+    /// for moved in abi.diff(&stack) {
+    ///     println!("{}", moved);
+    /// }
+    /// ```
+    pub fn set_layout_override(&mut self, layout: LayoutOverride) {
+        if self.layout != layout {
+            self.layout = layout;
+            self.relayout(|_| ());
+        }
+    }
+
+    /// 通过 `set_layout_override` 设置的参数布局
+    pub fn layout_override(&self) -> LayoutOverride {
+        self.layout
+    }
+
+    /// 设置栈上参数的排列顺序, 默认为 `ArgOrder::RightToLeft`
+    ///
+    /// 只影响在栈上传递的参数, 寄存器的分配不变. 只有 `Backend::Asm` 支持 `ArgOrder::LeftToRight`,
+    /// 以其他后端调用时 `try_call` 返回 `CallError::UnsupportedArgOrder`
+    ///
+    /// ```
+    /// use funcall::{ArgLocation, ArgOrder, Convention, Func};
+    ///
+    /// let mut func = Func::null();
+    /// func.set_arg_order(ArgOrder::LeftToRight);
+    /// for i in 0..8usize {
+    ///     func.push(i);
+    /// }
+    /// let plan = func.dry_run(Convention::Cdecl);
+    /// // 最后一个参数位于栈顶
+    /// let top = ArgLocation::Stack(plan.shadow_space);
+    /// assert_eq!(plan.args[7].locations[0], top);
+    /// ```
+    pub fn set_arg_order(&mut self, order: ArgOrder) {
+        self.order = order;
+    }
+
+    /// 栈上参数的排列顺序
+    pub fn arg_order(&self) -> ArgOrder {
+        self.order
+    }
+
+    /// 声明函数的固定参数个数, variadic 表示其后是否还可以跟任意个可变参数
+    ///
+    /// `try_call` 会在调用前检查压入的参数个数是否与之相符, 固定参数位置上的 f32 也不再提升为 double
+    pub fn set_arity(&mut self, fixed: usize, variadic: bool) {
+        self.arity = Some((fixed, variadic));
+    }
+
+    /// 声明返回值的类型
+    ///
+    /// 汇编后端会取回所有返回值寄存器, 不需要声明; libffi 后端则只按声明的类型取回返回值,
+    /// 未声明时视为与指针等宽的整数
+    pub fn set_ret_kind(&mut self, kind: ArgKind) {
+        self.ret_kind = Some(kind);
+    }
+
+    /// 检查函数指针是否指向可执行的内存
+    ///
+    /// Linux 下查找 `/proc/self/maps`, 读取失败时与其他 Unix 平台一样通过 `dladdr` 确认地址位于某个已加载的模块中,
+    /// Windows 下使用 `VirtualQuery`. 无法发现所有错误的指针, 但能排除空指针, 数据指针与已卸载的库中的地址
+    pub fn validate_ptr(&self) -> core::result::Result<(), PtrError> {
+        validate::validate(self.func)
+    }
+
+    /// 开启后 `try_call` 会在调用前先执行 `validate_ptr`, 每次调用都要查询内存映射, 开销较大.
+    ///
+    /// 32 位 x86 下还会在调用后检查被调用者弹出的字节数与栈上参数之上的哨兵,
+    /// 调用约定不符或被调用者越界改写了栈时立即 panic, 而不是等到之后莫名其妙地崩溃.
+    /// 开启 debug 断言时即使没有开启也会检查 stdcall, thiscall 与 fastcall 调用中被调用者弹出的字节数
+    pub fn set_paranoid(&mut self, paranoid: bool) {
+        self.paranoid = paranoid;
+    }
+
+    /// 开启后在每次调用前保存浮点控制状态, 调用后恢复, 以免被调用者修改的舍入模式与异常屏蔽影响之后的 Rust 代码
+    ///
+    /// x86 与 x86_64 下为 x87 控制字与 MXCSR, aarch64 下为 FPCR, 其他平台上没有效果.
+    /// 默认关闭, 此时被调用者对这些状态的修改会一直保留在当前线程中
+    pub fn protect_fp_state(&mut self, enable: bool) {
+        self.protect_fp = enable;
+    }
+
+    /// 设置以 `Convention::Thiscall` 调用时 this 的传递方式, 默认为 `ThiscallFlavor::Msvc`
+    ///
+    /// `ThiscallFlavor::Gcc` 下 this 与其余参数一样压栈并由调用者清理, 用于按这种方式编译的 MinGW 二进制文件
+    pub fn set_thiscall_flavor(&mut self, flavor: ThiscallFlavor) {
+        self.thiscall_flavor = flavor;
+    }
+
+    /// 设置栈上参数所占字节数的上限, 默认为 64 KiB
+    ///
+    /// 超出时 `try_call` 返回 `CallError::StackTooLarge`, 直接调用 `cdecl` 等则会 panic,
+    /// 而不是将栈指针移到不知何处. 不论上限为多少, 超过一页的参数区都会逐页访问后再写入参数
+    pub fn set_max_stack_bytes(&mut self, bytes: usize) {
+        self.max_stack = bytes;
+    }
+
+    /// 栈上参数的字节数在上限之内
+    fn check_stack_size(&self) -> core::result::Result<(), CallError> {
+        let bytes = self.args.len().saturating_sub(plan::INT_REGS.len()) * mem::size_of::<usize>();
+        if bytes > self.max_stack {
+            return Err(CallError::StackTooLarge {
+                bytes,
+                limit: self.max_stack,
+            });
+        }
+        Ok(())
+    }
+
+    /// 同 `check_stack_size`, 超出上限时 panic
+    #[cfg_attr(
+        not(any(
+            target_arch = "x86",
+            all(target_arch = "x86_64", any(target_os = "linux", windows)),
+            all(target_arch = "loongarch64", target_os = "linux"),
+            feature = "libffi"
+        )),
+        allow(dead_code)
+    )]
+    pub(crate) fn assert_stack_size(&self) {
+        if let Err(e) = self.check_stack_size() {
+            panic!("{}", e);
+        }
+    }
+
+    /// 检查函数指针, 调用约定与参数个数, 确认无误后再以指定的调用约定调用函数
+    ///
+    /// # Safety
+    ///
+    /// 只能排除一部分明显的错误, 调用者仍需保证函数指针, 调用约定与参数类型正确
+    pub unsafe fn try_call(&mut self, conv: Convention) -> core::result::Result<(), CallError> {
+        self.try_call_with(conv, conv.backend().unwrap_or(Backend::Asm))
+    }
+
+    /// 同 `try_call`, 成功时返回各返回值寄存器的值
+    ///
+    /// 调用不会改变已压入的参数, 同一个 `Func` 可以反复调用, 两次调用之间也可以调用其他函数.
+    /// 每次调用前上一次的返回值都会被清零; 通过 `push_arg` 复制的字节串与字符串在 `Func` 存在期间一直有效,
+    /// 但被调用者对其内容的修改会保留到之后的调用中, 需要原样的输入时应重新压入
+    ///
+    /// ```
+    /// use funcall::{Convention, Func};
+    /// extern "C" fn add(a: i32, b: i32) -> i32 {
+    ///     a + b
+    /// }
+    ///
+    /// let mut func = Func::from_raw(add as *const fn());
+    /// func.push(1i32).push(2i32);
+    /// for _ in 0..3 {
+    ///     assert_eq!(unsafe { func.invoke(Convention::Cdecl) }.unwrap().as_i32(), 3);
+    /// }
+    /// ```
+    ///
+    /// # Safety
+    ///
+    /// 同 `try_call`
+    pub unsafe fn invoke(
+        &mut self,
+        conv: Convention,
+    ) -> core::result::Result<RetValues, CallError> {
+        self.try_call(conv)?;
+        Ok(self.ret())
+    }
+
+    /// 以 conv 调用函数, 与 `cdecl` 等方法相同不检查参数, 只在当前平台不支持该调用约定时返回错误
+    ///
+    /// 在所有平台上都存在, 没有任何后端的平台上返回 `CallError::UnsupportedTarget`.
+    /// 需要检查函数指针与参数个数时使用 `try_call`
+    ///
+    /// # Safety
+    ///
+    /// 压入的参数必须与函数的实际签名一致
+    pub unsafe fn call(&mut self, conv: Convention) -> core::result::Result<(), CallError> {
+        #[cfg(feature = "loader")]
+        self.resolve()?;
+        let backend = conv.backend().ok_or_else(|| CallError::unsupported(conv))?;
+        self.call_backend(conv, backend);
+        Ok(())
+    }
+
+    /// 同 `try_call`, 但使用指定的后端发出调用
+    ///
+    /// # Safety
+    ///
+    /// 同 `try_call`
+    pub unsafe fn try_call_with(
+        &mut self,
+        conv: Convention,
+        backend: Backend,
+    ) -> core::result::Result<(), CallError> {
+        self.check_call(conv, backend)?;
+        self.call_backend(conv, backend);
+        Ok(())
+    }
+
+    /// `try_call_with` 调用前进行的检查, 通过 `new_lazy` 创建的函数在这里进行查找
+    pub(crate) fn check_call(
+        &mut self,
+        conv: Convention,
+        backend: Backend,
+    ) -> core::result::Result<(), CallError> {
+        #[cfg(feature = "loader")]
+        self.resolve()?;
+        if self.func.is_null() {
+            return Err(CallError::NullTarget);
+        }
+        #[cfg(feature = "std")]
+        if self.is_poisoned() {
+            return Err(CallError::Poisoned);
+        }
+        if self.paranoid {
+            self.validate_ptr().map_err(CallError::InvalidTarget)?;
+        }
+        if !backend.supports(conv) {
+            return Err(CallError::unsupported(conv));
+        }
+        if let Some((_, e)) = &self.rejected {
+            return Err(e.clone());
+        }
+        if self.order != ArgOrder::RightToLeft && backend != Backend::Asm {
+            return Err(CallError::UnsupportedArgOrder(backend));
+        }
+        if self.layout != LayoutOverride::Abi && backend != Backend::Asm {
+            return Err(CallError::UnsupportedLayout(backend));
+        }
+        if matches!(&self.aggregate, Some(ret) if !ret.supported_by(backend)) {
+            return Err(CallError::UnsupportedReturn(backend));
+        }
+        if !self.unwind_policy.supported_by(backend) {
+            return Err(CallError::UnsupportedUnwind(backend));
+        }
+        if !float16::SUPPORTED
+            && (self.ret_kind == Some(ArgKind::F16)
+                || self.slots.iter().any(|slot| slot.kind == ArgKind::F16))
+        {
+            return Err(CallError::UnsupportedKind(ArgKind::F16));
+        }
+        if conv == Convention::Thiscall && self.slots.is_empty() {
+            return Err(CallError::ArgCountMismatch {
+                expected: 1,
+                variadic: true,
+                got: 0,
+            });
+        }
+        if let Some((expected, variadic)) = self.arity {
+            let got = self.slots.len();
+            if got < expected || (!variadic && got != expected) {
+                return Err(CallError::ArgCountMismatch {
+                    expected,
+                    variadic,
+                    got,
+                });
+            }
+        }
+        #[cfg(all(feature = "loader", windows, target_arch = "x86"))]
+        if let Some(expected) = self.decorated_bytes {
+            let got = self.args.len() * mem::size_of::<usize>();
+            if got != expected {
+                return Err(CallError::ArgSizeMismatch { expected, got });
+            }
+        }
+        self.check_stack_size()
+    }
+
+    /// 同 `try_call`, 但调用后取出类型为 T 的返回值并销毁自身
+    ///
+    /// 压入的字符串等参数与对库的引用会随之释放
+    ///
+    /// ```
+    /// use funcall::{Arg, Convention, Func};
+    /// extern "C" fn add(a: i32, b: i32) -> i32 {
+    ///     a + b
+    /// }
+    ///
+    /// # fn main() -> Result<(), funcall::CallError> {
+    /// let func = Func::from_raw_with(add as *const fn(), vec![Arg::I32(1), Arg::I32(2)]);
+    /// let n: i32 = unsafe { func.call_once(Convention::Cdecl)? };
+    /// assert_eq!(n, 3);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Safety
+    ///
+    /// 同 `try_call`
+    pub unsafe fn call_once<T: FromRet>(
+        mut self,
+        conv: Convention,
+    ) -> core::result::Result<T, CallError> {
+        self.try_call(conv)?;
+        Ok(self.ret().get())
+    }
+
+    /// 以指定的调用约定调用函数, 调用中发生段错误等硬件异常时返回 `Err` 而不是让整个进程崩溃
+    ///
+    /// 调用前进行与 `try_call` 相同的检查, 未通过时返回 `ProtectedError::Call`, 捕获到的异常以 `ProtectedError::Fault` 返回.
+    /// Unix 下在调用期间为 SIGSEGV, SIGBUS, SIGFPE 与 SIGILL 安装信号处理函数, 调用在新创建的线程中进行,
+    /// 当前线程等待其结束. 出错时信号处理函数报告异常后将该线程永久挂起, 而不是通过 siglongjmp 跳过 Rust 的调用帧.
+    /// Windows 下在当前线程中通过 SEH 捕获. 调用结束后恢复原先的处理函数. 这只是尽力而为的措施:
+    ///
+    /// - 被调用者在出错时的状态不会被清理, 它持有的锁, 分配的内存与打开的文件都会泄漏, 全局状态可能已经损坏.
+    ///   Unix 下出错的线程与它的栈也会一直保留
+    /// - Unix 下被调用者看到的 errno 等线程局部状态属于新线程, 依赖调用者线程局部状态的函数不能这样调用
+    /// - 观察者只会收到调用开始的通知, 返回值会被清零
+    /// - 信号处理函数是整个进程共享的, Unix 下同一时间只能有一个受保护的调用, 其他线程会等待;
+    ///   同一线程中嵌套调用会 panic
+    /// - 被调用者自己安装的信号处理函数, 或由它创建的其他线程中发生的异常都无法捕获
+    /// - 栈溢出在 Unix 下通过新线程的备用信号栈捕获, 新线程的栈大小为系统默认值
+    ///
+    /// ```
+    /// use funcall::{Convention, Fault, Func, ProtectedError};
+    ///
+    /// // 地址 0 附近的页面不会被映射
+    /// let mut func = Func::from_raw(0x10 as *const fn());
+    /// let fault = unsafe { func.call_protected(Convention::Cdecl) };
+    /// assert_eq!(fault, Err(ProtectedError::Fault(Fault::Segv { addr: 0x10 })));
+    /// ```
+    ///
+    /// # Safety
+    ///
+    /// 同 `try_call`. 出错后继续使用被调用者所在的库是否安全由调用者判断
+    #[cfg(all(feature = "protected", any(unix, all(windows, target_env = "msvc"))))]
+    pub unsafe fn call_protected(
+        &mut self,
+        conv: Convention,
+    ) -> core::result::Result<(), ProtectedError> {
+        let backend = conv.backend().unwrap_or(Backend::Asm);
+        self.check_call(conv, backend)?;
+        Ok(protect::call(self, conv, backend)?)
+    }
+
+    /// 同 `try_call`, 但被调用者抛出的 SEH 异常或 C++ 异常在调用帧处被捕获, 以 `CallError::ForeignException` 返回
+    ///
+    /// 默认情况下这样的异常展开到 `try_call` 等的调用帧时进程会以 abort 终止 (与 `extern "C"` 函数相同),
+    /// 没有任何处理者时则由系统终止进程. 与 `call_protected` 一样, 被调用者的状态不会被清理
+    ///
+    /// # Safety
+    ///
+    /// 同 `try_call`
+    #[cfg(all(feature = "protected", windows, target_env = "msvc"))]
+    pub unsafe fn try_call_catching(
+        &mut self,
+        conv: Convention,
+    ) -> core::result::Result<(), CallError> {
+        let backend = conv.backend().unwrap_or(Backend::Asm);
+        self.check_call(conv, backend)?;
+        protect::catching(self, conv, backend).map_err(|code| CallError::ForeignException { code })
+    }
+
+    /// 以指定的后端调用函数, 后端必须支持该调用约定
+    pub(crate) unsafe fn call_backend(&mut self, conv: Convention, backend: Backend) {
+        #[cfg(feature = "std")]
+        if self.memo.is_some() {
+            return self.call_memoized(conv, backend);
+        }
+        self.call_uncached(conv, backend)
+    }
+
+    /// 不经过缓存直接调用, 参见 `memoize`
+    pub(crate) unsafe fn call_uncached(&mut self, conv: Convention, backend: Backend) {
+        // 没有正常返回时不会留下上一次调用的返回值
+        self.ret = RetValues::default();
+        // 被调用者抛出的异常展开到这里时终止进程, 由外层的 SEH 处理时除外, 参见 `unwind`
+        if self.unwind_policy == UnwindPolicy::Abort && !unwind::handled_outside() {
+            return unwind::abort_on_unwind(|| self.dispatch(conv, backend));
+        }
+        self.dispatch(conv, backend)
+    }
+
+    unsafe fn dispatch(&mut self, conv: Convention, backend: Backend) {
+        match (backend, conv) {
+            (backend, Convention::System) => self.dispatch(Convention::native_system(), backend),
+            #[cfg(feature = "libffi")]
+            (Backend::Libffi, conv) => libffi::call(self, conv),
+            #[cfg(all(feature = "std", target_arch = "x86_64", target_os = "linux"))]
+            (Backend::Jit, conv) => jit::call(self, conv),
+            (Backend::Asm, conv) => <native::Native as NativeCall>::call(self, conv),
+            #[allow(unreachable_patterns)]
+            (backend, conv) => unreachable!("unsupported convention {:?} for {:?}", conv, backend),
+        }
+        if backend != Backend::Libffi {
+            self.store_float_ret();
+        }
+    }
+
+    /// 以静态已知的签名直接调用函数, 不使用已压入的参数
+    ///
+    /// ```
+    /// use funcall::Func;
+    /// extern "C" fn add(a: i32, b: f64) -> f64 {
+    ///     a as f64 + b
+    /// }
+    ///
+    /// let func = Func::from_raw(add as *const fn());
+    /// let ret = unsafe { func.call_as::<extern "C" fn(i32, f64) -> f64>((1, 2.5)) };
+    /// assert_eq!(ret, 3.5);
+    /// ```
+    ///
+    /// # Safety
+    ///
+    /// F 必须与函数的实际签名一致
+    pub unsafe fn call_as<F: FnPtr>(&self, args: F::Args) -> F::Ret {
+        F::from_ptr(self.func).call(args)
+    }
+
+    /// 转换为静态已知签名的函数指针, 返回值会持有对函数所在库的引用
+    ///
+    /// ```no_run
+    /// # #[cfg(feature = "loader")]
+    /// # fn main() {
+    /// use funcall::Func;
+    ///
+    /// let cos = Func::new("libm.so.6", b"cos\0").unwrap();
+    /// let cos = unsafe { cos.to_extern_c::<extern "C" fn(f64) -> f64>() };
+    /// assert_eq!(cos(0.0), 1.0);
+    /// # }
+    /// # #[cfg(not(feature = "loader"))]
+    /// # fn main() {}
+    /// ```
+    ///
+    /// # Safety
+    ///
+    /// F 必须与函数的实际签名一致
+    pub unsafe fn to_extern_c<F: FnPtr>(&self) -> BoundFn<F> {
+        BoundFn::new(self)
+    }
+
+    /// 依次压入元组中的所有参数
+    #[cfg_attr(feature = "provenance", track_caller)]
+    pub fn push_args<T: ArgTuple>(&mut self, args: T) -> &mut Self {
+        let start = self.slots.len();
+        args.push_into(self);
+        self.mark_origin(start)
+    }
+
+    /// 开启 `set_paranoid` 时检查被调用者弹出的字节数与参数之上的哨兵, 出错时 panic
+    #[cfg(target_arch = "x86")]
+    fn check_stack(&self, conv: Convention, popped: i32, canary: i32) {
+        // debug 构建下总是检查由被调用者清理堆栈的调用约定, 可变参数函数仍由调用者清理
+        let callee_cleans = conv != Convention::Cdecl && !matches!(self.arity, Some((_, true)));
+        let debug_check = cfg!(debug_assertions) && callee_cleans;
+        if !self.paranoid && !debug_check {
+            return;
+        }
+        let expected = match conv {
+            Convention::Stdcall => self.args.len() as isize * 4,
+            // this 在 ecx 中, 不占栈空间
+            Convention::Thiscall => (self.args.len() as isize - 1) * 4,
+            // 前两个字总是 ecx 与 edx
+            Convention::Fastcall => (self.fastcall_words().len() as isize - 2) * 4,
+            // 32 位 Linux 下返回结构体的函数会弹出隐藏指针
+            _ if cfg!(not(windows)) && self.returns_in_memory() => 4,
+            _ => 0,
+        };
+        let popped = popped as isize;
+        if !self.paranoid {
+            assert!(
+                popped == expected,
+                "callee popped {} bytes, expected {} — wrong convention or wrong argument sizes?",
+                popped,
+                expected
+            );
+            return;
+        }
+        if popped > expected {
+            panic!(
+                "callee consumed {} more bytes than pushed for {:?} — convention mismatch?",
+                popped - expected,
+                conv
+            );
+        }
+        if popped < expected {
+            panic!(
+                "callee consumed {} fewer bytes than pushed for {:?} — convention mismatch?",
+                expected - popped,
+                conv
+            );
+        }
+        assert_eq!(
+            canary as u32, STACK_CANARY,
+            "stack canary above the arguments was overwritten by the callee"
+        );
+    }
+
+    /// 声明返回 float 时取按 float 读出的返回值, 否则取按 double 读出的返回值
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    fn float_ret(&self, double: f64, single: f32) -> f64 {
+        match self.ret_kind {
+            Some(ArgKind::F32) | Some(ArgKind::CFloat) => f64::from(single),
+            _ => double,
+        }
+    }
+
+    /// 以 cdecl 调用约定调用函数
+    /// 即 C 语言默认使用的调用约定
+    ///
+    /// # Safety
+    ///
+    /// 压入的参数必须与函数的实际签名一致
+    #[cfg(target_arch = "x86")]
+    pub unsafe fn cdecl(&mut self) {
+        self.assert_stack_size();
+        sanitize::outgoing_args(self);
+        let fp = fpstate::save(self);
+        let observed = observer::begin(self, Convention::Cdecl);
+        self.called = true;
+        let args = self.stack_args(0);
+        let (low, high, float, single, popped, canary): (usize, usize, f64, f32, i32, i32);
+        asm!(
+            // GCC 与 Clang 生成的代码假定调用时栈对齐到 16 字节,
+            // 因此先留出对齐所需的空间, 使参数全部压栈后 esp 恰好对齐
+            "mov edi, esp",
+            // edi 之下的两个字分别保存哨兵的地址与 call 时的栈顶
+            "sub esp, 8",
+            "lea ecx, [ecx * 4 + 4]",
+            "sub esp, ecx",
+            "and esp, -16",
+            "add esp, ecx",
+            "shr ecx, 2",
+            "dec ecx",
+            // 参数之上的哨兵, 调用后检查是否被改写
+            "push {canary}",
+            "mov dword ptr [edi - 8], esp",
+            // args 已按 `ArgOrder` 排好, 最后入栈的字在最前, 因此从末尾开始向前读取.
+            // ebx 是被调用者保护的寄存器, PIC 代码中还用于保存 GOT 的地址, 因此以 ecx 计数
+            "test ecx, ecx",
+            "jz 3f",
+            "2:",
+            "push dword ptr [edx - 4]",
+            "sub edx, 4",
+            "dec ecx",
+            "jnz 2b",
+            "3:",
+            "mov dword ptr [edi - 4], esp",
+            "call eax",
+            // 被调用者弹出的字节数与调用后的哨兵, 在之后的栈操作改写它们之前取出
+            "mov ecx, esp",
+            "sub ecx, dword ptr [edi - 4]",
+            "movd xmm2, ecx",
+            "mov ecx, dword ptr [edi - 8]",
+            "movd xmm3, dword ptr [ecx]",
+            // 浮点返回值在 st(0) 中, 其他函数返回时 x87 栈为空, 此时不能弹出.
+            // fnstsw 会改写 ax, 因此先暂存 eax
+            "mov ecx, eax",
+            "sub esp, 12",
+            "fxam",
+            "fnstsw ax",
+            "and ah, 0x45",
+            "cmp ah, 0x41", // C3 = 1 且 C0 = 1 表示为空
+            "je 4f",
+            // 分别按 float 与 double 舍入一次, 不经过 double 再转换为 float
+            "fst dword ptr [esp]",
+            "fstp qword ptr [esp + 4]",
+            "movss xmm1, dword ptr [esp]",
+            "movsd xmm0, qword ptr [esp + 4]",
+            "jmp 5f",
+            "4:",
+            "xorps xmm0, xmm0",
+            "xorps xmm1, xmm1",
+            "5:",
+            "mov eax, ecx",
+            "mov esp, edi", // 恢复堆栈指针
+            // 调用前的栈顶, 放在被调用者保护的寄存器中, 调用后仍可使用
+            out("edi") _,
+            // 指向最后一个参数之后, 没有参数时也不会越界
+            inout("edx") args.as_ptr().add(args.len()) => high,
+            inout("ecx") args.len() => _,
+            inout("eax") self.func => low,
+            out("xmm0") float,
+            out("xmm1") single,
+            out("xmm2") popped,
+            out("xmm3") canary,
+            canary = const STACK_CANARY,
+            clobber_abi("C"),
+        );
+        self.ret.low = low;
+        self.ret.high = high;
+        self.ret.float = self.float_ret(float, single);
+        self.check_stack(Convention::Cdecl, popped, canary);
+        fpstate::restore(fp);
+        observer::end(observed, self, Convention::Cdecl);
+    }
+
+    /// 64 位 Linux 默认使用的调用约定
+    ///
+    /// # Safety
+    ///
+    /// 压入的参数必须与函数的实际签名一致
+    #[cfg(all(target_arch = "x86_64", target_os = "linux"))]
+    pub unsafe fn cdecl(&mut self) {
+        self.assert_stack_size();
+        sanitize::outgoing_args(self);
+        let fp = fpstate::save(self);
+        let observed = observer::begin(self, Convention::Cdecl);
+        self.called = true;
+        let (low, high, float) = match self.unwind_policy {
+            UnwindPolicy::Abort => self.cdecl_inline(),
+            // panic 展开经过汇编函数的调用帧之后在这里捕获, 恢复浮点控制状态后继续展开
+            #[cfg(feature = "unwind")]
+            UnwindPolicy::Catch => {
+                match std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                    unwind::sysv_call(self)
+                })) {
+                    Ok(ret) => ret,
+                    Err(payload) => {
+                        fpstate::restore(fp);
+                        std::panic::resume_unwind(payload)
+                    }
+                }
+            }
+        };
+        self.ret.low = low;
+        self.ret.high = high;
+        self.ret.float = self.float_ret(float, f32::from_bits(float.to_bits() as u32));
+        fpstate::restore(fp);
+        observer::end(observed, self, Convention::Cdecl);
+    }
+
+    /// 以内联汇编发出调用, 调用帧没有展开信息
+    #[cfg(all(target_arch = "x86_64", target_os = "linux"))]
+    #[inline(always)]
+    unsafe fn cdecl_inline(&self) -> (usize, usize, f64) {
+        // 不需要栈上参数时走不含分支的快速路径
+        #[cfg(not(feature = "regcheck"))]
+        return match (self.args.padded(), self.fargs.padded()) {
+            (Some(ints), Some(floats)) if self.args.len() <= plan::INT_REGS.len() => {
+                Self::cdecl_regs(self.func, ints, floats, self.fargs.len())
+            }
+            _ => self.cdecl_stack(),
+        };
+        #[cfg(feature = "regcheck")]
+        return self.cdecl_checked();
+    }
+
+    /// 参数全部通过寄存器传递时, 从补零的内联数组中无条件地送入所有传参寄存器
+    ///
+    /// 多余的寄存器中为 0, 没有浮点参数时 xmm0 同样为 0, 结果与 `cdecl_stack` 完全一致
+    #[cfg(all(target_arch = "x86_64", target_os = "linux"))]
+    #[cfg_attr(feature = "regcheck", allow(dead_code))]
+    #[inline(always)]
+    unsafe fn cdecl_regs(
+        func: *const fn(),
+        ints: &[usize; 8],
+        floats: &[f64; 8],
+        float_count: usize,
+    ) -> (usize, usize, f64) {
+        let (low, high, float): (usize, usize, f64);
+        asm!(
+            "mov r12, rsp",
+            // 越过 red zone 并对齐到 16 字节, 同 `cdecl_stack`
+            "sub rsp, 128",
+            "and rsp, -16",
+            "mov rdi, qword ptr [r13]",
+            "mov rsi, qword ptr [r13 + 8]",
+            "mov rdx, qword ptr [r13 + 16]",
+            "mov rcx, qword ptr [r13 + 24]",
+            "mov r8, qword ptr [r13 + 32]",
+            "mov r9, qword ptr [r13 + 40]",
+            "movsd xmm0, qword ptr [r10]",
+            "movsd xmm1, qword ptr [r10 + 8]",
+            "movsd xmm2, qword ptr [r10 + 16]",
+            "movsd xmm3, qword ptr [r10 + 24]",
+            "movsd xmm4, qword ptr [r10 + 32]",
+            "movsd xmm5, qword ptr [r10 + 40]",
+            "movsd xmm6, qword ptr [r10 + 48]",
+            "movsd xmm7, qword ptr [r10 + 56]",
+            "call r11",
+            "mov rsp, r12",
+            out("r12") _,
+            in("r13") ints.as_ptr(),
+            in("r10") floats.as_ptr(),
+            in("r11") func,
+            inout("rax") float_count => low,
+            out("rdx") high,
+            out("xmm0") float,
+            clobber_abi("C"),
+        );
+        (low, high, float)
+    }
+
+    /// 有参数需要通过栈传递时的通用路径
+    #[cfg(all(target_arch = "x86_64", target_os = "linux"))]
+    #[cfg_attr(feature = "regcheck", allow(dead_code))]
+    unsafe fn cdecl_stack(&self) -> (usize, usize, f64) {
+        let args = self.stack_args(plan::INT_REGS.len());
+        let (low, high, float): (usize, usize, f64);
+        asm!(
+            // 在对齐到 16 字节的位置上构造栈上的参数, 使得 call 时 rsp 满足 ABI 的要求,
+            // 不论需要压栈的参数个数是奇数还是偶数
+            "mov r12, rsp",
+            // 越过 red zone, 即使编译器在其中存放了数据, call 压入的返回地址也不会覆盖它们
+            "sub rsp, 128",
+            "and rsp, -16",
+            // 第七个及之后的参数复制到栈上
+            "cmp r14, 6",
+            "jbe 3f",
+            "lea rsi, [r14 * 8 - 48]",
+            // 超过一页时逐页下移 rsp 并访问新的一页, 不会跳过栈末尾的 guard page
+            "6:",
+            "cmp rsi, 4096",
+            "jb 7f",
+            "sub rsp, 4096",
+            "or qword ptr [rsp], 0",
+            "sub rsi, 4096",
+            "jmp 6b",
+            "7:",
+            "sub rsp, rsi",
+            "and rsp, -16",
+            "mov rsi, 6",
+            "2:",
+            "mov rdi, qword ptr [r13 + rsi * 8]",
+            "mov qword ptr [rsp + rsi * 8 - 48], rdi",
+            "inc rsi",
+            "cmp rsi, r14",
+            "jb 2b",
+            "3:",
+            // 将前六个参数送入寄存器
+            "test r14, r14",
+            "jz 4f",
+            "mov rdi, qword ptr [r13]",
+            "cmp r14, 1",
+            "je 4f",
+            "mov rsi, qword ptr [r13 + 8]",
+            "cmp r14, 2",
+            "je 4f",
+            "mov rdx, qword ptr [r13 + 16]",
+            "cmp r14, 3",
+            "je 4f",
+            "mov rcx, qword ptr [r13 + 24]",
+            "cmp r14, 4",
+            "je 4f",
+            "mov r8, qword ptr [r13 + 32]",
+            "cmp r14, 5",
+            "je 4f",
+            "mov r9, qword ptr [r13 + 40]",
+            "4:",
+            // 浮点参数最后再送入寄存器, 之后直到 call 都不会再有指令碰到 xmm 寄存器.
+            // 可变参数函数通过 al 得知用到了几个向量寄存器, 其值一定不大于 8
+            // 没有浮点参数时 xmm0 置零, 使不返回浮点数的函数的 float 返回值是确定的
+            "xorps xmm0, xmm0",
+            "test eax, eax",
+            "jz 5f",
+            "movsd xmm0, qword ptr [r10]",
+            "cmp eax, 1",
+            "je 5f",
+            "movsd xmm1, qword ptr [r10 + 8]",
+            "cmp eax, 2",
+            "je 5f",
+            "movsd xmm2, qword ptr [r10 + 16]",
+            "cmp eax, 3",
+            "je 5f",
+            "movsd xmm3, qword ptr [r10 + 24]",
+            "cmp eax, 4",
+            "je 5f",
+            "movsd xmm4, qword ptr [r10 + 32]",
+            "cmp eax, 5",
+            "je 5f",
+            "movsd xmm5, qword ptr [r10 + 40]",
+            "cmp eax, 6",
+            "je 5f",
+            "movsd xmm6, qword ptr [r10 + 48]",
+            "cmp eax, 7",
+            "je 5f",
+            "movsd xmm7, qword ptr [r10 + 56]",
+            "5:",
+            "call r11",
+            "mov rsp, r12", // 清理堆栈
+            // 以下的值放在被调用者保护的寄存器中, 在送入传参寄存器的过程中不会被覆盖
+            out("r12") _,
+            inout("r13") args.as_ptr() => _,
+            inout("r14") args.len() => _,
+            in("r10") self.fargs.as_ptr(),
+            in("r11") self.func,
+            inout("rax") self.fargs.len() => low,
+            out("rdx") high,
+            out("xmm0") float,
+            clobber_abi("C"),
+        );
+        (low, high, float)
+    }
+
+    /// 64 位 Windows 使用的调用约定
+    ///
+    /// # Safety
+    ///
+    /// 压入的参数必须与函数的实际签名一致
+    #[cfg(all(target_arch = "x86_64", windows))]
+    pub unsafe fn cdecl(&mut self) {
+        self.assert_stack_size();
+        sanitize::outgoing_args(self);
+        let fp = fpstate::save(self);
+        let observed = observer::begin(self, Convention::Cdecl);
+        self.called = true;
+        let args = self.stack_args(plan::INT_REGS.len());
+        // 在带有展开信息的汇编函数中发出调用, 被调用者抛出的异常可以正确地展开经过调用帧
+        let mut call = unwind::Win64Call {
+            func: self.func as *const c_void,
+            args: args.as_ptr(),
+            len: args.len(),
+            low: 0,
+            high: 0,
+            float: 0.0,
+        };
+        match self.unwind_policy {
+            UnwindPolicy::Abort => unwind::funcall_win64_call(&mut call),
+            // panic 展开经过汇编函数的调用帧之后在这里捕获, 恢复浮点控制状态后继续展开
+            #[cfg(feature = "unwind")]
+            UnwindPolicy::Catch => {
+                let caught = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                    unwind::funcall_win64_call(&mut call)
+                }));
+                if let Err(payload) = caught {
+                    fpstate::restore(fp);
+                    std::panic::resume_unwind(payload);
+                }
+            }
+        }
+        let (low, high, float) = (call.low, call.high, call.float);
+        self.ret.low = low;
+        self.ret.high = high;
+        self.ret.float = self.float_ret(float, f32::from_bits(float.to_bits() as u32));
+        fpstate::restore(fp);
+        observer::end(observed, self, Convention::Cdecl);
+    }
+
+    /// 以 cdecl 调用约定调用函数, 没有手写汇编的平台上通过 libffi 发出调用
+    ///
+    /// # Safety
+    ///
+    /// 压入的参数必须与函数的实际签名一致
+    #[cfg(all(
+        feature = "libffi",
+        not(any(
+            target_arch = "x86",
+            all(target_arch = "x86_64", any(target_os = "linux", windows)),
+            all(target_arch = "loongarch64", target_os = "linux")
+        ))
+    ))]
+    pub unsafe fn cdecl(&mut self) {
+        libffi::call(self, Convention::Cdecl);
+    }
+
+    /// 没有任何后端的平台上总是 panic, 只是为了让调用它的代码在所有平台上都能编译.
+    /// 需要在运行时判断时使用 `try_call` 或 `supported_conventions`
+    ///
+    /// # Safety
+    ///
+    /// 压入的参数必须与函数的实际签名一致
+    #[cfg(not(any(
+        feature = "libffi",
+        target_arch = "x86",
+        all(target_arch = "x86_64", any(target_os = "linux", windows)),
+        all(target_arch = "loongarch64", target_os = "linux")
+    )))]
+    pub unsafe fn cdecl(&mut self) {
+        panic!("{}", CallError::unsupported_target());
+    }
+
+    /// 以 stdcall 调用约定调用函数
+    /// 即 32 位下 WINAPI 使用的调用约定
+    ///
+    /// # Safety
+    ///
+    /// 压入的参数必须与函数的实际签名一致
+    #[cfg(target_arch = "x86")]
+    pub unsafe fn stdcall(&mut self) {
+        self.assert_stack_size();
+        sanitize::outgoing_args(self);
+        let fp = fpstate::save(self);
+        let observed = observer::begin(self, Convention::Stdcall);
+        self.called = true;
+        let args = self.stack_args(0);
+        let (low, high, float, single, popped, canary): (usize, usize, f64, f32, i32, i32);
+        asm!(
+            // 与 cdecl 相同, 先留出对齐所需的空间并压入哨兵
+            "mov edi, esp",
+            "sub esp, 8",
+            "lea ecx, [ecx * 4 + 4]",
+            "sub esp, ecx",
+            "and esp, -16",
+            "add esp, ecx",
+            "shr ecx, 2",
+            "dec ecx",
+            "push {canary}",
+            "mov dword ptr [edi - 8], esp",
+            "test ecx, ecx",
+            "jz 3f",
+            "2:",
+            "push dword ptr [edx - 4]",
+            "sub edx, 4",
+            "dec ecx",
+            "jnz 2b",
+            "3:",
+            "mov dword ptr [edi - 4], esp",
+            // 被调用者会弹出参数, 之后的处理与 cdecl 相同
+            "call eax",
+            "mov ecx, esp",
+            "sub ecx, dword ptr [edi - 4]",
+            "movd xmm2, ecx",
+            "mov ecx, dword ptr [edi - 8]",
+            "movd xmm3, dword ptr [ecx]",
+            "mov ecx, eax",
+            "sub esp, 12",
+            "fxam",
+            "fnstsw ax",
+            "and ah, 0x45",
+            "cmp ah, 0x41",
+            "je 4f",
+            "fst dword ptr [esp]",
+            "fstp qword ptr [esp + 4]",
+            "movss xmm1, dword ptr [esp]",
+            "movsd xmm0, qword ptr [esp + 4]",
+            "jmp 5f",
+            "4:",
+            "xorps xmm0, xmm0",
+            "xorps xmm1, xmm1",
+            "5:",
+            "mov eax, ecx",
+            // 去掉对齐用的空间
+            "mov esp, edi",
+            out("edi") _,
+            inout("edx") args.as_ptr().add(args.len()) => high,
+            inout("ecx") args.len() => _,
+            inout("eax") self.func => low,
+            out("xmm0") float,
+            out("xmm1") single,
+            out("xmm2") popped,
+            out("xmm3") canary,
+            canary = const STACK_CANARY,
+            clobber_abi("stdcall"),
+        );
+        self.ret.low = low;
+        self.ret.high = high;
+        self.ret.float = self.float_ret(float, single);
+        self.check_stack(Convention::Stdcall, popped, canary);
+        fpstate::restore(fp);
+        observer::end(observed, self, Convention::Stdcall);
+    }
+
+    /// 以 thiscall 调用约定调用函数, 参见 `Convention::Thiscall`
+    ///
+    /// 第一个压入的参数作为 this 通过 ecx 传递, 其余参数由被调用者清理;
+    /// 声明为可变参数函数或设置了 `ThiscallFlavor::Gcc` 时改为以 cdecl 调用, this 作为第一个栈参数
+    ///
+    /// # Safety
+    ///
+    /// 压入的参数必须与函数的实际签名一致
+    ///
+    /// # Panics
+    ///
+    /// 没有压入任何参数时 panic
+    #[cfg(target_arch = "x86")]
+    pub unsafe fn thiscall(&mut self) {
+        assert!(
+            !self.args.is_empty(),
+            "thiscall requires `this` as the first argument"
+        );
+        // MSVC 下可变参数的成员函数无法由被调用者清理参数, this 也随之改为压栈; GCC 的 thiscall 总是如此
+        if self.thiscall_as_cdecl() {
+            return self.cdecl();
+        }
+        self.assert_stack_size();
+        sanitize::outgoing_args(self);
+        let fp = fpstate::save(self);
+        let observed = observer::begin(self, Convention::Thiscall);
+        self.called = true;
+        let args = self.stack_args(1);
+        let (low, high, float, single, popped, canary): (usize, usize, f64, f32, i32, i32);
+        asm!(
+            // 与 stdcall 相同, 但只压入 this 之后的参数
+            "mov edi, esp",
+            "sub esp, 8",
+            "lea ecx, [ecx * 4 + 4]",
+            "sub esp, ecx",
+            "and esp, -16",
+            "add esp, ecx",
+            "shr ecx, 2",
+            "dec ecx",
+            "push {canary}",
+            "mov dword ptr [edi - 8], esp",
+            "test ecx, ecx",
+            "jz 3f",
+            "2:",
+            "push dword ptr [edx - 4]",
+            "sub edx, 4",
+            "dec ecx",
+            "jnz 2b",
+            "3:",
+            "mov dword ptr [edi - 4], esp",
+            // 此时 edx 指向第二个参数, 其前一个字即为 this
+            "mov ecx, dword ptr [edx - 4]",
+            "call eax",
+            "mov ecx, esp",
+            "sub ecx, dword ptr [edi - 4]",
+            "movd xmm2, ecx",
+            "mov ecx, dword ptr [edi - 8]",
+            "movd xmm3, dword ptr [ecx]",
+            "mov ecx, eax",
+            "sub esp, 12",
+            "fxam",
+            "fnstsw ax",
+            "and ah, 0x45",
+            "cmp ah, 0x41",
+            "je 4f",
+            "fst dword ptr [esp]",
+            "fstp qword ptr [esp + 4]",
+            "movss xmm1, dword ptr [esp]",
+            "movsd xmm0, qword ptr [esp + 4]",
+            "jmp 5f",
+            "4:",
+            "xorps xmm0, xmm0",
+            "xorps xmm1, xmm1",
+            "5:",
+            "mov eax, ecx",
+            "mov esp, edi",
+            out("edi") _,
+            inout("edx") args.as_ptr().add(args.len()) => high,
+            inout("ecx") args.len() - 1 => _,
+            inout("eax") self.func => low,
+            out("xmm0") float,
+            out("xmm1") single,
+            out("xmm2") popped,
+            out("xmm3") canary,
+            canary = const STACK_CANARY,
+            clobber_abi("C"),
+        );
+        self.ret.low = low;
+        self.ret.high = high;
+        self.ret.float = self.float_ret(float, single);
+        self.check_stack(Convention::Thiscall, popped, canary);
+        fpstate::restore(fp);
+        observer::end(observed, self, Convention::Thiscall);
+    }
+
+    /// 以 fastcall 调用约定调用函数, 参见 `Convention::Fastcall`
+    ///
+    /// # Safety
+    ///
+    /// 压入的参数必须与函数的实际签名一致
+    #[cfg(target_arch = "x86")]
+    pub unsafe fn fastcall(&mut self) {
+        if matches!(self.arity, Some((_, true))) {
+            return self.cdecl();
+        }
+        self.assert_stack_size();
+        sanitize::outgoing_args(self);
+        let fp = fpstate::save(self);
+        let observed = observer::begin(self, Convention::Fastcall);
+        self.called = true;
+        let words = self.fastcall_words();
+        let (low, high, float, single, popped, canary): (usize, usize, f64, f32, i32, i32);
+        asm!(
+            // 与 stdcall 相同, 但只压入 ecx 与 edx 之后的字
+            "mov edi, esp",
+            "sub esp, 8",
+            "lea ecx, [ecx * 4 + 4]",
+            "sub esp, ecx",
+            "and esp, -16",
+            "add esp, ecx",
+            "shr ecx, 2",
+            "dec ecx",
+            "push {canary}",
+            "mov dword ptr [edi - 8], esp",
+            "test ecx, ecx",
+            "jz 3f",
+            "2:",
+            "push dword ptr [edx - 4]",
+            "sub edx, 4",
+            "dec ecx",
+            "jnz 2b",
+            "3:",
+            "mov dword ptr [edi - 4], esp",
+            // 此时 edx 指向第一个栈上的字, 其前两个字即为 ecx 与 edx
+            "mov ecx, dword ptr [edx - 8]",
+            "mov edx, dword ptr [edx - 4]",
+            "call eax",
+            "mov ecx, esp",
+            "sub ecx, dword ptr [edi - 4]",
+            "movd xmm2, ecx",
+            "mov ecx, dword ptr [edi - 8]",
+            "movd xmm3, dword ptr [ecx]",
+            "mov ecx, eax",
+            "sub esp, 12",
+            "fxam",
+            "fnstsw ax",
+            "and ah, 0x45",
+            "cmp ah, 0x41",
+            "je 4f",
+            "fst dword ptr [esp]",
+            "fstp qword ptr [esp + 4]",
+            "movss xmm1, dword ptr [esp]",
+            "movsd xmm0, qword ptr [esp + 4]",
+            "jmp 5f",
+            "4:",
+            "xorps xmm0, xmm0",
+            "xorps xmm1, xmm1",
+            "5:",
+            "mov eax, ecx",
+            "mov esp, edi",
+            out("edi") _,
+            inout("edx") words.as_ptr().add(words.len()) => high,
+            inout("ecx") words.len() - 2 => _,
+            inout("eax") self.func => low,
+            out("xmm0") float,
+            out("xmm1") single,
+            out("xmm2") popped,
+            out("xmm3") canary,
+            canary = const STACK_CANARY,
+            clobber_abi("C"),
+        );
+        self.ret.low = low;
+        self.ret.high = high;
+        self.ret.float = self.float_ret(float, single);
+        self.check_stack(Convention::Fastcall, popped, canary);
+        fpstate::restore(fp);
+        observer::end(observed, self, Convention::Fastcall);
+    }
+
+    /// fastcall 下依次送入 ecx, edx 与压栈的字, 没有用到的寄存器为 0
+    ///
+    /// 按参数而不是按字分配寄存器: 占两个字的 i64 与 f64 以及浮点数整个留在栈上, 不会被拆到 edx 与栈中
+    #[cfg(target_arch = "x86")]
+    fn fastcall_words(&self) -> InlineVec<usize, 10> {
+        let mut words = InlineVec::new();
+        words.extend_from_slice(&[0, 0]);
+        let mut regs = 0;
+        let mut stacked: InlineVec<bool, 8> = InlineVec::new();
+        for slot in self.slots.iter() {
+            let float = matches!(slot.kind, ArgKind::F32 | ArgKind::F64 | ArgKind::CFloat);
+            let in_reg = regs < 2 && slot.len == 1 && !float && self.layout == LayoutOverride::Abi;
+            if in_reg {
+                words[regs] = self.args[slot.index];
+                regs += 1;
+            }
+            stacked.push(!in_reg);
+        }
+        let stack = self
+            .slots
+            .iter()
+            .zip(stacked.iter())
+            .filter(|(_, stacked)| **stacked)
+            .map(|(slot, _)| &self.args[slot.index..slot.index + slot.len]);
+        if self.order == ArgOrder::LeftToRight {
+            stack.rev().for_each(|arg| words.extend_from_slice(arg));
+        } else {
+            stack.for_each(|arg| words.extend_from_slice(arg));
+        }
+        words
+    }
+
+    /// 以与 `extern "system"` 相同的调用约定调用函数
+    /// 即 32 位 Windows 下的 stdcall, 其他平台上的 cdecl
+    ///
+    /// # Safety
+    ///
+    /// 压入的参数必须与函数的实际签名一致
+    pub unsafe fn system(&mut self) {
+        #[cfg(all(target_arch = "x86", windows))]
+        self.stdcall();
+        #[cfg(not(all(target_arch = "x86", windows)))]
+        self.cdecl();
+    }
+}
+
+/// 一个已压入的参数
+#[derive(Debug, Clone, Copy, PartialOrd, PartialEq)]
+struct ArgSlot {
+    kind: ArgKind,
+    /// 是否储存在 fargs 中
+    float: bool,
+    /// 在 args 或 fargs 中的起始位置
+    index: usize,
+    /// 占用的字数
+    len: usize,
+    /// 起始位置所需的对齐字节数
+    align: usize,
+    /// 压入参数的代码位置, 见 `provenance` feature
+    #[cfg(feature = "provenance")]
+    origin: Origin,
+}
+
+/// 参数的压入位置, 只用于调试输出, 比较 `Func` 与 `ArgView` 时视为总是相等
+#[cfg(feature = "provenance")]
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct Origin(Option<&'static Location<'static>>);
+
+#[cfg(feature = "provenance")]
+impl PartialEq for Origin {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+#[cfg(feature = "provenance")]
+impl Eq for Origin {}
+
+#[cfg(feature = "provenance")]
+impl PartialOrd for Origin {
+    fn partial_cmp(&self, _other: &Self) -> Option<core::cmp::Ordering> {
+        Some(core::cmp::Ordering::Equal)
+    }
+}
+
+#[cfg(feature = "provenance")]
+impl core::hash::Hash for Origin {
+    fn hash<H: core::hash::Hasher>(&self, _state: &mut H) {}
+}
+
+/// 仅用于填充 `InlineVec` 中未使用的位置
+impl Default for ArgSlot {
+    fn default() -> Self {
+        ArgSlot {
+            kind: ArgKind::Other,
+            float: false,
+            index: 0,
+            len: 0,
+            align: 0,
+            #[cfg(feature = "provenance")]
+            origin: Origin(None),
+        }
+    }
+}
+
+impl Func {
+    /// 上一次调用的返回值
+    ///
+    /// 通过 `set_restype` 声明了窄于寄存器的整数类型时, 会舍弃寄存器高位中的残留值并按类型做符号扩展或零扩展
+    pub fn ret(&self) -> RetValues {
+        match &self.restype {
+            Some(ty) => signature::narrow_ret(ty, self.ret),
+            None => self.ret,
+        }
+    }
+
+    pub fn ret_as_i8(&self) -> i8 {
+        self.ret().as_i8()
+    }
+
+    pub fn ret_as_u8(&self) -> u8 {
+        self.ret().as_u8()
+    }
+
+    pub fn ret_as_i16(&self) -> i16 {
+        self.ret().as_i16()
+    }
+
+    pub fn ret_as_u16(&self) -> u16 {
+        self.ret().as_u16()
+    }
+
+    pub fn ret_as_i32(&self) -> i32 {
+        self.ret().as_i32()
+    }
+
+    pub fn ret_as_u32(&self) -> u32 {
+        self.ret().as_u32()
+    }
+
+    pub fn ret_as_i64(&self) -> i64 {
+        self.ret().as_i64()
+    }
+
+    pub fn ret_as_u64(&self) -> u64 {
+        self.ret().as_u64()
+    }
+
+    pub fn ret_as_isize(&self) -> isize {
+        self.ret().as_isize()
+    }
+
+    pub fn ret_as_usize(&self) -> usize {
+        self.ret().as_usize()
+    }
+
+    pub fn ret_as_i128(&self) -> i128 {
+        self.ret().as_i128()
+    }
+
+    pub fn ret_as_u128(&self) -> u128 {
+        self.ret().as_u128()
+    }
+
+    pub fn ret_as_f32(&self) -> f32 {
+        self.ret().as_f32()
+    }
+
+    pub fn ret_as_f64(&self) -> f64 {
+        self.ret().as_f64()
+    }
+
+    /// 取出浮点返回值寄存器低 16 位中的 `_Float16`, libffi 后端需要先通过 `set_ret_kind` 声明为 `ArgKind::F16`
+    pub fn ret_as_f16(&self) -> F16 {
+        self.ret().as_f16()
+    }
+
+    pub fn ret_as_c_char(&self) -> c_char {
+        self.ret().as_c_char()
+    }
+
+    pub fn ret_as_c_int(&self) -> c_int {
+        self.ret().as_c_int()
+    }
+
+    pub fn ret_as_c_uint(&self) -> c_uint {
+        self.ret().as_c_uint()
+    }
+
+    pub fn ret_as_c_long(&self) -> c_long {
+        self.ret().as_c_long()
+    }
+
+    pub fn ret_as_c_ulong(&self) -> c_ulong {
+        self.ret().as_c_ulong()
+    }
+
+    pub fn ret_as_c_size_t(&self) -> usize {
+        self.ret().as_usize()
+    }
+
+    pub fn ret_as_c_ssize_t(&self) -> isize {
+        self.ret().as_isize()
+    }
+
+    /// 按 Linux 系统调用的约定解读返回值: -4095 到 -1 之间的值为取反的错误码, 其他值为成功时的结果
+    ///
+    /// 用于直接发出系统调用的函数, 如不经过 libc 的 `syscall` 指令的包装.
+    /// libc 的 `syscall(2)` 等函数已经将错误转换为返回 -1 并设置 errno, 应改为检查 `last_os_error`
+    ///
+    /// ```
+    /// use funcall::Func;
+    /// extern "C" fn fail() -> isize {
+    ///     -2
+    /// }
+    ///
+    /// let mut func = Func::from_raw(fail as *const fn());
+    /// unsafe { func.cdecl() };
+    /// let e = func.ret_as_syscall_result().unwrap_err();
+    /// assert_eq!(e.kind(), std::io::ErrorKind::NotFound);
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn ret_as_syscall_result(&self) -> std::io::Result<usize> {
+        match self.ret_as_isize() {
+            ret @ -4095..=-1 => Err(std::io::Error::from_raw_os_error(-ret as i32)),
+            ret => Ok(ret as usize),
+        }
+    }
+}
+
+/// 函数调用后各返回值寄存器的值
+#[derive(Debug, Clone, Copy, Default, PartialOrd, PartialEq)]
+pub struct RetValues {
+    /// 返回值低位
+    pub low: usize,
+    /// 返回值高位
+    pub high: usize,
+    /// 浮点寄存器的值
+    pub float: f64,
+}
+
+/// 从返回值寄存器中取出指定类型的返回值
+pub trait FromRet {
+    fn from_ret(ret: &RetValues) -> Self;
+}
+
+macro_rules! impl_fromret {
+    ($($ty:ty => $method:ident), *) => {
+        $(impl FromRet for $ty {
+            fn from_ret(ret: &RetValues) -> Self {
+                ret.$method()
+            }
+        })*
+    };
+}
+
+impl_fromret!(
+    i8 => as_i8, u8 => as_u8, i16 => as_i16, u16 => as_u16, i32 => as_i32, u32 => as_u32,
+    i64 => as_i64, u64 => as_u64, i128 => as_i128, u128 => as_u128,
+    isize => as_isize, usize => as_usize, f32 => as_f32, f64 => as_f64
+);
+
+impl FromRet for () {
+    fn from_ret(_: &RetValues) -> Self {}
+}
+
+impl<T> FromRet for *const T {
+    fn from_ret(ret: &RetValues) -> Self {
+        ret.low as *const T
+    }
+}
+
+impl<T> FromRet for *mut T {
+    fn from_ret(ret: &RetValues) -> Self {
+        ret.low as *mut T
+    }
+}
+
+impl RetValues {
+    /// 以 T 类型取出返回值
+    pub fn get<T: FromRet>(&self) -> T {
+        T::from_ret(self)
+    }
+
+    pub fn as_i8(&self) -> i8 {
+        self.low as i8
+    }
+
+    pub fn as_u8(&self) -> u8 {
+        self.low as u8
+    }
+
+    pub fn as_i16(&self) -> i16 {
+        self.low as i16
+    }
+
+    pub fn as_u16(&self) -> u16 {
+        self.low as u16
+    }
+
+    pub fn as_i32(&self) -> i32 {
+        self.low as i32
+    }
+
+    pub fn as_u32(&self) -> u32 {
+        self.low as u32
+    }
+
+    pub fn as_i64(&self) -> i64 {
+        self.as_u64() as i64
+    }
+
+    pub fn as_u64(&self) -> u64 {
+        if cfg!(target_arch = "x86") {
+            (self.high as u64) << 32 | self.low as u64
+        } else {
+            self.low as u64
+        }
+    }
+
+    pub fn as_isize(&self) -> isize {
+        self.low as isize
+    }
+
+    pub fn as_usize(&self) -> usize {
+        self.low
+    }
+
+    pub fn as_i128(&self) -> i128 {
+        self.as_u128() as i128
+    }
+
+    pub fn as_u128(&self) -> u128 {
+        if cfg!(target_pointer_width = "64") {
+            (self.high as u128) << 64 | self.low as u128
+        } else {
+            unimplemented!()
+        }
+    }
+
+    pub fn as_f32(&self) -> f32 {
+        self.float as f32
+    }
+
+    pub fn as_f64(&self) -> f64 {
+        self.float
+    }
+
+    /// 浮点寄存器的低 16 位, 返回 `_Float16` 的函数不会提升返回值
+    pub fn as_f16(&self) -> F16 {
+        F16::from_bits(self.float.to_bits() as u16)
+    }
+
+    // C 语言的整数类型都不比指针宽, 按目标平台上的宽度截断 low 即可
+
+    pub fn as_c_char(&self) -> c_char {
+        self.low as c_char
+    }
+
+    pub fn as_c_int(&self) -> c_int {
+        self.low as c_int
+    }
+
+    pub fn as_c_uint(&self) -> c_uint {
+        self.low as c_uint
+    }
+
+    pub fn as_c_long(&self) -> c_long {
+        self.low as c_long
+    }
+
+    pub fn as_c_ulong(&self) -> c_ulong {
+        self.low as c_ulong
     }
 }
@@ -0,0 +1,255 @@
+//! 通过 libffi 发出调用, 用于没有手写汇编的平台
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::ffi::{c_uint, c_ushort, c_void};
+use core::mem;
+use core::ptr::{self, addr_of_mut};
+
+// ABI 的取值与 ffi_cif 末尾的字段因平台和 libffi 的版本而异, 由 libffi-sys 按 ffitarget.h 给出
+use libffi_sys::{
+    ffi_abi, ffi_abi_FFI_DEFAULT_ABI, ffi_call, ffi_cif, ffi_prep_cif, ffi_prep_cif_var,
+    ffi_status_FFI_OK, ffi_type as FfiType, ffi_type_double, ffi_type_float, ffi_type_pointer,
+    ffi_type_sint16, ffi_type_sint32, ffi_type_sint64, ffi_type_sint8, ffi_type_uint16,
+    ffi_type_uint32, ffi_type_uint64, ffi_type_uint8, FFI_TYPE_STRUCT,
+};
+
+use crate::{fpstate, observer, ArgKind, ArgSlot, Convention, Func};
+
+/// conv 对应的 libffi ABI
+fn abi(conv: Convention) -> ffi_abi {
+    match conv {
+        #[cfg(target_arch = "x86")]
+        Convention::Stdcall => libffi_sys::ffi_abi_FFI_STDCALL,
+        // ARM32 下的 FFI_SYSV 与 FFI_VFP, 分别将浮点参数放在整数寄存器与 VFP 寄存器中
+        #[cfg(target_arch = "arm")]
+        Convention::AapcsSoftFloat => libffi_sys::ffi_abi_FFI_SYSV,
+        #[cfg(target_arch = "arm")]
+        Convention::AapcsVfp => libffi_sys::ffi_abi_FFI_VFP,
+        _ => ffi_abi_FFI_DEFAULT_ABI,
+    }
+}
+
+/// 与指针等宽的无符号整数
+unsafe fn word_type() -> *mut FfiType {
+    if mem::size_of::<usize>() == 8 {
+        addr_of_mut!(ffi_type_uint64)
+    } else {
+        addr_of_mut!(ffi_type_uint32)
+    }
+}
+
+/// 由若干个字组成的结构体, 用于 i128 等 libffi 没有对应类型的参数, 以及由浮点数组成的结构体返回值
+struct WordStruct {
+    ty: Box<FfiType>,
+    _elements: Vec<*mut FfiType>,
+}
+
+impl WordStruct {
+    unsafe fn new(words: usize) -> Self {
+        Self::with_alignment(words, 0)
+    }
+
+    /// i128 与 u128 的对齐比字更大, 需要预先给出 size 与 alignment, 否则栈上的位置不对
+    unsafe fn int128() -> Self {
+        Self::with_alignment(16 / mem::size_of::<usize>(), mem::align_of::<i128>())
+    }
+
+    /// 由 count 个 float 或 double 组成的结构体, 用于 `RetLayout::FloatAggregate`
+    unsafe fn floats(count: usize, double: bool) -> Self {
+        let element = if double {
+            addr_of_mut!(ffi_type_double)
+        } else {
+            addr_of_mut!(ffi_type_float)
+        };
+        Self::of(element, count, 0)
+    }
+
+    unsafe fn with_alignment(words: usize, alignment: usize) -> Self {
+        Self::of(word_type(), words, alignment)
+    }
+
+    unsafe fn of(element: *mut FfiType, count: usize, alignment: usize) -> Self {
+        let mut elements = vec![element; count];
+        elements.push(ptr::null_mut());
+        // size 为 0 时由 ffi_prep_cif 计算 size 与 alignment
+        let size = if alignment == 0 {
+            0
+        } else {
+            count * mem::size_of::<usize>()
+        };
+        let ty = Box::new(FfiType {
+            size,
+            alignment: alignment as c_ushort,
+            type_: FFI_TYPE_STRUCT,
+            elements: elements.as_mut_ptr(),
+        });
+        Self {
+            ty,
+            _elements: elements,
+        }
+    }
+
+    fn as_ptr(&mut self) -> *mut FfiType {
+        &mut *self.ty
+    }
+}
+
+/// 按照压入时记录的参数类型构造 CIF 并调用函数
+pub(crate) unsafe fn call(func: &mut Func, conv: Convention) {
+    func.assert_stack_size();
+    let abi = abi(conv);
+
+    // 每个参数的值按其实际类型写入各自的缓冲区, 结构体类型需要在调用结束前保持有效
+    let mut values = vec![[0u64; 4]; func.slots.len()];
+    let mut structs = Vec::new();
+    let mut types = Vec::with_capacity(func.slots.len());
+    for (slot, value) in func.slots.iter().zip(&mut values) {
+        types.push(write_arg(func, slot, value, &mut structs));
+    }
+    let mut pointers = values
+        .iter_mut()
+        .map(|value| value.as_mut_ptr() as *mut c_void)
+        .collect::<Vec<_>>();
+
+    let mut ret_struct = None;
+    // 声明为结构体的返回值交给 libffi 按 ABI 处理, 需要的缓冲区可能超过 4 个字
+    let aggregate = func.aggregate.and_then(|ret| ret.ffi_words());
+    let mut ret = vec![0usize; aggregate.unwrap_or(0).max(4)];
+    let floats = func.aggregate.and_then(|ret| ret.ffi_floats());
+    let rtype = match func.ret_kind {
+        // 浮点结构体需要如实描述成员的类型, libffi 才能按 ABI 从浮点寄存器中取回
+        _ if aggregate.is_some() => ret_struct
+            .get_or_insert_with(|| match floats {
+                Some((count, double)) => WordStruct::floats(count, double),
+                None => WordStruct::new(aggregate.unwrap_or(1)),
+            })
+            .as_ptr(),
+        // libffi 没有半精度浮点数, 以 float 取回的 s0 / xmm0 的低 16 位即为 _Float16
+        Some(ArgKind::F32) | Some(ArgKind::CFloat) | Some(ArgKind::F16) => {
+            addr_of_mut!(ffi_type_float)
+        }
+        Some(ArgKind::F64) => addr_of_mut!(ffi_type_double),
+        Some(ArgKind::I64) | Some(ArgKind::U64) => addr_of_mut!(ffi_type_uint64),
+        Some(ArgKind::I128) | Some(ArgKind::U128) => ret_struct
+            .get_or_insert_with(|| WordStruct::int128())
+            .as_ptr(),
+        _ => word_type(),
+    };
+
+    let mut cif = mem::zeroed::<ffi_cif>();
+    let status = match func.arity {
+        Some((fixed, true)) => ffi_prep_cif_var(
+            &mut cif,
+            abi,
+            fixed as c_uint,
+            types.len() as c_uint,
+            rtype,
+            types.as_mut_ptr(),
+        ),
+        _ => ffi_prep_cif(
+            &mut cif,
+            abi,
+            types.len() as c_uint,
+            rtype,
+            types.as_mut_ptr(),
+        ),
+    };
+    assert_eq!(status, ffi_status_FFI_OK, "ffi_prep_cif failed");
+
+    let fp = fpstate::save(func);
+    let observed = observer::begin(func, conv);
+    func.called = true;
+    // libffi 会将不足一个字的整数返回值扩展为一个字, 缓冲区至少要有 16 字节
+    ffi_call(
+        &mut cif,
+        mem::transmute::<*const fn(), Option<unsafe extern "C" fn()>>(func.func),
+        ret.as_mut_ptr() as *mut c_void,
+        pointers.as_mut_ptr(),
+    );
+    func.ret = Default::default();
+    match func.ret_kind {
+        Some(ArgKind::F32) | Some(ArgKind::CFloat) => {
+            func.ret.float = f64::from(ptr::read(ret.as_ptr() as *const f32));
+        }
+        Some(ArgKind::F64) => func.ret.float = ptr::read(ret.as_ptr() as *const f64),
+        Some(ArgKind::F16) => {
+            func.ret.float = f64::from_bits(u64::from(ptr::read(ret.as_ptr() as *const u32)));
+        }
+        // 按内存中的顺序读出, 大端序下高位的字在前
+        Some(ArgKind::I128) | Some(ArgKind::U128) if mem::size_of::<usize>() == 8 => {
+            let value = ptr::read_unaligned(ret.as_ptr() as *const u128);
+            func.ret.low = value as usize;
+            func.ret.high = (value >> 64) as usize;
+        }
+        _ => {
+            func.ret.low = ret[0];
+            func.ret.high = ret[1];
+        }
+    }
+    // 隐藏指针不作为参数传递时, 写入内存的返回值由 libffi 放在 ret 中
+    if let (Some(_), Some(info)) = (aggregate, func.aggregate) {
+        if let Some(buf) = info.ptr(func) {
+            ptr::copy_nonoverlapping(ret.as_ptr() as *const u8, buf, info.size);
+        }
+    }
+    fpstate::restore(fp);
+    observer::end(observed, func, conv);
+}
+
+/// 将参数写入 value, 返回其 libffi 类型
+unsafe fn write_arg(
+    func: &Func,
+    slot: &ArgSlot,
+    value: &mut [u64; 4],
+    structs: &mut Vec<WordStruct>,
+) -> *mut FfiType {
+    let dst = value.as_mut_ptr() as *mut u8;
+    if slot.float {
+        let bits = func.fargs[slot.index].to_bits();
+        return if matches!(slot.kind, ArgKind::CFloat | ArgKind::F16) {
+            // xmm 寄存器的低 32 位即为 float, 其中的低 16 位即为 _Float16
+            ptr::write(dst as *mut u32, bits as u32);
+            addr_of_mut!(ffi_type_float)
+        } else {
+            ptr::write(dst as *mut u64, bits);
+            addr_of_mut!(ffi_type_double)
+        };
+    }
+
+    // 多字的参数按顺序拼接即为其在内存中的表示
+    let words = &func.args[slot.index..slot.index + slot.len];
+    ptr::copy_nonoverlapping(words.as_ptr() as *const u8, dst, mem::size_of_val(words));
+    let word = words.first().copied().unwrap_or_default();
+    macro_rules! narrow {
+        ($ty:ty, $ffi:ident) => {{
+            ptr::write(dst as *mut $ty, word as $ty);
+            addr_of_mut!($ffi)
+        }};
+    }
+    match slot.kind {
+        ArgKind::I8 => narrow!(i8, ffi_type_sint8),
+        ArgKind::U8 => narrow!(u8, ffi_type_uint8),
+        ArgKind::I16 => narrow!(i16, ffi_type_sint16),
+        ArgKind::U16 => narrow!(u16, ffi_type_uint16),
+        ArgKind::I32 => narrow!(i32, ffi_type_sint32),
+        ArgKind::U32 => narrow!(u32, ffi_type_uint32),
+        ArgKind::CFloat | ArgKind::F16 => narrow!(u32, ffi_type_float),
+        ArgKind::I64 if slot.len == 1 => narrow!(i64, ffi_type_sint64),
+        ArgKind::U64 if slot.len == 1 => narrow!(u64, ffi_type_uint64),
+        ArgKind::I64 => addr_of_mut!(ffi_type_sint64),
+        ArgKind::U64 => addr_of_mut!(ffi_type_uint64),
+        // 提升为 double 的 f32 与 f64 相同
+        ArgKind::F32 | ArgKind::F64 => addr_of_mut!(ffi_type_double),
+        ArgKind::Ptr => addr_of_mut!(ffi_type_pointer),
+        _ if slot.len == 1 => word_type(),
+        ArgKind::I128 | ArgKind::U128 => {
+            structs.push(WordStruct::int128());
+            structs.last_mut().unwrap().as_ptr()
+        }
+        _ => {
+            structs.push(WordStruct::new(slot.len));
+            structs.last_mut().unwrap().as_ptr()
+        }
+    }
+}
@@ -0,0 +1,502 @@
+//! 动态库的加载与符号查找
+
+use std::cmp::Ordering;
+use std::error::Error;
+use std::ffi::{c_void, OsStr, OsString};
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::mem::ManuallyDrop;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::SystemTime;
+
+#[cfg(unix)]
+use libloading::os::unix::Library as RawLibrary;
+#[cfg(windows)]
+use libloading::os::windows::Library as RawLibrary;
+
+use crate::resolver::{self, ResolvedLib};
+use crate::{CType, Func, Result};
+
+/// 已加载的动态库
+///
+/// 克隆只会增加引用计数, 由它解析出的 `Func` 也会持有一份引用,
+/// 因此在最后一个引用被 drop 之前库都不会被卸载
+#[derive(Debug, Clone)]
+pub struct Library {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug)]
+struct Inner {
+    lib: libloading::Library,
+    /// dlopen / LoadLibrary 返回的原始句柄
+    handle: usize,
+    /// 加载时使用的路径, 接管已有句柄时为 `None`
+    path: Option<PathBuf>,
+    /// 通过 `open_reloadable` 打开时的重载信息
+    reload: Option<Arc<Reload>>,
+    /// 加载时源文件的修改时间和大小
+    stamp: Stamp,
+}
+
+type Stamp = Option<(SystemTime, u64)>;
+
+/// 同一路径的各代可重载库共享的状态
+#[derive(Debug)]
+struct Reload {
+    path: PathBuf,
+    generation: AtomicUsize,
+    /// 最新加载的一代, 避免每个 Func 各自重新加载一次
+    latest: Mutex<Weak<Inner>>,
+}
+
+impl Library {
+    /// 加载动态库, 库名先经过 `funcall::set_resolver` 安装的解析器解析
+    pub fn new<P: AsRef<OsStr>>(path: P) -> Result<Self> {
+        match resolver::resolve(path.as_ref())? {
+            ResolvedLib::Path(path) => {
+                let lib = libloading::Library::new(&path)?;
+                Ok(Self::from_lib(lib, Some(path), None, None))
+            }
+            ResolvedLib::Handle(lib) => Ok(lib),
+            ResolvedLib::Bytes(bytes) => {
+                let id = IN_MEMORY.fetch_add(1, AtomicOrdering::Relaxed);
+                let lib = load_copy(&format!("mem{}", id), |file| file.write_all(&bytes))?;
+                Ok(Self::from_lib(lib, None, None, None))
+            }
+        }
+    }
+
+    /// 依次尝试加载 candidates 中的库, 返回第一个加载成功的库及其名字
+    ///
+    /// 用于不同发行版或版本间名字不同的库. 每个名字都和 `Library::new` 一样先经过解析器解析,
+    /// 全部失败时在 `OpenFirstError` 中按顺序列出每个名字及其错误
+    ///
+    /// ```
+    /// use funcall::Library;
+    ///
+    /// # #[cfg(target_os = "linux")]
+    /// # {
+    /// let (lib, name) = Library::open_first(&["libc.so.7", "libc.so.6"]).unwrap();
+    /// assert_eq!(name, "libc.so.6");
+    /// assert!(lib.get("strlen").is_ok());
+    /// # }
+    /// ```
+    pub fn open_first<P: AsRef<OsStr>>(
+        candidates: &[P],
+    ) -> std::result::Result<(Self, &OsStr), OpenFirstError> {
+        open_first(candidates, |name| Self::new(name))
+    }
+
+    /// 以可重载的方式加载动态库
+    ///
+    /// 会记录文件的修改时间和大小, 之后可以通过 `Func::ensure_fresh` 在文件变化时重新加载.
+    /// 由于同一路径的库重复 dlopen 只会返回已加载的那份, 实际加载的是库文件的一份临时副本.
+    /// 旧版本的库会一直保持加载, 直到没有 `Func` 再引用它为止
+    pub fn open_reloadable<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let reload = Arc::new(Reload {
+            path: path.as_ref().to_owned(),
+            generation: AtomicUsize::new(0),
+            latest: Mutex::new(Weak::new()),
+        });
+        reload.load()
+    }
+
+    /// 接管一个已有的 dlopen / LoadLibrary 句柄
+    ///
+    /// 最后一个引用被 drop 时会对其调用 dlclose / FreeLibrary
+    ///
+    /// # Safety
+    ///
+    /// handle 必须是有效的库句柄, 且此后不能再由其他人关闭
+    pub unsafe fn from_raw(handle: *mut c_void) -> Self {
+        Self::from_lib(RawLibrary::from_raw(handle as _).into(), None, None, None)
+    }
+
+    fn from_lib(
+        lib: libloading::Library,
+        path: Option<PathBuf>,
+        reload: Option<Arc<Reload>>,
+        stamp: Stamp,
+    ) -> Self {
+        // libloading 只能通过 into_raw 取得句柄, 取出后再放回去
+        let handle = RawLibrary::from(lib).into_raw();
+        let lib = unsafe { RawLibrary::from_raw(handle).into() };
+        Self {
+            inner: Arc::new(Inner {
+                lib,
+                handle: handle as usize,
+                path,
+                reload,
+                stamp,
+            }),
+        }
+    }
+
+    /// 进程中已经加载的 C 运行库, 不会读取文件系统
+    ///
+    /// Unix 下为 `dlopen(NULL)` 返回的句柄, 查找时与 `RTLD_DEFAULT` 一样在全局范围内查找;
+    /// Windows 下为已加载的 `ucrtbase.dll`, 没有时为 `msvcrt.dll`. 需要按符号回退时使用 `funcall::libc_func`
+    pub fn libc() -> Result<Self> {
+        #[cfg(unix)]
+        return Ok(Self::from_lib(RawLibrary::this().into(), None, None, None));
+        #[cfg(windows)]
+        return loaded_crts().next().ok_or_else(crt_not_loaded);
+    }
+
+    /// 获取原始句柄, 所有权仍归本库所有
+    pub fn as_raw(&self) -> *mut c_void {
+        self.inner.handle as *mut c_void
+    }
+
+    /// 加载时使用的路径, 可重载的库返回原始文件而非临时副本的路径
+    pub fn path(&self) -> Option<&Path> {
+        self.inner.path.as_deref()
+    }
+
+    /// 列出库导出的所有符号名
+    #[cfg(windows)]
+    pub fn exports(&self) -> Vec<String> {
+        unsafe { crate::pe::export_names(self.as_raw() as *const u8) }
+    }
+
+    /// 若本库是通过 `open_reloadable` 打开且文件已发生变化, 返回重新加载后的库
+    pub(crate) fn reloaded(&self) -> Result<Option<Self>> {
+        match &self.inner.reload {
+            Some(reload) => {
+                let latest = reload.latest()?;
+                Ok(if latest == *self { None } else { Some(latest) })
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// 查找函数, 返回的 `Func` 会持有对本库的引用
+    ///
+    /// 32 位 Windows 下找到的是 `_name@N` 这样的修饰名时, `try_call` 会检查压入的参数是否恰好为 N 字节,
+    /// 不符时返回 `CallError::ArgSizeMismatch`
+    pub fn get(&self, symbol: &str) -> Result<Func> {
+        self.get_bytes(symbol.as_bytes())
+    }
+
+    /// 同 `get`, 但找不到函数时返回 `Func::null()`, 用于插件中可选的函数
+    pub fn get_optional(&self, symbol: &str) -> Func {
+        self.get_optional_bytes(symbol.as_bytes())
+    }
+
+    pub(crate) fn get_optional_bytes(&self, symbol: &[u8]) -> Func {
+        // 库已经加载, 查找失败只可能是符号不存在
+        self.get_bytes(symbol).unwrap_or_else(|_| Func::null())
+    }
+
+    /// 查找 C++ 函数, 会根据参数类型生成 Itanium ABI 下的修饰名, 参见 `Func::new_cpp`
+    pub fn get_cpp(&self, name: &str, params: &[CType]) -> Result<Func> {
+        self.get(&crate::cpp::itanium_mangle(name, params)?)
+    }
+
+    /// 在导出表中查找名字为 name 的 MSVC 修饰过的 C++ 函数, 返回所有候选的修饰名
+    ///
+    /// name 可以是不带作用域的函数名 (如 `add`), 也可以是完整的限定名 (如 `math::add`).
+    /// 只根据名字进行匹配, 重载的函数会返回多个候选, 需要调用者自行挑选
+    #[cfg(windows)]
+    pub fn find_cpp(&self, name: &str) -> Vec<String> {
+        self.exports()
+            .into_iter()
+            .filter(|symbol| match crate::cpp::msvc_demangle_name(symbol) {
+                Some(qualified) => qualified == name || qualified.rsplit("::").next() == Some(name),
+                None => false,
+            })
+            .collect()
+    }
+
+    /// 一次性查找多个函数
+    ///
+    /// 不会在第一个失败处停下, 而是在 `BatchError` 中列出所有查找失败的符号
+    pub fn get_many(&self, names: &[&str]) -> std::result::Result<Vec<Func>, BatchError> {
+        let mut resolved = Vec::with_capacity(names.len());
+        let mut failed = Vec::new();
+        for name in names {
+            match self.get(name) {
+                Ok(func) => resolved.push(Some(func)),
+                Err(e) => {
+                    resolved.push(None);
+                    failed.push((name.to_string(), e));
+                }
+            }
+        }
+        if failed.is_empty() {
+            Ok(resolved.into_iter().map(Option::unwrap).collect())
+        } else {
+            Err(BatchError { resolved, failed })
+        }
+    }
+
+    pub(crate) fn get_bytes(&self, symbol: &[u8]) -> Result<Func> {
+        let (ptr, symbol) = match unsafe { lookup(&self.inner.lib, symbol) } {
+            Ok(ptr) => (ptr, symbol_to_string(symbol)),
+            // 32 位 Windows 下导出的符号名常常带有修饰, 找不到时在导出表中查找修饰后的名字
+            #[cfg(all(windows, target_arch = "x86"))]
+            Err(e) => {
+                let exports = self.exports();
+                let name = crate::pe::find_decorated(&exports, &symbol_to_string(symbol))
+                    .ok_or(e)?
+                    .to_owned();
+                (unsafe { lookup(&self.inner.lib, name.as_bytes())? }, name)
+            }
+            #[cfg(not(all(windows, target_arch = "x86")))]
+            Err(e) => return Err(e),
+        };
+        let mut func = Func::from_raw(ptr);
+        func.lib = Some(self.clone());
+        #[cfg(all(windows, target_arch = "x86"))]
+        {
+            func.decorated_bytes = crate::pe::decorated_bytes(&symbol);
+        }
+        func.symbol = Some(symbol);
+        Ok(func)
+    }
+}
+
+/// 在进程已经加载的 C 运行库中查找函数, 不会读取文件系统
+///
+/// 与 `Library::libc()?.get(symbol)` 相同, 但 Windows 下会依次在 `ucrtbase.dll` 与 `msvcrt.dll` 中查找,
+/// 因为 UCRT 不再导出 `sprintf` 等在头文件中内联实现的函数
+///
+/// ```
+/// use funcall::Convention;
+///
+/// let mut strlen = funcall::libc_func("strlen").unwrap();
+/// strlen.push(b"hello\0".as_ptr());
+/// unsafe { strlen.try_call(Convention::Cdecl).unwrap() };
+/// assert_eq!(strlen.ret_as_i32(), 5);
+/// ```
+pub fn libc_func(symbol: &str) -> Result<Func> {
+    #[cfg(unix)]
+    return Library::libc()?.get(symbol);
+    #[cfg(windows)]
+    {
+        let mut last = None;
+        for lib in loaded_crts() {
+            match lib.get(symbol) {
+                Ok(func) => return Ok(func),
+                Err(e) => last = Some(e),
+            }
+        }
+        Err(last.unwrap_or_else(crt_not_loaded))
+    }
+}
+
+/// 按优先级列出已经加载的 C 运行库
+#[cfg(windows)]
+fn loaded_crts() -> impl Iterator<Item = Library> {
+    extern "system" {
+        fn GetModuleHandleExW(flags: u32, name: *const u16, module: *mut *mut c_void) -> i32;
+    }
+    ["ucrtbase.dll", "msvcrt.dll"].iter().filter_map(|name| {
+        let name: Vec<u16> = name.encode_utf16().chain(Some(0)).collect();
+        let mut handle = std::ptr::null_mut();
+        // 不带 GET_MODULE_HANDLE_EX_FLAG_UNCHANGED_REFCOUNT, 引用计数加一, 与 drop 时的 FreeLibrary 相抵
+        match unsafe { GetModuleHandleExW(0, name.as_ptr(), &mut handle) } {
+            0 => None,
+            _ => Some(unsafe { Library::from_raw(handle) }),
+        }
+    })
+}
+
+#[cfg(windows)]
+fn crt_not_loaded() -> crate::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::NotFound,
+        "neither ucrtbase.dll nor msvcrt.dll is loaded",
+    )
+    .into()
+}
+
+/// 依次对 candidates 调用 open, 返回第一个成功的结果, 全部失败时收集每个候选的错误
+pub(crate) fn open_first<P: AsRef<OsStr>, T>(
+    candidates: &[P],
+    mut open: impl FnMut(&OsStr) -> Result<T>,
+) -> std::result::Result<(T, &OsStr), OpenFirstError> {
+    let mut failed = Vec::with_capacity(candidates.len());
+    for candidate in candidates {
+        let name = candidate.as_ref();
+        match open(name) {
+            Ok(opened) => return Ok((opened, name)),
+            Err(e) => failed.push((name.to_owned(), e)),
+        }
+    }
+    Err(OpenFirstError { failed })
+}
+
+/// `Library::open_first` 与 `Func::new_first` 的所有候选都失败时的错误
+#[derive(Debug)]
+pub struct OpenFirstError {
+    /// 按尝试的顺序列出每个候选的名字及其错误
+    pub failed: Vec<(OsString, crate::Error)>,
+}
+
+impl fmt::Display for OpenFirstError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "none of {} candidate(s) could be opened:",
+            self.failed.len()
+        )?;
+        for (name, e) in &self.failed {
+            write!(f, "\n  {}: {}", name.to_string_lossy(), e)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for OpenFirstError {}
+
+/// `Library::get_many` 的错误
+#[derive(Debug)]
+pub struct BatchError {
+    /// 与传入的符号一一对应, 查找失败处为 `None`
+    pub resolved: Vec<Option<Func>>,
+    /// 所有查找失败的符号及其错误
+    pub failed: Vec<(String, crate::Error)>,
+}
+
+impl fmt::Display for BatchError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "failed to resolve {} symbol(s):", self.failed.len())?;
+        for (name, e) in &self.failed {
+            write!(f, "\n  {}: {}", name, e)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for BatchError {}
+
+/// 两个 `Library` 指向同一次加载时相等
+impl PartialEq for Library {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.inner, &other.inner)
+    }
+}
+
+impl PartialOrd for Library {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Arc::as_ptr(&self.inner).partial_cmp(&Arc::as_ptr(&other.inner))
+    }
+}
+
+impl Reload {
+    /// 返回最新的一代, 文件有变化时重新加载
+    fn latest(self: &Arc<Self>) -> Result<Library> {
+        let latest = self.latest.lock().unwrap().upgrade();
+        match latest {
+            Some(inner) if inner.stamp == stamp(&self.path)? => Ok(Library { inner }),
+            _ => self.load(),
+        }
+    }
+
+    fn load(self: &Arc<Self>) -> Result<Library> {
+        let mut latest = self.latest.lock().unwrap();
+        // 可能已经被其他线程重新加载过了
+        let stamp = stamp(&self.path)?;
+        if let Some(inner) = latest.upgrade() {
+            if inner.stamp == stamp {
+                return Ok(Library { inner });
+            }
+        }
+
+        let name = self.path.file_name().unwrap_or_default().to_string_lossy();
+        let generation = self.generation.fetch_add(1, AtomicOrdering::Relaxed);
+        let lib = load_copy(&format!("{}-{}", generation, name), |file| {
+            io::copy(&mut fs::File::open(&self.path)?, file).map(drop)
+        })?;
+
+        let lib = Library::from_lib(lib, Some(self.path.clone()), Some(self.clone()), stamp);
+        *latest = Arc::downgrade(&lib.inner);
+        Ok(lib)
+    }
+}
+
+/// `ResolvedLib::Bytes` 加载的次数, 用于区分各自的临时文件
+static IN_MEMORY: AtomicUsize = AtomicUsize::new(0);
+
+/// 通过 write 在临时目录中写出库文件后加载, name 区分同一进程中的各个副本
+///
+/// 副本以 O_EXCL 新建且只有所有者可读写, 不会写入或加载其他人预先放置的文件,
+/// 同名文件已存在时换一个后缀重试
+fn load_copy(
+    name: &str,
+    write: impl FnOnce(&mut fs::File) -> io::Result<()>,
+) -> Result<libloading::Library> {
+    let dir = std::env::temp_dir();
+    let mut attempt = 0;
+    let (shadow, mut file) = loop {
+        let shadow = match attempt {
+            0 => dir.join(format!("funcall-{}-{}", std::process::id(), name)),
+            n => dir.join(format!("funcall-{}-{}-{}", std::process::id(), name, n)),
+        };
+        match create_new(&shadow) {
+            Ok(file) => break (shadow, file),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists && attempt < 100 => attempt += 1,
+            Err(e) => return Err(e.into()),
+        }
+    };
+    let written = write(&mut file);
+    drop(file);
+    if let Err(e) = written {
+        let _ = fs::remove_file(&shadow);
+        return Err(e.into());
+    }
+    let lib = libloading::Library::new(&shadow);
+    // Unix 下映射建立后即可删除副本, Windows 下则只能留在临时目录中
+    #[cfg(unix)]
+    let _ = fs::remove_file(&shadow);
+    Ok(lib?)
+}
+
+fn create_new(path: &Path) -> io::Result<fs::File> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options.open(path)
+}
+
+fn stamp(path: &Path) -> Result<Stamp> {
+    let meta = fs::metadata(path)?;
+    Ok(Some((meta.modified()?, meta.len())))
+}
+
+/// 符号名可能带有结尾的 '\0'
+pub(crate) fn symbol_to_string(symbol: &[u8]) -> String {
+    let symbol = symbol.strip_suffix(b"\0").unwrap_or(symbol);
+    String::from_utf8_lossy(symbol).into_owned()
+}
+
+/// `dlsym` 的伪句柄, 从调用者所在模块之后加载的模块中查找
+#[cfg(unix)]
+pub(crate) const RTLD_NEXT: *mut c_void = -1isize as *mut c_void;
+
+/// `dlsym` 的伪句柄, 按加载顺序在全局范围内查找. glibc 与 musl 中为 0, macOS 与 BSD 中为 -2
+#[cfg(unix)]
+pub(crate) const RTLD_DEFAULT: *mut c_void =
+    if cfg!(any(target_os = "linux", target_os = "android")) {
+        std::ptr::null_mut()
+    } else {
+        -2isize as *mut c_void
+    };
+
+/// 在不获取所有权的情况下从句柄中查找符号
+pub(crate) unsafe fn lookup_in_handle(handle: *mut c_void, symbol: &[u8]) -> Result<*const fn()> {
+    // 借用句柄而不关闭它
+    let lib = ManuallyDrop::new(libloading::Library::from(RawLibrary::from_raw(handle as _)));
+    lookup(&lib, symbol)
+}
+
+unsafe fn lookup(lib: &libloading::Library, symbol: &[u8]) -> Result<*const fn()> {
+    let func = lib.get::<fn()>(symbol)?;
+    Ok(*func.into_raw() as *const fn())
+}
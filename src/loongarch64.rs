@@ -0,0 +1,99 @@
+//! LoongArch64 (LP64D) 的调用路径
+//!
+//! 前八个整数参数通过 a0~a7 传递, 前八个固定的浮点参数通过 fa0~fa7 传递, 浮点寄存器用完后的浮点参数与
+//! 可变参数部分的浮点数同整数一样使用剩下的整数寄存器, 之后才放到栈上. 返回值在 a0, a1 与 fa0 中
+
+use core::arch::asm;
+
+use crate::native::NativeCall;
+use crate::{fpstate, observer, plan, sanitize, Convention, Func};
+
+/// LoongArch64 只有一种调用约定
+pub(crate) struct LoongArch64;
+
+impl NativeCall for LoongArch64 {
+    fn supports(conv: Convention) -> bool {
+        conv == Convention::Cdecl
+    }
+
+    unsafe fn call(func: &mut Func, _conv: Convention) {
+        func.cdecl()
+    }
+}
+
+impl Func {
+    /// LoongArch64 Linux 默认使用的调用约定
+    ///
+    /// # Safety
+    ///
+    /// 压入的参数必须与函数的实际签名一致
+    pub unsafe fn cdecl(&mut self) {
+        self.assert_stack_size();
+        sanitize::outgoing_args(self);
+        let fp = fpstate::save(self);
+        let observed = observer::begin(self, Convention::Cdecl);
+        self.called = true;
+        let regs = plan::INT_REGS.len();
+        let mut ints = [0usize; 8];
+        let split = self.args.len().min(regs);
+        ints[..split].copy_from_slice(&self.args[..split]);
+        let mut floats = [0f64; 8];
+        floats[..self.fargs.len()].copy_from_slice(&self.fargs);
+        let stack = &self.args[split..];
+        // 调用时 sp 需要 16 字节对齐
+        let bytes = (stack.len() * 8 + 15) & !15;
+        let (low, high, float): (usize, usize, f64);
+        asm!(
+            "move $s0, $sp",
+            "sub.d $sp, $sp, $t3",
+            "move $t5, $sp",
+            "2:",
+            "beqz $t2, 3f",
+            "ld.d $t6, $t7, 0",
+            "st.d $t6, $t5, 0",
+            "addi.d $t7, $t7, 8",
+            "addi.d $t5, $t5, 8",
+            "addi.d $t2, $t2, -1",
+            "b 2b",
+            "3:",
+            "fld.d $fa0, $t1, 0",
+            "fld.d $fa1, $t1, 8",
+            "fld.d $fa2, $t1, 16",
+            "fld.d $fa3, $t1, 24",
+            "fld.d $fa4, $t1, 32",
+            "fld.d $fa5, $t1, 40",
+            "fld.d $fa6, $t1, 48",
+            "fld.d $fa7, $t1, 56",
+            "ld.d $a0, $t0, 0",
+            "ld.d $a1, $t0, 8",
+            "ld.d $a2, $t0, 16",
+            "ld.d $a3, $t0, 24",
+            "ld.d $a4, $t0, 32",
+            "ld.d $a5, $t0, 40",
+            "ld.d $a6, $t0, 48",
+            "ld.d $a7, $t0, 56",
+            "jirl $ra, $t4, 0",
+            "move $sp, $s0",
+            in("$t0") ints.as_ptr(),
+            in("$t1") floats.as_ptr(),
+            inout("$t2") stack.len() => _,
+            in("$t3") bytes,
+            in("$t4") self.func,
+            out("$t5") _,
+            out("$t6") _,
+            inout("$t7") stack.as_ptr() => _,
+            // s0 由被调用者保存, 用于在调用后恢复 sp
+            out("$s0") _,
+            lateout("$a0") low,
+            lateout("$a1") high,
+            lateout("$fa0") float,
+            clobber_abi("C"),
+        );
+        self.ret.low = low;
+        self.ret.high = high;
+        // float 返回值在 fa0 的低 32 位
+        self.ret.float = self.float_ret(float, f32::from_bits(float.to_bits() as u32));
+        fpstate::restore(fp);
+        observer::end(observed, self, Convention::Cdecl);
+    }
+}
@@ -0,0 +1,136 @@
+/// 一行完成函数的查找, 压参, 调用以及取返回值
+///
+/// 支持以下两种形式, 其中 conv 为调用约定 (如 `cdecl`, `stdcall`), 返回值类型由上下文推导,
+/// 可以是任何实现了 `FromRet` 的类型. 宏展开后会调用 unsafe 的函数, 因此需要在 unsafe 块中使用
+///
+/// - `funcall!(conv lib[path]::symbol(args...))`: 从库中查找函数, 返回 `Result<T>`
+/// - `funcall!(conv raw ptr(args...))` 或 `funcall!(conv raw (expr)(args...))`: 直接调用函数指针, 返回 `T`
+///
+/// # 示例
+///
+/// ```
+/// use funcall::funcall;
+///
+/// extern "C" fn add(a: i32, b: i32) -> i32 {
+///     a + b
+/// }
+///
+/// let n: i32 = unsafe { funcall!(cdecl raw (add as *const fn())(1i32, 2i32)) };
+/// assert_eq!(n, 3);
+/// ```
+///
+/// ```
+/// # #[cfg(all(feature = "loader", target_os = "linux"))]
+/// # fn main() -> std::io::Result<()> {
+/// use funcall::funcall;
+/// use std::ffi::CStr;
+///
+/// let mut buf = vec![0i8; 100];
+/// let n: i32 = unsafe {
+///     funcall!(cdecl lib["libc.so.6"]::sprintf(buf.as_mut_ptr(), b"%d\0".as_ptr(), 42i32))?
+/// };
+/// assert_eq!(n, 2);
+/// assert_eq!(unsafe { CStr::from_ptr(buf.as_ptr()) }.to_str().unwrap(), "42");
+/// # Ok(())
+/// # }
+/// # #[cfg(not(all(feature = "loader", target_os = "linux")))]
+/// # fn main() {}
+/// ```
+#[macro_export]
+macro_rules! funcall {
+    ($conv:ident lib[$lib:expr] :: $symbol:ident ( $($arg:expr),* $(,)? )) => {
+        $crate::Library::new($lib)
+            .and_then(|lib| lib.get(stringify!($symbol)))
+            .map(|mut func| {
+                $(func.push($arg);)*
+                func.$conv();
+                func.ret().get()
+            })
+    };
+    ($conv:ident raw $ptr:ident ( $($arg:expr),* $(,)? )) => {
+        $crate::funcall!($conv raw ($ptr)($($arg),*))
+    };
+    ($conv:ident raw ($ptr:expr) ( $($arg:expr),* $(,)? )) => {{
+        let mut func = $crate::Func::from_raw($ptr);
+        $(func.push($arg);)*
+        func.$conv();
+        func.ret().get()
+    }};
+}
+
+/// 声明首次使用时才查找的函数, 展开为类型为 `LazyFunc` 的 static
+///
+/// ```
+/// # #[cfg(all(feature = "loader", target_os = "linux"))]
+/// # fn main() {
+/// use funcall::lazy_func;
+///
+/// lazy_func! {
+///     static ABS = ("libc.so.6", "abs");
+/// }
+///
+/// let mut func = ABS.func().unwrap();
+/// func.push(-1i32);
+/// unsafe { func.cdecl() };
+/// assert_eq!(func.ret_as_i32(), 1);
+/// # }
+/// # #[cfg(not(all(feature = "loader", target_os = "linux")))]
+/// # fn main() {}
+/// ```
+#[macro_export]
+macro_rules! lazy_func {
+    ($($(#[$attr:meta])* $vis:vis static $name:ident = ($lib:expr, $symbol:expr);)*) => {
+        $($(#[$attr])* $vis static $name: $crate::LazyFunc = $crate::LazyFunc::new($lib, $symbol);)*
+    };
+}
+
+/// 为只含一个字段的元组结构体 (通常是 `#[repr(transparent)]` 的 newtype) 实现 `IntoArg`,
+/// 参数的类型与传递方式都与其中的字段相同, 如包装了 f64 的结构体仍然通过浮点寄存器传递
+///
+/// ```
+/// use funcall::{transparent_arg, Convention, Func};
+/// use std::os::raw::c_void;
+///
+/// #[repr(transparent)]
+/// struct Fd(i32);
+/// #[repr(transparent)]
+/// struct Handle(*mut c_void);
+/// #[repr(transparent)]
+/// struct Seconds(f64);
+///
+/// transparent_arg! {
+///     Fd(i32),
+///     Handle(*mut c_void),
+///     Seconds(f64),
+/// }
+///
+/// extern "C" fn wait(fd: i32, handle: *mut c_void, timeout: f64) -> f64 {
+///     fd as f64 + handle as usize as f64 + timeout
+/// }
+///
+/// let mut func = Func::from_raw(wait as *const fn());
+/// func.push(Fd(3)).push(Handle(0x10 as *mut c_void)).push(Seconds(0.5));
+/// # if Convention::Cdecl.is_supported() {
+/// unsafe { func.try_call(Convention::Cdecl).unwrap() };
+/// assert_eq!(func.ret_as_f64(), 19.5);
+/// # }
+/// ```
+#[macro_export]
+macro_rules! transparent_arg {
+    ($($name:ident($inner:ty)),* $(,)?) => {
+        $(
+            // 字段之外还有其他成员时大小不同, 无法编译
+            const _: () = assert!(
+                ::core::mem::size_of::<$name>() == ::core::mem::size_of::<$inner>()
+            );
+
+            impl $crate::IntoArg for $name {
+                const KIND: $crate::ArgKind = <$inner as $crate::IntoArg>::KIND;
+
+                fn push_into(self, out: &mut $crate::ArgSink) {
+                    $crate::IntoArg::push_into(self.0, out)
+                }
+            }
+        )*
+    };
+}
@@ -0,0 +1,204 @@
+//! 纯函数的调用结果缓存, 参见 `Func::memoize`
+
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::mem;
+use std::ptr;
+
+use crate::{Backend, Convention, Func, RetValues};
+
+/// 调用结果缓存的命中情况
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Hash)]
+pub struct CacheStats {
+    /// 命中缓存而没有发出的调用次数
+    pub hits: u64,
+    /// 未命中缓存而实际发出的调用次数
+    pub misses: u64,
+    /// 缓存中的结果个数
+    pub entries: usize,
+}
+
+/// 一个 `Func` 的调用结果缓存
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Memo {
+    entries: HashMap<Key, Entry>,
+    hits: u64,
+    misses: u64,
+}
+
+/// 缓存只影响是否发出调用, 不参与 `Func` 的比较
+impl PartialEq for Memo {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl PartialOrd for Memo {
+    fn partial_cmp(&self, _other: &Self) -> Option<Ordering> {
+        Some(Ordering::Equal)
+    }
+}
+
+/// 参数中的一个字, 指向 `Func` 持有的缓冲区时记为缓冲区的序号与偏移, 与缓冲区的实际地址无关
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Word {
+    Value(usize),
+    Owned(usize, usize),
+}
+
+/// 决定调用结果的全部输入
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Key {
+    target: usize,
+    conv: Convention,
+    args: Vec<Word>,
+    fargs: Vec<u64>,
+    /// 调用前各缓冲区的内容, 指针数组中的元素同参数一样记为序号与偏移, 结构体返回值的缓冲区为空
+    owned: Vec<Vec<Word>>,
+}
+
+/// 一次调用的结果
+#[derive(Debug, Clone)]
+struct Entry {
+    ret: RetValues,
+    /// 调用后各缓冲区的内容, 命中时写回; 指针数组为 `None`, 视为只读的输入
+    owned: Vec<Option<Vec<u8>>>,
+}
+
+impl Func {
+    /// 开启或关闭调用结果的缓存, 默认关闭, 关闭时清空缓存
+    ///
+    /// 用于开销大且没有副作用的函数. 开启后 `try_call` 等方法调用前先以参数查找缓存,
+    /// 命中时不发出调用, 直接恢复当时的返回值, 并将 `Func` 持有的缓冲区 (`Arg::Bytes`, 输出参数,
+    /// 结构体返回值等) 恢复为当时调用后的内容. 这些缓冲区按内容而非地址参与比较, 克隆得到的 `Func` 仍能命中缓存;
+    /// 但其他指针参数只按地址比较, 指向的内容变化后仍会命中过时的结果, 此时应通过 `Arg::Bytes` 等方式压入,
+    /// 或者在内容变化后调用 `clear_cache`. 被调用者写入的缓冲区的内容同样是查找的依据,
+    /// 因此输出参数在第一次调用后可能还会再未命中一次. 命中时不会通知观察者, 也不计入 `stats`
+    ///
+    /// ```
+    /// use funcall::{Convention, Func};
+    /// use std::sync::atomic::{AtomicU32, Ordering};
+    ///
+    /// static CALLS: AtomicU32 = AtomicU32::new(0);
+    /// extern "C" fn square(x: i32) -> i32 {
+    ///     CALLS.fetch_add(1, Ordering::SeqCst);
+    ///     x * x
+    /// }
+    ///
+    /// let mut func = Func::from_raw(square as *const fn());
+    /// func.memoize(true);
+    /// func.push(7i32);
+    /// # if Convention::Cdecl.is_supported() {
+    /// for _ in 0..3 {
+    ///     unsafe { func.try_call(Convention::Cdecl).unwrap() };
+    ///     assert_eq!(func.ret_as_i32(), 49);
+    /// }
+    /// assert_eq!(CALLS.load(Ordering::SeqCst), 1);
+    /// assert_eq!(func.cache_stats().hits, 2);
+    /// # }
+    /// ```
+    pub fn memoize(&mut self, enabled: bool) {
+        match (enabled, &self.memo) {
+            (true, None) => self.memo = Some(Box::default()),
+            (false, _) => self.memo = None,
+            _ => {}
+        }
+    }
+
+    /// 调用结果缓存的命中情况, 未开启缓存时全为 0
+    pub fn cache_stats(&self) -> CacheStats {
+        self.memo
+            .as_ref()
+            .map(|memo| CacheStats {
+                hits: memo.hits,
+                misses: memo.misses,
+                entries: memo.entries.len(),
+            })
+            .unwrap_or_default()
+    }
+
+    /// 清空缓存的结果与命中统计, 缓存保持开启
+    pub fn clear_cache(&mut self) {
+        if let Some(memo) = &mut self.memo {
+            **memo = Memo::default();
+        }
+    }
+
+    /// 清零命中统计, 保留缓存的结果
+    pub(crate) fn reset_cache_stats(&mut self) {
+        if let Some(memo) = &mut self.memo {
+            memo.hits = 0;
+            memo.misses = 0;
+        }
+    }
+
+    /// 参数中的地址指向 `owned` 中的缓冲区时记为其序号与偏移
+    fn memo_word(&self, word: usize) -> Word {
+        self.owned
+            .iter()
+            .enumerate()
+            .find_map(|(index, buf)| {
+                let offset = word.wrapping_sub(buf.as_ptr() as usize);
+                (offset < buf.len()).then_some(Word::Owned(index, offset))
+            })
+            .unwrap_or(Word::Value(word))
+    }
+
+    fn memo_key(&self, conv: Convention) -> Key {
+        let ret_buf = self.aggregate.and_then(|ret| ret.buf_index());
+        let owned = self
+            .owned
+            .iter()
+            .enumerate()
+            .map(|(index, buf)| {
+                if Some(index) == ret_buf {
+                    Vec::new()
+                } else if self.tables.contains(&index) {
+                    buf.chunks_exact(mem::size_of::<usize>())
+                        .map(|word| self.memo_word(usize::from_ne_bytes(word.try_into().unwrap())))
+                        .collect()
+                } else {
+                    buf.iter().map(|&byte| Word::Value(byte as usize)).collect()
+                }
+            })
+            .collect();
+        Key {
+            target: self.func as usize,
+            conv,
+            args: self.args.iter().map(|&word| self.memo_word(word)).collect(),
+            fargs: self.fargs.iter().map(|float| float.to_bits()).collect(),
+            owned,
+        }
+    }
+
+    /// 开启缓存时的 `call_backend`, 命中时不发出调用
+    pub(crate) unsafe fn call_memoized(&mut self, conv: Convention, backend: Backend) {
+        let key = self.memo_key(conv);
+        let memo = self.memo.as_mut().unwrap();
+        if let Some(entry) = memo.entries.get(&key) {
+            memo.hits += 1;
+            self.ret = entry.ret;
+            self.called = true;
+            for (buf, contents) in self.owned.iter().zip(&entry.owned) {
+                if let Some(contents) = contents {
+                    // 与被调用者相同, 通过指针写入缓冲区
+                    ptr::copy_nonoverlapping(contents.as_ptr(), buf.as_ptr() as *mut u8, buf.len());
+                }
+            }
+            return;
+        }
+        memo.misses += 1;
+        self.call_uncached(conv, backend);
+        let entry = Entry {
+            ret: self.ret,
+            owned: self
+                .owned
+                .iter()
+                .enumerate()
+                .map(|(index, buf)| (!self.tables.contains(&index)).then(|| buf.to_vec()))
+                .collect(),
+        };
+        self.memo.as_mut().unwrap().entries.insert(key, entry);
+    }
+}
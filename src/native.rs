@@ -0,0 +1,90 @@
+//! `Backend::Asm` 在各平台上的实现
+//!
+//! 每个有手写汇编的平台提供一个实现了 `NativeCall` 的类型, 并将其导出为 `Native`,
+//! `Backend::supports` 与 `Func::call_backend` 只通过它判断与发出调用, 新增平台时不需要改动这两处
+
+use crate::{Convention, Func};
+
+/// 一个平台上手写汇编的调用路径
+pub(crate) trait NativeCall {
+    /// 是否支持以 conv 调用, conv 不会是 `Convention::System`
+    fn supports(conv: Convention) -> bool;
+
+    /// 以 conv 调用 func, conv 已经过 `supports` 检查
+    unsafe fn call(func: &mut Func, conv: Convention);
+}
+
+/// 32 位 x86, 支持 cdecl, stdcall, thiscall 与 fastcall
+#[cfg(target_arch = "x86")]
+pub(crate) struct X86;
+
+#[cfg(target_arch = "x86")]
+impl NativeCall for X86 {
+    fn supports(conv: Convention) -> bool {
+        matches!(
+            conv,
+            Convention::Cdecl | Convention::Stdcall | Convention::Thiscall | Convention::Fastcall
+        )
+    }
+
+    unsafe fn call(func: &mut Func, conv: Convention) {
+        match conv {
+            Convention::Cdecl => func.cdecl(),
+            Convention::Stdcall => func.stdcall(),
+            Convention::Thiscall => func.thiscall(),
+            Convention::Fastcall => func.fastcall(),
+            _ => unreachable!("unsupported convention {:?}", conv),
+        }
+    }
+}
+
+/// 64 位 Linux 与 Windows, 只有 cdecl
+#[cfg(all(target_arch = "x86_64", any(target_os = "linux", windows)))]
+pub(crate) struct X86_64;
+
+#[cfg(all(target_arch = "x86_64", any(target_os = "linux", windows)))]
+impl NativeCall for X86_64 {
+    fn supports(conv: Convention) -> bool {
+        conv == Convention::Cdecl
+    }
+
+    unsafe fn call(func: &mut Func, _conv: Convention) {
+        func.cdecl()
+    }
+}
+
+/// 没有手写汇编的平台, 只能通过 libffi 调用
+#[cfg(not(any(
+    target_arch = "x86",
+    all(target_arch = "x86_64", any(target_os = "linux", windows)),
+    all(target_arch = "loongarch64", target_os = "linux")
+)))]
+pub(crate) struct Unsupported;
+
+#[cfg(not(any(
+    target_arch = "x86",
+    all(target_arch = "x86_64", any(target_os = "linux", windows)),
+    all(target_arch = "loongarch64", target_os = "linux")
+)))]
+impl NativeCall for Unsupported {
+    fn supports(_conv: Convention) -> bool {
+        false
+    }
+
+    unsafe fn call(_func: &mut Func, conv: Convention) {
+        unreachable!("unsupported convention {:?}", conv)
+    }
+}
+
+#[cfg(target_arch = "x86")]
+pub(crate) type Native = X86;
+#[cfg(all(target_arch = "x86_64", any(target_os = "linux", windows)))]
+pub(crate) type Native = X86_64;
+#[cfg(all(target_arch = "loongarch64", target_os = "linux"))]
+pub(crate) type Native = crate::loongarch64::LoongArch64;
+#[cfg(not(any(
+    target_arch = "x86",
+    all(target_arch = "x86_64", any(target_os = "linux", windows)),
+    all(target_arch = "loongarch64", target_os = "linux")
+)))]
+pub(crate) type Native = Unsupported;
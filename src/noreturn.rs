@@ -0,0 +1,187 @@
+//! 调用不会返回的函数, 参见 `Func::call_noreturn`
+
+use core::arch::asm;
+
+#[cfg(target_arch = "x86_64")]
+use crate::plan;
+use crate::{Backend, Convention, Func};
+
+impl Func {
+    /// 调用不会返回的函数, 如 `exit`, `execv`, `longjmp` 与 `ExitProcess`
+    ///
+    /// 调用在 `noreturn` 的汇编块中进行, 调用后不恢复堆栈也不取回返回值, 编译器知道此后的代码不会执行.
+    /// 不会通知 `set_observer` 设置的观察者, 也不计入调用统计.
+    /// 被调用者若仍然返回, 会立即执行 `ud2` 以非法指令异常终止整个进程 (与 `core::intrinsics::abort` 相同),
+    /// 而不会回到调用者已经不再存在的栈帧中
+    ///
+    /// ```no_run
+    /// # #[cfg(feature = "loader")]
+    /// # fn main() {
+    /// use funcall::{libc_func, Convention};
+    ///
+    /// let mut exit = libc_func("exit").unwrap();
+    /// exit.push(0i32);
+    /// unsafe { exit.call_noreturn(Convention::Cdecl) }
+    /// # }
+    /// # #[cfg(not(feature = "loader"))]
+    /// # fn main() {}
+    /// ```
+    ///
+    /// # Safety
+    ///
+    /// 压入的参数必须与函数的实际签名一致. 调用者栈帧中的值不会被 drop, 持有锁等资源时需要自行释放
+    ///
+    /// # Panics
+    ///
+    /// 与 `try_call` 相同的检查未通过时 panic
+    pub unsafe fn call_noreturn(&mut self, conv: Convention) -> ! {
+        let conv = match conv {
+            Convention::System => Convention::native_system(),
+            conv => conv,
+        };
+        if let Err(e) = self.check_call(conv, Backend::Asm) {
+            panic!("{}", e);
+        }
+        self.noreturn(conv)
+    }
+
+    #[cfg(target_arch = "x86")]
+    unsafe fn noreturn(&self, conv: Convention) -> ! {
+        use crate::inline::InlineVec;
+
+        let variadic = matches!(self.arity, Some((_, true)));
+        // 依次为 ecx, edx 与压栈的字
+        let mut words: InlineVec<usize, 10> = InlineVec::new();
+        match conv {
+            Convention::Fastcall if !variadic => words = self.fastcall_words(),
+            Convention::Thiscall if !self.thiscall_as_cdecl() => {
+                words.extend_from_slice(&[self.args[0], 0]);
+                words.extend_from_slice(&self.stack_args(1)[1..]);
+            }
+            _ => {
+                words.extend_from_slice(&[0, 0]);
+                words.extend_from_slice(&self.stack_args(0));
+            }
+        }
+        asm!(
+            // 与 cdecl 相同, 使参数全部压栈后 esp 恰好对齐到 16 字节
+            "shl ecx, 2",
+            "sub esp, ecx",
+            "and esp, -16",
+            "add esp, ecx",
+            "shr ecx, 2",
+            "test ecx, ecx",
+            "jz 3f",
+            "2:",
+            "push dword ptr [edx - 4]",
+            "sub edx, 4",
+            "dec ecx",
+            "jnz 2b",
+            "3:",
+            "mov ecx, dword ptr [edi]",
+            "mov edx, dword ptr [edi + 4]",
+            "call eax",
+            "ud2",
+            in("eax") self.func,
+            in("ecx") words.len() - 2,
+            in("edx") words.as_ptr().add(words.len()),
+            in("edi") words.as_ptr(),
+            options(noreturn),
+        )
+    }
+
+    #[cfg(all(target_arch = "x86_64", target_os = "linux"))]
+    unsafe fn noreturn(&self, _conv: Convention) -> ! {
+        let split = self.args.len().min(plan::INT_REGS.len());
+        let mut ints = [0usize; 6];
+        ints[..split].copy_from_slice(&self.args[..split]);
+        let mut floats = [0f64; 8];
+        floats[..self.fargs.len()].copy_from_slice(&self.fargs);
+        let args = self.stack_args(split);
+        let stack = &args[split..];
+        asm!(
+            // 跳过 red zone, 以免覆盖其中的局部变量, 再使参数全部压栈后 rsp 恰好对齐到 16 字节
+            "sub rsp, 128",
+            "and rsp, -16",
+            "test cl, 1",
+            "jz 2f",
+            "sub rsp, 8",
+            "2:",
+            "test rcx, rcx",
+            "jz 4f",
+            "3:",
+            "push qword ptr [rsi - 8]",
+            "sub rsi, 8",
+            "dec rcx",
+            "jnz 3b",
+            "4:",
+            "movsd xmm0, qword ptr [r14]",
+            "movsd xmm1, qword ptr [r14 + 8]",
+            "movsd xmm2, qword ptr [r14 + 16]",
+            "movsd xmm3, qword ptr [r14 + 24]",
+            "movsd xmm4, qword ptr [r14 + 32]",
+            "movsd xmm5, qword ptr [r14 + 40]",
+            "movsd xmm6, qword ptr [r14 + 48]",
+            "movsd xmm7, qword ptr [r14 + 56]",
+            "mov rdi, qword ptr [r13]",
+            "mov rsi, qword ptr [r13 + 8]",
+            "mov rdx, qword ptr [r13 + 16]",
+            "mov rcx, qword ptr [r13 + 24]",
+            "mov r8, qword ptr [r13 + 32]",
+            "mov r9, qword ptr [r13 + 40]",
+            // 可变参数函数通过 al 得知使用了几个浮点寄存器
+            "mov rax, r15",
+            "call r12",
+            "ud2",
+            in("r12") self.func,
+            in("r13") ints.as_ptr(),
+            in("r14") floats.as_ptr(),
+            in("r15") self.fargs.len(),
+            in("rcx") stack.len(),
+            in("rsi") stack.as_ptr().add(stack.len()),
+            options(noreturn),
+        )
+    }
+
+    #[cfg(all(target_arch = "x86_64", windows))]
+    unsafe fn noreturn(&self, _conv: Convention) -> ! {
+        let split = self.args.len().min(plan::INT_REGS.len());
+        let mut ints = [0usize; 4];
+        ints[..split].copy_from_slice(&self.args[..split]);
+        let args = self.stack_args(split);
+        let stack = &args[split..];
+        asm!(
+            // 参数与 shadow space 全部压栈后 rsp 恰好对齐到 16 字节
+            "and rsp, -16",
+            "test cl, 1",
+            "jz 2f",
+            "sub rsp, 8",
+            "2:",
+            "test rcx, rcx",
+            "jz 4f",
+            "3:",
+            "push qword ptr [rsi - 8]",
+            "sub rsi, 8",
+            "dec rcx",
+            "jnz 3b",
+            "4:",
+            "sub rsp, 32",
+            // 前四个参数同时送入整数寄存器与对应的 xmm 寄存器
+            "mov rcx, qword ptr [r13]",
+            "mov rdx, qword ptr [r13 + 8]",
+            "mov r8, qword ptr [r13 + 16]",
+            "mov r9, qword ptr [r13 + 24]",
+            "movq xmm0, rcx",
+            "movq xmm1, rdx",
+            "movq xmm2, r8",
+            "movq xmm3, r9",
+            "call r12",
+            "ud2",
+            in("r12") self.func,
+            in("r13") ints.as_ptr(),
+            in("rcx") stack.len(),
+            in("rsi") stack.as_ptr().add(stack.len()),
+            options(noreturn),
+        )
+    }
+}
@@ -0,0 +1,81 @@
+//! Objective-C 运行时的辅助函数, 只在 Apple 平台上可用
+//!
+//! 消息通过 `objc_msgSend` 发送, 得到的 `Func` 以 `Convention::Cdecl` 调用.
+//! Apple 平台上的汇编后端不受支持, 需要启用 `libffi` feature
+
+use alloc::ffi::CString;
+use core::ffi::{c_char, c_void};
+
+use crate::{ArgKind, Func};
+
+#[link(name = "objc")]
+extern "C" {
+    fn objc_getClass(name: *const c_char) -> *mut c_void;
+    fn sel_registerName(name: *const c_char) -> *mut c_void;
+    // 实际的参数与返回值随消息而定, 这里只用于取得地址
+    fn objc_msgSend();
+    #[cfg(target_arch = "x86")]
+    fn objc_msgSend_fpret();
+}
+
+/// 按名字查找类, 不存在时返回空指针
+///
+/// # Panics
+///
+/// name 中含有 `\0` 时 panic
+pub fn class(name: &str) -> *mut c_void {
+    let name = CString::new(name).expect("class name contains a nul byte");
+    unsafe { objc_getClass(name.as_ptr()) }
+}
+
+/// 向 receiver 发送名为 selector 的消息, 返回值为对象或指针
+///
+/// 得到的 `Func` 中已压入 receiver 与注册后的 SEL, 之后压入的参数依次作为消息的参数.
+/// 参数的类型必须与方法的声明完全一致, 不会经过可变参数的提升
+///
+/// ```
+/// use funcall::{objc, Convention};
+///
+/// #[link(name = "Foundation", kind = "framework")]
+/// extern "C" {}
+///
+/// let mut func = objc::msg_send(objc::class("NSString"), "stringWithUTF8String:");
+/// func.push(b"hello\0".as_ptr());
+/// let string = unsafe { func.invoke(Convention::Cdecl) }.unwrap().as_usize();
+/// let mut length = objc::msg_send_ret(string as *mut _, "length", funcall::ArgKind::Usize);
+/// assert_eq!(unsafe { length.invoke(Convention::Cdecl) }.unwrap().as_usize(), 5);
+/// ```
+///
+/// # Panics
+///
+/// selector 中含有 `\0` 时 panic
+pub fn msg_send(receiver: *mut c_void, selector: &str) -> Func {
+    msg_send_ret(receiver, selector, ArgKind::Ptr)
+}
+
+/// 同 `msg_send`, 但声明返回值的类型, 并据此选用 `objc_msgSend` 的变体
+///
+/// # Panics
+///
+/// selector 中含有 `\0` 时 panic
+pub fn msg_send_ret(receiver: *mut c_void, selector: &str, ret: ArgKind) -> Func {
+    let name = CString::new(selector).expect("selector contains a nul byte");
+    // 注册后的 SEL 在进程的整个生命周期内有效
+    let sel = unsafe { sel_registerName(name.as_ptr()) };
+    let mut func = Func::from_raw(msg_send_fn(ret));
+    func.set_ret_kind(ret);
+    func.push(receiver).push(sel);
+    func
+}
+
+fn msg_send_fn(ret: ArgKind) -> *const fn() {
+    // 32 位 x86 下浮点数从 x87 栈返回
+    #[cfg(target_arch = "x86")]
+    if matches!(ret, ArgKind::F32 | ArgKind::F64 | ArgKind::CFloat) {
+        return objc_msgSend_fpret as *const fn();
+    }
+    // x86_64 下 `_fpret` 只用于 long double, `_stret` 只用于放不进寄存器的结构体,
+    // 都无法以 `ArgKind` 声明; arm64 下则没有这些变体
+    let _ = ret;
+    objc_msgSend as *const fn()
+}
@@ -0,0 +1,172 @@
+//! 调用前后的全局观察者
+
+// 不支持任何调用约定的平台上不会发出调用
+#![cfg_attr(
+    not(any(
+        target_arch = "x86",
+        all(target_arch = "x86_64", any(target_os = "linux", windows)),
+        all(target_arch = "loongarch64", target_os = "linux"),
+        feature = "libffi"
+    )),
+    allow(dead_code)
+)]
+
+#[cfg(feature = "std")]
+use core::cell::Cell;
+#[cfg(feature = "std")]
+use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(feature = "std")]
+use std::sync::{Arc, RwLock};
+#[cfg(feature = "std")]
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "loader")]
+use crate::Library;
+#[cfg(feature = "std")]
+use crate::{ArgView, RetValues};
+use crate::{Convention, Func};
+
+#[cfg(feature = "std")]
+/// 观察每一次调用, 通过 `set_observer` 安装
+///
+/// 观察者只能读取调用的信息, 不能修改参数或返回值. 观察者中的 panic 会被捕获并忽略, 不影响调用本身
+pub trait CallObserver: Send + Sync {
+    /// 调用前
+    fn before(&self, _info: &CallInfo) {}
+
+    /// 调用后, elapsed 为调用耗时
+    fn after(&self, _info: &CallInfo, _ret: &RetValues, _elapsed: Duration) {}
+}
+
+#[cfg(feature = "std")]
+/// 一次调用的信息
+#[derive(Debug, Clone, Copy)]
+pub struct CallInfo<'a> {
+    func: &'a Func,
+    conv: Convention,
+}
+
+#[cfg(feature = "std")]
+impl<'a> CallInfo<'a> {
+    /// 被调用的函数指针
+    pub fn target(&self) -> *const fn() {
+        self.func.func
+    }
+
+    /// 查找函数时使用的符号名
+    pub fn symbol(&self) -> Option<&'a str> {
+        self.func.symbol_name()
+    }
+
+    /// 函数所在的库
+    #[cfg(feature = "loader")]
+    pub fn library(&self) -> Option<&'a Library> {
+        self.func.lib.as_ref()
+    }
+
+    /// 调用约定
+    pub fn convention(&self) -> Convention {
+        self.conv
+    }
+
+    /// 依次返回所有参数的视图
+    pub fn args(&self) -> impl Iterator<Item = ArgView> + 'a {
+        self.func.arg_views()
+    }
+}
+
+#[cfg(feature = "std")]
+/// 没有安装观察者时只需读取这一个标志
+static ENABLED: AtomicBool = AtomicBool::new(false);
+#[cfg(feature = "std")]
+static OBSERVER: RwLock<Option<Arc<dyn CallObserver>>> = RwLock::new(None);
+
+#[cfg(feature = "std")]
+thread_local! {
+    /// 观察者自身发出的调用不会再通知观察者, 以免无限递归
+    static NOTIFYING: Cell<bool> = const { Cell::new(false) };
+}
+
+#[cfg(feature = "std")]
+/// 安装全局的观察者, 会替换之前安装的
+pub fn set_observer(observer: Box<dyn CallObserver>) {
+    *OBSERVER.write().unwrap() = Some(observer.into());
+    ENABLED.store(true, Ordering::Release);
+}
+
+#[cfg(feature = "std")]
+/// 移除全局的观察者
+pub fn clear_observer() {
+    ENABLED.store(false, Ordering::Release);
+    *OBSERVER.write().unwrap() = None;
+}
+
+#[cfg(feature = "std")]
+/// `begin` 的返回值, 需传给 `end`
+pub(crate) struct Started {
+    observer: Option<Arc<dyn CallObserver>>,
+    /// 有观察者或开启了计时时才读取时钟
+    start: Option<Instant>,
+}
+
+#[cfg(feature = "std")]
+/// 调用开始时通知观察者, 返回值需传给 `end`
+pub(crate) fn begin(func: &Func, conv: Convention) -> Started {
+    #[cfg(feature = "log")]
+    crate::plan::log_before(func, conv);
+    #[cfg(feature = "recorder")]
+    crate::recorder::begin(func, conv);
+    let observer = if !ENABLED.load(Ordering::Acquire) || NOTIFYING.with(Cell::get) {
+        None
+    } else {
+        OBSERVER.read().unwrap().clone()
+    };
+    if let Some(observer) = &observer {
+        notify(|| observer.before(&CallInfo { func, conv }));
+    }
+    let start = (observer.is_some() || func.stats.is_some()).then(Instant::now);
+    Started { observer, start }
+}
+
+#[cfg(feature = "std")]
+/// 调用结束时记录耗时并通知观察者
+pub(crate) fn end(started: Started, func: &mut Func, conv: Convention) {
+    let elapsed = started.start.map(|start| start.elapsed());
+    #[cfg(feature = "log")]
+    crate::plan::log_after(func);
+    #[cfg(feature = "recorder")]
+    crate::recorder::end(func);
+    if let Some(elapsed) = elapsed {
+        crate::stats::record(func, elapsed);
+    }
+    if let (Some(observer), Some(elapsed)) = (started.observer, elapsed) {
+        let func = &*func;
+        notify(|| observer.after(&CallInfo { func, conv }, &func.ret, elapsed));
+    }
+}
+
+#[cfg(feature = "std")]
+fn notify(f: impl FnOnce()) {
+    NOTIFYING.with(|notifying| {
+        notifying.set(true);
+        // 观察者 panic 时标志也要复位, 否则本线程之后的调用都不会再通知观察者
+        let _ = std::panic::catch_unwind(std::panic::AssertUnwindSafe(f));
+        notifying.set(false);
+    });
+}
+
+/// 没有标准库时没有观察者, 只保留日志
+#[cfg(not(feature = "std"))]
+pub(crate) fn begin(func: &Func, conv: Convention) -> Option<()> {
+    #[cfg(feature = "log")]
+    crate::plan::log_before(func, conv);
+    let _ = (func, conv);
+    None
+}
+
+#[cfg(not(feature = "std"))]
+pub(crate) fn end(_started: Option<()>, func: &mut Func, _conv: Convention) {
+    #[cfg(feature = "log")]
+    crate::plan::log_after(func);
+    let _ = func;
+}
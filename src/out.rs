@@ -0,0 +1,83 @@
+//! 由 `Func` 持有的输出参数, 参见 `Func::push_out_param`
+
+use core::marker::PhantomData;
+use core::mem::{align_of, size_of};
+use core::ptr;
+
+use crate::Func;
+
+/// `Func::push_out_param` 返回的凭据, 用于在调用后读取输出参数的值
+///
+/// 只记录缓冲区在 `Func` 中的位置, 因此对克隆得到的 `Func` 同样有效, 读取的是克隆自己的那一份
+#[derive(Debug)]
+pub struct OutParam<T> {
+    /// 在 `Func::owned` 中的序号
+    index: usize,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Clone for OutParam<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for OutParam<T> {}
+
+impl<T: Copy> OutParam<T> {
+    /// 读取 func 中的输出参数, 同 `Func::read_out`
+    pub fn get(self, func: &Func) -> T {
+        func.read_out(self)
+    }
+}
+
+impl Func {
+    /// 分配一个值为 `T::default()` 的 T, 压入指向它的指针, 返回用于在调用后读取它的凭据
+    ///
+    /// 缓冲区与 `Arg::Bytes` 一样由 `Func` 持有, 按 T 的要求对齐, 在 `Func` 被 drop 前一直有效.
+    /// 被调用者写入的值通过 `read_out` 或 `OutParam::get` 复制出来
+    ///
+    /// ```
+    /// use funcall::{Convention, Func};
+    /// unsafe extern "C" fn div_mod(a: i32, b: i32, q: *mut i32, r: *mut i32) {
+    ///     *q = a / b;
+    ///     *r = a % b;
+    /// }
+    ///
+    /// let mut func = Func::from_raw(div_mod as *const fn());
+    /// func.push(17i32).push(5i32);
+    /// let q = func.push_out_param::<i32>();
+    /// let r = func.push_out_param::<i32>();
+    /// # if Convention::Cdecl.is_supported() {
+    /// unsafe { func.try_call(Convention::Cdecl).unwrap() };
+    /// assert_eq!((func.read_out(q), r.get(&func)), (3, 2));
+    /// # }
+    /// ```
+    #[cfg_attr(feature = "provenance", track_caller)]
+    pub fn push_out_param<T: Copy + Default>(&mut self) -> OutParam<T> {
+        let index = self.owned.len();
+        let bytes = self.own_aligned(size_of::<T>(), align_of::<T>());
+        unsafe { ptr::write(bytes.as_mut_ptr() as *mut T, T::default()) };
+        let ptr = bytes.as_ptr();
+        self.push(ptr);
+        OutParam {
+            index,
+            _marker: PhantomData,
+        }
+    }
+
+    /// 读取通过 `push_out_param` 压入的输出参数的当前值
+    ///
+    /// # Panics
+    ///
+    /// token 不是由本 `Func` (或其克隆的来源) 的 `push_out_param` 返回的时可能 panic
+    pub fn read_out<T: Copy>(&self, token: OutParam<T>) -> T {
+        let buf = self
+            .owned
+            .get(token.index)
+            .map(|buf| &buf[self.owned_start(token.index)..])
+            .filter(|buf| size_of::<T>() <= buf.len())
+            .expect("the out-parameter does not belong to this Func");
+        unsafe { ptr::read_unaligned(buf.as_ptr() as *const T) }
+    }
+}
@@ -0,0 +1,84 @@
+//! 由指定函数释放的返回指针, 参见 `Func::ret_as_owned_ptr`
+
+use core::ffi::c_void;
+use core::{mem, ptr};
+
+#[cfg(feature = "loader")]
+use crate::Library;
+use crate::{Convention, Func};
+
+/// `Func::ret_as_owned_ptr` 返回的守卫, drop 时以指针为唯一的参数调用释放函数
+///
+/// 守卫同时持有返回指针的函数所在的库, 在释放之前库不会被卸载. 指针为空时不会调用释放函数
+#[derive(Debug)]
+pub struct OwnedPtr {
+    ptr: *mut c_void,
+    free: Func,
+    conv: Convention,
+    #[cfg(feature = "loader")]
+    _lib: Option<Library>,
+}
+
+impl OwnedPtr {
+    pub fn as_ptr(&self) -> *mut c_void {
+        self.ptr
+    }
+
+    pub fn is_null(&self) -> bool {
+        self.ptr.is_null()
+    }
+
+    /// 调用释放函数时使用的调用约定, 默认为 `Convention::Cdecl`
+    pub fn set_convention(&mut self, conv: Convention) {
+        self.conv = conv;
+    }
+
+    /// 放弃所有权, 返回指针而不释放, 之后需要调用者自行释放
+    pub fn into_raw(mut self) -> *mut c_void {
+        mem::replace(&mut self.ptr, ptr::null_mut())
+    }
+}
+
+impl Drop for OwnedPtr {
+    fn drop(&mut self) {
+        if self.ptr.is_null() {
+            return;
+        }
+        self.free.rollback([0; 4]);
+        self.free.push(self.ptr);
+        // 无法调用时只能泄漏, 不能在 drop 中 panic
+        let _ = unsafe { self.free.try_call(self.conv) };
+    }
+}
+
+impl Func {
+    /// 将返回值视为需要由 free 释放的指针, 返回在 drop 时释放它的守卫
+    ///
+    /// 用于 `strdup` 等将内存的所有权交给调用者, 并要求以同一个库中的函数释放的函数.
+    /// 释放时清空 free 中已压入的参数, 以指针为唯一的参数经 `try_call` 调用
+    ///
+    /// ```
+    /// # #[cfg(feature = "loader")]
+    /// # fn main() {
+    /// use funcall::{libc_func, Convention};
+    ///
+    /// let mut strdup = libc_func(if cfg!(windows) { "_strdup" } else { "strdup" }).unwrap();
+    /// strdup.push(b"hello\0".as_ptr());
+    /// unsafe { strdup.try_call(Convention::Cdecl).unwrap() };
+    /// let owned = strdup.ret_as_owned_ptr(libc_func("free").unwrap());
+    /// let s = unsafe { std::ffi::CStr::from_ptr(owned.as_ptr() as *const std::os::raw::c_char) };
+    /// assert_eq!(s.to_str(), Ok("hello"));
+    /// # }
+    /// # #[cfg(not(feature = "loader"))]
+    /// # fn main() {}
+    /// ```
+    pub fn ret_as_owned_ptr(&self, free: Func) -> OwnedPtr {
+        OwnedPtr {
+            ptr: self.ret_as_usize() as *mut c_void,
+            free,
+            conv: Convention::Cdecl,
+            #[cfg(feature = "loader")]
+            _lib: self.lib.clone(),
+        }
+    }
+}
@@ -0,0 +1,105 @@
+//! 可以装入多个 `Func` 的一组参数
+
+use alloc::ffi::CString;
+
+use crate::{Arg, Func, IntoArg};
+
+/// 预先压入的一组参数, 通过 `Func::load` 或 `Func::load_copy` 装入任意多个 `Func`
+///
+/// 参数的类型判断与字的扩展只在压入时进行一次, 装入时按目标的布局直接放置.
+/// 字符串与字节等缓冲区由本值持有, `load` 装入的参数与本值共享这些缓冲区,
+/// `load_copy` 则为目标各复制一份
+///
+/// ```
+/// use funcall::{Arg, ArgPack, Func};
+/// extern "C" fn first(s: *const u8, x: f64) -> f64 {
+///     unsafe { *s as f64 + x }
+/// }
+/// extern "C" fn second(s: *const u8, x: f64) -> f64 {
+///     unsafe { *s.add(1) as f64 * x }
+/// }
+///
+/// let mut pack = ArgPack::new();
+/// pack.push_arg(Arg::Str("ab".to_owned())).push(2.0f64);
+/// let mut rets = Vec::new();
+/// for f in [first as extern "C" fn(*const u8, f64) -> f64, second] {
+///     let mut func = Func::from_raw(f as *const fn());
+///     func.load(&pack);
+///     unsafe { func.cdecl() };
+///     rets.push(func.ret_as_f64());
+/// }
+/// assert_eq!(rets, [99.0, 196.0]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct ArgPack {
+    /// 只用于保存参数, 不会被调用
+    func: Func,
+}
+
+impl Default for ArgPack {
+    fn default() -> Self {
+        ArgPack { func: Func::null() }
+    }
+}
+
+impl ArgPack {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 压入参数, 同 `Func::push`; 未声明参数个数, f32 总是提升为 double
+    pub fn push<T: IntoArg>(&mut self, arg: T) -> &mut Self {
+        self.func.push(arg);
+        self
+    }
+
+    /// 以 C 语言的 float 类型压入 f32, 同 `Func::push_float`
+    pub fn push_float(&mut self, arg: f32) -> &mut Self {
+        self.func.push_float(arg);
+        self
+    }
+
+    /// 压入运行时才确定类型的参数, 同 `Func::push_arg`
+    pub fn push_arg(&mut self, arg: Arg) -> &mut Self {
+        self.func.push_arg(arg);
+        self
+    }
+
+    /// 压入字符串, 不再复制而是直接持有 s, 实际传递的是指向它的指针
+    pub fn push_c_string(&mut self, s: CString) -> &mut Self {
+        self.func.push_owned(s.into_bytes_with_nul());
+        self
+    }
+
+    /// 参数个数
+    pub fn len(&self) -> usize {
+        self.func.slots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.func.slots.is_empty()
+    }
+}
+
+impl Func {
+    /// 在已压入的参数之后装入 pack 中的参数, 返回自身以便链式调用
+    ///
+    /// 参数按目标已声明的参数个数与布局放置, 与 `push_raw` 相同不经原型检查.
+    /// 字符串等缓冲区与 pack 共享, 被调用者对其的修改会出现在 pack 与其他装入了它的 `Func` 中
+    pub fn load(&mut self, pack: &ArgPack) -> &mut Self {
+        let src = &pack.func;
+        for slot in src.slots.iter() {
+            self.place_slot(slot, &src.args, &src.fargs);
+        }
+        self.owned.extend(src.owned.iter().cloned());
+        self
+    }
+
+    /// 与 `load` 相同, 但复制一份 pack 中的缓冲区, 指向它们的参数随之改为指向副本
+    pub fn load_copy(&mut self, pack: &ArgPack) -> &mut Self {
+        let from = self.checkpoint();
+        self.load(pack);
+        self.copy_owned(from);
+        self
+    }
+}
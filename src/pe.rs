@@ -0,0 +1,74 @@
+//! PE 导出表解析
+
+use std::ffi::CStr;
+use std::os::raw::c_char;
+
+/// 列出模块导出的所有符号名
+///
+/// # Safety
+///
+/// base 必须是一个已加载模块的基址 (即 HMODULE)
+pub(crate) unsafe fn export_names(base: *const u8) -> Vec<String> {
+    let read_u32 = |offset: usize| (base.add(offset) as *const u32).read_unaligned() as usize;
+
+    // IMAGE_DOS_HEADER.e_lfanew
+    let nt = read_u32(0x3c);
+    // 跳过 Signature 与 IMAGE_FILE_HEADER, 根据 Magic 区分 PE32 / PE32+
+    let optional = nt + 24;
+    let magic = (base.add(optional) as *const u16).read_unaligned();
+    let directory = optional + if magic == 0x20b { 112 } else { 96 };
+
+    let export = read_u32(directory);
+    if export == 0 {
+        return Vec::new();
+    }
+    // IMAGE_EXPORT_DIRECTORY.NumberOfNames / AddressOfNames
+    let count = read_u32(export + 24);
+    let names = read_u32(export + 32);
+    (0..count)
+        .map(|i| {
+            let name = base.add(read_u32(names + i * 4)) as *const c_char;
+            CStr::from_ptr(name).to_string_lossy().into_owned()
+        })
+        .collect()
+}
+
+/// 在导出表中查找 32 位 Windows 下被修饰过的符号名
+///
+/// 依次尝试 `_name` (cdecl), `_name@N` (stdcall), `@name@N` (fastcall)
+#[cfg(target_arch = "x86")]
+pub(crate) fn find_decorated<'a>(exports: &'a [String], name: &str) -> Option<&'a str> {
+    let is_stack_size = |s: &str| {
+        s.strip_prefix('@')
+            .is_some_and(|n| !n.is_empty() && n.bytes().all(|c| c.is_ascii_digit()))
+    };
+    let cdecl = format!("_{}", name);
+    let fastcall = format!("@{}", name);
+    exports
+        .iter()
+        .find(|e| **e == cdecl)
+        .or_else(|| {
+            exports
+                .iter()
+                .find(|e| e.strip_prefix(&cdecl).is_some_and(is_stack_size))
+        })
+        .or_else(|| {
+            exports
+                .iter()
+                .find(|e| e.strip_prefix(&fastcall).is_some_and(is_stack_size))
+        })
+        .map(String::as_str)
+}
+
+/// 解析 stdcall (`_name@N`) 与 fastcall (`@name@N`) 修饰名中被调用者弹出的参数字节数 N
+#[cfg(target_arch = "x86")]
+pub(crate) fn decorated_bytes(symbol: &str) -> Option<usize> {
+    let rest = symbol
+        .strip_prefix('_')
+        .or_else(|| symbol.strip_prefix('@'))?;
+    let (name, bytes) = rest.rsplit_once('@')?;
+    if name.is_empty() || bytes.is_empty() || !bytes.bytes().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    bytes.parse().ok()
+}
@@ -0,0 +1,528 @@
+//! 调用前参数在寄存器与栈中的布局
+
+use alloc::borrow::Cow;
+use alloc::vec::Vec;
+use core::fmt;
+use core::mem;
+
+use crate::{ArgKind, ArgSlot, ArgView, Convention, Func};
+
+/// 64 位下依次用于传递整数参数的寄存器
+#[cfg(all(target_arch = "x86_64", not(windows)))]
+pub(crate) const INT_REGS: [&str; 6] = ["rdi", "rsi", "rdx", "rcx", "r8", "r9"];
+#[cfg(all(target_arch = "x86_64", windows))]
+pub(crate) const INT_REGS: [&str; 4] = ["rcx", "rdx", "r8", "r9"];
+/// ARM32 (AAPCS) 下 args 的前四个字依次送入 r0~r3
+///
+/// 这是软浮点 (`Convention::AapcsSoftFloat`) 的布局, `Convention::AapcsVfp` 与硬浮点 (eabihf) 下的 `Cdecl`
+/// 中 libffi 会将浮点参数放入 VFP 寄存器, 与这里给出的位置不同
+#[cfg(target_arch = "arm")]
+pub(crate) const INT_REGS: [&str; 4] = ["r0", "r1", "r2", "r3"];
+/// MIPS64 (n64) 与 LoongArch64 下 args 的前八个字依次送入 a0~a7
+#[cfg(any(
+    target_arch = "mips64",
+    target_arch = "mips64r6",
+    target_arch = "loongarch64"
+))]
+pub(crate) const INT_REGS: [&str; 8] = ["a0", "a1", "a2", "a3", "a4", "a5", "a6", "a7"];
+/// s390x 下 args 的前五个字依次送入 r2~r6
+#[cfg(target_arch = "s390x")]
+pub(crate) const INT_REGS: [&str; 5] = ["r2", "r3", "r4", "r5", "r6"];
+/// AAPCS64 下 args 的前八个字依次送入 x0~x7, Windows 下保存 TEB 的 x18 与其他平台上的平台寄存器都不会被使用.
+/// Apple arm64 下栈上不足 8 字节的参数实际按自然大小紧凑排列, 与这里给出的偏移不同
+#[cfg(target_arch = "aarch64")]
+pub(crate) const INT_REGS: [&str; 8] = ["x0", "x1", "x2", "x3", "x4", "x5", "x6", "x7"];
+#[cfg(not(any(
+    target_arch = "x86_64",
+    target_arch = "arm",
+    target_arch = "mips64",
+    target_arch = "mips64r6",
+    target_arch = "s390x",
+    target_arch = "loongarch64",
+    target_arch = "aarch64"
+)))]
+pub(crate) const INT_REGS: [&str; 0] = [];
+
+/// 依次用于传递浮点参数的寄存器, Windows 下与整数寄存器按位置一一对应
+#[cfg(all(target_arch = "x86_64", not(windows)))]
+pub(crate) const FLOAT_REGS: [&str; 8] = [
+    "xmm0", "xmm1", "xmm2", "xmm3", "xmm4", "xmm5", "xmm6", "xmm7",
+];
+#[cfg(all(target_arch = "x86_64", windows))]
+pub(crate) const FLOAT_REGS: [&str; 4] = ["xmm0", "xmm1", "xmm2", "xmm3"];
+/// MIPS64 (n64) 下固定参数中前八个位置上的浮点数使用对应的 f12~f19, 可变参数部分的浮点数则使用整数寄存器
+#[cfg(any(target_arch = "mips64", target_arch = "mips64r6"))]
+pub(crate) const FLOAT_REGS: [&str; 8] = ["f12", "f13", "f14", "f15", "f16", "f17", "f18", "f19"];
+/// s390x 下依次用于传递浮点参数的寄存器, 与整数寄存器各自分配
+#[cfg(target_arch = "s390x")]
+pub(crate) const FLOAT_REGS: [&str; 4] = ["f0", "f2", "f4", "f6"];
+/// LoongArch64 下依次用于传递固定的浮点参数的寄存器, 用完后浮点参数改用剩下的整数寄存器
+#[cfg(target_arch = "loongarch64")]
+pub(crate) const FLOAT_REGS: [&str; 8] = ["fa0", "fa1", "fa2", "fa3", "fa4", "fa5", "fa6", "fa7"];
+/// AAPCS64 下依次用于传递浮点参数的寄存器, Windows 下可变参数部分的浮点数改用整数寄存器, Apple arm64 下则在栈上
+#[cfg(target_arch = "aarch64")]
+pub(crate) const FLOAT_REGS: [&str; 8] = ["v0", "v1", "v2", "v3", "v4", "v5", "v6", "v7"];
+#[cfg(not(any(
+    target_arch = "x86_64",
+    target_arch = "mips64",
+    target_arch = "mips64r6",
+    target_arch = "s390x",
+    target_arch = "loongarch64",
+    target_arch = "aarch64"
+)))]
+pub(crate) const FLOAT_REGS: [&str; 0] = [];
+
+/// 浮点参数是否放在 fargs 中, 独立于整数参数使用浮点寄存器, 即 64 位 Linux, s390x, LoongArch64 与 aarch64
+pub(crate) const SEPARATE_FLOATS: bool = cfg!(any(
+    all(target_arch = "x86_64", not(windows)),
+    target_arch = "s390x",
+    target_arch = "loongarch64",
+    target_arch = "aarch64"
+));
+
+/// 浮点参数是否按位置使用与整数寄存器一一对应的浮点寄存器, 即 64 位 Windows 与 MIPS64 (n64)
+const POSITIONAL_FLOATS: bool = cfg!(any(
+    all(target_arch = "x86_64", windows),
+    target_arch = "mips64",
+    target_arch = "mips64r6"
+));
+
+/// LoongArch64 下 32 位的无符号整数同样被符号扩展到整个字
+pub(crate) const SIGN_EXTEND_U32: bool = cfg!(target_arch = "loongarch64");
+
+/// 64 位 Windows 下调用者需要在栈上参数之前为前四个参数保留的空间 (shadow space),
+/// s390x 下则是栈上参数之前 160 字节的寄存器保存区
+const SHADOW_SPACE: usize = if cfg!(all(target_arch = "x86_64", windows)) {
+    32
+} else if cfg!(target_arch = "s390x") {
+    160
+} else {
+    0
+};
+
+/// 参数在 args 中的起始位置默认需要的对齐字节数
+///
+/// System V AMD64 ABI 中栈上的 i128 与 u128 按 16 字节对齐, n64 与 AAPCS64 中它们从偶数号寄存器或 16 字节对齐的栈位置开始,
+/// AAPCS 中 64 位的参数从偶数号寄存器或 8 字节对齐的栈位置开始, 其余的参数只需按字对齐
+pub(crate) fn arg_align(kind: ArgKind) -> usize {
+    match kind {
+        ArgKind::I128 | ArgKind::U128
+            if cfg!(any(
+                all(target_arch = "x86_64", not(windows)),
+                target_arch = "mips64",
+                target_arch = "mips64r6",
+                target_arch = "aarch64"
+            )) =>
+        {
+            16
+        }
+        ArgKind::I64
+        | ArgKind::U64
+        | ArgKind::I128
+        | ArgKind::U128
+        | ArgKind::F32
+        | ArgKind::F64
+            if cfg!(target_arch = "arm") =>
+        {
+            8
+        }
+        _ => mem::size_of::<usize>(),
+    }
+}
+
+/// 保存返回值的寄存器
+#[cfg(all(feature = "log", target_arch = "x86_64"))]
+const RET_REGS: [&str; 3] = ["rax", "rdx", "xmm0"];
+#[cfg(all(feature = "log", not(target_arch = "x86_64")))]
+const RET_REGS: [&str; 3] = ["eax", "edx", "st0"];
+
+/// 参数在调用时所处的位置
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ArgLocation {
+    /// 第 n 个整数寄存器, 即 rdi, rsi, rdx, rcx, r8, r9, Windows 下为 rcx, rdx, r8, r9, ARM32 下为 r0 ~ r3,
+    /// MIPS64 与 LoongArch64 下为 a0 ~ a7, s390x 下为 r2 ~ r6
+    IntReg(usize),
+    /// 第 n 个浮点寄存器, 即 xmm0 ~ xmm7, Windows 下为 xmm0 ~ xmm3, MIPS64 下为 f12 ~ f19, s390x 下为 f0, f2, f4, f6,
+    /// LoongArch64 下为 fa0 ~ fa7
+    FloatReg(usize),
+    /// 相对于调用时栈顶的字节偏移
+    Stack(usize),
+}
+
+/// 输出寄存器名或 `[sp+偏移]`
+impl fmt::Display for ArgLocation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ArgLocation::IntReg(i) => f.write_str(INT_REGS[i]),
+            ArgLocation::FloatReg(i) => f.write_str(FLOAT_REGS[i]),
+            ArgLocation::Stack(offset) => write!(f, "[sp+{:#x}]", offset),
+        }
+    }
+}
+
+/// 栈上参数的排列顺序, 参见 `Func::set_arg_order`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Hash)]
+pub enum ArgOrder {
+    /// 从右往左入栈, 第一个参数位于最低的地址, 即 cdecl 与 stdcall 的顺序
+    #[default]
+    RightToLeft,
+    /// 从左往右入栈, 最后一个参数位于最低的地址, 即 pascal 等调用约定的顺序
+    LeftToRight,
+}
+
+/// 调试用的参数布局, 参见 `Func::set_layout_override`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Hash)]
+pub enum LayoutOverride {
+    /// 按调用约定使用寄存器与栈
+    #[default]
+    Abi,
+    /// 所有参数都通过栈传递, 寄存器被留空
+    StackOnly,
+}
+
+/// 压入参数时进行的转换
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Promotion {
+    /// 有符号整数被符号扩展到整个字
+    SignExtend,
+    /// 无符号整数被零扩展到整个字
+    ZeroExtend,
+    /// f32 被提升为 f64
+    FloatToDouble,
+}
+
+/// `Func::dry_run` 得到的调用计划
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CallPlan {
+    /// 调用约定
+    pub convention: Convention,
+    /// 栈上参数的排列顺序
+    pub order: ArgOrder,
+    /// 参数的布局
+    pub layout: LayoutOverride,
+    /// 依次为每个参数的计划
+    pub args: Vec<PlannedArg>,
+    /// 64 位下通过 al 告知可变参数函数使用了几个浮点寄存器
+    pub float_regs: usize,
+    /// 参数在栈上占用的总字节数, 包括 shadow space 与对齐用的填充
+    pub stack_size: usize,
+    /// 64 位 Windows 下在栈上参数之前保留的 32 字节, s390x 下为 160 字节的寄存器保存区, 其他平台下为 0
+    pub shadow_space: usize,
+    /// 为了对齐而在参数之上额外保留的字节数
+    pub padding: usize,
+}
+
+/// 一个参数的计划
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlannedArg {
+    /// 参数的值
+    pub value: ArgView,
+    /// 参数的每个字所处的位置
+    pub locations: Vec<ArgLocation>,
+    /// 写入寄存器或栈中的字节, 按参数在内存中的表示排列, 大端序平台上各字的高位在前
+    pub bytes: Vec<u8>,
+    /// 压入时进行的转换
+    pub promotion: Option<Promotion>,
+}
+
+/// `CallPlan::diff` 给出的一个位置不同的参数
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArgMove {
+    /// 参数的序号
+    pub index: usize,
+    /// 参数的值
+    pub value: ArgView,
+    /// 在前一个计划中的位置
+    pub from: Vec<ArgLocation>,
+    /// 在后一个计划中的位置
+    pub to: Vec<ArgLocation>,
+}
+
+/// 输出为 `#序号 值: 原位置 -> 新位置`, 多个字的位置以空格分隔
+impl fmt::Display for ArgMove {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "#{} {}:", self.index, self.value)?;
+        for location in &self.from {
+            write!(f, " {}", location)?;
+        }
+        f.write_str(" ->")?;
+        for location in &self.to {
+            write!(f, " {}", location)?;
+        }
+        Ok(())
+    }
+}
+
+impl CallPlan {
+    /// 与 other 相比位置不同的参数, 按参数的顺序排列
+    ///
+    /// 只比较两个计划中都有的参数, 用于比较同一组参数在不同布局或调用约定下的去向
+    pub fn diff(&self, other: &CallPlan) -> Vec<ArgMove> {
+        self.args
+            .iter()
+            .zip(other.args.iter())
+            .enumerate()
+            .filter(|(_, (a, b))| a.locations != b.locations)
+            .map(|(index, (a, b))| ArgMove {
+                index,
+                value: a.value,
+                from: a.locations.clone(),
+                to: b.locations.clone(),
+            })
+            .collect()
+    }
+}
+
+impl ArgSlot {
+    /// 参数的第 word 个字所处的位置
+    pub(crate) fn location(&self, word: usize) -> ArgLocation {
+        let index = self.index + word;
+        if self.float {
+            ArgLocation::FloatReg(index)
+        } else if index < INT_REGS.len() {
+            // Windows 下前四个位置与 MIPS64 下前八个位置上的浮点数使用对应的浮点寄存器
+            match self.kind {
+                ArgKind::F32 | ArgKind::F64 | ArgKind::CFloat | ArgKind::F16
+                    if POSITIONAL_FLOATS =>
+                {
+                    ArgLocation::FloatReg(index)
+                }
+                _ => ArgLocation::IntReg(index),
+            }
+        } else {
+            ArgLocation::Stack(SHADOW_SPACE + (index - INT_REGS.len()) * mem::size_of::<usize>())
+        }
+    }
+}
+
+impl Func {
+    /// 计算以 conv 调用时各参数的去向, 但并不进行调用
+    ///
+    /// 布局的计算方式与实际调用时完全一致, 可用于检查绑定是否正确
+    pub fn dry_run(&self, conv: Convention) -> CallPlan {
+        let args = self
+            .slots
+            .iter()
+            .zip(self.arg_views())
+            .enumerate()
+            .map(|(param, (slot, value))| {
+                let bytes = if slot.float {
+                    self.fargs[slot.index].to_ne_bytes().to_vec()
+                } else {
+                    self.args[slot.index..slot.index + slot.len]
+                        .iter()
+                        .flat_map(|word| word.to_ne_bytes().to_vec())
+                        .collect()
+                };
+                PlannedArg {
+                    value,
+                    locations: (0..slot.len)
+                        .map(|word| self.location(slot, word, param))
+                        .collect(),
+                    bytes,
+                    promotion: promotion(slot),
+                }
+            })
+            .collect();
+        let frame = self.frame();
+        CallPlan {
+            convention: conv,
+            order: self.order,
+            layout: self.layout,
+            args,
+            float_regs: FLOAT_REGS.len().min(self.fargs.len()),
+            stack_size: SHADOW_SPACE + frame.stack.len() * mem::size_of::<usize>() + frame.padding,
+            shadow_space: SHADOW_SPACE,
+            padding: frame.padding,
+        }
+    }
+
+    /// 同 `dry_run`, 但按 layout 重新放置参数后计算, 不改变自身
+    pub fn dry_run_with(&self, conv: Convention, layout: LayoutOverride) -> CallPlan {
+        let mut func = self.shallow_clone();
+        func.set_layout_override(layout);
+        func.dry_run(conv)
+    }
+
+    /// 按 `ArgOrder` 排列后第 param 个参数的第 word 个字所处的位置
+    fn location(&self, slot: &ArgSlot, word: usize, param: usize) -> ArgLocation {
+        match (self.order, slot.location(word)) {
+            // MIPS64 下可变参数部分的浮点数与整数一样使用整数寄存器
+            (_, ArgLocation::FloatReg(index))
+                if cfg!(any(target_arch = "mips64", target_arch = "mips64r6"))
+                    && matches!(self.arity, Some((fixed, true)) if param >= fixed) =>
+            {
+                ArgLocation::IntReg(index)
+            }
+            (ArgOrder::LeftToRight, ArgLocation::Stack(_)) => {
+                // 参数整体倒序, 内部各字的顺序不变
+                let begin = slot.index.max(INT_REGS.len());
+                let end = slot.index + slot.len;
+                let at = self.args.len() - end + (slot.index + word - begin);
+                ArgLocation::Stack(SHADOW_SPACE + at * mem::size_of::<usize>())
+            }
+            (_, location) => location,
+        }
+    }
+
+    /// 按 `ArgOrder` 排列后的 args, 从 start 开始的部分在栈上
+    ///
+    /// 从左往右入栈时以参数为单位倒序, 参数内部各字的顺序不变, 对齐用的空位各自作为一个单位
+    pub(crate) fn stack_args(&self, start: usize) -> Cow<'_, [usize]> {
+        if self.order == ArgOrder::RightToLeft {
+            return Cow::Borrowed(&self.args);
+        }
+        let start = start.min(self.args.len());
+        let mut words = self.args[..start].to_vec();
+        let mut end = self.args.len();
+        while end > start {
+            let begin = self
+                .slots
+                .iter()
+                .find(|slot| !slot.float && slot.index < end && slot.index + slot.len >= end)
+                .map_or(end - 1, |slot| slot.index.max(start));
+            words.extend_from_slice(&self.args[begin..end]);
+            end = begin;
+        }
+        Cow::Owned(words)
+    }
+
+    /// 已压入的参数在栈上占用的字节数, 不包括 shadow space 与栈顶对齐用的填充
+    ///
+    /// 32 位下即 stdcall 函数返回时需要弹出的字节数, 也是其修饰名中 `@` 之后的数字.
+    /// 一个参数可能占用多个字, 如 32 位下的 u64 与 f64 各占 8 字节; 参数之间对齐用的空位也计算在内
+    pub fn stack_bytes(&self) -> usize {
+        self.args.len().saturating_sub(INT_REGS.len()) * mem::size_of::<usize>()
+    }
+
+    /// 计算调用时寄存器与栈中的值, 与汇编中的处理方式一致
+    ///
+    /// 目前支持的各调用约定的布局相同, 区别只在于由谁清理堆栈
+    pub(crate) fn frame(&self) -> Frame {
+        let split = self.args.len().min(INT_REGS.len());
+        let mut regs = self
+            .fargs
+            .iter()
+            .zip(FLOAT_REGS.iter())
+            .map(|(arg, reg)| (*reg, arg.to_bits()))
+            .collect::<Vec<_>>();
+        regs.extend(
+            self.args[..split]
+                .iter()
+                .zip(INT_REGS.iter())
+                .map(|(arg, reg)| (*reg, *arg as u64)),
+        );
+        if cfg!(all(target_arch = "x86_64", windows)) {
+            // Windows 下前四个参数同时送入整数寄存器与对应的 xmm 寄存器
+            regs.extend(
+                self.args[..split]
+                    .iter()
+                    .zip(FLOAT_REGS.iter())
+                    .map(|(arg, reg)| (*reg, *arg as u64)),
+            );
+        } else if cfg!(target_arch = "x86_64") {
+            // 可变参数函数通过 al 得知使用了几个浮点寄存器
+            regs.push(("al", self.fargs.len() as u64));
+        } else if POSITIONAL_FLOATS {
+            // MIPS64 下固定参数中的浮点数送入对应的 f 寄存器, 而不是整数寄存器
+            for (param, slot) in self.slots.iter().enumerate() {
+                if let ArgLocation::FloatReg(index) = self.location(slot, 0, param) {
+                    if let Some(reg) = regs.iter_mut().find(|(name, _)| *name == INT_REGS[index]) {
+                        reg.0 = FLOAT_REGS[index];
+                    }
+                }
+            }
+        }
+
+        let stack = self.stack_args(split)[split..].to_vec();
+        // 调用时栈需要 16 字节对齐, 这里给出所需的最少填充
+        let padding = (16 - stack.len() * mem::size_of::<usize>() % 16) % 16;
+        Frame {
+            regs,
+            stack,
+            padding,
+        }
+    }
+}
+
+/// 调用时寄存器与栈中的值
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Frame {
+    /// 寄存器名与其中的值
+    pub regs: Vec<(&'static str, u64)>,
+    /// 从调用时的栈顶 (64 位 Windows 下为 shadow space 之上) 开始依次存放的字
+    pub stack: Vec<usize>,
+    /// 为了对齐而在参数之上额外保留的字节数
+    pub padding: usize,
+}
+
+fn promotion(slot: &ArgSlot) -> Option<Promotion> {
+    let size = match slot.kind {
+        ArgKind::F32 => return Some(Promotion::FloatToDouble),
+        ArgKind::I8 | ArgKind::U8 => 1,
+        ArgKind::I16 | ArgKind::U16 => 2,
+        ArgKind::I32 | ArgKind::U32 => 4,
+        _ => return None,
+    };
+    if size >= mem::size_of::<usize>() {
+        None
+    } else if matches!(slot.kind, ArgKind::I8 | ArgKind::I16 | ArgKind::I32)
+        || (slot.kind == ArgKind::U32 && SIGN_EXTEND_U32)
+    {
+        Some(Promotion::SignExtend)
+    } else {
+        Some(Promotion::ZeroExtend)
+    }
+}
+
+/// 在 trace 级别输出调用前的参数布局, 参数压入的位置单独输出到 `funcall::provenance`
+#[cfg(feature = "log")]
+pub(crate) fn log_before(func: &Func, conv: Convention) {
+    if !log::log_enabled!(log::Level::Trace) {
+        return;
+    }
+    let frame = func.frame();
+    log::trace!("call {:p} ({:?}): {}", func.func, conv, func);
+    #[cfg(feature = "provenance")]
+    for (i, arg) in func.arg_views().enumerate() {
+        if let Some(origin) = arg.origin() {
+            log::trace!(
+                target: "funcall::provenance",
+                "arg {} ({}) pushed at {}",
+                i,
+                arg,
+                origin
+            );
+        }
+    }
+    for (reg, value) in &frame.regs {
+        if reg.starts_with("xmm") {
+            log::trace!("{} = {:#x} ({})", reg, value, f64::from_bits(*value));
+        } else {
+            log::trace!("{} = {:#x}", reg, value);
+        }
+    }
+    let size = mem::size_of::<usize>();
+    for (i, word) in frame.stack.iter().enumerate() {
+        log::trace!("[sp+{:#x}] = {:#x}", SHADOW_SPACE + i * size, word);
+    }
+    if frame.padding != 0 {
+        log::trace!("padding = {} bytes", frame.padding);
+    }
+}
+
+/// 在 trace 级别输出调用后返回值寄存器的值
+#[cfg(feature = "log")]
+pub(crate) fn log_after(func: &Func) {
+    let ret = &func.ret;
+    log::trace!(
+        "{} = {:#x}, {} = {:#x}, {} = {:#x} ({})",
+        RET_REGS[0],
+        ret.low,
+        RET_REGS[1],
+        ret.high,
+        RET_REGS[2],
+        ret.float.to_bits(),
+        ret.float
+    );
+}
@@ -0,0 +1,297 @@
+//! 反复调用同一组参数的准备, 参见 `Func::prepare` 与 `Func::call_batch`
+
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::error::Error;
+use core::fmt;
+
+#[cfg(all(feature = "std", target_arch = "x86_64", target_os = "linux"))]
+use crate::jit::Trampoline;
+use crate::{Arg, ArgKind, ArgSink, Backend, CallError, Convention, Func, IntoArg, RetValues};
+
+/// `Func::prepare` 得到的调用
+///
+/// 调用前的检查只在准备时进行一次, 参数在寄存器与栈中的布局也已确定.
+/// 之后只能通过 `replace_arg` 替换同类型的参数, 布局不会再变化, 每次 `invoke` 只需发出调用
+///
+/// ```
+/// use funcall::{Convention, Func};
+/// extern "C" fn add(a: i32, b: f64) -> f64 {
+///     a as f64 + b
+/// }
+///
+/// let mut func = Func::from_raw(add as *const fn());
+/// func.push(0i32).push(0.5f64);
+/// let mut call = func.prepare(Convention::Cdecl).unwrap();
+/// for i in 0..3 {
+///     call.replace_arg(0, i).unwrap();
+///     assert_eq!(unsafe { call.invoke() }.as_f64(), i as f64 + 0.5);
+/// }
+/// ```
+#[derive(Debug)]
+pub struct PreparedCall<'a> {
+    func: &'a mut Func,
+    conv: Convention,
+    backend: Backend,
+    /// 使用 `Backend::Jit` 时预先取得的跳板
+    #[cfg(all(feature = "std", target_arch = "x86_64", target_os = "linux"))]
+    trampoline: Option<Arc<Trampoline>>,
+}
+
+impl Func {
+    /// 检查已压入的参数并确定其布局, 得到可以反复调用的 `PreparedCall`
+    ///
+    /// 检查的内容与 `try_call` 相同, 出错时返回 `Err`
+    pub fn prepare(&mut self, conv: Convention) -> Result<PreparedCall<'_>, CallError> {
+        self.prepare_with(conv, conv.backend().unwrap_or(Backend::Asm))
+    }
+
+    /// 同 `prepare`, 但指定调用时使用的后端
+    ///
+    /// 使用 `Backend::Jit` 时跳板在准备时就已生成, 调用时直接进入跳板
+    pub fn prepare_with(
+        &mut self,
+        conv: Convention,
+        backend: Backend,
+    ) -> Result<PreparedCall<'_>, CallError> {
+        self.check_call(conv, backend)?;
+        #[cfg(all(feature = "std", target_arch = "x86_64", target_os = "linux"))]
+        let trampoline = if backend == Backend::Jit {
+            Some(Trampoline::for_func(self).expect("failed to allocate a trampoline"))
+        } else {
+            None
+        };
+        Ok(PreparedCall {
+            func: self,
+            conv,
+            backend,
+            #[cfg(all(feature = "std", target_arch = "x86_64", target_os = "linux"))]
+            trampoline,
+        })
+    }
+
+    /// 在已压入的参数之后依次接上 rows 中的每一行参数并调用, 返回值依次追加到 out
+    ///
+    /// 第一行确定参数的布局, 之后各行只改写与上一行不同的参数, 调用前的检查也只进行一次.
+    /// 某一行的参数个数或类型与第一行不同时停止, 返回出错的行号, 之前各行的返回值已在 out 中.
+    /// 附加了原型时每一行都按原型重新压入. 调用结束后 `Func` 中的参数恢复原状
+    ///
+    /// 各行复制的字符串只保留到下一行调用前, 之前通过 `push_out_param` 压入的输出参数不受影响,
+    /// 其 `OutParam` 在调用后仍然有效, 读到的是最后一次调用写入的值
+    ///
+    /// ```
+    /// use funcall::{Arg, Convention, Func};
+    /// extern "C" fn add(a: i32, b: f64) -> f64 {
+    ///     a as f64 + b
+    /// }
+    ///
+    /// let mut func = Func::from_raw(add as *const fn());
+    /// let rows: Vec<Vec<Arg>> = (0..3).map(|i| vec![Arg::I32(i), Arg::F64(0.5)]).collect();
+    /// let rows: Vec<&[Arg]> = rows.iter().map(Vec::as_slice).collect();
+    /// let mut out = Vec::new();
+    /// unsafe { func.call_batch(funcall::Convention::Cdecl, &rows, &mut out) }.unwrap();
+    /// assert_eq!(out[2].as_f64(), 2.5);
+    /// ```
+    ///
+    /// # Safety
+    ///
+    /// 同 `cdecl`, 每一行的参数都必须与函数的签名相符
+    pub unsafe fn call_batch(
+        &mut self,
+        conv: Convention,
+        rows: &[&[Arg]],
+        out: &mut Vec<RetValues>,
+    ) -> Result<(), RowError> {
+        let first = match rows.first() {
+            Some(first) => *first,
+            None => return Ok(()),
+        };
+        let base = self.checkpoint();
+        let result = self.batch(conv, first, rows, out);
+        self.rollback(base);
+        result
+    }
+
+    unsafe fn batch(
+        &mut self,
+        conv: Convention,
+        first: &[Arg],
+        rows: &[&[Arg]],
+        out: &mut Vec<RetValues>,
+    ) -> Result<(), RowError> {
+        let base = self.checkpoint();
+        let checked = self.signature.is_some();
+        for arg in first {
+            self.push_arg(arg.clone());
+        }
+        out.reserve(rows.len());
+        let mut call = self
+            .prepare(conv)
+            .map_err(|error| RowError { row: 0, error })?;
+        let mut prev: &[Arg] = &[];
+        for (row, args) in rows.iter().enumerate() {
+            let error = |error| RowError { row, error };
+            if args.len() != first.len() {
+                return Err(error(CallError::ArgCountMismatch {
+                    expected: first.len(),
+                    variadic: false,
+                    got: args.len(),
+                }));
+            }
+            if checked {
+                if row != 0 {
+                    call.func.rollback(base);
+                    for arg in args.iter() {
+                        call.func.push_arg(arg.clone());
+                    }
+                    call.func.check_call(conv, call.backend).map_err(error)?;
+                }
+            } else {
+                // 重新复制字符串前先释放上一行的副本, 调用前压入的输出参数等缓冲区都在 base 之前
+                call.func.truncate_owned(base[3]);
+                for (i, arg) in args.iter().enumerate() {
+                    if prev.get(i) != Some(arg) || matches!(arg, Arg::Str(_)) {
+                        call.func
+                            .replace_with_arg(base[2] + i, arg)
+                            .map_err(error)?;
+                    }
+                }
+            }
+            out.push(call.invoke());
+            prev = args;
+        }
+        Ok(())
+    }
+
+    /// 将第 index 个参数替换为 arg, 参见 `PreparedCall::replace_arg`
+    pub(crate) fn replace_slot<T: IntoArg>(
+        &mut self,
+        index: usize,
+        arg: T,
+    ) -> Result<(), CallError> {
+        let slot = match self.slots.get(index) {
+            Some(slot) => *slot,
+            None => {
+                return Err(CallError::ArgCountMismatch {
+                    expected: self.slots.len(),
+                    variadic: false,
+                    got: index + 1,
+                })
+            }
+        };
+        let mut sink = ArgSink::new(T::KIND);
+        arg.push_into(&mut sink);
+        let c_float = slot.kind == ArgKind::CFloat && sink.kind() == ArgKind::F32;
+        // 第三方实现的参数的类型都是 `ArgKind::Other`, 占用的字数也可能不同
+        if (sink.kind() != slot.kind || sink.words().len() != slot.len) && !c_float {
+            return Err(CallError::ArgKindMismatch {
+                index,
+                expected: slot.kind,
+                got: sink.kind(),
+            });
+        }
+        if c_float {
+            let bits = (sink.float() as f32).to_bits();
+            if slot.float {
+                self.fargs[slot.index] = f64::from_bits(u64::from(bits));
+            } else {
+                self.args[slot.index] = bits as usize;
+            }
+        } else if slot.float {
+            self.fargs[slot.index] = sink.float();
+        } else {
+            self.args[slot.index..slot.index + slot.len].copy_from_slice(sink.words());
+        }
+        Ok(())
+    }
+
+    /// 同 `replace_slot`, 但替换为运行时才确定类型的参数
+    ///
+    /// 字节串在调用期间一直被借用, 直接传递其地址; 字符串需要复制一份加上结尾的 `\0`
+    fn replace_with_arg(&mut self, index: usize, arg: &Arg) -> Result<(), CallError> {
+        match *arg {
+            Arg::I8(v) => self.replace_slot(index, v),
+            Arg::U8(v) => self.replace_slot(index, v),
+            Arg::I16(v) => self.replace_slot(index, v),
+            Arg::U16(v) => self.replace_slot(index, v),
+            Arg::I32(v) => self.replace_slot(index, v),
+            Arg::U32(v) => self.replace_slot(index, v),
+            Arg::I64(v) => self.replace_slot(index, v),
+            Arg::U64(v) => self.replace_slot(index, v),
+            Arg::I128(v) => self.replace_slot(index, v),
+            Arg::U128(v) => self.replace_slot(index, v),
+            Arg::Isize(v) => self.replace_slot(index, v),
+            Arg::Usize(v) => self.replace_slot(index, v),
+            Arg::F32(v) => self.replace_slot(index, v),
+            Arg::F64(v) => self.replace_slot(index, v),
+            Arg::Ptr(v) => self.replace_slot(index, v as *const u8),
+            Arg::Bytes(ref v) => self.replace_slot(index, v.as_ptr()),
+            Arg::Str(ref v) => {
+                let mut bytes = Vec::with_capacity(v.len() + 1);
+                bytes.extend_from_slice(v.as_bytes());
+                bytes.push(0);
+                let bytes: Arc<[u8]> = bytes.into();
+                self.replace_slot(index, bytes.as_ptr())?;
+                self.owned.push(bytes);
+                Ok(())
+            }
+        }
+    }
+}
+
+/// `Func::call_batch` 在某一行上检查出的错误
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+pub struct RowError {
+    /// 出错的行号
+    pub row: usize,
+    /// 出错的原因
+    pub error: CallError,
+}
+
+impl fmt::Display for RowError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "row {}: {}", self.row, self.error)
+    }
+}
+
+impl Error for RowError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+impl PreparedCall<'_> {
+    /// 将第 index 个参数替换为 arg, arg 的类型必须与原先压入的相同
+    ///
+    /// 以 `push_float` 或在固定参数位置上压入的 C float 可以用 f32 替换
+    pub fn replace_arg<T: IntoArg>(
+        &mut self,
+        index: usize,
+        arg: T,
+    ) -> Result<&mut Self, CallError> {
+        self.func.replace_slot(index, arg)?;
+        Ok(self)
+    }
+
+    /// 以当前的参数调用函数, 返回各返回值寄存器的值
+    ///
+    /// # Safety
+    ///
+    /// 同 `Func::cdecl`
+    pub unsafe fn invoke(&mut self) -> RetValues {
+        #[cfg(all(feature = "std", target_arch = "x86_64", target_os = "linux"))]
+        {
+            if let Some(trampoline) = &self.trampoline {
+                trampoline.call(self.func, self.conv);
+                return self.func.ret;
+            }
+        }
+        self.func.call_backend(self.conv, self.backend);
+        self.func.ret
+    }
+
+    /// 准备调用的 `Func`, 其中保存着上一次调用的返回值
+    pub fn func(&self) -> &Func {
+        self.func
+    }
+}
@@ -0,0 +1,232 @@
+//! 捕获调用中的硬件异常, 参见 `Func::call_protected`
+
+use std::cell::Cell;
+use std::error::Error;
+use std::fmt;
+use std::os::raw::{c_int, c_void};
+#[cfg(unix)]
+use std::sync::Mutex;
+
+use crate::{Backend, CallError, Convention, Func};
+
+/// `Func::call_protected` 捕获到的硬件异常
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Fault {
+    /// 访问了未映射或没有权限的内存, 即 SIGSEGV 或 EXCEPTION_ACCESS_VIOLATION
+    Segv {
+        /// 访问的地址
+        addr: usize,
+    },
+    /// 总线错误, 即 SIGBUS 或 EXCEPTION_IN_PAGE_ERROR
+    Bus {
+        /// 访问的地址
+        addr: usize,
+    },
+    /// 算术异常, 如整数除以零
+    Fpe {
+        /// 出错的指令地址
+        addr: usize,
+    },
+    /// 非法指令
+    Ill {
+        /// 出错的指令地址
+        addr: usize,
+    },
+    /// 栈溢出, 只在 Windows 下能够与 `Segv` 区分
+    StackOverflow {
+        /// 出错的指令地址
+        addr: usize,
+    },
+}
+
+impl fmt::Display for Fault {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Fault::Segv { addr } => write!(f, "segmentation fault at {:#x}", addr),
+            Fault::Bus { addr } => write!(f, "bus error at {:#x}", addr),
+            Fault::Fpe { addr } => write!(f, "arithmetic exception at {:#x}", addr),
+            Fault::Ill { addr } => write!(f, "illegal instruction at {:#x}", addr),
+            Fault::StackOverflow { addr } => write!(f, "stack overflow at {:#x}", addr),
+        }
+    }
+}
+
+impl Error for Fault {}
+
+/// `Func::call_protected` 没能完成调用
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum ProtectedError {
+    /// 调用前的检查没有通过, 没有进行调用
+    Call(CallError),
+    /// 调用中发生了硬件异常
+    Fault(Fault),
+}
+
+impl fmt::Display for ProtectedError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ProtectedError::Call(e) => e.fmt(f),
+            ProtectedError::Fault(e) => e.fmt(f),
+        }
+    }
+}
+
+impl Error for ProtectedError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ProtectedError::Call(e) => Some(e),
+            ProtectedError::Fault(e) => Some(e),
+        }
+    }
+}
+
+impl From<CallError> for ProtectedError {
+    fn from(e: CallError) -> Self {
+        ProtectedError::Call(e)
+    }
+}
+
+impl From<Fault> for ProtectedError {
+    fn from(e: Fault) -> Self {
+        ProtectedError::Fault(e)
+    }
+}
+
+extern "C" {
+    fn funcall_protected_call(
+        callback: unsafe extern "C-unwind" fn(*mut c_void),
+        data: *mut c_void,
+        code: *mut c_int,
+        addr: *mut *mut c_void,
+    ) -> c_int;
+    #[cfg(windows)]
+    fn funcall_catching_call(
+        callback: unsafe extern "C-unwind" fn(*mut c_void),
+        data: *mut c_void,
+        code: *mut u32,
+    ) -> c_int;
+}
+
+/// 信号处理函数是整个进程共享的, 同一时间只能有一个受保护的调用
+#[cfg(unix)]
+static LOCK: Mutex<()> = Mutex::new(());
+
+thread_local! {
+    static ACTIVE: Cell<bool> = const { Cell::new(false) };
+}
+
+struct Call<'a> {
+    func: &'a mut Func,
+    conv: Convention,
+    backend: Backend,
+}
+
+unsafe extern "C-unwind" fn trampoline(data: *mut c_void) {
+    let call = &mut *(data as *mut Call);
+    call.func.call_backend(call.conv, call.backend);
+}
+
+/// 当前线程是否正在进行受保护的调用, 此时异常由外层的 SEH 处理, 不能在调用帧中终止进程
+#[cfg(windows)]
+pub(crate) fn active() -> bool {
+    ACTIVE.with(Cell::get)
+}
+
+/// 以 conv 通过 backend 调用 func, 捕获到硬件异常时返回 `Err`
+pub(crate) unsafe fn call(
+    func: &mut Func,
+    conv: Convention,
+    backend: Backend,
+) -> Result<(), Fault> {
+    assert!(
+        !ACTIVE.with(Cell::get),
+        "call_protected is not reentrant on the same thread"
+    );
+    #[cfg(unix)]
+    let _lock = LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    ACTIVE.with(|active| active.set(true));
+    let mut call = Call {
+        func,
+        conv,
+        backend,
+    };
+    let (mut code, mut addr) = (0, std::ptr::null_mut());
+    let faulted = funcall_protected_call(
+        trampoline,
+        &mut call as *mut Call as *mut c_void,
+        &mut code,
+        &mut addr,
+    );
+    ACTIVE.with(|active| active.set(false));
+    #[cfg(unix)]
+    assert!(
+        faulted >= 0,
+        "call_protected failed to spawn the calling thread"
+    );
+    if faulted == 0 {
+        return Ok(());
+    }
+    // 调用没有正常返回, 返回值寄存器中的值没有意义
+    call.func.ret = Default::default();
+    Err(fault(code, addr as usize))
+}
+
+/// 以 conv 通过 backend 调用 func, 被调用者抛出任何 SEH 异常时返回其异常代码
+#[cfg(windows)]
+pub(crate) unsafe fn catching(
+    func: &mut Func,
+    conv: Convention,
+    backend: Backend,
+) -> Result<(), u32> {
+    assert!(
+        !ACTIVE.with(Cell::get),
+        "call_protected is not reentrant on the same thread"
+    );
+    ACTIVE.with(|active| active.set(true));
+    let mut call = Call {
+        func,
+        conv,
+        backend,
+    };
+    let mut code = 0;
+    let caught =
+        funcall_catching_call(trampoline, &mut call as *mut Call as *mut c_void, &mut code);
+    ACTIVE.with(|active| active.set(false));
+    if caught == 0 {
+        return Ok(());
+    }
+    call.func.ret = Default::default();
+    Err(code)
+}
+
+#[cfg(unix)]
+fn fault(sig: c_int, addr: usize) -> Fault {
+    // SIGBUS 在 Linux 下为 7, 在 macOS 与 BSD 下为 10
+    const SIGILL: c_int = 4;
+    const SIGFPE: c_int = 8;
+    const SIGBUS: c_int = if cfg!(target_os = "linux") { 7 } else { 10 };
+    match sig {
+        SIGILL => Fault::Ill { addr },
+        SIGFPE => Fault::Fpe { addr },
+        SIGBUS => Fault::Bus { addr },
+        _ => Fault::Segv { addr },
+    }
+}
+
+#[cfg(windows)]
+fn fault(code: c_int, addr: usize) -> Fault {
+    const EXCEPTION_IN_PAGE_ERROR: u32 = 0xC000_0006;
+    const EXCEPTION_ILLEGAL_INSTRUCTION: u32 = 0xC000_001D;
+    const EXCEPTION_PRIV_INSTRUCTION: u32 = 0xC000_0096;
+    const EXCEPTION_STACK_OVERFLOW: u32 = 0xC000_00FD;
+    match code as u32 {
+        EXCEPTION_IN_PAGE_ERROR => Fault::Bus { addr },
+        EXCEPTION_ILLEGAL_INSTRUCTION | EXCEPTION_PRIV_INSTRUCTION => Fault::Ill { addr },
+        EXCEPTION_STACK_OVERFLOW => Fault::StackOverflow { addr },
+        // 整数与浮点运算的异常代码都在 0xC000008D 到 0xC0000095 之间
+        0xC000_008D..=0xC000_0095 => Fault::Fpe { addr },
+        _ => Fault::Segv { addr },
+    }
+}
@@ -0,0 +1,94 @@
+//! 不经过 `Func` 的最简调用, 参见 `call_raw6`
+
+use core::arch::asm;
+
+use crate::RetValues;
+
+/// 以 cdecl 调用约定调用最多有六个整数或指针参数的函数, args 中的前 used 个为参数
+///
+/// 直接将 args 送入传参寄存器后调用, 不经过 `Func` 的参数分类, 检查与观察者.
+/// 64 位 Linux 下六个参数都在寄存器中, 多余的寄存器中为 args 中剩下的值;
+/// 64 位 Windows 下前四个参数在寄存器中, 第五, 六个参数写在栈上. 不传递任何浮点参数,
+/// 因此也可以调用可变参数函数
+///
+/// ```
+/// extern "C" fn add(a: i32, b: i32) -> i32 {
+///     a + b
+/// }
+///
+/// let ret = unsafe { funcall::call_raw6(add as *const fn(), [1, 2, 0, 0, 0, 0], 2) };
+/// assert_eq!(ret.as_i32(), 3);
+/// ```
+///
+/// # Panics
+///
+/// used 大于 6 时 panic
+///
+/// # Safety
+///
+/// ptr 必须指向一个 cdecl 函数, 其参数均为不超过机器字长的整数或指针, 且个数为 used
+#[inline]
+pub unsafe fn call_raw6(ptr: *const fn(), args: [usize; 6], used: usize) -> RetValues {
+    assert!(used <= args.len(), "call_raw6 takes at most 6 arguments");
+    let (low, high, float) = raw6(ptr, &args);
+    RetValues { low, high, float }
+}
+
+#[cfg(target_os = "linux")]
+#[inline(always)]
+unsafe fn raw6(func: *const fn(), args: &[usize; 6]) -> (usize, usize, f64) {
+    let (low, high, float): (usize, usize, f64);
+    asm!(
+        "mov r12, rsp",
+        // 越过 red zone 并对齐到 16 字节, 同 `Func::cdecl`
+        "sub rsp, 128",
+        "and rsp, -16",
+        "mov rdi, qword ptr [r13]",
+        "mov rsi, qword ptr [r13 + 8]",
+        "mov rdx, qword ptr [r13 + 16]",
+        "mov rcx, qword ptr [r13 + 24]",
+        "mov r8, qword ptr [r13 + 32]",
+        "mov r9, qword ptr [r13 + 40]",
+        "call r11",
+        "mov rsp, r12",
+        out("r12") _,
+        in("r13") args.as_ptr(),
+        in("r11") func,
+        // 可变参数函数通过 al 得知浮点参数的个数
+        inout("rax") 0usize => low,
+        out("rdx") high,
+        out("xmm0") float,
+        clobber_abi("C"),
+    );
+    (low, high, float)
+}
+
+#[cfg(windows)]
+#[inline(always)]
+unsafe fn raw6(func: *const fn(), args: &[usize; 6]) -> (usize, usize, f64) {
+    let (low, high, float): (usize, usize, f64);
+    asm!(
+        "mov r12, rsp",
+        // 32 字节的 shadow space 之上是第五, 六个参数
+        "sub rsp, 48",
+        "and rsp, -16",
+        "mov rax, qword ptr [r13 + 32]",
+        "mov qword ptr [rsp + 32], rax",
+        "mov rax, qword ptr [r13 + 40]",
+        "mov qword ptr [rsp + 40], rax",
+        "mov rcx, qword ptr [r13]",
+        "mov rdx, qword ptr [r13 + 8]",
+        "mov r8, qword ptr [r13 + 16]",
+        "mov r9, qword ptr [r13 + 24]",
+        "call r11",
+        "mov rsp, r12",
+        out("r12") _,
+        in("r13") args.as_ptr(),
+        in("r11") func,
+        out("rax") low,
+        out("rdx") high,
+        out("xmm0") float,
+        clobber_abi("C"),
+    );
+    (low, high, float)
+}
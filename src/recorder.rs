@@ -0,0 +1,458 @@
+//! 记录与重放调用, 参见 `Recorder` 与 `replay`
+//!
+//! 指针参数只能以地址的形式记录, 重放时原样传入, 在其他进程中通常没有意义.
+//! 只有通过 `Func::push_arg` 压入的字节与字符串会连同内容一起记录, 重放时还会比较调用后其内容是否相同
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Arg, ArgKind, Convention, Error, Func, Library, Result, RetValues, F16};
+
+/// 日志格式的版本, 格式发生不兼容的变化时递增
+pub const LOG_VERSION: u32 = 1;
+
+/// 一次被记录的调用
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedCall {
+    /// 函数所在库的路径, 不是从库中查找到的函数为 `None`
+    pub library: Option<PathBuf>,
+    /// 查找函数时使用的符号名
+    pub symbol: Option<String>,
+    /// 发出调用的线程名
+    pub thread: Option<String>,
+    /// 调用约定
+    pub convention: Convention,
+    /// 声明的固定参数个数与是否为可变参数函数
+    pub arity: Option<(usize, bool)>,
+    /// 声明的返回值类型
+    pub ret_kind: Option<ArgKind>,
+    /// 依次压入的参数, 指向 `push_arg` 压入的缓冲区的指针记录为调用前的内容
+    pub args: Vec<Arg>,
+    /// 以 C float 传递的 `Arg::F32` 的序号
+    pub c_floats: Vec<usize>,
+    /// 作为 `F16` 传递的 `Arg::U16` 的序号
+    #[serde(default)]
+    pub halves: Vec<usize>,
+    /// 调用中被改写了的缓冲区的序号及调用后的内容
+    pub written: Vec<(usize, Vec<u8>)>,
+    /// 返回值低位
+    pub ret_low: usize,
+    /// 返回值高位
+    pub ret_high: usize,
+    /// 浮点寄存器的值, 以位的形式保存
+    pub ret_float: u64,
+}
+
+impl RecordedCall {
+    /// 调用前记录函数与参数
+    fn capture(func: &Func, conv: Convention) -> Self {
+        let mut args = Vec::with_capacity(func.slots.len());
+        let mut c_floats = Vec::new();
+        let mut halves = Vec::new();
+        for (i, view) in func.arg_views().enumerate() {
+            let bits = view.bits();
+            let arg = match view.kind() {
+                ArgKind::I8 => Arg::I8(bits as i8),
+                ArgKind::U8 => Arg::U8(bits as u8),
+                ArgKind::I16 => Arg::I16(bits as i16),
+                ArgKind::U16 => Arg::U16(bits as u16),
+                ArgKind::I32 => Arg::I32(bits as i32),
+                ArgKind::U32 => Arg::U32(bits as u32),
+                ArgKind::I64 => Arg::I64(bits as i64),
+                ArgKind::U64 => Arg::U64(bits as u64),
+                ArgKind::I128 => Arg::I128(bits as i128),
+                ArgKind::U128 => Arg::U128(bits),
+                ArgKind::Isize => Arg::Isize(bits as isize),
+                ArgKind::Usize => Arg::Usize(bits as usize),
+                ArgKind::F32 => Arg::F32(f64::from_bits(bits as u64) as f32),
+                ArgKind::F64 => Arg::F64(f64::from_bits(bits as u64)),
+                ArgKind::CFloat => {
+                    c_floats.push(i);
+                    Arg::F32(f32::from_bits(bits as u32))
+                }
+                ArgKind::F16 => {
+                    halves.push(i);
+                    Arg::U16(bits as u16)
+                }
+                ArgKind::Ptr => match owned_buffer(func, i) {
+                    Some(buf) => Arg::Bytes(buf.to_vec()),
+                    None => Arg::Ptr(bits as usize),
+                },
+                // 第三方的类型只能按占用的字数记录
+                ArgKind::Other => match func.slots[i].len * std::mem::size_of::<usize>() {
+                    16 => Arg::U128(bits),
+                    8 if func.slots[i].len == 2 => Arg::U64(bits as u64),
+                    _ => Arg::Usize(bits as usize),
+                },
+            };
+            args.push(arg);
+        }
+        Self {
+            library: func.lib.as_ref().and_then(|lib| lib.path()).map(Into::into),
+            symbol: func.symbol.clone(),
+            thread: std::thread::current().name().map(str::to_owned),
+            convention: conv,
+            arity: func.arity,
+            ret_kind: func.ret_kind,
+            args,
+            c_floats,
+            halves,
+            written: Vec::new(),
+            ret_low: 0,
+            ret_high: 0,
+            ret_float: 0,
+        }
+    }
+
+    /// 记录时的返回值
+    pub fn ret(&self) -> RetValues {
+        RetValues {
+            low: self.ret_low,
+            high: self.ret_high,
+            float: f64::from_bits(self.ret_float),
+        }
+    }
+
+    /// 调用后 args 中第 i 个缓冲区应有的内容
+    fn expected_buffer(&self, i: usize) -> Option<&[u8]> {
+        match self.written.iter().find(|(index, _)| *index == i) {
+            Some((_, bytes)) => Some(bytes),
+            None => match &self.args[i] {
+                Arg::Bytes(bytes) => Some(bytes),
+                _ => None,
+            },
+        }
+    }
+}
+
+/// 第 i 个参数指向的通过 `push_arg` 压入的缓冲区
+fn owned_buffer(func: &Func, i: usize) -> Option<&[u8]> {
+    let slot = &func.slots[i];
+    if slot.kind != ArgKind::Ptr || slot.float {
+        return None;
+    }
+    let ptr = func.args[slot.index];
+    func.owned
+        .iter()
+        .find(|buf| buf.as_ptr() as usize == ptr)
+        .map(|buf| &**buf)
+}
+
+/// 一系列被记录的调用, 以 JSON 的形式保存
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CallLog {
+    /// 日志格式的版本, 即保存时的 `LOG_VERSION`
+    pub version: u32,
+    /// 按发出顺序排列的调用
+    pub calls: Vec<RecordedCall>,
+}
+
+impl CallLog {
+    pub fn new(calls: Vec<RecordedCall>) -> Self {
+        Self {
+            version: LOG_VERSION,
+            calls,
+        }
+    }
+
+    /// 读取日志, 版本与当前的不符时返回错误
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let log: Self = serde_json::from_slice(&fs::read(path)?).map_err(io::Error::from)?;
+        if log.version != LOG_VERSION {
+            return Err(Error::InvalidData(format!(
+                "unsupported call log version {}, expected {}",
+                log.version, LOG_VERSION
+            )));
+        }
+        Ok(log)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let json = serde_json::to_vec_pretty(self).map_err(io::Error::from)?;
+        Ok(fs::write(path, json)?)
+    }
+}
+
+type Calls = Arc<Mutex<Vec<RecordedCall>>>;
+
+/// 没有附加记录器时只需读取这一个标志
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static ACTIVE: Mutex<Option<Calls>> = Mutex::new(None);
+
+thread_local! {
+    /// 已开始尚未结束的调用, 被调用者可能通过回调再次发出调用
+    static PENDING: RefCell<Vec<RecordedCall>> = const { RefCell::new(Vec::new()) };
+}
+
+/// 记录此后通过 funcall 发出的所有调用
+///
+/// 同一时间只能有一个记录器, 附加新的记录器会替换之前的, 记录器被 drop 时停止记录.
+/// 所有线程中的调用都会被记录, 可以通过 `RecordedCall::thread` 区分
+///
+/// ```no_run
+/// use funcall::{Convention, Func, Recorder, ReplayOptions};
+///
+/// let recorder = Recorder::attach();
+/// let mut func = Func::new("libc.so.6", b"abs\0").unwrap();
+/// func.push(-1i32);
+/// unsafe { func.cdecl() };
+/// recorder.save("calls.json").unwrap();
+///
+/// let report = unsafe { funcall::replay("calls.json", &ReplayOptions::default()).unwrap() };
+/// assert!(report.is_identical());
+/// ```
+#[derive(Debug)]
+pub struct Recorder {
+    calls: Calls,
+}
+
+impl Recorder {
+    pub fn attach() -> Self {
+        let calls = Calls::default();
+        *ACTIVE.lock().unwrap() = Some(calls.clone());
+        ENABLED.store(true, Ordering::Release);
+        Self { calls }
+    }
+
+    /// 到目前为止记录的调用
+    pub fn log(&self) -> CallLog {
+        CallLog::new(self.calls.lock().unwrap().clone())
+    }
+
+    /// 将到目前为止记录的调用保存到 path
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        self.log().save(path)
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        let mut active = ACTIVE.lock().unwrap();
+        if matches!(&*active, Some(calls) if Arc::ptr_eq(calls, &self.calls)) {
+            ENABLED.store(false, Ordering::Release);
+            *active = None;
+        }
+    }
+}
+
+/// 调用开始时记录函数与参数
+pub(crate) fn begin(func: &Func, conv: Convention) {
+    if ENABLED.load(Ordering::Acquire) {
+        let call = RecordedCall::capture(func, conv);
+        PENDING.with(|pending| pending.borrow_mut().push(call));
+    }
+}
+
+/// 调用结束时记录返回值与被改写的缓冲区
+pub(crate) fn end(func: &Func) {
+    let mut call = match PENDING.with(|pending| pending.borrow_mut().pop()) {
+        Some(call) => call,
+        None => return,
+    };
+    for (i, arg) in call.args.iter().enumerate() {
+        if let (Arg::Bytes(before), Some(after)) = (arg, owned_buffer(func, i)) {
+            if before[..] != *after {
+                call.written.push((i, after.to_vec()));
+            }
+        }
+    }
+    call.ret_low = func.ret.low;
+    call.ret_high = func.ret.high;
+    call.ret_float = func.ret.float.to_bits();
+    if let Some(calls) = &*ACTIVE.lock().unwrap() {
+        calls.lock().unwrap().push(call);
+    }
+}
+
+/// `replay` 的选项
+#[derive(Debug, Clone, Default)]
+pub struct ReplayOptions {
+    /// 以此代替记录中所有调用所在的库, 如用于测试的桩库
+    pub library: Option<PathBuf>,
+    /// 在第一处分歧处停止
+    pub stop_on_divergence: bool,
+}
+
+/// 重放时与记录不符之处
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum Divergence {
+    /// 无法还原或发出调用
+    Failed {
+        /// 调用在记录中的序号
+        index: usize,
+        reason: String,
+    },
+    /// 返回值不同
+    Ret {
+        /// 调用在记录中的序号
+        index: usize,
+        expected: RetValues,
+        got: RetValues,
+    },
+    /// 调用后缓冲区的内容不同
+    Buffer {
+        /// 调用在记录中的序号
+        index: usize,
+        /// 缓冲区是第几个参数
+        arg: usize,
+        expected: Vec<u8>,
+        got: Vec<u8>,
+    },
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Divergence::Failed { index, reason } => write!(f, "call {} failed: {}", index, reason),
+            Divergence::Ret {
+                index,
+                expected,
+                got,
+            } => write!(f, "call {} returned {}, expected {}", index, got, expected),
+            Divergence::Buffer {
+                index,
+                arg,
+                expected,
+                got,
+            } => write!(
+                f,
+                "call {} left argument {} as {:?}, expected {:?}",
+                index,
+                arg,
+                String::from_utf8_lossy(got),
+                String::from_utf8_lossy(expected)
+            ),
+        }
+    }
+}
+
+/// `replay` 的结果
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ReplayReport {
+    /// 重放了的调用个数
+    pub replayed: usize,
+    pub divergences: Vec<Divergence>,
+}
+
+impl ReplayReport {
+    /// 所有调用的结果都与记录的相同
+    pub fn is_identical(&self) -> bool {
+        self.divergences.is_empty()
+    }
+}
+
+/// 读取 path 中记录的调用并依次重放, 比较返回值与缓冲区的内容
+///
+/// 只比较声明的返回值类型所使用的寄存器, 未声明时只比较与指针等宽的整数返回值.
+/// 没有库的调用会按符号名在注册表中查找, 参见 `funcall::register`
+///
+/// # Safety
+///
+/// 会以记录中的参数调用任意函数, 调用者需保证记录可信, 且其中的指针参数在当前进程中依然有效
+pub unsafe fn replay<P: AsRef<Path>>(path: P, options: &ReplayOptions) -> Result<ReplayReport> {
+    Ok(replay_log(&CallLog::load(path)?, options))
+}
+
+/// 同 `replay`, 但重放已读取的记录
+///
+/// # Safety
+///
+/// 同 `replay`
+pub unsafe fn replay_log(log: &CallLog, options: &ReplayOptions) -> ReplayReport {
+    let mut libs = BTreeMap::new();
+    let mut report = ReplayReport::default();
+    for (index, call) in log.calls.iter().enumerate() {
+        report.replayed += 1;
+        let before = report.divergences.len();
+        match replay_call(call, options, &mut libs) {
+            Ok(func) => compare(index, call, &func, &mut report.divergences),
+            Err(reason) => report
+                .divergences
+                .push(Divergence::Failed { index, reason }),
+        }
+        if options.stop_on_divergence && report.divergences.len() != before {
+            break;
+        }
+    }
+    report
+}
+
+unsafe fn replay_call(
+    call: &RecordedCall,
+    options: &ReplayOptions,
+    libs: &mut BTreeMap<PathBuf, Library>,
+) -> std::result::Result<Func, String> {
+    let symbol = call.symbol.as_deref().ok_or("the call has no symbol")?;
+    let func = match options.library.as_ref().or(call.library.as_ref()) {
+        Some(path) => {
+            if !libs.contains_key(path) {
+                let lib = Library::new(path).map_err(|e| e.to_string())?;
+                libs.insert(path.clone(), lib);
+            }
+            libs[path].get(symbol)
+        }
+        None => Func::from_registry(symbol),
+    };
+    let mut func = func.map_err(|e| e.to_string())?;
+    if let Some((fixed, variadic)) = call.arity {
+        func.set_arity(fixed, variadic);
+    }
+    if let Some(kind) = call.ret_kind {
+        func.set_ret_kind(kind);
+    }
+    for (i, arg) in call.args.iter().enumerate() {
+        match arg {
+            Arg::F32(v) if call.c_floats.contains(&i) => func.push_float(*v),
+            Arg::U16(v) if call.halves.contains(&i) => func.push(F16::from_bits(*v)),
+            arg => func.push_arg(arg.clone()),
+        };
+    }
+    func.try_call(call.convention).map_err(|e| e.to_string())?;
+    Ok(func)
+}
+
+fn compare(index: usize, call: &RecordedCall, func: &Func, divergences: &mut Vec<Divergence>) {
+    let (expected, got) = (call.ret(), func.ret);
+    let two_words = std::mem::size_of::<usize>() == 4;
+    let same = match call.ret_kind {
+        Some(ArgKind::F32) | Some(ArgKind::F64) | Some(ArgKind::CFloat) => {
+            expected.float.to_bits() == got.float.to_bits()
+        }
+        Some(ArgKind::F16) => expected.as_f16() == got.as_f16(),
+        Some(ArgKind::I128) | Some(ArgKind::U128) => {
+            (expected.low, expected.high) == (got.low, got.high)
+        }
+        Some(ArgKind::I64) | Some(ArgKind::U64) if two_words => {
+            (expected.low, expected.high) == (got.low, got.high)
+        }
+        _ => expected.low == got.low,
+    };
+    if !same {
+        divergences.push(Divergence::Ret {
+            index,
+            expected,
+            got,
+        });
+    }
+    for arg in 0..call.args.len() {
+        if let (Some(expected), Some(got)) = (call.expected_buffer(arg), owned_buffer(func, arg)) {
+            if expected != got {
+                divergences.push(Divergence::Buffer {
+                    index,
+                    arg,
+                    expected: expected.to_vec(),
+                    got: got.to_vec(),
+                });
+            }
+        }
+    }
+}
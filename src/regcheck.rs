@@ -0,0 +1,176 @@
+//! 检查被调用者是否破坏了被调用者保护的寄存器, 参见 `regcheck` feature
+//!
+//! 检查路径与 `Func::cdecl` 的通用路径相同, 只是在参数之上多留出一块保存区, 记录调用前各寄存器的值.
+//! 调用后除 rsp 外的寄存器都不可信, 因此保存区的开头写入由其自身地址算出的标记, 调用后从 rsp 向上查找.
+//! 找到后记下调用后的值, 再从保存区恢复所有寄存器, 编译器的状态不会因此被破坏, 可以安全地 panic
+
+use alloc::format;
+use alloc::string::String;
+use core::arch::asm;
+
+use crate::{plan, Func};
+
+/// 与保存区的地址异或后作为标记
+const MARKER: usize = 0x5245_4743_4845_434b;
+
+/// 依次检查的寄存器, 调用时 r12 保存着调用前的 rsp, r13 与 r14 保存着参数的地址与个数
+const REGS: [&str; 6] = ["rbx", "rbp", "r12", "r13", "r14", "r15"];
+
+impl Func {
+    /// 同 `cdecl_stack`, 但调用后检查并恢复被调用者保护的寄存器, 被破坏时 panic
+    pub(crate) unsafe fn cdecl_checked(&self) -> (usize, usize, f64) {
+        let args = self.stack_args(plan::INT_REGS.len());
+        // 前 6 个为调用前的值, 后 6 个为调用后的值
+        let mut regs = [0usize; 12];
+        let (low, high, float): (usize, usize, f64);
+        asm!(
+            "mov r12, rsp",
+            "sub rsp, 128",
+            "and rsp, -16",
+            // 保存区: 标记, regs 的地址, 以及调用前的 rbx, rbp, r12, r13, r14, r15
+            "sub rsp, 64",
+            "mov rdi, rsp",
+            "xor rdi, r8",
+            "mov qword ptr [rsp], rdi",
+            "mov qword ptr [rsp + 8], rsi",
+            "mov qword ptr [rsp + 16], rbx",
+            "mov qword ptr [rsp + 24], rbp",
+            "mov qword ptr [rsp + 32], r12",
+            "mov qword ptr [rsp + 40], r13",
+            "mov qword ptr [rsp + 48], r14",
+            "mov qword ptr [rsp + 56], r15",
+            // 以下与 `cdecl_stack` 相同
+            "cmp r14, 6",
+            "jbe 3f",
+            "lea rsi, [r14 * 8 - 48]",
+            "6:",
+            "cmp rsi, 4096",
+            "jb 7f",
+            "sub rsp, 4096",
+            "or qword ptr [rsp], 0",
+            "sub rsi, 4096",
+            "jmp 6b",
+            "7:",
+            "sub rsp, rsi",
+            "and rsp, -16",
+            "mov rsi, 6",
+            "2:",
+            "mov rdi, qword ptr [r13 + rsi * 8]",
+            "mov qword ptr [rsp + rsi * 8 - 48], rdi",
+            "inc rsi",
+            "cmp rsi, r14",
+            "jb 2b",
+            "3:",
+            "test r14, r14",
+            "jz 4f",
+            "mov rdi, qword ptr [r13]",
+            "cmp r14, 1",
+            "je 4f",
+            "mov rsi, qword ptr [r13 + 8]",
+            "cmp r14, 2",
+            "je 4f",
+            "mov rdx, qword ptr [r13 + 16]",
+            "cmp r14, 3",
+            "je 4f",
+            "mov rcx, qword ptr [r13 + 24]",
+            "cmp r14, 4",
+            "je 4f",
+            "mov r8, qword ptr [r13 + 32]",
+            "cmp r14, 5",
+            "je 4f",
+            "mov r9, qword ptr [r13 + 40]",
+            "4:",
+            "xorps xmm0, xmm0",
+            "test eax, eax",
+            "jz 5f",
+            "movsd xmm0, qword ptr [r10]",
+            "cmp eax, 1",
+            "je 5f",
+            "movsd xmm1, qword ptr [r10 + 8]",
+            "cmp eax, 2",
+            "je 5f",
+            "movsd xmm2, qword ptr [r10 + 16]",
+            "cmp eax, 3",
+            "je 5f",
+            "movsd xmm3, qword ptr [r10 + 24]",
+            "cmp eax, 4",
+            "je 5f",
+            "movsd xmm4, qword ptr [r10 + 32]",
+            "cmp eax, 5",
+            "je 5f",
+            "movsd xmm5, qword ptr [r10 + 40]",
+            "cmp eax, 6",
+            "je 5f",
+            "movsd xmm6, qword ptr [r10 + 48]",
+            "cmp eax, 7",
+            "je 5f",
+            "movsd xmm7, qword ptr [r10 + 56]",
+            "5:",
+            "call r11",
+            // 保存区与参数都对齐到 16 字节, 从 rsp 开始每次上移 16 字节查找标记.
+            // rax, rdx 与 xmm0 中是返回值, 只使用其他的临时寄存器
+            "mov rcx, rsp",
+            "movabs r8, {marker}",
+            "8:",
+            "mov rsi, qword ptr [rcx]",
+            "xor rsi, rcx",
+            "cmp rsi, r8",
+            "je 9f",
+            "add rcx, 16",
+            "jmp 8b",
+            "9:",
+            "mov rsi, qword ptr [rcx + 8]",
+            "mov qword ptr [rsi + 48], rbx",
+            "mov qword ptr [rsi + 56], rbp",
+            "mov qword ptr [rsi + 64], r12",
+            "mov qword ptr [rsi + 72], r13",
+            "mov qword ptr [rsi + 80], r14",
+            "mov qword ptr [rsi + 88], r15",
+            "mov rbx, qword ptr [rcx + 16]",
+            "mov rbp, qword ptr [rcx + 24]",
+            "mov r12, qword ptr [rcx + 32]",
+            "mov r13, qword ptr [rcx + 40]",
+            "mov r14, qword ptr [rcx + 48]",
+            "mov r15, qword ptr [rcx + 56]",
+            "mov qword ptr [rsi], rbx",
+            "mov qword ptr [rsi + 8], rbp",
+            "mov qword ptr [rsi + 16], r12",
+            "mov qword ptr [rsi + 24], r13",
+            "mov qword ptr [rsi + 32], r14",
+            "mov qword ptr [rsi + 40], r15",
+            "mov rsp, r12",
+            marker = const MARKER,
+            out("r12") _,
+            inout("r13") args.as_ptr() => _,
+            inout("r14") args.len() => _,
+            in("r10") self.fargs.as_ptr(),
+            in("r11") self.func,
+            inout("rsi") regs.as_mut_ptr() => _,
+            inout("r8") MARKER => _,
+            inout("rax") self.fargs.len() => low,
+            out("rdx") high,
+            out("xmm0") float,
+            clobber_abi("C"),
+        );
+        check(&regs);
+        (low, high, float)
+    }
+}
+
+/// 比较调用前后的寄存器, 有不同时 panic 并列出所有被破坏的寄存器
+fn check(regs: &[usize; 12]) {
+    let (before, after) = regs.split_at(REGS.len());
+    let mut clobbered = REGS
+        .iter()
+        .zip(before.iter().zip(after))
+        .filter(|(_, (before, after))| before != after)
+        .peekable();
+    if clobbered.peek().is_none() {
+        return;
+    }
+    let mut msg = String::from("callee clobbered callee-saved register(s):");
+    for (name, (before, after)) in clobbered {
+        msg += &format!(" {} ({:#x} -> {:#x})", name, before, after);
+    }
+    panic!("{}", msg);
+}
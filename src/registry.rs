@@ -0,0 +1,67 @@
+//! 进程内的函数注册表
+
+use std::collections::BTreeMap;
+use std::sync::RwLock;
+
+use crate::{Error, Func, Result, SharedFunc, Signature};
+
+/// 一个已注册的函数
+struct Entry {
+    func: SharedFunc,
+    signature: Option<Signature>,
+}
+
+static REGISTRY: RwLock<BTreeMap<String, Entry>> = RwLock::new(BTreeMap::new());
+
+/// 以 name 注册一个函数, 之后可以通过 `Func::from_registry` 按名字取得它
+///
+/// 同名的函数会被替换, 返回是否替换了已有的函数
+pub fn register(name: &str, func: *const fn()) -> bool {
+    register_with(name, Func::from_raw(func).share(), None)
+}
+
+/// 同 `register`, 但可以注册来自动态库的函数并附带原型
+///
+/// 注册表会持有对函数所在库的引用. 若附带了原型, `Func::from_registry`
+/// 会据此设置参数个数, `registered_signature` 也可以取得原型来检查参数类型
+pub fn register_with(name: &str, func: SharedFunc, signature: Option<Signature>) -> bool {
+    REGISTRY
+        .write()
+        .unwrap()
+        .insert(name.to_owned(), Entry { func, signature })
+        .is_some()
+}
+
+/// 移除以 name 注册的函数, 返回是否存在这个函数
+///
+/// 已经取得的 `Func` 不受影响, 它们各自持有对函数所在库的引用
+pub fn unregister(name: &str) -> bool {
+    REGISTRY.write().unwrap().remove(name).is_some()
+}
+
+/// 以 name 注册的函数的原型
+pub fn registered_signature(name: &str) -> Option<Signature> {
+    REGISTRY
+        .read()
+        .unwrap()
+        .get(name)
+        .and_then(|entry| entry.signature.clone())
+}
+
+impl Func {
+    /// 从注册表中按名字取得函数, 参见 `funcall::register`
+    pub fn from_registry(name: &str) -> Result<Self> {
+        let registry = REGISTRY.read().unwrap();
+        let entry = registry
+            .get(name)
+            .ok_or_else(|| Error::NotFound(format!("no function registered as `{}`", name)))?;
+        let mut func = entry.func.func();
+        if func.symbol.is_none() {
+            func.symbol = Some(name.to_owned());
+        }
+        if let Some(signature) = &entry.signature {
+            signature.prepare(&mut func);
+        }
+        Ok(func)
+    }
+}
@@ -0,0 +1,60 @@
+//! 将库名解析为要加载的库, 参见 `set_resolver`
+
+use std::ffi::OsStr;
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+
+use crate::{Library, Result};
+
+/// `LibraryResolver` 的解析结果
+#[derive(Debug)]
+pub enum ResolvedLib {
+    /// 从该路径加载, 相对路径与不含路径的库名按系统的规则查找
+    Path(PathBuf),
+    /// 已经打开的库, 可以通过 `Library::from_raw` 接管预先打开的原始句柄
+    Handle(Library),
+    /// 库文件的内容, 写入临时文件后加载
+    Bytes(Vec<u8>),
+}
+
+/// 决定 `Library::new` 与 `Func::new` 等如何将库名解析为要加载的库
+pub trait LibraryResolver {
+    /// 解析 requested, 返回的错误会原样作为 `Library::new` 的错误
+    ///
+    /// 在其中调用 `Library::new` 会再次经过解析器, 需要时通过 `DefaultResolver` 解析
+    fn resolve(&self, requested: &OsStr) -> Result<ResolvedLib>;
+}
+
+/// 默认的解析器, 将库名原样作为路径
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DefaultResolver;
+
+impl LibraryResolver for DefaultResolver {
+    fn resolve(&self, requested: &OsStr) -> Result<ResolvedLib> {
+        Ok(ResolvedLib::Path(requested.into()))
+    }
+}
+
+static RESOLVER: RwLock<Option<Arc<dyn LibraryResolver + Send + Sync>>> = RwLock::new(None);
+
+/// 安装全局的解析器, 会替换之前安装的
+///
+/// 之后的 `Library::new`, `Func::new`, `LazyFunc` 等都先经过它解析库名; 已经加载的库不受影响.
+/// `Library::open_reloadable` 需要监视文件的变化, 仍直接读取传入的路径
+pub fn set_resolver(resolver: Box<dyn LibraryResolver + Send + Sync>) {
+    *RESOLVER.write().unwrap() = Some(resolver.into());
+}
+
+/// 移除全局的解析器, 恢复默认的行为
+pub fn clear_resolver() {
+    *RESOLVER.write().unwrap() = None;
+}
+
+/// 以当前安装的解析器解析 requested, 解析器本身可能会加载库, 因此不持有锁调用
+pub(crate) fn resolve(requested: &OsStr) -> Result<ResolvedLib> {
+    let resolver = RESOLVER.read().unwrap().clone();
+    match resolver {
+        Some(resolver) => resolver.resolve(requested),
+        None => DefaultResolver.resolve(requested),
+    }
+}
@@ -0,0 +1,102 @@
+//! 供 AddressSanitizer 与 Valgrind 使用的标注, 参见 `asan` 与 `valgrind` feature
+//!
+//! 手写汇编在当前栈顶之下对齐, 留出 red zone 并逐字压入参数, 这片区域中可能残留着之前的调用帧留下的
+//! ASan 毒化标记, 对齐留出的空位在 Valgrind 看来也是未初始化的. 调用前将这片区域标记为可访问且已初始化.
+//! 两个 feature 都关闭时 `outgoing_args` 是空的内联函数, 没有任何开销
+
+#[cfg(any(feature = "asan", feature = "valgrind"))]
+use core::ffi::c_void;
+#[cfg(any(feature = "asan", feature = "valgrind"))]
+use core::mem;
+
+use crate::Func;
+
+/// 栈顶之下除参数本身外还可能用到的字节数: x86_64 的 red zone, 对齐, 哨兵与保存的栈顶
+#[cfg(any(feature = "asan", feature = "valgrind"))]
+const SLACK: usize = 128 + 64;
+
+#[cfg(feature = "asan")]
+extern "C" {
+    fn __asan_unpoison_memory_region(addr: *const c_void, size: usize);
+}
+
+/// 调用前将汇编构造参数的区域标记为可访问且已初始化
+#[inline(always)]
+#[cfg_attr(
+    not(any(feature = "asan", feature = "valgrind")),
+    allow(unused_variables)
+)]
+pub(crate) fn outgoing_args(func: &Func) {
+    #[cfg(any(feature = "asan", feature = "valgrind"))]
+    {
+        let size = func.args.len() * mem::size_of::<usize>() + SLACK;
+        let addr = (stack_pointer() - size) as *const c_void;
+        #[cfg(feature = "asan")]
+        unsafe {
+            __asan_unpoison_memory_region(addr, size)
+        };
+        #[cfg(feature = "valgrind")]
+        make_mem_defined(addr, size);
+    }
+}
+
+/// 当前的栈顶
+#[cfg(any(feature = "asan", feature = "valgrind"))]
+#[inline(always)]
+fn stack_pointer() -> usize {
+    let sp: usize;
+    #[cfg(target_arch = "x86_64")]
+    unsafe {
+        core::arch::asm!("mov {}, rsp", out(reg) sp, options(nomem, nostack, preserves_flags))
+    };
+    #[cfg(target_arch = "x86")]
+    unsafe {
+        core::arch::asm!("mov {}, esp", out(reg) sp, options(nomem, nostack, preserves_flags))
+    };
+    #[cfg(target_arch = "loongarch64")]
+    unsafe {
+        core::arch::asm!("move {}, $sp", out(reg) sp, options(nomem, nostack, preserves_flags))
+    };
+    sp
+}
+
+/// Memcheck 的 `VALGRIND_MAKE_MEM_DEFINED`, 不在 Valgrind 中运行时什么也不做
+#[cfg(feature = "valgrind")]
+#[inline(always)]
+fn make_mem_defined(addr: *const c_void, size: usize) {
+    /// `VG_USERREQ_TOOL_BASE('M', 'C') + 2`
+    const MAKE_MEM_DEFINED: usize = 0x4d43_0002;
+    let request = [MAKE_MEM_DEFINED, addr as usize, size, 0, 0, 0];
+    // valgrind.h 中的魔数指令序列: 对同一寄存器的循环移位合计为字长的整数倍, 直接运行时没有效果,
+    // Valgrind 识别到后以 rax / eax 指向的请求处理, 结果写入 rdx / edx
+    #[cfg(target_arch = "x86_64")]
+    unsafe {
+        core::arch::asm!(
+            "rol rdi, 3",
+            "rol rdi, 13",
+            "rol rdi, 61",
+            "rol rdi, 51",
+            "xchg rbx, rbx",
+            in("rax") request.as_ptr(),
+            inout("rdx") 0usize => _,
+            inout("rdi") 0usize => _,
+            options(nostack),
+        )
+    };
+    #[cfg(target_arch = "x86")]
+    unsafe {
+        core::arch::asm!(
+            "rol edi, 3",
+            "rol edi, 13",
+            "rol edi, 29",
+            "rol edi, 19",
+            "xchg ebx, ebx",
+            in("eax") request.as_ptr(),
+            inout("edx") 0usize => _,
+            inout("edi") 0usize => _,
+            options(nostack),
+        )
+    };
+    #[cfg(not(any(target_arch = "x86_64", target_arch = "x86")))]
+    let _ = request;
+}
@@ -0,0 +1,288 @@
+//! 运行时自检, 参见 `self_test`
+
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::error::Error;
+use core::fmt;
+use core::hint::black_box;
+
+use crate::{ArgKind, Backend, Convention, Func};
+
+/// 自检中通过 `Func` 调用与直接调用结果不同的一个用例
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SelfTestFailure {
+    /// 用例的名字, 即参考函数的名字
+    pub case: &'static str,
+    /// 直接调用的结果
+    pub expected: String,
+    /// 通过 `Func` 调用的结果, 调用被拒绝时为 `try_call` 的错误
+    pub got: String,
+}
+
+impl fmt::Display for SelfTestFailure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}: expected {}, got {}",
+            self.case, self.expected, self.got
+        )
+    }
+}
+
+/// `self_test` 发现的所有不符的用例
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SelfTestReport {
+    /// 检查的用例总数
+    pub cases: usize,
+    /// 不符的用例, 按检查的顺序排列
+    pub failures: Vec<SelfTestFailure>,
+}
+
+impl fmt::Display for SelfTestReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} of {} self-test case(s) failed:",
+            self.failures.len(),
+            self.cases
+        )?;
+        for failure in &self.failures {
+            write!(f, "\n  {}", failure)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for SelfTestReport {}
+
+/// 以 cdecl 与默认的后端调用一组内置的参考函数, 与直接调用的结果逐一比较
+///
+/// 用例覆盖各标量类型的传递与返回, 整数与浮点数交替排列的参数, 超过寄存器个数的整数与浮点参数,
+/// f32 与 f64 的区别以及指针的往返. 可以在程序启动时调用, 尽早发现新版 rustc 或特殊的编译选项导致的错误调用.
+/// 调用会照常通知 `set_observer` 设置的观察者
+///
+/// ```
+/// funcall::self_test().unwrap();
+/// ```
+pub fn self_test() -> Result<(), SelfTestReport> {
+    self_test_with(Convention::Cdecl.backend().unwrap_or(Backend::Asm))
+}
+
+/// 同 `self_test`, 但使用指定的后端发出调用
+pub fn self_test_with(backend: Backend) -> Result<(), SelfTestReport> {
+    let failures = CASES
+        .iter()
+        .filter_map(|(case, run)| {
+            run(backend).err().map(|(expected, got)| SelfTestFailure {
+                case,
+                expected,
+                got,
+            })
+        })
+        .collect::<Vec<_>>();
+    if failures.is_empty() {
+        Ok(())
+    } else {
+        Err(SelfTestReport {
+            cases: CASES.len(),
+            failures,
+        })
+    }
+}
+
+/// 参考函数的参数与返回值类型
+trait Scalar: Copy + fmt::Debug {
+    const KIND: ArgKind;
+
+    fn push_to(self, func: &mut Func);
+
+    fn read(func: &Func) -> Self;
+
+    /// 比较用的位, 浮点数不经过 NaN 的比较
+    fn bits(self) -> u64;
+}
+
+macro_rules! impl_scalar {
+    ($($ty:ty => $kind:ident, $ret:ident;)*) => {
+        $(impl Scalar for $ty {
+            const KIND: ArgKind = ArgKind::$kind;
+
+            fn push_to(self, func: &mut Func) {
+                func.push(self);
+            }
+
+            fn read(func: &Func) -> Self {
+                func.$ret()
+            }
+
+            fn bits(self) -> u64 {
+                self as u64
+            }
+        })*
+    };
+}
+
+impl_scalar! {
+    i8 => I8, ret_as_i8;
+    u8 => U8, ret_as_u8;
+    i16 => I16, ret_as_i16;
+    u16 => U16, ret_as_u16;
+    i32 => I32, ret_as_i32;
+    u32 => U32, ret_as_u32;
+    i64 => I64, ret_as_i64;
+    u64 => U64, ret_as_u64;
+    isize => Isize, ret_as_isize;
+    usize => Usize, ret_as_usize;
+}
+
+impl Scalar for f32 {
+    const KIND: ArgKind = ArgKind::CFloat;
+
+    fn push_to(self, func: &mut Func) {
+        func.push_float(self);
+    }
+
+    fn read(func: &Func) -> Self {
+        func.ret_as_f32()
+    }
+
+    fn bits(self) -> u64 {
+        u64::from(self.to_bits())
+    }
+}
+
+impl Scalar for f64 {
+    const KIND: ArgKind = ArgKind::F64;
+
+    fn push_to(self, func: &mut Func) {
+        func.push(self);
+    }
+
+    fn read(func: &Func) -> Self {
+        func.ret_as_f64()
+    }
+
+    fn bits(self) -> u64 {
+        self.to_bits()
+    }
+}
+
+type Ptr = *const u8;
+
+impl Scalar for Ptr {
+    const KIND: ArgKind = ArgKind::Ptr;
+
+    fn push_to(self, func: &mut Func) {
+        func.push(self);
+    }
+
+    fn read(func: &Func) -> Self {
+        func.ret_as_usize() as Ptr
+    }
+
+    fn bits(self) -> u64 {
+        self as usize as u64
+    }
+}
+
+/// 按位置混合一个参数, 参数的顺序或位置错了都会得到不同的结果
+fn mix(hash: u64, value: impl Scalar) -> u64 {
+    (hash ^ value.bits())
+        .wrapping_mul(0x9e37_79b9_7f4a_7c15)
+        .rotate_left(29)
+}
+
+/// 由 backend 调用一个参考函数的用例, 不符时返回直接调用与通过 `Func` 调用的结果
+type Run = fn(Backend) -> Result<(), (String, String)>;
+
+/// 通过 `Func` 以 backend 调用 target, 与直接调用得到的 expected 比较
+fn compare<R: Scalar>(
+    target: *const fn(),
+    push: impl FnOnce(&mut Func),
+    expected: R,
+    backend: Backend,
+) -> Result<(), (String, String)> {
+    let mut func = Func::from_raw(target);
+    func.set_ret_kind(R::KIND);
+    push(&mut func);
+    // 参考函数由本模块定义, 签名与压入的参数一致
+    if let Err(e) = unsafe { func.try_call_with(Convention::Cdecl, backend) } {
+        return Err((format!("{:?}", expected), e.to_string()));
+    }
+    let got = R::read(&func);
+    if got.bits() == expected.bits() {
+        Ok(())
+    } else {
+        Err((format!("{:?}", expected), format!("{:?}", got)))
+    }
+}
+
+/// 定义参考函数与对应的用例, 参数的值在用例中给出
+///
+/// 函数体中可以使用参数; 省略函数体时将所有参数混合为一个 u64 返回
+macro_rules! cases {
+    (@body $($arg:ident)*; $body:block) => { $body };
+    (@body $($arg:ident)*;) => {{
+        #[allow(unused_mut)]
+        let mut hash = 0x2233;
+        $(hash = mix(hash, $arg);)*
+        hash
+    }};
+    ($($name:ident($($arg:ident: $ty:ty = $value:expr),*) -> $ret:ty $($body:block)?;)*) => {
+        $(#[allow(clippy::too_many_arguments)]
+        extern "C" fn $name($($arg: $ty),*) -> $ret {
+            cases!(@body $($arg)*; $($body)?)
+        })*
+
+        const CASES: &[(&str, Run)] = &[$((stringify!($name), |backend| {
+            $(let $arg: $ty = $value;)*
+            let expected = $name($(black_box($arg)),*);
+            let push = |func: &mut Func| {
+                $(Scalar::push_to($arg, func);)*
+            };
+            compare($name as *const fn(), push, expected, backend)
+        }),)*];
+    };
+}
+
+static TARGET: u64 = 0x1122_3344_5566_7788;
+
+cases! {
+    echo_i8(a: i8 = -0x5a) -> i8 { a };
+    echo_u8(a: u8 = 0xa5) -> u8 { a };
+    echo_i16(a: i16 = -0x1234) -> i16 { a };
+    echo_u16(a: u16 = 0xfedc) -> u16 { a };
+    echo_i32(a: i32 = -0x1234_5678) -> i32 { a };
+    echo_u32(a: u32 = 0xfedc_ba98) -> u32 { a };
+    echo_i64(a: i64 = -0x1234_5678_9abc_def0) -> i64 { a };
+    echo_u64(a: u64 = 0xfedc_ba98_7654_3210) -> u64 { a };
+    echo_isize(a: isize = -2233) -> isize { a };
+    echo_usize(a: usize = usize::MAX - 2233) -> usize { a };
+    echo_f32(a: f32 = -1.0 / 3.0) -> f32 { a };
+    echo_f64(a: f64 = -1.0 / 3.0) -> f64 { a };
+    echo_ptr(a: Ptr = &TARGET as *const u64 as Ptr) -> Ptr { a };
+    // 被调用者通过指针读取, 指针本身与指向的内存都必须完好
+    deref_ptr(a: Ptr = &TARGET as *const u64 as Ptr) -> u64 {
+        unsafe { *(a as *const u64) }
+    };
+    narrow_ints(a: i8 = -1, b: u8 = 0xff, c: i16 = -2, d: u16 = 0xfffe) -> u64;
+    int_then_float(a: i32 = 7, b: f64 = 1.5, c: i64 = -9, d: f64 = -0.25) -> u64;
+    float_then_int(a: f64 = 2.5, b: i32 = -3, c: f32 = 0.75, d: u64 = u64::MAX) -> u64;
+    f32_and_f64(a: f32 = 0.1, b: f64 = 0.1, c: f32 = f32::MIN_POSITIVE, d: f64 = f64::MAX) -> u64;
+    // 64 位 Linux 的六个整数寄存器, aarch64 等的八个整数寄存器都不够用
+    many_ints(
+        a0: i64 = 1, a1: i64 = -2, a2: i64 = 3, a3: i64 = -4, a4: i64 = 5,
+        a5: i64 = -6, a6: i64 = 7, a7: i64 = -8, a8: i64 = 9, a9: i64 = -10
+    ) -> u64;
+    many_floats(
+        a0: f64 = 0.5, a1: f64 = -1.5, a2: f64 = 2.5, a3: f64 = -3.5, a4: f64 = 4.5,
+        a5: f64 = -5.5, a6: f64 = 6.5, a7: f64 = -7.5, a8: f64 = 8.5, a9: f64 = -9.5
+    ) -> u64;
+    many_mixed(
+        a0: i32 = 1, a1: f32 = 1.25, a2: u64 = 2, a3: f64 = 2.5, a4: i8 = -3, a5: f32 = 3.75,
+        a6: u16 = 4, a7: f64 = 5.0, a8: i64 = -5, a9: f32 = 6.25, a10: u32 = 6, a11: f64 = 7.5,
+        a12: Ptr = &TARGET as *const u64 as Ptr, a13: f32 = 8.75, a14: isize = -7,
+        a15: f64 = 10.0, a16: i16 = 8, a17: f32 = 11.25
+    ) -> u64;
+}
@@ -0,0 +1,90 @@
+//! 可在线程间共享的函数
+
+use crate::Func;
+#[cfg(feature = "loader")]
+use crate::Library;
+
+/// 可在线程间共享的函数
+///
+/// 只包含函数地址与所在的库, 不包含任何参数状态. 每个线程通过 `SharedFunc::func`
+/// 得到自己的 `Func` 后再压入参数并调用, 因此多个线程可以同时调用同一个函数
+///
+/// ```
+/// use funcall::Func;
+/// extern "C" fn add(a: i32, b: i32) -> i32 {
+///     a + b
+/// }
+///
+/// let shared = Func::from_raw(add as *const fn()).share();
+/// let handles = (0..4)
+///     .map(|i| {
+///         let shared = shared.clone();
+///         std::thread::spawn(move || {
+///             let mut func = shared.func();
+///             func.push(i).push(1i32);
+///             unsafe { func.cdecl() };
+///             func.ret_as_i32()
+///         })
+///     })
+///     .collect::<Vec<_>>();
+/// for (i, handle) in handles.into_iter().enumerate() {
+///     assert_eq!(handle.join().unwrap(), i as i32 + 1);
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct SharedFunc {
+    /// 以整数形式储存的函数地址
+    func: usize,
+    #[cfg(feature = "loader")]
+    lib: Option<Library>,
+    symbol: Option<String>,
+    #[cfg(all(feature = "loader", windows, target_arch = "x86"))]
+    decorated_bytes: Option<usize>,
+}
+
+impl SharedFunc {
+    /// 创建一个参数为空的 `Func`
+    pub fn func(&self) -> Func {
+        let mut func = Func::from_raw(self.func as *const fn());
+        #[cfg(feature = "loader")]
+        {
+            func.lib = self.lib.clone();
+        }
+        func.symbol = self.symbol.clone();
+        #[cfg(all(feature = "loader", windows, target_arch = "x86"))]
+        {
+            func.decorated_bytes = self.decorated_bytes;
+        }
+        func
+    }
+
+    /// 查找函数时实际匹配到的符号名
+    pub fn symbol_name(&self) -> Option<&str> {
+        self.symbol.as_deref()
+    }
+
+    /// 函数指针
+    pub fn ptr(&self) -> *const fn() {
+        self.func as *const fn()
+    }
+}
+
+impl From<&Func> for SharedFunc {
+    fn from(func: &Func) -> Self {
+        Self {
+            func: func.func as usize,
+            #[cfg(feature = "loader")]
+            lib: func.lib.clone(),
+            symbol: func.symbol.clone(),
+            #[cfg(all(feature = "loader", windows, target_arch = "x86"))]
+            decorated_bytes: func.decorated_bytes,
+        }
+    }
+}
+
+impl Func {
+    /// 得到一个可在线程间共享的 `SharedFunc`, 已压入的参数不会被包含在内
+    pub fn share(&self) -> SharedFunc {
+        SharedFunc::from(self)
+    }
+}
@@ -0,0 +1,839 @@
+//! C 函数原型的解析
+
+use alloc::borrow::ToOwned;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::any::type_name;
+use core::convert::{TryFrom, TryInto};
+use core::ffi::{c_char, c_int, c_long, c_longlong, c_schar, c_short, c_uchar, c_uint, c_ulong};
+use core::ffi::{c_ulonglong, c_ushort};
+use core::mem;
+
+use crate::{Arg, ArgKind, ArgSink, CType, CallError, Error, Func, IntoArg, Result, RetValues};
+
+/// 一个 C 函数原型, 如 `int snprintf(char*, size_t, const char*, ...)`
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Hash)]
+pub struct Signature {
+    /// 函数名
+    pub name: String,
+    /// 返回值类型
+    pub ret: CType,
+    /// 固定参数的类型
+    pub params: Vec<CType>,
+    /// 是否为可变参数函数
+    pub variadic: bool,
+}
+
+impl Signature {
+    /// 解析 C 函数原型
+    ///
+    /// 支持基本类型, 指针, const 以及 `size_t`, `int32_t` 等常见类型别名, 参数名可以省略.
+    /// 结构体与联合体只能通过指针传递, 视为 `void *`; 枚举视为 `int`; 函数指针参数视为 `void *`
+    pub fn parse(proto: &str) -> Result<Self> {
+        Parser::new(proto)?.signature()
+    }
+
+    /// 根据原型设置 func 的参数个数与返回值类型, 参见 `Func::set_arity` 与 `Func::set_ret_kind`
+    pub fn prepare(&self, func: &mut Func) {
+        func.set_arity(self.params.len(), self.variadic);
+        func.ret_kind = ret_kind(&self.ret);
+    }
+
+    /// 按照第 index 个参数声明的类型压入 arg
+    ///
+    /// 整数会被转换为声明的宽度, 超出范围时报错; 整数与浮点数可以传给浮点参数;
+    /// 指针参数只接受 `Arg::Ptr`, `Arg::Bytes` 与 `Arg::Str`.
+    /// 可变参数部分会进行默认参数提升, 即 char 与 short 提升为 int, float 提升为 double
+    #[cfg_attr(feature = "provenance", track_caller)]
+    pub fn push_checked(
+        &self,
+        func: &mut Func,
+        index: usize,
+        arg: Arg,
+    ) -> core::result::Result<(), CallError> {
+        let start = func.slots.len();
+        match self.params.get(index) {
+            Some(ty) => push_as(func, ty, index, arg)?,
+            None if self.variadic => promote(func, arg),
+            None => {
+                return Err(CallError::ArgCountMismatch {
+                    expected: self.params.len(),
+                    variadic: false,
+                    got: index + 1,
+                })
+            }
+        }
+        func.mark_origin(start);
+        Ok(())
+    }
+}
+
+impl Func {
+    /// 附加原型并进入检查模式, 同时按原型设置参数个数与返回值类型
+    ///
+    /// 此后 `push`, `push_float` 与 `push_arg` 都会按 `try_push` 检查参数, 被拒绝的参数不会压入,
+    /// 第一个错误会在 `try_call` 时报告. 需要绕过检查时使用 `push_raw`
+    pub fn set_signature(&mut self, sig: Signature) {
+        sig.prepare(self);
+        self.restype = Some(sig.ret.clone());
+        self.signature = Some(Arc::new(sig));
+        self.rejected = None;
+    }
+
+    /// 仿照 Python ctypes 的 `argtypes`, 声明固定参数的类型并进入检查模式
+    ///
+    /// 之后压入的参数按 `try_push` 的规则转换为声明的类型: 整数会扩展到声明的宽度, f32 只在参数声明为 double 时才提升,
+    /// 类型不符或超出范围的参数会被拒绝, 错误在 `try_call` 时报告. 固定参数之后的参数只在 `set_variadic` 后才被接受.
+    /// 与 `set_signature` 不同, 不会改变已声明的返回值类型
+    ///
+    /// ```
+    /// use funcall::{CType, Convention, Func};
+    /// extern "C" fn scale(a: f32, b: i64) -> f64 {
+    ///     a as f64 * b as f64
+    /// }
+    ///
+    /// let mut func = Func::from_raw(scale as *const fn());
+    /// func.set_argtypes(&[CType::Float, CType::LongLong]);
+    /// func.set_restype(CType::Double);
+    /// func.push(1.5f64).push(4i8);
+    /// let ret = unsafe { func.invoke(Convention::Cdecl) }.unwrap();
+    /// assert_eq!(ret.as_f64(), 6.0);
+    /// ```
+    pub fn set_argtypes(&mut self, types: &[CType]) {
+        let (name, ret, variadic) = match self.signature.as_deref() {
+            Some(sig) => (sig.name.clone(), sig.ret.clone(), sig.variadic),
+            None => (
+                String::new(),
+                self.restype.clone().unwrap_or(CType::Void),
+                false,
+            ),
+        };
+        self.attach(Signature {
+            name,
+            ret,
+            params: types.to_vec(),
+            variadic,
+        });
+    }
+
+    /// 声明 `set_argtypes` 的固定参数之后还可以跟任意个可变参数, 它们会进行默认参数提升
+    ///
+    /// 没有声明过参数类型时, 所有参数都视为可变参数
+    pub fn set_variadic(&mut self, variadic: bool) {
+        let sig = match self.signature.as_deref() {
+            Some(sig) => Signature {
+                variadic,
+                ..sig.clone()
+            },
+            None => Signature {
+                name: String::new(),
+                ret: self.restype.clone().unwrap_or(CType::Void),
+                params: Vec::new(),
+                variadic,
+            },
+        };
+        self.attach(sig);
+    }
+
+    /// 仿照 ctypes 的 `restype`, 声明返回值的 C 类型
+    ///
+    /// 会按类型设置 `set_ret_kind`, 此后 `ret`, `invoke` 与 `ret_as_*` 都按声明的类型转换返回值.
+    /// 只声明返回值类型时不会进入检查模式
+    pub fn set_restype(&mut self, ty: CType) {
+        self.ret_kind = ret_kind(&ty);
+        if let Some(sig) = &mut self.signature {
+            Arc::make_mut(sig).ret = ty.clone();
+        }
+        self.restype = Some(ty);
+    }
+
+    /// 附加原型, 但保留已声明的返回值类型
+    fn attach(&mut self, sig: Signature) {
+        self.set_arity(sig.params.len(), sig.variadic);
+        self.signature = Some(Arc::new(sig));
+        self.rejected = None;
+    }
+
+    /// 通过 `set_signature` 附加的原型
+    pub fn signature(&self) -> Option<&Signature> {
+        self.signature.as_deref()
+    }
+
+    /// 按附加的原型检查下一个参数后压入, 没有附加原型时与 `push_raw` 相同
+    ///
+    /// 参数会转换为声明的类型, 规则见 `Signature::push_checked`: 如 i32 可以传给 long,
+    /// 但 f64 不能传给 int, 超出声明宽度的整数值也会被拒绝. 固定参数之后的参数只在可变参数函数中才被接受.
+    /// 第三方实现的 `IntoArg` 按其 `KIND` 检查, `KIND` 为 `ArgKind::Other` 时无法检查, 只能作为可变参数压入
+    #[cfg_attr(feature = "provenance", track_caller)]
+    pub fn try_push<T: IntoArg>(&mut self, arg: T) -> core::result::Result<&mut Self, CallError> {
+        if self.signature.is_none() {
+            return Ok(self.push_raw(arg));
+        }
+        let mut sink = ArgSink::new(T::KIND);
+        arg.push_into(&mut sink);
+        match typed_arg(&sink) {
+            Some(arg) => self.with_signature(|func, sig, index| sig.push_checked(func, index, arg)),
+            None => self.with_signature(|func, sig, index| match sig.params.get(index) {
+                Some(ty) => Err(CallError::ArgTypeMismatch {
+                    index,
+                    expected: ty.clone(),
+                    got: type_name::<T>().to_owned(),
+                }),
+                None if sig.variadic => {
+                    func.push_sink(sink);
+                    Ok(())
+                }
+                None => Err(CallError::ArgCountMismatch {
+                    expected: sig.params.len(),
+                    variadic: false,
+                    got: index + 1,
+                }),
+            }),
+        }
+    }
+
+    /// 同 `try_push`, 但压入运行时才确定类型的参数
+    #[cfg_attr(feature = "provenance", track_caller)]
+    pub fn try_push_arg(&mut self, arg: Arg) -> core::result::Result<&mut Self, CallError> {
+        if self.signature.is_none() {
+            return Ok(self.push_arg(arg));
+        }
+        self.with_signature(|func, sig, index| sig.push_checked(func, index, arg))
+    }
+
+    /// 暂时取出原型, 以免其中的压入再次被检查
+    #[cfg_attr(feature = "provenance", track_caller)]
+    fn with_signature(
+        &mut self,
+        push: impl FnOnce(&mut Func, &Signature, usize) -> core::result::Result<(), CallError>,
+    ) -> core::result::Result<&mut Self, CallError> {
+        let sig = self.signature.take().expect("no signature attached");
+        let index = self.slots.len();
+        let pushed = push(self, &sig, index);
+        self.signature = Some(sig);
+        pushed?;
+        Ok(self.mark_origin(index))
+    }
+
+    /// 检查模式下的压入, 记下第一个错误, 其后的参数都不再压入
+    pub(crate) fn push_declared(
+        &mut self,
+        push: impl FnOnce(&mut Func) -> core::result::Result<(), CallError>,
+    ) -> &mut Self {
+        if self.rejected.is_none() {
+            let index = self.slots.len();
+            if let Err(e) = push(self) {
+                self.rejected = Some((index, e));
+            }
+        }
+        self
+    }
+}
+
+/// 按 `KIND` 将写入的参数还原为 `Arg`, 未知的类型或写入的大小与类型不符时返回 `None`
+fn typed_arg(sink: &ArgSink) -> Option<Arg> {
+    let words = sink.words();
+    let bytes = sink.bytes();
+    macro_rules! read {
+        ($ty:ty) => {{
+            let size = mem::size_of::<$ty>();
+            if words.len() != size.div_ceil(mem::size_of::<usize>()) {
+                return None;
+            }
+            if size <= mem::size_of::<usize>() {
+                words[0] as $ty
+            } else {
+                <$ty>::from_ne_bytes(bytes[..size].try_into().unwrap())
+            }
+        }};
+    }
+    let arg = match sink.kind() {
+        ArgKind::I8 => Arg::I8(read!(i8)),
+        ArgKind::U8 => Arg::U8(read!(u8)),
+        ArgKind::I16 => Arg::I16(read!(i16)),
+        ArgKind::U16 => Arg::U16(read!(u16)),
+        ArgKind::I32 => Arg::I32(read!(i32)),
+        ArgKind::U32 => Arg::U32(read!(u32)),
+        ArgKind::I64 => Arg::I64(read!(i64)),
+        ArgKind::U64 => Arg::U64(read!(u64)),
+        ArgKind::I128 => Arg::I128(read!(i128)),
+        ArgKind::U128 => Arg::U128(read!(u128)),
+        ArgKind::Isize => Arg::Isize(read!(isize)),
+        ArgKind::Usize => Arg::Usize(read!(usize)),
+        // f32 已被提升为 double
+        ArgKind::F32 => Arg::F32(f64::from_bits(read!(u64)) as f32),
+        ArgKind::F64 => Arg::F64(f64::from_bits(read!(u64))),
+        ArgKind::Ptr => Arg::Ptr(read!(usize)),
+        _ => return None,
+    };
+    Some(arg)
+}
+
+/// 将 arg 转换为 ty 后压入, 规则见 `Signature::push_checked`
+pub(crate) fn push_as(
+    func: &mut Func,
+    ty: &CType,
+    index: usize,
+    arg: Arg,
+) -> core::result::Result<(), CallError> {
+    let mismatch = || CallError::ArgTypeMismatch {
+        index,
+        expected: ty.clone(),
+        got: format!("{:?}", arg),
+    };
+
+    macro_rules! push_int {
+        ($ty:ty) => {
+            match int_value(&arg).and_then(|v| <$ty>::try_from(v).ok()) {
+                Some(v) => func.push(v),
+                None => return Err(mismatch()),
+            }
+        };
+    }
+
+    let resolved = match ty {
+        CType::Const(ty) => ty.resolve_alias(),
+        ty => ty.resolve_alias(),
+    };
+    match resolved {
+        CType::Bool => match int_value(&arg) {
+            Some(v @ 0..=1) => func.push(v as u8),
+            _ => return Err(mismatch()),
+        },
+        CType::Char => push_int!(c_char),
+        CType::SChar => push_int!(c_schar),
+        CType::UChar => push_int!(c_uchar),
+        CType::Short => push_int!(c_short),
+        CType::UShort => push_int!(c_ushort),
+        CType::Int => push_int!(c_int),
+        CType::UInt => push_int!(c_uint),
+        CType::Long => push_int!(c_long),
+        CType::ULong => push_int!(c_ulong),
+        CType::LongLong => push_int!(c_longlong),
+        CType::ULongLong => push_int!(c_ulonglong),
+        CType::Float => match (&arg, int_value(&arg)) {
+            (Arg::F32(v), _) => func.push_float(*v),
+            (Arg::F64(v), _) => func.push_float(*v as f32),
+            (_, Some(v)) => func.push_float(v as f32),
+            _ => return Err(mismatch()),
+        },
+        CType::Double => match (&arg, int_value(&arg)) {
+            (Arg::F32(v), _) => func.push(f64::from(*v)),
+            (Arg::F64(v), _) => func.push(*v),
+            (_, Some(v)) => func.push(v as f64),
+            _ => return Err(mismatch()),
+        },
+        CType::Ptr(_) => match arg {
+            Arg::Ptr(_) | Arg::Bytes(_) | Arg::Str(_) => func.push_arg(arg),
+            _ => return Err(mismatch()),
+        },
+        _ => return Err(mismatch()),
+    };
+    Ok(())
+}
+
+/// 返回值的类型, 与指针等宽的整数返回 `None`
+pub(crate) fn ret_kind(ty: &CType) -> Option<ArgKind> {
+    match ty.resolve_alias() {
+        CType::Float => Some(ArgKind::CFloat),
+        CType::Double => Some(ArgKind::F64),
+        CType::LongLong => Some(ArgKind::I64),
+        CType::ULongLong => Some(ArgKind::U64),
+        CType::Const(ty) => ret_kind(&ty),
+        _ => None,
+    }
+}
+
+/// 按声明的返回值类型整理返回值, 窄于寄存器的整数舍弃高位的残留值并扩展到 `low` 与 `high`
+pub(crate) fn narrow_ret(ty: &CType, mut ret: RetValues) -> RetValues {
+    let resolved = match ty {
+        CType::Const(ty) => ty.resolve_alias(),
+        ty => ty.resolve_alias(),
+    };
+    let low = match resolved {
+        CType::Bool => i128::from(ret.low as u8 != 0),
+        CType::Char => ret.low as c_char as i128,
+        CType::SChar => ret.low as c_schar as i128,
+        CType::UChar => ret.low as c_uchar as i128,
+        CType::Short => ret.low as c_short as i128,
+        CType::UShort => ret.low as c_ushort as i128,
+        CType::Int => ret.low as c_int as i128,
+        CType::UInt => ret.low as c_uint as i128,
+        CType::Long => ret.low as c_long as i128,
+        _ => return ret,
+    };
+    ret.low = low as usize;
+    ret.high = if low < 0 { usize::MAX } else { 0 };
+    ret
+}
+
+/// 可变参数的默认参数提升
+pub(crate) fn promote(func: &mut Func, arg: Arg) {
+    match arg {
+        Arg::I8(v) => func.push(c_int::from(v)),
+        Arg::U8(v) => func.push(c_int::from(v)),
+        Arg::I16(v) => func.push(c_int::from(v)),
+        Arg::U16(v) => func.push(c_int::from(v)),
+        Arg::F32(v) => func.push(f64::from(v)),
+        arg => func.push_arg(arg),
+    };
+}
+
+/// 整数参数的值, 超出 i128 范围的 u128 一定放不进任何 C 整数类型, 统一视为 i128::MAX
+pub(crate) fn int_value(arg: &Arg) -> Option<i128> {
+    Some(match *arg {
+        Arg::I8(v) => v.into(),
+        Arg::U8(v) => v.into(),
+        Arg::I16(v) => v.into(),
+        Arg::U16(v) => v.into(),
+        Arg::I32(v) => v.into(),
+        Arg::U32(v) => v.into(),
+        Arg::I64(v) => v.into(),
+        Arg::U64(v) => v.into(),
+        Arg::I128(v) => v,
+        Arg::U128(v) => i128::try_from(v).unwrap_or(i128::MAX),
+        Arg::Isize(v) => v as i128,
+        Arg::Usize(v) => v as i128,
+        _ => return None,
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Token<'a> {
+    Ident(&'a str),
+    Star,
+    LParen,
+    RParen,
+    Comma,
+    Ellipsis,
+    Semi,
+}
+
+/// 类型说明符与限定符
+const KEYWORDS: &[&str] = &[
+    "const",
+    "volatile",
+    "signed",
+    "unsigned",
+    "short",
+    "long",
+    "char",
+    "int",
+    "float",
+    "double",
+    "void",
+    "_Bool",
+    "bool",
+    "size_t",
+    "ssize_t",
+    "intptr_t",
+    "uintptr_t",
+    "int8_t",
+    "uint8_t",
+    "int16_t",
+    "uint16_t",
+    "int32_t",
+    "uint32_t",
+    "int64_t",
+    "uint64_t",
+];
+
+/// 通过 typedef 定义的类型名, 参见 `headers::parse`
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum TypeDef {
+    Type(CType),
+    /// 结构体或联合体, 只能通过指针传递, 其中为出错时显示的名字
+    Record(String),
+}
+
+pub(crate) struct Parser<'a> {
+    /// 每个 token 及其所在的列 (从 1 开始)
+    tokens: Vec<(usize, Token<'a>)>,
+    pos: usize,
+    /// 输入结束处的列
+    end: usize,
+    /// 除基本类型外可以使用的类型名
+    typedefs: Option<&'a BTreeMap<String, TypeDef>>,
+}
+
+impl<'a> Parser<'a> {
+    /// 同 `new`, 但还可以使用 typedefs 中的类型名
+    #[cfg(feature = "headers")]
+    pub(crate) fn with_typedefs(
+        proto: &'a str,
+        typedefs: &'a BTreeMap<String, TypeDef>,
+    ) -> Result<Self> {
+        let mut parser = Self::new(proto)?;
+        parser.typedefs = Some(typedefs);
+        Ok(parser)
+    }
+
+    fn new(proto: &'a str) -> Result<Self> {
+        let mut tokens = Vec::new();
+        let bytes = proto.as_bytes();
+        let mut i = 0;
+        while i < bytes.len() {
+            let start = i;
+            let token = match bytes[i] {
+                c if c.is_ascii_whitespace() => {
+                    i += 1;
+                    continue;
+                }
+                c if c.is_ascii_alphabetic() || c == b'_' => {
+                    while i < bytes.len() && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'_')
+                    {
+                        i += 1;
+                    }
+                    tokens.push((start + 1, Token::Ident(&proto[start..i])));
+                    continue;
+                }
+                b'*' => Token::Star,
+                b'(' => Token::LParen,
+                b')' => Token::RParen,
+                b',' => Token::Comma,
+                b';' => Token::Semi,
+                b'.' if proto[i..].starts_with("...") => {
+                    i += 2;
+                    Token::Ellipsis
+                }
+                _ => {
+                    let c = proto[i..].chars().next().unwrap();
+                    return Err(invalid(format!(
+                        "unexpected character `{}` at column {}",
+                        c,
+                        i + 1
+                    )));
+                }
+            };
+            i += 1;
+            tokens.push((start + 1, token));
+        }
+        Ok(Self {
+            tokens,
+            pos: 0,
+            end: proto.len() + 1,
+            typedefs: None,
+        })
+    }
+
+    fn peek(&self) -> Option<Token<'a>> {
+        self.tokens.get(self.pos).map(|&(_, token)| token)
+    }
+
+    fn eat(&mut self, token: Token) -> bool {
+        let eaten = self.peek() == Some(token);
+        if eaten {
+            self.pos += 1;
+        }
+        eaten
+    }
+
+    /// 在当前位置报错
+    fn error(&self, expected: &str) -> Error {
+        match self.tokens.get(self.pos) {
+            Some((col, token)) => invalid(format!(
+                "expected {}, found {} at column {}",
+                expected,
+                describe(*token),
+                col
+            )),
+            None => invalid(format!(
+                "expected {}, found end of input at column {}",
+                expected, self.end
+            )),
+        }
+    }
+
+    fn expect(&mut self, token: Token, expected: &str) -> Result<()> {
+        if self.eat(token) {
+            Ok(())
+        } else {
+            Err(self.error(expected))
+        }
+    }
+
+    /// 列号, 输入结束时为结尾处的列
+    fn col(&self) -> usize {
+        self.tokens.get(self.pos).map_or(self.end, |t| t.0)
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.eat(Token::Semi);
+        if self.pos < self.tokens.len() {
+            return Err(self.error("end of input"));
+        }
+        Ok(())
+    }
+
+    pub(crate) fn signature(mut self) -> Result<Signature> {
+        let ret = self.ty()?;
+        let name = match self.peek() {
+            Some(Token::Ident(name)) if !KEYWORDS.contains(&name) => name.to_owned(),
+            _ => return Err(self.error("function name")),
+        };
+        self.pos += 1;
+        self.expect(Token::LParen, "`(`")?;
+
+        let mut params = Vec::new();
+        let mut variadic = false;
+        // `f()` 与 `f(void)` 都表示没有参数
+        if self.peek() == Some(Token::Ident("void"))
+            && self.tokens.get(self.pos + 1).map(|t| t.1) == Some(Token::RParen)
+        {
+            self.pos += 1;
+        } else if self.peek() != Some(Token::RParen) {
+            loop {
+                if self.eat(Token::Ellipsis) {
+                    variadic = true;
+                    break;
+                }
+                let col = self.col();
+                let mut param = self.ty()?;
+                if self.peek() == Some(Token::LParen) {
+                    self.fn_ptr()?;
+                    param = CType::ptr(CType::Void);
+                }
+                if param == CType::Void || param == CType::constant(CType::Void) {
+                    return Err(invalid(format!(
+                        "`void` is not a valid parameter type at column {}",
+                        col
+                    )));
+                }
+                params.push(param);
+                // 参数名可以省略
+                if let Some(Token::Ident(name)) = self.peek() {
+                    if !KEYWORDS.contains(&name) {
+                        self.pos += 1;
+                    }
+                }
+                if !self.eat(Token::Comma) {
+                    break;
+                }
+            }
+        }
+        self.expect(Token::RParen, if variadic { "`)`" } else { "`,` or `)`" })?;
+        self.finish()?;
+
+        Ok(Signature {
+            name,
+            ret,
+            params,
+            variadic,
+        })
+    }
+
+    /// 解析 `typedef` 之后的部分, 如 `unsigned long my_size;`, 返回新的类型名与其类型
+    ///
+    /// 函数指针类型 `ret (*name)(...)` 视为 `void *`
+    #[cfg(feature = "headers")]
+    pub(crate) fn typedef(mut self) -> Result<(String, TypeDef)> {
+        let mut ty = self.any_ty()?;
+        let name = if self.peek() == Some(Token::LParen) {
+            ty = TypeDef::Type(CType::ptr(CType::Void));
+            self.fn_ptr()?
+        } else {
+            self.name()?
+        };
+        let name = name.ok_or_else(|| self.error("a type name"))?;
+        self.finish()?;
+        if let TypeDef::Record(_) = ty {
+            ty = TypeDef::Record(name.to_owned());
+        }
+        Ok((name.to_owned(), ty))
+    }
+
+    /// 可以省略的名字
+    fn name(&mut self) -> Result<Option<&'a str>> {
+        match self.peek() {
+            Some(Token::Ident(name)) if !KEYWORDS.contains(&name) => {
+                self.pos += 1;
+                Ok(Some(name))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// 函数指针类型中返回值类型之后的部分 `(*name)(...)`, 返回其中的名字
+    fn fn_ptr(&mut self) -> Result<Option<&'a str>> {
+        self.expect(Token::LParen, "`(`")?;
+        self.expect(Token::Star, "`*`")?;
+        let name = self.name()?;
+        self.expect(Token::RParen, "`)`")?;
+        self.expect(Token::LParen, "`(`")?;
+        // 参数的类型不影响函数指针本身的传递
+        let mut depth = 1;
+        while depth > 0 {
+            match self.peek() {
+                Some(Token::LParen) => depth += 1,
+                Some(Token::RParen) => depth -= 1,
+                Some(_) => (),
+                None => return Err(self.error("`)`")),
+            }
+            self.pos += 1;
+        }
+        Ok(name)
+    }
+
+    fn ty(&mut self) -> Result<CType> {
+        let col = self.col();
+        match self.any_ty()? {
+            TypeDef::Type(ty) => Ok(ty),
+            TypeDef::Record(name) => Err(invalid(format!(
+                "`{}` passed by value is not supported at column {}",
+                name, col
+            ))),
+        }
+    }
+
+    /// 同 `ty`, 但允许不经指针使用的结构体
+    fn any_ty(&mut self) -> Result<TypeDef> {
+        let col = self.col();
+        let mut constant = false;
+        let mut words = Vec::new();
+        let mut named = None;
+        while let Some(Token::Ident(word)) = self.peek() {
+            match word {
+                "const" => constant = true,
+                // volatile 不影响调用方式
+                "volatile" => (),
+                "struct" | "union" | "enum" if words.is_empty() && named.is_none() => {
+                    self.pos += 1;
+                    let tag = match self.peek() {
+                        Some(Token::Ident(tag)) if !KEYWORDS.contains(&tag) => tag,
+                        _ => return Err(self.error("a tag name")),
+                    };
+                    named = Some(if word == "enum" {
+                        TypeDef::Type(CType::Int)
+                    } else {
+                        TypeDef::Record(format!("{} {}", word, tag))
+                    });
+                }
+                word if KEYWORDS.contains(&word) => words.push(word),
+                word if words.is_empty() && named.is_none() => {
+                    match self.typedefs.and_then(|typedefs| typedefs.get(word)) {
+                        Some(ty) => named = Some(ty.clone()),
+                        None => break,
+                    }
+                }
+                _ => break,
+            }
+            self.pos += 1;
+        }
+        let unsupported = || {
+            invalid(format!(
+                "unsupported type `{}` at column {}",
+                words.join(" "),
+                col
+            ))
+        };
+        let mut ty = match named {
+            Some(_) if !words.is_empty() => return Err(unsupported()),
+            Some(TypeDef::Type(ty)) => ty,
+            Some(TypeDef::Record(name)) => {
+                if self.peek() != Some(Token::Star) {
+                    return Ok(TypeDef::Record(name));
+                }
+                CType::Void
+            }
+            None if words.is_empty() => return Err(self.error("a type")),
+            None => builtin(&words).ok_or_else(unsupported)?,
+        };
+        if constant {
+            ty = CType::constant(ty);
+        }
+
+        while self.eat(Token::Star) {
+            ty = CType::ptr(ty);
+            let mut constant = false;
+            while let Some(Token::Ident(word @ ("const" | "volatile"))) = self.peek() {
+                constant |= word == "const";
+                self.pos += 1;
+            }
+            if constant {
+                ty = CType::constant(ty);
+            }
+        }
+        Ok(TypeDef::Type(ty))
+    }
+}
+
+/// 将一组类型说明符组合为类型, 如 `unsigned long int` -> `ULong`
+fn builtin(words: &[&str]) -> Option<CType> {
+    let (mut signed, mut unsigned, mut short, mut long) = (0, 0, 0, 0);
+    let mut base = None;
+    for &word in words {
+        match word {
+            "signed" => signed += 1,
+            "unsigned" => unsigned += 1,
+            "short" => short += 1,
+            "long" => long += 1,
+            word => {
+                if base.replace(word).is_some() {
+                    return None;
+                }
+            }
+        }
+    }
+    if signed + unsigned > 1 || short > 1 || long > 2 || short * long > 0 {
+        return None;
+    }
+    let sign = signed + unsigned == 1;
+    let unsigned = unsigned == 1;
+
+    Some(match (base, short, long) {
+        (Some("char"), 0, 0) if !sign => CType::Char,
+        (Some("char"), 0, 0) if unsigned => CType::UChar,
+        (Some("char"), 0, 0) => CType::SChar,
+        (None, 0, 0) | (Some("int"), 0, 0) => {
+            if unsigned {
+                CType::UInt
+            } else {
+                CType::Int
+            }
+        }
+        (None, 1, 0) | (Some("int"), 1, 0) if unsigned => CType::UShort,
+        (None, 1, 0) | (Some("int"), 1, 0) => CType::Short,
+        (None, 0, 1) | (Some("int"), 0, 1) if unsigned => CType::ULong,
+        (None, 0, 1) | (Some("int"), 0, 1) => CType::Long,
+        (None, 0, 2) | (Some("int"), 0, 2) if unsigned => CType::ULongLong,
+        (None, 0, 2) | (Some("int"), 0, 2) => CType::LongLong,
+        (Some(base), 0, 0) if !sign => match base {
+            "float" => CType::Float,
+            "double" => CType::Double,
+            "void" => CType::Void,
+            "_Bool" | "bool" => CType::Bool,
+            "size_t" => CType::SizeT,
+            "ssize_t" => CType::SSizeT,
+            "intptr_t" => CType::IntPtr,
+            "uintptr_t" => CType::UIntPtr,
+            "int8_t" => CType::Int8,
+            "uint8_t" => CType::UInt8,
+            "int16_t" => CType::Int16,
+            "uint16_t" => CType::UInt16,
+            "int32_t" => CType::Int32,
+            "uint32_t" => CType::UInt32,
+            "int64_t" => CType::Int64,
+            "uint64_t" => CType::UInt64,
+            _ => return None,
+        },
+        _ => return None,
+    })
+}
+
+fn describe(token: Token) -> String {
+    match token {
+        Token::Ident(ident) => format!("`{}`", ident),
+        Token::Star => "`*`".into(),
+        Token::LParen => "`(`".into(),
+        Token::RParen => "`)`".into(),
+        Token::Comma => "`,`".into(),
+        Token::Ellipsis => "`...`".into(),
+        Token::Semi => "`;`".into(),
+    }
+}
+
+fn invalid(msg: String) -> Error {
+    Error::InvalidInput(msg)
+}
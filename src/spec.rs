@@ -0,0 +1,103 @@
+//! 可序列化的调用描述
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+#[cfg(feature = "loader")]
+use crate::{errno, Func, Library, Result, RetValues};
+use crate::{Arg, ArgKind, Convention};
+
+/// 对一次调用的完整描述: 库, 符号, 调用约定, 参数与返回值类型
+///
+/// 开启 `serde` feature 后可以序列化保存, 之后再通过 `instantiate` 还原为 `Func`;
+/// 开启 `json` feature 后还可以使用 `from_json` 中更简洁的 JSON 格式.
+/// 注意 `Arg::Ptr` 只会以整数的形式保存, 在其他进程中没有意义.
+/// 以后还可能增加字段, 在 crate 外需要通过 `new` 构造
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[non_exhaustive]
+pub struct CallSpec {
+    /// 动态库的路径
+    pub library: String,
+    /// 函数的符号名
+    pub symbol: String,
+    /// 调用约定
+    pub convention: Convention,
+    /// 依次压入的参数
+    pub args: Vec<Arg>,
+    /// 返回值的类型, 为 `None` 时没有返回值或不关心返回值
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub ret: Option<ArgKind>,
+}
+
+impl CallSpec {
+    /// 不声明返回值类型的调用, 需要时再设置 `ret`
+    pub fn new(
+        library: impl Into<String>,
+        symbol: impl Into<String>,
+        convention: Convention,
+        args: Vec<Arg>,
+    ) -> Self {
+        CallSpec {
+            library: library.into(),
+            symbol: symbol.into(),
+            convention,
+            args,
+            ret: None,
+        }
+    }
+
+    /// 加载库并查找函数, 然后依次压入所有参数
+    ///
+    /// 返回的 `Func` 尚未调用, 可通过 `Func::try_call(spec.convention)` 进行调用.
+    /// 声明了返回值类型时会通过 `Func::set_ret_kind` 设置
+    #[cfg(feature = "loader")]
+    pub fn instantiate(&self) -> Result<Func> {
+        let mut func = Library::new(&self.library)?.get(&self.symbol)?;
+        for arg in &self.args {
+            func.push_arg(arg.clone());
+        }
+        if let Some(kind) = self.ret {
+            func.set_ret_kind(kind);
+        }
+        Ok(func)
+    }
+
+    /// 还原为 `Func` 后立即调用, 并记录调用刚结束时的 errno
+    ///
+    /// # Safety
+    ///
+    /// 同 `Func::try_call`, 参数必须与函数的实际签名一致
+    #[cfg(feature = "loader")]
+    pub unsafe fn execute(&self) -> SpecResult {
+        let mut func = match self.instantiate() {
+            Ok(func) => func,
+            Err(e) => {
+                return SpecResult {
+                    ret: Err(e.to_string()),
+                    errno: 0,
+                }
+            }
+        };
+        errno::clear();
+        let ret = func.invoke(self.convention);
+        let errno = errno::get();
+        SpecResult {
+            ret: ret.map_err(|e| e.to_string()),
+            errno,
+        }
+    }
+}
+
+/// `CallSpec::execute` 的结果
+#[cfg(feature = "loader")]
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpecResult {
+    /// 调用成功时为各返回值寄存器的值, 否则为加载或调用前检查出的错误
+    pub ret: core::result::Result<RetValues, String>,
+    /// 调用刚结束时的 errno, Windows 下为 `GetLastError` 的值
+    pub errno: i32,
+}
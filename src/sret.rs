@@ -0,0 +1,267 @@
+//! 按 ABI 决定结构体返回值通过寄存器还是隐藏指针传回, 参见 `Func::set_return`
+
+use core::mem;
+use core::ptr;
+
+use crate::{ArgKind, Backend, Func};
+
+/// 返回值的类型与大小, 参见 `Func::set_return`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Hash)]
+pub enum RetLayout {
+    /// 整数, 指针或浮点数, 同 `Func::set_ret_kind`
+    Scalar(ArgKind),
+    /// 只含整数与指针成员的结构体或联合体, 大小与对齐均以字节计
+    Aggregate { size: usize, align: usize },
+    /// 只含 count 个 float (double 为 `false`) 或 double 成员的结构体, 如 `struct { float x, y, z, w; }`
+    ///
+    /// aarch64 与 ARM32 (硬浮点) 下不超过 4 个成员时即为 HFA, 各成员分别通过 v0~v3 (s0~s3 或 d0~d3) 返回;
+    /// 64 位 Linux 下不超过 16 字节时通过 xmm0 与 xmm1 返回
+    FloatAggregate { count: usize, double: bool },
+}
+
+impl RetLayout {
+    /// 按当前平台的 ABI, 返回值是否写入调用者提供的内存
+    ///
+    /// - aarch64 与 ARM32 (硬浮点) 下的 HFA 总是通过寄存器返回, 不受以下大小的限制
+    /// - 64 位 Linux 与 aarch64 (包括 Windows ARM64) 下超过 16 字节的结构体
+    /// - x86 与 x86_64 的 Windows 下大小不是 1, 2, 4, 8 字节的结构体
+    /// - 32 位 Linux 与 s390x 下所有的结构体
+    /// - ARM32 下超过 4 字节的结构体
+    pub fn in_memory(self) -> bool {
+        let size = match self {
+            RetLayout::Scalar(_) => return false,
+            RetLayout::Aggregate { size, .. } => size,
+            RetLayout::FloatAggregate { count, .. } if count <= 4 && HFA_IN_REGS => return false,
+            RetLayout::FloatAggregate { count, double } => count * float_size(double),
+        };
+        if cfg!(all(windows, not(target_arch = "aarch64"))) {
+            !matches!(size, 1 | 2 | 4 | 8)
+        } else if cfg!(any(target_arch = "x86", target_arch = "s390x")) {
+            true
+        } else if cfg!(target_arch = "arm") {
+            size > 4
+        } else {
+            size > 2 * mem::size_of::<usize>()
+        }
+    }
+}
+
+/// 不超过 4 个成员的 HFA 通过浮点寄存器返回的平台
+const HFA_IN_REGS: bool = cfg!(any(
+    target_arch = "aarch64",
+    all(target_arch = "arm", target_abi = "eabihf")
+));
+
+fn float_size(double: bool) -> usize {
+    if double {
+        8
+    } else {
+        4
+    }
+}
+
+/// 通过隐藏指针传回返回值时, 指针作为第一个参数传递; aarch64 下则通过 x8 传递, 由 libffi 处理
+const HIDDEN_ARG: bool = !cfg!(target_arch = "aarch64");
+
+/// 声明为结构体的返回值
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub(crate) struct RetBuf {
+    /// 结构体的大小
+    pub(crate) size: usize,
+    /// 缓冲区在 `Func::owned` 中的序号
+    buf: Option<usize>,
+    /// 是否写入内存, 否则缓冲区中是调用后从寄存器中取出的浮点结构体
+    in_memory: bool,
+    /// 浮点结构体的成员是否为 double, 其他结构体为 `None`
+    floats: Option<bool>,
+}
+
+impl RetBuf {
+    /// libffi 需要以结构体类型描述返回值时其占用的字数, 隐藏指针已作为参数压入时为 `None`
+    #[cfg_attr(not(feature = "libffi"), allow(unused))]
+    pub(crate) fn ffi_words(&self) -> Option<usize> {
+        if HIDDEN_ARG && self.in_memory {
+            return None;
+        }
+        Some(self.size.div_ceil(mem::size_of::<usize>()).max(1))
+    }
+
+    /// 浮点结构体的成员个数与是否为 double, libffi 需要据此描述返回值的类型
+    #[cfg_attr(not(feature = "libffi"), allow(unused))]
+    pub(crate) fn ffi_floats(&self) -> Option<(usize, bool)> {
+        self.floats
+            .map(|double| (self.size / float_size(double), double))
+    }
+
+    /// backend 能否取回通过寄存器返回的浮点结构体
+    ///
+    /// libffi 按结构体类型取回返回值; 手写汇编与 JIT 的后端只保存了 `RetValues` 中的寄存器,
+    /// 64 位 Linux 下放不下需要 xmm1 的结构体, LoongArch64 下放不下通过 fa0 与 fa1 返回的两个成员
+    pub(crate) fn supported_by(&self, backend: Backend) -> bool {
+        let double = match self.floats {
+            Some(double) if !self.in_memory && backend != Backend::Libffi => double,
+            _ => return true,
+        };
+        if cfg!(all(target_arch = "x86_64", not(windows))) {
+            self.size <= 8
+        } else if cfg!(target_arch = "loongarch64") {
+            self.size / float_size(double) != 2
+        } else {
+            true
+        }
+    }
+
+    /// 缓冲区在 `Func::owned` 中的序号, 调用前的内容不影响结果
+    #[cfg(feature = "std")]
+    pub(crate) fn buf_index(&self) -> Option<usize> {
+        self.buf
+    }
+
+    /// 缓冲区中返回值的起始地址
+    pub(crate) fn ptr(&self, func: &Func) -> Option<*mut u8> {
+        self.buf.map(|index| unsafe {
+            func.owned[index].as_ptr().add(func.owned_start(index)) as *mut u8
+        })
+    }
+}
+
+impl Func {
+    /// 声明返回值的类型, 结构体返回值按当前平台的 ABI 通过寄存器或隐藏指针传回
+    ///
+    /// 需要通过隐藏指针传回时 (参见 `RetLayout::in_memory`) 会分配由 `Func` 持有的缓冲区,
+    /// 并将指向它的指针插入到所有参数之前, `dry_run` 中可以看到它作为第一个参数.
+    /// 与 `from_vtable` 压入的 this 一样, 已声明的固定参数个数随之加一; 对于 C++ 成员函数,
+    /// 指针位于 this 之前还是之后取决于调用 `set_return` 与 `from_vtable` 的先后, Itanium ABI 中位于 this 之前.
+    /// 调用后通过 `read_ret` 读出返回的结构体
+    ///
+    /// ```
+    /// use funcall::{Convention, Func, RetLayout};
+    ///
+    /// #[repr(C)]
+    /// #[derive(Clone, Copy, Debug, PartialEq)]
+    /// struct Vec3 {
+    ///     x: i64,
+    ///     y: i64,
+    ///     z: i64,
+    /// }
+    /// extern "C" fn vec3(x: i64) -> Vec3 {
+    ///     Vec3 { x, y: x + 1, z: x + 2 }
+    /// }
+    ///
+    /// let mut func = Func::from_raw(vec3 as *const fn());
+    /// func.set_return(RetLayout::Aggregate { size: 24, align: 8 });
+    /// func.push(1i64);
+    /// # if Convention::Cdecl.is_supported() {
+    /// unsafe { func.try_call(Convention::Cdecl).unwrap() };
+    /// assert_eq!(func.read_ret::<Vec3>(), Vec3 { x: 1, y: 2, z: 3 });
+    /// # }
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// 已经声明过结构体返回值, 或 align 不是 2 的幂时 panic
+    pub fn set_return(&mut self, layout: RetLayout) {
+        let (size, align, floats) = match layout {
+            RetLayout::Scalar(kind) => return self.set_ret_kind(kind),
+            RetLayout::Aggregate { size, align } => (size, align, None),
+            RetLayout::FloatAggregate { count, double } => {
+                assert!(count > 0, "floating-point aggregate has no members");
+                (count * float_size(double), float_size(double), Some(double))
+            }
+        };
+        assert!(self.aggregate.is_none(), "return layout already set");
+        assert!(
+            align.is_power_of_two(),
+            "alignment {} is not a power of two",
+            align
+        );
+        let in_memory = layout.in_memory();
+        let mut buf = None;
+        // 通过寄存器返回的浮点结构体可能超过 `ret` 中的两个字, 同样在调用后放入缓冲区
+        if in_memory || floats.is_some() {
+            buf = Some(self.owned.len());
+            let ptr = self.own_aligned(size, align).as_ptr();
+            if in_memory && HIDDEN_ARG {
+                self.insert_front(ptr);
+            }
+        }
+        self.aggregate = Some(RetBuf {
+            size,
+            buf,
+            in_memory,
+            floats,
+        });
+    }
+
+    /// 通过 `set_return` 声明的结构体返回值是否写入内存, 未声明时为 `false`
+    pub fn returns_in_memory(&self) -> bool {
+        matches!(
+            self.aggregate,
+            Some(RetBuf {
+                in_memory: true,
+                ..
+            })
+        )
+    }
+
+    /// 手写汇编与 JIT 的后端调用后, 将通过寄存器返回的浮点结构体从 `ret` 写入缓冲区
+    ///
+    /// 64 位 Linux 下在 xmm0 中, LoongArch64 下只有一个成员时在 fa0 中, 其余情况下在整数寄存器中
+    pub(crate) fn store_float_ret(&mut self) {
+        let (ret, double) = match self.aggregate {
+            Some(
+                ret @ RetBuf {
+                    in_memory: false,
+                    floats: Some(double),
+                    ..
+                },
+            ) => (ret, double),
+            _ => return,
+        };
+        let single = self.ret.float.to_bits().to_ne_bytes();
+        let words = [self.ret.low.to_ne_bytes(), self.ret.high.to_ne_bytes()].concat();
+        let bytes = if cfg!(all(target_arch = "x86_64", not(windows)))
+            || (cfg!(target_arch = "loongarch64") && ret.size == float_size(double))
+        {
+            &single[..]
+        } else {
+            &words[..]
+        };
+        if let Some(buf) = ret.ptr(self) {
+            let len = ret.size.min(bytes.len());
+            unsafe { ptr::copy_nonoverlapping(bytes.as_ptr(), buf, len) };
+        }
+    }
+
+    /// 读出上一次调用返回的结构体, T 应与 `set_return` 声明的结构体相同
+    ///
+    /// 通过整数寄存器返回的结构体从 `ret` 的低位与高位中按小端序取出.
+    /// 浮点结构体在调用后已从浮点寄存器中取出, 但需要通过 `try_call` 等方法调用,
+    /// 不支持的后端由 `try_call` 报告 `CallError::UnsupportedReturn`
+    ///
+    /// # Panics
+    ///
+    /// 未通过 `set_return` 声明结构体返回值, 或 T 的大小与声明的不同时 panic
+    pub fn read_ret<T: Copy>(&self) -> T {
+        let aggregate = self
+            .aggregate
+            .expect("no aggregate return layout was declared");
+        assert_eq!(
+            mem::size_of::<T>(),
+            aggregate.size,
+            "size of the declared return layout differs"
+        );
+        match aggregate.buf {
+            Some(index) => unsafe {
+                ptr::read_unaligned(
+                    self.owned[index].as_ptr().add(self.owned_start(index)) as *const T
+                )
+            },
+            None => {
+                let words = [self.ret.low, self.ret.high];
+                assert!(aggregate.size <= mem::size_of_val(&words));
+                unsafe { ptr::read_unaligned(words.as_ptr() as *const T) }
+            }
+        }
+    }
+}
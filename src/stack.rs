@@ -0,0 +1,49 @@
+//! 在指定栈大小的线程中发出的调用, 参见 `Func::call_on_thread`
+
+use std::panic;
+use std::thread;
+
+use crate::{CallError, Convention, Func};
+
+impl Func {
+    /// 在一个栈大小为 stack_size 字节的新线程中调用函数, 调用结束后才返回
+    ///
+    /// 用于需要比当前线程更大的栈的函数, 如递归很深的解析器. 调用前的检查与 `try_call` 相同,
+    /// 返回值会写回本 `Func`. 每次调用都会创建新的线程, 调用期间参数由该线程借用.
+    /// 被调用者中的 panic 会在当前线程中继续传播
+    ///
+    /// ```
+    /// use funcall::{Convention, Func};
+    /// extern "C" fn add(a: i32, b: i32) -> i32 {
+    ///     a + b
+    /// }
+    ///
+    /// let mut func = Func::from_raw(add as *const fn());
+    /// func.push(1i32).push(2i32);
+    /// unsafe { func.call_on_thread(Convention::Cdecl, 16 << 20).unwrap() };
+    /// assert_eq!(func.ret_as_i32(), 3);
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// 无法创建线程时 panic
+    ///
+    /// # Safety
+    ///
+    /// 同 `try_call`. 函数会在另一个线程中执行, 依赖线程局部状态的函数可能出错
+    pub unsafe fn call_on_thread(
+        &mut self,
+        conv: Convention,
+        stack_size: usize,
+    ) -> Result<(), CallError> {
+        thread::scope(|scope| {
+            thread::Builder::new()
+                .name("funcall".to_owned())
+                .stack_size(stack_size)
+                .spawn_scoped(scope, || unsafe { self.try_call(conv) })
+                .expect("failed to spawn a thread for the call")
+                .join()
+                .unwrap_or_else(|e| panic::resume_unwind(e))
+        })
+    }
+}
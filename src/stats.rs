@@ -0,0 +1,111 @@
+//! 每个 `Func` 的调用次数与耗时, 参见 `Func::set_timing`
+
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::Func;
+
+/// 调用次数与耗时的统计, 耗时只包括调用本身, 不含调用前的检查与观察者
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Hash)]
+pub struct CallStats {
+    /// 调用次数
+    pub count: u64,
+    /// 总耗时
+    pub total: Duration,
+    /// 单次调用的最大耗时
+    pub max: Duration,
+}
+
+impl CallStats {
+    /// 平均耗时, 没有调用过时为 0
+    pub fn mean(&self) -> Duration {
+        match self.count {
+            0 => Duration::ZERO,
+            n => self.total / u32::try_from(n).unwrap_or(u32::MAX),
+        }
+    }
+
+    fn record(&mut self, elapsed: Duration) {
+        self.count += 1;
+        self.total += elapsed;
+        self.max = self.max.max(elapsed);
+    }
+}
+
+/// 以符号名为键汇总所有开启了计时的 `Func`, 没有符号名时以函数地址为键
+static GLOBAL: Mutex<BTreeMap<String, CallStats>> = Mutex::new(BTreeMap::new());
+
+/// 所有开启了计时的 `Func` 按符号名汇总的统计, 按符号名排序
+///
+/// 没有符号名的函数以 `0x` 开头的地址为键
+pub fn call_stats() -> Vec<(String, CallStats)> {
+    let global = GLOBAL.lock().unwrap();
+    global.iter().map(|(k, v)| (k.clone(), *v)).collect()
+}
+
+/// 清空 `call_stats` 的汇总, 各个 `Func` 自身的统计不受影响
+pub fn reset_call_stats() {
+    GLOBAL.lock().unwrap().clear();
+}
+
+impl Func {
+    /// 开启或关闭对本函数每次调用的计时, 默认关闭
+    ///
+    /// 开启后每次调用都会读取两次单调时钟, 结果记录在本 `Func` 中并汇总到 `funcall::call_stats`;
+    /// 关闭时只多一次分支. 重复开启不会清空已有的统计, 关闭则会
+    ///
+    /// ```
+    /// use funcall::{Convention, Func};
+    /// extern "C" fn add(a: i32, b: i32) -> i32 {
+    ///     a + b
+    /// }
+    ///
+    /// let mut func = Func::from_raw(add as *const fn());
+    /// func.set_timing(true);
+    /// func.push(1i32).push(2i32);
+    /// # if Convention::Cdecl.is_supported() {
+    /// unsafe { func.try_call(Convention::Cdecl).unwrap() };
+    /// assert_eq!(func.stats().count, 1);
+    /// # }
+    /// ```
+    pub fn set_timing(&mut self, enabled: bool) {
+        match (enabled, self.stats) {
+            (true, None) => self.stats = Some(CallStats::default()),
+            (false, _) => self.stats = None,
+            _ => {}
+        }
+    }
+
+    /// 本函数的调用统计, 未开启计时时全为 0
+    pub fn stats(&self) -> CallStats {
+        self.stats.unwrap_or_default()
+    }
+
+    /// 清空本函数的调用统计, 计时保持开启
+    pub fn reset_stats(&mut self) {
+        if let Some(stats) = &mut self.stats {
+            *stats = CallStats::default();
+        }
+    }
+}
+
+/// 记录一次耗时为 elapsed 的调用, 未开启计时时什么也不做
+pub(crate) fn record(func: &mut Func, elapsed: Duration) {
+    let stats = match &mut func.stats {
+        Some(stats) => stats,
+        None => return,
+    };
+    stats.record(elapsed);
+    let key = match &func.symbol {
+        Some(symbol) => symbol.clone(),
+        None => format!("{:#x}", func.func as usize),
+    };
+    GLOBAL
+        .lock()
+        .unwrap()
+        .entry(key)
+        .or_default()
+        .record(elapsed);
+}
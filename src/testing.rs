@@ -0,0 +1,440 @@
+//! 对照直接调用检查 `Func` 的随机测试
+//!
+//! 由宏生成一族签名各不相同的 `extern "C"` 参考函数, 它们都按位置将所有参数混合为一个 u64 返回.
+//! 对每个随机生成的参数列表, 分别在 Rust 中直接调用参考函数与通过 `Func::push` 压入参数后调用,
+//! 两者的返回值必须相同. 参数与值都由种子决定, 出错时可以通过 `check_case` 单独重现
+//!
+//! 签名中既有参数全部通过寄存器传递的, 也有需要栈上参数的, `Backend::Asm` 的快速路径与通用路径都会被检查到
+//!
+//! ```
+//! use funcall::testing;
+//!
+//! testing::check(2233, 1000, funcall::Backend::Asm).unwrap();
+//! ```
+
+use alloc::vec::Vec;
+use core::error::Error;
+use core::fmt;
+
+use crate::{ArgKind, Backend, Convention, Func};
+
+/// 参考函数参数的类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Scalar {
+    I8,
+    U16,
+    I32,
+    U32,
+    I64,
+    U64,
+    I128,
+    U128,
+    /// 以 C float 传递
+    F32,
+    F64,
+    Ptr,
+}
+
+/// 一个参数的值
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Value {
+    I8(i8),
+    U16(u16),
+    I32(i32),
+    U32(u32),
+    I64(i64),
+    U64(u64),
+    I128(i128),
+    U128(u128),
+    F32(f32),
+    F64(f64),
+    Ptr(usize),
+}
+
+impl Value {
+    fn push(self, func: &mut Func) {
+        match self {
+            Value::I8(v) => func.push(v),
+            Value::U16(v) => func.push(v),
+            Value::I32(v) => func.push(v),
+            Value::U32(v) => func.push(v),
+            Value::I64(v) => func.push(v),
+            Value::U64(v) => func.push(v),
+            Value::I128(v) => func.push(v),
+            Value::U128(v) => func.push(v),
+            Value::F32(v) => func.push_float(v),
+            Value::F64(v) => func.push(v),
+            Value::Ptr(v) => func.push(v as *const u8),
+        };
+    }
+}
+
+type Ptr = *const u8;
+
+/// 可以作为参考函数参数的类型
+trait Param: Copy {
+    const SCALAR: Scalar;
+
+    fn from_value(value: Value) -> Self;
+
+    /// 参数本身的位, 不含寄存器中多余的部分
+    fn bits(self) -> u128;
+}
+
+macro_rules! impl_param {
+    ($($ty:ty => $scalar:ident, $bits:expr;)*) => {
+        $(impl Param for $ty {
+            const SCALAR: Scalar = Scalar::$scalar;
+
+            fn from_value(value: Value) -> Self {
+                match value {
+                    Value::$scalar(v) => v as $ty,
+                    other => panic!("expected {:?}, got {:?}", Scalar::$scalar, other),
+                }
+            }
+
+            fn bits(self) -> u128 {
+                let bits: fn($ty) -> u128 = $bits;
+                bits(self)
+            }
+        })*
+    };
+}
+
+impl_param! {
+    i8 => I8, |v| v as u8 as u128;
+    u16 => U16, |v| v as u128;
+    i32 => I32, |v| v as u32 as u128;
+    u32 => U32, |v| v as u128;
+    i64 => I64, |v| v as u64 as u128;
+    u64 => U64, |v| v as u128;
+    i128 => I128, |v| v as u128;
+    u128 => U128, |v| v;
+    f32 => F32, |v| v.to_bits() as u128;
+    f64 => F64, |v| v.to_bits() as u128;
+    Ptr => Ptr, |v| v as usize as u128;
+}
+
+/// 按位置混合一个参数, 参数的顺序或位置错了都会得到不同的结果
+fn mix(hash: u64, bits: u128) -> u64 {
+    let hash = (hash ^ bits as u64)
+        .wrapping_mul(0x9e37_79b9_7f4a_7c15)
+        .rotate_left(29);
+    (hash ^ (bits >> 64) as u64)
+        .wrapping_mul(0xbf58_476d_1ce4_e5b9)
+        .rotate_left(31)
+}
+
+/// 一个参考函数
+#[derive(Debug, Clone, Copy)]
+pub struct Shape {
+    /// 参考函数的名字
+    pub name: &'static str,
+    /// 参数的类型
+    pub params: &'static [Scalar],
+    target: *const fn(),
+    direct: fn(&[Value]) -> u64,
+}
+
+impl Shape {
+    /// 在 Rust 中直接调用参考函数
+    pub fn call_direct(&self, args: &[Value]) -> u64 {
+        assert_eq!(args.len(), self.params.len(), "argument count mismatch");
+        (self.direct)(args)
+    }
+
+    /// 通过 `Func` 以 backend 调用参考函数
+    pub fn call_func(&self, args: &[Value], backend: Backend) -> u64 {
+        let mut func = Func::from_raw(self.target);
+        func.set_ret_kind(ArgKind::U64);
+        for arg in args {
+            arg.push(&mut func);
+        }
+        // 参考函数由本模块生成, 签名与压入的参数一致
+        unsafe { func.try_call_with(Convention::Cdecl, backend).unwrap() };
+        func.ret_as_u64()
+    }
+}
+
+macro_rules! shapes {
+    ($collect:ident; $($name:ident($($arg:ident: $ty:ty),*);)*) => {
+        $(#[allow(clippy::too_many_arguments)]
+        mod $name {
+            use super::*;
+
+            pub extern "C" fn target($($arg: $ty),*) -> u64 {
+                #[allow(unused_mut)]
+                let mut hash = 0x2233;
+                $(hash = mix(hash, Param::bits($arg));)*
+                hash
+            }
+
+            #[allow(unused_variables, unused_mut)]
+            pub fn direct(args: &[Value]) -> u64 {
+                let mut args = args.iter().copied();
+                target($(<$ty as Param>::from_value(args.next().unwrap())),*)
+            }
+        })*
+
+        fn $collect(shapes: &mut Vec<Shape>) {
+            $(shapes.push(Shape {
+                name: stringify!($name),
+                params: &[$(<$ty as Param>::SCALAR),*],
+                target: $name::target as *const fn(),
+                direct: $name::direct,
+            });)*
+        }
+    };
+}
+
+/// 所有参考函数
+///
+/// 128 位整数只在 64 位 Linux 等非 Windows 的 x86_64 平台上参与测试
+pub fn shapes() -> Vec<Shape> {
+    let mut shapes = Vec::new();
+    common(&mut shapes);
+    #[cfg(all(target_arch = "x86_64", not(windows)))]
+    wide(&mut shapes);
+    shapes
+}
+
+/// SplitMix64, 只用于生成测试数据
+#[derive(Debug, Clone)]
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// 随机的 128 位整数, 偏向于 0, -1 与各类型的边界值
+    fn bits(&mut self) -> u128 {
+        let bits = (u128::from(self.next_u64()) << 64) | u128::from(self.next_u64());
+        match self.next_u64() % 8 {
+            0 => 0,
+            1 => u128::MAX,
+            2 => 1 << (self.next_u64() % 128),
+            3 => (1 << (self.next_u64() % 128)) - 1,
+            _ => bits,
+        }
+    }
+
+    /// 随机的浮点数, 不含 signaling NaN, 以免在传递中被转为 quiet NaN
+    fn float(&mut self) -> f64 {
+        const SPECIAL: [f64; 8] = [
+            0.0,
+            -0.0,
+            1.0,
+            f64::INFINITY,
+            f64::NEG_INFINITY,
+            f64::NAN,
+            f64::MIN_POSITIVE,
+            f64::MAX,
+        ];
+        match self.next_u64() % 4 {
+            0 => SPECIAL[(self.next_u64() % 8) as usize],
+            1 => f64::from_bits(self.next_u64() & 0x800f_ffff_ffff_ffff),
+            _ => (self.next_u64() as i64 as f64) / (self.next_u64() % 1000 + 1) as f64,
+        }
+    }
+
+    /// 随机的 scalar 类型的值
+    pub fn value(&mut self, scalar: Scalar) -> Value {
+        let bits = self.bits();
+        match scalar {
+            Scalar::I8 => Value::I8(bits as i8),
+            Scalar::U16 => Value::U16(bits as u16),
+            Scalar::I32 => Value::I32(bits as i32),
+            Scalar::U32 => Value::U32(bits as u32),
+            Scalar::I64 => Value::I64(bits as i64),
+            Scalar::U64 => Value::U64(bits as u64),
+            Scalar::I128 => Value::I128(bits as i128),
+            Scalar::U128 => Value::U128(bits),
+            Scalar::F32 => Value::F32(self.float() as f32),
+            Scalar::F64 => Value::F64(self.float()),
+            Scalar::Ptr => Value::Ptr(bits as usize),
+        }
+    }
+}
+
+/// 直接调用与通过 `Func` 调用的结果不同
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mismatch {
+    /// 重现这一用例的种子, 参见 `check_case`
+    pub seed: u64,
+    pub shape: &'static str,
+    pub args: Vec<Value>,
+    /// 直接调用的结果
+    pub expected: u64,
+    /// 通过 `Func` 调用的结果
+    pub got: u64,
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} with {:?} returned {:#x} through Func, expected {:#x} (seed {:#x})",
+            self.shape, self.args, self.got, self.expected, self.seed
+        )
+    }
+}
+
+impl Error for Mismatch {}
+
+/// 以 seed 随机选取一个参考函数与参数, 比较直接调用与通过 `Func` 以 backend 调用的结果
+pub fn check_case(seed: u64, backend: Backend) -> Result<(), Mismatch> {
+    let shapes = shapes();
+    check_with(&shapes, seed, backend)
+}
+
+fn check_with(shapes: &[Shape], seed: u64, backend: Backend) -> Result<(), Mismatch> {
+    let mut rng = Rng::new(seed);
+    let shape = shapes[(rng.next_u64() % shapes.len() as u64) as usize];
+    let args = shape
+        .params
+        .iter()
+        .map(|&scalar| rng.value(scalar))
+        .collect::<Vec<_>>();
+    let expected = shape.call_direct(&args);
+    let got = shape.call_func(&args, backend);
+    if expected == got {
+        Ok(())
+    } else {
+        Err(Mismatch {
+            seed,
+            shape: shape.name,
+            args,
+            expected,
+            got,
+        })
+    }
+}
+
+/// 由 seed 派生出 cases 个用例依次检查, 返回第一个不符的用例
+pub fn check(seed: u64, cases: usize, backend: Backend) -> Result<(), Mismatch> {
+    let shapes = shapes();
+    let mut rng = Rng::new(seed);
+    (0..cases).try_for_each(|_| check_with(&shapes, rng.next_u64(), backend))
+}
+
+shapes! {
+    common;
+    s0();
+    s1(a0: i8);
+    s2(a0: u16);
+    s3(a0: i32);
+    s4(a0: u32);
+    s5(a0: i64);
+    s6(a0: u64);
+    s7(a0: f32);
+    s8(a0: f64);
+    s9(a0: Ptr);
+    s10(a0: i32, a1: i32, a2: i32, a3: i32, a4: i32, a5: i32, a6: i32, a7: i32, a8: i32, a9: i32, a10: i32, a11: i32, a12: i32, a13: i32, a14: i32, a15: i32);
+    s11(a0: i64, a1: i64, a2: i64, a3: i64, a4: i64, a5: i64, a6: i64, a7: i64, a8: i64, a9: i64, a10: i64, a11: i64, a12: i64, a13: i64, a14: i64, a15: i64);
+    s12(a0: f32, a1: f32, a2: f32, a3: f32, a4: f32, a5: f32, a6: f32, a7: f32, a8: f32, a9: f32, a10: f32, a11: f32, a12: f32, a13: f32, a14: f32, a15: f32);
+    s13(a0: f64, a1: f64, a2: f64, a3: f64, a4: f64, a5: f64, a6: f64, a7: f64, a8: f64, a9: f64, a10: f64, a11: f64, a12: f64, a13: f64, a14: f64, a15: f64);
+    s14(a0: f64, a1: u32, a2: f64, a3: u32, a4: u64, a5: u32, a6: Ptr, a7: u16, a8: i64, a9: u32, a10: i64, a11: i32, a12: i8, a13: u16);
+    s15(a0: f64, a1: u16, a2: u64, a3: u64);
+    s16(a0: u16, a1: i8, a2: u16, a3: i8, a4: i32, a5: i32, a6: f32, a7: u32, a8: u32, a9: Ptr);
+    s17(a0: i8, a1: u32, a2: i64, a3: u16, a4: u16, a5: i64);
+    s18(a0: i8, a1: i32, a2: u32, a3: u16, a4: u64, a5: Ptr, a6: f32, a7: Ptr, a8: i32, a9: f64, a10: Ptr);
+    s19(a0: i8);
+    s20(a0: u64, a1: Ptr);
+    s21(a0: u32, a1: i8, a2: f32, a3: u32);
+    s22(a0: u64, a1: Ptr, a2: i64, a3: u16, a4: f64, a5: f32, a6: i8, a7: f32, a8: f32, a9: u32, a10: f64, a11: i32);
+    s23(a0: Ptr, a1: u32, a2: i32, a3: u64, a4: i64, a5: i8, a6: u32, a7: i64, a8: i64, a9: i64);
+    s24(a0: i32, a1: u32, a2: Ptr, a3: f32, a4: u16, a5: i8, a6: i32, a7: i64, a8: i64, a9: u64);
+    s25(a0: i8, a1: i64, a2: u64, a3: f64, a4: i64, a5: Ptr, a6: f32, a7: u64, a8: Ptr, a9: u64, a10: u16, a11: u16, a12: u32, a13: f32, a14: u32, a15: u32);
+    s26(a0: i64);
+    s27(a0: i64, a1: u16, a2: f64, a3: i32, a4: u32, a5: Ptr, a6: i8, a7: i64, a8: u32, a9: Ptr, a10: f32, a11: u16, a12: f64, a13: f64, a14: Ptr);
+    s28(a0: i32, a1: i32, a2: u16, a3: i32, a4: Ptr, a5: f32, a6: i64, a7: u16, a8: f32, a9: f64, a10: i32, a11: u64);
+    s29(a0: i32, a1: f64, a2: u32, a3: u32, a4: Ptr, a5: u16, a6: u64);
+    s30(a0: Ptr, a1: i64, a2: u32, a3: i32, a4: Ptr);
+    s31(a0: u32, a1: i8, a2: u32, a3: f32, a4: u16, a5: i64, a6: u16, a7: u16, a8: u32, a9: u16, a10: Ptr, a11: f64, a12: Ptr);
+    s32(a0: u16, a1: i64, a2: i8, a3: u16);
+    s33(a0: f32, a1: Ptr);
+    s34(a0: Ptr, a1: u32, a2: i64, a3: i8, a4: Ptr, a5: u16, a6: f32, a7: u32, a8: f64, a9: i64, a10: u64, a11: f64, a12: u32);
+    s35();
+    s36();
+    s37(a0: i8, a1: Ptr, a2: Ptr, a3: u16, a4: f32, a5: i64, a6: f32, a7: u32, a8: u16, a9: u64, a10: i64, a11: i32, a12: f64, a13: f32, a14: u32, a15: u16);
+    s38(a0: i64, a1: u16, a2: u16, a3: f32, a4: u64, a5: i64, a6: i64, a7: f64, a8: f64, a9: u32, a10: i32, a11: f32, a12: Ptr, a13: f32, a14: i8, a15: f32);
+    s39(a0: f64, a1: f32, a2: f64, a3: u64, a4: i8, a5: Ptr, a6: f64, a7: u16, a8: i8);
+    s40(a0: i64, a1: f64, a2: i64, a3: u32, a4: u64, a5: u32, a6: f32);
+    s41(a0: u64, a1: u16, a2: u16, a3: i8, a4: f64, a5: f64, a6: i8, a7: i64, a8: u64, a9: i8, a10: Ptr, a11: f32, a12: u64, a13: u16, a14: i64, a15: f32);
+    s42(a0: u32, a1: u16, a2: Ptr, a3: f32);
+    s43(a0: f64, a1: u16, a2: i64, a3: i32, a4: f64, a5: u64, a6: i64, a7: f64, a8: i64, a9: u64, a10: u32);
+    s44(a0: f64, a1: f64, a2: i8, a3: i32, a4: i64, a5: i64, a6: Ptr, a7: i8, a8: f64, a9: u64, a10: i32);
+    s45(a0: f64, a1: u64, a2: f64, a3: i8, a4: i32);
+    s46(a0: f64, a1: f32, a2: i8, a3: i8, a4: u64, a5: f32, a6: f32, a7: Ptr, a8: u64, a9: u64, a10: i32, a11: Ptr, a12: i64);
+    s47(a0: f64, a1: u32, a2: Ptr);
+    s48(a0: u32);
+    s49(a0: f32, a1: f64, a2: u16, a3: f32, a4: u64, a5: i8, a6: i32, a7: u32, a8: i32, a9: i32, a10: i64, a11: u64, a12: i8, a13: i8, a14: i64, a15: u64);
+    s50(a0: u32, a1: u32, a2: u16);
+    s51(a0: u16, a1: i64, a2: i8, a3: i64, a4: u64, a5: Ptr);
+    s52(a0: i8, a1: f64, a2: u64, a3: u16, a4: f32, a5: i32, a6: i64, a7: i32, a8: i32, a9: u16, a10: f64, a11: f64, a12: Ptr, a13: u16, a14: Ptr);
+    s53(a0: i64, a1: u64, a2: f64, a3: i8, a4: f64, a5: Ptr, a6: f32, a7: f64, a8: u32, a9: f64, a10: u16, a11: f64, a12: f32);
+    s54();
+    s55(a0: u32, a1: i64, a2: u64, a3: u16, a4: i32);
+    s56(a0: Ptr, a1: i64, a2: u64, a3: u16, a4: f32, a5: f64, a6: u32, a7: u16, a8: u16, a9: i32, a10: i64, a11: i64, a12: i8, a13: u16, a14: f64, a15: u32);
+    s57(a0: u32, a1: Ptr, a2: u32, a3: i32, a4: f32, a5: i8, a6: i32, a7: i8, a8: u64, a9: f64, a10: i64);
+    s58();
+    s59(a0: f32, a1: i64, a2: u64, a3: u16, a4: f64, a5: i32, a6: f32);
+    s60(a0: u64, a1: u16, a2: u16);
+    s61(a0: Ptr, a1: i32, a2: i8, a3: u32, a4: u64, a5: u16);
+    s62(a0: i64, a1: i8, a2: u16, a3: Ptr, a4: f32, a5: Ptr, a6: u32, a7: i8, a8: u32, a9: Ptr, a10: i32, a11: u32, a12: u64, a13: Ptr, a14: f32);
+    s63(a0: f32, a1: f64, a2: Ptr, a3: u32, a4: u32, a5: u64, a6: u32, a7: f64, a8: f32, a9: Ptr, a10: f64, a11: Ptr);
+    s64(a0: u32, a1: i64, a2: Ptr, a3: i64, a4: f64, a5: u16, a6: u16, a7: f32, a8: Ptr, a9: Ptr);
+    s65(a0: i32, a1: Ptr, a2: i64, a3: i8, a4: i64, a5: f64, a6: i32, a7: i32, a8: u64, a9: i32, a10: i8, a11: Ptr, a12: Ptr, a13: i8, a14: f64);
+    s66(a0: u64, a1: Ptr, a2: i32, a3: i64, a4: i8, a5: i32, a6: u64, a7: i32, a8: u32, a9: f64, a10: i64);
+    s67(a0: u32, a1: i32, a2: u32, a3: Ptr, a4: i8, a5: i64, a6: Ptr, a7: u64, a8: f64, a9: f32, a10: i8, a11: u32);
+    s68(a0: i32, a1: f64, a2: i64, a3: f32, a4: Ptr, a5: i8, a6: u16, a7: i32, a8: f32, a9: i32, a10: i8, a11: u16, a12: f32, a13: i32);
+    s69(a0: i64, a1: f64, a2: Ptr, a3: f32, a4: f64, a5: i8, a6: Ptr);
+    s70(a0: i64, a1: u64, a2: Ptr, a3: u64, a4: f32, a5: i32, a6: u64, a7: i8, a8: i64, a9: Ptr, a10: u64, a11: u16, a12: u64, a13: u16);
+    s71(a0: u64, a1: f32);
+    s72(a0: i64, a1: u16, a2: u32, a3: i8, a4: f32);
+    s73(a0: Ptr, a1: i64, a2: Ptr, a3: Ptr, a4: f32, a5: i8, a6: Ptr, a7: Ptr, a8: i32, a9: u64, a10: u16, a11: Ptr, a12: i32);
+    s74();
+    s75(a0: i64, a1: i32, a2: Ptr, a3: f64, a4: f64, a5: i8, a6: u16);
+    s76(a0: Ptr, a1: i32, a2: f32, a3: Ptr, a4: i8, a5: i64, a6: u16, a7: f64, a8: i64, a9: f64, a10: i8, a11: i8, a12: i8, a13: f64, a14: u32, a15: u32);
+    s77(a0: f64, a1: i32, a2: i32, a3: f64, a4: i32, a5: f32, a6: f32, a7: i8, a8: u64, a9: f64, a10: u32, a11: Ptr, a12: f32, a13: u16, a14: i64);
+    s78(a0: f32, a1: i32, a2: i64, a3: u64, a4: u64, a5: i8, a6: f32, a7: u64, a8: i32, a9: f64, a10: u16, a11: u16, a12: i32, a13: f32, a14: f64);
+    s79(a0: i32, a1: u16, a2: u32, a3: u16, a4: i64, a5: f64);
+}
+
+#[cfg(all(target_arch = "x86_64", not(windows)))]
+shapes! {
+    wide;
+    w0(a0: i128, a1: i32);
+    w1(a0: f64, a1: u128, a2: u128, a3: i128, a4: u128, a5: f64, a6: f64, a7: u128, a8: u128, a9: i128, a10: u32);
+    w2(a0: u128, a1: u128, a2: f32, a3: u64, a4: f32, a5: i128, a6: i32, a7: u128, a8: u64, a9: i128);
+    w3(a0: i128, a1: u128, a2: f64, a3: i128, a4: u128);
+    w4(a0: u16, a1: u32, a2: f64, a3: i128, a4: f64, a5: i128, a6: u16, a7: Ptr, a8: i128, a9: Ptr, a10: u64, a11: i128, a12: f32, a13: f64);
+    w5(a0: u32, a1: u128, a2: i128, a3: i32);
+    w6(a0: u128, a1: u16);
+    w7(a0: f32, a1: u128, a2: Ptr, a3: i128, a4: i32, a5: i128, a6: u16, a7: u64, a8: u128, a9: u64, a10: i128, a11: u32, a12: u128);
+    w8(a0: u32, a1: u16, a2: u16, a3: u64, a4: f64, a5: f32, a6: i128, a7: u32, a8: i128, a9: Ptr, a10: u64, a11: i128);
+    w9(a0: i128, a1: Ptr, a2: u128, a3: u128, a4: Ptr, a5: i32);
+    w10(a0: f64, a1: i128);
+    w11(a0: f32, a1: i32, a2: u128, a3: u128, a4: u128, a5: u128, a6: f64, a7: i128);
+    w12(a0: f64, a1: i128, a2: i128, a3: u128, a4: i128, a5: u32, a6: i128, a7: u128, a8: u16, a9: i128);
+    w13(a0: i8, a1: i64, a2: i128, a3: i128, a4: f64, a5: i128, a6: i128, a7: i128);
+    w14(a0: i8, a1: i128, a2: u16, a3: u32, a4: Ptr, a5: u128, a6: i128, a7: i128, a8: Ptr, a9: u32, a10: f64);
+    w15(a0: u128, a1: f32);
+    w16(a0: u128, a1: u128, a2: i32);
+    w17(a0: i32, a1: i8, a2: u128, a3: i64, a4: u128, a5: u32, a6: i128, a7: f64, a8: i64, a9: i8, a10: i128, a11: u128, a12: u128, a13: i128, a14: u128);
+    w18(a0: Ptr, a1: u128, a2: f64, a3: i32, a4: i8, a5: i8, a6: f32, a7: i64, a8: u64, a9: u128, a10: Ptr, a11: u16, a12: u16, a13: u128, a14: i64, a15: u64);
+    w19(a0: i32, a1: f32, a2: i8, a3: i32, a4: u32, a5: i128, a6: i64, a7: i64, a8: u64);
+    w20(a0: u64, a1: i64, a2: i32, a3: f32, a4: i64, a5: u128, a6: u64);
+    w21(a0: i64, a1: i8, a2: u128, a3: Ptr, a4: i128, a5: u128, a6: f64, a7: i128, a8: u128, a9: i32, a10: f32, a11: u16, a12: u32);
+    w22(a0: u64, a1: u32, a2: u128, a3: u128, a4: u32, a5: u64, a6: u128, a7: u128, a8: i128, a9: u64, a10: u32, a11: u32, a12: f32);
+    w23(a0: i64, a1: f64, a2: i64, a3: i128, a4: i8, a5: u32, a6: u128, a7: u128, a8: u64, a9: f64, a10: u128, a11: i64, a12: i8);
+}
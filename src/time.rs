@@ -0,0 +1,158 @@
+//! `timespec` 与 `timeval` 参数
+//!
+//! 两个结构体的字段宽度随平台而不同, 这里按目标平台的 C 库构造它们,
+//! 如 32 位 glibc 下 `time_t` 只有 32 位, Windows 下 `timeval` 的字段都是 `long`
+
+use alloc::vec::Vec;
+use core::ffi::c_void;
+use core::mem::{offset_of, size_of};
+use core::ptr;
+use core::time::Duration;
+
+use crate::{Arg, Func};
+
+// Windows 下的 `time_t` 为 64 位, 而 winsock 的 `timeval` 使用 `long`
+#[cfg(windows)]
+mod types {
+    pub type Sec = i64;
+    pub type Nsec = core::ffi::c_long;
+    pub type TvSec = core::ffi::c_long;
+    pub type TvUsec = core::ffi::c_long;
+}
+
+// Apple 平台上的 `time_t` 为 `long`, `suseconds_t` 始终为 32 位
+#[cfg(target_vendor = "apple")]
+mod types {
+    pub type Sec = core::ffi::c_long;
+    pub type Nsec = core::ffi::c_long;
+    pub type TvSec = core::ffi::c_long;
+    pub type TvUsec = i32;
+}
+
+// 其他平台上的 `time_t`, `tv_nsec` 与 `suseconds_t` 宽度相同.
+// 32 位的 glibc 与 bionic 中为 32 位; musl 1.2 起在所有平台上都为 64 位,
+// 32 位小端平台上 `tv_nsec` 之后的填充恰好作为其高位, x32 下则都是 `long long`
+#[cfg(not(any(windows, target_vendor = "apple")))]
+mod types {
+    #[cfg(all(
+        target_pointer_width = "32",
+        not(target_arch = "x86_64"),
+        not(target_env = "musl")
+    ))]
+    pub type Sec = i32;
+    #[cfg(not(all(
+        target_pointer_width = "32",
+        not(target_arch = "x86_64"),
+        not(target_env = "musl")
+    )))]
+    pub type Sec = i64;
+    pub type Nsec = Sec;
+    pub type TvSec = Sec;
+    pub type TvUsec = Sec;
+}
+
+#[repr(C)]
+struct Timespec {
+    tv_sec: types::Sec,
+    tv_nsec: types::Nsec,
+}
+
+#[repr(C)]
+struct Timeval {
+    tv_sec: types::TvSec,
+    tv_usec: types::TvUsec,
+}
+
+/// 按字段的偏移写入, 结构体中的填充保持为 0
+fn to_bytes<S, F>(size: usize, (sec, sec_at): (S, usize), (frac, frac_at): (F, usize)) -> Vec<u8> {
+    let mut bytes = vec![0u8; size];
+    unsafe {
+        ptr::write_unaligned(bytes.as_mut_ptr().add(sec_at) as *mut S, sec);
+        ptr::write_unaligned(bytes.as_mut_ptr().add(frac_at) as *mut F, frac);
+    }
+    bytes
+}
+
+/// 超出 `time_t` 范围的秒数取最大值
+macro_rules! secs {
+    ($d:expr, $ty:ty) => {
+        $d.as_secs().min(<$ty>::MAX as u64) as $ty
+    };
+}
+
+/// 秒数为负时取 0. 被调用者写入的小数部分可能超出范围, 换算为纳秒时不会溢出
+fn duration(sec: i64, frac: i64, unit: u32) -> Duration {
+    if sec < 0 {
+        return Duration::ZERO;
+    }
+    let nanos = (frac.max(0) as u64).saturating_mul(u64::from(unit));
+    Duration::from_secs(sec as u64).saturating_add(Duration::from_nanos(nanos))
+}
+
+impl Func {
+    /// 以 d 构造 `struct timespec`, 压入指向它的指针
+    ///
+    /// 结构体由 `Func` 持有, 与 `Arg::Bytes` 相同; 也可以用作输出参数, 调用后通过 `time::read_timespec` 读取.
+    /// 超出 `time_t` 范围的秒数会被截断为最大值
+    ///
+    /// ```
+    /// use funcall::{Convention, Func};
+    /// use std::time::Duration;
+    ///
+    /// # #[cfg(all(feature = "loader", target_os = "linux"))]
+    /// # {
+    /// let mut func = Func::new("libc.so.6", b"nanosleep\0").unwrap();
+    /// func.push_timespec(Duration::from_millis(1)).push(0usize);
+    /// unsafe { func.try_call(Convention::Cdecl) }.unwrap();
+    /// assert_eq!(func.ret_as_i32(), 0);
+    /// # }
+    /// ```
+    #[cfg_attr(feature = "provenance", track_caller)]
+    pub fn push_timespec(&mut self, d: Duration) -> &mut Self {
+        let bytes = to_bytes(
+            size_of::<Timespec>(),
+            (secs!(d, types::Sec), offset_of!(Timespec, tv_sec)),
+            (
+                d.subsec_nanos() as types::Nsec,
+                offset_of!(Timespec, tv_nsec),
+            ),
+        );
+        self.push_arg(Arg::Bytes(bytes))
+    }
+
+    /// 以 d 构造 `struct timeval`, 压入指向它的指针, 不足一微秒的部分被舍去
+    ///
+    /// 同 `push_timespec`, 调用后可以通过 `time::read_timeval` 读取
+    #[cfg_attr(feature = "provenance", track_caller)]
+    pub fn push_timeval(&mut self, d: Duration) -> &mut Self {
+        let bytes = to_bytes(
+            size_of::<Timeval>(),
+            (secs!(d, types::TvSec), offset_of!(Timeval, tv_sec)),
+            (
+                d.subsec_micros() as types::TvUsec,
+                offset_of!(Timeval, tv_usec),
+            ),
+        );
+        self.push_arg(Arg::Bytes(bytes))
+    }
+}
+
+/// 读取 ptr 指向的 `struct timespec`, 秒数为负时返回 0
+///
+/// # Safety
+///
+/// ptr 必须指向一个可读的 `struct timespec`, 不要求对齐
+pub unsafe fn read_timespec(ptr: *const c_void) -> Duration {
+    let ts = ptr::read_unaligned(ptr as *const Timespec);
+    duration(ts.tv_sec as i64, ts.tv_nsec as i64, 1)
+}
+
+/// 读取 ptr 指向的 `struct timeval`, 秒数为负时返回 0
+///
+/// # Safety
+///
+/// ptr 必须指向一个可读的 `struct timeval`, 不要求对齐
+pub unsafe fn read_timeval(ptr: *const c_void) -> Duration {
+    let tv = ptr::read_unaligned(ptr as *const Timeval);
+    duration(tv.tv_sec as i64, tv.tv_usec as i64, 1000)
+}
@@ -0,0 +1,193 @@
+//! 带超时的调用, 参见 `Func::call_with_timeout`
+
+use std::cmp::Ordering;
+use std::error::Error;
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, Ordering as AtomicOrdering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::{Backend, CallError, Convention, Func, RetValues};
+
+/// `Func::call_with_timeout` 在限定时间内完成的调用
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub struct CallOutcome {
+    /// 调用的返回值, 同时也会写回 `Func`
+    pub ret: RetValues,
+    /// 调用实际花费的时间
+    pub elapsed: Duration,
+}
+
+/// `Func::call_with_timeout` 的调用没能在限定时间内返回
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd)]
+pub struct Timeout {
+    /// 限定的时间
+    pub timeout: Duration,
+}
+
+impl fmt::Display for Timeout {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "the call did not return within {:?}", self.timeout)
+    }
+}
+
+impl Error for Timeout {}
+
+/// `Func::call_with_timeout` 没能完成调用
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum TimeoutError {
+    /// 调用前的检查没有通过, 没有创建线程
+    Call(CallError),
+    /// 调用没能在限定时间内返回
+    TimedOut(Timeout),
+}
+
+impl fmt::Display for TimeoutError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TimeoutError::Call(e) => e.fmt(f),
+            TimeoutError::TimedOut(e) => e.fmt(f),
+        }
+    }
+}
+
+impl Error for TimeoutError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            TimeoutError::Call(e) => Some(e),
+            TimeoutError::TimedOut(e) => Some(e),
+        }
+    }
+}
+
+impl From<CallError> for TimeoutError {
+    fn from(e: CallError) -> Self {
+        TimeoutError::Call(e)
+    }
+}
+
+impl From<Timeout> for TimeoutError {
+    fn from(e: Timeout) -> Self {
+        TimeoutError::TimedOut(e)
+    }
+}
+
+/// 超时后仍在运行的调用, 调用返回后标记被清除
+#[derive(Debug, Clone)]
+pub(crate) struct Orphan(Arc<AtomicBool>);
+
+impl Orphan {
+    pub(crate) fn running(&self) -> bool {
+        self.0.load(AtomicOrdering::Acquire)
+    }
+}
+
+/// 两个 `Orphan` 指向同一次调用时相等
+impl PartialEq for Orphan {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl PartialOrd for Orphan {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Arc::as_ptr(&self.0).partial_cmp(&Arc::as_ptr(&other.0))
+    }
+}
+
+/// 调用线程不论正常返回还是 panic, 都在退出时清除运行中的标记
+struct Finished(Arc<AtomicBool>);
+
+impl Drop for Finished {
+    fn drop(&mut self) {
+        self.0.store(false, AtomicOrdering::Release);
+    }
+}
+
+impl Func {
+    /// 在单独的线程中调用函数, 最多等待 timeout
+    ///
+    /// 调用前进行与 `try_call` 相同的检查, 未通过时返回 `TimeoutError::Call`.
+    /// 调用的线程无法被安全地终止, 超时后它会继续运行, 直到函数返回或进程退出, 其返回值会被丢弃.
+    /// 超时后本 `Func` 会被标记为中毒, 在遗留的调用返回之前 `try_call` 与本方法都会返回 `CallError::Poisoned`.
+    /// 遗留的调用持有一份参数的克隆, 通过 `push_arg` 压入的字节与字符串以及对库的引用都会保持有效,
+    /// 直到它返回为止. 调用在限定时间内 panic 时 (参见 `UnwindPolicy::Catch`), panic 会在当前线程中继续
+    ///
+    /// ```
+    /// use funcall::{Convention, Func};
+    /// use std::time::Duration;
+    /// extern "C" fn add(a: i32, b: i32) -> i32 {
+    ///     a + b
+    /// }
+    ///
+    /// let mut func = Func::from_raw(add as *const fn());
+    /// func.push(1i32).push(2i32);
+    /// let outcome = unsafe { func.call_with_timeout(Convention::Cdecl, Duration::from_secs(1)) };
+    /// assert_eq!(outcome.unwrap().ret.as_i32(), 3);
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// 无法创建线程时 panic
+    ///
+    /// # Safety
+    ///
+    /// 同 `try_call`. 函数会在另一个线程中执行, 依赖线程局部状态的函数可能出错;
+    /// 超时后指针参数指向的数据仍可能被遗留的调用访问, 需要调用者保证其一直有效
+    pub unsafe fn call_with_timeout(
+        &mut self,
+        conv: Convention,
+        timeout: Duration,
+    ) -> Result<CallOutcome, TimeoutError> {
+        let backend = conv.backend().unwrap_or(Backend::Asm);
+        self.check_call(conv, backend)?;
+        let running = Arc::new(AtomicBool::new(true));
+        // 与 self 共享缓冲区, 被调用者写入其中的内容在调用返回后仍然可见
+        let mut func = self.shallow_clone();
+        // 计时由本 `Func` 在收到结果后记录
+        func.stats = None;
+        let (tx, rx) = mpsc::channel();
+        let finished = Finished(running.clone());
+        let handle = thread::spawn(move || {
+            let called = panic::catch_unwind(AssertUnwindSafe(|| {
+                let start = Instant::now();
+                func.call_backend(conv, backend);
+                CallOutcome {
+                    ret: func.ret,
+                    elapsed: start.elapsed(),
+                }
+            }));
+            drop(finished);
+            // 超时后接收端已被 drop, 发送失败可以忽略
+            let _ = tx.send(called);
+        });
+        match rx.recv_timeout(timeout) {
+            Ok(Ok(outcome)) => {
+                crate::stats::record(self, outcome.elapsed);
+                self.ret = outcome.ret;
+                self.called = true;
+                self.orphan = None;
+                Ok(outcome)
+            }
+            Ok(Err(payload)) => panic::resume_unwind(payload),
+            Err(RecvTimeoutError::Timeout) => {
+                self.orphan = Some(Orphan(running));
+                Err(Timeout { timeout }.into())
+            }
+            // 没有发送结果就退出了, 只可能是在 catch_unwind 之外 panic
+            Err(RecvTimeoutError::Disconnected) => match handle.join() {
+                Err(payload) => panic::resume_unwind(payload),
+                Ok(()) => unreachable!("the calling thread exited without a result"),
+            },
+        }
+    }
+
+    /// 是否有超时后仍在运行的调用, 参见 `call_with_timeout`
+    pub fn is_poisoned(&self) -> bool {
+        self.orphan.as_ref().is_some_and(Orphan::running)
+    }
+}
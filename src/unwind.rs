@@ -0,0 +1,352 @@
+//! 被调用者抛出的异常与回调中的 panic 展开穿过调用帧时的处理, 参见 `UnwindPolicy`
+//!
+//! 默认的策略与 `extern "C"` 函数相同: 展开到 `try_call` 等的调用帧时进程以 abort 终止,
+//! 并输出 "panic in a function that cannot unwind", 而不会带着被破坏的栈继续执行.
+//! 64 位 Windows 与 64 位 Linux 下另有以 rbp 为帧指针并带有展开信息的汇编函数, 展开经过它时不会算错之前各帧的位置.
+//! 启用 `protected` feature 时, Windows 下可以通过 `Func::try_call_catching` 将异常转换为 `CallError::ForeignException`
+
+use core::ffi::c_void;
+
+use crate::{Backend, Func};
+
+/// 展开到达调用帧时的处理方式, 通过 `Func::set_unwind_policy` 设置
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Hash)]
+pub enum UnwindPolicy {
+    /// 以 abort 终止进程
+    #[default]
+    Abort,
+    /// 在带有展开信息的汇编函数中发出调用, 回调中的 panic 展开经过调用帧之后在边界处捕获,
+    /// 恢复浮点控制状态等之后再继续展开, 对调用者而言与普通的 panic 相同.
+    /// 回调与被调用者都必须能够展开, 如 Rust 中的 `extern "C-unwind"` 函数, 以及带有展开信息编译的 C 代码
+    ///
+    /// 只支持 64 位 Linux 与 64 位 Windows 上的 `Backend::Asm`, 其他情况下 `try_call` 等返回 `CallError::UnsupportedUnwind`
+    #[cfg(feature = "unwind")]
+    Catch,
+}
+
+impl UnwindPolicy {
+    /// 能否以 backend 按本策略发出调用
+    #[cfg_attr(not(feature = "unwind"), allow(unused_variables))]
+    pub(crate) fn supported_by(self, backend: Backend) -> bool {
+        match self {
+            UnwindPolicy::Abort => true,
+            #[cfg(feature = "unwind")]
+            UnwindPolicy::Catch => {
+                backend == Backend::Asm
+                    && cfg!(all(
+                        target_arch = "x86_64",
+                        any(target_os = "linux", windows)
+                    ))
+            }
+        }
+    }
+}
+
+impl Func {
+    /// 设置展开到达调用帧时的处理方式, 默认为 `UnwindPolicy::Abort`
+    ///
+    /// ```
+    /// # #[cfg(feature = "unwind")]
+    /// # {
+    /// use funcall::{Convention, Func, UnwindPolicy};
+    ///
+    /// extern "C-unwind" fn apply(f: extern "C-unwind" fn(i32) -> i32, x: i32) -> i32 {
+    ///     f(x)
+    /// }
+    /// extern "C-unwind" fn check(x: i32) -> i32 {
+    ///     assert!(x >= 0, "negative input");
+    ///     x
+    /// }
+    ///
+    /// let mut func = Func::from_raw(apply as *const fn());
+    /// func.set_unwind_policy(UnwindPolicy::Catch);
+    /// func.push(check as *const ()).push(-1i32);
+    /// # if cfg!(all(target_arch = "x86_64", any(target_os = "linux", windows))) {
+    /// let panicked = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| unsafe {
+    ///     func.try_call(Convention::Cdecl).unwrap()
+    /// }));
+    /// assert!(panicked.is_err());
+    /// # }
+    /// # }
+    /// ```
+    pub fn set_unwind_policy(&mut self, policy: UnwindPolicy) -> &mut Self {
+        self.unwind_policy = policy;
+        self
+    }
+
+    /// 当前的展开策略
+    pub fn unwind_policy(&self) -> UnwindPolicy {
+        self.unwind_policy
+    }
+}
+
+/// 在不能展开的边界内执行 f, 外部异常展开到这里时进程以 abort 终止
+pub(crate) fn abort_on_unwind<F: FnOnce()>(f: F) {
+    // Rust 会在 `extern "C"` 函数中为可能展开的调用加上终止进程的 landing pad, 对外部异常同样有效
+    extern "C" fn boundary<F: FnOnce()>(data: *mut c_void) {
+        let f = unsafe { &mut *(data as *mut Option<F>) };
+        (f.take().unwrap())()
+    }
+    let mut f = Some(f);
+    boundary::<F>(&mut f as *mut Option<F> as *mut c_void);
+}
+
+/// 当前线程是否处在 `call_protected` 或 `try_call_catching` 中, 此时异常由外层的 SEH 处理
+#[cfg(all(feature = "protected", target_env = "msvc"))]
+pub(crate) fn handled_outside() -> bool {
+    crate::protect::active()
+}
+
+#[cfg(not(all(feature = "protected", target_env = "msvc")))]
+pub(crate) fn handled_outside() -> bool {
+    false
+}
+
+/// `funcall_win64_call` 的参数与返回值
+#[cfg(all(target_arch = "x86_64", windows))]
+#[repr(C)]
+pub(crate) struct Win64Call {
+    pub func: *const c_void,
+    pub args: *const usize,
+    pub len: usize,
+    pub low: usize,
+    pub high: usize,
+    pub float: f64,
+}
+
+#[cfg(all(target_arch = "x86_64", windows))]
+extern "C-unwind" {
+    /// 以 64 位 Windows 的调用约定调用 call.func, 返回后写入 rax, rdx 与 xmm0
+    pub(crate) fn funcall_win64_call(call: *mut Win64Call);
+}
+
+// 被调用者可以将前四个参数寄存器保存到返回地址之上的 32 字节中 (shadow space),
+// 即使参数不足四个也必须留出这部分空间, 之后是其余的参数, 且 call 时 rsp 需要 16 字节对齐.
+// rsp 在函数体中是变化的, 因此以 rbp 为帧指针, 展开时由 rbp 恢复 rsp
+#[cfg(all(target_arch = "x86_64", windows))]
+core::arch::global_asm!(
+    ".text",
+    ".def funcall_win64_call",
+    ".scl 2",
+    ".type 32",
+    ".endef",
+    ".globl funcall_win64_call",
+    ".p2align 4",
+    "funcall_win64_call:",
+    ".seh_proc funcall_win64_call",
+    "push rbp",
+    ".seh_pushreg rbp",
+    "push rbx",
+    ".seh_pushreg rbx",
+    "push rsi",
+    ".seh_pushreg rsi",
+    "mov rbp, rsp",
+    ".seh_setframe rbp, 0",
+    ".seh_endprologue",
+    "mov rbx, rcx",
+    "mov rsi, qword ptr [rbx + 8]",
+    "mov r10, qword ptr [rbx + 16]",
+    "cmp r10, 4",
+    "jae 2f",
+    "mov r10, 4",
+    "2:",
+    "shl r10, 3",
+    // 同 __chkstk, 超过一页时逐页下移 rsp 并访问新的一页, 不会跳过 guard page
+    "6:",
+    "cmp r10, 4096",
+    "jb 7f",
+    "sub rsp, 4096",
+    "or qword ptr [rsp], 0",
+    "sub r10, 4096",
+    "jmp 6b",
+    "7:",
+    "sub rsp, r10",
+    "and rsp, -16",
+    // 将第五个及之后的参数复制到 shadow space 之上
+    "mov r10, 4",
+    "3:",
+    "cmp r10, qword ptr [rbx + 16]",
+    "jae 4f",
+    "mov rcx, qword ptr [rsi + r10 * 8]",
+    "mov qword ptr [rsp + r10 * 8], rcx",
+    "inc r10",
+    "jmp 3b",
+    "4:",
+    // 前四个参数按位置使用寄存器, 不论其是整数还是浮点数,
+    // 都同时送入整数寄存器与对应的 xmm 寄存器, 可变参数函数也能正确读取
+    "mov r10, qword ptr [rbx + 16]",
+    "xorps xmm0, xmm0",
+    "test r10, r10",
+    "jz 5f",
+    "mov rcx, qword ptr [rsi]",
+    "movq xmm0, rcx",
+    "cmp r10, 1",
+    "je 5f",
+    "mov rdx, qword ptr [rsi + 8]",
+    "movq xmm1, rdx",
+    "cmp r10, 2",
+    "je 5f",
+    "mov r8, qword ptr [rsi + 16]",
+    "movq xmm2, r8",
+    "cmp r10, 3",
+    "je 5f",
+    "mov r9, qword ptr [rsi + 24]",
+    "movq xmm3, r9",
+    "5:",
+    "call qword ptr [rbx]",
+    "mov qword ptr [rbx + 24], rax",
+    "mov qword ptr [rbx + 32], rdx",
+    "movsd qword ptr [rbx + 40], xmm0",
+    "lea rsp, [rbp]",
+    "pop rsi",
+    "pop rbx",
+    "pop rbp",
+    "ret",
+    ".seh_endproc",
+);
+
+/// 通过 `funcall_sysv_call` 以 64 位 Linux 的调用约定调用 func, 返回 rax, rdx 与 xmm0
+#[cfg(all(feature = "unwind", target_arch = "x86_64", target_os = "linux"))]
+pub(crate) unsafe fn sysv_call(func: &Func) -> (usize, usize, f64) {
+    let args = func.stack_args(crate::plan::INT_REGS.len());
+    let mut call = SysvCall {
+        func: func.func as *const c_void,
+        args: args.as_ptr(),
+        len: args.len(),
+        fargs: func.fargs.as_ptr(),
+        flen: func.fargs.len(),
+        low: 0,
+        high: 0,
+        float: 0.0,
+    };
+    funcall_sysv_call(&mut call);
+    (call.low, call.high, call.float)
+}
+
+/// `funcall_sysv_call` 的参数与返回值
+#[cfg(all(feature = "unwind", target_arch = "x86_64", target_os = "linux"))]
+#[repr(C)]
+pub(crate) struct SysvCall {
+    pub func: *const c_void,
+    pub args: *const usize,
+    pub len: usize,
+    pub fargs: *const f64,
+    pub flen: usize,
+    pub low: usize,
+    pub high: usize,
+    pub float: f64,
+}
+
+#[cfg(all(feature = "unwind", target_arch = "x86_64", target_os = "linux"))]
+extern "C-unwind" {
+    /// 以 64 位 Linux 的调用约定调用 call.func, 返回后写入 rax, rdx 与 xmm0
+    pub(crate) fn funcall_sysv_call(call: *mut SysvCall);
+}
+
+// 与 `Func::cdecl_stack` 相同地排列参数, 但作为独立的函数带有 CFI 展开信息.
+// rsp 在函数体中是变化的, 因此以 rbp 为帧指针, 展开时由 rbp 计算 CFA
+#[cfg(all(feature = "unwind", target_arch = "x86_64", target_os = "linux"))]
+core::arch::global_asm!(
+    ".text",
+    ".globl funcall_sysv_call",
+    ".hidden funcall_sysv_call",
+    ".type funcall_sysv_call, @function",
+    ".p2align 4",
+    "funcall_sysv_call:",
+    ".cfi_startproc",
+    "push rbp",
+    ".cfi_def_cfa_offset 16",
+    ".cfi_offset rbp, -16",
+    "mov rbp, rsp",
+    ".cfi_def_cfa_register rbp",
+    "push rbx",
+    ".cfi_offset rbx, -24",
+    "mov rbx, rdi",
+    // 第七个及之后的参数所占的字节数
+    "mov r10, qword ptr [rbx + 16]",
+    "xor r11, r11",
+    "cmp r10, 6",
+    "jbe 6f",
+    "lea r11, [r10 * 8 - 48]",
+    // 超过一页时逐页下移 rsp 并访问新的一页, 不会跳过栈末尾的 guard page
+    "6:",
+    "cmp r11, 4096",
+    "jb 7f",
+    "sub rsp, 4096",
+    "or qword ptr [rsp], 0",
+    "sub r11, 4096",
+    "jmp 6b",
+    "7:",
+    "sub rsp, r11",
+    "and rsp, -16",
+    "mov r11, qword ptr [rbx + 8]",
+    "mov rcx, 6",
+    "2:",
+    "cmp rcx, r10",
+    "jae 3f",
+    "mov rax, qword ptr [r11 + rcx * 8]",
+    "mov qword ptr [rsp + rcx * 8 - 48], rax",
+    "inc rcx",
+    "jmp 2b",
+    "3:",
+    // 将前六个参数送入寄存器
+    "test r10, r10",
+    "jz 4f",
+    "mov rdi, qword ptr [r11]",
+    "cmp r10, 1",
+    "je 4f",
+    "mov rsi, qword ptr [r11 + 8]",
+    "cmp r10, 2",
+    "je 4f",
+    "mov rdx, qword ptr [r11 + 16]",
+    "cmp r10, 3",
+    "je 4f",
+    "mov rcx, qword ptr [r11 + 24]",
+    "cmp r10, 4",
+    "je 4f",
+    "mov r8, qword ptr [r11 + 32]",
+    "cmp r10, 5",
+    "je 4f",
+    "mov r9, qword ptr [r11 + 40]",
+    "4:",
+    // 浮点参数, 可变参数函数通过 al 得知用到了几个向量寄存器
+    "mov r10, qword ptr [rbx + 24]",
+    "mov rax, qword ptr [rbx + 32]",
+    "xorps xmm0, xmm0",
+    "test eax, eax",
+    "jz 5f",
+    "movsd xmm0, qword ptr [r10]",
+    "cmp eax, 1",
+    "je 5f",
+    "movsd xmm1, qword ptr [r10 + 8]",
+    "cmp eax, 2",
+    "je 5f",
+    "movsd xmm2, qword ptr [r10 + 16]",
+    "cmp eax, 3",
+    "je 5f",
+    "movsd xmm3, qword ptr [r10 + 24]",
+    "cmp eax, 4",
+    "je 5f",
+    "movsd xmm4, qword ptr [r10 + 32]",
+    "cmp eax, 5",
+    "je 5f",
+    "movsd xmm5, qword ptr [r10 + 40]",
+    "cmp eax, 6",
+    "je 5f",
+    "movsd xmm6, qword ptr [r10 + 48]",
+    "cmp eax, 7",
+    "je 5f",
+    "movsd xmm7, qword ptr [r10 + 56]",
+    "5:",
+    "call qword ptr [rbx]",
+    "mov qword ptr [rbx + 40], rax",
+    "mov qword ptr [rbx + 48], rdx",
+    "movsd qword ptr [rbx + 56], xmm0",
+    "lea rsp, [rbp - 8]",
+    "pop rbx",
+    "pop rbp",
+    ".cfi_def_cfa rsp, 8",
+    "ret",
+    ".cfi_endproc",
+    ".size funcall_sysv_call, . - funcall_sysv_call",
+);
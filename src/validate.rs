@@ -0,0 +1,144 @@
+//! 调用前对函数指针的检查
+
+use core::error::Error;
+#[cfg(any(unix, windows))]
+use core::ffi::c_void;
+use core::fmt;
+
+/// `Func::validate_ptr` 检查出的错误
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd)]
+#[non_exhaustive]
+pub enum PtrError {
+    /// 函数指针为空
+    Null,
+    /// 地址不在任何已映射的内存或已加载的模块中
+    Unmapped(usize),
+    /// 地址所在的内存不可执行, 通常是数据指针
+    NotExecutable(usize),
+}
+
+impl fmt::Display for PtrError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PtrError::Null => write!(f, "the target function pointer is null"),
+            PtrError::Unmapped(addr) => write!(f, "address {:#x} is not mapped", addr),
+            PtrError::NotExecutable(addr) => {
+                write!(f, "address {:#x} is not in executable memory", addr)
+            }
+        }
+    }
+}
+
+impl Error for PtrError {}
+
+/// 检查 ptr 是否指向可执行的内存
+pub(crate) fn validate(ptr: *const fn()) -> Result<(), PtrError> {
+    if ptr.is_null() {
+        return Err(PtrError::Null);
+    }
+    let addr = ptr as usize;
+    #[cfg(all(feature = "std", target_os = "linux"))]
+    if let Some(executable) = maps_lookup(addr) {
+        return match executable {
+            Some(true) => Ok(()),
+            Some(false) => Err(PtrError::NotExecutable(addr)),
+            None => Err(PtrError::Unmapped(addr)),
+        };
+    }
+    #[cfg(unix)]
+    return unsafe { dladdr_lookup(addr) };
+    #[cfg(windows)]
+    return unsafe { virtual_query(addr) };
+    #[cfg(not(any(unix, windows)))]
+    {
+        let _ = addr;
+        Ok(())
+    }
+}
+
+/// 在 `/proc/self/maps` 中查找 addr 所在的映射, 返回其是否可执行, 无法读取时返回 `None`
+#[cfg(all(feature = "std", target_os = "linux"))]
+fn maps_lookup(addr: usize) -> Option<Option<bool>> {
+    let maps = std::fs::read_to_string("/proc/self/maps").ok()?;
+    // 每行形如 `7f0e2c000000-7f0e2c021000 r-xp 00000000 00:00 0 [path]`
+    let executable = maps.lines().find_map(|line| {
+        let mut fields = line.split_whitespace();
+        let (start, end) = fields.next()?.split_once('-')?;
+        let start = usize::from_str_radix(start, 16).ok()?;
+        let end = usize::from_str_radix(end, 16).ok()?;
+        let perms = fields.next()?;
+        if (start..end).contains(&addr) {
+            Some(perms.as_bytes().get(2) == Some(&b'x'))
+        } else {
+            None
+        }
+    });
+    Some(executable)
+}
+
+#[cfg(unix)]
+#[repr(C)]
+struct DlInfo {
+    dli_fname: *const core::ffi::c_char,
+    dli_fbase: *mut c_void,
+    dli_sname: *const core::ffi::c_char,
+    dli_saddr: *mut c_void,
+}
+
+#[cfg(unix)]
+extern "C" {
+    fn dladdr(addr: *const c_void, info: *mut DlInfo) -> core::ffi::c_int;
+}
+
+/// 没有 `/proc/self/maps` 时只能确认地址落在某个已加载的模块中, 无法得知其是否可执行
+#[cfg(unix)]
+unsafe fn dladdr_lookup(addr: usize) -> Result<(), PtrError> {
+    let mut info = core::mem::zeroed::<DlInfo>();
+    if dladdr(addr as *const c_void, &mut info) != 0 && !info.dli_fbase.is_null() {
+        Ok(())
+    } else {
+        Err(PtrError::Unmapped(addr))
+    }
+}
+
+#[cfg(windows)]
+#[repr(C)]
+struct MemoryBasicInformation {
+    base_address: *mut c_void,
+    allocation_base: *mut c_void,
+    allocation_protect: u32,
+    region_size: usize,
+    state: u32,
+    protect: u32,
+    type_: u32,
+}
+
+#[cfg(windows)]
+extern "system" {
+    fn VirtualQuery(
+        address: *const c_void,
+        buffer: *mut MemoryBasicInformation,
+        length: usize,
+    ) -> usize;
+}
+
+#[cfg(windows)]
+const MEM_COMMIT: u32 = 0x1000;
+/// PAGE_EXECUTE, PAGE_EXECUTE_READ, PAGE_EXECUTE_READWRITE 与 PAGE_EXECUTE_WRITECOPY
+#[cfg(windows)]
+const PAGE_EXECUTE_ANY: u32 = 0x10 | 0x20 | 0x40 | 0x80;
+#[cfg(windows)]
+const PAGE_GUARD: u32 = 0x100;
+
+#[cfg(windows)]
+unsafe fn virtual_query(addr: usize) -> Result<(), PtrError> {
+    let mut info = core::mem::zeroed::<MemoryBasicInformation>();
+    let size = core::mem::size_of::<MemoryBasicInformation>();
+    if VirtualQuery(addr as *const c_void, &mut info, size) == 0 || info.state != MEM_COMMIT {
+        Err(PtrError::Unmapped(addr))
+    } else if info.protect & PAGE_EXECUTE_ANY == 0 || info.protect & PAGE_GUARD != 0 {
+        Err(PtrError::NotExecutable(addr))
+    } else {
+        Ok(())
+    }
+}
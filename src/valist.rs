@@ -0,0 +1,329 @@
+//! 由参数值构造 va_list, 参见 `Func::push_va_list`
+
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::mem::size_of;
+
+use crate::{Arg, Error, Func, Result};
+
+const WORD: usize = size_of::<usize>();
+
+/// va_list 中的一个参数, 已经过默认实参提升
+#[derive(Debug, Clone, Copy)]
+enum Item {
+    /// 不超过一个字的整数与指针, 扩展到整个字
+    Word(usize),
+    /// 64 位整数, 32 位平台上占两个字
+    Long(u64),
+    Double(f64),
+    /// 128 位整数
+    Wide(u128),
+}
+
+/// 当前平台 va_list 的形式
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Abi {
+    /// 64 位 SysV: 含整数与向量寄存器保存区的结构体, 以数组的形式传递指针
+    SysV,
+    /// AAPCS64: 同样含寄存器保存区的结构体, 超过 16 字节, 按值传递时实际传递指向副本的指针
+    Aapcs64,
+    /// 指向依次排列的参数的指针, 其余各平台
+    Pointer,
+    Unsupported,
+}
+
+const ABI: Abi = if cfg!(all(target_arch = "x86_64", not(windows))) {
+    Abi::SysV
+} else if cfg!(all(
+    target_arch = "aarch64",
+    not(any(windows, target_vendor = "apple"))
+)) {
+    Abi::Aapcs64
+} else if cfg!(any(
+    target_arch = "x86",
+    target_arch = "x86_64",
+    target_arch = "aarch64",
+    target_arch = "arm",
+    target_arch = "loongarch64",
+    target_arch = "riscv64"
+)) {
+    Abi::Pointer
+} else {
+    Abi::Unsupported
+};
+
+/// 64 位整数与 double 在栈上的对齐, 只有 32 位 x86 不要求 8 字节对齐
+const LONG_ALIGN: usize = if cfg!(target_arch = "x86") { 4 } else { 8 };
+
+/// 128 位整数在栈上的对齐
+const WIDE_ALIGN: usize = if cfg!(target_arch = "x86") {
+    4
+} else if cfg!(target_arch = "arm") {
+    8
+} else {
+    16
+};
+
+/// 64 位 Windows 中超过 8 字节的参数传递指向副本的指针
+const WIDE_BY_REF: bool = cfg!(all(target_arch = "x86_64", windows));
+
+/// 依次放置参数的内存区域, 即栈上参数或 overflow_arg_area 的布局
+#[derive(Default)]
+struct Area(Vec<u8>);
+
+impl Area {
+    /// 对齐到 align 后放入 bytes, 大小补齐到字
+    fn place(&mut self, bytes: &[u8], align: usize) {
+        let start = self.0.len().next_multiple_of(align);
+        self.0.resize(start, 0);
+        self.0.extend_from_slice(bytes);
+        let end = self.0.len().next_multiple_of(WORD);
+        self.0.resize(end, 0);
+    }
+
+    fn push(&mut self, item: Item) {
+        match item {
+            Item::Word(v) => self.place(&v.to_ne_bytes(), WORD),
+            Item::Long(v) => self.place(&v.to_ne_bytes(), LONG_ALIGN),
+            Item::Double(v) => self.place(&v.to_ne_bytes(), LONG_ALIGN),
+            Item::Wide(v) => self.place(&v.to_ne_bytes(), WIDE_ALIGN),
+        }
+    }
+}
+
+/// 按 ABI 排好的 va_list, 所有地址都是相对于缓冲区起点的偏移
+struct Layout {
+    bytes: Vec<u8>,
+    /// 需要加上缓冲区起点的指针的位置
+    ptrs: Vec<usize>,
+}
+
+impl Layout {
+    fn set(&mut self, at: usize, bytes: &[u8]) {
+        self.bytes[at..at + bytes.len()].copy_from_slice(bytes);
+    }
+
+    /// 在 at 处写入相对于缓冲区起点的偏移 offset, 分配后再加上起点
+    fn set_ptr(&mut self, at: usize, offset: usize) {
+        self.set(at, &offset.to_ne_bytes());
+        self.ptrs.push(at);
+    }
+
+    /// 将 `set_ptr` 写入的偏移换算为地址
+    fn relocate(&mut self, base: usize) {
+        for &at in &self.ptrs {
+            let mut offset = [0; WORD];
+            offset.copy_from_slice(&self.bytes[at..at + WORD]);
+            let addr = base + usize::from_ne_bytes(offset);
+            self.bytes[at..at + WORD].copy_from_slice(&addr.to_ne_bytes());
+        }
+    }
+}
+
+/// 64 位 SysV: 24 字节的头部, 6 个整数寄存器与 8 个 xmm 寄存器的保存区, 之后为 overflow_arg_area.
+/// 按 `va_arg` 的规则依次分配: 寄存器保存区用完后的参数放入 overflow_arg_area
+fn sysv(items: &[Item]) -> Layout {
+    const HEADER: usize = 32;
+    const GP: usize = 6 * 8;
+    const SAVE: usize = GP + 8 * 16;
+    let mut save = vec![0u8; SAVE];
+    let mut overflow = Area::default();
+    let (mut gp, mut fp) = (0, GP);
+    for &item in items {
+        match item {
+            Item::Word(v) if gp + 8 <= GP => {
+                save[gp..gp + 8].copy_from_slice(&v.to_ne_bytes());
+                gp += 8;
+            }
+            Item::Long(v) if gp + 8 <= GP => {
+                save[gp..gp + 8].copy_from_slice(&v.to_ne_bytes());
+                gp += 8;
+            }
+            Item::Wide(v) if gp + 16 <= GP => {
+                save[gp..gp + 16].copy_from_slice(&v.to_ne_bytes());
+                gp += 16;
+            }
+            Item::Double(v) if fp + 16 <= SAVE => {
+                save[fp..fp + 8].copy_from_slice(&v.to_ne_bytes());
+                fp += 16;
+            }
+            item => overflow.push(item),
+        }
+    }
+    let mut layout = Layout {
+        bytes: vec![0; HEADER],
+        ptrs: Vec::new(),
+    };
+    layout.bytes.extend_from_slice(&save);
+    layout.bytes.extend_from_slice(&overflow.0);
+    // gp_offset 与 fp_offset 从 0 与 48 开始
+    layout.set(0, &0u32.to_ne_bytes());
+    layout.set(4, &(GP as u32).to_ne_bytes());
+    layout.set_ptr(8, HEADER + SAVE);
+    layout.set_ptr(16, HEADER);
+    layout
+}
+
+/// AAPCS64: 32 字节的头部, 8 个整数寄存器与 8 个向量寄存器的保存区, 之后为栈上的参数.
+/// `__gr_offs` 与 `__vr_offs` 为相对于保存区末尾的负偏移, 同样按 `va_arg` 的规则依次分配
+fn aapcs64(items: &[Item]) -> Layout {
+    const HEADER: usize = 32;
+    const GR: usize = 8 * 8;
+    const VR: usize = 8 * 16;
+    let mut gr = vec![0u8; GR];
+    let mut vr = vec![0u8; VR];
+    let mut stack = Area::default();
+    let (mut gp, mut fp) = (0, 0);
+    for &item in items {
+        match item {
+            Item::Word(v) if gp + 8 <= GR => {
+                gr[gp..gp + 8].copy_from_slice(&v.to_ne_bytes());
+                gp += 8;
+            }
+            Item::Long(v) if gp + 8 <= GR => {
+                gr[gp..gp + 8].copy_from_slice(&v.to_ne_bytes());
+                gp += 8;
+            }
+            // 128 位整数从偶数号寄存器开始
+            Item::Wide(v) if gp.next_multiple_of(16) + 16 <= GR => {
+                gp = gp.next_multiple_of(16);
+                gr[gp..gp + 16].copy_from_slice(&v.to_ne_bytes());
+                gp += 16;
+            }
+            Item::Double(v) if fp + 16 <= VR => {
+                vr[fp..fp + 8].copy_from_slice(&v.to_ne_bytes());
+                fp += 16;
+            }
+            item => {
+                // 整数寄存器不够放下 128 位整数时, 之后的整数也都在栈上
+                if let Item::Wide(_) = item {
+                    gp = GR;
+                }
+                stack.push(item)
+            }
+        }
+    }
+    let mut layout = Layout {
+        bytes: vec![0; HEADER],
+        ptrs: Vec::new(),
+    };
+    layout.bytes.extend_from_slice(&gr);
+    layout.bytes.extend_from_slice(&vr);
+    layout.bytes.extend_from_slice(&stack.0);
+    layout.set_ptr(0, HEADER + GR + VR);
+    layout.set_ptr(8, HEADER + GR);
+    layout.set_ptr(16, HEADER + GR + VR);
+    layout.set(24, &(-(GR as i32)).to_ne_bytes());
+    layout.set(28, &(-(VR as i32)).to_ne_bytes());
+    layout
+}
+
+impl Func {
+    /// 由 args 构造当前平台的 va_list 并压入, 用于 `vsnprintf` 等只接受 va_list 的函数
+    ///
+    /// args 与可变参数部分的参数一样经过默认实参提升: 不足 int 的整数扩展为 int, f32 提升为 double.
+    /// 64 位 SysV 与 AAPCS64 下构造含寄存器保存区的结构体并压入指向它的指针, 其他平台上压入指向依次排列的参数的指针.
+    /// va_list 与 `Arg::Str` 等参数指向的缓冲区都由 `Func` 持有, 克隆 `Func` 时各自复制一份.
+    /// 前两种平台上被调用者会推进 va_list 中的偏移, 再次调用前需要回滚并重新压入.
+    /// s390x 等尚不支持的平台上返回 `Error::InvalidInput`, 不压入任何参数
+    ///
+    /// ```
+    /// # #[cfg(unix)]
+    /// # fn main() {
+    /// use funcall::{Arg, Convention, Func};
+    /// use std::os::raw::c_char;
+    ///
+    /// extern "C" {
+    ///     fn vsnprintf(buf: *mut c_char, size: usize, fmt: *const c_char, ap: *mut u8) -> i32;
+    /// }
+    ///
+    /// let mut buf = [0u8; 32];
+    /// let mut func = Func::from_raw(vsnprintf as *const fn());
+    /// func.push(buf.as_mut_ptr()).push(buf.len()).push(b"%d %.1f\0".as_ptr());
+    /// func.push_va_list(&[Arg::I32(42), Arg::F64(2.5)]).unwrap();
+    /// unsafe { func.try_call(Convention::Cdecl).unwrap() };
+    /// assert_eq!(&buf[..func.ret_as_usize()], b"42 2.5");
+    /// # }
+    /// # #[cfg(not(unix))]
+    /// # fn main() {}
+    /// ```
+    #[cfg_attr(feature = "provenance", track_caller)]
+    pub fn push_va_list(&mut self, args: &[Arg]) -> Result<&mut Self> {
+        if ABI == Abi::Unsupported {
+            return Err(Error::InvalidInput(
+                "va_list is not supported on this target".into(),
+            ));
+        }
+        let items = args.iter().map(|arg| self.va_item(arg)).collect::<Vec<_>>();
+        let mut layout = match ABI {
+            Abi::SysV => sysv(&items),
+            Abi::Aapcs64 => aapcs64(&items),
+            _ => {
+                let mut area = Area::default();
+                for &item in &items {
+                    area.push(item);
+                }
+                Layout {
+                    bytes: area.0,
+                    ptrs: Vec::new(),
+                }
+            }
+        };
+        // 在分配好的缓冲区中对齐到 16 字节, 再将偏移换算为地址
+        self.tables.push(self.owned.len());
+        let bytes = self.own_aligned(layout.bytes.len(), 16);
+        let base = bytes.as_ptr() as usize;
+        layout.relocate(base);
+        bytes.copy_from_slice(&layout.bytes);
+        Ok(self.push(base as *const u8))
+    }
+
+    /// 按默认实参提升转换一个参数, 字节与字符串复制一份由 `Func` 持有
+    fn va_item(&mut self, arg: &Arg) -> Item {
+        let word = |v: i64| {
+            if WORD == 8 {
+                Item::Word(v as usize)
+            } else {
+                Item::Long(v as u64)
+            }
+        };
+        let owned = |func: &mut Func, bytes: Vec<u8>| {
+            let bytes: Arc<[u8]> = bytes.into();
+            let ptr = bytes.as_ptr() as usize;
+            func.owned.push(bytes);
+            Item::Word(ptr)
+        };
+        match arg {
+            Arg::I8(v) => Item::Word(*v as isize as usize),
+            Arg::U8(v) => Item::Word(*v as usize),
+            Arg::I16(v) => Item::Word(*v as isize as usize),
+            Arg::U16(v) => Item::Word(*v as usize),
+            Arg::I32(v) => Item::Word(*v as isize as usize),
+            Arg::U32(v) => Item::Word(*v as usize),
+            Arg::I64(v) => word(*v),
+            Arg::U64(v) => word(*v as i64),
+            Arg::Isize(v) => Item::Word(*v as usize),
+            Arg::Usize(v) | Arg::Ptr(v) => Item::Word(*v),
+            Arg::F32(v) => Item::Double(f64::from(*v)),
+            Arg::F64(v) => Item::Double(*v),
+            Arg::I128(v) => self.va_wide(*v as u128),
+            Arg::U128(v) => self.va_wide(*v),
+            Arg::Bytes(v) => owned(self, v.clone()),
+            Arg::Str(v) => {
+                let mut v = v.clone().into_bytes();
+                v.push(0);
+                owned(self, v)
+            }
+        }
+    }
+
+    fn va_wide(&mut self, v: u128) -> Item {
+        if !WIDE_BY_REF {
+            return Item::Wide(v);
+        }
+        let bytes = self.own_aligned(16, 16);
+        bytes.copy_from_slice(&v.to_ne_bytes());
+        Item::Word(bytes.as_ptr() as usize)
+    }
+}
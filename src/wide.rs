@@ -0,0 +1,50 @@
+//! 以 UTF-16 字符串读取返回值, 用于返回 `LPCWSTR` 的 Windows API
+
+use core::slice;
+#[cfg(feature = "std")]
+use std::ffi::OsString;
+#[cfg(feature = "std")]
+use std::os::windows::ffi::OsStringExt;
+
+use crate::Func;
+
+impl Func {
+    /// 将返回值视为指向以 0 结尾的 UTF-16 字符串的指针, 返回不含结尾 0 的部分, 返回值为空指针时为 `None`
+    ///
+    /// ```
+    /// use funcall::{Convention, Func};
+    ///
+    /// extern "C" fn greeting() -> *const u16 {
+    ///     [0x48u16, 0x69, 0].as_ptr()
+    /// }
+    ///
+    /// let mut func = Func::from_raw(greeting as *const fn());
+    /// unsafe { func.try_call(Convention::Cdecl).unwrap() };
+    /// assert_eq!(unsafe { func.ret_as_wide_c_str() }, Some(&[0x48, 0x69][..]));
+    /// ```
+    ///
+    /// # Safety
+    ///
+    /// 返回值必须是空指针或指向以 0 结尾的 UTF-16 字符串, 且字符串在返回的切片被使用期间保持有效
+    pub unsafe fn ret_as_wide_c_str(&self) -> Option<&[u16]> {
+        let ptr = self.ret_as_usize() as *const u16;
+        if ptr.is_null() {
+            return None;
+        }
+        let mut len = 0;
+        while *ptr.add(len) != 0 {
+            len += 1;
+        }
+        Some(slice::from_raw_parts(ptr, len))
+    }
+
+    /// 同 `ret_as_wide_c_str`, 但复制为 `OsString`, 不成对的代理项也会原样保留
+    ///
+    /// # Safety
+    ///
+    /// 同 `ret_as_wide_c_str`
+    #[cfg(feature = "std")]
+    pub unsafe fn ret_as_os_string(&self) -> Option<OsString> {
+        self.ret_as_wide_c_str().map(OsString::from_wide)
+    }
+}
@@ -0,0 +1,341 @@
+//! WOW64 下从 32 位进程调用 64 位代码, 参见 `Func::call_x64`
+
+#[cfg(all(target_arch = "x86", windows))]
+use alloc::vec::Vec;
+#[cfg(all(target_arch = "x86", windows))]
+use core::arch::asm;
+#[cfg(all(target_arch = "x86", windows))]
+use core::convert::TryInto;
+#[cfg(all(target_arch = "x86", windows))]
+use core::sync::atomic::{AtomicU8, Ordering};
+
+use crate::{CallError, Func};
+
+/// 传给 64 位代码的调用描述, 各字段都按 64 位存放
+#[cfg(all(target_arch = "x86", windows))]
+#[repr(C)]
+struct Context {
+    target: u64,
+    args: u64,
+    count: u64,
+    ret: u64,
+}
+
+/// 切换到 64 位模式, 按 Win64 的调用约定调用 ctx.target, 返回后切换回 32 位模式
+///
+/// 通过远返回进入代码段 0x33, 再远返回到 0x23. 64 位部分保存 rsp 后在对齐到 16 字节的栈上放置
+/// 参数与 32 字节的 shadow space, 前四个参数同时送入整数与 xmm 寄存器, 与可变参数函数的调用方式相同
+#[cfg(all(target_arch = "x86", windows))]
+unsafe fn gate(ctx: &mut Context) {
+    asm!(
+        "push 0x33",
+        "call 2f",
+        // 返回地址加上本条与下一条指令的长度, 即 retf 之后的 64 位代码
+        "2:",
+        "add dword ptr [esp], 5",
+        "retf",
+        ".code64",
+        // 切换模式后寄存器的高 32 位是未定义的
+        "mov edi, edi",
+        "mov rbx, rsp",
+        "mov rcx, qword ptr [rdi + 16]",
+        "lea rax, [rcx * 8 + 32]",
+        "sub rsp, rax",
+        "and rsp, -16",
+        "mov r10, qword ptr [rdi + 8]",
+        "xor eax, eax",
+        "6:",
+        "cmp rax, rcx",
+        "jae 7f",
+        "mov r11, qword ptr [r10 + rax * 8]",
+        "mov qword ptr [rsp + rax * 8], r11",
+        "inc rax",
+        "jmp 6b",
+        "7:",
+        "mov rcx, qword ptr [rsp]",
+        "mov rdx, qword ptr [rsp + 8]",
+        "mov r8, qword ptr [rsp + 16]",
+        "mov r9, qword ptr [rsp + 24]",
+        "movq xmm0, rcx",
+        "movq xmm1, rdx",
+        "movq xmm2, r8",
+        "movq xmm3, r9",
+        "call qword ptr [rdi]",
+        // rbx 与 rdi 由被调用者保存
+        "mov qword ptr [rdi + 24], rax",
+        "mov rsp, rbx",
+        // 同样以返回地址之后 13 字节处的 32 位代码与 0x23 构造远返回的目标
+        "call 4f",
+        "4:",
+        "mov dword ptr [rsp + 4], 0x23",
+        "add dword ptr [rsp], 13",
+        "retf",
+        ".code32",
+        // 64 位模式下的中断可能将 ss 置为空选择子, 部分 CPU 回到 32 位模式后需要重新加载
+        "mov ax, ss",
+        "mov ss, ax",
+        inout("edi") ctx => _,
+        out("ebx") _,
+        clobber_abi("C"),
+    );
+}
+
+#[cfg(all(target_arch = "x86", windows))]
+#[link(name = "kernel32")]
+extern "system" {
+    fn GetCurrentProcess() -> isize;
+    fn GetModuleHandleA(name: *const u8) -> isize;
+    fn GetProcAddress(module: isize, name: *const u8) -> usize;
+    fn IsWow64Process(process: isize, wow64: *mut i32) -> i32;
+}
+
+/// 在 32 位的模块中查找函数, 找不到时返回 0
+#[cfg(all(target_arch = "x86", windows))]
+unsafe fn proc_address(module: &[u8], name: &[u8]) -> usize {
+    let module = GetModuleHandleA(module.as_ptr());
+    if module == 0 {
+        return 0;
+    }
+    GetProcAddress(module, name.as_ptr())
+}
+
+/// 是否运行在 x64 Windows 的 WOW64 中, ARM64 Windows 的 x86 模拟器不能切换到 64 位模式
+#[cfg(all(target_arch = "x86", windows))]
+fn is_wow64() -> bool {
+    // 0 为未检查, 1 为是, 2 为否
+    static STATE: AtomicU8 = AtomicU8::new(0);
+    match STATE.load(Ordering::Relaxed) {
+        1 => return true,
+        2 => return false,
+        _ => {}
+    }
+    const IMAGE_FILE_MACHINE_AMD64: u16 = 0x8664;
+    let wow64 = unsafe {
+        // Windows 10 之前没有 IsWow64Process2, 也没有 ARM64 版本
+        let wow64_2 = proc_address(b"kernel32.dll\0", b"IsWow64Process2\0");
+        if wow64_2 != 0 {
+            let wow64_2: unsafe extern "system" fn(isize, *mut u16, *mut u16) -> i32 =
+                core::mem::transmute(wow64_2);
+            let (mut process, mut native) = (0, 0);
+            wow64_2(GetCurrentProcess(), &mut process, &mut native) != 0
+                && process != 0
+                && native == IMAGE_FILE_MACHINE_AMD64
+        } else {
+            let mut wow64 = 0;
+            IsWow64Process(GetCurrentProcess(), &mut wow64) != 0 && wow64 != 0
+        }
+    };
+    STATE.store(if wow64 { 1 } else { 2 }, Ordering::Relaxed);
+    wow64
+}
+
+#[cfg(not(all(target_arch = "x86", windows)))]
+fn is_wow64() -> bool {
+    false
+}
+
+impl Func {
+    /// 在 WOW64 中切换到 64 位模式, 以 Win64 的调用约定调用函数, 用于 64 位 ntdll 的导出函数等 64 位代码
+    ///
+    /// 函数地址为 `Func` 中的地址零扩展到 64 位, 高于 4 GiB 的函数使用 `call_x64_at`.
+    /// 参数按压入时的类型扩展为 64 位: 有符号整数做符号扩展, 指针与无符号整数做零扩展,
+    /// 超过 8 字节的参数复制一份后传递其地址. 返回值为 64 位的 rax, 低 32 位在 `RetValues::low`,
+    /// 高 32 位在 `RetValues::high`, 可以通过 `ret_as_u64` 读取.
+    /// 只在 32 位 Windows 上可用, 不在 x64 Windows 的 WOW64 中运行时返回 `CallError::UnsupportedTarget`
+    ///
+    /// # Safety
+    ///
+    /// 压入的参数必须与函数的实际签名一致, 函数必须是 64 位代码
+    pub unsafe fn call_x64(&mut self) -> Result<(), CallError> {
+        // 不支持时不论 `Func` 中的地址如何都返回同一个错误
+        if !is_wow64() {
+            return Err(CallError::unsupported_target());
+        }
+        #[cfg(feature = "loader")]
+        self.resolve()?;
+        if self.func.is_null() {
+            return Err(CallError::NullTarget);
+        }
+        self.call_x64_at(self.func as usize as u64)
+    }
+
+    /// 同 `call_x64`, 但调用 64 位地址 target, 忽略 `Func` 中的地址
+    ///
+    /// # Safety
+    ///
+    /// 同 `call_x64`
+    #[cfg(all(target_arch = "x86", windows))]
+    pub unsafe fn call_x64_at(&mut self, target: u64) -> Result<(), CallError> {
+        use crate::ArgKind::*;
+
+        if !is_wow64() {
+            return Err(CallError::unsupported_target());
+        }
+        // 超过 8 字节的参数的副本, 调用结束前不能释放
+        let mut copies = Vec::new();
+        let mut args = Vec::with_capacity(self.slots.len());
+        for slot in self.slots.iter() {
+            let words = &self.args[slot.index..slot.index + slot.len];
+            args.push(match (slot.kind, words) {
+                (I8 | I16 | I32 | Isize, [word]) => *word as i32 as i64 as u64,
+                (_, [word]) => *word as u64,
+                (_, [low, high]) => (*high as u64) << 32 | *low as u64,
+                _ => {
+                    let copy = words.to_vec();
+                    let addr = copy.as_ptr() as u64;
+                    copies.push(copy);
+                    addr
+                }
+            });
+        }
+        let mut ctx = Context {
+            target,
+            args: args.as_ptr() as u64,
+            count: args.len() as u64,
+            ret: 0,
+        };
+        self.called = true;
+        gate(&mut ctx);
+        self.ret.low = ctx.ret as usize;
+        self.ret.high = (ctx.ret >> 32) as usize;
+        Ok(())
+    }
+
+    /// 同 `call_x64`, 但调用 64 位地址 target, 忽略 `Func` 中的地址
+    ///
+    /// # Safety
+    ///
+    /// 同 `call_x64`
+    #[cfg(not(all(target_arch = "x86", windows)))]
+    pub unsafe fn call_x64_at(&mut self, _target: u64) -> Result<(), CallError> {
+        Err(CallError::unsupported_target())
+    }
+}
+
+/// WOW64 中 64 位 ntdll 的导出函数 name 的地址, 用于 `Func::call_x64_at`
+///
+/// 通过 32 位 ntdll 的 `NtWow64QueryInformationProcess64` 与 `NtWow64ReadVirtualMemory64`
+/// 读取 64 位的 PEB 与 ntdll 的导出表. 不在 WOW64 中运行或找不到该函数时返回 `None`
+#[cfg(all(target_arch = "x86", windows))]
+pub fn ntdll64_export(name: &str) -> Option<u64> {
+    if !is_wow64() {
+        return None;
+    }
+    unsafe { Ntdll64::new()?.export(name) }
+}
+
+/// 在其他平台上总是返回 `None`
+#[cfg(not(all(target_arch = "x86", windows)))]
+pub fn ntdll64_export(_name: &str) -> Option<u64> {
+    None
+}
+
+/// `NtWow64ReadVirtualMemory64`
+#[cfg(all(target_arch = "x86", windows))]
+type ReadMemory64 = unsafe extern "system" fn(isize, u64, *mut u8, u64, *mut u64) -> i32;
+
+/// 读取 64 位地址空间中的 ntdll
+#[cfg(all(target_arch = "x86", windows))]
+struct Ntdll64 {
+    read: ReadMemory64,
+    base: u64,
+}
+
+#[cfg(all(target_arch = "x86", windows))]
+impl Ntdll64 {
+    /// 沿 64 位 PEB 中按加载顺序排列的模块链表找到 ntdll
+    unsafe fn new() -> Option<Self> {
+        let query = proc_address(b"ntdll.dll\0", b"NtWow64QueryInformationProcess64\0");
+        let read = proc_address(b"ntdll.dll\0", b"NtWow64ReadVirtualMemory64\0");
+        if query == 0 || read == 0 {
+            return None;
+        }
+        let query: unsafe extern "system" fn(isize, u32, *mut u8, u32, *mut u32) -> i32 =
+            core::mem::transmute(query);
+        let mut ntdll = Ntdll64 {
+            read: core::mem::transmute::<usize, ReadMemory64>(read),
+            base: 0,
+        };
+        // PROCESS_BASIC_INFORMATION64, PebBaseAddress 在偏移 8 处
+        let mut info = [0u8; 48];
+        if query(
+            GetCurrentProcess(),
+            0,
+            info.as_mut_ptr(),
+            48,
+            core::ptr::null_mut(),
+        ) != 0
+        {
+            return None;
+        }
+        let peb = u64::from_le_bytes(info[8..16].try_into().ok()?);
+        // PEB64.Ldr, PEB_LDR_DATA64.InLoadOrderModuleList
+        let head = ntdll.read_u64(peb + 0x18)? + 0x10;
+        let mut entry = ntdll.read_u64(head)?;
+        while entry != head {
+            // LDR_DATA_TABLE_ENTRY64.BaseDllName 的长度与缓冲区
+            let len = ntdll.read_u64(entry + 0x58)? as u16 as usize;
+            let mut name = alloc::vec![0u8; len];
+            ntdll.read_into(ntdll.read_u64(entry + 0x60)?, &mut name)?;
+            let name = name
+                .chunks_exact(2)
+                .map(|c| u16::from_le_bytes([c[0], c[1]]))
+                .collect::<Vec<_>>();
+            if alloc::string::String::from_utf16_lossy(&name).eq_ignore_ascii_case("ntdll.dll") {
+                ntdll.base = ntdll.read_u64(entry + 0x30)?;
+                return Some(ntdll);
+            }
+            entry = ntdll.read_u64(entry)?;
+        }
+        None
+    }
+
+    unsafe fn read_into(&self, addr: u64, buf: &mut [u8]) -> Option<()> {
+        let len = buf.len() as u64;
+        let status = (self.read)(
+            GetCurrentProcess(),
+            addr,
+            buf.as_mut_ptr(),
+            len,
+            core::ptr::null_mut(),
+        );
+        (status == 0).then_some(())
+    }
+
+    unsafe fn read_u64(&self, addr: u64) -> Option<u64> {
+        let mut buf = [0u8; 8];
+        self.read_into(addr, &mut buf)?;
+        Some(u64::from_le_bytes(buf))
+    }
+
+    /// 读出整个导出目录后在其中按名字查找, 与 `pe::export_names` 的解析相同
+    unsafe fn export(&self, name: &str) -> Option<u64> {
+        let mut header = [0u8; 4];
+        self.read_into(self.base + 0x3c, &mut header)?;
+        // PE32+ 的数据目录在可选头的偏移 112 处
+        let directory = self.base + u64::from(u32::from_le_bytes(header)) + 24 + 112;
+        let mut export = [0u8; 8];
+        self.read_into(directory, &mut export)?;
+        let rva = u32::from_le_bytes(export[..4].try_into().ok()?) as usize;
+        let size = u32::from_le_bytes(export[4..].try_into().ok()?) as usize;
+        let mut table = alloc::vec![0u8; size];
+        self.read_into(self.base + rva as u64, &mut table)?;
+        // 导出目录中的 RVA 换算为 table 中的偏移后读取, 超出导出目录时返回 None
+        let slice_at = |at: usize, len: usize| table.get(at.checked_sub(rva)?..)?.get(..len);
+        let u32_at =
+            |at: usize| Some(u32::from_le_bytes(slice_at(at, 4)?.try_into().ok()?) as usize);
+        let count = u32_at(rva + 24)?;
+        let functions = u32_at(rva + 28)?;
+        let names = u32_at(rva + 32)?;
+        let ordinals = u32_at(rva + 36)?;
+        (0..count).find_map(|i| {
+            let found = table.get(u32_at(names + i * 4)?.checked_sub(rva)?..)?;
+            if found.split(|b| *b == 0).next()? != name.as_bytes() {
+                return None;
+            }
+            let ordinal = slice_at(ordinals + i * 2, 2)?;
+            let ordinal = u16::from_le_bytes([ordinal[0], ordinal[1]]) as usize;
+            Some(self.base + u32_at(functions + ordinal * 4)? as u64)
+        })
+    }
+}
@@ -0,0 +1,54 @@
+//! 替换了全局分配器的测试, 单独编译为一个测试程序, 以免影响其他测试
+
+use funcall::Func;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+/// 统计当前线程分配内存的次数
+struct Counting;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+fn allocations() -> usize {
+    ALLOCATIONS.with(Cell::get)
+}
+
+extern "C" fn six_args(a: i32, b: u8, c: i64, d: usize, e: u16, f: f64) -> f64 {
+    (a as i64 * 10000 + b as i64 * 1000 + c * 100 + d as i64 * 10 + e as i64) as f64 + f
+}
+
+#[test]
+fn six_args_without_allocation() {
+    let mut func = Func::from_raw(six_args as *const fn());
+    let before = allocations();
+    func.push(1i32)
+        .push(2u8)
+        .push(3i64)
+        .push(4usize)
+        .push(5u16)
+        .push(0.5f64);
+    unsafe { func.cdecl() };
+    assert_eq!(allocations(), before);
+    assert_eq!(func.ret_as_f64(), 12345.5);
+}
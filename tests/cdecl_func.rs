@@ -11,6 +11,280 @@ pub extern "C" fn more_than_6_args(
     a + b + c + d + e + f + g + h
 }
 
+/// 第九个浮点数需要通过栈传递
+#[allow(clippy::too_many_arguments)]
+pub extern "C" fn more_than_8_floats(
+    a: f64,
+    b: f64,
+    c: f64,
+    d: f64,
+    e: f64,
+    f: f64,
+    g: f64,
+    h: f64,
+    i: f64,
+) -> f64 {
+    a + b + c + d + e + f + g + h + i * 100.0
+}
+
+pub extern "C" fn no_args() -> i32 {
+    42
+}
+
+pub extern "C" fn float_then_int(a: f32, b: i32) -> i32 {
+    (a * 10.0) as i32 * 100 + b
+}
+
+pub extern "C" fn return_third() -> f32 {
+    1.0 / 3.0
+}
+
+/// 最小的非规格化 float
+pub extern "C" fn return_denormal() -> f32 {
+    f32::from_bits(1)
+}
+
+/// # Safety
+///
+/// 用于触发段错误, p 可以是任意地址
+#[cfg(any(feature = "protected", unix))]
+pub unsafe extern "C" fn read_i32(p: *const i32) -> i32 {
+    std::ptr::read_volatile(p)
+}
+
+pub extern "C" fn sleep_ms(ms: u32) -> u32 {
+    std::thread::sleep(std::time::Duration::from_millis(ms.into()));
+    ms
+}
+
+/// 第九个浮点数之后的整数参数仍通过寄存器传递
+#[allow(clippy::too_many_arguments)]
+pub extern "C" fn floats_then_int(
+    f0: f64,
+    f1: f64,
+    f2: f64,
+    f3: f64,
+    f4: f64,
+    f5: f64,
+    f6: f64,
+    f7: f64,
+    f8: f64,
+    n: i32,
+) -> f64 {
+    f0 + f1 + f2 + f3 + f4 + f5 + f6 + f7 + f8 * 100.0 + f64::from(n) * 10000.0
+}
+
+/// 放不进最后一个寄存器的 u128 整个在栈上, 之后的参数仍使用该寄存器
+#[cfg(all(target_arch = "x86_64", not(windows)))]
+pub extern "C" fn int128_spill(a: u64, b: u64, c: u64, d: u64, e: u64, x: u128, y: u64) -> u64 {
+    assert_eq!((a, b, c, d, e), (1, 2, 3, 4, 5));
+    (x >> 64) as u64 * 3 + x as u64 * 5 + y * 7
+}
+
+/// 栈上的 u128 需要 16 字节对齐, 前面的 g 之后要空出一个字
+#[cfg(all(feature = "libffi", target_arch = "x86_64", not(windows)))]
+#[allow(clippy::too_many_arguments)]
+pub extern "C" fn int128_aligned(
+    a: u64,
+    b: u64,
+    c: u64,
+    d: u64,
+    e: u64,
+    f: u64,
+    g: u64,
+    x: u128,
+) -> u64 {
+    assert_eq!((a, b, c, d, e, f), (1, 2, 3, 4, 5, 6));
+    (x >> 64) as u64 * 3 + x as u64 * 5 + g * 7
+}
+
+/// 按顺序混合所有参数, 参数的顺序错了也会得到不同的结果
+macro_rules! mix_function {
+    ($name:ident, $($arg:ident),*) => {
+        #[allow(clippy::too_many_arguments)]
+        pub extern "C" fn $name($($arg: i64),*) -> i64 {
+            let mut sum = 0i64;
+            $(sum = sum.wrapping_mul(31).wrapping_add($arg);)*
+            sum
+        }
+    };
+}
+
+mix_function!(
+    mix200, a0, a1, a2, a3, a4, a5, a6, a7, a8, a9, a10, a11, a12, a13, a14, a15, a16, a17, a18,
+    a19, a20, a21, a22, a23, a24, a25, a26, a27, a28, a29, a30, a31, a32, a33, a34, a35, a36, a37,
+    a38, a39, a40, a41, a42, a43, a44, a45, a46, a47, a48, a49, a50, a51, a52, a53, a54, a55, a56,
+    a57, a58, a59, a60, a61, a62, a63, a64, a65, a66, a67, a68, a69, a70, a71, a72, a73, a74, a75,
+    a76, a77, a78, a79, a80, a81, a82, a83, a84, a85, a86, a87, a88, a89, a90, a91, a92, a93, a94,
+    a95, a96, a97, a98, a99, a100, a101, a102, a103, a104, a105, a106, a107, a108, a109, a110,
+    a111, a112, a113, a114, a115, a116, a117, a118, a119, a120, a121, a122, a123, a124, a125, a126,
+    a127, a128, a129, a130, a131, a132, a133, a134, a135, a136, a137, a138, a139, a140, a141, a142,
+    a143, a144, a145, a146, a147, a148, a149, a150, a151, a152, a153, a154, a155, a156, a157, a158,
+    a159, a160, a161, a162, a163, a164, a165, a166, a167, a168, a169, a170, a171, a172, a173, a174,
+    a175, a176, a177, a178, a179, a180, a181, a182, a183, a184, a185, a186, a187, a188, a189, a190,
+    a191, a192, a193, a194, a195, a196, a197, a198, a199
+);
+
+mix_function!(mix10, a0, a1, a2, a3, a4, a5, a6, a7, a8, a9);
+
+// 用于与 `call_raw6` 对照
+#[cfg(all(target_arch = "x86_64", any(target_os = "linux", windows)))]
+mix_function!(mix1, a0);
+#[cfg(all(target_arch = "x86_64", any(target_os = "linux", windows)))]
+mix_function!(mix2, a0, a1);
+#[cfg(all(target_arch = "x86_64", any(target_os = "linux", windows)))]
+mix_function!(mix3, a0, a1, a2);
+#[cfg(all(target_arch = "x86_64", any(target_os = "linux", windows)))]
+mix_function!(mix4, a0, a1, a2, a3);
+#[cfg(all(target_arch = "x86_64", any(target_os = "linux", windows)))]
+mix_function!(mix5, a0, a1, a2, a3, a4);
+#[cfg(all(target_arch = "x86_64", any(target_os = "linux", windows)))]
+mix_function!(mix6, a0, a1, a2, a3, a4, a5);
+
+/// 每层占用 4KiB 以上的栈, 返回递归的深度
+pub extern "C" fn recurse(depth: u32) -> u32 {
+    let frame = std::hint::black_box([0u8; 4096]);
+    if depth == 0 {
+        return 0;
+    }
+    recurse(depth - 1) + 1 + u32::from(std::hint::black_box(&frame)[0])
+}
+
+#[cfg(target_arch = "x86")]
+pub extern "stdcall" fn no_args_stdcall() -> i32 {
+    42
+}
+
+#[cfg(target_arch = "x86")]
+pub extern "stdcall" fn mixed_stdcall(a: i64, b: f64, c: i32, d: u64) -> f64 {
+    a as f64 * 1000.0 + b * 100.0 + c as f64 * 10.0 + d as f64
+}
+
+pub extern "C" fn mixed_args(a: i32, b: f64, c: &u8, d: u64) -> f64 {
+    a as f64 * 1000.0 + b + *c as f64 * 100.0 + d as f64 * 10.0
+}
+
+pub extern "C" fn float_args(a: f32, b: f64, c: f32) -> f64 {
+    a as f64 * 100.0 + b * 10.0 + c as f64
+}
+
+pub extern "C" fn six_args(a: i32, b: u8, c: i64, d: usize, e: u16, f: f64) -> f64 {
+    (a as i64 * 10000 + b as i64 * 1000 + c * 100 + d as i64 * 10 + e as i64) as f64 + f
+}
+
+/// 参数用满 8 个浮点寄存器, 计算时会用到更多的 xmm 寄存器
+pub extern "C" fn eight_floats(
+    a: f64,
+    b: f64,
+    c: f64,
+    d: f64,
+    e: f64,
+    f: f64,
+    g: f64,
+    h: f64,
+) -> f64 {
+    let v = [a, b, c, d, e, f, g, h];
+    let squares: Vec<f64> = v.iter().map(|x| x * x).collect();
+    let sum: f64 = v.iter().zip(&squares).map(|(x, y)| x + y).sum();
+    sum - squares.iter().sum::<f64>()
+}
+
+/// 在栈上的局部变量上执行 movaps, 调用时栈未对齐到 16 字节就会崩溃
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+fn movaps_local() {
+    #[repr(align(16))]
+    struct Aligned([u8; 16]);
+
+    let mut local = Aligned([1; 16]);
+    unsafe {
+        std::arch::asm!(
+            "xorps xmm0, xmm0",
+            "movaps xmmword ptr [{}], xmm0",
+            in(reg) &mut local,
+            out("xmm0") _,
+        );
+    }
+    assert_eq!(std::hint::black_box(&local).0, [0; 16]);
+}
+
+macro_rules! define_movaps {
+    ($arch:literal, $cv:tt, $func:ident, $($arg:ident),*) => {
+        #[cfg(target_arch = $arch)]
+        pub extern $cv fn $func($($arg: usize),*) -> usize {
+            movaps_local();
+            0 $(+ $arg)*
+        }
+    };
+}
+
+// 除去寄存器中的 6 个参数, 分别有 7 个和 8 个参数需要压栈
+define_movaps!("x86_64", "C", movaps_7, a, b, c, d, e, f, g, h, i, j, k, l, m);
+define_movaps!("x86_64", "C", movaps_8, a, b, c, d, e, f, g, h, i, j, k, l, m, n);
+
+// 32 位下所有参数都需要压栈, 覆盖 esp 模 16 的所有余数
+define_movaps!("x86", "C", movaps_1, a);
+define_movaps!("x86", "C", movaps_2, a, b);
+define_movaps!("x86", "C", movaps_3, a, b, c);
+define_movaps!("x86", "C", movaps_4, a, b, c, d);
+define_movaps!("x86", "stdcall", movaps_stdcall_1, a);
+define_movaps!("x86", "stdcall", movaps_stdcall_2, a, b);
+define_movaps!("x86", "stdcall", movaps_stdcall_3, a, b, c);
+define_movaps!("x86", "stdcall", movaps_stdcall_4, a, b, c, d);
+
+// 像未优化的代码那样, 先将四个寄存器参数保存到返回地址之上的 shadow space 中, 再返回它们的和
+#[cfg(all(target_arch = "x86_64", windows))]
+std::arch::global_asm!(
+    ".globl home_store",
+    "home_store:",
+    "mov qword ptr [rsp + 8], rcx",
+    "mov qword ptr [rsp + 16], rdx",
+    "mov qword ptr [rsp + 24], r8",
+    "mov qword ptr [rsp + 32], r9",
+    "mov rax, qword ptr [rsp + 8]",
+    "add rax, qword ptr [rsp + 16]",
+    "add rax, qword ptr [rsp + 24]",
+    "add rax, qword ptr [rsp + 32]",
+    "ret",
+);
+
+#[cfg(all(target_arch = "x86_64", windows))]
+extern "C" {
+    pub fn home_store();
+}
+
+// 直接发出系统调用, 第一个参数为调用号, 返回内核给出的原始值
+#[cfg(all(target_arch = "x86_64", target_os = "linux"))]
+std::arch::global_asm!(
+    ".globl raw_syscall3",
+    "raw_syscall3:",
+    "mov rax, rdi",
+    "mov rdi, rsi",
+    "mov rsi, rdx",
+    "mov rdx, rcx",
+    "syscall",
+    "ret",
+);
+
+#[cfg(all(target_arch = "x86_64", target_os = "linux"))]
+extern "C" {
+    pub fn raw_syscall3(nr: usize, a: usize, b: usize, c: usize) -> isize;
+}
+
+// 违反调用约定, 返回前改写了被调用者保护的 r12
+#[cfg(all(feature = "regcheck", target_arch = "x86_64", target_os = "linux"))]
+std::arch::global_asm!(
+    ".globl clobber_r12",
+    "clobber_r12:",
+    "mov r12, 0x2233",
+    "mov eax, 7",
+    "ret",
+);
+
+#[cfg(all(feature = "regcheck", target_arch = "x86_64", target_os = "linux"))]
+extern "C" {
+    pub fn clobber_r12() -> i32;
+}
+
 macro_rules! define_functions {
     ($cv:tt, $func:ident, $ty:ty) => {
         pub extern $cv fn $func(n: $ty) -> $ty {
@@ -28,8 +302,371 @@ define_functions!("C", return_u64, u64);
 define_functions!("C", return_f32, f32);
 define_functions!("C", return_f64, f64);
 
-#[cfg(target_arch = "x86_64")]
+#[cfg(any(
+    all(target_arch = "x86_64", not(windows)),
+    target_arch = "mips64",
+    target_arch = "mips64r6"
+))]
 define_functions!("C", return_i128, i128);
 
-#[cfg(target_arch = "x86_64")]
+#[cfg(all(target_arch = "x86_64", not(windows)))]
 define_functions!("C", return_u128, u128);
+
+pub type FakeVtable = [*const fn(); 3];
+
+/// 模仿 COM 对象的布局, 第一个字段为指向虚函数表的指针
+#[repr(C)]
+pub struct FakeObject {
+    pub vtable: *const FakeVtable,
+    pub value: i32,
+}
+
+pub extern "system" fn object_value(this: &FakeObject) -> i32 {
+    this.value
+}
+
+pub extern "system" fn object_set(this: &mut FakeObject, value: i32) {
+    this.value = value;
+}
+
+pub extern "system" fn object_scale(this: &FakeObject, mul: i32, add: f64) -> f64 {
+    (this.value * mul) as f64 + add
+}
+
+pub extern "system" fn system_sub(a: i32, b: i32) -> i32 {
+    a - b
+}
+
+pub extern "C" fn negate_long(v: std::os::raw::c_long) -> std::os::raw::c_long {
+    -v
+}
+
+pub extern "C" fn ulong_max(_: std::os::raw::c_int) -> std::os::raw::c_ulong {
+    std::os::raw::c_ulong::MAX
+}
+
+/// 低 32 位为 -2 的 int, 高位是残留的无关值
+pub extern "C" fn int_with_high_bits() -> i64 {
+    0x1234_5678_ffff_fffe
+}
+
+/// 模仿有两个基类的 C++ 对象, 第二个基类子对象带有自己的虚函数表指针
+#[repr(C)]
+pub struct TwoBases {
+    pub vtable_a: *const [*const fn(); 1],
+    pub a: i32,
+    pub vtable_b: *const [*const fn(); 2],
+    pub b: i32,
+}
+
+/// 第二个基类子对象的布局
+#[repr(C)]
+pub struct SecondBase {
+    pub vtable: *const [*const fn(); 2],
+    pub b: i32,
+}
+
+pub extern "system" fn second_base_value(this: &SecondBase, add: i32) -> i32 {
+    this.b + add
+}
+
+/// 在派生类中覆盖的函数, 期望收到整个对象的地址
+pub extern "system" fn two_bases_sum(this: &TwoBases, mul: i32) -> i32 {
+    (this.a + this.b) * mul
+}
+
+/// 以下三个函数签名相同, 各自返回不同的参数
+pub extern "C" fn echo_int(a: i32, _: f64, _: &u8) -> i64 {
+    a as i64
+}
+
+pub extern "C" fn echo_double(_: i32, b: f64, _: &u8) -> f64 {
+    b
+}
+
+pub extern "C" fn echo_first_byte(_: i32, _: f64, c: &u8) -> i64 {
+    *c as i64
+}
+
+/// 三个小整数参数, 编码到一个 i64 中以便检查被调用者看到的值
+pub extern "C" fn small_ints(a: i8, b: u8, c: i16) -> i64 {
+    a as i64 * 1_000_000_000 + b as i64 * 1_000_000 + c as i64
+}
+
+/// 通过输出参数返回 s 的长度
+///
+/// # Safety
+///
+/// s 必须是有效的 C 字符串
+pub unsafe extern "C" fn len_into(len: &mut usize, s: *const std::os::raw::c_char) {
+    *len = std::ffi::CStr::from_ptr(s).to_bytes().len();
+}
+
+/// 通过指针写入两个输出参数
+pub extern "C" fn split_double(x: f64, int_part: &mut i64, frac_part: &mut f64) -> i32 {
+    *int_part = x.trunc() as i64;
+    *frac_part = x.fract();
+    (x < 0.0) as i32
+}
+
+/// 参数在 r10, r11, 栈与 xmm9 中, 以 rcx 返回 r10 + 2 * r11 - 栈上的参数, 以 xmm3 返回 xmm9
+#[cfg(target_arch = "x86_64")]
+#[unsafe(naked)]
+pub extern "C" fn usercall_probe() {
+    core::arch::naked_asm!(
+        "lea rcx, [r10 + r11 * 2]",
+        "sub rcx, qword ptr [rsp + 8]",
+        "movq xmm3, xmm9",
+        "ret"
+    )
+}
+
+/// 8, 16 与 24 字节的结构体返回值, 各成员依次为 x, x + 1, ...
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Pair32 {
+    pub a: i32,
+    pub b: i32,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Pair64 {
+    pub a: i64,
+    pub b: i64,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Triple64 {
+    pub a: i64,
+    pub b: i64,
+    pub c: i64,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Vec2 {
+    pub x: f32,
+    pub y: f32,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Vec4 {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+    pub w: f32,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DVec3 {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
+pub extern "C" fn make_pair32(x: i32) -> Pair32 {
+    Pair32 { a: x, b: x + 1 }
+}
+
+pub extern "C" fn make_pair64(x: i64) -> Pair64 {
+    Pair64 { a: x, b: x + 1 }
+}
+
+pub extern "C" fn make_vec2(x: i32) -> Vec2 {
+    Vec2 {
+        x: x as f32,
+        y: x as f32 + 0.5,
+    }
+}
+
+pub extern "C" fn make_vec4(x: i32) -> Vec4 {
+    Vec4 {
+        x: x as f32,
+        y: x as f32 + 0.5,
+        z: x as f32 + 0.25,
+        w: -(x as f32),
+    }
+}
+
+pub extern "C" fn make_dvec3(x: f64) -> DVec3 {
+    DVec3 {
+        x,
+        y: x * 2.0,
+        z: -x,
+    }
+}
+
+pub extern "C" fn make_triple64(x: i64, y: f64) -> Triple64 {
+    Triple64 {
+        a: x,
+        b: x + 1,
+        c: y as i64,
+    }
+}
+
+/// 以参数之和作为退出码结束进程, 多出的整数参数与浮点参数分别在栈与 xmm 寄存器中
+#[cfg(all(
+    feature = "loader",
+    any(
+        target_arch = "x86",
+        all(target_arch = "x86_64", any(target_os = "linux", windows))
+    )
+))]
+pub extern "C" fn exit_sum(
+    a: i32,
+    b: f64,
+    c: i64,
+    d: u8,
+    e: i32,
+    f: i32,
+    g: f32,
+    h: i32,
+    i: i32,
+    j: i64,
+) -> ! {
+    let sum = a as f64 + b + (c + d as i64 + (e + f + h + i) as i64 + j) as f64 + g as f64;
+    std::process::exit(sum as i32)
+}
+
+/// 只从栈上读取参数, 以 rax 返回第一个栈参数减去第二个
+#[cfg(all(target_arch = "x86_64", not(windows)))]
+#[unsafe(naked)]
+pub extern "C" fn stack_only_probe() {
+    core::arch::naked_asm!(
+        "mov rax, qword ptr [rsp + 8]",
+        "sub rax, qword ptr [rsp + 16]",
+        "ret"
+    )
+}
+
+#[cfg(windows)]
+#[link(name = "kernel32")]
+extern "system" {
+    fn RaiseException(code: u32, flags: u32, nargs: u32, args: *const usize);
+}
+
+/// 抛出异常代码为 code 的 SEH 异常, 不会返回
+#[cfg(windows)]
+pub extern "C" fn raise_seh(code: u32) -> i32 {
+    unsafe { RaiseException(code, 0, 0, std::ptr::null()) };
+    0
+}
+
+/// 将舍入模式改为向上舍入后返回, 不恢复原先的浮点控制状态
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+pub extern "C" fn round_upward() {
+    let (mut control, mut mxcsr) = (0u16, 0u32);
+    unsafe {
+        std::arch::asm!(
+            "fnstcw word ptr [{}]",
+            "stmxcsr dword ptr [{}]",
+            in(reg) &mut control,
+            in(reg) &mut mxcsr,
+        );
+        // x87 控制字的第 10, 11 位与 MXCSR 的第 13, 14 位为舍入模式
+        control = control & !0x0c00 | 0x0800;
+        mxcsr = mxcsr & !0x6000 | 0x4000;
+        std::arch::asm!(
+            "fldcw word ptr [{}]",
+            "ldmxcsr dword ptr [{}]",
+            in(reg) &control,
+            in(reg) &mxcsr,
+        );
+    }
+}
+
+/// 同上, aarch64 下 FPCR 的第 22, 23 位为舍入模式
+#[cfg(target_arch = "aarch64")]
+pub extern "C" fn round_upward() {
+    unsafe {
+        let mut fpcr: u64;
+        std::arch::asm!("mrs {}, fpcr", out(reg) fpcr);
+        fpcr = fpcr & !(3 << 22) | 1 << 22;
+        std::arch::asm!("msr fpcr, {}", in(reg) fpcr);
+    }
+}
+
+/// 按软浮点 ABI 编译的函数, 硬浮点平台上浮点参数与返回值也通过 r0~r3 传递
+#[cfg(target_arch = "arm")]
+pub extern "aapcs" fn soft_scale(x: f64, y: f32) -> f64 {
+    x * y as f64
+}
+
+#[cfg(target_arch = "arm")]
+pub extern "aapcs" fn soft_half(x: f32) -> f32 {
+    x / 2.0
+}
+
+/// 与编译目标的浮点 ABI 一致的版本, eabihf 下浮点数通过 VFP 寄存器传递
+#[cfg(target_arch = "arm")]
+pub extern "C" fn native_scale(x: f64, y: f32) -> f64 {
+    x * y as f64
+}
+
+#[cfg(target_arch = "arm")]
+pub extern "C" fn native_half(x: f32) -> f32 {
+    x / 2.0
+}
+
+/// 以下三个函数的参数相同, 分别返回其中一部分, 用于检查装入了同一个 `ArgPack` 的调用
+pub extern "C" fn pack_str(s: *const u8, _x: f64) -> *const u8 {
+    s
+}
+
+pub extern "C" fn pack_f64(_s: *const u8, x: f64) -> f64 {
+    x
+}
+
+pub extern "C" fn pack_len(s: *const u8, x: f64) -> f64 {
+    unsafe { std::ffi::CStr::from_ptr(s.cast()) }
+        .to_bytes()
+        .len() as f64
+        + x
+}
+
+/// 依次将以空指针结尾的 argv 中的字符串以 sep 连接后写入 out, 返回字符串的个数
+///
+/// # Safety
+///
+/// argv 中的字符串必须有效, out 必须足够容纳连接后的结果
+pub unsafe extern "C" fn join_argv(argv: *const *const u8, sep: u8, out: *mut u8) -> usize {
+    let mut n = 0;
+    let mut len = 0;
+    while !(*argv.add(n)).is_null() {
+        if n != 0 {
+            *out.add(len) = sep;
+            len += 1;
+        }
+        let s = std::ffi::CStr::from_ptr((*argv.add(n)).cast()).to_bytes();
+        std::ptr::copy_nonoverlapping(s.as_ptr(), out.add(len), s.len());
+        len += s.len();
+        n += 1;
+    }
+    *out.add(len) = 0;
+    n
+}
+
+/// 调用 f(x) 并加一, 允许 f 中的 panic 展开经过本函数
+#[cfg(feature = "unwind")]
+pub extern "C-unwind" fn apply_callback(f: extern "C-unwind" fn(i32) -> i32, x: i32) -> i32 {
+    f(x) + 1
+}
+
+/// 原样返回 `_Float16`, 它在 xmm0 / s0 的低 16 位中, 以 f32 接收与返回时这些位不变
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+pub extern "C" fn echo_f16(x: f32) -> f32 {
+    x
+}
+
+/// `int a, _Float16 b, double c, _Float16 d` 的替身, 返回 d 与 b 的位拼接, a 或 c 不对时返回 0
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+pub extern "C" fn f16_pair(a: i32, b: f32, c: f64, d: f32) -> u32 {
+    if a != 7 || c != 2.5 {
+        return 0;
+    }
+    (d.to_bits() & 0xffff) << 16 | (b.to_bits() & 0xffff)
+}
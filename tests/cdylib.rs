@@ -0,0 +1,75 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// 将一段 Rust 源码编译为动态库, 返回动态库的路径
+pub fn build(name: &str, source: &str) -> PathBuf {
+    build_with(name, source, &[])
+}
+
+/// 同 `build`, 但源码中可以使用 funcall
+#[cfg(target_os = "linux")]
+pub fn build_with_funcall(name: &str, source: &str) -> PathBuf {
+    build_with_funcall_having(name, source, "")
+}
+
+/// 同 `build_with_funcall`, 但只使用含有 symbol 的 funcall, 用于要求开启某个 feature 的源码
+///
+/// 以不同的 feature 编译出的 rlib 都在同一目录中, 最新的一个不一定是本次测试所用的
+#[cfg(target_os = "linux")]
+pub fn build_with_funcall_having(name: &str, source: &str, symbol: &str) -> PathBuf {
+    // 测试程序与 funcall 及其依赖位于同一目录, 同名的 rlib 可能有多个, 取最新的一个
+    let deps = std::env::current_exe()
+        .unwrap()
+        .parent()
+        .unwrap()
+        .to_owned();
+    let rlib = fs::read_dir(&deps)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| {
+            let name = path.file_name().unwrap().to_string_lossy();
+            name.starts_with("libfuncall-") && name.ends_with(".rlib")
+        })
+        .filter(|path| {
+            symbol.is_empty()
+                || fs::read(path)
+                    .unwrap()
+                    .windows(symbol.len())
+                    .any(|window| window == symbol.as_bytes())
+        })
+        .max_by_key(|path| fs::metadata(path).unwrap().modified().unwrap())
+        .unwrap();
+    let deps = format!("dependency={}", deps.display());
+    let rlib = format!("funcall={}", rlib.display());
+    build_with(
+        name,
+        source,
+        &["--edition", "2018", "-L", &deps, "--extern", &rlib],
+    )
+}
+
+fn build_with(name: &str, source: &str, args: &[&str]) -> PathBuf {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("fixtures");
+    fs::create_dir_all(&dir).unwrap();
+    let src = dir.join(format!("{}.rs", name));
+    fs::write(&src, source).unwrap();
+    let status = Command::new(option_env!("RUSTC").unwrap_or("rustc"))
+        .args(["--crate-type", "cdylib", "--crate-name", name, "-o"])
+        .arg(dir.join(lib_name(name)))
+        .args(args)
+        .arg(&src)
+        .status()
+        .unwrap();
+    assert!(status.success());
+    dir.join(lib_name(name))
+}
+
+fn lib_name(name: &str) -> String {
+    format!(
+        "{}{}{}",
+        std::env::consts::DLL_PREFIX,
+        name,
+        std::env::consts::DLL_SUFFIX
+    )
+}
@@ -0,0 +1,100 @@
+/*
+ * 插件接口, 用于测试 `funcall::headers::parse`
+ */
+#ifndef PLUGIN_H
+#define PLUGIN_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef _WIN32
+#  define PLUGIN_API __declspec(dllexport)
+#else
+#  define PLUGIN_API __attribute__((visibility("default")))
+#endif
+
+#define PLUGIN_VERSION 3
+#define PLUGIN_CALLBACK(name) void (*name)(void *)
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef int32_t plugin_status;
+typedef unsigned long plugin_id;
+typedef const char *plugin_str;
+typedef struct plugin plugin;
+typedef struct plugin *plugin_handle;
+typedef enum { PLUGIN_OK, PLUGIN_ERROR } plugin_result;
+typedef void (*plugin_log_fn)(int level, const char *msg);
+
+typedef struct plugin_rect {
+    int x, y;
+    int w, h;
+} plugin_rect;
+
+struct plugin_info {
+    plugin_str name;
+    uint32_t version;
+};
+
+/* 生命周期 */
+PLUGIN_API plugin_handle plugin_open(plugin_id id, plugin_str path);
+PLUGIN_API void plugin_close(plugin_handle handle);
+PLUGIN_API plugin_status plugin_reload(plugin *p);
+PLUGIN_API int plugin_version(void);
+PLUGIN_API const struct plugin_info *plugin_info(plugin_handle handle);
+
+// 日志
+PLUGIN_API void plugin_set_logger(plugin_handle handle, plugin_log_fn log);
+PLUGIN_API void plugin_log(plugin_handle handle, int level, const char *fmt, ...);
+PLUGIN_API int plugin_on_event(plugin_handle handle, void (*callback)(void *data, int event), void *data);
+
+/* 数值 */
+PLUGIN_API long negate_long(long v);
+PLUGIN_API int float_then_int(float a, int b);
+PLUGIN_API double plugin_scale(double value, float factor);
+PLUGIN_API int64_t plugin_counter(plugin_handle handle);
+PLUGIN_API uint8_t plugin_flags(plugin_handle handle);
+PLUGIN_API size_t plugin_size(const plugin *p);
+PLUGIN_API ssize_t plugin_read(plugin_handle handle, void *buf, size_t len);
+PLUGIN_API _Bool plugin_is_open(plugin_handle handle);
+PLUGIN_API unsigned short plugin_port(plugin_handle handle);
+PLUGIN_API long long plugin_uptime(void);
+PLUGIN_API plugin_result plugin_check(plugin_handle handle);
+PLUGIN_API enum plugin_mode plugin_mode(plugin_handle handle);
+
+/* 字符串 */
+PLUGIN_API plugin_str plugin_name(plugin_handle handle);
+PLUGIN_API int plugin_set_name(plugin_handle handle, const char *name);
+PLUGIN_API char *plugin_format(const char *fmt, ...);
+PLUGIN_API void plugin_free(void *ptr);
+PLUGIN_API int plugin_compare(const char *const *a, const char *const *b);
+
+/* 不支持的声明 */
+PLUGIN_API int plugin_area(plugin_rect rect);
+PLUGIN_API plugin_rect plugin_bounds(plugin_handle handle);
+PLUGIN_API int plugin_sum(const int values[], size_t len);
+PLUGIN_API int __stdcall plugin_legacy(int a);
+PLUGIN_API int plugin_watch(plugin_handle handle, PLUGIN_CALLBACK(cb));
+extern int plugin_errno;
+
+static inline int plugin_ok(plugin_status status) {
+    return status == 0;
+}
+
+#if PLUGIN_VERSION >= 3
+PLUGIN_API int plugin_v3(void);
+#else
+PLUGIN_API int plugin_v2(void);
+#endif
+
+#if defined(__GNUC__) && __GNUC__ >= 4
+PLUGIN_API int plugin_gnu(void);
+#endif
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* PLUGIN_H */
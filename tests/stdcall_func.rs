@@ -0,0 +1,128 @@
+//! 与 `cdecl_func` 对应的 stdcall 函数, 只有 32 位 x86 才区分这两种调用约定
+#![cfg(target_arch = "x86")]
+
+pub extern "stdcall" fn more_than_6_args(
+    a: i32,
+    b: i32,
+    c: i32,
+    d: i32,
+    e: i32,
+    f: i32,
+    g: i32,
+    h: i32,
+) -> i32 {
+    a + b * 2 + c * 3 + d * 4 + e * 5 + f * 6 + g * 7 + h * 8
+}
+
+pub extern "stdcall" fn float_then_int(a: f32, b: i32) -> i32 {
+    (a * 10.0) as i32 * 100 + b
+}
+
+/// float 占 4 字节, double 与 i64 各占 8 字节, 被调用者弹出的字节数取决于每个参数的宽度
+pub extern "stdcall" fn float_args(a: f32, b: f64, c: f32) -> f64 {
+    a as f64 * 100.0 + b * 10.0 + c as f64
+}
+
+pub extern "stdcall" fn double_first(a: f64, b: i8, c: i64, d: f32) -> f64 {
+    a * 1000.0 + b as f64 * 100.0 + c as f64 * 10.0 + d as f64
+}
+
+/// 八个参数, 各种宽度交替出现
+#[allow(clippy::too_many_arguments)]
+pub extern "stdcall" fn many_mixed(
+    a: i32,
+    b: f64,
+    c: i64,
+    d: f32,
+    e: u8,
+    f: f64,
+    g: i64,
+    h: i32,
+) -> f64 {
+    a as f64
+        + b * 10.0
+        + c as f64 * 100.0
+        + d as f64 * 1000.0
+        + e as f64 * 10000.0
+        + f * 100000.0
+        + g as f64 * 1e6
+        + h as f64 * 1e7
+}
+
+macro_rules! define_functions {
+    ($($func:ident: $ty:ty),*) => {
+        $(
+            pub extern "stdcall" fn $func(n: $ty) -> $ty {
+                n
+            }
+        )*
+    };
+}
+
+define_functions!(
+    return_i8: i8,
+    return_u8: u8,
+    return_i16: i16,
+    return_u16: u16,
+    return_i32: i32,
+    return_u32: u32,
+    return_isize: isize,
+    return_usize: usize,
+    return_i64: i64,
+    return_u64: u64,
+    return_f32: f32,
+    return_f64: f64
+);
+
+/// 以 thiscall 调用的 C++ 成员函数
+#[repr(C)]
+pub struct Widget {
+    pub base: i32,
+}
+
+impl Widget {
+    pub extern "thiscall" fn scaled(&self, factor: i32, offset: f64) -> f64 {
+        self.base as f64 * factor as f64 + offset
+    }
+
+    pub extern "thiscall" fn base(&self) -> i32 {
+        self.base
+    }
+
+    /// 按 GCC 的 thiscall 编译的成员函数, this 作为第一个栈参数, 由调用者清理
+    pub extern "C" fn gcc_scaled(&self, factor: i32, offset: f64) -> f64 {
+        self.base as f64 * factor as f64 + offset
+    }
+
+    /// 可变参数的成员函数以 cdecl 调用, this 作为第一个栈参数
+    pub extern "C" fn sum(&self, a: i32, b: i32) -> i32 {
+        self.base * 100 + a * 10 + b
+    }
+}
+
+/// 浮点数不占用寄存器, b 与 d 在 ecx 与 edx 中, 其余在栈上
+pub extern "fastcall" fn fastcall_mixed(a: f64, b: i32, c: f32, d: i32, e: i32) -> f64 {
+    a + b as f64 * 10.0 + c as f64 * 100.0 + d as f64 * 1000.0 + e as f64 * 10000.0
+}
+
+/// 以 u64 返回 ecx 与 edx, 并弹出 8 字节的栈上参数
+///
+/// rustc 的 `extern "fastcall"` 中 i64 会占用寄存器, 与 MSVC 不同, 因此直接检查寄存器与栈中的内容
+#[unsafe(naked)]
+pub extern "C" fn fastcall_probe_regs() {
+    core::arch::naked_asm!("mov eax, ecx", "ret 8")
+}
+
+/// 以 u64 返回前两个栈上的字, 并弹出它们
+#[unsafe(naked)]
+pub extern "C" fn fastcall_probe_stack() {
+    core::arch::naked_asm!(
+        "mov eax, dword ptr [esp + 4]",
+        "mov edx, dword ptr [esp + 8]",
+        "ret 8"
+    )
+}
+
+pub extern "stdcall" fn wide_middle(a: i32, b: i64, c: i32) -> i64 {
+    a as i64 * 100 + b * 10 + c as i64
+}
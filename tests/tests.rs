@@ -1,12 +1,42 @@
-use funcall::Func;
+// 关闭 `loader` 或 `std` 时依赖它们的测试不参与编译, 只用于它们的辅助函数与导入会未被使用
+#![cfg_attr(not(feature = "loader"), allow(unused))]
+
+use funcall::{funcall, Arg, CallError, CallSpec, Convention, Func, RetValues, Signature};
 use std::ffi::CStr;
 
 mod cdecl_func;
+mod cdylib;
+mod stdcall_func;
+
+/// 通过 `libc_func` 找到的 sprintf 格式化 push 压入的参数, declared 表示是否声明为可变参数函数
+///
+/// UCRT 不导出 sprintf, `libc_func` 会在 msvcrt.dll 中找到它
+#[cfg(all(
+    feature = "loader",
+    windows,
+    any(
+        target_arch = "x86_64",
+        all(feature = "libffi", target_arch = "aarch64")
+    )
+))]
+fn sprintf(fmt: &str, push: impl Fn(&mut Func), declared: bool) -> String {
+    let mut buf = [0u8; 64];
+    let fmt = format!("{}\0", fmt);
+    let mut func = funcall::libc_func("sprintf").unwrap();
+    if declared {
+        func.set_arity(2, true);
+    }
+    func.push(buf.as_mut_ptr()).push(fmt.as_ptr());
+    push(&mut func);
+    unsafe { func.try_call(Convention::Cdecl).unwrap() };
+    let len = func.ret_as_i32() as usize;
+    String::from_utf8(buf[..len].to_vec()).unwrap()
+}
 
 // test push with miri
 #[test]
 fn push() {
-    let mut func = Func::from_raw(0 as *const fn());
+    let mut func = Func::from_raw(std::ptr::null());
     func.push(0u8);
     func.push(0i8);
     func.push(0u16);
@@ -24,6 +54,82 @@ fn push() {
     func.push(b"".as_ptr());
 }
 
+// 多字的参数按小端序分割, 低位的字在前
+#[test]
+fn push_wide() {
+    let value = 0x0102_0304_0506_0708_090a_0b0c_0d0e_0f10u128;
+    let mut func = Func::from_raw(std::ptr::null());
+    func.push(value).push(-2i64).push(u64::MAX - 1);
+    let plan = func.dry_run(Convention::Cdecl);
+    assert_eq!(plan.args[0].bytes, value.to_le_bytes());
+    assert_eq!(plan.args[1].bytes[..8], (-2i64).to_le_bytes());
+    assert_eq!(plan.args[2].bytes[..8], (u64::MAX - 1).to_le_bytes());
+}
+
+#[test]
+fn push_chained() {
+    let c = 5u8;
+    let mut chained = Func::from_raw(cdecl_func::mixed_args as *const fn());
+    chained
+        .push(1i32)
+        .push(0.5f64)
+        .push_arg(Arg::Ptr(&c as *const u8 as usize))
+        .push(7u64);
+
+    // 逐条语句压入的写法仍然可用
+    let mut expected = Func::from_raw(cdecl_func::mixed_args as *const fn());
+    expected.push(1i32);
+    expected.push(0.5f64);
+    expected.push(&c as *const u8);
+    expected.push(7u64);
+    assert_eq!(chained, expected);
+
+    let with = Func::from_raw_with(
+        cdecl_func::mixed_args as *const fn(),
+        vec![
+            Arg::I32(1),
+            Arg::F64(0.5),
+            Arg::Ptr(&c as *const u8 as usize),
+            Arg::U64(7),
+        ],
+    );
+    assert_eq!(with, expected);
+
+    unsafe {
+        chained.cdecl();
+    }
+    assert_eq!(chained.ret_as_f64(), 1570.5);
+}
+
+#[test]
+#[cfg(all(feature = "loader", target_os = "linux"))]
+fn new_with() {
+    let mut func = Func::new_with("libc.so.6", b"abs\0", vec![Arg::I32(-3)]).unwrap();
+    unsafe {
+        func.cdecl();
+    }
+    assert_eq!(func.ret_as_i32(), 3);
+}
+
+#[test]
+fn push_args() {
+    let c = 5u8;
+    let mut func = Func::from_raw(cdecl_func::mixed_args as *const fn());
+    func.push_args((1i32, 0.5f64, &c as *const u8, 7u64));
+
+    let mut expected = Func::from_raw(cdecl_func::mixed_args as *const fn());
+    expected.push(1i32);
+    expected.push(0.5f64);
+    expected.push(&c as *const u8);
+    expected.push(7u64);
+    assert_eq!(func, expected);
+
+    unsafe {
+        func.cdecl();
+    }
+    assert_eq!(func.ret_as_f64(), 1570.5);
+}
+
 macro_rules! define_test {
     ($name: ident, $func: path, $arg: expr, $ret: ident) => {
         #[test]
@@ -49,40 +155,336 @@ mod cdecl {
         }
         unsafe {
             func.cdecl();
-            assert_eq!(func.ret_as_usize(), (1..=8).sum());
+            assert_eq!(func.ret_as_usize(), (1..=8).sum::<usize>());
         }
     }
 
     #[test]
-    #[cfg(target_os = "linux")]
+    fn more_than_8_floats() {
+        let mut func = Func::from_raw(cdecl_func::more_than_8_floats as *const fn());
+        for i in 1..=9 {
+            func.push(i as f64);
+        }
+        unsafe { func.cdecl() };
+        assert_eq!(func.ret_as_f64(), 936.0);
+    }
+
+    // 可变参数函数依赖 al 得知浮点参数的个数, 且浮点参数寄存器不能在送入后被改写.
+    // 出错时通常只是偶尔有浮点数变成 0.0, 因此需要反复调用, 并在 debug 与 release 下都运行
+    #[test]
+    #[cfg(all(feature = "loader", target_os = "linux"))]
     fn sprintf() {
-        for _ in 0..100 {
-            let mut buf = vec![0i8; 100];
-            let mut func = if cfg!(target_arch = "x86") {
-                Func::new("/usr/lib32/libc.so.6", b"sprintf\0").unwrap()
-            } else {
-                Func::new("/usr/lib/libc.so.6", b"sprintf\0").unwrap()
-            };
-            func.push(buf.as_mut_ptr());
-            func.push(b"%d %d %d %d %d %d %d %.4f\0".as_ptr());
-            func.push(3i32);
-            func.push(4i32);
-            func.push(5i32);
-            func.push(6i32);
-            func.push(7i32);
-            func.push(8i32);
-            func.push(9i32);
-            func.push(1234.5678f64);
+        let libc = funcall::Library::new("libc.so.6").unwrap();
+        for i in 0..2000 {
+            let mut buf: Vec<std::os::raw::c_char> = vec![0; 128];
+            let mut func = libc.get("sprintf").unwrap();
+            let (a, b, c) = (1234.5678 + i as f64, -0.25 * i as f64, 1.0 / (i + 1) as f64);
+            func.push(buf.as_mut_ptr())
+                .push(b"%d %d %d %d %d %d %d %.4f %.4f %.4f\0".as_ptr());
+            for n in 3..=9 {
+                func.push(n * i);
+            }
+            func.push(a).push(b).push(c);
             unsafe {
                 func.cdecl();
                 assert_eq!(
                     CStr::from_ptr(buf.as_ptr()).to_str().unwrap(),
-                    "3 4 5 6 7 8 9 1234.5678"
+                    format!(
+                        "{} {} {} {} {} {} {} {:.4} {:.4} {:.4}",
+                        3 * i,
+                        4 * i,
+                        5 * i,
+                        6 * i,
+                        7 * i,
+                        8 * i,
+                        9 * i,
+                        a,
+                        b,
+                        c
+                    )
                 );
             }
         }
     }
 
+    // 调用前后存活的浮点数可能被编译器放在任意 xmm 寄存器中, 调用不能破坏它们
+    #[test]
+    fn float_clobbers() {
+        let values: Vec<f64> = (1..=16)
+            .map(|i| std::hint::black_box(i as f64 * 1.5))
+            .collect();
+        let (a, b, c, d) = (values[0], values[5], values[10], values[15]);
+        let expected = a * b + c * d;
+
+        let mut func = Func::from_raw(cdecl_func::eight_floats as *const fn());
+        for v in &values[..8] {
+            func.push(*v);
+        }
+        unsafe {
+            func.cdecl();
+        }
+        assert_eq!(func.ret_as_f64(), values[..8].iter().sum::<f64>());
+        assert_eq!(a * b + c * d, expected);
+        assert_eq!(values.iter().sum::<f64>(), 204.0);
+    }
+
+    #[test]
+    #[cfg(target_arch = "x86_64")]
+    fn stack_alignment() {
+        for &(func, n) in &[
+            (cdecl_func::movaps_7 as *const fn(), 13),
+            (cdecl_func::movaps_8 as *const fn(), 14),
+        ] {
+            let mut func = Func::from_raw(func);
+            for i in 1..=n {
+                func.push(i);
+            }
+            unsafe {
+                func.cdecl();
+            }
+            assert_eq!(func.ret_as_usize(), (1..=n).sum::<usize>());
+        }
+    }
+
+    #[test]
+    #[cfg(target_arch = "x86")]
+    fn stack_alignment_x86() {
+        let cdecl = [
+            cdecl_func::movaps_1 as *const fn(),
+            cdecl_func::movaps_2 as *const fn(),
+            cdecl_func::movaps_3 as *const fn(),
+            cdecl_func::movaps_4 as *const fn(),
+        ];
+        let stdcall = [
+            cdecl_func::movaps_stdcall_1 as *const fn(),
+            cdecl_func::movaps_stdcall_2 as *const fn(),
+            cdecl_func::movaps_stdcall_3 as *const fn(),
+            cdecl_func::movaps_stdcall_4 as *const fn(),
+        ];
+        for (n, (&c, &s)) in (1..=4).zip(cdecl.iter().zip(&stdcall)) {
+            let mut c = Func::from_raw(c);
+            let mut s = Func::from_raw(s);
+            for i in 1..=n {
+                c.push(i);
+                s.push(i);
+            }
+            unsafe {
+                c.cdecl();
+                s.stdcall();
+            }
+            assert_eq!(c.ret_as_usize(), (1..=n).sum::<usize>());
+            assert_eq!(s.ret_as_usize(), (1..=n).sum::<usize>());
+        }
+    }
+
+    // 被调用者会将寄存器参数保存到 shadow space 中, 调用者必须为其留出空间
+    #[test]
+    #[cfg(all(target_arch = "x86_64", windows))]
+    fn shadow_space() {
+        let locals: Vec<u64> = (0..32).map(|i| std::hint::black_box(i * 3)).collect();
+        let (a, b) = (
+            std::hint::black_box(0x1234u64),
+            std::hint::black_box(0x5678u64),
+        );
+        for n in 0..=6u64 {
+            let mut func = Func::from_raw(cdecl_func::home_store as *const fn());
+            for i in 1..=n {
+                func.push(i);
+            }
+            unsafe {
+                func.cdecl();
+            }
+            if n >= 4 {
+                assert_eq!(func.ret_as_u64(), 1 + 2 + 3 + 4);
+            }
+        }
+        assert_eq!(a + b, 0x68ac);
+        assert_eq!(locals, (0..32).map(|i| i * 3).collect::<Vec<_>>());
+    }
+
+    // 调用时栈上有大量参数, 调用前后存活的局部变量不能被破坏
+    #[test]
+    #[cfg(target_arch = "x86_64")]
+    fn live_locals() {
+        for round in 0..100usize {
+            let locals: [usize; 24] = std::hint::black_box(std::array::from_fn(|i| i * round));
+            let (a, b, c) = std::hint::black_box((round, round * 2, round * 3));
+            let mut func = Func::from_raw(cdecl_func::movaps_8 as *const fn());
+            for i in 0..14 {
+                func.push(i + round);
+            }
+            unsafe {
+                func.cdecl();
+            }
+            assert_eq!(
+                func.ret_as_usize(),
+                (0..14).map(|i| i + round).sum::<usize>()
+            );
+            assert_eq!((a, b, c), (round, round * 2, round * 3));
+            for (i, local) in locals.iter().enumerate() {
+                assert_eq!(*local, i * round);
+            }
+        }
+    }
+
+    #[test]
+    fn no_args() {
+        // 反复调用, 若多压入或少弹出了参数, 栈会逐渐失衡
+        for _ in 0..100 {
+            let mut func = Func::from_raw(cdecl_func::no_args as *const fn());
+            unsafe {
+                func.cdecl();
+            }
+            assert_eq!(func.ret_as_i32(), 42);
+        }
+    }
+
+    #[test]
+    #[cfg(target_arch = "x86")]
+    fn no_args_stdcall() {
+        for _ in 0..100 {
+            let mut func = Func::from_raw(cdecl_func::no_args_stdcall as *const fn());
+            unsafe {
+                func.stdcall();
+            }
+            assert_eq!(func.ret_as_i32(), 42);
+        }
+    }
+
+    #[test]
+    #[cfg(target_arch = "x86")]
+    fn mixed_stdcall() {
+        // 被调用者按字节数弹出参数, 多字的参数计算错误时栈会逐渐失衡
+        for i in 0..100 {
+            let mut func = Func::from_raw(cdecl_func::mixed_stdcall as *const fn());
+            func.push(-2i64).push(0.5f64).push(i).push(7u64);
+            assert_eq!(func.stack_bytes(), 28);
+            unsafe {
+                func.stdcall();
+            }
+            assert_eq!(func.ret_as_f64(), -2000.0 + 50.0 + i as f64 * 10.0 + 7.0);
+        }
+    }
+
+    #[test]
+    #[cfg(target_arch = "x86")]
+    fn paranoid_stack() {
+        // 调用约定正确时哨兵与弹出的字节数都不会报错
+        let mut func = Func::from_raw(cdecl_func::mixed_stdcall as *const fn());
+        func.set_paranoid(true);
+        func.push(-2i64).push(0.5f64).push(1i32).push(7u64);
+        unsafe {
+            func.stdcall();
+        }
+        let mut func = Func::from_raw(cdecl_func::more_than_6_args as *const fn());
+        func.set_paranoid(true);
+        func.push_args((1, 2, 3, 4, 5, 6, 7, 8));
+        unsafe {
+            func.cdecl();
+        }
+        assert_eq!(func.ret_as_i32(), 36);
+    }
+
+    #[test]
+    #[cfg(target_arch = "x86")]
+    #[should_panic(expected = "callee consumed 28 more bytes than pushed for Cdecl")]
+    fn paranoid_stdcall_as_cdecl() {
+        let mut func = Func::from_raw(cdecl_func::mixed_stdcall as *const fn());
+        func.set_paranoid(true);
+        func.push(-2i64).push(0.5f64).push(1i32).push(7u64);
+        unsafe {
+            func.cdecl();
+        }
+    }
+
+    #[test]
+    #[cfg(target_arch = "x86")]
+    #[should_panic(expected = "callee consumed 24 fewer bytes than pushed for Stdcall")]
+    fn paranoid_cdecl_as_stdcall() {
+        let mut func = Func::from_raw(cdecl_func::mixed_args as *const fn());
+        let c = 1u8;
+        func.set_paranoid(true);
+        func.push(1i32)
+            .push(0.5f64)
+            .push(&c as *const u8)
+            .push(7u64);
+        unsafe {
+            func.stdcall();
+        }
+    }
+
+    // 没有开启 paranoid 时, debug 构建下同样会检查由被调用者清理堆栈的调用
+    #[test]
+    #[cfg(all(target_arch = "x86", debug_assertions))]
+    #[should_panic(
+        expected = "callee popped 0 bytes, expected 24 — wrong convention or wrong argument sizes?"
+    )]
+    fn debug_cdecl_as_stdcall() {
+        let mut func = Func::from_raw(cdecl_func::mixed_args as *const fn());
+        let c = 1u8;
+        func.push(1i32)
+            .push(0.5f64)
+            .push(&c as *const u8)
+            .push(7u64);
+        unsafe {
+            func.stdcall();
+        }
+    }
+
+    #[test]
+    #[cfg(all(target_arch = "x86", debug_assertions))]
+    #[should_panic(expected = "callee popped 0 bytes, expected 4")]
+    fn debug_cdecl_as_fastcall() {
+        let mut func = Func::from_raw(cdecl_func::more_than_6_args as *const fn());
+        func.push_args((1, 2, 3));
+        unsafe {
+            func.fastcall();
+        }
+    }
+
+    #[test]
+    fn prototyped_f32() {
+        // 声明了参数个数时 f32 只占一个 float 的位置, 32 位下之后的参数不会错开 4 字节
+        let mut func = Func::from_raw(cdecl_func::float_then_int as *const fn());
+        func.set_arity(2, false);
+        func.push(1.5f32).push(7i32);
+        assert_eq!(
+            func.stack_bytes(),
+            if cfg!(target_arch = "x86") { 8 } else { 0 }
+        );
+        unsafe {
+            func.cdecl();
+        }
+        assert_eq!(func.ret_as_i32(), 1507);
+    }
+
+    #[test]
+    #[cfg(all(feature = "loader", target_os = "linux"))]
+    fn variadic_f32() {
+        // 可变参数部分的 f32 仍然提升为 double
+        let mut func = Func::new("libc.so.6", b"sprintf\0").unwrap();
+        let mut buf: Vec<std::os::raw::c_char> = vec![0; 32];
+        func.set_arity(2, true);
+        func.push(buf.as_mut_ptr())
+            .push(b"%.2f %d\0".as_ptr())
+            .push(1.5f32)
+            .push(7i32);
+        unsafe {
+            func.cdecl();
+            assert_eq!(CStr::from_ptr(buf.as_ptr()).to_str().unwrap(), "1.50 7");
+        }
+    }
+
+    #[test]
+    #[cfg(all(feature = "loader", target_os = "linux"))]
+    fn rand() {
+        let mut func = Func::new("libc.so.6", b"rand\0").unwrap();
+        unsafe {
+            func.cdecl();
+        }
+        assert!(func.ret_as_i32() >= 0);
+    }
+
     define_test!(return_i8, cdecl_func::return_i8, -1i8, ret_as_i8);
     define_test!(return_u8, cdecl_func::return_u8, 1u8, ret_as_u8);
     define_test!(
@@ -95,9 +497,9 @@ mod cdecl {
     define_test!(return_i64, cdecl_func::return_i64, -1i64, ret_as_i64);
     define_test!(return_u64, cdecl_func::return_u64, 1u64, ret_as_u64);
 
-    #[cfg(target_arch = "x86_64")]
+    #[cfg(all(target_arch = "x86_64", not(windows)))]
     define_test!(return_i128, cdecl_func::return_i128, -1i128, ret_as_i128);
-    #[cfg(target_arch = "x86_64")]
+    #[cfg(all(target_arch = "x86_64", not(windows)))]
     define_test!(return_u128, cdecl_func::return_u128, 1u128, ret_as_u128);
 
     #[test]
@@ -107,7 +509,36 @@ mod cdecl {
         unsafe {
             func.cdecl();
         }
-        assert!(func.ret_as_f32() - 123.456 <= std::f32::EPSILON);
+        assert!(func.ret_as_f32() - 123.456 <= f32::EPSILON);
+    }
+
+    #[test]
+    fn return_f32_exact() {
+        // 声明返回 float 后按 float 取回返回值, 不经过 double 的舍入
+        for (target, expect) in [
+            (cdecl_func::return_third as *const fn(), 1.0f32 / 3.0),
+            (
+                cdecl_func::return_denormal as *const fn(),
+                f32::from_bits(1),
+            ),
+        ] {
+            let mut func = Func::from_raw(target);
+            func.set_ret_kind(funcall::ArgKind::CFloat);
+            // 反复调用, 32 位下 x87 的寄存器栈若未弹出就会溢出
+            for _ in 0..16 {
+                unsafe {
+                    func.cdecl();
+                }
+                assert_eq!(func.ret_as_f32().to_bits(), expect.to_bits());
+            }
+        }
+        let mut func = Func::from_raw(cdecl_func::no_args as *const fn());
+        unsafe {
+            func.cdecl();
+        }
+        assert_eq!(func.ret_as_i32(), 42);
+        // 调用之后本线程的浮点运算仍然正常
+        assert_eq!(std::hint::black_box(1.0f64) / 4.0, 0.25);
     }
 
     #[test]
@@ -117,6 +548,7928 @@ mod cdecl {
         unsafe {
             func.cdecl();
         }
-        assert!(func.ret_as_f64() - 123.456 <= std::f64::EPSILON);
+        assert!(func.ret_as_f64() - 123.456 <= f64::EPSILON);
+    }
+}
+
+#[cfg(all(feature = "loader", target_os = "linux"))]
+mod library {
+    use super::*;
+    use libloading::os::unix::Library as RawLibrary;
+
+    #[test]
+    fn from_handle() {
+        let handle = RawLibrary::new("libc.so.6").unwrap().into_raw();
+        let mut func = unsafe { Func::from_handle(handle, "strlen").unwrap() };
+        func.push(b"hello\0".as_ptr());
+        unsafe {
+            func.cdecl();
+        }
+        assert_eq!(func.ret_as_usize(), 5);
+        assert!(unsafe { Func::from_handle(handle, "no_such_symbol") }.is_err());
+
+        // 句柄仍由我们持有, 需要手动关闭
+        drop(unsafe { RawLibrary::from_raw(handle) });
+    }
+
+    #[test]
+    fn from_handle_owned() {
+        let handle = RawLibrary::new("libc.so.6").unwrap().into_raw();
+        let mut func = unsafe { Func::from_handle_owned(handle, "strlen").unwrap() };
+        func.push(b"funcall\0".as_ptr());
+        unsafe {
+            func.cdecl();
+        }
+        assert_eq!(func.ret_as_usize(), 7);
+    }
+}
+
+#[cfg(all(feature = "loader", target_os = "linux"))]
+mod get_many {
+    use funcall::Library;
+
+    #[test]
+    fn all_found() {
+        let lib = Library::new("libc.so.6").unwrap();
+        let funcs = lib.get_many(&["strlen", "sprintf", "abs"]).unwrap();
+        assert_eq!(funcs.len(), 3);
+    }
+
+    #[test]
+    fn report_missing() {
+        let lib = Library::new("libc.so.6").unwrap();
+        let err = lib
+            .get_many(&["strlen", "no_such_fn1", "abs", "no_such_fn2"])
+            .unwrap_err();
+        let failed = err
+            .failed
+            .iter()
+            .map(|(n, _)| n.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(failed, ["no_such_fn1", "no_such_fn2"]);
+        let resolved = err.resolved.iter().map(Option::is_some).collect::<Vec<_>>();
+        assert_eq!(resolved, [true, false, true, false]);
+    }
+}
+
+#[cfg(feature = "loader")]
+mod reload {
+    use super::*;
+    use funcall::Library;
+
+    fn source(n: i32) -> String {
+        format!(
+            "#[no_mangle] pub extern \"C\" fn version() -> i32 {{ {} }}",
+            n
+        )
+    }
+
+    #[test]
+    fn ensure_fresh() {
+        let path = cdylib::build("reload_fixture", &source(1));
+        let lib = Library::open_reloadable(&path).unwrap();
+        let mut func = lib.get("version").unwrap();
+        assert!(!func.ensure_fresh().unwrap());
+        unsafe {
+            func.cdecl();
+        }
+        assert_eq!(func.ret_as_i32(), 1);
+
+        // 修改时间的精度可能不够, 稍等一下再重新编译
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        cdylib::build("reload_fixture", &source(2));
+        assert!(func.ensure_fresh().unwrap());
+        unsafe {
+            func.cdecl();
+        }
+        assert_eq!(func.ret_as_i32(), 2);
+
+        // 旧的库仍然可用
+        let mut old = lib.get("version").unwrap();
+        unsafe {
+            old.cdecl();
+        }
+        assert_eq!(old.ret_as_i32(), 1);
+    }
+
+    // 预先放置在临时目录中的同名文件不会被覆盖或加载
+    #[test]
+    fn planted_copy() {
+        let path = cdylib::build("reload_planted", &source(3));
+        let planted = std::env::temp_dir().join(format!(
+            "funcall-{}-0-{}",
+            std::process::id(),
+            path.file_name().unwrap().to_string_lossy()
+        ));
+        std::fs::write(&planted, b"planted").unwrap();
+        let lib = Library::open_reloadable(&path);
+        let contents = std::fs::read(&planted).unwrap();
+        let _ = std::fs::remove_file(&planted);
+        assert_eq!(contents, b"planted");
+        let mut func = lib.unwrap().get("version").unwrap();
+        unsafe {
+            func.cdecl();
+        }
+        assert_eq!(func.ret_as_i32(), 3);
+    }
+}
+
+// 动态库总是以位置无关的方式编译, 32 位下会用 ebx 保存 GOT 的地址,
+// 调用后若 ebx 被破坏, 读取库中的静态变量就会出错
+#[cfg(target_os = "linux")]
+mod pic {
+    use super::*;
+
+    #[cfg(feature = "loader")]
+    #[test]
+    fn statics_after_call() {
+        let path = cdylib::build_with_funcall(
+            "pic_fixture",
+            r#"
+                use funcall::Func;
+
+                static TABLE: [i32; 4] = [1, 10, 100, 1000];
+                static mut COUNTER: i32 = 0;
+
+                extern "C" fn add(a: i32, b: i32, c: i32, d: i32, e: i32, f: i32, g: i32, h: i32) -> i32 {
+                    a + b + c + d + e + f + g + h
+                }
+
+                #[no_mangle]
+                pub extern "C" fn pic_call(n: i32) -> i32 {
+                    let mut total = 0;
+                    for i in 0..n {
+                        let mut func = Func::from_raw(add as *const fn());
+                        for v in 0..8 {
+                            func.push(v + i);
+                        }
+                        unsafe {
+                            func.cdecl();
+                            COUNTER += 1;
+                            total += func.ret_as_i32() + TABLE[(i % 4) as usize] + COUNTER;
+                        }
+                    }
+                    total
+                }
+            "#,
+        );
+        let mut func = funcall::Library::new(&path)
+            .unwrap()
+            .get("pic_call")
+            .unwrap();
+        func.push(100i32);
+        unsafe { func.cdecl() };
+
+        let expected: i32 = (0..100)
+            .map(|i| {
+                (0..8).map(|v| v + i).sum::<i32>() + [1, 10, 100, 1000][i as usize % 4] + i + 1
+            })
+            .sum();
+        assert_eq!(func.ret_as_i32(), expected);
+    }
+}
+
+#[cfg(all(feature = "loader", windows, target_arch = "x86"))]
+mod decorated {
+    use super::*;
+    use funcall::Library;
+
+    #[test]
+    fn stdcall_decoration() {
+        let path = cdylib::build(
+            "decorated_fixture",
+            r#"
+            #[export_name = "_Add@8"]
+            pub extern "stdcall" fn add(a: i32, b: i32) -> i32 { a + b }
+            #[export_name = "@Sub@8"]
+            pub extern "fastcall" fn sub(a: i32, b: i32) -> i32 { a - b }
+            "#,
+        );
+        let lib = Library::new(&path).unwrap();
+
+        let mut func = lib.get("Add").unwrap();
+        assert_eq!(func.symbol_name(), Some("_Add@8"));
+        func.push(1i32);
+        func.push(2i32);
+        unsafe {
+            func.stdcall();
+        }
+        assert_eq!(func.ret_as_i32(), 3);
+
+        assert_eq!(lib.get("Sub").unwrap().symbol_name(), Some("@Sub@8"));
+        assert!(lib.get("Mul").is_err());
+    }
+
+    #[test]
+    fn pushed_bytes_checked() {
+        let path = cdylib::build(
+            "decorated_size_fixture",
+            r#"
+            #[export_name = "_Add@8"]
+            pub extern "stdcall" fn add(a: i32, b: i32) -> i32 { a + b }
+            #[export_name = "_Scale@12"]
+            pub extern "stdcall" fn scale(a: i64, b: i32) -> i64 { a * b as i64 }
+            "#,
+        );
+        let lib = Library::new(&path).unwrap();
+
+        let mut add = lib.get("Add").unwrap();
+        add.push(1i32).push(2i32);
+        unsafe { add.try_call(Convention::Stdcall).unwrap() };
+        assert_eq!(add.ret_as_i32(), 3);
+
+        let mut add = lib.get("Add").unwrap();
+        add.push(1i32).push(2i32).push(3i32);
+        let err = unsafe { add.try_call(Convention::Stdcall) }.unwrap_err();
+        assert!(matches!(
+            err,
+            CallError::ArgSizeMismatch {
+                expected: 8,
+                got: 12
+            }
+        ));
+
+        // 误将 i64 参数压入为 i32
+        let mut scale = lib.get("Scale").unwrap();
+        scale.push(5i32).push(3i32);
+        let err = unsafe { scale.try_call(Convention::Stdcall) }.unwrap_err();
+        assert!(matches!(
+            err,
+            CallError::ArgSizeMismatch {
+                expected: 12,
+                got: 8
+            }
+        ));
+
+        let mut scale = lib.get("Scale").unwrap();
+        scale.push(5i64).push(3i32);
+        unsafe { scale.try_call(Convention::Stdcall).unwrap() };
+        assert_eq!(scale.ret_as_i64(), 15);
+    }
+}
+
+mod cpp {
+    use funcall::cpp::{itanium_mangle, msvc_demangle_name};
+    use funcall::CType;
+
+    #[test]
+    fn msvc_demangle() {
+        assert_eq!(msvc_demangle_name("?add@@YAHHH@Z").unwrap(), "add");
+        assert_eq!(
+            msvc_demangle_name("?add@math@@YANNN@Z").unwrap(),
+            "math::add"
+        );
+        assert_eq!(
+            msvc_demangle_name("?sub@ops@math@@YAHHH@Z").unwrap(),
+            "math::ops::sub"
+        );
+        assert_eq!(msvc_demangle_name("??0Foo@@QEAA@XZ"), None);
+        assert_eq!(msvc_demangle_name("??$max@H@@YAHHH@Z"), None);
+        assert_eq!(msvc_demangle_name("add"), None);
+    }
+
+    #[test]
+    fn itanium() {
+        use CType::*;
+
+        let char_ptr = || CType::ptr(Char);
+        let const_char_ptr = || CType::ptr(CType::constant(Char));
+        let cases = vec![
+            ("foo", vec![Int, Double], "_Z3fooid"),
+            ("foo", vec![], "_Z3foov"),
+            ("sz", vec![ULong, LongLong, SChar, Char, Bool], "_Z2szmxacb"),
+            ("ns::foo", vec![char_ptr(), char_ptr()], "_ZN2ns3fooEPcS0_"),
+            (
+                "ns::bar",
+                vec![
+                    const_char_ptr(),
+                    const_char_ptr(),
+                    CType::ptr(Int),
+                    CType::ptr(Int),
+                ],
+                "_ZN2ns3barEPKcS1_PiS2_",
+            ),
+            (
+                "baz",
+                vec![
+                    CType::ptr(char_ptr()),
+                    CType::ptr(char_ptr()),
+                    CType::constant(Int),
+                ],
+                "_Z3bazPPcS0_i",
+            ),
+            (
+                "cc",
+                vec![
+                    CType::ptr(CType::constant(const_char_ptr())),
+                    const_char_ptr(),
+                ],
+                "_Z2ccPKPKcS0_",
+            ),
+            ("free", vec![CType::ptr(Void)], "_Z4freePv"),
+        ];
+        for (name, params, mangled) in cases {
+            assert_eq!(itanium_mangle(name, &params).unwrap(), mangled);
+        }
+
+        assert!(itanium_mangle("max<int>", &[Int]).is_err());
+        assert!(itanium_mangle("foo", &[Void]).is_err());
+    }
+
+    #[test]
+    #[cfg(all(feature = "loader", target_os = "linux"))]
+    fn new_cpp() {
+        use funcall::Func;
+
+        let path = super::cdylib::build(
+            "itanium_fixture",
+            r#"
+            #[export_name = "_ZN4math3addEii"]
+            pub extern "C" fn add(a: i32, b: i32) -> i32 { a + b }
+            "#,
+        );
+        let mut func = Func::new_cpp(&path, "math::add", &[CType::Int, CType::Int]).unwrap();
+        assert_eq!(func.symbol_name(), Some("_ZN4math3addEii"));
+        func.push(1i32);
+        func.push(2i32);
+        unsafe {
+            func.cdecl();
+        }
+        assert_eq!(func.ret_as_i32(), 3);
+    }
+
+    #[test]
+    #[cfg(all(feature = "loader", windows))]
+    fn find_cpp() {
+        use super::*;
+        use funcall::Library;
+
+        let path = cdylib::build(
+            "cpp_fixture",
+            r#"
+            #[export_name = "?add@@YAHHH@Z"]
+            pub extern "C" fn add(a: i32, b: i32) -> i32 { a + b }
+            #[export_name = "?add@math@@YANNN@Z"]
+            pub extern "C" fn add_f64(a: f64, b: f64) -> f64 { a + b }
+            "#,
+        );
+        let lib = Library::new(&path).unwrap();
+        let mut found = lib.find_cpp("add");
+        found.sort();
+        assert_eq!(found, ["?add@@YAHHH@Z", "?add@math@@YANNN@Z"]);
+        assert_eq!(lib.find_cpp("math::add"), ["?add@math@@YANNN@Z"]);
+
+        #[cfg(target_arch = "x86")]
+        {
+            let mut func = lib.get("?add@@YAHHH@Z").unwrap();
+            func.push(1i32);
+            func.push(2i32);
+            unsafe {
+                func.cdecl();
+            }
+            assert_eq!(func.ret_as_i32(), 3);
+        }
+    }
+}
+
+mod builder {
+    use super::*;
+
+    #[test]
+    fn chain() {
+        let func = Func::from_raw(cdecl_func::more_than_6_args as *const fn());
+        let mut builder = func.builder();
+        for i in 1..=8 {
+            builder = builder.arg(i);
+        }
+        let ret = unsafe { builder.call_cdecl() }.unwrap();
+        assert_eq!(ret.as_i32(), (1..=8).sum::<i32>());
+        // func 本身没有被修改
+        assert_eq!(func.ret_as_i32(), 0);
+    }
+
+    #[test]
+    #[cfg(all(feature = "loader", target_os = "linux"))]
+    fn arg_cstr() {
+        let func = funcall::Library::new("libc.so.6")
+            .unwrap()
+            .get("strlen")
+            .unwrap();
+        let s = CStr::from_bytes_with_nul(b"funcall\0").unwrap();
+        for _ in 0..2 {
+            let ret = unsafe { func.builder().arg_cstr(s).call_cdecl() }.unwrap();
+            assert_eq!(ret.as_usize(), 7);
+        }
+    }
+
+    // 与 `Func::invoke` 相同, 调用前的检查未通过时返回错误
+    #[test]
+    #[cfg(all(target_arch = "x86_64", any(target_os = "linux", windows)))]
+    fn unsupported() {
+        let func = Func::from_raw(cdecl_func::no_args as *const fn());
+        let ret = unsafe { func.builder().call_stdcall() };
+        assert_eq!(
+            ret.unwrap_err(),
+            CallError::UnsupportedConvention(Convention::Stdcall)
+        );
+    }
+}
+
+mod funcall_macro {
+    use super::*;
+
+    #[test]
+    fn raw() {
+        let sum: i32 = unsafe {
+            funcall!(cdecl raw (cdecl_func::more_than_6_args as *const fn())(1, 2, 3, 4, 5, 6, 7, 8))
+        };
+        assert_eq!(sum, 36);
+
+        let ptr = cdecl_func::return_f64 as *const fn();
+        let ret: f64 = unsafe { funcall!(cdecl raw ptr(1.5f64)) };
+        assert_eq!(ret, 1.5);
+    }
+
+    #[test]
+    #[cfg(all(feature = "loader", target_os = "linux"))]
+    fn lib() {
+        let len: usize =
+            unsafe { funcall!(cdecl lib["libc.so.6"]::strlen(b"hello\0".as_ptr())) }.unwrap();
+        assert_eq!(len, 5);
+
+        let ret: funcall::Result<i32> = unsafe { funcall!(cdecl lib["libc.so.6"]::no_such_fn()) };
+        assert!(ret.is_err());
+    }
+}
+
+mod try_call {
+    use super::*;
+
+    #[test]
+    fn null_target() {
+        let mut func = Func::from_raw(std::ptr::null());
+        assert_eq!(
+            unsafe { func.try_call(Convention::Cdecl) },
+            Err(CallError::NullTarget)
+        );
+    }
+
+    #[test]
+    fn unsupported_convention() {
+        for &conv in &[Convention::Cdecl, Convention::Stdcall] {
+            if !conv.is_supported() {
+                let mut func = Func::from_raw(cdecl_func::return_i8 as *const fn());
+                func.push(1i8);
+                let expected = if funcall::supported_conventions().is_empty() {
+                    CallError::unsupported_target()
+                } else {
+                    CallError::UnsupportedConvention(conv)
+                };
+                assert_eq!(unsafe { func.try_call(conv) }, Err(expected));
+            }
+        }
+    }
+
+    #[test]
+    fn arity() {
+        let mut func = Func::from_raw(cdecl_func::return_i8 as *const fn());
+        func.set_arity(1, false);
+        assert_eq!(
+            unsafe { func.try_call(Convention::Cdecl) },
+            Err(CallError::ArgCountMismatch {
+                expected: 1,
+                variadic: false,
+                got: 0
+            })
+        );
+        func.push(7i8);
+        assert_eq!(unsafe { func.try_call(Convention::Cdecl) }, Ok(()));
+        assert_eq!(func.ret_as_i8(), 7);
+        func.push(8i8);
+        assert!(unsafe { func.try_call(Convention::Cdecl) }.is_err());
+
+        // 可变参数函数只要求不少于固定参数个数
+        func.set_arity(1, true);
+        assert_eq!(unsafe { func.try_call(Convention::Cdecl) }, Ok(()));
+    }
+}
+
+mod call_as {
+    use super::*;
+
+    #[test]
+    fn same_as_dynamic() {
+        let c = 5u8;
+        let mut func = Func::from_raw(cdecl_func::mixed_args as *const fn());
+        func.push_args((1i32, 0.5f64, &c as *const u8, 7u64));
+        unsafe {
+            func.cdecl();
+        }
+        let ret = unsafe {
+            func.call_as::<unsafe extern "C" fn(i32, f64, *const u8, u64) -> f64>((
+                1,
+                0.5,
+                &c as *const u8,
+                7,
+            ))
+        };
+        assert_eq!(ret, func.ret_as_f64());
+
+        let mut func = Func::from_raw(cdecl_func::more_than_6_args as *const fn());
+        for i in 1..=8 {
+            func.push(i);
+        }
+        unsafe {
+            func.cdecl();
+        }
+        type Sum8 = extern "system" fn(i32, i32, i32, i32, i32, i32, i32, i32) -> i32;
+        let ret = unsafe { func.call_as::<Sum8>((1, 2, 3, 4, 5, 6, 7, 8)) };
+        assert_eq!(ret, func.ret_as_i32());
+    }
+}
+
+mod to_extern_c {
+    use super::*;
+
+    #[cfg(all(feature = "loader", target_os = "linux"))]
+    #[test]
+    fn cos() {
+        let cos = {
+            let func = Func::new("libm.so.6", b"cos\0").unwrap();
+            unsafe { func.to_extern_c::<extern "C" fn(f64) -> f64>() }
+        };
+        // Func 已被 drop, 库仍由 BoundFn 持有
+        assert!(cos.library().is_some());
+        assert_eq!(cos(0.0), 1.0);
+        assert_eq!(cos(std::f64::consts::PI), -1.0);
+
+        let f: extern "C" fn(f64) -> f64 = *cos;
+        assert_eq!(f(0.0), 1.0);
+    }
+}
+
+mod bind {
+    use super::*;
+
+    #[cfg(all(feature = "loader", target_os = "linux"))]
+    #[test]
+    fn sprintf() {
+        let mut buf = vec![0u8; 32];
+        let mut func = funcall::Library::new("libc.so.6")
+            .unwrap()
+            .get("sprintf")
+            .unwrap();
+        func.push(buf.as_mut_ptr())
+            .push_arg(Arg::Str("n = %d".into()));
+        let mut sprintf = func.bind();
+        for i in 0..1000 {
+            let ret = unsafe { sprintf.call(&[Arg::I32(i)]) }.unwrap();
+            let s = CStr::from_bytes_until_nul(&buf).unwrap().to_str().unwrap();
+            assert_eq!(s, format!("n = {}", i));
+            assert_eq!(ret.as_i32() as usize, s.len());
+        }
+        assert_eq!(sprintf.func().arg_views().count(), 2);
+    }
+
+    #[cfg(all(feature = "loader", target_os = "linux"))]
+    #[test]
+    fn arity() {
+        let mut func = Func::new("libc.so.6", b"abs\0").unwrap();
+        func.set_arity(1, false);
+        let mut abs = func.bind();
+        assert!(matches!(
+            unsafe { abs.call(&[]) },
+            Err(CallError::ArgCountMismatch { got: 0, .. })
+        ));
+        assert_eq!(unsafe { abs.call(&[Arg::I32(-7)]) }.unwrap().as_i32(), 7);
+    }
+}
+
+#[cfg(feature = "std")]
+mod shared {
+    use super::*;
+    use std::sync::{Arc, Barrier};
+    use std::thread;
+
+    #[test]
+    #[cfg(all(feature = "loader", target_os = "linux"))]
+    fn call_from_threads() {
+        let shared = funcall::Library::new("libc.so.6")
+            .unwrap()
+            .get("abs")
+            .unwrap()
+            .share();
+        let barrier = Arc::new(Barrier::new(8));
+        let handles = (0..8)
+            .map(|i| {
+                let shared = shared.clone();
+                let barrier = barrier.clone();
+                thread::spawn(move || {
+                    barrier.wait();
+                    (0..1000)
+                        .map(|j| {
+                            let mut func = shared.func();
+                            func.push(-(i * 1000 + j));
+                            unsafe {
+                                func.cdecl();
+                            }
+                            func.ret_as_i32()
+                        })
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect::<Vec<_>>();
+        for (i, handle) in handles.into_iter().enumerate() {
+            let expected = (0..1000).map(|j| i as i32 * 1000 + j).collect::<Vec<_>>();
+            assert_eq!(handle.join().unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn send_func() {
+        let mut func = Func::from_raw(cdecl_func::return_i64 as *const fn());
+        func.push(-1i64);
+        let ret = thread::spawn(move || {
+            unsafe {
+                func.cdecl();
+            }
+            func.ret_as_i64()
+        });
+        assert_eq!(ret.join().unwrap(), -1);
+    }
+}
+
+mod display {
+    use super::*;
+
+    #[test]
+    fn pending_call() {
+        let mut func = Func::from_raw(0x1000 as *const fn());
+        func.push(-1i32);
+        func.push(7u8);
+        func.push(1.5f64);
+        func.push(0.25f32);
+        func.push(0x2000 as *const u8);
+        func.push(-2i64);
+        func.push(3u128);
+        assert_eq!(
+            func.to_string(),
+            "0x1000(i32 -1, u8 7, f64 1.5, f32 0.25, ptr 0x2000, i64 -2, u128 3)"
+        );
+        // 开启 provenance 时每个参数后还有压入的位置
+        #[cfg(all(feature = "loader", not(feature = "provenance")))]
+        assert_eq!(
+            format!("{:?}", func),
+            "Func { func: 0x1000, symbol: None, lib: None, \
+             args: [i32 -1, u8 7, f64 1.5, f32 0.25, ptr 0x2000, i64 -2, u128 3] }"
+        );
+    }
+
+    #[test]
+    #[cfg(all(feature = "loader", target_os = "linux"))]
+    fn after_call() {
+        let mut func = funcall::Library::new("libc.so.6")
+            .unwrap()
+            .get("abs")
+            .unwrap();
+        func.push(-5i32);
+        assert_eq!(func.to_string(), "abs(i32 -5)");
+        unsafe {
+            func.cdecl();
+        }
+        assert!(func.to_string().starts_with("abs(i32 -5) -> (low 0x5, "));
+    }
+}
+
+mod spec {
+    use super::*;
+
+    #[test]
+    #[cfg(all(feature = "loader", target_os = "linux"))]
+    fn push_arg() {
+        let mut func = funcall::Library::new("libc.so.6")
+            .unwrap()
+            .get("strlen")
+            .unwrap();
+        func.push_arg(Arg::Str("2233".to_owned()));
+        // 克隆后原来的 Func 被 drop, 字符串仍然有效
+        let mut func = func.clone();
+        unsafe {
+            func.cdecl();
+        }
+        assert_eq!(func.ret_as_usize(), 4);
+    }
+
+    #[test]
+    #[cfg(all(feature = "loader", target_os = "linux"))]
+    fn instantiate() {
+        let spec = CallSpec::new(
+            "libc.so.6",
+            "strncmp",
+            Convention::Cdecl,
+            vec![
+                Arg::Bytes(b"abcd".to_vec()),
+                Arg::Str("abce".to_owned()),
+                Arg::Usize(3),
+            ],
+        );
+        let mut func = spec.instantiate().unwrap();
+        unsafe {
+            func.try_call(spec.convention).unwrap();
+        }
+        assert_eq!(func.ret_as_i32(), 0);
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn round_trip() {
+        let mut spec = CallSpec::new(
+            "libfoo.so",
+            "foo",
+            Convention::Stdcall,
+            vec![
+                Arg::I8(i8::MIN),
+                Arg::U8(u8::MAX),
+                Arg::I16(i16::MIN),
+                Arg::U16(u16::MAX),
+                Arg::I32(i32::MIN),
+                Arg::U32(u32::MAX),
+                Arg::I64(i64::MIN),
+                Arg::U64(u64::MAX),
+                Arg::I128(i128::MIN),
+                Arg::U128(u128::MAX),
+                Arg::Isize(isize::MIN),
+                Arg::Usize(usize::MAX),
+                Arg::F32(0.1),
+                Arg::F64(-2233.3322),
+                Arg::Ptr(0x2000),
+                Arg::Bytes(vec![0, 1, 2, 255]),
+                Arg::Str("你好\0world".to_owned()),
+            ],
+        );
+        spec.ret = Some(funcall::ArgKind::F64);
+        let json = serde_json::to_string(&spec).unwrap();
+        assert_eq!(serde_json::from_str::<CallSpec>(&json).unwrap(), spec);
+        assert!(json.contains(r#"{"Ptr":8192}"#));
+    }
+}
+
+mod signature {
+    use super::*;
+    use funcall::CType;
+
+    fn ptr(ty: CType) -> CType {
+        CType::ptr(ty)
+    }
+
+    fn cnst(ty: CType) -> CType {
+        CType::constant(ty)
+    }
+
+    #[test]
+    fn parse() {
+        let cases = vec![
+            ("void abort(void)", CType::Void, vec![], false),
+            ("int rand()", CType::Int, vec![], false),
+            (
+                "double sqrt(double x);",
+                CType::Double,
+                vec![CType::Double],
+                false,
+            ),
+            (
+                "int snprintf(char*, size_t, const char*, ...)",
+                CType::Int,
+                vec![ptr(CType::Char), CType::SizeT, ptr(cnst(CType::Char))],
+                true,
+            ),
+            (
+                "int main(int argc, char **argv)",
+                CType::Int,
+                vec![CType::Int, ptr(ptr(CType::Char))],
+                false,
+            ),
+            (
+                "unsigned long long strtoull(const char *s, char **end, int base)",
+                CType::ULongLong,
+                vec![ptr(cnst(CType::Char)), ptr(ptr(CType::Char)), CType::Int],
+                false,
+            ),
+            (
+                "void *memcpy(void *dst, const void *src, size_t n)",
+                ptr(CType::Void),
+                vec![ptr(CType::Void), ptr(cnst(CType::Void)), CType::SizeT],
+                false,
+            ),
+            (
+                "long int labs(long int)",
+                CType::Long,
+                vec![CType::Long],
+                false,
+            ),
+            (
+                "short f(unsigned short, signed char, unsigned char, char, unsigned)",
+                CType::Short,
+                vec![
+                    CType::UShort,
+                    CType::SChar,
+                    CType::UChar,
+                    CType::Char,
+                    CType::UInt,
+                ],
+                false,
+            ),
+            (
+                "bool g(_Bool, float, long long, int64_t, uint8_t)",
+                CType::Bool,
+                vec![
+                    CType::Bool,
+                    CType::Float,
+                    CType::LongLong,
+                    CType::Int64,
+                    CType::UInt8,
+                ],
+                false,
+            ),
+            (
+                "const char * const h(char * const * volatile p)",
+                cnst(ptr(cnst(CType::Char))),
+                vec![ptr(cnst(ptr(CType::Char)))],
+                false,
+            ),
+            (
+                "int printf(const char*, ...)",
+                CType::Int,
+                vec![ptr(cnst(CType::Char))],
+                true,
+            ),
+            (
+                "ssize_t w(intptr_t, uintptr_t)",
+                CType::SSizeT,
+                vec![CType::IntPtr, CType::UIntPtr],
+                false,
+            ),
+        ];
+        for (proto, ret, params, variadic) in cases {
+            let sig = Signature::parse(proto).unwrap();
+            assert_eq!(sig.ret, ret, "{}", proto);
+            assert_eq!(sig.params, params, "{}", proto);
+            assert_eq!(sig.variadic, variadic, "{}", proto);
+        }
+        assert_eq!(Signature::parse("int rand()").unwrap().name, "rand");
+    }
+
+    #[test]
+    fn reject() {
+        let cases = [
+            ("", "expected a type, found end of input at column 1"),
+            (
+                "int",
+                "expected function name, found end of input at column 4",
+            ),
+            ("int f(", "expected a type, found end of input at column 7"),
+            (
+                "int f(int",
+                "expected `,` or `)`, found end of input at column 10",
+            ),
+            ("int f(int,)", "expected a type, found `)` at column 11"),
+            (
+                "int f(int, void)",
+                "`void` is not a valid parameter type at column 12",
+            ),
+            ("int f(..., int)", "expected `)`, found `,` at column 10"),
+            (
+                "int f(long double)",
+                "unsupported type `long double` at column 7",
+            ),
+            (
+                "int f(unsigned float)",
+                "unsupported type `unsigned float` at column 7",
+            ),
+            ("int f(int int)", "unsupported type `int int` at column 7"),
+            ("int f(foo)", "expected a type, found `foo` at column 7"),
+            (
+                "int f(int) x",
+                "expected end of input, found `x` at column 12",
+            ),
+            ("int f(int[4])", "unexpected character `[` at column 10"),
+            ("int int(int)", "unsupported type `int int` at column 1"),
+        ];
+        for (proto, msg) in &cases {
+            let err = Signature::parse(proto).unwrap_err();
+            assert!(matches!(err, funcall::Error::InvalidInput(_)));
+            assert_eq!(err.to_string(), *msg, "{}", proto);
+        }
+    }
+
+    #[test]
+    fn push_checked() {
+        let sig = Signature::parse("void f(char, unsigned short, double, void*, bool)").unwrap();
+        let mut func = Func::from_raw(0x1000 as *const fn());
+        sig.prepare(&mut func);
+        sig.push_checked(&mut func, 0, Arg::I64(-1)).unwrap();
+        sig.push_checked(&mut func, 1, Arg::U8(7)).unwrap();
+        sig.push_checked(&mut func, 2, Arg::I32(3)).unwrap();
+        sig.push_checked(&mut func, 3, Arg::Ptr(0x2000)).unwrap();
+        sig.push_checked(&mut func, 4, Arg::U8(1)).unwrap();
+        assert_eq!(
+            func.to_string(),
+            "0x1000(i8 -1, u16 7, f64 3, ptr 0x2000, u8 1)"
+        );
+
+        let mut func = Func::from_raw(0x1000 as *const fn());
+        assert_eq!(
+            sig.push_checked(&mut func, 1, Arg::I32(-1)),
+            Err(CallError::ArgTypeMismatch {
+                index: 1,
+                expected: CType::UShort,
+                got: "I32(-1)".into()
+            })
+        );
+        assert!(sig.push_checked(&mut func, 0, Arg::I32(128)).is_err());
+        assert!(sig.push_checked(&mut func, 2, Arg::Ptr(0)).is_err());
+        assert!(sig.push_checked(&mut func, 3, Arg::U64(0)).is_err());
+        assert!(sig.push_checked(&mut func, 4, Arg::I32(2)).is_err());
+        assert_eq!(
+            sig.push_checked(&mut func, 5, Arg::I32(0)),
+            Err(CallError::ArgCountMismatch {
+                expected: 5,
+                variadic: false,
+                got: 6
+            })
+        );
+    }
+
+    #[test]
+    #[cfg(all(feature = "loader", target_os = "linux"))]
+    fn variadic() {
+        let sig = Signature::parse("int snprintf(char*, size_t, const char*, ...)").unwrap();
+        let mut func = funcall::Library::new("libc.so.6")
+            .unwrap()
+            .get(&sig.name)
+            .unwrap();
+        let mut buf = vec![0u8; 64];
+        let args = vec![
+            Arg::Ptr(buf.as_mut_ptr() as usize),
+            Arg::I32(64),
+            Arg::Str("%d %hhu %.2f".into()),
+            Arg::I8(-3),
+            Arg::U8(200),
+            Arg::F32(1.5),
+        ];
+        sig.prepare(&mut func);
+        for (i, arg) in args.into_iter().enumerate() {
+            sig.push_checked(&mut func, i, arg).unwrap();
+        }
+        unsafe {
+            func.try_call(Convention::Cdecl).unwrap();
+        }
+        assert_eq!(func.ret_as_i32(), 11);
+        assert_eq!(&buf[..12], b"-3 200 1.50\0");
+    }
+}
+
+mod push_fmt {
+    use super::*;
+    use funcall::CType;
+
+    #[test]
+    fn float_and_double() {
+        let ptr = cdecl_func::float_args as *const fn();
+        let mut func = Func::from_raw(ptr);
+        func.push_fmt("fdf", &[Arg::F64(1.0), Arg::F32(2.0), Arg::I32(3)])
+            .unwrap();
+        assert_eq!(
+            func.to_string(),
+            format!("{:p}(float 1, f64 2, float 3)", ptr)
+        );
+        unsafe {
+            func.cdecl();
+        }
+        assert_eq!(func.ret_as_f64(), 123.0);
+    }
+
+    #[test]
+    fn widths() {
+        let mut func = Func::from_raw(0x1000 as *const fn());
+        func.push_fmt(
+            "bBhHiIqQ?p",
+            &[
+                Arg::I64(-128),
+                Arg::I32(255),
+                Arg::U64(32767),
+                Arg::I8(1),
+                Arg::I64(-1),
+                Arg::U8(1),
+                Arg::U128(1),
+                Arg::I8(1),
+                Arg::U8(1),
+                Arg::Ptr(0x2000),
+            ],
+        )
+        .unwrap();
+        assert_eq!(
+            func.to_string(),
+            "0x1000(i8 -128, u8 255, i16 32767, u16 1, i32 -1, u32 1, i64 1, u64 1, u8 1, ptr 0x2000)"
+        );
+
+        // 超出范围的值会报错, 不会截断
+        let mut func = Func::from_raw(0x1000 as *const fn());
+        assert_eq!(
+            func.push_fmt("ii", &[Arg::I32(0), Arg::I64(1 << 31)]),
+            Err(CallError::ArgTypeMismatch {
+                index: 1,
+                expected: CType::Int,
+                got: "I64(2147483648)".into(),
+            })
+        );
+        assert!(func.push_fmt("I", &[Arg::I32(-1)]).is_err());
+        assert!(func.push_fmt("Q", &[Arg::I8(-1)]).is_err());
+        assert!(func.push_fmt("i", &[Arg::F64(1.0)]).is_err());
+        assert!(func.push_fmt("z", &[Arg::Ptr(0)]).is_err());
+        // 出错时不会留下已压入的参数
+        assert_eq!(func.to_string(), "0x1000()");
+    }
+
+    #[test]
+    fn invalid() {
+        let mut func = Func::from_raw(0x1000 as *const fn());
+        assert_eq!(
+            func.push_fmt("ix", &[Arg::I32(0), Arg::I32(0)]),
+            Err(CallError::InvalidFormat {
+                index: 1,
+                spec: 'x'
+            })
+        );
+        assert_eq!(
+            func.push_fmt("ii", &[Arg::I32(0)]),
+            Err(CallError::ArgCountMismatch {
+                expected: 2,
+                variadic: false,
+                got: 1
+            })
+        );
+    }
+
+    #[test]
+    #[cfg(all(feature = "loader", target_os = "linux"))]
+    fn string() {
+        let mut func = funcall::Library::new("libc.so.6")
+            .unwrap()
+            .get("strtol")
+            .unwrap();
+        func.push_fmt(
+            "zpi",
+            &[Arg::Str("-0x10".into()), Arg::Ptr(0), Arg::I32(16)],
+        )
+        .unwrap();
+        unsafe {
+            func.cdecl();
+        }
+        assert_eq!(func.ret_as_i64(), -16);
+    }
+}
+
+mod compiled {
+    use super::*;
+    use funcall::ArgLocation;
+
+    #[test]
+    #[cfg(all(target_arch = "x86_64", not(windows)))]
+    fn locations() {
+        let sig = Signature::parse(
+            "void f(int, double, char*, long long, float, int, int, int, short, ...)",
+        )
+        .unwrap();
+        assert_eq!(
+            sig.compile(Convention::Cdecl).locations(),
+            vec![
+                ArgLocation::IntReg(0),
+                ArgLocation::FloatReg(0),
+                ArgLocation::IntReg(1),
+                ArgLocation::IntReg(2),
+                ArgLocation::FloatReg(1),
+                ArgLocation::IntReg(3),
+                ArgLocation::IntReg(4),
+                ArgLocation::IntReg(5),
+                ArgLocation::Stack(0),
+            ]
+        );
+    }
+
+    #[test]
+    #[cfg(target_arch = "x86")]
+    fn locations() {
+        let sig = Signature::parse("void f(int, double, char*, long long, float)").unwrap();
+        assert_eq!(
+            sig.compile(Convention::Cdecl).locations(),
+            vec![
+                ArgLocation::Stack(0),
+                ArgLocation::Stack(4),
+                ArgLocation::Stack(12),
+                ArgLocation::Stack(16),
+                ArgLocation::Stack(24),
+            ]
+        );
+    }
+
+    /// 逐个压入与通过 CompiledCall 调用的结果应当相同
+    fn same_as_push(proto: &str, target: *const fn(), args: Vec<Arg>) -> RetValues {
+        let sig = Signature::parse(proto).unwrap();
+        let mut func = Func::from_raw(target);
+        for (i, arg) in args.iter().enumerate() {
+            sig.push_checked(&mut func, i, arg.clone()).unwrap();
+        }
+        unsafe {
+            func.try_call(Convention::Cdecl).unwrap();
+        }
+        let call = sig.compile(Convention::Cdecl);
+        // 帧会被复用, 调用两次以确认不会残留上一次的参数
+        for _ in 0..2 {
+            let ret = unsafe { call.invoke(target, &args).unwrap() };
+            assert_eq!(ret, func.ret(), "{}", proto);
+        }
+        func.ret()
+    }
+
+    #[test]
+    fn invoke() {
+        let c = 3u8;
+        let ret = same_as_push(
+            "double mixed_args(int, double, const unsigned char*, uint64_t)",
+            cdecl_func::mixed_args as *const fn(),
+            vec![
+                Arg::I32(1),
+                Arg::F32(0.5),
+                Arg::Ptr(&c as *const u8 as usize),
+                Arg::U8(4),
+            ],
+        );
+        assert_eq!(ret.as_f64(), 1340.5);
+
+        let ret = same_as_push(
+            "int more_than_6_args(int, int, int, int, int, int, int, int)",
+            cdecl_func::more_than_6_args as *const fn(),
+            (1..=8).map(Arg::I64).collect(),
+        );
+        assert_eq!(ret.as_i32(), 36);
+
+        let ret = same_as_push(
+            "double float_args(float, double, float)",
+            cdecl_func::float_args as *const fn(),
+            vec![Arg::F64(1.0), Arg::I32(2), Arg::F32(3.0)],
+        );
+        assert_eq!(ret.as_f64(), 123.0);
+    }
+
+    // char 的符号与 c_char 相同, 与逐个压入时的检查一致
+    #[test]
+    fn plain_char() {
+        let sig = Signature::parse("long long small_ints(char, unsigned char, short)").unwrap();
+        let call = sig.compile(Convention::Cdecl);
+        let target = cdecl_func::small_ints as *const fn();
+        let signed = std::os::raw::c_char::MIN != 0;
+        for (value, ok) in [(-1, signed), (200, !signed)] {
+            let args = [Arg::I32(value), Arg::U8(2), Arg::I16(3)];
+            let ret = unsafe { call.invoke(target, &args) };
+            assert_eq!(ret.is_ok(), ok, "{}", value);
+            let mut func = Func::from_raw(target);
+            assert_eq!(sig.push_checked(&mut func, 0, args[0].clone()).is_ok(), ok);
+        }
+    }
+
+    #[test]
+    #[cfg(all(feature = "loader", target_os = "linux"))]
+    fn variadic() {
+        let snprintf = funcall::Library::new("libc.so.6")
+            .unwrap()
+            .get("snprintf")
+            .unwrap();
+        let call = Signature::parse("int snprintf(char*, size_t, const char*, ...)")
+            .unwrap()
+            .compile(Convention::Cdecl);
+        let mut buf = vec![0u8; 32];
+        let ptr = Arg::Ptr(buf.as_mut_ptr() as usize);
+        let ret = unsafe {
+            call.invoke(
+                snprintf.as_raw(),
+                &[
+                    ptr.clone(),
+                    Arg::I32(32),
+                    Arg::Str("%s %.1f".into()),
+                    Arg::Str("a".into()),
+                    Arg::F32(0.5),
+                ],
+            )
+        };
+        assert_eq!(ret.unwrap().as_i32(), 5);
+        let ret = unsafe {
+            call.invoke(
+                snprintf.as_raw(),
+                &[ptr, Arg::I32(32), Arg::Str("%d".into()), Arg::I8(-1)],
+            )
+        };
+        assert_eq!(ret.unwrap().as_i32(), 2);
+        assert_eq!(&buf[..3], b"-1\0");
+    }
+
+    #[test]
+    fn errors() {
+        let call = Signature::parse("int f(int, char*)")
+            .unwrap()
+            .compile(Convention::Cdecl);
+        let target = cdecl_func::return_i8 as *const fn();
+        unsafe {
+            assert_eq!(
+                call.invoke(std::ptr::null(), &[]),
+                Err(CallError::NullTarget)
+            );
+            assert_eq!(
+                call.invoke(target, &[Arg::I32(0)]),
+                Err(CallError::ArgCountMismatch {
+                    expected: 2,
+                    variadic: false,
+                    got: 1
+                })
+            );
+            assert!(call
+                .invoke(target, &[Arg::I64(1 << 40), Arg::Ptr(0)])
+                .is_err());
+            assert!(call.invoke(target, &[Arg::I32(0), Arg::I32(0)]).is_err());
+        }
+    }
+}
+
+mod call_once {
+    use super::*;
+
+    #[test]
+    fn typed() {
+        let c = 2u8;
+        let ret: f64 = unsafe {
+            Func::from_raw_with(
+                cdecl_func::mixed_args as *const fn(),
+                vec![
+                    Arg::I32(1),
+                    Arg::F64(0.5),
+                    Arg::Ptr(&c as *const u8 as usize),
+                    Arg::U64(3),
+                ],
+            )
+            .call_once(Convention::Cdecl)
+            .unwrap()
+        };
+        assert_eq!(ret, 1230.5);
+    }
+
+    #[test]
+    fn validates() {
+        let mut func = Func::from_raw(cdecl_func::return_i8 as *const fn());
+        func.set_arity(1, false);
+        assert_eq!(
+            unsafe { func.call_once::<i8>(Convention::Cdecl) },
+            Err(CallError::ArgCountMismatch {
+                expected: 1,
+                variadic: false,
+                got: 0
+            })
+        );
+        assert_eq!(
+            unsafe { Func::from_raw(std::ptr::null()).call_once::<()>(Convention::Cdecl) },
+            Err(CallError::NullTarget)
+        );
+    }
+
+    #[test]
+    #[cfg(all(feature = "loader", target_os = "linux"))]
+    fn one_liner() {
+        let n: i32 = unsafe {
+            Func::new_with("libc.so.6", b"atoi\0", vec![Arg::Str("-42".into())])
+                .unwrap()
+                .call_once(Convention::Cdecl)
+                .unwrap()
+        };
+        assert_eq!(n, -42);
+    }
+}
+
+#[cfg(feature = "loader")]
+mod lazy_func {
+    use super::*;
+    use funcall::{lazy_func, LazyFunc, SharedFunc};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static RESOLVED: AtomicUsize = AtomicUsize::new(0);
+
+    fn resolve() -> funcall::Result<SharedFunc> {
+        RESOLVED.fetch_add(1, Ordering::SeqCst);
+        // 拖慢查找, 让多个线程同时等待
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        Ok(Func::from_raw(cdecl_func::more_than_6_args as *const fn()).share())
+    }
+
+    static SUM: LazyFunc = LazyFunc::with_resolver(resolve);
+
+    #[test]
+    fn resolve_once() {
+        assert!(!SUM.is_resolved());
+        let handles = (0..8)
+            .map(|i| {
+                std::thread::spawn(move || {
+                    for j in 0..100 {
+                        let mut func = SUM.func().unwrap();
+                        func.push_args((i, j, 0i32, 0i32, 0i32, 0i32, 0i32, 1i32));
+                        unsafe { func.cdecl() };
+                        assert_eq!(func.ret_as_i32(), i + j + 1);
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        assert!(SUM.is_resolved());
+        assert_eq!(RESOLVED.load(Ordering::SeqCst), 1);
+    }
+
+    lazy_func! {
+        static MISSING = ("libfuncall_does_not_exist.so", "foo");
+        #[allow(dead_code)]
+        pub(crate) static ANOTHER = ("libfuncall_does_not_exist.so", "bar");
+    }
+
+    #[test]
+    fn error() {
+        assert!(MISSING.func().is_err());
+        // 错误会被缓存
+        assert!(MISSING.is_resolved());
+        assert!(MISSING.get().is_err());
+    }
+
+    #[test]
+    #[should_panic(expected = "failed to resolve `foo` from `libfuncall_does_not_exist.so`")]
+    fn panic() {
+        MISSING.func_or_panic();
+    }
+}
+
+#[cfg(feature = "std")]
+mod observer {
+    use super::*;
+    use funcall::{CallInfo, CallObserver};
+    use std::sync::{Arc, Mutex};
+    use std::thread::ThreadId;
+    use std::time::Duration;
+
+    /// 线程, 符号, 参数个数, 参数及返回值
+    type Call = (ThreadId, String, usize, String);
+
+    /// 观察者是全局的, 安装观察者的测试不能同时运行
+    static GLOBAL: Mutex<()> = Mutex::new(());
+
+    #[derive(Default, Clone)]
+    struct Recorder {
+        calls: Arc<Mutex<Vec<Call>>>,
+    }
+
+    impl CallObserver for Recorder {
+        fn before(&self, info: &CallInfo) {
+            let args = info.args().map(|arg| arg.to_string()).collect::<Vec<_>>();
+            self.calls.lock().unwrap().push((
+                std::thread::current().id(),
+                info.symbol().unwrap_or("?").to_owned(),
+                args.len(),
+                args.join(", "),
+            ));
+        }
+
+        fn after(&self, info: &CallInfo, ret: &RetValues, _elapsed: Duration) {
+            let mut calls = self.calls.lock().unwrap();
+            let last = calls.last_mut().unwrap();
+            assert_eq!(last.1, info.symbol().unwrap_or("?"));
+            last.3 += &format!(" -> {}", ret.as_isize());
+        }
+    }
+
+    #[test]
+    fn report() {
+        let _guard = GLOBAL.lock().unwrap_or_else(|e| e.into_inner());
+        let recorder = Recorder::default();
+        funcall::set_observer(Box::new(recorder.clone()));
+
+        let mut func = Func::from_raw(cdecl_func::return_isize as *const fn());
+        func.push(-3isize);
+        unsafe { func.cdecl() };
+        let mut func = Func::from_raw(cdecl_func::more_than_6_args as *const fn());
+        func.push_args((1i32, 2i32, 3i32, 4i32, 5i32, 6i32, 7i32, 8i32));
+        unsafe { func.cdecl() };
+
+        funcall::clear_observer();
+        let mut func = Func::from_raw(cdecl_func::return_i8 as *const fn());
+        func.push(0i8);
+        unsafe { func.cdecl() };
+
+        // 其他测试可能同时在别的线程中调用函数
+        let me = std::thread::current().id();
+        let calls = recorder
+            .calls
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|call| call.0 == me)
+            .map(|call| (call.2, call.3.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            calls,
+            vec![
+                (1, "isize -3 -> -3".to_owned()),
+                (
+                    8,
+                    "i32 1, i32 2, i32 3, i32 4, i32 5, i32 6, i32 7, i32 8 -> 36".to_owned()
+                ),
+            ]
+        );
+    }
+
+    struct Panicking(ThreadId);
+
+    impl CallObserver for Panicking {
+        fn before(&self, _info: &CallInfo) {
+            if std::thread::current().id() == self.0 {
+                panic!("observer panicked");
+            }
+        }
+    }
+
+    // 观察者 panic 时调用照常进行, 之后的调用仍会通知观察者
+    #[test]
+    fn panicking() {
+        let _guard = GLOBAL.lock().unwrap_or_else(|e| e.into_inner());
+        let me = std::thread::current().id();
+        funcall::set_observer(Box::new(Panicking(me)));
+        let mut func = Func::from_raw(cdecl_func::return_isize as *const fn());
+        func.push(-3isize);
+        unsafe { func.cdecl() };
+        assert_eq!(func.ret_as_isize(), -3);
+
+        let recorder = Recorder::default();
+        funcall::set_observer(Box::new(recorder.clone()));
+        unsafe { func.cdecl() };
+        funcall::clear_observer();
+        let calls = recorder.calls.lock().unwrap();
+        assert!(calls.iter().any(|call| call.0 == me));
+    }
+}
+
+#[cfg(all(feature = "log", target_arch = "x86_64", target_os = "linux"))]
+mod log {
+    use super::*;
+    use std::sync::Mutex;
+    use std::thread::ThreadId;
+
+    struct Capture(Mutex<Vec<(ThreadId, String, String)>>);
+
+    impl ::log::Log for Capture {
+        fn enabled(&self, _: &::log::Metadata) -> bool {
+            true
+        }
+
+        fn log(&self, record: &::log::Record) {
+            self.0.lock().unwrap().push((
+                std::thread::current().id(),
+                record.target().to_owned(),
+                record.args().to_string(),
+            ));
+        }
+
+        fn flush(&self) {}
+    }
+
+    static CAPTURE: Capture = Capture(Mutex::new(Vec::new()));
+
+    #[test]
+    fn layout() {
+        ::log::set_logger(&CAPTURE).unwrap();
+        ::log::set_max_level(::log::LevelFilter::Trace);
+
+        let mut func = Func::from_raw(cdecl_func::more_than_6_args as *const fn());
+        func.push_args((1i32, 2i32, 3i32, 4i32, 5i32, 6i32, 7i32, 8i32));
+        // 多余的浮点参数会被忽略, 只是为了观察布局
+        func.push(0.5f64);
+        unsafe { func.cdecl() };
+        let ret = func.ret();
+        assert_eq!(ret.as_i32(), 36);
+
+        let me = std::thread::current().id();
+        let lines = |target: &str| {
+            CAPTURE
+                .0
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|(id, t, _)| *id == me && t == target)
+                .map(|(_, _, line)| line.clone())
+                .collect::<Vec<_>>()
+        };
+        // 开启 provenance 时参数压入的位置输出到单独的 target, 不会插入布局之中
+        #[cfg(feature = "provenance")]
+        {
+            let origins = lines("funcall::provenance");
+            assert_eq!(origins.len(), 9, "{:?}", origins);
+            assert!(
+                origins[0].starts_with("arg 0 (i32 1) pushed at tests/tests.rs:"),
+                "{:?}",
+                origins
+            );
+        }
+        let lines = lines("funcall::plan");
+        assert!(lines[0].starts_with("call 0x"), "{:?}", lines);
+        assert_eq!(
+            lines[1..].to_vec(),
+            vec![
+                "xmm0 = 0x3fe0000000000000 (0.5)".to_owned(),
+                "rdi = 0x1".to_owned(),
+                "rsi = 0x2".to_owned(),
+                "rdx = 0x3".to_owned(),
+                "rcx = 0x4".to_owned(),
+                "r8 = 0x5".to_owned(),
+                "r9 = 0x6".to_owned(),
+                "al = 0x1".to_owned(),
+                "[sp+0x0] = 0x7".to_owned(),
+                "[sp+0x8] = 0x8".to_owned(),
+                format!(
+                    "rax = {:#x}, rdx = {:#x}, xmm0 = {:#x} ({})",
+                    ret.low,
+                    ret.high,
+                    ret.float.to_bits(),
+                    ret.float
+                ),
+            ]
+        );
+    }
+}
+
+mod dry_run {
+    use super::*;
+    use funcall::CallPlan;
+
+    /// 将调用计划输出为便于比较的文本, 每个参数一行
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    fn render(plan: &CallPlan) -> Vec<String> {
+        let mut lines = plan
+            .args
+            .iter()
+            .map(|arg| {
+                let locations = arg
+                    .locations
+                    .iter()
+                    .map(|l| l.to_string())
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                let bytes = arg
+                    .bytes
+                    .iter()
+                    .map(|b| format!("{:02x}", b))
+                    .collect::<String>();
+                match arg.promotion {
+                    Some(promotion) => {
+                        format!("{}: {} {} {:?}", arg.value, locations, bytes, promotion)
+                    }
+                    None => format!("{}: {} {}", arg.value, locations, bytes),
+                }
+            })
+            .collect::<Vec<_>>();
+        lines.push(format!(
+            "float_regs={} stack={} padding={}",
+            plan.float_regs, plan.stack_size, plan.padding
+        ));
+        lines
+    }
+
+    #[test]
+    #[cfg(all(target_arch = "x86_64", not(windows)))]
+    fn ints_and_floats() {
+        let mut func = Func::from_raw(0x1000 as *const fn());
+        for i in 1..=9 {
+            if i <= 7 {
+                func.push(i);
+            }
+            func.push(i as f64);
+        }
+        assert_eq!(
+            render(&func.dry_run(Convention::Cdecl)),
+            vec![
+                "i32 1: rdi 0100000000000000 SignExtend",
+                "f64 1: xmm0 000000000000f03f",
+                "i32 2: rsi 0200000000000000 SignExtend",
+                "f64 2: xmm1 0000000000000040",
+                "i32 3: rdx 0300000000000000 SignExtend",
+                "f64 3: xmm2 0000000000000840",
+                "i32 4: rcx 0400000000000000 SignExtend",
+                "f64 4: xmm3 0000000000001040",
+                "i32 5: r8 0500000000000000 SignExtend",
+                "f64 5: xmm4 0000000000001440",
+                "i32 6: r9 0600000000000000 SignExtend",
+                "f64 6: xmm5 0000000000001840",
+                "i32 7: [sp+0x0] 0700000000000000 SignExtend",
+                "f64 7: xmm6 0000000000001c40",
+                "f64 8: xmm7 0000000000002040",
+                "f64 9: [sp+0x8] 0000000000002240",
+                "float_regs=8 stack=16 padding=0",
+            ]
+        );
+    }
+
+    #[test]
+    #[cfg(all(target_arch = "x86_64", not(windows)))]
+    fn wide_and_narrow() {
+        let mut func = Func::from_raw(0x1000 as *const fn());
+        func.push(-1i8)
+            .push(-1i128)
+            .push(0.5f32)
+            .push(7u16)
+            .push(0x2000 as *const u8)
+            .push(u64::MAX)
+            .push(-2i64);
+        assert_eq!(
+            render(&func.dry_run(Convention::Cdecl)),
+            vec![
+                "i8 -1: rdi ffffffffffffffff SignExtend",
+                "i128 -1: rsi rdx ffffffffffffffffffffffffffffffff",
+                "f32 0.5: xmm0 000000000000e03f FloatToDouble",
+                "u16 7: rcx 0700000000000000 ZeroExtend",
+                "ptr 0x2000: r8 0020000000000000",
+                "u64 18446744073709551615: r9 ffffffffffffffff",
+                "i64 -2: [sp+0x0] feffffffffffffff",
+                "float_regs=1 stack=16 padding=8",
+            ]
+        );
+    }
+
+    #[test]
+    #[cfg(all(target_arch = "x86_64", windows))]
+    fn win64() {
+        let mut func = Func::from_raw(0x1000 as *const fn());
+        func.push(1i32)
+            .push(2.0f64)
+            .push_float(0.5)
+            .push(3u8)
+            .push(-1i64)
+            .push(4.0f64);
+        let plan = func.dry_run(Convention::Cdecl);
+        assert_eq!(plan.shadow_space, 32);
+        assert_eq!(
+            render(&plan),
+            vec![
+                "i32 1: rcx 0100000000000000 SignExtend",
+                "f64 2: xmm1 0000000000000040",
+                "float 0.5: xmm2 0000003f00000000",
+                "u8 3: r9 0300000000000000 ZeroExtend",
+                "i64 -1: [sp+0x20] ffffffffffffffff",
+                "f64 4: [sp+0x28] 0000000000001040",
+                "float_regs=0 stack=48 padding=0",
+            ]
+        );
+    }
+
+    #[test]
+    #[cfg(target_arch = "x86")]
+    fn stack_only() {
+        let mut func = Func::from_raw(0x1000 as *const fn());
+        func.push(1i32)
+            .push(2.0f64)
+            .push(-1i64)
+            .push(0.5f32)
+            .push(3u8)
+            .push_float(0.25);
+        assert_eq!(
+            render(&func.dry_run(Convention::Stdcall)),
+            vec![
+                "i32 1: [sp+0x0] 01000000",
+                "f64 2: [sp+0x4] [sp+0x8] 0000000000000040",
+                "i64 -1: [sp+0xc] [sp+0x10] ffffffffffffffff",
+                "f32 0.5: [sp+0x14] [sp+0x18] 000000000000e03f FloatToDouble",
+                "u8 3: [sp+0x1c] 03000000 ZeroExtend",
+                "float 0.25: [sp+0x20] 0000803e",
+                "float_regs=0 stack=48 padding=12",
+            ]
+        );
+    }
+
+    #[test]
+    fn stack_bytes() {
+        let word = std::mem::size_of::<usize>();
+        let mut func = Func::from_raw(0x1000 as *const fn());
+        assert_eq!(func.stack_bytes(), 0);
+        func.push(1i32).push(2.0f64).push(3u64);
+        let expected = if cfg!(target_arch = "x86") {
+            4 + 8 + 8
+        } else {
+            0
+        };
+        assert_eq!(func.stack_bytes(), expected);
+
+        for i in 0..8 {
+            func.push(i);
+        }
+        let plan = func.dry_run(Convention::Cdecl);
+        assert_eq!(
+            func.stack_bytes(),
+            plan.stack_size - plan.padding - plan.shadow_space
+        );
+        assert_eq!(func.stack_bytes() % word, 0);
+    }
+
+    /// 每个参数的各个字所处的位置
+    #[cfg(any(
+        target_arch = "x86",
+        target_arch = "arm",
+        target_arch = "mips64",
+        target_arch = "mips64r6",
+        target_arch = "s390x",
+        target_arch = "loongarch64",
+        target_arch = "aarch64",
+        all(target_arch = "x86_64", not(windows))
+    ))]
+    fn locations(plan: &CallPlan) -> Vec<String> {
+        plan.args
+            .iter()
+            .map(|arg| {
+                let locations = arg.locations.iter().map(|l| l.to_string());
+                locations.collect::<Vec<_>>().join(" ")
+            })
+            .collect()
+    }
+
+    #[test]
+    #[cfg(all(target_arch = "x86_64", not(windows)))]
+    fn aligned_on_stack() {
+        // 栈上的 i128 默认按 16 字节对齐, 寄存器中的参数不受 push_aligned 影响
+        let mut func = Func::from_raw(0x1000 as *const fn());
+        func.push_aligned(1u8, 16);
+        func.push_args((2, 3, 4, 5, 6, 7)).push(-1i128);
+        func.push_aligned(8u32, 32).push(9i32);
+        assert_eq!(
+            locations(&func.dry_run(Convention::Cdecl)),
+            vec![
+                "rdi",
+                "rsi",
+                "rdx",
+                "rcx",
+                "r8",
+                "r9",
+                "[sp+0x0]",
+                "[sp+0x10] [sp+0x18]",
+                "[sp+0x20]",
+                "[sp+0x28]",
+            ]
+        );
+        assert_eq!(func.stack_bytes(), 48);
+    }
+
+    #[test]
+    #[cfg(target_arch = "x86")]
+    fn aligned_on_stack() {
+        // 32 位下 i64 只需 4 字节对齐, 更大的对齐通过 push_aligned 指定
+        let mut func = Func::from_raw(0x1000 as *const fn());
+        func.push(1i32).push(2i64).push(3i32);
+        func.push_aligned(4i64, 8).push_aligned(5u8, 16);
+        assert_eq!(
+            locations(&func.dry_run(Convention::Cdecl)),
+            vec![
+                "[sp+0x0]",
+                "[sp+0x4] [sp+0x8]",
+                "[sp+0xc]",
+                "[sp+0x10] [sp+0x14]",
+                "[sp+0x20]",
+            ]
+        );
+        assert_eq!(func.stack_bytes(), 36);
+    }
+
+    /// AAPCS 中的 64 位参数从偶数号寄存器或 8 字节对齐的栈位置开始
+    #[test]
+    #[cfg(target_arch = "arm")]
+    fn aapcs_pairs() {
+        let mut func = Func::from_raw(0x1000 as *const fn());
+        func.push(1i32).push(2i64).push(3i32).push(4.0f64);
+        assert_eq!(
+            locations(&func.dry_run(Convention::Cdecl)),
+            vec!["r0", "r2 r3", "[sp+0x0]", "[sp+0x8] [sp+0xc]"]
+        );
+        assert_eq!(func.stack_bytes(), 16);
+
+        // 放不进剩下的一个寄存器时整个参数都在栈上, 之后的参数也不再使用寄存器
+        let mut func = Func::from_raw(0x1000 as *const fn());
+        func.push(1i32).push(2i32).push(3i32).push(4u64).push(5i32);
+        func.push_aligned(6i32, 8);
+        assert_eq!(
+            locations(&func.dry_run(Convention::Cdecl)),
+            vec![
+                "r0",
+                "r1",
+                "r2",
+                "[sp+0x0] [sp+0x4]",
+                "[sp+0x8]",
+                "[sp+0x10]"
+            ]
+        );
+    }
+
+    /// n64 中浮点数按位置使用 f12~f19, i128 从偶数号寄存器开始, 可变参数部分的浮点数使用整数寄存器
+    #[test]
+    #[cfg(any(target_arch = "mips64", target_arch = "mips64r6"))]
+    fn n64_registers() {
+        let mut func = Func::from_raw(0x1000 as *const fn());
+        func.push(1i32).push(2.0f64).push_float(3.5).push(4i64);
+        func.push(-1i128).push(5u8).push_args((6, 7, 8));
+        assert_eq!(
+            locations(&func.dry_run(Convention::Cdecl)),
+            vec!["a0", "f13", "f14", "a3", "a4 a5", "a6", "a7", "[sp+0x0]", "[sp+0x8]"]
+        );
+        assert_eq!(func.stack_bytes(), 16);
+
+        let mut func = Func::from_raw(0x1000 as *const fn());
+        func.set_arity(2, true);
+        func.push(1i32).push(2.0f64).push(3.0f64);
+        assert_eq!(
+            locations(&func.dry_run(Convention::Cdecl)),
+            vec!["a0", "f13", "a2"]
+        );
+    }
+
+    /// s390x 中整数与浮点数各自分配 r2~r6 与 f0~f6, 栈上的参数位于 160 字节的寄存器保存区之上
+    #[test]
+    #[cfg(target_arch = "s390x")]
+    fn s390x_registers() {
+        let mut func = Func::from_raw(0x1000 as *const fn());
+        func.push(1i32).push(2.0f64).push_float(3.5).push(4i64);
+        func.push(5.0f64).push(6.0f64).push(7.0f64);
+        func.push_args((8, 9, 10, 11));
+        let plan = func.dry_run(Convention::Cdecl);
+        assert_eq!(
+            locations(&plan),
+            vec![
+                "r2",
+                "f0",
+                "f2",
+                "r3",
+                "f4",
+                "f6",
+                "[sp+0xa0]",
+                "r4",
+                "r5",
+                "r6",
+                "[sp+0xa8]"
+            ]
+        );
+        assert_eq!(plan.shadow_space, 160);
+        assert_eq!(func.stack_bytes(), 16);
+    }
+
+    /// LP64D 中浮点寄存器用完后的浮点参数与可变参数部分的浮点数使用整数寄存器, u32 被符号扩展
+    #[test]
+    #[cfg(target_arch = "loongarch64")]
+    fn lp64d_registers() {
+        let mut func = Func::from_raw(0x1000 as *const fn());
+        for i in 0..9 {
+            func.push(i as f64);
+        }
+        func.push(1i32).push(u32::MAX);
+        let plan = func.dry_run(Convention::Cdecl);
+        assert_eq!(
+            locations(&plan),
+            vec!["fa0", "fa1", "fa2", "fa3", "fa4", "fa5", "fa6", "fa7", "a0", "a1", "a2"]
+        );
+        assert_eq!(
+            plan.args[10].promotion,
+            Some(funcall::Promotion::SignExtend)
+        );
+        assert_eq!(plan.args[10].bytes, [0xff; 8]);
+
+        let mut func = Func::from_raw(0x1000 as *const fn());
+        func.set_arity(1, true);
+        func.push(1.0f64).push(2.0f64).push(3i32);
+        assert_eq!(
+            locations(&func.dry_run(Convention::Cdecl)),
+            vec!["fa0", "a0", "a1"]
+        );
+    }
+
+    /// AAPCS64 中整数与浮点数各自分配 x0~x7 与 v0~v7, i128 从偶数号寄存器开始,
+    /// 放不进寄存器的整数参数之后的整数参数也都在栈上
+    #[test]
+    #[cfg(target_arch = "aarch64")]
+    fn aapcs64_registers() {
+        let mut func = Func::from_raw(0x1000 as *const fn());
+        func.push(1i32).push(2.0f64).push_float(3.5).push(-1i128);
+        func.push_args((4, 5, 6, 7, 8))
+            .push(-2i128)
+            .push(9i32)
+            .push(10.0f64);
+        let plan = func.dry_run(Convention::Cdecl);
+        assert_eq!(
+            locations(&plan),
+            vec![
+                "x0",
+                "v0",
+                "v1",
+                "x2 x3",
+                "x4",
+                "x5",
+                "x6",
+                "x7",
+                "[sp+0x0]",
+                "[sp+0x10] [sp+0x18]",
+                "[sp+0x20]",
+                "v2"
+            ]
+        );
+        assert_eq!(func.stack_bytes(), 40);
+    }
+
+    /// 可变参数部分的浮点数在 Linux 下使用 v0~v7, Windows 下使用整数寄存器, Apple arm64 下与整数一起放在栈上
+    #[test]
+    #[cfg(target_arch = "aarch64")]
+    fn aarch64_variadic() {
+        let mut func = Func::from_raw(0x1000 as *const fn());
+        func.set_arity(2, true);
+        func.push(1usize).push(2usize).push(3i32).push(4.0f64);
+        let expected = if cfg!(windows) {
+            ["x0", "x1", "x2", "x3"]
+        } else if cfg!(target_vendor = "apple") {
+            ["x0", "x1", "[sp+0x0]", "[sp+0x8]"]
+        } else {
+            ["x0", "x1", "x2", "v0"]
+        };
+        assert_eq!(locations(&func.dry_run(Convention::Cdecl)), expected);
+    }
+
+    #[test]
+    fn bytes_in_memory_order() {
+        // 多个字的参数按内存中的表示分割, 大端序平台上同样如此
+        let value = 0x0102_0304_0506_0708_090a_0b0c_0d0e_0f10u128;
+        let mut func = Func::from_raw(0x1000 as *const fn());
+        func.push(value).push(0x1112_1314_1516_1718u64);
+        let plan = func.dry_run(Convention::Cdecl);
+        assert_eq!(plan.args[0].bytes, value.to_ne_bytes());
+        assert_eq!(plan.args[1].bytes, 0x1112_1314_1516_1718u64.to_ne_bytes());
+    }
+
+    #[test]
+    fn aligned_padding_is_zero() {
+        // 空位不属于任何参数, 值与对齐无关的参数相同
+        let mut plain = Func::from_raw(0x1000 as *const fn());
+        plain.push(1i32).push(2u64);
+        let mut aligned = Func::from_raw(0x1000 as *const fn());
+        aligned.push(1i32).push_aligned(2u64, 64);
+        let plain = plain.dry_run(Convention::Cdecl);
+        let aligned = aligned.dry_run(Convention::Cdecl);
+        for (plain, aligned) in plain.args.iter().zip(aligned.args.iter()) {
+            assert_eq!(plain.value, aligned.value);
+            assert_eq!(plain.bytes, aligned.bytes);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "not a power of two")]
+    fn aligned_not_power_of_two() {
+        Func::from_raw(0x1000 as *const fn()).push_aligned(1i32, 12);
+    }
+
+    #[test]
+    fn matches_call() {
+        // 计划中的值与实际调用时函数收到的值一致
+        let c = 4u8;
+        let mut func = Func::from_raw(cdecl_func::mixed_args as *const fn());
+        func.push(1i32)
+            .push(0.5f64)
+            .push(&c as *const u8)
+            .push(2u64);
+        let plan = func.dry_run(Convention::Cdecl);
+        assert_eq!(plan.args.len(), 4);
+        assert_eq!(plan.args[1].bytes, 0.5f64.to_bits().to_ne_bytes());
+        unsafe { func.cdecl() };
+        assert_eq!(func.ret_as_f64(), 1420.5);
+    }
+}
+
+#[cfg(feature = "std")]
+mod registry {
+    use super::*;
+
+    extern "C" fn host_add(a: i32, b: i32) -> i32 {
+        a + b
+    }
+
+    extern "C" fn host_sub(a: i32, b: i32) -> i32 {
+        a - b
+    }
+
+    #[test]
+    fn call() {
+        assert!(!funcall::register("host_add", host_add as *const fn()));
+        let mut func = Func::from_registry("host_add").unwrap();
+        assert_eq!(func.symbol_name(), Some("host_add"));
+        func.push(1i32).push(2i32);
+        unsafe { func.try_call(Convention::Cdecl).unwrap() };
+        assert_eq!(func.ret_as_i32(), 3);
+    }
+
+    #[test]
+    fn overwrite_and_unregister() {
+        funcall::register("host_op", host_add as *const fn());
+        let mut old = Func::from_registry("host_op").unwrap();
+        assert!(funcall::register("host_op", host_sub as *const fn()));
+        let mut new = Func::from_registry("host_op").unwrap();
+        assert!(funcall::unregister("host_op"));
+        assert!(!funcall::unregister("host_op"));
+        assert_eq!(
+            Func::from_registry("host_op").unwrap_err().kind(),
+            std::io::ErrorKind::NotFound
+        );
+
+        // 已经取得的 Func 仍然指向注册时的函数
+        old.push(5i32).push(3i32);
+        new.push(5i32).push(3i32);
+        unsafe {
+            old.cdecl();
+            new.cdecl();
+        }
+        assert_eq!(old.ret_as_i32(), 8);
+        assert_eq!(new.ret_as_i32(), 2);
+    }
+
+    #[test]
+    fn signature() {
+        let sig = Signature::parse("int host_add(int, int)").unwrap();
+        funcall::register_with(
+            "host_add_typed",
+            Func::from_raw(host_add as *const fn()).share(),
+            Some(sig.clone()),
+        );
+        assert_eq!(
+            funcall::registered_signature("host_add_typed"),
+            Some(sig.clone())
+        );
+
+        let mut func = Func::from_registry("host_add_typed").unwrap();
+        sig.push_checked(&mut func, 0, Arg::I64(40)).unwrap();
+        assert_eq!(
+            unsafe { func.try_call(Convention::Cdecl) },
+            Err(CallError::ArgCountMismatch {
+                expected: 2,
+                variadic: false,
+                got: 1
+            })
+        );
+        sig.push_checked(&mut func, 1, Arg::U8(2)).unwrap();
+        unsafe { func.try_call(Convention::Cdecl).unwrap() };
+        assert_eq!(func.ret_as_i32(), 42);
+    }
+}
+
+#[cfg(feature = "libffi")]
+mod libffi {
+    use super::*;
+    use funcall::{ArgKind, Backend};
+
+    /// 通过 libffi 调用, 有手写汇编时两个后端的结果应当相同
+    fn call<T: PartialEq + std::fmt::Debug>(
+        target: *const fn(),
+        args: &[Arg],
+        kind: Option<ArgKind>,
+        get: impl Fn(&RetValues) -> T,
+    ) -> T {
+        let call = |backend| {
+            let mut func = Func::from_raw_with(target, args.to_vec());
+            if let Some(kind) = kind {
+                func.set_ret_kind(kind);
+            }
+            unsafe { func.try_call_with(Convention::Cdecl, backend).unwrap() };
+            get(&func.ret())
+        };
+        let ret = call(Backend::Libffi);
+        if Backend::Asm.supports(Convention::Cdecl) {
+            assert_eq!(ret, call(Backend::Asm), "{:?}", args);
+        }
+        ret
+    }
+
+    /// 以 conv 经由 libffi 调用一个混合整数与浮点参数的函数, 返回结果
+    fn call_as(conv: Convention) -> f64 {
+        let c = 3u8;
+        let mut func = match conv {
+            #[cfg(target_arch = "x86")]
+            Convention::Stdcall => {
+                let mut func = Func::from_raw(cdecl_func::mixed_stdcall as *const fn());
+                func.push(-2i64).push(0.5f64).push(3).push(7u64);
+                func
+            }
+            #[cfg(all(target_arch = "x86", windows))]
+            Convention::System => return call_as(Convention::Stdcall),
+            #[cfg(target_arch = "arm")]
+            Convention::AapcsSoftFloat => {
+                let mut func = Func::from_raw(cdecl_func::soft_scale as *const fn());
+                func.set_arity(2, false);
+                func.push(1.5f64).push(4.0f32);
+                func
+            }
+            #[cfg(target_arch = "arm")]
+            Convention::AapcsVfp => {
+                let mut func = Func::from_raw(cdecl_func::native_scale as *const fn());
+                func.set_arity(2, false);
+                func.push(1.5f64).push(4.0f32);
+                func
+            }
+            _ => {
+                let mut func = Func::from_raw(cdecl_func::mixed_args as *const fn());
+                func.push(1).push(0.5f64).push(&c as *const u8).push(4u64);
+                func
+            }
+        };
+        func.set_ret_kind(ArgKind::F64);
+        unsafe { func.try_call_with(conv, Backend::Libffi).unwrap() };
+        func.ret_as_f64()
+    }
+
+    /// libffi 支持的每一种调用约定都要按各自的 ABI 值真正经过 libffi
+    #[test]
+    fn conventions() {
+        let all = [
+            Convention::Cdecl,
+            Convention::Stdcall,
+            Convention::System,
+            Convention::Thiscall,
+            Convention::Fastcall,
+            Convention::AapcsSoftFloat,
+            Convention::AapcsVfp,
+        ];
+        let mut called = 0;
+        for conv in all {
+            if !Backend::Libffi.supports(conv) {
+                continue;
+            }
+            let expected = match conv {
+                Convention::Stdcall => -1913.0,
+                Convention::System if cfg!(all(target_arch = "x86", windows)) => -1913.0,
+                Convention::AapcsSoftFloat | Convention::AapcsVfp => 6.0,
+                _ => 1340.5,
+            };
+            assert_eq!(call_as(conv), expected, "{:?}", conv);
+            called += 1;
+        }
+        assert!(called >= 2);
+    }
+
+    #[test]
+    fn backend() {
+        let expected = if Backend::Asm.supports(Convention::Cdecl) {
+            Backend::Asm
+        } else {
+            Backend::Libffi
+        };
+        assert_eq!(Convention::Cdecl.backend(), Some(expected));
+    }
+
+    #[test]
+    fn returns() {
+        let ret = call(
+            cdecl_func::return_i8 as *const fn(),
+            &[Arg::I8(-1)],
+            None,
+            RetValues::as_i8,
+        );
+        assert_eq!(ret, -1);
+        let ret = call(
+            cdecl_func::return_u8 as *const fn(),
+            &[Arg::U8(1)],
+            None,
+            RetValues::as_u8,
+        );
+        assert_eq!(ret, 1);
+        let ret = call(
+            cdecl_func::return_isize as *const fn(),
+            &[Arg::Isize(-1)],
+            None,
+            RetValues::as_isize,
+        );
+        assert_eq!(ret, -1);
+        let ret = call(
+            cdecl_func::return_i64 as *const fn(),
+            &[Arg::I64(-1)],
+            Some(ArgKind::I64),
+            RetValues::as_i64,
+        );
+        assert_eq!(ret, -1);
+        let ret = call(
+            cdecl_func::return_u64 as *const fn(),
+            &[Arg::U64(u64::MAX - 1)],
+            Some(ArgKind::U64),
+            RetValues::as_u64,
+        );
+        assert_eq!(ret, u64::MAX - 1);
+        let ret = call(
+            cdecl_func::return_f64 as *const fn(),
+            &[Arg::F64(123.456)],
+            Some(ArgKind::F64),
+            RetValues::as_f64,
+        );
+        assert_eq!(ret, 123.456);
+    }
+
+    #[test]
+    #[cfg(any(
+        all(target_arch = "x86_64", not(windows)),
+        target_arch = "mips64",
+        target_arch = "mips64r6"
+    ))]
+    fn return_i128() {
+        let ret = call(
+            cdecl_func::return_i128 as *const fn(),
+            &[Arg::I128(-1 << 100)],
+            Some(ArgKind::I128),
+            RetValues::as_i128,
+        );
+        assert_eq!(ret, -1 << 100);
+    }
+
+    #[test]
+    #[cfg(all(target_arch = "x86_64", not(windows)))]
+    fn int128_on_stack() {
+        let mut args = (1..=7).map(Arg::U64).collect::<Vec<_>>();
+        args.push(Arg::U128(0x1234 << 64 | 0x10));
+        let ret = call(
+            cdecl_func::int128_aligned as *const fn(),
+            &args,
+            Some(ArgKind::U64),
+            RetValues::as_u64,
+        );
+        assert_eq!(ret, 0x1234 * 3 + 0x10 * 5 + 7 * 7);
+    }
+
+    #[test]
+    fn return_f32() {
+        // 声明返回 float 时两种后端取回的位模式相同
+        let ret = call(
+            cdecl_func::return_denormal as *const fn(),
+            &[],
+            Some(ArgKind::CFloat),
+            RetValues::as_f32,
+        );
+        assert_eq!(ret.to_bits(), 1);
+
+        let mut func = Func::from_raw(cdecl_func::return_f32 as *const fn());
+        func.push_float(123.456);
+        func.set_ret_kind(ArgKind::CFloat);
+        unsafe {
+            func.try_call_with(Convention::Cdecl, Backend::Libffi)
+                .unwrap()
+        };
+        assert_eq!(func.ret_as_f32(), 123.456);
+    }
+
+    #[test]
+    fn many_args() {
+        let ret = call(
+            cdecl_func::more_than_6_args as *const fn(),
+            &(1..=8).map(Arg::I32).collect::<Vec<_>>(),
+            None,
+            RetValues::as_i32,
+        );
+        assert_eq!(ret, 36);
+        let ret = call(
+            cdecl_func::eight_floats as *const fn(),
+            &(1..=8).map(|i| Arg::F64(f64::from(i))).collect::<Vec<_>>(),
+            Some(ArgKind::F64),
+            RetValues::as_f64,
+        );
+        assert_eq!(ret, 36.0);
+    }
+
+    #[test]
+    fn mixed_args() {
+        let c = 3u8;
+        let ret = call(
+            cdecl_func::mixed_args as *const fn(),
+            &[
+                Arg::I32(1),
+                Arg::F64(0.5),
+                Arg::Ptr(&c as *const u8 as usize),
+                Arg::U64(4),
+            ],
+            Some(ArgKind::F64),
+            RetValues::as_f64,
+        );
+        assert_eq!(ret, 1340.5);
+    }
+
+    #[test]
+    #[cfg(all(feature = "loader", target_os = "linux"))]
+    fn variadic() {
+        let snprintf = funcall::Library::new("libc.so.6")
+            .unwrap()
+            .get("snprintf")
+            .unwrap();
+        let sig = Signature::parse("int snprintf(char*, size_t, const char*, ...)").unwrap();
+        let mut buf = vec![0u8; 32];
+        let mut func = snprintf.clone();
+        sig.prepare(&mut func);
+        let args = [
+            Arg::Ptr(buf.as_mut_ptr() as usize),
+            Arg::Usize(32),
+            Arg::Str("%d %.1f %s".into()),
+            Arg::I32(-1),
+            Arg::F64(0.5),
+            Arg::Str("a".into()),
+        ];
+        for (i, arg) in args.iter().enumerate() {
+            sig.push_checked(&mut func, i, arg.clone()).unwrap();
+        }
+        unsafe {
+            func.try_call_with(Convention::Cdecl, Backend::Libffi)
+                .unwrap()
+        };
+        assert_eq!(func.ret_as_i32(), 8);
+        assert_eq!(&buf[..9], b"-1 0.5 a\0");
+    }
+
+    #[test]
+    #[cfg(target_arch = "x86")]
+    fn stdcall() {
+        let mut func = Func::from_raw(cdecl_func::mixed_stdcall as *const fn());
+        func.push(-2i64).push(0.5f64).push(3).push(7u64);
+        func.set_ret_kind(ArgKind::F64);
+        unsafe {
+            func.try_call_with(Convention::Stdcall, Backend::Libffi)
+                .unwrap()
+        };
+        assert_eq!(func.ret_as_f64(), -1913.0);
+    }
+}
+
+#[cfg(all(feature = "std", target_arch = "x86_64", target_os = "linux"))]
+mod jit {
+    use super::*;
+    use funcall::Backend;
+    use std::sync::Arc;
+
+    /// 通过跳板调用的结果应当与内联汇编相同
+    fn same_as_asm<T: PartialEq + std::fmt::Debug>(
+        proto: &str,
+        target: *const fn(),
+        args: &[Arg],
+        get: impl Fn(&RetValues) -> T,
+    ) -> T {
+        let sig = Signature::parse(proto).unwrap();
+        let jit = sig.compile_with(Convention::Cdecl, Backend::Jit);
+        assert!(jit.trampoline().is_some());
+        let expected =
+            get(&unsafe { sig.compile(Convention::Cdecl).invoke(target, args) }.unwrap());
+        for _ in 0..2 {
+            let ret = unsafe { jit.invoke(target, args) }.unwrap();
+            assert_eq!(get(&ret), expected, "{}", proto);
+        }
+        expected
+    }
+
+    #[test]
+    fn invoke() {
+        let c = 3u8;
+        let ret = same_as_asm(
+            "double mixed_args(int, double, const unsigned char*, uint64_t)",
+            cdecl_func::mixed_args as *const fn(),
+            &[
+                Arg::I32(1),
+                Arg::F64(0.5),
+                Arg::Ptr(&c as *const u8 as usize),
+                Arg::U64(4),
+            ],
+            RetValues::as_f64,
+        );
+        assert_eq!(ret, 1340.5);
+
+        let ret = same_as_asm(
+            "int more_than_6_args(int, int, int, int, int, int, int, int)",
+            cdecl_func::more_than_6_args as *const fn(),
+            &(1..=8).map(Arg::I32).collect::<Vec<_>>(),
+            RetValues::as_i32,
+        );
+        assert_eq!(ret, 36);
+
+        let ret = same_as_asm(
+            "double eight_floats(double, double, double, double, double, double, double, double)",
+            cdecl_func::eight_floats as *const fn(),
+            &(1..=8).map(|i| Arg::F64(f64::from(i))).collect::<Vec<_>>(),
+            RetValues::as_f64,
+        );
+        assert_eq!(ret, 36.0);
+    }
+
+    #[test]
+    fn stack_alignment() {
+        // 奇数个与偶数个栈上参数都需要保持对齐
+        for &n in &[13usize, 14] {
+            let mut func = if n == 13 {
+                Func::from_raw(cdecl_func::movaps_7 as *const fn())
+            } else {
+                Func::from_raw(cdecl_func::movaps_8 as *const fn())
+            };
+            for i in 0..n {
+                func.push(i);
+            }
+            unsafe { func.try_call_with(Convention::Cdecl, Backend::Jit).unwrap() };
+            assert_eq!(func.ret_as_usize(), (0..n).sum::<usize>());
+        }
+    }
+
+    #[test]
+    fn float_return() {
+        let mut func = Func::from_raw(cdecl_func::return_third as *const fn());
+        func.set_ret_kind(funcall::ArgKind::CFloat);
+        unsafe { func.try_call_with(Convention::Cdecl, Backend::Jit).unwrap() };
+        assert_eq!(func.ret_as_f32(), 1.0 / 3.0);
+    }
+
+    #[cfg(feature = "loader")]
+    #[test]
+    fn variadic() {
+        let snprintf = funcall::Library::new("libc.so.6")
+            .unwrap()
+            .get("snprintf")
+            .unwrap();
+        let call = Signature::parse("int snprintf(char*, size_t, const char*, ...)")
+            .unwrap()
+            .compile_with(Convention::Cdecl, Backend::Jit);
+        let mut buf = vec![0u8; 32];
+        let ret = unsafe {
+            call.invoke(
+                snprintf.as_raw(),
+                &[
+                    Arg::Ptr(buf.as_mut_ptr() as usize),
+                    Arg::I32(32),
+                    Arg::Str("%d %.1f".into()),
+                    Arg::I32(-1),
+                    Arg::F32(0.5),
+                ],
+            )
+        };
+        assert_eq!(ret.unwrap().as_i32(), 6);
+        assert_eq!(&buf[..7], b"-1 0.5\0");
+    }
+
+    #[test]
+    fn cache() {
+        // 参数布局为 11 个整数与 3 个浮点数, 其他测试不会用到
+        let proto =
+            "long f(int, int, int, int, int, int, int, int, int, int, long, float, double, float)";
+        let sig = Signature::parse(proto).unwrap();
+        let a = sig.compile_with(Convention::Cdecl, Backend::Jit);
+        let b = sig.compile_with(Convention::Cdecl, Backend::Jit);
+        let trampoline = a.trampoline().unwrap();
+        assert!(Arc::ptr_eq(trampoline, b.trampoline().unwrap()));
+        let other = Signature::parse(
+            "long f(int, int, int, int, int, int, int, int, int, int, long, float, double)",
+        )
+        .unwrap()
+        .compile_with(Convention::Cdecl, Backend::Jit);
+        assert!(!Arc::ptr_eq(trampoline, other.trampoline().unwrap()));
+
+        // 所有使用者都 drop 之后跳板即被释放
+        let weak = Arc::downgrade(trampoline);
+        drop(a);
+        assert!(weak.upgrade().is_some());
+        drop(b);
+        assert!(weak.upgrade().is_none());
+    }
+}
+
+mod validate_ptr {
+    use super::*;
+    use funcall::PtrError;
+
+    #[cfg(feature = "loader")]
+    #[test]
+    fn symbol() {
+        let func = Func::from_raw(cdecl_func::no_args as *const fn());
+        assert_eq!(func.validate_ptr(), Ok(()));
+        #[cfg(target_os = "linux")]
+        assert_eq!(
+            Func::new("libc.so.6", b"rand\0").unwrap().validate_ptr(),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn invalid() {
+        assert_eq!(
+            Func::from_raw(std::ptr::null()).validate_ptr(),
+            Err(PtrError::Null)
+        );
+        #[cfg(any(all(feature = "std", target_os = "linux"), windows))]
+        {
+            let heap = Box::new([0xc3u8; 64]);
+            let func = Func::from_raw(heap.as_ptr() as *const fn());
+            assert_eq!(
+                func.validate_ptr(),
+                Err(PtrError::NotExecutable(heap.as_ptr() as usize))
+            );
+        }
+    }
+
+    #[test]
+    fn paranoid() {
+        let heap = Box::new([0xc3u8; 64]);
+        let mut func = Func::from_raw(heap.as_ptr() as *const fn());
+        func.set_paranoid(true);
+        let err = unsafe { func.try_call(Convention::Cdecl).unwrap_err() };
+        assert!(matches!(err, CallError::InvalidTarget(_)));
+
+        let mut func = Func::from_raw(cdecl_func::no_args as *const fn());
+        func.set_paranoid(true);
+        unsafe { func.try_call(Convention::Cdecl).unwrap() };
+        assert_eq!(func.ret_as_i32(), 42);
+    }
+}
+
+mod checked {
+    use super::*;
+    use funcall::CType;
+
+    fn mixed_args() -> Func {
+        let mut func = Func::from_raw(cdecl_func::mixed_args as *const fn());
+        func.set_signature(
+            Signature::parse("double mixed_args(int, double, const unsigned char*, uint64_t)")
+                .unwrap(),
+        );
+        func
+    }
+
+    #[test]
+    fn accepted() {
+        let c = 3u8;
+        let mut func = mixed_args();
+        // i8 可以传给 int, f32 可以传给 double, i32 可以传给 uint64_t
+        func.push(1i8).push(2.5f32).push(&c as *const u8).push(4i32);
+        unsafe { func.try_call(Convention::Cdecl).unwrap() };
+        assert_eq!(func.ret_as_f64(), 1000.0 + 2.5 + 300.0 + 40.0);
+
+        let mut func = mixed_args();
+        func.try_push(1i64).unwrap().try_push(2.5f64).unwrap();
+        func.try_push_arg(Arg::Ptr(&c as *const u8 as usize))
+            .unwrap()
+            .try_push_arg(Arg::U8(4))
+            .unwrap();
+        unsafe { func.try_call(Convention::Cdecl).unwrap() };
+        assert_eq!(func.ret_as_f64(), 1000.0 + 2.5 + 300.0 + 40.0);
+    }
+
+    #[test]
+    fn rejected() {
+        let mut func = mixed_args();
+        assert_eq!(
+            func.try_push(1.5f64).unwrap_err(),
+            CallError::ArgTypeMismatch {
+                index: 0,
+                expected: CType::Int,
+                got: "F64(1.5)".into(),
+            }
+        );
+        // 超出 int 范围的值
+        assert!(func.try_push(1i64 << 40).is_err());
+        func.push(1i32);
+        assert!(func.try_push(b'a').is_ok());
+        // 整数不能作为指针
+        assert!(matches!(
+            func.try_push(0x1000usize),
+            Err(CallError::ArgTypeMismatch { index: 2, .. })
+        ));
+        func.push(std::ptr::null::<u8>());
+        // 负数不能传给无符号参数
+        assert!(func.try_push(-1i32).is_err());
+        func.push(1u64);
+        assert_eq!(
+            func.try_push(1i32).unwrap_err(),
+            CallError::ArgCountMismatch {
+                expected: 4,
+                variadic: false,
+                got: 5,
+            }
+        );
+    }
+
+    #[test]
+    fn reported_on_call() {
+        let c = 3u8;
+        let mut func = mixed_args();
+        // 被拒绝的参数与其后的参数都不会压入, 错误在调用时报告
+        func.push(1.5f64)
+            .push(2.5f64)
+            .push(&c as *const u8)
+            .push(4u64);
+        let err = unsafe { func.try_call(Convention::Cdecl).unwrap_err() };
+        assert!(matches!(err, CallError::ArgTypeMismatch { index: 0, .. }));
+        assert_eq!(func.arg_views().count(), 0);
+
+        // 参数不足
+        let mut func = mixed_args();
+        func.push(1i32).push(2.5f64);
+        let err = unsafe { func.try_call(Convention::Cdecl).unwrap_err() };
+        assert!(matches!(err, CallError::ArgCountMismatch { got: 2, .. }));
+    }
+
+    #[test]
+    fn raw() {
+        let mut func = mixed_args();
+        assert_eq!(func.signature().unwrap().params.len(), 4);
+        // push_raw 不经检查
+        func.push_raw(1.5f64).push_raw(2usize);
+        assert_eq!(func.arg_views().count(), 2);
+    }
+
+    #[test]
+    #[cfg(all(feature = "loader", target_os = "linux"))]
+    fn variadic() {
+        let mut func = Func::new("libc.so.6", b"sprintf\0").unwrap();
+        func.set_signature(Signature::parse("int sprintf(char*, const char*, ...)").unwrap());
+        let mut buf: Vec<std::os::raw::c_char> = vec![0; 32];
+        func.push(buf.as_mut_ptr())
+            .push_arg(Arg::Str("%.2f %d %s".into()))
+            .push(1.5f32)
+            .push(7i8)
+            .push_arg(Arg::Str("x".into()));
+        unsafe {
+            func.try_call(Convention::Cdecl).unwrap();
+            assert_eq!(CStr::from_ptr(buf.as_ptr()).to_str().unwrap(), "1.50 7 x");
+        }
+    }
+}
+
+#[cfg(all(feature = "protected", any(unix, all(windows, target_env = "msvc"))))]
+mod protected {
+    use super::*;
+    use funcall::{Fault, ProtectedError};
+
+    #[test]
+    fn unmapped() {
+        // 地址 0 附近的页面不会被映射
+        let mut func = Func::from_raw(0x10 as *const fn());
+        let fault = unsafe { func.call_protected(Convention::Cdecl) };
+        assert_eq!(
+            fault,
+            Err(ProtectedError::Fault(Fault::Segv { addr: 0x10 }))
+        );
+        assert_eq!(fault.unwrap_err().to_string(), "segmentation fault at 0x10");
+    }
+
+    #[test]
+    fn bad_read() {
+        for _ in 0..3 {
+            let mut func = Func::from_raw(cdecl_func::read_i32 as *const fn());
+            func.push(0x10usize);
+            let fault = unsafe { func.call_protected(Convention::Cdecl) };
+            assert_eq!(
+                fault,
+                Err(ProtectedError::Fault(Fault::Segv { addr: 0x10 }))
+            );
+            assert_eq!(func.ret_as_usize(), 0);
+        }
+
+        // 处理函数恢复后正常的调用不受影响
+        let n = 7;
+        let mut func = Func::from_raw(cdecl_func::read_i32 as *const fn());
+        func.push(&n as *const i32);
+        unsafe { func.call_protected(Convention::Cdecl).unwrap() };
+        assert_eq!(func.ret_as_i32(), 7);
+    }
+
+    #[test]
+    fn threads() {
+        let handles = (0..4)
+            .map(|i| {
+                std::thread::spawn(move || {
+                    let mut func = Func::from_raw(cdecl_func::read_i32 as *const fn());
+                    func.push(0x100usize * (i + 1));
+                    unsafe { func.call_protected(Convention::Cdecl) }
+                })
+            })
+            .collect::<Vec<_>>();
+        for (i, handle) in handles.into_iter().enumerate() {
+            let addr = 0x100 * (i + 1);
+            assert_eq!(
+                handle.join().unwrap(),
+                Err(ProtectedError::Fault(Fault::Segv { addr }))
+            );
+        }
+    }
+
+    // 调用前与 try_call 一样进行检查
+    #[test]
+    fn checked() {
+        let mut func = Func::null();
+        let ret = unsafe { func.call_protected(Convention::Cdecl) };
+        assert_eq!(ret, Err(ProtectedError::Call(CallError::NullTarget)));
+        assert_eq!(
+            ret.unwrap_err().to_string(),
+            CallError::NullTarget.to_string()
+        );
+
+        let mut func = Func::from_raw(cdecl_func::read_i32 as *const fn());
+        func.set_arity(1, false);
+        let ret = unsafe { func.call_protected(Convention::Cdecl) };
+        assert!(
+            matches!(
+                ret,
+                Err(ProtectedError::Call(CallError::ArgCountMismatch { .. }))
+            ),
+            "{:?}",
+            ret
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn stack_overflow() {
+        // 调用所在的线程设置了备用信号栈, 栈溢出同样可以捕获
+        let mut func = Func::from_raw(cdecl_func::recurse as *const fn());
+        func.push(1_000_000u32);
+        let fault = unsafe { func.call_protected(Convention::Cdecl) };
+        assert!(
+            matches!(fault, Err(ProtectedError::Fault(Fault::Segv { .. }))),
+            "{:?}",
+            fault
+        );
+    }
+}
+
+#[cfg(feature = "std")]
+mod timeout {
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn finished() {
+        let mut func = Func::from_raw(cdecl_func::sleep_ms as *const fn());
+        func.push(10u32);
+        let outcome = unsafe { func.call_with_timeout(Convention::Cdecl, Duration::from_secs(5)) };
+        let outcome = outcome.unwrap();
+        assert_eq!(outcome.ret.as_u32(), 10);
+        assert!(outcome.elapsed >= Duration::from_millis(10));
+        assert_eq!(func.ret_as_u32(), 10);
+        assert!(!func.is_poisoned());
+    }
+
+    #[cfg(all(feature = "loader", target_os = "linux"))]
+    #[test]
+    fn libc_sleep() {
+        let mut func = Func::new("libc.so.6", b"sleep\0").unwrap();
+        func.push(5u32);
+        let start = Instant::now();
+        let timeout = Duration::from_millis(100);
+        let outcome = unsafe { func.call_with_timeout(Convention::Cdecl, timeout) };
+        assert_eq!(
+            outcome,
+            Err(funcall::TimeoutError::TimedOut(funcall::Timeout {
+                timeout
+            }))
+        );
+        assert!(start.elapsed() < Duration::from_secs(2));
+        assert!(func.is_poisoned());
+        unsafe {
+            assert_eq!(func.try_call(Convention::Cdecl), Err(CallError::Poisoned));
+        }
+    }
+
+    #[test]
+    fn recovered() {
+        let mut func = Func::from_raw(cdecl_func::sleep_ms as *const fn());
+        func.push(300u32);
+        let outcome =
+            unsafe { func.call_with_timeout(Convention::Cdecl, Duration::from_millis(20)) };
+        assert!(outcome.is_err());
+        assert!(func.is_poisoned());
+
+        // 遗留的调用返回后即可再次使用
+        let start = Instant::now();
+        while func.is_poisoned() {
+            assert!(start.elapsed() < Duration::from_secs(5));
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        unsafe { func.try_call(Convention::Cdecl).unwrap() };
+        assert_eq!(func.ret_as_u32(), 300);
+    }
+
+    // 调用前与 try_call 一样进行检查, 通过 new_lazy 创建的函数在这里查找
+    #[cfg(all(feature = "loader", target_os = "linux"))]
+    #[test]
+    fn lazy() {
+        let mut func = Func::new_lazy("libc.so.6", "getpid");
+        let outcome = unsafe { func.call_with_timeout(Convention::Cdecl, Duration::from_secs(5)) };
+        assert_eq!(outcome.unwrap().ret.as_u32(), std::process::id());
+        assert!(!func.is_pending());
+    }
+
+    #[test]
+    fn checked() {
+        let mut func = Func::null();
+        let outcome = unsafe { func.call_with_timeout(Convention::Cdecl, Duration::from_secs(5)) };
+        assert_eq!(
+            outcome,
+            Err(funcall::TimeoutError::Call(CallError::NullTarget))
+        );
+        assert!(!func.is_poisoned());
+
+        // 超时后再次调用返回错误而不是 panic
+        let mut func = Func::from_raw(cdecl_func::sleep_ms as *const fn());
+        func.push(300u32);
+        let timeout = Duration::from_millis(20);
+        assert!(unsafe { func.call_with_timeout(Convention::Cdecl, timeout) }.is_err());
+        assert_eq!(
+            unsafe { func.call_with_timeout(Convention::Cdecl, timeout) },
+            Err(funcall::TimeoutError::Call(CallError::Poisoned))
+        );
+    }
+}
+
+#[cfg(feature = "std")]
+mod on_thread {
+    use super::*;
+
+    /// 约需要 4MiB 的栈
+    const DEPTH: u32 = 1024;
+
+    #[test]
+    fn large_stack() {
+        let mut func = Func::from_raw(cdecl_func::recurse as *const fn());
+        func.push(DEPTH);
+        unsafe { func.call_on_thread(Convention::Cdecl, 16 << 20).unwrap() };
+        assert_eq!(func.ret_as_u32(), DEPTH);
+    }
+
+    #[test]
+    fn checked() {
+        let mut func = Func::from_raw(std::ptr::null());
+        unsafe {
+            assert_eq!(
+                func.call_on_thread(Convention::Cdecl, 1 << 20),
+                Err(CallError::NullTarget)
+            );
+        }
+    }
+
+    /// 由 small_stack 在子进程中运行
+    #[test]
+    fn small_stack_child() {
+        if std::env::var_os("FUNCALL_SMALL_STACK").is_none() {
+            return;
+        }
+        let mut func = Func::from_raw(cdecl_func::recurse as *const fn());
+        func.push(DEPTH);
+        let _ = unsafe { func.call_on_thread(Convention::Cdecl, 128 << 10) };
+    }
+
+    #[test]
+    fn small_stack() {
+        // 栈溢出会终止整个进程, 只能在子进程中观察
+        let status = std::process::Command::new(std::env::current_exe().unwrap())
+            .args(["on_thread::small_stack_child", "--exact", "--nocapture"])
+            .env("FUNCALL_SMALL_STACK", "1")
+            .stderr(std::process::Stdio::null())
+            .status()
+            .unwrap();
+        assert!(!status.success());
+    }
+}
+
+#[cfg(all(feature = "recorder", target_os = "linux"))]
+mod recorder {
+    use super::*;
+    use funcall::{CallLog, Divergence, RecordedCall, Recorder, ReplayOptions};
+
+    #[test]
+    fn round_trip() {
+        let recorder = Recorder::attach();
+        for (i, value) in [1.5f64, -0.25, 1e300].iter().enumerate() {
+            let mut func = Func::new("libc.so.6", b"sprintf\0").unwrap();
+            func.push_arg(Arg::Bytes(vec![0; 400]))
+                .push_arg(Arg::Str("%d %.17g".into()))
+                .push(i as i32)
+                .push(*value);
+            unsafe { func.try_call(Convention::Cdecl).unwrap() };
+        }
+        let mut log = recorder.log();
+        drop(recorder);
+
+        // 其他测试可能同时在别的线程中调用函数
+        let me = std::thread::current().name().map(str::to_owned);
+        log.calls.retain(|call| call.thread == me);
+        assert_eq!(log.calls.len(), 3);
+        let call = &log.calls[2];
+        assert_eq!(call.symbol.as_deref(), Some("sprintf"));
+        assert_eq!(call.args[1], Arg::Bytes(b"%d %.17g\0".to_vec()));
+        assert_eq!(call.args[3], Arg::F64(1e300));
+        assert_eq!(call.written[0].0, 0);
+        assert!(call.written[0]
+            .1
+            .starts_with(b"2 1.0000000000000001e+300\0"));
+        assert_eq!(call.ret_low, 25);
+
+        let path = std::env::temp_dir().join(format!("funcall-replay-{}.json", std::process::id()));
+        log.save(&path).unwrap();
+        assert_eq!(CallLog::load(&path).unwrap(), log);
+        let report = unsafe { funcall::replay(&path, &ReplayOptions::default()).unwrap() };
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(report.replayed, 3);
+        assert!(report.is_identical(), "{:?}", report.divergences);
+    }
+
+    #[test]
+    fn divergence() {
+        let call = RecordedCall {
+            library: Some("libc.so.6".into()),
+            symbol: Some("abs".into()),
+            thread: None,
+            convention: Convention::Cdecl,
+            arity: None,
+            ret_kind: Some(funcall::ArgKind::I32),
+            args: vec![Arg::I32(-5)],
+            c_floats: vec![],
+            halves: vec![],
+            written: vec![],
+            ret_low: 4,
+            ret_high: 0,
+            ret_float: 0,
+        };
+        let missing = RecordedCall {
+            symbol: Some("no_such_function".into()),
+            ..call.clone()
+        };
+        let log = CallLog::new(vec![call.clone(), missing, call]);
+        let report = unsafe { funcall::replay_log(&log, &ReplayOptions::default()) };
+        assert_eq!(report.replayed, 3);
+        assert_eq!(report.divergences.len(), 3);
+        match &report.divergences[0] {
+            Divergence::Ret {
+                index,
+                expected,
+                got,
+            } => assert_eq!((*index, expected.low, got.low), (0, 4, 5)),
+            other => panic!("unexpected divergence {}", other),
+        }
+        assert!(matches!(
+            report.divergences[1],
+            Divergence::Failed { index: 1, .. }
+        ));
+
+        let options = ReplayOptions {
+            stop_on_divergence: true,
+            ..Default::default()
+        };
+        let report = unsafe { funcall::replay_log(&log, &options) };
+        assert_eq!(report.replayed, 1);
+    }
+}
+
+#[cfg(feature = "testing")]
+mod testing {
+    use funcall::testing::{self, Scalar, Shape, Value};
+    use funcall::Backend;
+    use proptest::prelude::*;
+
+    /// signaling NaN 在传递中可能被转为 quiet NaN, 统一换成 quiet NaN
+    fn float() -> impl Strategy<Value = f64> {
+        any::<f64>().prop_map(|v| if v.is_nan() { f64::NAN } else { v })
+    }
+
+    fn value(scalar: Scalar) -> BoxedStrategy<Value> {
+        match scalar {
+            Scalar::I8 => any::<i8>().prop_map(Value::I8).boxed(),
+            Scalar::U16 => any::<u16>().prop_map(Value::U16).boxed(),
+            Scalar::I32 => any::<i32>().prop_map(Value::I32).boxed(),
+            Scalar::U32 => any::<u32>().prop_map(Value::U32).boxed(),
+            Scalar::I64 => any::<i64>().prop_map(Value::I64).boxed(),
+            Scalar::U64 => any::<u64>().prop_map(Value::U64).boxed(),
+            Scalar::I128 => any::<i128>().prop_map(Value::I128).boxed(),
+            Scalar::U128 => any::<u128>().prop_map(Value::U128).boxed(),
+            Scalar::F32 => float().prop_map(|v| Value::F32(v as f32)).boxed(),
+            Scalar::F64 => float().prop_map(Value::F64).boxed(),
+            Scalar::Ptr => any::<usize>().prop_map(Value::Ptr).boxed(),
+        }
+    }
+
+    /// 随机选取一个参考函数与它的参数, 出错时 proptest 会缩小到最简单的参数
+    fn case() -> impl Strategy<Value = (Shape, Vec<Value>)> {
+        prop::sample::select(testing::shapes()).prop_flat_map(|shape| {
+            let args = shape.params.iter().map(|&scalar| value(scalar));
+            (Just(shape), args.collect::<Vec<_>>())
+        })
+    }
+
+    fn check(backend: Backend) {
+        proptest!(ProptestConfig::with_cases(2000), |(case in case())| {
+            let (shape, args) = case;
+            prop_assert_eq!(
+                shape.call_func(&args, backend),
+                shape.call_direct(&args),
+                "{} with {:?}",
+                shape.name,
+                args
+            );
+        });
+    }
+
+    #[test]
+    fn shapes() {
+        let shapes = testing::shapes();
+        assert!(shapes.iter().any(|shape| shape.params.is_empty()));
+        assert!(shapes.iter().any(|shape| shape.params.len() == 16));
+        assert!(shapes.iter().any(
+            |shape| shape.params.contains(&Scalar::F32) && shape.params.contains(&Scalar::I64)
+        ));
+    }
+
+    #[test]
+    fn asm() {
+        check(Backend::Asm);
+    }
+
+    #[cfg(feature = "libffi")]
+    #[test]
+    fn libffi() {
+        check(Backend::Libffi);
+    }
+
+    #[cfg(all(feature = "std", target_arch = "x86_64", target_os = "linux"))]
+    #[test]
+    fn jit() {
+        check(Backend::Jit);
+    }
+}
+
+mod spill {
+    use super::*;
+
+    #[test]
+    fn float_then_int() {
+        let mut func = Func::from_raw(cdecl_func::floats_then_int as *const fn());
+        for _ in 0..9 {
+            func.push(1.0f64);
+        }
+        func.push(2i32);
+        unsafe { func.try_call(Convention::Cdecl).unwrap() };
+        assert_eq!(func.ret_as_f64(), 20108.0);
+    }
+
+    #[cfg(all(target_arch = "x86_64", not(windows)))]
+    #[test]
+    fn int128() {
+        let mut func = Func::from_raw(cdecl_func::int128_spill as *const fn());
+        func.push_args((1u64, 2u64, 3u64, 4u64, 5u64));
+        func.push(0x1234u128 << 64 | 0x10).push(0x1000u64);
+        unsafe { func.try_call(Convention::Cdecl).unwrap() };
+        assert_eq!(func.ret_as_u64(), 0x1234 * 3 + 0x10 * 5 + 0x1000 * 7);
+    }
+}
+
+mod many_args {
+    use super::*;
+
+    fn mix(args: impl Iterator<Item = i64>) -> i64 {
+        args.fold(0i64, |sum, arg| sum.wrapping_mul(31).wrapping_add(arg))
+    }
+
+    #[test]
+    fn two_hundred() {
+        let mut func = Func::from_raw(cdecl_func::mix200 as *const fn());
+        for i in 0..200 {
+            func.push(i * 1_000_003 - 7);
+        }
+        unsafe { func.try_call(Convention::Cdecl).unwrap() };
+        assert_eq!(func.ret_as_i64(), mix((0..200).map(|i| i * 1_000_003 - 7)));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn probed() {
+        // 多出的参数被调用者忽略, 只用来使参数区跨越多个页面, 在很小的栈上也能正常调用
+        let mut func = Func::from_raw(cdecl_func::mix200 as *const fn());
+        for i in 0..4000i64 {
+            func.push(i);
+        }
+        unsafe { func.call_on_thread(Convention::Cdecl, 256 << 10).unwrap() };
+        assert_eq!(func.ret_as_i64(), mix(0..200));
+
+        #[cfg(all(target_arch = "x86_64", target_os = "linux"))]
+        {
+            unsafe {
+                func.try_call_with(Convention::Cdecl, funcall::Backend::Jit)
+                    .unwrap()
+            };
+            assert_eq!(func.ret_as_i64(), mix(0..200));
+        }
+    }
+
+    #[test]
+    fn limit() {
+        let mut func = Func::from_raw(cdecl_func::mix200 as *const fn());
+        for i in 0..200i64 {
+            func.push(i);
+        }
+        func.set_max_stack_bytes(256);
+        let err = unsafe { func.try_call(Convention::Cdecl) }.unwrap_err();
+        assert!(matches!(err, CallError::StackTooLarge { limit: 256, .. }));
+        assert!(err.to_string().ends_with("more than the limit of 256"));
+
+        // 默认的上限为 64 KiB
+        let mut func = Func::from_raw(cdecl_func::mix200 as *const fn());
+        for i in 0..10000i64 {
+            func.push(i);
+        }
+        let err = unsafe { func.try_call(Convention::Cdecl) }.unwrap_err();
+        assert!(matches!(err, CallError::StackTooLarge { limit: 65536, .. }));
+    }
+
+    #[test]
+    #[should_panic(expected = "more than the limit of 256")]
+    fn limit_unchecked() {
+        let mut func = Func::from_raw(cdecl_func::mix200 as *const fn());
+        for i in 0..200i64 {
+            func.push(i);
+        }
+        func.set_max_stack_bytes(256);
+        unsafe { func.cdecl() };
+    }
+}
+
+mod arg_sink {
+    use super::*;
+    use funcall::{ArgSink, IntoArg};
+
+    /// 按字写入的参数
+    struct Handle(usize);
+
+    impl IntoArg for Handle {
+        fn push_into(self, out: &mut ArgSink) {
+            out.push_word(self.0);
+        }
+    }
+
+    /// 按字节写入的参数
+    struct Bytes([u8; 8]);
+
+    impl IntoArg for Bytes {
+        const KIND: funcall::ArgKind = funcall::ArgKind::U64;
+
+        fn push_into(self, out: &mut ArgSink) {
+            assert_eq!(out.kind(), funcall::ArgKind::U64);
+            out.push_bytes(&self.0);
+            assert_eq!(out.size(), 8);
+        }
+    }
+
+    #[test]
+    fn words() {
+        let mut func = Func::from_raw(cdecl_func::return_usize as *const fn());
+        func.push(Handle(42));
+        unsafe { func.cdecl() };
+        assert_eq!(func.ret_as_usize(), 42);
+    }
+
+    #[test]
+    fn bytes() {
+        let mut func = Func::from_raw(cdecl_func::return_u64 as *const fn());
+        func.push(Bytes(0x0102_0304_0506_0708u64.to_ne_bytes()));
+        unsafe { func.cdecl() };
+        assert_eq!(func.ret_as_u64(), 0x0102_0304_0506_0708);
+    }
+
+    // 旧接口默认通过 `push_into` 实现
+    #[test]
+    #[allow(deprecated)]
+    fn into_arg() {
+        assert_eq!(Bytes([1, 0, 0, 0, 0, 0, 0, 0]).into_arg()[0], 1);
+        assert_eq!(7u8.into_arg(), vec![7]);
+    }
+}
+
+mod newtype {
+    use super::*;
+    use funcall::{ArgKind, ArgSink, IntoArg};
+
+    /// 转发 f64 实现的包装类型
+    #[repr(transparent)]
+    #[derive(Clone, Copy)]
+    struct Meters(f64);
+
+    impl IntoArg for Meters {
+        const KIND: ArgKind = f64::KIND;
+
+        fn push_into(self, out: &mut ArgSink) {
+            self.0.push_into(out);
+        }
+    }
+
+    #[repr(transparent)]
+    #[derive(Clone, Copy)]
+    struct Single(f32);
+
+    impl IntoArg for Single {
+        const KIND: ArgKind = f32::KIND;
+
+        fn push_into(self, out: &mut ArgSink) {
+            self.0.push_into(out);
+        }
+    }
+
+    #[test]
+    fn float_register() {
+        let c = 3u8;
+        let mut func = Func::from_raw(cdecl_func::mixed_args as *const fn());
+        func.push(1i32)
+            .push(Meters(2.5))
+            .push(&c as *const u8)
+            .push(4u64);
+        unsafe { func.cdecl() };
+        assert_eq!(func.ret_as_f64(), 1342.5);
+    }
+
+    #[test]
+    fn fixed_f32() {
+        let mut func = Func::from_raw(cdecl_func::float_then_int as *const fn());
+        func.set_arity(2, false);
+        func.push(Single(1.5)).push(7i32);
+        unsafe { func.cdecl() };
+        assert_eq!(func.ret_as_i32(), 1507);
+    }
+
+    // 附加了原型时按 `KIND` 检查
+    #[test]
+    fn checked() {
+        let mut func = Func::from_raw(cdecl_func::mixed_args as *const fn());
+        func.set_signature(
+            Signature::parse("double mixed_args(int, double, const char*, uint64_t)").unwrap(),
+        );
+        let c = 3u8;
+        func.push(1i32)
+            .push(Meters(2.5))
+            .push(&c as *const u8)
+            .push(4u64);
+        unsafe { func.try_call(Convention::Cdecl).unwrap() };
+        assert_eq!(func.ret_as_f64(), 1342.5);
+
+        let mut func = Func::from_raw(cdecl_func::mixed_args as *const fn());
+        func.set_signature(
+            Signature::parse("double mixed_args(int, double, const char*, uint64_t)").unwrap(),
+        );
+        let err = func.try_push(Meters(1.0)).unwrap_err();
+        assert!(matches!(err, CallError::ArgTypeMismatch { index: 0, .. }));
+    }
+}
+
+mod prepared {
+    use super::*;
+
+    #[test]
+    fn replace() {
+        let c = 3u8;
+        let mut func = Func::from_raw(cdecl_func::mixed_args as *const fn());
+        func.push(0i32)
+            .push(0.0f64)
+            .push(&c as *const u8)
+            .push(0u64);
+        let mut call = func.prepare(Convention::Cdecl).unwrap();
+        for i in 0..100 {
+            call.replace_arg(0, i).unwrap();
+            call.replace_arg(1, i as f64 / 4.0).unwrap();
+            call.replace_arg(3, i as u64 * 2).unwrap();
+            let ret = unsafe { call.invoke() };
+            assert_eq!(
+                ret.as_f64(),
+                cdecl_func::mixed_args(i, i as f64 / 4.0, &c, i as u64 * 2)
+            );
+        }
+        assert_eq!(
+            call.func().ret_as_f64(),
+            cdecl_func::mixed_args(99, 24.75, &c, 198)
+        );
+    }
+
+    #[test]
+    #[cfg(all(feature = "std", target_arch = "x86_64", target_os = "linux"))]
+    fn jit() {
+        let mut func = Func::from_raw(cdecl_func::mix200 as *const fn());
+        for i in 0..200i64 {
+            func.push(i);
+        }
+        let mut call = func
+            .prepare_with(Convention::Cdecl, funcall::Backend::Jit)
+            .unwrap();
+        let expected = unsafe { call.invoke() }.as_i64();
+        call.replace_arg(199, 0i64).unwrap();
+        assert_ne!(unsafe { call.invoke() }.as_i64(), expected);
+        call.replace_arg(199, 199i64).unwrap();
+        assert_eq!(unsafe { call.invoke() }.as_i64(), expected);
+    }
+
+    // 以 C float 压入的参数可以用 f32 替换
+    #[test]
+    fn c_float() {
+        let mut func = Func::from_raw(cdecl_func::float_then_int as *const fn());
+        func.set_arity(2, false);
+        func.push(1.5f32).push(7i32);
+        let mut call = func.prepare(Convention::Cdecl).unwrap();
+        call.replace_arg(0, 2.5f32).unwrap();
+        assert_eq!(unsafe { call.invoke() }.as_i32(), 2507);
+    }
+
+    #[test]
+    fn errors() {
+        let err = Func::from_raw(std::ptr::null())
+            .prepare(Convention::Cdecl)
+            .unwrap_err();
+        assert_eq!(err, CallError::NullTarget);
+
+        let mut func = Func::from_raw(cdecl_func::return_i64 as *const fn());
+        func.push(1i64);
+        let mut call = func.prepare(Convention::Cdecl).unwrap();
+        let err = call.replace_arg(0, 1i32).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "argument 0 was pushed as I64 and cannot be replaced by I32"
+        );
+        assert!(matches!(
+            call.replace_arg(1, 1i64),
+            Err(CallError::ArgCountMismatch { got: 2, .. })
+        ));
+        assert_eq!(unsafe { call.invoke() }.as_i64(), 1);
+    }
+}
+
+mod batch {
+    use super::*;
+
+    /// 生成伪随机的参数
+    fn rng(seed: &mut u64) -> u64 {
+        *seed ^= *seed << 13;
+        *seed ^= *seed >> 7;
+        *seed ^= *seed << 17;
+        *seed
+    }
+
+    #[test]
+    fn random_rows() {
+        let mut seed = 0x2545_f491_4f6c_dd1d;
+        let rows: Vec<Vec<Arg>> = (0..1000)
+            .map(|_| {
+                let mut args = vec![
+                    Arg::I32(rng(&mut seed) as i32 >> 16),
+                    Arg::U8(rng(&mut seed) as u8),
+                    Arg::I64(rng(&mut seed) as i32 as i64),
+                    Arg::Usize(rng(&mut seed) as u16 as usize),
+                    Arg::U16(rng(&mut seed) as u16),
+                    Arg::F64(rng(&mut seed) as u32 as f64 / 7.0),
+                ];
+                // 有些参数与上一行相同
+                if rng(&mut seed).is_multiple_of(2) {
+                    args[1] = Arg::U8(1);
+                }
+                args
+            })
+            .collect();
+        let rows: Vec<&[Arg]> = rows.iter().map(Vec::as_slice).collect();
+
+        let mut func = Func::from_raw(cdecl_func::six_args as *const fn());
+        let mut out = Vec::new();
+        unsafe { func.call_batch(Convention::Cdecl, &rows, &mut out) }.unwrap();
+        assert_eq!(out.len(), rows.len());
+        for (row, ret) in rows.iter().zip(&out) {
+            let mut func = Func::from_raw(cdecl_func::six_args as *const fn());
+            for arg in row.iter() {
+                func.push_arg(arg.clone());
+            }
+            unsafe { func.cdecl() };
+            assert_eq!(ret.as_f64(), func.ret_as_f64());
+        }
+    }
+
+    // 附加了原型时每一行都按原型转换
+    #[test]
+    fn checked() {
+        let mut func = Func::from_raw(cdecl_func::float_then_int as *const fn());
+        func.set_signature(Signature::parse("int float_then_int(float, int)").unwrap());
+        let rows: [&[Arg]; 3] = [
+            &[Arg::F64(1.5), Arg::I8(2)],
+            &[Arg::I32(2), Arg::U16(3)],
+            &[Arg::F32(0.5), Arg::F64(1.0)],
+        ];
+        let mut out = Vec::new();
+        let err = unsafe { func.call_batch(Convention::Cdecl, &rows, &mut out) }.unwrap_err();
+        assert_eq!(err.row, 2);
+        assert!(matches!(
+            err.error,
+            CallError::ArgTypeMismatch { index: 1, .. }
+        ));
+        assert_eq!(
+            out.iter().map(RetValues::as_i32).collect::<Vec<_>>(),
+            [1502, 2003]
+        );
+    }
+
+    #[test]
+    #[cfg(all(feature = "loader", target_os = "linux"))]
+    fn strings() {
+        let mut func = Func::new("libc.so.6", b"strlen\0").unwrap();
+        let rows = [
+            vec![Arg::Str("a".into())],
+            vec![Arg::Str("abc".into())],
+            vec![Arg::Bytes(b"hello\0".to_vec())],
+        ];
+        let rows: Vec<&[Arg]> = rows.iter().map(Vec::as_slice).collect();
+        let mut out = Vec::new();
+        unsafe { func.call_batch(Convention::Cdecl, &rows, &mut out) }.unwrap();
+        let lens: Vec<usize> = out.iter().map(RetValues::as_usize).collect();
+        assert_eq!(lens, [1, 3, 5]);
+    }
+
+    // 每一行释放上一行的字符串副本时, 调用前压入的输出参数仍然有效
+    #[test]
+    fn out_param() {
+        let mut func = Func::from_raw(cdecl_func::len_into as *const fn());
+        let len = func.push_out_param::<usize>();
+        let rows: [&[Arg]; 3] = [
+            &[Arg::Str("a".into())],
+            &[Arg::Str("abcd".into())],
+            &[Arg::Str("ab".into())],
+        ];
+        let mut out = Vec::new();
+        unsafe { func.call_batch(Convention::Cdecl, &rows, &mut out) }.unwrap();
+        assert_eq!(out.len(), 3);
+        assert_eq!(func.read_out(len), 2);
+        assert_eq!(func.arg_views().count(), 1);
+    }
+
+    #[test]
+    fn errors() {
+        // 已压入的参数在每一行之前
+        let mut func = Func::from_raw(cdecl_func::float_then_int as *const fn());
+        func.push_float(1.5);
+        let rows: [&[Arg]; 3] = [&[Arg::I32(2)], &[Arg::I32(3)], &[Arg::I32(4), Arg::I32(5)]];
+        let mut out = Vec::new();
+        let err = unsafe { func.call_batch(Convention::Cdecl, &rows, &mut out) }.unwrap_err();
+        assert_eq!(err.row, 2);
+        assert!(matches!(
+            err.error,
+            CallError::ArgCountMismatch { got: 2, .. }
+        ));
+        // 出错之前的调用已经完成, 已压入的参数不受影响
+        assert_eq!(
+            out.iter().map(RetValues::as_i32).collect::<Vec<_>>(),
+            [1502, 1503]
+        );
+        assert_eq!(func.arg_views().count(), 1);
+
+        let rows: [&[Arg]; 2] = [&[Arg::I32(2)], &[Arg::I64(3)]];
+        let err = unsafe { func.call_batch(Convention::Cdecl, &rows, &mut out) }.unwrap_err();
+        assert_eq!(err.row, 1);
+        assert_eq!(
+            err.to_string(),
+            "row 1: argument 1 was pushed as I32 and cannot be replaced by I64"
+        );
+    }
+}
+
+// 只用寄存器传参的快速路径与需要栈上参数的通用路径
+mod fast_path {
+    use super::*;
+
+    #[test]
+    fn boundary() {
+        let mut func = Func::from_raw(cdecl_func::six_args as *const fn());
+        func.push(1i32)
+            .push(2u8)
+            .push(3i64)
+            .push(4usize)
+            .push(5u16)
+            .push(0.5f64);
+        unsafe { func.cdecl() };
+        assert_eq!(func.ret_as_f64(), 12345.5);
+
+        let mut func = Func::from_raw(cdecl_func::eight_floats as *const fn());
+        for i in 1..=8 {
+            func.push(i as f64);
+        }
+        unsafe { func.cdecl() };
+        assert_eq!(func.ret_as_f64(), 36.0);
+    }
+
+    // 撤销的参数不会残留在之后调用的寄存器中
+    #[test]
+    #[cfg(all(feature = "loader", target_os = "linux"))]
+    fn reused_frame() {
+        let lib = funcall::Library::new("libc.so.6").unwrap();
+        let target = lib.get("sprintf").unwrap().as_raw();
+        let call = Signature::parse("int sprintf(char*, const char*, ...)")
+            .unwrap()
+            .compile(Convention::Cdecl);
+        let mut buf = vec![0u8; 64];
+        let mut sprintf = |fmt: &str, args: &[Arg]| {
+            let mut all = vec![Arg::Ptr(buf.as_mut_ptr() as usize), Arg::Str(fmt.into())];
+            all.extend_from_slice(args);
+            unsafe { call.invoke(target, &all).unwrap() };
+            CStr::from_bytes_until_nul(&buf)
+                .unwrap()
+                .to_str()
+                .unwrap()
+                .to_owned()
+        };
+        assert_eq!(
+            sprintf("%.1f %.1f", &[Arg::F64(1.5), Arg::F64(2.5)]),
+            "1.5 2.5"
+        );
+        let ints = (0..10).map(Arg::I32).collect::<Vec<_>>();
+        assert_eq!(sprintf("%d%d%d%d%d%d%d%d%d%d", &ints), "0123456789");
+        assert_eq!(sprintf("%d", &[Arg::I32(7)]), "7");
+        assert_eq!(sprintf("none", &[]), "none");
+    }
+
+    // 不返回浮点数的函数的 float 返回值是确定的
+    #[test]
+    fn no_float() {
+        let mut func = Func::from_raw(cdecl_func::return_i64 as *const fn());
+        func.push(7i64);
+        unsafe { func.cdecl() };
+        assert_eq!(func.ret().float, 0.0);
+    }
+}
+
+mod reuse {
+    use super::*;
+
+    #[test]
+    fn alternating() {
+        let c = 3u8;
+        let mut mixed = Func::from_raw(cdecl_func::mixed_args as *const fn());
+        mixed
+            .push(1i32)
+            .push(2.5f64)
+            .push(&c as *const u8)
+            .push(4u64);
+        let mut floats = Func::from_raw(cdecl_func::float_args as *const fn());
+        floats.push_float(1.0).push(2.0f64).push_float(3.0);
+        for _ in 0..100 {
+            let ret = unsafe { mixed.invoke(Convention::Cdecl) }.unwrap();
+            assert_eq!(ret.as_f64(), 1342.5);
+            let ret = unsafe { floats.invoke(Convention::Cdecl) }.unwrap();
+            assert_eq!(ret.as_f64(), 123.0);
+        }
+    }
+
+    #[test]
+    #[cfg(all(feature = "loader", target_os = "linux"))]
+    fn sprintf() {
+        let mut buf = vec![0u8; 64];
+        let mut sprintf = Func::new("libc.so.6", b"sprintf\0").unwrap();
+        sprintf.set_arity(2, true);
+        // 格式串复制到 `Func` 中, 在所有调用中都保持有效
+        sprintf
+            .push(buf.as_mut_ptr())
+            .push_arg(Arg::Str("%d-%s".into()))
+            .push(42i32)
+            .push_arg(Arg::Str("abc".into()));
+        let mut strlen = Func::new("libc.so.6", b"strlen\0").unwrap();
+        strlen.push(buf.as_ptr());
+        for i in 0..100 {
+            let ret = unsafe { sprintf.invoke(Convention::Cdecl) }.unwrap();
+            assert_eq!(ret.as_i32(), 6);
+            assert_eq!(&buf[..7], b"42-abc\0");
+            let ret = unsafe { strlen.invoke(Convention::Cdecl) }.unwrap();
+            assert_eq!(ret.as_usize(), 6);
+            buf[..7].fill(i as u8 + 1);
+        }
+    }
+}
+
+#[cfg(all(target_arch = "x86_64", any(target_os = "linux", windows)))]
+mod raw6 {
+    use super::*;
+    use funcall::call_raw6;
+
+    #[test]
+    fn matches_cdecl() {
+        let funcs = [
+            cdecl_func::no_args as *const fn(),
+            cdecl_func::mix1 as *const fn(),
+            cdecl_func::mix2 as *const fn(),
+            cdecl_func::mix3 as *const fn(),
+            cdecl_func::mix4 as *const fn(),
+            cdecl_func::mix5 as *const fn(),
+            cdecl_func::mix6 as *const fn(),
+        ];
+        let args = [7, -3i64 as usize, usize::MAX, 0x1234_5678_9abc, 1 << 63, 42];
+        for (used, &ptr) in funcs.iter().enumerate() {
+            let mut func = Func::from_raw(ptr);
+            for &arg in &args[..used] {
+                func.push(arg);
+            }
+            unsafe { func.cdecl() };
+            let ret = unsafe { call_raw6(ptr, args, used) };
+            assert_eq!(ret.low, func.ret().low, "{} args", used);
+        }
+    }
+
+    // 多余的位置中的值不影响结果
+    #[test]
+    fn unused_slots() {
+        let ptr = cdecl_func::mix2 as *const fn();
+        let ret = unsafe { call_raw6(ptr, [3, 4, 5, 6, 7, 8], 2) };
+        assert_eq!(ret.as_i64(), cdecl_func::mix2(3, 4));
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn variadic() {
+        extern "C" {
+            fn snprintf(buf: *mut u8, len: usize, fmt: *const u8, ...) -> i32;
+        }
+        let mut buf = [0u8; 32];
+        let args = [
+            buf.as_mut_ptr() as usize,
+            buf.len(),
+            b"%d %x %s\0".as_ptr() as usize,
+            42,
+            255,
+            b"abc\0".as_ptr() as usize,
+        ];
+        let ret = unsafe { call_raw6(snprintf as *const fn(), args, 6) };
+        assert_eq!(ret.as_i32(), 9);
+        assert_eq!(&buf[..10], b"42 ff abc\0");
+    }
+
+    #[test]
+    #[should_panic(expected = "at most 6 arguments")]
+    fn too_many() {
+        unsafe { call_raw6(cdecl_func::no_args as *const fn(), [0; 6], 7) };
+    }
+}
+
+mod vtable {
+    use super::*;
+    use cdecl_func::{FakeObject, FakeVtable};
+    use std::ffi::c_void;
+
+    fn object() -> (Box<FakeVtable>, FakeObject) {
+        let vtable = Box::new([
+            cdecl_func::object_value as *const fn(),
+            cdecl_func::object_set as *const fn(),
+            cdecl_func::object_scale as *const fn(),
+        ]);
+        let object = FakeObject {
+            vtable: &*vtable,
+            value: 7,
+        };
+        (vtable, object)
+    }
+
+    #[test]
+    fn entries() {
+        let (_vtable, mut object) = object();
+        let this = &mut object as *mut FakeObject as *mut c_void;
+
+        let mut value = unsafe { Func::from_vtable(this, 0) };
+        let ret = unsafe { value.invoke(Convention::native_system()) }.unwrap();
+        assert_eq!(ret.as_i32(), 7);
+
+        // 之后压入的参数都在 this 之后
+        let mut scale = unsafe { Func::from_vtable(this, 2) };
+        scale.push(3i32).push(0.5f64);
+        let ret = unsafe { scale.invoke(Convention::native_system()) }.unwrap();
+        assert_eq!(ret.as_f64(), 21.5);
+        assert_eq!(scale.arg_views().count(), 3);
+    }
+
+    #[test]
+    fn mutates_object() {
+        let (_vtable, mut object) = object();
+        let this = &mut object as *mut FakeObject as *mut c_void;
+        let mut set = unsafe { Func::from_vtable(this, 1) };
+        set.push(-4i32);
+        unsafe { set.invoke(Convention::native_system()) }.unwrap();
+        assert_eq!(object.value, -4);
+    }
+
+    #[test]
+    fn native_system() {
+        let expected = if cfg!(all(target_arch = "x86", windows)) {
+            Convention::Stdcall
+        } else {
+            Convention::Cdecl
+        };
+        assert_eq!(Convention::native_system(), expected);
+    }
+}
+
+#[cfg(all(target_vendor = "apple", feature = "libffi"))]
+mod objc {
+    use super::*;
+    use funcall::{objc, ArgKind};
+
+    #[link(name = "Foundation", kind = "framework")]
+    extern "C" {}
+
+    #[test]
+    fn nsstring_length() {
+        let class = objc::class("NSString");
+        assert!(!class.is_null());
+        let mut create = objc::msg_send(class, "stringWithUTF8String:");
+        create.push(b"hello, world\0".as_ptr());
+        let string = unsafe { create.invoke(Convention::Cdecl) }
+            .unwrap()
+            .as_usize();
+        assert_ne!(string, 0);
+
+        let mut length = objc::msg_send_ret(string as *mut _, "length", ArgKind::Usize);
+        let ret = unsafe { length.invoke(Convention::Cdecl) }.unwrap();
+        assert_eq!(ret.as_usize(), 12);
+    }
+
+    #[test]
+    fn missing_class() {
+        assert!(objc::class("FuncallNoSuchClass").is_null());
+    }
+}
+
+mod system {
+    use super::*;
+
+    #[test]
+    fn calls_extern_system() {
+        let mut func = Func::from_raw(cdecl_func::system_sub as *const fn());
+        func.push(5i32).push(2i32);
+        unsafe { func.system() };
+        assert_eq!(func.ret_as_i32(), 3);
+
+        let ret = unsafe { func.invoke(Convention::System) }.unwrap();
+        assert_eq!(ret.as_i32(), 3);
+        assert_eq!(
+            Convention::System.is_supported(),
+            Convention::native_system().is_supported()
+        );
+    }
+
+    #[cfg(not(all(target_arch = "x86", windows)))]
+    #[test]
+    fn same_as_cdecl() {
+        let mut func = Func::from_raw(cdecl_func::system_sub as *const fn());
+        func.push(5i32).push(2i32);
+        unsafe { func.cdecl() };
+        let cdecl = func.ret();
+        unsafe { func.system() };
+        assert_eq!(func.ret(), cdecl);
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn current_process_id() {
+        #[link(name = "kernel32")]
+        extern "system" {
+            fn GetCurrentProcessId() -> u32;
+        }
+
+        let mut func = Func::from_raw(GetCurrentProcessId as *const fn());
+        unsafe { func.system() };
+        assert_eq!(func.ret_as_u32(), std::process::id());
+
+        let ret = unsafe { func.invoke(Convention::System) }.unwrap();
+        assert_eq!(ret.as_u32(), std::process::id());
+    }
+}
+
+mod c_types {
+    use super::*;
+    use funcall::ArgKind;
+    use std::os::raw::{c_long, c_ulong};
+
+    #[test]
+    fn long_width() {
+        let mut func = Func::from_raw(cdecl_func::negate_long as *const fn());
+        func.push_c_long(-5);
+        let expected = if cfg!(all(target_pointer_width = "64", not(windows))) {
+            ArgKind::I64
+        } else {
+            ArgKind::I32
+        };
+        assert_eq!(func.arg_views().next().unwrap().kind(), expected);
+        unsafe { func.invoke(Convention::Cdecl) }.unwrap();
+        assert_eq!(func.ret_as_c_long(), 5 as c_long);
+    }
+
+    #[test]
+    fn ulong_ret() {
+        let mut func = Func::from_raw(cdecl_func::ulong_max as *const fn());
+        func.push_c_int(-1);
+        unsafe { func.invoke(Convention::Cdecl) }.unwrap();
+        assert_eq!(func.ret_as_c_ulong(), c_ulong::MAX);
+        assert_eq!(func.ret_as_c_uint(), u32::MAX);
+        assert_eq!(func.ret_as_c_int(), -1);
+    }
+}
+
+#[cfg(feature = "headers")]
+mod headers {
+    use super::*;
+    use funcall::headers;
+    use funcall::CType;
+
+    #[test]
+    fn plugin_header() {
+        let header = headers::parse(include_str!("plugin.h"));
+        assert_eq!(header.functions.len(), 26);
+
+        let open = header.get("plugin_open").unwrap();
+        assert_eq!(open.ret, CType::ptr(CType::Void));
+        assert_eq!(
+            open.params,
+            [CType::ULong, CType::ptr(CType::constant(CType::Char))]
+        );
+        let log = header.get("plugin_log").unwrap();
+        assert!(log.variadic);
+        assert_eq!(log.params.len(), 3);
+        // 函数指针, 枚举与 typedef 过的整数
+        assert_eq!(
+            header.get("plugin_on_event").unwrap().params[1],
+            CType::ptr(CType::Void)
+        );
+        assert_eq!(header.get("plugin_check").unwrap().ret, CType::Int);
+        assert_eq!(header.get("plugin_reload").unwrap().ret, CType::Int32);
+        assert!(header.get("plugin_v3").is_some());
+        assert!(header.get("plugin_v2").is_none());
+
+        let lines: Vec<_> = header.diagnostics.iter().map(|d| d.line).collect();
+        assert_eq!(lines, [7, 8, 17, 75, 76, 77, 78, 79, 80, 82]);
+        assert_eq!(
+            header.diagnostics[3].to_string(),
+            "line 75: ignored `plugin_area`: `plugin_rect` passed by value is not supported at column 19"
+        );
+        assert!(header.diagnostics[6].message.contains("__stdcall"));
+    }
+
+    #[test]
+    fn call() {
+        let header = headers::parse(include_str!("plugin.h"));
+
+        let mut func = Func::from_raw(cdecl_func::negate_long as *const fn());
+        func.set_signature(header.get("negate_long").unwrap().clone());
+        func.try_push(7i32).unwrap();
+        assert_eq!(
+            unsafe { func.invoke(Convention::Cdecl) }
+                .unwrap()
+                .as_c_long(),
+            -7
+        );
+
+        // float 参数按声明传递, 不会被提升为 double
+        let mut func = Func::from_raw(cdecl_func::float_then_int as *const fn());
+        func.set_signature(header.get("float_then_int").unwrap().clone());
+        func.try_push(1.5f32).unwrap().try_push(3i32).unwrap();
+        assert!(func.try_push(0i32).is_err());
+        assert_eq!(
+            unsafe { func.invoke(Convention::Cdecl) }.unwrap().as_i32(),
+            1503
+        );
+    }
+
+    #[test]
+    #[cfg(all(feature = "loader", target_os = "linux"))]
+    fn library() {
+        let header = headers::parse("size_t strlen(const char *s);");
+        let libc = funcall::Library::new("libc.so.6").unwrap();
+        let mut strlen = header.func(&libc, "strlen").unwrap();
+        strlen.try_push_arg(Arg::Str("hello".into())).unwrap();
+        assert_eq!(
+            unsafe { strlen.invoke(Convention::Cdecl) }
+                .unwrap()
+                .as_usize(),
+            5
+        );
+        assert!(header.func(&libc, "strcmp").is_err());
+    }
+
+    #[test]
+    fn conditionals() {
+        let header = headers::parse(
+            "#define LEVEL 2\n\
+             #if LEVEL > 2\n\
+             int a(void);\n\
+             #elif LEVEL == 2 && !defined(NOPE)\n\
+             int b(void);\n\
+             #  ifdef NOPE\n\
+             int c(void);\n\
+             #  else\n\
+             int d(void);\n\
+             #  endif\n\
+             #else\n\
+             int e(void);\n\
+             #endif\n\
+             #undef LEVEL\n\
+             #if LEVEL\n\
+             int f(void);\n\
+             #else\n\
+             int g(void);\n\
+             #endif\n",
+        );
+        let names: Vec<_> = header.functions.keys().map(String::as_str).collect();
+        assert_eq!(names, ["b", "d"]);
+        assert_eq!(header.diagnostics.len(), 1);
+        assert_eq!(header.diagnostics[0].line, 15);
+    }
+}
+
+#[cfg(all(feature = "capi", target_os = "linux"))]
+mod capi {
+    use super::*;
+    use funcall::capi::*;
+    use funcall::Library;
+    use std::os::raw::{c_char, c_int, c_void};
+    use std::path::PathBuf;
+    use std::sync::OnceLock;
+
+    type Handle = *mut c_void;
+
+    /// 像其他语言那样只通过导出的符号使用 funcall
+    struct Api {
+        lib: Library,
+    }
+
+    impl Api {
+        fn load() -> Self {
+            static PATH: OnceLock<PathBuf> = OnceLock::new();
+            let path = PATH.get_or_init(|| {
+                cdylib::build_with_funcall_having(
+                    "capi_fixture",
+                    "pub use funcall::capi::*;",
+                    "funcall_last_error",
+                )
+            });
+            Self {
+                lib: Library::new(path).unwrap(),
+            }
+        }
+
+        fn sym<F: Copy>(&self, name: &str) -> F {
+            let ptr = self.lib.get(name).unwrap().as_raw();
+            unsafe { std::mem::transmute_copy(&ptr) }
+        }
+
+        fn open(&self, lib: &[u8], sym: &[u8]) -> Handle {
+            let f: unsafe extern "C" fn(*const c_char, *const c_char) -> Handle =
+                self.sym("funcall_new");
+            unsafe { f(lib.as_ptr().cast(), sym.as_ptr().cast()) }
+        }
+
+        fn call(&self, func: Handle, conv: c_int) -> c_int {
+            let f: unsafe extern "C" fn(Handle, c_int) -> c_int = self.sym("funcall_call");
+            unsafe { f(func, conv) }
+        }
+
+        fn last_error(&self) -> (c_int, String) {
+            let f: unsafe extern "C" fn(*mut *const c_char) -> c_int =
+                self.sym("funcall_last_error");
+            let mut message = std::ptr::null();
+            let code = unsafe { f(&mut message) };
+            let message = unsafe { CStr::from_ptr(message) };
+            (code, message.to_str().unwrap().to_owned())
+        }
+
+        fn free(&self, func: Handle) {
+            let f: unsafe extern "C" fn(Handle) = self.sym("funcall_free");
+            unsafe { f(func) }
+        }
+    }
+
+    #[test]
+    fn calls() {
+        let api = Api::load();
+        let push_i64: unsafe extern "C" fn(Handle, i64) -> c_int = api.sym("funcall_push_i64");
+        let push_f64: unsafe extern "C" fn(Handle, f64) -> c_int = api.sym("funcall_push_f64");
+        let push_ptr: unsafe extern "C" fn(Handle, *const c_void) -> c_int =
+            api.sym("funcall_push_ptr");
+        let push_bytes: unsafe extern "C" fn(Handle, *const u8, usize) -> c_int =
+            api.sym("funcall_push_bytes");
+        let ret_i64: unsafe extern "C" fn(Handle) -> i64 = api.sym("funcall_ret_i64");
+        let ret_f64: unsafe extern "C" fn(Handle) -> f64 = api.sym("funcall_ret_f64");
+        let from_raw: extern "C" fn(*const c_void) -> Handle = api.sym("funcall_from_raw");
+
+        unsafe {
+            let func = from_raw(cdecl_func::negate_long as *const c_void);
+            assert_eq!(push_i64(func, -5), FUNCALL_OK);
+            assert_eq!(api.call(func, FUNCALL_CDECL), FUNCALL_OK);
+            assert_eq!(ret_i64(func), 5);
+            // 同一个句柄可以反复调用
+            assert_eq!(api.call(func, FUNCALL_SYSTEM), FUNCALL_OK);
+            assert_eq!(ret_i64(func), 5);
+            api.free(func);
+
+            let pow = api.open(b"libm.so.6\0", b"pow\0");
+            assert!(!pow.is_null());
+            push_f64(pow, 2.0);
+            push_f64(pow, 10.0);
+            assert_eq!(api.call(pow, FUNCALL_CDECL), FUNCALL_OK);
+            assert_eq!(ret_f64(pow), 1024.0);
+            api.free(pow);
+
+            let strlen = api.open(b"libc.so.6\0", b"strlen\0");
+            let text = b"hello\0".to_vec();
+            assert_eq!(push_bytes(strlen, text.as_ptr(), text.len()), FUNCALL_OK);
+            drop(text);
+            assert_eq!(api.call(strlen, FUNCALL_CDECL), FUNCALL_OK);
+            assert_eq!(ret_i64(strlen), 5);
+            api.free(strlen);
+
+            let strlen = api.open(b"libc.so.6\0", b"strlen\0");
+            push_ptr(strlen, b"abc\0".as_ptr().cast());
+            assert_eq!(api.call(strlen, FUNCALL_CDECL), FUNCALL_OK);
+            assert_eq!(ret_i64(strlen), 3);
+            api.free(strlen);
+        }
+    }
+
+    #[test]
+    fn errors() {
+        let api = Api::load();
+        assert!(api.open(b"libfuncall_missing.so\0", b"nope\0").is_null());
+        let (code, message) = api.last_error();
+        assert_eq!(code, FUNCALL_ERR_LOAD);
+        assert!(message.contains("libfuncall_missing.so"));
+
+        assert_eq!(
+            api.call(std::ptr::null_mut(), FUNCALL_CDECL),
+            FUNCALL_ERR_INVALID
+        );
+        assert_eq!(
+            api.last_error(),
+            (FUNCALL_ERR_INVALID, "handle is null".into())
+        );
+        let ret_i64: unsafe extern "C" fn(Handle) -> i64 = api.sym("funcall_ret_i64");
+        let ret_f64: unsafe extern "C" fn(Handle) -> f64 = api.sym("funcall_ret_f64");
+        unsafe {
+            assert_eq!(ret_i64(std::ptr::null_mut()), 0);
+            assert_eq!(api.last_error().0, FUNCALL_ERR_INVALID);
+            assert_eq!(ret_f64(std::ptr::null_mut()), 0.0);
+            assert_eq!(api.last_error().0, FUNCALL_ERR_INVALID);
+        }
+
+        let strlen = api.open(b"libc.so.6\0", b"strlen\0");
+        assert_eq!(api.call(strlen, 99), FUNCALL_ERR_INVALID);
+        assert_eq!(api.last_error().1, "unknown convention 99");
+        #[cfg(target_arch = "x86_64")]
+        {
+            assert_eq!(api.call(strlen, FUNCALL_STDCALL), FUNCALL_ERR_CALL);
+            assert_eq!(api.last_error().0, FUNCALL_ERR_CALL);
+        }
+        api.free(strlen);
+        api.free(std::ptr::null_mut());
+    }
+
+    /// 头文件中声明的函数都能在动态库中找到
+    #[cfg(feature = "headers")]
+    #[test]
+    fn header() {
+        let api = Api::load();
+        let header = funcall::headers::parse(include_str!("../include/funcall.h"));
+        assert_eq!(header.functions.len(), 12);
+        // 除了 `#include` 之外都能被解析
+        assert_eq!(header.diagnostics.len(), 2);
+        for name in header.functions.keys() {
+            assert!(api.lib.get(name).is_ok(), "{} is not exported", name);
+        }
+    }
+}
+
+#[cfg(feature = "json")]
+mod json {
+    use super::*;
+    use funcall::ArgKind;
+    use serde_json::Value;
+
+    /// 每种类型标签都能原样读回
+    #[test]
+    fn round_trip() {
+        let json = r#"{"lib": "libm.so.6", "symbol": "f", "convention": "system", "args": [
+            {"i8": -8}, {"u8": 255}, {"i16": -16}, {"u16": 65535}, {"i32": -32}, {"u32": 4294967295},
+            {"i64": -9223372036854775808}, {"u64": 18446744073709551615}, {"isize": -1}, {"usize": 1},
+            {"f32": 1.5}, {"f64": -2.25}, {"ptr": 4096}, {"bytes": [0, 1, 255]}, {"cstr": "hello"}
+        ], "ret": "u16"}"#;
+        let spec = CallSpec::from_json(json).unwrap();
+        assert_eq!(spec.convention, Convention::System);
+        assert_eq!(spec.ret, Some(ArgKind::U16));
+        assert_eq!(
+            spec.args,
+            [
+                Arg::I8(-8),
+                Arg::U8(255),
+                Arg::I16(-16),
+                Arg::U16(65535),
+                Arg::I32(-32),
+                Arg::U32(u32::MAX),
+                Arg::I64(i64::MIN),
+                Arg::U64(u64::MAX),
+                Arg::Isize(-1),
+                Arg::Usize(1),
+                Arg::F32(1.5),
+                Arg::F64(-2.25),
+                Arg::Ptr(4096),
+                Arg::Bytes(vec![0, 1, 255]),
+                Arg::Str("hello".into()),
+            ]
+        );
+        assert_eq!(CallSpec::from_json(&spec.to_json()).unwrap(), spec);
+
+        // 省略的字段取默认值
+        let spec = CallSpec::from_json(r#"{"lib": "a", "symbol": "b"}"#).unwrap();
+        assert_eq!(spec.convention, Convention::Cdecl);
+        assert!(spec.args.is_empty());
+        assert_eq!(spec.ret, None);
+        assert_eq!(CallSpec::from_json(&spec.to_json()).unwrap(), spec);
+    }
+
+    #[test]
+    fn errors() {
+        let err = |json: &str| CallSpec::from_json(json).unwrap_err().to_string();
+        assert_eq!(err("[]"), "$: expected an object");
+        assert!(err("{").starts_with("$: "));
+        assert_eq!(err(r#"{"lib": "a"}"#), "$: missing field `symbol`");
+        assert_eq!(
+            err(r#"{"lib": 1, "symbol": "b"}"#),
+            "$.lib: expected a string"
+        );
+        assert_eq!(
+            err(r#"{"lib": "a", "symbol": "b", "extra": 1}"#),
+            "$.extra: unknown field"
+        );
+        assert_eq!(
+            err(r#"{"lib": "a", "symbol": "b", "convention": "vectorcall"}"#),
+            "$.convention: unknown convention \"vectorcall\""
+        );
+        assert_eq!(
+            err(r#"{"lib": "a", "symbol": "b", "args": [{"i32": 1}, {"i33": 1}]}"#),
+            "$.args[1]: unknown type tag `i33`"
+        );
+        assert_eq!(
+            err(r#"{"lib": "a", "symbol": "b", "args": [{"i32": 1, "u32": 1}]}"#),
+            "$.args[0]: expected an object with a single type tag"
+        );
+        assert_eq!(
+            err(r#"{"lib": "a", "symbol": "b", "args": [{"u8": 256}]}"#),
+            "$.args[0].u8: `256` is out of range for u8"
+        );
+        assert_eq!(
+            err(r#"{"lib": "a", "symbol": "b", "args": [{"i32": 1.5}]}"#),
+            "$.args[0].i32: expected an integer, found 1.5"
+        );
+        assert_eq!(
+            err(r#"{"lib": "a", "symbol": "b", "args": [{"bytes": [1, 300]}]}"#),
+            "$.args[0].bytes[1]: expected a byte"
+        );
+        assert_eq!(
+            err(r#"{"lib": "a", "symbol": "b", "ret": "cstr"}"#),
+            "$.ret: unknown return type \"cstr\""
+        );
+    }
+
+    /// 读取请求, 执行后输出响应
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn execute() {
+        let spec = CallSpec::from_json(
+            r#"{"lib": "libc.so.6", "symbol": "abs", "convention": "cdecl", "args": [{"i32": -5}], "ret": "i32"}"#,
+        )
+        .unwrap();
+        let result = unsafe { spec.execute() };
+        let response: Value = serde_json::from_str(&spec.result_to_json(&result)).unwrap();
+        assert_eq!(response["ret"]["i32"], 5);
+        assert_eq!(response["error"], Value::Null);
+
+        // strtol 溢出时设置 errno 为 ERANGE
+        let spec = CallSpec::from_json(
+            r#"{"lib": "libc.so.6", "symbol": "strtol", "args": [{"cstr": "99999999999999999999"}, {"ptr": 0}, {"i32": 10}], "ret": "isize"}"#,
+        )
+        .unwrap();
+        let result = unsafe { spec.execute() };
+        let response: Value = serde_json::from_str(&spec.result_to_json(&result)).unwrap();
+        assert_eq!(response["ret"]["isize"], isize::MAX);
+        assert_eq!(response["errno"], 34);
+
+        let spec =
+            CallSpec::from_json(r#"{"lib": "libc.so.6", "symbol": "no_such_function"}"#).unwrap();
+        let result = unsafe { spec.execute() };
+        let response: Value = serde_json::from_str(&spec.result_to_json(&result)).unwrap();
+        assert_eq!(response["ret"], Value::Null);
+        assert!(response["error"].is_string());
+    }
+}
+
+mod argtypes {
+    use super::*;
+    use funcall::CType;
+
+    #[test]
+    fn coerce() {
+        let mut func = Func::from_raw(cdecl_func::float_then_int as *const fn());
+        func.set_argtypes(&[CType::Float, CType::Int]);
+        func.set_restype(CType::Int);
+        // f64 收窄为 float, u8 扩展为 int
+        func.push(1.5f64).push(7u8);
+        let ret = unsafe { func.invoke(Convention::Cdecl) }.unwrap();
+        assert_eq!(ret.as_i64(), 1507);
+        assert_eq!(func.signature().unwrap().ret, CType::Int);
+    }
+
+    #[test]
+    fn rejected() {
+        let mut func = Func::from_raw(cdecl_func::float_then_int as *const fn());
+        func.set_argtypes(&[CType::Float, CType::UChar]);
+        func.push(1.5f32).push(300i32);
+        assert_eq!(
+            unsafe { func.try_call(Convention::Cdecl) },
+            Err(CallError::ArgTypeMismatch {
+                index: 1,
+                expected: CType::UChar,
+                got: "I32(300)".into(),
+            })
+        );
+
+        let mut func = Func::from_raw(cdecl_func::float_then_int as *const fn());
+        func.set_argtypes(&[CType::ptr(CType::Char), CType::Int]);
+        func.push(1i32);
+        assert!(matches!(
+            unsafe { func.try_call(Convention::Cdecl) },
+            Err(CallError::ArgTypeMismatch { index: 0, .. })
+        ));
+
+        // 没有声明为可变参数时多余的参数被拒绝
+        let mut func = Func::from_raw(cdecl_func::float_then_int as *const fn());
+        func.set_argtypes(&[CType::Float, CType::Int]);
+        func.push(1.5f32).push(7i32).push(8i32);
+        assert!(matches!(
+            unsafe { func.try_call(Convention::Cdecl) },
+            Err(CallError::ArgCountMismatch { got: 3, .. })
+        ));
+    }
+
+    /// 固定参数按声明的类型传递, 可变参数部分则进行默认参数提升
+    #[test]
+    #[cfg(all(feature = "loader", target_os = "linux"))]
+    fn variadic_tail() {
+        let mut func = Func::new("libc.so.6", b"snprintf\0").unwrap();
+        let fmt = CType::ptr(CType::constant(CType::Char));
+        func.set_argtypes(&[CType::ptr(CType::Char), CType::SizeT, fmt]);
+        func.set_variadic(true);
+        func.set_restype(CType::Int);
+        let mut buf: Vec<std::os::raw::c_char> = vec![0; 32];
+        func.push(buf.as_mut_ptr())
+            .push(32u8)
+            .push_arg(Arg::Str("%.1f %d %s".into()))
+            .push(2.5f32)
+            .push(-3i8)
+            .push_arg(Arg::Str("x".into()));
+        unsafe {
+            assert_eq!(func.invoke(Convention::Cdecl).unwrap().as_i64(), 8);
+            assert_eq!(CStr::from_ptr(buf.as_ptr()).to_str().unwrap(), "2.5 -3 x");
+        }
+        // 可变参数部分不做类型检查, 但固定参数仍然检查
+        let mut func = Func::new("libc.so.6", b"snprintf\0").unwrap();
+        func.set_argtypes(&[CType::ptr(CType::Char), CType::SizeT]);
+        func.set_variadic(true);
+        func.push(1.5f64);
+        assert!(matches!(
+            unsafe { func.try_call(Convention::Cdecl) },
+            Err(CallError::ArgTypeMismatch { index: 0, .. })
+        ));
+    }
+
+    #[test]
+    fn restype() {
+        let mut func = Func::from_raw(cdecl_func::int_with_high_bits as *const fn());
+        unsafe { func.try_call(Convention::Cdecl) }.unwrap();
+        assert_eq!(func.ret_as_i64(), 0x1234_5678_ffff_fffe);
+
+        func.set_restype(CType::Int);
+        assert_eq!(func.ret_as_i64(), -2);
+        #[cfg(target_arch = "x86_64")]
+        assert_eq!(func.ret().as_i128(), -2);
+        func.set_restype(CType::UInt);
+        assert_eq!(func.ret_as_i64(), 0xffff_fffe);
+        func.set_restype(CType::Bool);
+        assert_eq!(func.ret_as_i64(), 1);
+        // 只声明返回值类型时不检查参数
+        assert!(func.signature().is_none());
+    }
+}
+
+mod vtable_adjusted {
+    use super::*;
+    use cdecl_func::TwoBases;
+    use std::ffi::c_void;
+    use std::mem::offset_of;
+
+    #[test]
+    fn second_base() {
+        let vtable_a = [cdecl_func::no_args as *const fn()];
+        let vtable_b = [
+            cdecl_func::second_base_value as *const fn(),
+            cdecl_func::two_bases_sum as *const fn(),
+        ];
+        let mut obj = TwoBases {
+            vtable_a: &vtable_a,
+            a: 3,
+            vtable_b: &vtable_b,
+            b: 4,
+        };
+        let this = &mut obj as *mut TwoBases as *mut c_void;
+        let offset = offset_of!(TwoBases, vtable_b);
+        assert_ne!(offset, 0);
+
+        // 基类自己的函数收到子对象的地址
+        let mut value = unsafe { Func::from_vtable_adjusted(this, offset, 0, 0) };
+        value.push(10i32);
+        let subobject = this as usize + offset;
+        assert_eq!(value.arg_views().next().unwrap().bits() as usize, subobject);
+        let ret = unsafe { value.invoke(Convention::native_system()) }.unwrap();
+        assert_eq!(ret.as_i32(), 14);
+
+        // 派生类覆盖的函数需要调回对象的起始地址
+        let mut sum = unsafe { Func::from_vtable_adjusted(this, offset, 1, -(offset as isize)) };
+        sum.push(3i32);
+        assert_eq!(
+            sum.arg_views().next().unwrap().bits() as usize,
+            this as usize
+        );
+        let ret = unsafe { sum.invoke(Convention::native_system()) }.unwrap();
+        assert_eq!(ret.as_i32(), 21);
+    }
+
+    #[test]
+    fn same_as_from_vtable() {
+        let vtable_a = [cdecl_func::no_args as *const fn()];
+        let vtable_b = [cdecl_func::no_args as *const fn(); 2];
+        let mut obj = TwoBases {
+            vtable_a: &vtable_a,
+            a: 0,
+            vtable_b: &vtable_b,
+            b: 0,
+        };
+        let this = &mut obj as *mut TwoBases as *mut c_void;
+        let adjusted = unsafe { Func::from_vtable_adjusted(this, 0, 0, 0) };
+        let plain = unsafe { Func::from_vtable(this, 0) };
+        assert!(adjusted == plain);
+    }
+}
+
+mod set_target {
+    use super::*;
+
+    #[test]
+    fn rebind() {
+        let mut func = Func::from_raw(cdecl_func::echo_int as *const fn());
+        func.push(-7i32)
+            .push(2.5f64)
+            .push_arg(Arg::Bytes(b"xyz".to_vec()));
+        let args: Vec<_> = func.arg_views().collect();
+
+        let ret = unsafe { func.invoke(Convention::Cdecl) }.unwrap();
+        assert_eq!(ret.as_i64(), -7);
+
+        func.set_target(cdecl_func::echo_double as *const fn());
+        // 返回值不会残留到新的目标
+        assert_eq!(func.ret(), RetValues::default());
+        assert_eq!(func.as_raw(), cdecl_func::echo_double as *const fn());
+        let ret = unsafe { func.invoke(Convention::Cdecl) }.unwrap();
+        assert_eq!(ret.as_f64(), 2.5);
+
+        let mut func = func.with_target(cdecl_func::echo_first_byte as *const fn());
+        let ret = unsafe { func.invoke(Convention::Cdecl) }.unwrap();
+        assert_eq!(ret.as_i64(), b'x' as i64);
+
+        // 参数在各次调用间保持不变
+        assert!(func.arg_views().eq(args));
+    }
+
+    #[test]
+    #[cfg(all(feature = "loader", target_os = "linux"))]
+    fn forgets_symbol() {
+        let lib = funcall::Library::new("libc.so.6").unwrap();
+        let mut func = lib.get("abs").unwrap();
+        assert_eq!(func.symbol_name(), Some("abs"));
+        let labs = lib.get("labs").unwrap().as_raw();
+        drop(lib);
+        func.set_target(labs);
+        assert_eq!(func.symbol_name(), None);
+        // 仍持有库, labs 的地址依然有效
+        func.push(-3isize);
+        let ret = unsafe { func.invoke(Convention::Cdecl) }.unwrap();
+        assert_eq!(ret.as_isize(), 3);
+        assert!(!func.ensure_fresh().unwrap());
+    }
+}
+
+mod null_func {
+    use super::*;
+
+    #[test]
+    fn null() {
+        let mut func = Func::null();
+        assert!(func.is_null());
+        assert!(!Func::from_raw(cdecl_func::no_args as *const fn()).is_null());
+        // 压入参数不受影响, 调用时报错
+        func.push(1i32).push(2.0f64);
+        assert_eq!(
+            unsafe { func.try_call(Convention::Cdecl) },
+            Err(CallError::NullTarget)
+        );
+        assert_eq!(
+            unsafe { func.invoke(Convention::Cdecl) },
+            Err(CallError::NullTarget)
+        );
+        assert_eq!(
+            unsafe { Func::null().call_once::<i32>(Convention::Cdecl) },
+            Err(CallError::NullTarget)
+        );
+    }
+
+    #[test]
+    #[cfg(all(feature = "loader", target_os = "linux"))]
+    fn optional() {
+        let mut abs = Func::new_optional("libc.so.6", b"abs\0").unwrap();
+        assert!(!abs.is_null());
+        abs.push(-4i32);
+        assert_eq!(
+            unsafe { abs.invoke(Convention::Cdecl) }.unwrap().as_i32(),
+            4
+        );
+
+        let lib = funcall::Library::new("libc.so.6").unwrap();
+        let mut missing = lib.get_optional("funcall_no_such_function");
+        assert!(missing.is_null());
+        assert_eq!(
+            unsafe { missing.try_call(Convention::Cdecl) },
+            Err(CallError::NullTarget)
+        );
+        assert!(
+            Func::new_optional("libc.so.6", b"funcall_no_such_function\0")
+                .unwrap()
+                .is_null()
+        );
+        // 库不存在时仍然报错
+        assert!(Func::new_optional("libfuncall_no_such_lib.so", b"abs\0").is_err());
+    }
+}
+
+mod target {
+    use super::*;
+    use funcall::{ByTarget, Target};
+    use std::collections::{HashMap, HashSet};
+
+    #[test]
+    fn ignores_args() {
+        let mut a = Func::from_raw(cdecl_func::mixed_args as *const fn());
+        a.push(1i32).push(f64::NAN);
+        let b = Func::from_raw(cdecl_func::mixed_args as *const fn());
+        // PartialEq 仍比较参数
+        assert!(a != b);
+        assert!(a.same_target(&b));
+        assert_eq!(
+            a.target(),
+            Target {
+                addr: cdecl_func::mixed_args as *const fn() as usize,
+                lib: None
+            }
+        );
+        assert!(!a.same_target(&Func::from_raw(cdecl_func::no_args as *const fn())));
+    }
+
+    // `Func` 中的 Cell 等不参与 `ByTarget` 的比较与哈希
+    #[allow(clippy::mutable_key_type)]
+    #[test]
+    fn hash_set() {
+        let mut a = Func::from_raw(cdecl_func::mixed_args as *const fn());
+        a.push(1i32).push(f64::NAN);
+        let b = Func::from_raw(cdecl_func::mixed_args as *const fn());
+        let c = Func::from_raw(cdecl_func::no_args as *const fn());
+
+        let mut set = HashSet::new();
+        assert!(set.insert(ByTarget(a)));
+        assert!(!set.insert(ByTarget(b.clone())));
+        assert!(set.insert(ByTarget(c)));
+        assert_eq!(set.len(), 2);
+        assert!(set.contains(&ByTarget(b)));
+        // 保留的是先插入的那个, 已压入的参数不变
+        let kept = set
+            .iter()
+            .find(|func| {
+                func.0
+                    .same_target(&Func::from_raw(cdecl_func::mixed_args as *const fn()))
+            })
+            .unwrap();
+        assert_eq!(kept.0.arg_views().count(), 2);
+    }
+
+    #[test]
+    #[cfg(all(feature = "loader", target_os = "linux"))]
+    fn dedup_lookups() {
+        let mut first = funcall::Library::new("libc.so.6")
+            .unwrap()
+            .get("abs")
+            .unwrap();
+        first.push(-1i32);
+        let second = Func::new("libc.so.6", b"abs\0").unwrap();
+        let labs = Func::new("libc.so.6", b"labs\0").unwrap();
+        assert_eq!(first.target(), second.target());
+        assert!(first.target().lib.is_some());
+
+        let mut map = HashMap::new();
+        for func in [first, second, labs] {
+            map.entry(func.target()).or_insert(func);
+        }
+        assert_eq!(map.len(), 2);
+
+        // 同一地址但不知道所在的库
+        let raw = Func::from_raw(map.keys().next().unwrap().addr as *const fn());
+        let targets: HashSet<_> = map.keys().copied().collect();
+        assert!(!targets.contains(&raw.target()));
+    }
+}
+
+#[cfg(all(feature = "loader", target_os = "linux"))]
+mod clone {
+    use super::*;
+    use funcall::Library;
+
+    fn first_arg(func: &Func) -> *const u8 {
+        func.arg_views().next().unwrap().bits() as usize as *const u8
+    }
+
+    /// 被调用者写入缓冲区的内容只出现在被调用的那一份中
+    #[test]
+    fn independent_buffers() {
+        let lib = Library::new("libc.so.6").unwrap();
+        let mut func = lib.get("memset").unwrap();
+        drop(lib);
+        func.push_arg(Arg::Bytes(b"abcd".to_vec()))
+            .push(b'x' as i32)
+            .push(2usize);
+        let mut copy = func.clone();
+        assert_ne!(first_arg(&func), first_arg(&copy));
+
+        let ret = unsafe { copy.invoke(Convention::Cdecl) }.unwrap();
+        assert_eq!(ret.as_usize() as *const u8, first_arg(&copy));
+        unsafe {
+            assert_eq!(std::slice::from_raw_parts(first_arg(&copy), 4), b"xxcd");
+            assert_eq!(std::slice::from_raw_parts(first_arg(&func), 4), b"abcd");
+        }
+        // 克隆不带有原来的返回值
+        assert_eq!(copy.clone().ret(), RetValues::default());
+
+        // 之后压入的参数也互不影响
+        let mut strlen = Func::new("libc.so.6", b"strlen\0").unwrap();
+        strlen.push_arg(Arg::Str("hello".into()));
+        let mut other = strlen.clone();
+        other.push(1i32);
+        assert_eq!(strlen.arg_views().count(), 1);
+        assert_eq!(other.arg_views().count(), 2);
+    }
+
+    #[test]
+    fn drop_order() {
+        for drop_original in [true, false] {
+            let mut func = Func::new("libc.so.6", b"strlen\0").unwrap();
+            func.push_arg(Arg::Str("hello".into()));
+            let mut copy = func.clone();
+            if drop_original {
+                drop(func);
+                let ret = unsafe { copy.invoke(Convention::Cdecl) }.unwrap();
+                assert_eq!(ret.as_usize(), 5);
+            } else {
+                drop(copy);
+                let ret = unsafe { func.invoke(Convention::Cdecl) }.unwrap();
+                assert_eq!(ret.as_usize(), 5);
+            }
+        }
+    }
+}
+
+#[cfg(target_arch = "x86")]
+mod stdcall {
+    use super::*;
+
+    macro_rules! return_tests {
+        ($($name:ident: $arg:expr => $ret:ident),*) => {
+            $(
+                #[test]
+                fn $name() {
+                    let mut func = Func::from_raw(stdcall_func::$name as *const fn());
+                    func.set_paranoid(true);
+                    func.push($arg);
+                    unsafe {
+                        func.stdcall();
+                    }
+                    assert_eq!(func.$ret(), $arg);
+                }
+            )*
+        };
+    }
+
+    return_tests!(
+        return_i8: -8i8 => ret_as_i8,
+        return_u8: 0xfeu8 => ret_as_u8,
+        return_i16: -1600i16 => ret_as_i16,
+        return_u16: 0xfffeu16 => ret_as_u16,
+        return_i32: i32::MIN => ret_as_i32,
+        return_u32: u32::MAX => ret_as_u32,
+        return_isize: -3isize => ret_as_isize,
+        return_usize: usize::MAX => ret_as_usize,
+        return_i64: -0x1234_5678_9abc_i64 => ret_as_i64,
+        return_u64: u64::MAX - 1 => ret_as_u64,
+        return_f64: -0.125f64 => ret_as_f64
+    );
+
+    #[test]
+    fn return_f32() {
+        let mut func = Func::from_raw(stdcall_func::return_f32 as *const fn());
+        func.set_paranoid(true);
+        func.push_float(1.0 / 3.0);
+        unsafe {
+            func.stdcall();
+        }
+        assert_eq!(func.ret_as_f32(), 1.0 / 3.0);
+    }
+
+    #[test]
+    fn more_than_6_args() {
+        let mut func = Func::from_raw(stdcall_func::more_than_6_args as *const fn());
+        func.set_paranoid(true);
+        func.push_args((1, 2, 3, 4, 5, 6, 7, 8));
+        assert_eq!(func.stack_bytes(), 32);
+        unsafe {
+            func.stdcall();
+        }
+        assert_eq!(func.ret_as_i32(), (1..=8).map(|i| i * i).sum::<i32>());
+    }
+
+    #[test]
+    fn float_widths() {
+        let mut func = Func::from_raw(stdcall_func::float_args as *const fn());
+        func.set_paranoid(true);
+        func.push_float(1.0).push(2.0f64).push_float(3.0);
+        assert_eq!(func.stack_bytes(), 16);
+        unsafe {
+            func.stdcall();
+        }
+        assert_eq!(func.ret_as_f64(), 123.0);
+
+        // 声明了参数个数后 push 的 f32 不再提升
+        let mut func = Func::from_raw(stdcall_func::float_then_int as *const fn());
+        func.set_paranoid(true);
+        func.set_arity(2, false);
+        func.push(1.5f32).push(7i32);
+        assert_eq!(func.stack_bytes(), 8);
+        unsafe {
+            func.stdcall();
+        }
+        assert_eq!(func.ret_as_i32(), 1507);
+
+        let mut func = Func::from_raw(stdcall_func::double_first as *const fn());
+        func.set_paranoid(true);
+        func.push(2.5f64).push(-1i8).push(3i64).push_float(0.5);
+        assert_eq!(func.stack_bytes(), 24);
+        unsafe {
+            func.stdcall();
+        }
+        assert_eq!(func.ret_as_f64(), 2500.0 - 100.0 + 30.0 + 0.5);
+    }
+
+    #[test]
+    fn many_mixed() {
+        let mut func = Func::from_raw(stdcall_func::many_mixed as *const fn());
+        func.set_paranoid(true);
+        func.push(1i32)
+            .push(2.0f64)
+            .push(3i64)
+            .push_float(4.0)
+            .push(5u8)
+            .push(6.0f64)
+            .push(7i64)
+            .push(8i32);
+        assert_eq!(func.stack_bytes(), 48);
+        unsafe {
+            func.stdcall();
+        }
+        assert_eq!(func.ret_as_f64(), 87654321.0);
+    }
+
+    /// 反复调用, 被调用者弹出的字节数与压入的不符时栈会逐渐失衡, 局部变量随之被破坏
+    #[test]
+    fn repeated() {
+        let locals: [u32; 16] = std::hint::black_box(std::array::from_fn(|i| i as u32 * 7));
+        for i in 0..1000 {
+            let mut func = Func::from_raw(stdcall_func::many_mixed as *const fn());
+            func.push(i)
+                .push(0.0f64)
+                .push(0i64)
+                .push_float(0.0)
+                .push(0u8)
+                .push(0.0f64)
+                .push(0i64)
+                .push(1i32);
+            let ret = unsafe { func.invoke(Convention::Stdcall) }.unwrap();
+            assert_eq!(ret.as_f64(), i as f64 + 1e7);
+
+            let mut func = Func::from_raw(stdcall_func::return_i64 as *const fn());
+            func.push(-(i as i64));
+            unsafe {
+                func.stdcall();
+            }
+            assert_eq!(func.ret_as_i64(), -(i as i64));
+        }
+        for (i, local) in locals.iter().enumerate() {
+            assert_eq!(*local, i as u32 * 7);
+        }
+    }
+}
+
+mod thiscall {
+    use super::*;
+
+    #[test]
+    fn unsupported() {
+        let mut func = Func::from_raw(cdecl_func::return_i8 as *const fn());
+        func.push(1i8);
+        assert_eq!(
+            Convention::Thiscall.is_supported(),
+            cfg!(target_arch = "x86")
+        );
+        if !cfg!(target_arch = "x86") {
+            assert_eq!(
+                unsafe { func.try_call(Convention::Thiscall) },
+                Err(CallError::UnsupportedConvention(Convention::Thiscall))
+            );
+        }
+    }
+
+    #[cfg(target_arch = "x86")]
+    #[test]
+    fn method() {
+        let widget = stdcall_func::Widget { base: 7 };
+        let mut func = Func::from_raw(stdcall_func::Widget::scaled as *const fn());
+        func.set_paranoid(true);
+        func.push(&widget as *const _).push(3i32).push(0.5f64);
+        unsafe { func.try_call(Convention::Thiscall) }.unwrap();
+        assert_eq!(func.ret_as_f64(), 21.5);
+
+        let mut func = Func::from_raw(stdcall_func::Widget::base as *const fn());
+        func.set_paranoid(true);
+        func.push(&widget as *const _);
+        unsafe { func.try_call(Convention::Thiscall) }.unwrap();
+        assert_eq!(func.ret_as_i32(), 7);
+
+        let mut func = Func::from_raw(stdcall_func::Widget::base as *const fn());
+        assert_eq!(
+            unsafe { func.try_call(Convention::Thiscall) },
+            Err(CallError::ArgCountMismatch {
+                expected: 1,
+                variadic: true,
+                got: 0,
+            })
+        );
+    }
+
+    /// 声明为可变参数后退回 cdecl, 反复调用栈也保持平衡
+    #[cfg(target_arch = "x86")]
+    #[test]
+    fn variadic() {
+        let widget = stdcall_func::Widget { base: 4 };
+        let locals: [u32; 16] = std::hint::black_box(std::array::from_fn(|i| i as u32 * 7));
+        for i in 0..1000 {
+            let mut func = Func::from_raw(stdcall_func::Widget::sum as *const fn());
+            func.set_paranoid(true);
+            func.set_arity(1, true);
+            func.push(&widget as *const _).push(i % 10).push(3i32);
+            unsafe { func.try_call(Convention::Thiscall) }.unwrap();
+            assert_eq!(func.ret_as_i32(), 400 + i % 10 * 10 + 3);
+
+            let mut func = Func::from_raw(stdcall_func::Widget::scaled as *const fn());
+            func.set_paranoid(true);
+            func.push(&widget as *const _).push(i).push(0.25f64);
+            unsafe { func.try_call(Convention::Thiscall) }.unwrap();
+            assert_eq!(func.ret_as_f64(), 4.0 * i as f64 + 0.25);
+        }
+        assert_eq!(
+            std::hint::black_box(locals),
+            std::array::from_fn(|i| i as u32 * 7)
+        );
+    }
+}
+
+#[cfg(all(feature = "loader", target_os = "linux"))]
+mod time {
+    use super::*;
+    use funcall::time::{read_timespec, read_timeval};
+    use std::time::{Duration, Instant};
+
+    fn last_ptr(func: &Func) -> *const std::ffi::c_void {
+        func.arg_views().last().unwrap().bits() as usize as *const _
+    }
+
+    #[test]
+    fn nanosleep() {
+        let mut func = Func::new("libc.so.6", b"nanosleep\0").unwrap();
+        func.push_timespec(Duration::from_millis(10)).push(0usize);
+        let start = Instant::now();
+        unsafe { func.try_call(Convention::Cdecl) }.unwrap();
+        assert_eq!(func.ret_as_i32(), 0);
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_millis(10), "{:?}", elapsed);
+        assert!(elapsed < Duration::from_secs(2), "{:?}", elapsed);
+    }
+
+    #[test]
+    fn out_params() {
+        // CLOCK_MONOTONIC
+        let mut func = Func::new("libc.so.6", b"clock_gettime\0").unwrap();
+        func.push(1i32).push_timespec(Duration::ZERO);
+        unsafe { func.try_call(Convention::Cdecl) }.unwrap();
+        assert_eq!(func.ret_as_i32(), 0);
+        let now = unsafe { read_timespec(last_ptr(&func)) };
+        assert!(now > Duration::ZERO);
+
+        let mut func = Func::new("libc.so.6", b"gettimeofday\0").unwrap();
+        func.push_timeval(Duration::ZERO).push(0usize);
+        unsafe { func.try_call(Convention::Cdecl) }.unwrap();
+        let ptr = func.arg_views().next().unwrap().bits() as usize as *const _;
+        // 2020 年之后
+        assert!(unsafe { read_timeval(ptr) } > Duration::from_secs(1_577_836_800));
+    }
+
+    #[test]
+    fn round_trip() {
+        let d = Duration::new(3, 456_789_123);
+        let mut func = Func::from_raw(std::ptr::null());
+        func.push_timespec(d);
+        assert_eq!(unsafe { read_timespec(last_ptr(&func)) }, d);
+        func.push_timeval(d);
+        assert_eq!(
+            unsafe { read_timeval(last_ptr(&func)) },
+            Duration::new(3, 456_789_000)
+        );
+        // 32 位的 time_t 放不下时截断为最大值
+        func.push_timespec(Duration::from_secs(u64::MAX));
+        let max = unsafe { read_timespec(last_ptr(&func)) };
+        assert!(max.as_secs() >= i32::MAX as u64);
+    }
+
+    // 超出范围的 tv_usec 换算为纳秒时饱和而不是溢出
+    #[test]
+    #[cfg(target_pointer_width = "64")]
+    fn out_of_range_fraction() {
+        let tv = [i64::MAX, i64::MAX];
+        assert_eq!(
+            unsafe { read_timeval(tv.as_ptr().cast()) },
+            Duration::from_secs(i64::MAX as u64) + Duration::from_nanos(u64::MAX)
+        );
+    }
+}
+
+#[cfg(feature = "std")]
+mod stats {
+    use super::*;
+    use funcall::CallStats;
+    use std::time::Duration;
+
+    #[test]
+    fn sleepy() {
+        if !Convention::Cdecl.is_supported() {
+            return;
+        }
+        let mut func = Func::from_raw(cdecl_func::sleep_ms as *const fn());
+        func.set_timing(true);
+        func.push(20u32);
+        for i in 1..=3 {
+            unsafe { func.try_call(Convention::Cdecl) }.unwrap();
+            assert_eq!(func.stats().count, i);
+        }
+        let stats = func.stats();
+        assert!(stats.total >= Duration::from_millis(60), "{:?}", stats);
+        assert!(stats.total < Duration::from_secs(2), "{:?}", stats);
+        assert!(stats.max >= Duration::from_millis(20), "{:?}", stats);
+        assert!(stats.mean() >= Duration::from_millis(20), "{:?}", stats);
+
+        let key = format!("{:#x}", cdecl_func::sleep_ms as *const fn() as usize);
+        let global = funcall::call_stats();
+        let (_, total) = global.iter().find(|(k, _)| *k == key).unwrap();
+        assert!(total.count >= 3);
+
+        // 克隆得到的统计被清空, 关闭计时后不再记录
+        assert_eq!(func.clone().stats(), CallStats::default());
+        func.set_timing(false);
+        unsafe { func.try_call(Convention::Cdecl) }.unwrap();
+        assert_eq!(func.stats(), CallStats::default());
+    }
+
+    #[test]
+    fn disabled() {
+        if !Convention::Cdecl.is_supported() {
+            return;
+        }
+        let mut func = Func::from_raw(cdecl_func::return_i8 as *const fn());
+        func.push(1i8);
+        unsafe { func.try_call(Convention::Cdecl) }.unwrap();
+        assert_eq!(func.stats(), CallStats::default());
+        func.set_timing(true);
+        unsafe { func.try_call(Convention::Cdecl) }.unwrap();
+        unsafe { func.try_call(Convention::Cdecl) }.unwrap();
+        assert_eq!(func.stats().count, 2);
+        func.reset_stats();
+        assert_eq!(func.stats().count, 0);
+    }
+}
+
+#[cfg(all(feature = "macros", target_os = "linux"))]
+mod dynamic_extern {
+    use super::*;
+    use funcall::dynamic_extern;
+    use std::sync::Once;
+
+    const FIXTURE: &str = concat!(
+        env!("CARGO_TARGET_TMPDIR"),
+        "/fixtures/libdynamic_extern_fixture.so"
+    );
+
+    /// 包装函数在首次调用时才查找, 只需在调用前编译好动态库
+    fn build_fixture() {
+        static BUILD: Once = Once::new();
+        BUILD.call_once(|| {
+            cdylib::build(
+                "dynamic_extern_fixture",
+                r#"
+                #[no_mangle]
+                pub extern "C" fn add(a: i32, b: i32) -> i32 { a + b }
+                #[no_mangle]
+                pub extern "C" fn scale(x: f64, k: i32) -> f64 { x * k as f64 }
+                #[no_mangle]
+                pub unsafe extern "C" fn fill(buf: *mut u8, len: usize) {
+                    for i in 0..len { *buf.add(i) = i as u8 + 1; }
+                }
+                #[no_mangle]
+                pub extern "C" fn sum3(a: i32, b: i32, c: i32) -> i32 { a * 100 + b * 10 + c }
+                "#,
+            );
+        });
+    }
+
+    #[dynamic_extern(lib = FIXTURE)]
+    extern "C" {
+        fn add(a: i32, b: i32) -> i32;
+        fn scale(x: f64, _: i32) -> f64;
+        fn fill(buf: *mut u8, len: usize);
+        fn sum3(a: i32, ...) -> i32;
+        #[link_name = "add"]
+        fn renamed(a: i32, b: i32) -> i32;
+        fn missing();
+    }
+
+    mod panicking {
+        use super::*;
+
+        #[dynamic_extern(lib = FIXTURE, on_error = "panic")]
+        extern "C" {
+            pub fn add(a: i32, b: i32) -> i32;
+            pub fn missing() -> i32;
+        }
+    }
+
+    #[dynamic_extern(lib = "libno_such_library.so")]
+    extern "C" {
+        fn nowhere(a: i32) -> i32;
+    }
+
+    #[test]
+    fn typed_calls() {
+        build_fixture();
+        unsafe {
+            assert_eq!(add(1, 2).unwrap(), 3);
+            assert_eq!(scale(1.5, 4).unwrap(), 6.0);
+            let mut buf = [0u8; 4];
+            fill(buf.as_mut_ptr(), buf.len()).unwrap();
+            assert_eq!(buf, [1, 2, 3, 4]);
+            assert_eq!(sum3(1, &[Arg::I32(2), Arg::I32(3)]).unwrap(), 123);
+            assert_eq!(renamed(20, 22).unwrap(), 42);
+            assert_eq!(panicking::add(2, 3), 5);
+        }
+    }
+
+    #[test]
+    fn resolution_errors() {
+        build_fixture();
+        let e = unsafe { missing() }.unwrap_err();
+        assert!(e.to_string().contains("`missing`"), "{}", e);
+        // 错误被缓存, 再次调用得到同样的错误
+        assert_eq!(unsafe { missing() }.unwrap_err().to_string(), e.to_string());
+
+        let e = unsafe { nowhere(1) }.unwrap_err();
+        assert!(e.to_string().contains("libno_such_library.so"), "{}", e);
+
+        let panicked = std::panic::catch_unwind(|| unsafe { panicking::missing() });
+        assert!(panicked.is_err());
+    }
+}
+
+#[cfg(feature = "async")]
+mod call_async {
+    use super::*;
+    use funcall::CallFuture;
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::sync::Arc;
+    use std::task::{Context, Poll, Wake};
+    use std::thread::{self, Thread};
+    use std::time::{Duration, Instant};
+
+    struct Unpark(Thread);
+
+    impl Wake for Unpark {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    /// 在当前线程中轮询所有 future 直到全部完成, 不依赖异步运行时
+    fn join_all(futures: Vec<CallFuture>) -> Vec<funcall::CallResult> {
+        let waker = Arc::new(Unpark(thread::current())).into();
+        let mut cx = Context::from_waker(&waker);
+        let mut futures: Vec<_> = futures.into_iter().map(Some).collect();
+        let mut results: Vec<_> = futures.iter().map(|_| None).collect();
+        while results.iter().any(Option::is_none) {
+            for (future, result) in futures.iter_mut().zip(&mut results) {
+                if let Some(f) = future {
+                    if let Poll::Ready(r) = Pin::new(f).poll(&mut cx) {
+                        *result = Some(r);
+                        *future = None;
+                    }
+                }
+            }
+            thread::park_timeout(Duration::from_millis(10));
+        }
+        results.into_iter().map(Option::unwrap).collect()
+    }
+
+    /// 开启 `tokio` 时 `call_async` 需要在 tokio 运行时中调用, 返回值存在期间当前线程处于运行时中
+    #[cfg(feature = "tokio")]
+    fn enter() -> tokio::runtime::EnterGuard<'static> {
+        static RUNTIME: std::sync::OnceLock<tokio::runtime::Runtime> = std::sync::OnceLock::new();
+        RUNTIME
+            .get_or_init(|| {
+                tokio::runtime::Builder::new_current_thread()
+                    .build()
+                    .unwrap()
+            })
+            .enter()
+    }
+
+    #[cfg(not(feature = "tokio"))]
+    fn enter() -> impl Sized {}
+
+    #[cfg(all(feature = "loader", target_os = "linux"))]
+    #[test]
+    fn concurrent_usleep() {
+        let _runtime = enter();
+        let start = Instant::now();
+        let futures = (0..10)
+            .map(|_| {
+                let mut func = Func::new("libc.so.6", b"usleep\0").unwrap();
+                func.push(100_000u32);
+                unsafe { func.call_async(Convention::Cdecl) }
+            })
+            .collect();
+        let results = join_all(futures);
+        let elapsed = start.elapsed();
+        for result in results {
+            let (ret, func) = result.unwrap();
+            assert_eq!(ret.as_i32(), 0);
+            assert_eq!(func.symbol_name(), Some("usleep"));
+        }
+        // 依次调用需要一秒
+        assert!(elapsed >= Duration::from_millis(100), "{:?}", elapsed);
+        assert!(elapsed < Duration::from_millis(600), "{:?}", elapsed);
+    }
+
+    #[test]
+    fn reuse_and_errors() {
+        if !Convention::Cdecl.is_supported() {
+            return;
+        }
+        let _runtime = enter();
+        let mut func = Func::from_raw(cdecl_func::return_i8 as *const fn());
+        func.push(-3i8);
+        let (ret, func) = join_all(vec![unsafe { func.call_async(Convention::Cdecl) }])
+            .pop()
+            .unwrap()
+            .unwrap();
+        assert_eq!(ret.as_i8(), -3);
+        let (ret, _) = join_all(vec![unsafe { func.call_async(Convention::Cdecl) }])
+            .pop()
+            .unwrap()
+            .unwrap();
+        assert_eq!(ret.as_i8(), -3);
+
+        let result = join_all(vec![unsafe { Func::null().call_async(Convention::Cdecl) }]);
+        assert!(matches!(result[..], [Err(CallError::NullTarget)]));
+    }
+
+    // 在 tokio 的阻塞线程池而不是新建的线程中调用
+    #[cfg(all(feature = "tokio", feature = "loader", target_os = "linux"))]
+    #[test]
+    fn tokio_blocking_pool() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .thread_name("funcall-test-blocking")
+            .build()
+            .unwrap();
+        let name = runtime.block_on(async {
+            let mut func = Func::new("libc.so.6", b"prctl\0").unwrap();
+            let mut name = [0u8; 16];
+            // PR_GET_NAME
+            func.push(16i32).push(name.as_mut_ptr());
+            let (ret, _) = unsafe { func.call_async(Convention::Cdecl) }.await.unwrap();
+            assert_eq!(ret.as_i32(), 0);
+            name
+        });
+        let name = CStr::from_bytes_until_nul(&name).unwrap();
+        assert_eq!(name.to_str(), Ok("funcall-test-bl"));
+    }
+}
+
+mod out_param {
+    use super::*;
+
+    #[test]
+    fn two_values() {
+        if !Convention::Cdecl.is_supported() {
+            return;
+        }
+        let mut func = Func::from_raw(cdecl_func::split_double as *const fn());
+        func.push(-12.25f64);
+        let int_part = func.push_out_param::<i64>();
+        let frac_part = func.push_out_param::<f64>();
+        assert_eq!(
+            (func.read_out(int_part), func.read_out(frac_part)),
+            (0, 0.0)
+        );
+
+        // 克隆有自己的一份缓冲区, 同一个凭据读取的是各自的值
+        let mut clone = func.clone();
+        unsafe { clone.try_call(Convention::Cdecl) }.unwrap();
+        assert_eq!(clone.read_out(int_part), -12);
+        assert_eq!(func.read_out(int_part), 0);
+
+        unsafe { func.try_call(Convention::Cdecl) }.unwrap();
+        assert_eq!(func.ret_as_i32(), 1);
+        assert_eq!(func.read_out(int_part), -12);
+        assert_eq!(frac_part.get(&func), -0.25);
+    }
+
+    #[test]
+    fn over_aligned() {
+        #[derive(Clone, Copy, Default, PartialEq, Debug)]
+        #[repr(align(64))]
+        struct Aligned(u64);
+
+        let mut func = Func::null();
+        let token = func.push_out_param::<Aligned>();
+        let ptr = func.arg_views().last().unwrap().bits() as usize;
+        assert_eq!(ptr % 64, 0);
+        assert_eq!(func.read_out(token), Aligned(0));
+
+        // 副本的地址与原缓冲区对 64 的余数通常不同, 克隆中的指针同样需要对齐
+        for i in 1..=16 {
+            let clone = func.clone();
+            let ptr = clone.arg_views().last().unwrap().bits() as usize;
+            assert_eq!(ptr % 64, 0);
+            unsafe { (ptr as *mut Aligned).write(Aligned(i)) };
+            assert_eq!(clone.read_out(token), Aligned(i));
+        }
+        assert_eq!(func.read_out(token), Aligned(0));
+    }
+
+    #[test]
+    #[should_panic(expected = "does not belong")]
+    fn foreign_token() {
+        let mut func = Func::null();
+        let token = func.push_out_param::<u32>();
+        Func::null().read_out(token);
+    }
+
+    #[cfg(all(feature = "loader", target_os = "linux"))]
+    #[test]
+    fn pipe() {
+        let mut func = Func::new("libc.so.6", b"pipe\0").unwrap();
+        let fds = func.push_out_param::<[i32; 2]>();
+        unsafe { func.try_call(Convention::Cdecl) }.unwrap();
+        assert_eq!(func.ret_as_i32(), 0);
+        let [read, write] = fds.get(&func);
+        assert!(read > 2 && write > 2 && read != write);
+
+        for fd in [read, write] {
+            let mut close = Func::new("libc.so.6", b"close\0").unwrap();
+            close.push(fd);
+            unsafe { close.try_call(Convention::Cdecl) }.unwrap();
+            assert_eq!(close.ret_as_i32(), 0);
+        }
+    }
+}
+
+mod fastcall {
+    use super::*;
+
+    #[test]
+    fn supported() {
+        assert_eq!(
+            Convention::Fastcall.is_supported(),
+            cfg!(target_arch = "x86")
+        );
+    }
+
+    /// 宽参数在栈上且不占用寄存器, 之后的 i32 仍然送入 ecx 与 edx
+    #[cfg(target_arch = "x86")]
+    #[test]
+    fn wide_args() {
+        let wide = -0x1234_5678_9abc_i64;
+        let probe = |f: extern "C" fn(), push: &dyn Fn(&mut Func)| {
+            let mut func = Func::from_raw(f as *const fn());
+            func.set_paranoid(true);
+            push(&mut func);
+            unsafe { func.try_call(Convention::Fastcall) }.unwrap();
+            func.ret_as_u64()
+        };
+        let words = |lo: i32, hi: i32| (hi as u32 as u64) << 32 | lo as u32 as u64;
+
+        // (i64, i32, i32)
+        let push: &dyn Fn(&mut Func) = &|func| {
+            func.push(wide).push(2i32).push(3i32);
+        };
+        assert_eq!(probe(stdcall_func::fastcall_probe_regs, push), words(2, 3));
+        assert_eq!(probe(stdcall_func::fastcall_probe_stack, push), wide as u64);
+
+        // (i32, i64, i32)
+        let push: &dyn Fn(&mut Func) = &|func| {
+            func.push(1i32).push(wide).push(3i32);
+        };
+        assert_eq!(probe(stdcall_func::fastcall_probe_regs, push), words(1, 3));
+        assert_eq!(probe(stdcall_func::fastcall_probe_stack, push), wide as u64);
+    }
+
+    #[cfg(target_arch = "x86")]
+    #[test]
+    fn floats_on_stack() {
+        let mut func = Func::from_raw(stdcall_func::fastcall_mixed as *const fn());
+        func.set_paranoid(true);
+        func.set_arity(5, false);
+        func.push(0.5f64)
+            .push(2i32)
+            .push(3.0f32)
+            .push(4i32)
+            .push(5i32);
+        for _ in 0..100 {
+            unsafe { func.try_call(Convention::Fastcall) }.unwrap();
+            assert_eq!(func.ret_as_f64(), 54320.5);
+        }
+    }
+
+    /// stdcall 中被调用者弹出的字节数包括 i64 的全部 8 字节
+    #[cfg(target_arch = "x86")]
+    #[test]
+    fn stdcall_wide_middle() {
+        let mut func = Func::from_raw(stdcall_func::wide_middle as *const fn());
+        func.set_paranoid(true);
+        func.push(1i32).push(-7i64).push(3i32);
+        assert_eq!(func.stack_bytes(), 16);
+        unsafe { func.try_call(Convention::Stdcall) }.unwrap();
+        assert_eq!(func.ret_as_i64(), 100 - 70 + 3);
+    }
+}
+
+#[cfg(feature = "loader")]
+mod libc {
+    use super::*;
+    use funcall::Library;
+
+    #[test]
+    fn sprintf() {
+        let mut func = funcall::libc_func("sprintf").unwrap();
+        let mut buf = vec![0u8; 64];
+        func.push(buf.as_mut_ptr())
+            .push(b"%d-%s\0".as_ptr())
+            .push(42i32)
+            .push(b"ok\0".as_ptr());
+        unsafe { func.try_call(Convention::Cdecl) }.unwrap();
+        assert_eq!(func.ret_as_i32(), 5);
+        let s = unsafe { CStr::from_ptr(buf.as_ptr() as *const _) };
+        assert_eq!(s.to_str().unwrap(), "42-ok");
+    }
+
+    #[test]
+    fn strlen() {
+        let lib = Library::libc().unwrap();
+        assert_eq!(lib.path(), None);
+        let mut func = lib.get("strlen").unwrap();
+        func.push(b"funcall\0".as_ptr());
+        unsafe { func.try_call(Convention::Cdecl) }.unwrap();
+        assert_eq!(func.ret_as_usize(), 7);
+        assert!(funcall::libc_func("no_such_function_in_libc").is_err());
+    }
+}
+
+mod arg_order {
+    use super::*;
+    use funcall::{ArgLocation, ArgOrder, Backend};
+    use std::mem::size_of;
+
+    fn push_mixed(func: &mut Func) {
+        func.push(1i32)
+            .push(2u64)
+            .push(3i8)
+            .push(4usize)
+            .push(5.0f64)
+            .push(6i64)
+            .push(7u16)
+            .push(8usize)
+            .push(9u64)
+            .push(10i32);
+    }
+
+    /// 寄存器中的参数不变, 栈上的参数整体倒序而各自内部的字保持原样
+    #[test]
+    fn mirrored_layout() {
+        let mut func = Func::null();
+        push_mixed(&mut func);
+        let forward = func.dry_run(Convention::Cdecl);
+        func.set_arg_order(ArgOrder::LeftToRight);
+        let backward = func.dry_run(Convention::Cdecl);
+        assert_eq!(forward.order, ArgOrder::RightToLeft);
+        assert_eq!(backward.order, ArgOrder::LeftToRight);
+        assert_eq!(forward.stack_size, backward.stack_size);
+
+        let word = size_of::<usize>();
+        let shadow = forward.shadow_space;
+        let total = func.stack_bytes();
+        assert!(total > 0);
+        for (f, b) in forward.args.iter().zip(&backward.args) {
+            assert_eq!(f.bytes, b.bytes);
+            match f.locations[0] {
+                ArgLocation::Stack(offset) => {
+                    let start = shadow + total - (offset - shadow) - f.locations.len() * word;
+                    let expected = (0..f.locations.len())
+                        .map(|i| ArgLocation::Stack(start + i * word))
+                        .collect::<Vec<_>>();
+                    assert_eq!(b.locations, expected, "{:?}", f.value);
+                }
+                _ => assert_eq!(f.locations, b.locations),
+            }
+        }
+    }
+
+    /// 栈上的参数倒序压入后以 LeftToRight 排列, 被调用者看到的仍是原来的顺序
+    #[test]
+    fn reversed_call() {
+        let args = (1..=10).map(|i| i * 0x1_0000_0001i64).collect::<Vec<_>>();
+        let expected = cdecl_func::mix10(
+            args[0], args[1], args[2], args[3], args[4], args[5], args[6], args[7], args[8],
+            args[9],
+        );
+
+        let mut probe = Func::null();
+        for arg in &args {
+            probe.push(*arg);
+        }
+        let regs = probe
+            .dry_run(Convention::Cdecl)
+            .args
+            .iter()
+            .take_while(|arg| !matches!(arg.locations[0], ArgLocation::Stack(_)))
+            .count();
+        assert!(regs < args.len());
+
+        let mut func = Func::from_raw(cdecl_func::mix10 as *const fn());
+        func.set_arg_order(ArgOrder::LeftToRight);
+        func.set_paranoid(true);
+        for arg in args[..regs].iter().chain(args[regs..].iter().rev()) {
+            func.push(*arg);
+        }
+        if Convention::Cdecl.is_supported() {
+            unsafe { func.try_call(Convention::Cdecl) }.unwrap();
+            assert_eq!(func.ret_as_i64(), expected);
+        }
+        func.set_arg_order(ArgOrder::RightToLeft);
+        if Convention::Cdecl.is_supported() {
+            unsafe { func.try_call(Convention::Cdecl) }.unwrap();
+            assert_ne!(func.ret_as_i64(), expected);
+        }
+    }
+
+    #[test]
+    fn other_backends() {
+        let mut func = Func::from_raw(cdecl_func::mix10 as *const fn());
+        func.set_arg_order(ArgOrder::LeftToRight);
+        for i in 0..10i64 {
+            func.push(i);
+        }
+        for backend in [Backend::Libffi, Backend::Jit] {
+            if backend.supports(Convention::Cdecl) {
+                assert_eq!(
+                    unsafe { func.try_call_with(Convention::Cdecl, backend) },
+                    Err(CallError::UnsupportedArgOrder(backend))
+                );
+            }
+        }
+    }
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+mod custom_conv {
+    use super::*;
+    use funcall::{ArgDest, ArgLocation, ConvDesc};
+
+    /// 由 `dry_run` 得到的布局写出与平台默认的 cdecl 相同的描述
+    fn cdecl_desc(func: &Func) -> ConvDesc {
+        let plan = func.dry_run(Convention::Cdecl);
+        let mut desc = ConvDesc::new().shadow_space(plan.shadow_space);
+        for arg in &plan.args {
+            desc = match arg.locations[0] {
+                ArgLocation::Stack(_) => desc.stack(),
+                location => desc.reg(&location.to_string()),
+            };
+        }
+        desc
+    }
+
+    /// 只比较声明的返回值, 其余返回值寄存器中的内容是不确定的
+    fn same_as_cdecl(ptr: *const fn(), ret: fn(&Func) -> u64, push: impl Fn(&mut Func)) {
+        let mut func = Func::from_raw(ptr);
+        push(&mut func);
+        func.set_paranoid(true);
+        let desc = cdecl_desc(&func);
+        unsafe { func.call_custom(&desc) }.unwrap();
+        let custom = ret(&func);
+        unsafe { func.try_call(Convention::Cdecl) }.unwrap();
+        assert_eq!(custom, ret(&func), "{:?}", desc);
+    }
+
+    fn double(func: &Func) -> u64 {
+        func.ret_as_f64().to_bits()
+    }
+
+    #[test]
+    fn cdecl() {
+        same_as_cdecl(cdecl_func::six_args as *const fn(), double, |func| {
+            func.push(1i32)
+                .push(2u8)
+                .push(-3i64)
+                .push(4usize)
+                .push(5u16)
+                .push(6.5f64);
+        });
+        same_as_cdecl(cdecl_func::float_args as *const fn(), double, |func| {
+            func.push_float(1.5).push(2.25f64).push_float(-3.0);
+        });
+        let int = |func: &Func| func.ret_as_i64() as u64;
+        same_as_cdecl(cdecl_func::mix10 as *const fn(), int, |func| {
+            for i in 1..=10i64 {
+                func.push(i * 0x1_0000_0001);
+            }
+        });
+        same_as_cdecl(cdecl_func::eight_floats as *const fn(), double, |func| {
+            for i in 0..8 {
+                func.push(i as f64 + 0.5);
+            }
+        });
+    }
+
+    #[cfg(target_arch = "x86")]
+    #[test]
+    fn fastcall() {
+        use funcall::Cleanup;
+
+        let desc = ConvDesc::new()
+            .stack()
+            .reg("ecx")
+            .stack()
+            .reg("edx")
+            .stack()
+            .cleanup(Cleanup::Callee);
+        let mut func = Func::from_raw(stdcall_func::fastcall_mixed as *const fn());
+        func.set_paranoid(true);
+        func.set_arity(5, false);
+        func.push(0.5f64)
+            .push(2i32)
+            .push(3.0f32)
+            .push(4i32)
+            .push(5i32);
+        unsafe { func.call_custom(&desc) }.unwrap();
+        assert_eq!(func.ret_as_f64(), 54320.5);
+        unsafe { func.try_call(Convention::Fastcall) }.unwrap();
+        assert_eq!(func.ret_as_f64(), 54320.5);
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn usercall() {
+        let desc = ConvDesc::new()
+            .reg("r10")
+            .reg("R11")
+            .stack()
+            .reg("xmm9")
+            .ret("rcx", "rdx")
+            .ret_float("xmm3");
+        let mut func = Func::from_raw(cdecl_func::usercall_probe as *const fn());
+        func.set_paranoid(true);
+        func.push(100usize).push(7usize).push(5usize).push(2.5f64);
+        for _ in 0..10 {
+            unsafe { func.call_custom(&desc) }.unwrap();
+            assert_eq!(func.ret_as_usize(), 109);
+            assert_eq!(func.ret_as_f64(), 2.5);
+        }
+    }
+
+    #[test]
+    fn invalid() {
+        let error = |desc: ConvDesc| {
+            let mut func = Func::from_raw(cdecl_func::no_args as *const fn());
+            for _ in 0..desc.args.len() {
+                func.push(1i32);
+            }
+            match unsafe { func.call_custom(&desc) } {
+                Err(CallError::InvalidDescriptor(reason)) => reason,
+                other => panic!("{:?}", other),
+            }
+        };
+        let (a, b) = if cfg!(target_arch = "x86") {
+            ("ecx", "edx")
+        } else {
+            ("rcx", "rdx")
+        };
+        assert_eq!(
+            error(ConvDesc::new().reg(a).reg("xmm1").reg(a)),
+            format!("register `{}` is used by more than one argument", a)
+        );
+        assert_eq!(
+            error(ConvDesc::new().reg("xmm1").reg("XMM1")),
+            "register `XMM1` is used by more than one argument"
+        );
+        assert_eq!(error(ConvDesc::new().reg("foo")), "unknown register `foo`");
+        assert_eq!(
+            error(ConvDesc::new().reg("xmm01")),
+            "unknown register `xmm01`"
+        );
+        assert_eq!(
+            error(ConvDesc::new().reg("xmm16")),
+            "unknown register `xmm16`"
+        );
+        assert!(error(ConvDesc::new().reg("esp")).contains("cannot be used"));
+        assert!(error(ConvDesc::new().ret("xmm0", b)).contains("not a general purpose register"));
+        assert!(error(ConvDesc::new().ret_float(a)).contains("floating point"));
+
+        // 占多个字的参数不能放在通用寄存器中
+        let mut func = Func::from_raw(cdecl_func::no_args as *const fn());
+        func.push(1u128);
+        let desc = ConvDesc::new().reg(a);
+        match unsafe { func.call_custom(&desc) } {
+            Err(CallError::InvalidDescriptor(reason)) => assert!(reason.contains("argument 0")),
+            other => panic!("{:?}", other),
+        }
+
+        let mut func = Func::from_raw(cdecl_func::no_args as *const fn());
+        func.push(1i32);
+        let desc = ConvDesc {
+            args: vec![ArgDest::Stack, ArgDest::Stack],
+            ..ConvDesc::default()
+        };
+        assert_eq!(
+            unsafe { func.call_custom(&desc) },
+            Err(CallError::ArgCountMismatch {
+                expected: 2,
+                variadic: false,
+                got: 1,
+            })
+        );
+
+        // 多出的栈参数不影响无参函数
+        let desc = ConvDesc::new().stack();
+        unsafe { func.call_custom(&desc) }.unwrap();
+        assert_eq!(func.ret_as_i32(), 42);
+    }
+}
+
+mod sret {
+    use super::*;
+    use cdecl_func::{DVec3, Pair32, Pair64, Triple64, Vec2, Vec4};
+    use funcall::{ArgKind, RetLayout};
+
+    /// 通过隐藏指针返回时, 指针是第一个参数, 其余参数依次后移
+    fn check_plan(func: &Func, args: usize) {
+        let plan = func.dry_run(Convention::Cdecl);
+        if func.returns_in_memory() {
+            assert_eq!(plan.args.len(), args + 1);
+            assert!(plan.args[0].value.to_string().starts_with("ptr"));
+        } else {
+            assert_eq!(plan.args.len(), args);
+        }
+    }
+
+    #[test]
+    fn small_aggregates() {
+        let mut func = Func::from_raw(cdecl_func::make_pair32 as *const fn());
+        func.set_return(RetLayout::Aggregate { size: 8, align: 4 });
+        func.push(7i32);
+        // 32 位 Linux 与 s390x 下所有结构体都通过内存返回
+        assert_eq!(
+            func.returns_in_memory(),
+            cfg!(any(
+                all(target_arch = "x86", not(windows)),
+                target_arch = "s390x"
+            ))
+        );
+        check_plan(&func, 1);
+        if Convention::Cdecl.is_supported() {
+            unsafe { func.try_call(Convention::Cdecl) }.unwrap();
+            assert_eq!(func.read_ret::<Pair32>(), Pair32 { a: 7, b: 8 });
+        }
+
+        let mut func = Func::from_raw(cdecl_func::make_pair64 as *const fn());
+        func.push(-3i64);
+        func.set_return(RetLayout::Aggregate { size: 16, align: 8 });
+        assert_eq!(
+            func.returns_in_memory(),
+            cfg!(any(
+                all(windows, not(target_arch = "aarch64")),
+                target_arch = "x86",
+                target_arch = "arm",
+                target_arch = "s390x"
+            ))
+        );
+        check_plan(&func, 1);
+        if Convention::Cdecl.is_supported() {
+            unsafe { func.try_call(Convention::Cdecl) }.unwrap();
+            assert_eq!(func.read_ret::<Pair64>(), Pair64 { a: -3, b: -2 });
+        }
+    }
+
+    #[test]
+    fn in_memory() {
+        let mut func = Func::from_raw(cdecl_func::make_triple64 as *const fn());
+        func.set_arity(2, false);
+        func.push(10i64).push(2.5f64);
+        func.set_return(RetLayout::Aggregate { size: 24, align: 8 });
+        assert!(func.returns_in_memory());
+        check_plan(&func, 2);
+        if cfg!(all(target_arch = "x86_64", not(windows))) {
+            let plan = func.dry_run(Convention::Cdecl);
+            let locations = plan.args.iter().map(|arg| arg.locations[0].to_string());
+            assert_eq!(locations.collect::<Vec<_>>(), ["rdi", "rsi", "xmm0"]);
+        }
+        if Convention::Cdecl.is_supported() {
+            func.set_paranoid(true);
+            let expected = Triple64 { a: 10, b: 11, c: 2 };
+            unsafe { func.try_call(Convention::Cdecl) }.unwrap();
+            assert_eq!(func.read_ret::<Triple64>(), expected);
+            // 克隆有自己的缓冲区
+            let mut clone = func.clone();
+            unsafe { clone.try_call(Convention::Cdecl) }.unwrap();
+            assert_eq!(clone.read_ret::<Triple64>(), expected);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "libffi")]
+    fn libffi() {
+        use funcall::Backend;
+        // 寄存器与内存两种方式的结果都与汇编后端相同
+        let mut func = Func::from_raw(cdecl_func::make_pair64 as *const fn());
+        func.set_return(RetLayout::Aggregate { size: 16, align: 8 });
+        func.push(5i64);
+        unsafe { func.try_call_with(Convention::Cdecl, Backend::Libffi) }.unwrap();
+        assert_eq!(func.read_ret::<Pair64>(), Pair64 { a: 5, b: 6 });
+
+        let mut func = Func::from_raw(cdecl_func::make_triple64 as *const fn());
+        func.set_return(RetLayout::Aggregate { size: 24, align: 8 });
+        func.push(1i64).push(-4.0f64);
+        unsafe { func.try_call_with(Convention::Cdecl, Backend::Libffi) }.unwrap();
+        assert_eq!(func.read_ret::<Triple64>(), Triple64 { a: 1, b: 2, c: -4 });
+    }
+
+    #[test]
+    fn float_aggregates() {
+        let vec2 = RetLayout::FloatAggregate {
+            count: 2,
+            double: false,
+        };
+        assert_eq!(
+            vec2.in_memory(),
+            cfg!(any(
+                all(target_arch = "x86", not(windows)),
+                target_arch = "s390x",
+                all(target_arch = "arm", not(target_abi = "eabihf"))
+            ))
+        );
+        // 24 字节的 HFA 在 aarch64 下仍通过 v0~v2 返回
+        let dvec3 = RetLayout::FloatAggregate {
+            count: 3,
+            double: true,
+        };
+        assert_eq!(
+            dvec3.in_memory(),
+            !cfg!(any(
+                target_arch = "aarch64",
+                all(target_arch = "arm", target_abi = "eabihf")
+            ))
+        );
+        if !Convention::Cdecl.is_supported() {
+            return;
+        }
+
+        let mut func = Func::from_raw(cdecl_func::make_vec2 as *const fn());
+        func.set_return(vec2);
+        func.push(3i32);
+        unsafe { func.try_call(Convention::Cdecl) }.unwrap();
+        assert_eq!(func.read_ret::<Vec2>(), Vec2 { x: 3.0, y: 3.5 });
+
+        let mut func = Func::from_raw(cdecl_func::make_dvec3 as *const fn());
+        func.set_return(dvec3);
+        func.push(1.5f64);
+        unsafe { func.try_call(Convention::Cdecl) }.unwrap();
+        let expected = DVec3 {
+            x: 1.5,
+            y: 3.0,
+            z: -1.5,
+        };
+        assert_eq!(func.read_ret::<DVec3>(), expected);
+
+        // 64 位 Linux 下后两个成员在 xmm1 中, 汇编后端取不回
+        let mut func = Func::from_raw(cdecl_func::make_vec4 as *const fn());
+        func.set_return(RetLayout::FloatAggregate {
+            count: 4,
+            double: false,
+        });
+        func.push(2i32);
+        let called = unsafe { func.try_call(Convention::Cdecl) };
+        if cfg!(all(target_arch = "x86_64", not(windows))) {
+            assert_eq!(
+                called,
+                Err(CallError::UnsupportedReturn(funcall::Backend::Asm))
+            );
+        } else {
+            called.unwrap();
+            let expected = Vec4 {
+                x: 2.0,
+                y: 2.5,
+                z: 2.25,
+                w: -2.0,
+            };
+            assert_eq!(func.read_ret::<Vec4>(), expected);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "libffi")]
+    fn libffi_float_aggregates() {
+        use funcall::Backend;
+        let mut func = Func::from_raw(cdecl_func::make_vec4 as *const fn());
+        func.set_return(RetLayout::FloatAggregate {
+            count: 4,
+            double: false,
+        });
+        func.push(2i32);
+        unsafe { func.try_call_with(Convention::Cdecl, Backend::Libffi) }.unwrap();
+        let expected = Vec4 {
+            x: 2.0,
+            y: 2.5,
+            z: 2.25,
+            w: -2.0,
+        };
+        assert_eq!(func.read_ret::<Vec4>(), expected);
+        // 克隆有自己的缓冲区
+        let mut clone = func.clone();
+        unsafe { clone.try_call_with(Convention::Cdecl, Backend::Libffi) }.unwrap();
+        assert_eq!(clone.read_ret::<Vec4>(), expected);
+
+        let mut func = Func::from_raw(cdecl_func::make_dvec3 as *const fn());
+        func.set_return(RetLayout::FloatAggregate {
+            count: 3,
+            double: true,
+        });
+        func.push(-2.0f64);
+        unsafe { func.try_call_with(Convention::Cdecl, Backend::Libffi) }.unwrap();
+        let expected = DVec3 {
+            x: -2.0,
+            y: -4.0,
+            z: 2.0,
+        };
+        assert_eq!(func.read_ret::<DVec3>(), expected);
+    }
+
+    #[test]
+    fn scalar() {
+        let mut func = Func::from_raw(cdecl_func::make_pair32 as *const fn());
+        func.set_return(RetLayout::Scalar(ArgKind::I64));
+        assert!(!func.returns_in_memory());
+        assert!(!RetLayout::Scalar(ArgKind::I128).in_memory());
+    }
+
+    #[test]
+    #[should_panic(expected = "size of the declared return layout differs")]
+    fn wrong_size() {
+        let mut func = Func::from_raw(cdecl_func::make_pair64 as *const fn());
+        func.set_return(RetLayout::Aggregate { size: 16, align: 8 });
+        func.read_ret::<Pair32>();
+    }
+}
+
+#[cfg(all(
+    feature = "loader",
+    any(
+        target_arch = "x86",
+        all(target_arch = "x86_64", any(target_os = "linux", windows))
+    )
+))]
+mod noreturn {
+    use super::*;
+    use funcall::libc_func;
+
+    /// 由 exit_status 在子进程中运行, 按环境变量选择调用的函数
+    #[test]
+    fn child() {
+        let which = match std::env::var("FUNCALL_NORETURN") {
+            Ok(which) => which,
+            Err(_) => return,
+        };
+        let mut func = match which.as_str() {
+            "exit" => {
+                let mut func = libc_func("_exit").unwrap();
+                func.push(7i32);
+                func
+            }
+            "args" => {
+                let mut func = Func::from_raw(cdecl_func::exit_sum as *const fn());
+                func.set_arity(10, false);
+                func.push_args((
+                    1i32, 2.5f64, 3i64, 4u8, 5i32, 6i32, 7.5f32, 8i32, 9i32, 10i64,
+                ));
+                func
+            }
+            _ => {
+                let mut func = Func::from_raw(cdecl_func::mixed_args as *const fn());
+                func.push(1i32)
+                    .push(0.5f64)
+                    .push(&4u8 as *const u8)
+                    .push(2u64);
+                func
+            }
+        };
+        unsafe { func.call_noreturn(Convention::Cdecl) }
+    }
+
+    fn run(which: &str) -> std::process::ExitStatus {
+        std::process::Command::new(std::env::current_exe().unwrap())
+            .args(["noreturn::child", "--exact", "--nocapture"])
+            .env("FUNCALL_NORETURN", which)
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .status()
+            .unwrap()
+    }
+
+    #[test]
+    fn exit_status() {
+        assert_eq!(run("exit").code(), Some(7));
+        assert_eq!(run("args").code(), Some(56));
+    }
+
+    #[test]
+    fn returned() {
+        // 被调用者返回时进程被终止, 测试本身不会报告成功
+        let status = run("returned");
+        assert!(!status.success());
+        assert_ne!(status.code(), Some(0));
+    }
+
+    #[test]
+    #[should_panic(expected = "null")]
+    fn checked() {
+        unsafe { Func::null().call_noreturn(Convention::Cdecl) }
+    }
+}
+
+mod layout_override {
+    use super::*;
+    use funcall::LayoutOverride;
+
+    #[test]
+    #[cfg(all(target_arch = "x86_64", not(windows)))]
+    fn plan_diff() {
+        let mut func = Func::from_raw(0x1000 as *const fn());
+        func.push(1i32).push(2.0f64).push(3u8);
+        let abi = func.dry_run(Convention::Cdecl);
+        let stack = func.dry_run_with(Convention::Cdecl, LayoutOverride::StackOnly);
+        assert_eq!(stack.layout, LayoutOverride::StackOnly);
+        let moves = abi
+            .diff(&stack)
+            .iter()
+            .map(|m| m.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            moves,
+            [
+                "#0 i32 1: rdi -> [sp+0x0]",
+                "#1 f64 2: xmm0 -> [sp+0x8]",
+                "#2 u8 3: rsi -> [sp+0x10]",
+            ]
+        );
+        assert_eq!(stack.float_regs, 0);
+        assert_eq!(func.layout_override(), LayoutOverride::Abi);
+
+        // 设置后已压入的参数被重新放置, 之后压入的参数同样在栈上
+        func.set_layout_override(LayoutOverride::StackOnly);
+        assert_eq!(func.dry_run(Convention::Cdecl).args, stack.args);
+        func.push(4i64);
+        let plan = func.dry_run(Convention::Cdecl);
+        assert_eq!(plan.args[3].locations[0].to_string(), "[sp+0x18]");
+        func.set_layout_override(LayoutOverride::Abi);
+        assert_eq!(func.dry_run(Convention::Cdecl).args[..3], abi.args[..]);
+    }
+
+    #[test]
+    fn same_results() {
+        // 不读取参数的函数, 以及 32 位下本来就只从栈上读取参数的函数, 结果不受影响
+        let c = 4u8;
+        let mut funcs = vec![Func::from_raw(cdecl_func::no_args as *const fn())];
+        if cfg!(target_arch = "x86") {
+            let mut func = Func::from_raw(cdecl_func::mixed_args as *const fn());
+            func.push(1i32)
+                .push(0.5f64)
+                .push(&c as *const u8)
+                .push(2u64);
+            funcs.push(func);
+        }
+        for mut func in funcs {
+            let abi = unsafe { func.invoke(Convention::Cdecl) }.unwrap();
+            func.set_layout_override(LayoutOverride::StackOnly);
+            let stack = unsafe { func.invoke(Convention::Cdecl) }.unwrap();
+            assert_eq!(abi, stack);
+        }
+    }
+
+    #[test]
+    #[cfg(all(target_arch = "x86_64", not(windows)))]
+    fn stack_reader() {
+        let mut func = Func::from_raw(cdecl_func::stack_only_probe as *const fn());
+        func.set_layout_override(LayoutOverride::StackOnly);
+        func.push(10i64).push(3i64);
+        assert_eq!(
+            unsafe { func.invoke(Convention::Cdecl) }.unwrap().as_i64(),
+            7
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "libffi")]
+    fn other_backends() {
+        use funcall::Backend;
+        let mut func = Func::from_raw(cdecl_func::no_args as *const fn());
+        func.set_layout_override(LayoutOverride::StackOnly);
+        assert_eq!(
+            unsafe { func.try_call_with(Convention::Cdecl, Backend::Libffi) },
+            Err(CallError::UnsupportedLayout(Backend::Libffi))
+        );
+    }
+}
+
+#[cfg(all(windows, feature = "std"))]
+mod foreign_exception {
+    use super::*;
+
+    /// 由 uncaught 在子进程中运行, 异常抛出后不应回到这里
+    #[test]
+    fn child() {
+        if std::env::var("FUNCALL_SEH").is_err() {
+            return;
+        }
+        let mut func = Func::from_raw(cdecl_func::raise_seh as *const fn());
+        func.push(0xE000_0001u32);
+        let _ = unsafe { func.try_call(Convention::Cdecl) };
+        println!("returned");
+    }
+
+    #[test]
+    fn uncaught() {
+        let output = std::process::Command::new(std::env::current_exe().unwrap())
+            .args(["foreign_exception::child", "--exact", "--nocapture"])
+            .env("FUNCALL_SEH", "1")
+            .output()
+            .unwrap();
+        assert!(!output.status.success());
+        assert!(!String::from_utf8_lossy(&output.stdout).contains("returned"));
+    }
+
+    #[test]
+    #[cfg(all(feature = "protected", target_env = "msvc"))]
+    fn caught() {
+        for _ in 0..3 {
+            let mut func = Func::from_raw(cdecl_func::raise_seh as *const fn());
+            func.push(0xE000_0001u32);
+            let err = unsafe { func.try_call_catching(Convention::Cdecl) }.unwrap_err();
+            assert_eq!(err, CallError::ForeignException { code: 0xE000_0001 });
+            assert_eq!(
+                err.to_string(),
+                "callee raised foreign exception 0xe0000001"
+            );
+        }
+
+        // 之后的调用与栈都不受影响
+        let mut func = Func::from_raw(cdecl_func::mixed_args as *const fn());
+        func.push(1i32)
+            .push(0.5f64)
+            .push(&4u8 as *const u8)
+            .push(2u64);
+        unsafe { func.try_call_catching(Convention::Cdecl).unwrap() };
+        assert_eq!(func.ret_as_f64(), 1420.5);
+    }
+}
+
+#[cfg(all(feature = "loader", target_os = "linux"))]
+mod rtld {
+    use super::*;
+    use funcall::Library;
+
+    #[test]
+    fn next_symbol() {
+        // 像 LD_PRELOAD 的拦截库那样定义 getpid, 并通过 RTLD_NEXT 转发给 C 运行库中的原始定义
+        let path = cdylib::build_with_funcall_having(
+            "interposer_fixture",
+            r#"
+                use funcall::{Convention, Func};
+
+                #[no_mangle]
+                pub extern "C" fn getpid() -> i32 {
+                    let mut real = Func::next_symbol("getpid").unwrap();
+                    unsafe { real.try_call(Convention::Cdecl).unwrap() };
+                    real.ret_as_i32() + 1_000_000_000
+                }
+
+                #[no_mangle]
+                pub extern "C" fn real_getpid() -> *const fn() {
+                    Func::next_symbol("getpid").unwrap().target().addr as *const fn()
+                }
+            "#,
+            "next_symbol",
+        );
+        let lib = Library::new(&path).unwrap();
+        let mut getpid = lib.get("getpid").unwrap();
+        unsafe { getpid.try_call(Convention::Cdecl).unwrap() };
+        assert_eq!(getpid.ret_as_u32(), std::process::id() + 1_000_000_000);
+
+        let mut real = lib.get("real_getpid").unwrap();
+        unsafe { real.try_call(Convention::Cdecl).unwrap() };
+        let real = real.ret_as_usize();
+        assert_ne!(real, getpid.target().addr);
+        assert_eq!(real, funcall::libc_func("getpid").unwrap().target().addr);
+    }
+
+    #[test]
+    fn default_symbol() {
+        let func = Func::default_symbol("getpid").unwrap();
+        assert_eq!(func.target().lib, None);
+        assert_eq!(
+            func.target().addr,
+            funcall::libc_func("getpid").unwrap().target().addr
+        );
+        assert!(Func::default_symbol("funcall_no_such_symbol").is_err());
+    }
+}
+
+#[cfg(windows)]
+mod ansi_str {
+    use super::*;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetACP() -> u32;
+        fn MultiByteToWideChar(
+            code_page: u32,
+            flags: u32,
+            bytes: *const u8,
+            len: i32,
+            wide: *mut u16,
+            wide_len: i32,
+        ) -> i32;
+    }
+
+    /// 压入 s 并取回被调用者看到的字节
+    fn pushed(s: &str, strict: bool) -> Result<Vec<u8>, funcall::Error> {
+        let mut func = Func::from_raw(cdecl_func::return_usize as *const fn());
+        func.push_ansi_str(s, strict)?;
+        unsafe { func.try_call(Convention::Cdecl).unwrap() };
+        let ptr = func.ret_as_usize() as *const std::os::raw::c_char;
+        Ok(unsafe { CStr::from_ptr(ptr) }.to_bytes_with_nul().to_vec())
+    }
+
+    #[test]
+    fn ascii() {
+        assert_eq!(pushed("hello, world", true).unwrap(), b"hello, world\0");
+        assert_eq!(pushed("", false).unwrap(), b"\0");
+    }
+
+    #[test]
+    fn round_trip() {
+        let s = "caf\u{e9} na\u{ef}ve";
+        let bytes = match pushed(s, true) {
+            Ok(bytes) => bytes,
+            // 只有 Windows-1252 与 UTF-8 一定能表示这些字符
+            Err(e) => {
+                assert!(!matches!(unsafe { GetACP() }, 1252 | 65001), "{}", e);
+                return;
+            }
+        };
+        if unsafe { GetACP() } == 1252 {
+            assert_eq!(bytes, b"caf\xe9 na\xefve\0");
+        }
+        let mut wide = vec![0u16; 64];
+        let len = unsafe {
+            MultiByteToWideChar(
+                0,
+                0,
+                bytes.as_ptr(),
+                bytes.len() as i32 - 1,
+                wide.as_mut_ptr(),
+                wide.len() as i32,
+            )
+        };
+        assert_eq!(String::from_utf16(&wide[..len as usize]).unwrap(), s);
+    }
+
+    #[test]
+    fn unmappable() {
+        // 没有代码页能同时表示这些文字与表情符号, 除非 ACP 是 UTF-8
+        let s = "\u{5b57}\u{adf8}\u{3b1}\u{5d0}\u{1f600}";
+        if unsafe { GetACP() } == 65001 {
+            return;
+        }
+        let mut func = Func::from_raw(cdecl_func::return_usize as *const fn());
+        assert!(matches!(
+            func.push_ansi_str(s, true),
+            Err(funcall::Error::InvalidInput(_))
+        ));
+        assert!(func.dry_run(Convention::Cdecl).args.is_empty());
+        assert!(pushed(s, false).unwrap().contains(&b'?'));
+    }
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64", target_arch = "aarch64"))]
+mod fp_state {
+    use super::*;
+    use std::hint::black_box;
+
+    /// 1/3 在就近舍入与向上舍入下的结果相差最低一位
+    fn third() -> u64 {
+        (black_box(1.0f64) / black_box(3.0f64)).to_bits()
+    }
+
+    fn call_round_upward(protect: bool) {
+        let mut func = Func::from_raw(cdecl_func::round_upward as *const fn());
+        func.protect_fp_state(protect);
+        unsafe { func.try_call(Convention::Cdecl).unwrap() };
+    }
+
+    #[test]
+    fn protected() {
+        if !Convention::Cdecl.is_supported() {
+            return;
+        }
+        let nearest = third();
+        for _ in 0..3 {
+            call_round_upward(true);
+            assert_eq!(third(), nearest);
+        }
+    }
+
+    #[test]
+    fn leaked() {
+        if !Convention::Cdecl.is_supported() {
+            return;
+        }
+        let nearest = third();
+        // 没有开启时被调用者修改的舍入模式会一直保留在线程中, 因此在单独的线程中调用
+        let upward = std::thread::spawn(|| {
+            call_round_upward(false);
+            third()
+        })
+        .join()
+        .unwrap();
+        assert_eq!(upward, nearest + 1);
+        assert_eq!(third(), nearest);
+    }
+}
+
+mod transparent {
+    use super::*;
+    use funcall::{transparent_arg, ArgKind};
+
+    #[repr(transparent)]
+    struct Fd(i32);
+    #[repr(transparent)]
+    struct Bytes(*const u8);
+    #[repr(transparent)]
+    struct Seconds(f64);
+
+    transparent_arg! {
+        Fd(i32),
+        Bytes(*const u8),
+        Seconds(f64),
+    }
+
+    #[test]
+    fn kinds() {
+        let mut func = Func::from_raw(cdecl_func::mixed_args as *const fn());
+        func.push(Fd(1))
+            .push(Seconds(0.5))
+            .push(Bytes(&4u8))
+            .push(2u64);
+        let plan = func.dry_run(Convention::Cdecl);
+        let kinds: Vec<_> = plan.args.iter().map(|arg| arg.value.kind()).collect();
+        assert_eq!(
+            kinds,
+            [ArgKind::I32, ArgKind::F64, ArgKind::Ptr, ArgKind::U64]
+        );
+        #[cfg(all(target_arch = "x86_64", not(windows)))]
+        assert_eq!(plan.args[1].locations, [funcall::ArgLocation::FloatReg(0)]);
+    }
+
+    #[test]
+    fn call() {
+        if !Convention::Cdecl.is_supported() {
+            return;
+        }
+        let c = 4u8;
+        let mut func = Func::from_raw(cdecl_func::mixed_args as *const fn());
+        func.push(Fd(1))
+            .push(Seconds(0.5))
+            .push(Bytes(&c))
+            .push(2u64);
+        unsafe { func.try_call(Convention::Cdecl).unwrap() };
+        assert_eq!(func.ret_as_f64(), 1420.5);
+
+        let mut func = Func::from_raw(cdecl_func::return_f64 as *const fn());
+        func.push(Seconds(-0.125));
+        unsafe { func.try_call(Convention::Cdecl).unwrap() };
+        assert_eq!(func.ret_as_f64(), -0.125);
+    }
+}
+
+#[cfg(all(feature = "loader", target_os = "linux"))]
+mod cli {
+    use std::process::{Command, Output};
+
+    fn run(args: &[&str]) -> Output {
+        Command::new(env!("CARGO_BIN_EXE_funcall"))
+            .args(args)
+            .env("FUNCALL_CLI_TEST", "value")
+            .output()
+            .unwrap()
+    }
+
+    fn stdout(args: &[&str]) -> String {
+        let output = run(args);
+        assert!(output.status.success(), "{:?}", output);
+        String::from_utf8(output.stdout).unwrap()
+    }
+
+    #[test]
+    fn typed_calls() {
+        assert_eq!(
+            stdout(&[
+                "--lib",
+                "libc.so.6",
+                "--conv",
+                "cdecl",
+                "int strlen(const char*)",
+                "hello"
+            ]),
+            "5\n"
+        );
+        assert_eq!(stdout(&["long labs(long)", "-0x10"]), "16\n");
+        assert_eq!(stdout(&["int toupper(int)", "97"]), "65\n");
+        assert_eq!(
+            stdout(&[
+                "--lib",
+                "libm.so.6",
+                "double pow(double, double)",
+                "2",
+                "0.5"
+            ]),
+            format!("{}\n", 2f64.sqrt())
+        );
+        assert!(
+            stdout(&["char *getenv(const char *)", "FUNCALL_CLI_TEST"]).ends_with(" \"value\"\n")
+        );
+        assert_eq!(
+            stdout(&["char *getenv(const char *)", "FUNCALL_CLI_NO_SUCH_VAR"]),
+            "NULL\n"
+        );
+    }
+
+    #[test]
+    fn variadic() {
+        let out = stdout(&[
+            "int printf(const char *, ...)",
+            "%d-%s-%.1f|",
+            "42",
+            "abc",
+            "1.5",
+        ]);
+        assert!(out.contains("42-abc-1.5|"), "{}", out);
+        assert!(out.contains("11\n"), "{}", out);
+    }
+
+    #[test]
+    fn errno() {
+        // 关闭不存在的文件描述符, errno 为 EBADF
+        assert_eq!(
+            stdout(&["--errno", "int close(int)", "12345"]),
+            "-1\nerrno: 9\n"
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let output = run(&["int abs(int)", "99999999999"]);
+        assert_eq!(output.status.code(), Some(1));
+        assert!(String::from_utf8_lossy(&output.stderr).starts_with("error: argument 0"));
+
+        let output = run(&["int abs(int)", "abc"]);
+        assert_eq!(output.status.code(), Some(1));
+        assert_eq!(
+            String::from_utf8_lossy(&output.stderr),
+            "error: argument 0: `abc` is not an integer\n"
+        );
+
+        let output = run(&["int funcall_no_such_function(void)"]);
+        assert_eq!(output.status.code(), Some(1));
+
+        let output = run(&["--conv", "pascal", "int abs(int)"]);
+        assert_eq!(output.status.code(), Some(2));
+        assert!(String::from_utf8_lossy(&output.stderr).contains("usage: funcall"));
+    }
+}
+
+mod small_int_extension {
+    use super::*;
+    use funcall::{ArgKind, ArgSink, IntoArg};
+
+    // 按 kind 声明小整数, 却在字的高位留下任意值的参数
+    macro_rules! dirty {
+        ($($name:ident: $kind:ident),*) => {$(
+            struct $name(usize);
+
+            impl IntoArg for $name {
+                const KIND: ArgKind = ArgKind::$kind;
+
+                fn push_into(self, out: &mut ArgSink) {
+                    out.push_word(self.0);
+                }
+            }
+        )*};
+    }
+
+    dirty!(DirtyI8: I8, DirtyU8: U8, DirtyI16: I16, DirtyU16: U16);
+
+    /// 以整个字读取传入的参数
+    fn whole_word(arg: impl IntoArg) -> usize {
+        let mut func = Func::from_raw(cdecl_func::return_usize as *const fn());
+        func.push(arg);
+        unsafe { func.cdecl() };
+        func.ret_as_usize()
+    }
+
+    // Apple arm64 下被调用者直接使用寄存器中的 32 位值, 调用者必须完成扩展
+    #[test]
+    fn callee_sees_extended() {
+        let mut func = Func::from_raw(cdecl_func::small_ints as *const fn());
+        func.push(DirtyI8(0xABCD_00FB))
+            .push(DirtyU8(0x1234_56C8))
+            .push(DirtyI16(0xDEAD_FF85));
+        unsafe { func.cdecl() };
+        assert_eq!(func.ret_as_i64(), -5_000_000_000 + 200_000_000 - 123);
+    }
+
+    #[test]
+    fn extended_to_word() {
+        assert_eq!(whole_word(DirtyI8(0xABCD_00FB)), -5isize as usize);
+        assert_eq!(whole_word(DirtyU8(0x1234_56C8)), 0xC8);
+        assert_eq!(whole_word(DirtyI16(0xDEAD_FF85)), -123isize as usize);
+        assert_eq!(whole_word(DirtyU16(0xDEAD_FF85)), 0xFF85);
+    }
+}
+
+mod aapcs {
+    use super::*;
+
+    #[test]
+    fn default_variant() {
+        let expected = if cfg!(target_abi = "eabihf") {
+            Convention::AapcsVfp
+        } else {
+            Convention::AapcsSoftFloat
+        };
+        assert_eq!(Convention::aapcs(), expected);
+        assert_eq!(
+            Convention::AapcsSoftFloat.is_supported(),
+            cfg!(all(target_arch = "arm", feature = "libffi"))
+        );
+        assert_eq!(
+            Convention::AapcsVfp.is_supported(),
+            cfg!(all(
+                target_arch = "arm",
+                target_abi = "eabihf",
+                feature = "libffi"
+            ))
+        );
+    }
+
+    #[test]
+    #[cfg(not(target_arch = "arm"))]
+    fn unsupported() {
+        let mut func = Func::from_raw(cdecl_func::no_args as *const fn());
+        for conv in [Convention::AapcsSoftFloat, Convention::AapcsVfp] {
+            assert!(matches!(
+                unsafe { func.try_call(conv) },
+                Err(CallError::UnsupportedConvention(_) | CallError::UnsupportedTarget { .. })
+            ));
+        }
+    }
+
+    /// 以 conv 调用 `scale(1.5, 4.0f)` 与 `half(5.0f)`, 检查浮点参数与返回值所在的寄存器
+    #[cfg(all(target_arch = "arm", feature = "libffi"))]
+    fn float_cases(conv: Convention, scale: *const fn(), half: *const fn()) {
+        let mut func = Func::from_raw(scale);
+        func.set_arity(2, false);
+        func.set_ret_kind(funcall::ArgKind::F64);
+        func.push(1.5f64).push(4.0f32);
+        unsafe { func.try_call(conv).unwrap() };
+        assert_eq!(func.ret_as_f64(), 6.0, "{:?}", conv);
+
+        let mut func = Func::from_raw(half);
+        func.set_ret_kind(funcall::ArgKind::CFloat);
+        func.push_float(5.0);
+        unsafe { func.try_call(conv).unwrap() };
+        assert_eq!(func.ret_as_f32(), 2.5, "{:?}", conv);
+    }
+
+    #[test]
+    #[cfg(all(target_arch = "arm", feature = "libffi"))]
+    fn soft_float() {
+        float_cases(
+            Convention::AapcsSoftFloat,
+            cdecl_func::soft_scale as *const fn(),
+            cdecl_func::soft_half as *const fn(),
+        );
+    }
+
+    #[test]
+    #[cfg(all(target_arch = "arm", feature = "libffi"))]
+    fn native() {
+        // Cdecl 使用编译目标的浮点 ABI, 与 `Convention::aapcs()` 相同
+        for conv in [Convention::Cdecl, Convention::aapcs()] {
+            float_cases(
+                conv,
+                cdecl_func::native_scale as *const fn(),
+                cdecl_func::native_half as *const fn(),
+            );
+        }
+    }
+}
+
+/// 64 位 Windows 下可变参数函数从整数寄存器读取浮点参数, 前四个位置上的浮点数需要同时送入两种寄存器
+#[cfg(all(feature = "loader", windows, target_arch = "x86_64"))]
+mod win64_variadic {
+    use super::*;
+
+    #[test]
+    fn mixed_positions() {
+        for declared in [false, true] {
+            // 两个浮点数分别在 r9/xmm3 与栈上
+            let out = sprintf(
+                "%d %f %f",
+                |func| {
+                    func.push(7i32).push(1.5f64).push(2.25f64);
+                },
+                declared,
+            );
+            assert_eq!(out, "7 1.500000 2.250000");
+            // 浮点数在 r8/xmm2, 整数在 r9
+            let out = sprintf(
+                "%f %d %f",
+                |func| {
+                    func.push(-0.5f64).push(3i32).push(4.0f64);
+                },
+                declared,
+            );
+            assert_eq!(out, "-0.500000 3 4.000000");
+        }
+    }
+}
+
+#[cfg(windows)]
+mod wide_ret {
+    use super::*;
+
+    #[test]
+    fn null_and_slice() {
+        let mut func = Func::from_raw(cdecl_func::return_usize as *const fn());
+        func.push(0usize);
+        unsafe { func.cdecl() };
+        assert_eq!(unsafe { func.ret_as_wide_c_str() }, None);
+
+        let text: Vec<u16> = "路径\u{1F600}".encode_utf16().chain(Some(0)).collect();
+        let mut func = Func::from_raw(cdecl_func::return_usize as *const fn());
+        func.push(text.as_ptr());
+        unsafe { func.cdecl() };
+        assert_eq!(
+            unsafe { func.ret_as_wide_c_str() },
+            Some(&text[..text.len() - 1])
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "loader")]
+    fn command_line() {
+        let mut func = Func::new("kernel32.dll", b"GetCommandLineW\0").unwrap();
+        unsafe { func.try_call(Convention::System).unwrap() };
+        let line = unsafe { func.ret_as_os_string() }.unwrap();
+        let line = line.to_string_lossy();
+        // 命令行中的程序名可能带有引号或是相对路径, 只比较文件名
+        let exe = std::env::args_os().next().unwrap();
+        let name = std::path::Path::new(&exe).file_stem().unwrap();
+        assert!(line.contains(&*name.to_string_lossy()), "{}", line);
+    }
+}
+
+#[cfg(target_arch = "x86")]
+mod thiscall_flavor {
+    use super::*;
+    use funcall::ThiscallFlavor;
+
+    #[test]
+    fn msvc_by_default() {
+        assert_eq!(ThiscallFlavor::default(), ThiscallFlavor::Msvc);
+        let widget = stdcall_func::Widget { base: 7 };
+        let mut func = Func::from_raw(stdcall_func::Widget::scaled as *const fn());
+        func.set_paranoid(true);
+        func.push(&widget as *const _).push(3i32).push(0.5f64);
+        unsafe { func.try_call(Convention::Thiscall) }.unwrap();
+        assert_eq!(func.ret_as_f64(), 21.5);
+
+        let mut func = Func::from_raw(stdcall_func::Widget::scaled as *const fn());
+        func.set_thiscall_flavor(ThiscallFlavor::Msvc);
+        func.push(&widget as *const _).push(2i32).push(0.25f64);
+        unsafe { func.try_call(Convention::Thiscall) }.unwrap();
+        assert_eq!(func.ret_as_f64(), 14.25);
+    }
+
+    #[test]
+    fn gcc() {
+        let widget = stdcall_func::Widget { base: 7 };
+        let mut func = Func::from_raw(stdcall_func::Widget::gcc_scaled as *const fn());
+        func.set_thiscall_flavor(ThiscallFlavor::Gcc);
+        func.set_paranoid(true);
+        func.push(&widget as *const _).push(3i32).push(0.5f64);
+        unsafe { func.try_call(Convention::Thiscall) }.unwrap();
+        assert_eq!(func.ret_as_f64(), 21.5);
+
+        // 重复调用时栈仍然平衡
+        for _ in 0..3 {
+            unsafe { func.try_call(Convention::Thiscall) }.unwrap();
+            assert_eq!(func.ret_as_f64(), 21.5);
+        }
+    }
+}
+
+mod arg_pack {
+    use super::*;
+    use funcall::ArgPack;
+    use std::ffi::CString;
+
+    fn pack() -> ArgPack {
+        let mut pack = ArgPack::new();
+        pack.push_c_string(CString::new("hello").unwrap())
+            .push(1.5f64);
+        pack
+    }
+
+    #[test]
+    fn shared() {
+        let pack = pack();
+        assert_eq!(pack.len(), 2);
+        let mut funcs = [
+            Func::from_raw(cdecl_func::pack_str as *const fn()),
+            Func::from_raw(cdecl_func::pack_f64 as *const fn()),
+            Func::from_raw(cdecl_func::pack_len as *const fn()),
+        ];
+        for func in &mut funcs {
+            func.load(&pack);
+            unsafe { func.try_call(Convention::Cdecl) }.unwrap();
+        }
+        let s = funcs[0].ret_as_usize() as *const std::os::raw::c_char;
+        assert_eq!(unsafe { CStr::from_ptr(s) }.to_str(), Ok("hello"));
+        assert_eq!(funcs[1].ret_as_f64(), 1.5);
+        assert_eq!(funcs[2].ret_as_f64(), 6.5);
+        // 三个调用看到的是同一个字符串, 同一个调用再装入一次时也一样
+        let arg0 = |func: &Func| func.arg_views().next().unwrap().to_string();
+        assert_eq!(arg0(&funcs[1]), arg0(&funcs[0]));
+        assert_eq!(arg0(&funcs[2]), arg0(&funcs[0]));
+        let mut again = Func::from_raw(cdecl_func::pack_str as *const fn());
+        again.load(&pack);
+        unsafe { again.try_call(Convention::Cdecl) }.unwrap();
+        assert_eq!(again.ret_as_usize(), funcs[0].ret_as_usize());
+    }
+
+    #[test]
+    fn copied() {
+        let pack = pack();
+        let mut shared = Func::from_raw(cdecl_func::pack_str as *const fn());
+        shared.load(&pack);
+        unsafe { shared.try_call(Convention::Cdecl) }.unwrap();
+        let mut func = Func::from_raw(cdecl_func::pack_str as *const fn());
+        func.load_copy(&pack);
+        drop(pack);
+        unsafe { func.try_call(Convention::Cdecl) }.unwrap();
+        assert_ne!(func.ret_as_usize(), shared.ret_as_usize());
+        let s = func.ret_as_usize() as *const std::os::raw::c_char;
+        assert_eq!(unsafe { CStr::from_ptr(s) }.to_str(), Ok("hello"));
+    }
+
+    #[test]
+    fn after_pushed_args() {
+        let mut pack = ArgPack::new();
+        pack.push(3i32).push(4i32);
+        let mut func = Func::from_raw(cdecl_func::more_than_6_args as *const fn());
+        func.push_args((1i32, 1i32, 1i32, 1i32, 1i32, 1i32));
+        func.load(&pack);
+        unsafe { func.try_call(Convention::Cdecl) }.unwrap();
+        assert_eq!(func.ret_as_i32(), 13);
+        assert_eq!(func.dry_run(Convention::Cdecl), {
+            let mut direct = Func::from_raw(cdecl_func::more_than_6_args as *const fn());
+            direct.push_args((1i32, 1i32, 1i32, 1i32, 1i32, 1i32, 3i32, 4i32));
+            direct.dry_run(Convention::Cdecl)
+        });
+    }
+
+    #[test]
+    fn narrowed_floats() {
+        let mut pack = ArgPack::new();
+        pack.push(1.5f32).push(2.0f64).push(0.25f32);
+        let mut func = Func::from_raw(cdecl_func::float_args as *const fn());
+        func.set_arity(3, false);
+        func.load(&pack);
+        unsafe { func.try_call(Convention::Cdecl) }.unwrap();
+        assert_eq!(func.ret_as_f64(), 170.25);
+    }
+}
+
+#[cfg(all(
+    feature = "loader",
+    feature = "libffi",
+    windows,
+    target_arch = "aarch64"
+))]
+mod arm64_windows_variadic {
+    use super::*;
+
+    #[test]
+    fn floats_in_gprs() {
+        // 只有声明为可变参数函数时浮点数才会放入整数寄存器
+        let out = sprintf(
+            "%d %f %f",
+            |func| {
+                func.push(7i32).push(1.5f64).push(2.25f64);
+            },
+            true,
+        );
+        assert_eq!(out, "7 1.500000 2.250000");
+        let plan = {
+            let mut func = Func::from_raw(0x1000 as *const fn());
+            func.set_arity(2, true);
+            func.push(0usize).push(0usize).push(7i32).push(1.5f64);
+            func.dry_run(Convention::Cdecl)
+        };
+        assert_eq!(plan.args[3].locations[0].to_string(), "x3");
+    }
+
+    #[test]
+    fn scalars() {
+        let mut func = Func::from_raw(cdecl_func::pack_len as *const fn());
+        func.push(b"arm64\0".as_ptr()).push(0.5f64);
+        unsafe { func.try_call(Convention::Cdecl) }.unwrap();
+        assert_eq!(func.ret_as_f64(), 5.5);
+    }
+}
+
+#[cfg(all(feature = "loader", target_os = "linux"))]
+mod lazy_resolve {
+    use super::*;
+    use std::ffi::CString;
+    use std::os::raw::{c_char, c_int, c_void};
+    use std::path::Path;
+
+    /// 以 RTLD_NOLOAD 探测库是否已经加载, 不会加载它
+    fn loaded(path: &Path) -> bool {
+        extern "C" {
+            fn dlopen(path: *const c_char, flags: c_int) -> *mut c_void;
+            fn dlclose(handle: *mut c_void) -> c_int;
+        }
+        const RTLD_LAZY: c_int = 1;
+        const RTLD_NOLOAD: c_int = 4;
+        let path = CString::new(path.to_str().unwrap()).unwrap();
+        let handle = unsafe { dlopen(path.as_ptr(), RTLD_LAZY | RTLD_NOLOAD) };
+        if !handle.is_null() {
+            unsafe { dlclose(handle) };
+        }
+        !handle.is_null()
+    }
+
+    fn fixture(name: &str) -> std::path::PathBuf {
+        cdylib::build(
+            name,
+            r#"#[no_mangle] pub extern "C" fn twice(x: i32) -> i32 { x * 2 }"#,
+        )
+    }
+
+    #[test]
+    fn loaded_on_first_call() {
+        let path = fixture("lazy_first_call");
+        let mut func = Func::new_lazy(&path, "twice");
+        let mut clone = func.clone();
+        assert!(func.is_pending());
+        assert!(!loaded(&path));
+
+        func.push(21i32);
+        unsafe { func.try_call(Convention::Cdecl) }.unwrap();
+        assert_eq!(func.ret_as_i32(), 42);
+        assert!(!func.is_pending());
+        assert!(loaded(&path));
+
+        // 克隆共享查找的结果
+        clone.push(5i32);
+        unsafe { clone.try_call(Convention::Cdecl) }.unwrap();
+        assert_eq!(clone.ret_as_i32(), 10);
+        assert!(func.same_target(&clone));
+    }
+
+    // 返回 Result 的调用方法都会在调用前进行查找
+    #[test]
+    fn every_entry_point() {
+        let path = fixture("lazy_entry_points");
+        let lazy = || {
+            let mut func = Func::new_lazy(&path, "twice");
+            func.push(21i32);
+            func
+        };
+        let conv = Convention::Cdecl;
+        unsafe {
+            assert_eq!(lazy().invoke(conv).unwrap().as_i32(), 42);
+            assert_eq!(lazy().call_once::<i32>(conv).unwrap(), 42);
+
+            let mut func = lazy();
+            func.call(conv).unwrap();
+            assert_eq!(func.ret_as_i32(), 42);
+
+            let mut func = lazy();
+            func.try_call_with(conv, funcall::Backend::Asm).unwrap();
+            assert_eq!(func.ret_as_i32(), 42);
+
+            let mut func = lazy();
+            func.call_on_thread(conv, 1 << 20).unwrap();
+            assert_eq!(func.ret_as_i32(), 42);
+
+            let outcome = lazy().call_in_fork(conv).unwrap();
+            assert!(
+                matches!(outcome, funcall::ForkOutcome::Returned { ret, .. } if ret.as_i32() == 42),
+                "{:?}",
+                outcome
+            );
+
+            let outcome = lazy().call_with_timeout(conv, std::time::Duration::from_secs(5));
+            assert_eq!(outcome.unwrap().ret.as_i32(), 42);
+
+            #[cfg(feature = "protected")]
+            {
+                let mut func = lazy();
+                func.call_protected(conv).unwrap();
+                assert_eq!(func.ret_as_i32(), 42);
+            }
+        }
+    }
+
+    #[test]
+    fn explicit_resolve() {
+        let path = fixture("lazy_explicit");
+        let mut func = Func::new_lazy(&path, "twice");
+        assert!(!loaded(&path));
+        func.resolve().unwrap();
+        assert!(loaded(&path));
+        func.push(4i32);
+        unsafe { func.cdecl() };
+        assert_eq!(func.ret_as_i32(), 8);
+    }
+
+    #[test]
+    fn missing_symbol_at_call_time() {
+        let path = fixture("lazy_missing");
+        let mut func = Func::new_lazy(&path, "no_such_symbol");
+        func.push(1i32);
+        match unsafe { func.try_call(Convention::Cdecl) } {
+            Err(CallError::ResolveFailed { symbol, reason }) => {
+                assert_eq!(symbol, "no_such_symbol");
+                assert!(reason.contains("no_such_symbol"), "{}", reason);
+            }
+            other => panic!("{:?}", other),
+        }
+        // 失败同样被缓存
+        assert!(func.is_pending());
+        assert!(matches!(
+            func.resolve(),
+            Err(CallError::ResolveFailed { .. })
+        ));
+
+        let mut func = Func::new_lazy("libno_such_library.so", "twice");
+        assert!(matches!(
+            unsafe { func.try_call(Convention::Cdecl) },
+            Err(CallError::ResolveFailed { .. })
+        ));
+    }
+}
+
+mod self_test {
+    use funcall::{Backend, Convention, SelfTestFailure, SelfTestReport};
+
+    #[test]
+    fn default_backend() {
+        if !Convention::Cdecl.is_supported() {
+            return;
+        }
+        if let Err(report) = funcall::self_test() {
+            panic!("{}", report);
+        }
+    }
+
+    #[cfg(feature = "libffi")]
+    #[test]
+    fn libffi() {
+        if let Err(report) = funcall::self_test_with(Backend::Libffi) {
+            panic!("{}", report);
+        }
+    }
+
+    #[cfg(all(feature = "std", target_arch = "x86_64", target_os = "linux"))]
+    #[test]
+    fn jit() {
+        if let Err(report) = funcall::self_test_with(Backend::Jit) {
+            panic!("{}", report);
+        }
+    }
+
+    #[test]
+    fn unsupported_backend_reports_every_case() {
+        // 不支持 cdecl 的后端使每个用例都失败, 失败的原因是 try_call 的错误
+        if Backend::Jit.supports(Convention::Cdecl) {
+            return;
+        }
+        let report = funcall::self_test_with(Backend::Jit).unwrap_err();
+        assert_eq!(report.failures.len(), report.cases);
+        assert!(report.failures.iter().any(|f| f.case == "many_mixed"));
+        assert!(report.to_string().starts_with(&format!(
+            "{} of {} self-test case(s) failed:",
+            report.cases, report.cases
+        )));
+    }
+
+    #[test]
+    fn report_format() {
+        let report = SelfTestReport {
+            cases: 3,
+            failures: vec![SelfTestFailure {
+                case: "echo_f32",
+                expected: "-0.33333334".to_owned(),
+                got: "0.0".to_owned(),
+            }],
+        };
+        assert_eq!(
+            report.to_string(),
+            "1 of 3 self-test case(s) failed:\n  echo_f32: expected -0.33333334, got 0.0"
+        );
+    }
+}
+
+// 以 `--no-default-features --features std` 运行, 确认不依赖 libloading 时仍然可以调用已知地址的函数
+#[cfg(all(feature = "std", not(feature = "loader")))]
+mod without_loader {
+    use super::*;
+
+    #[test]
+    fn raw_pointer() {
+        let shared = Func::from_raw(cdecl_func::float_then_int as *const fn()).share();
+        let mut func = shared.func();
+        func.push_float(1.5f32).push(2i32);
+        unsafe { func.try_call(Convention::Cdecl) }.unwrap();
+        assert_eq!(func.ret_as_i32(), cdecl_func::float_then_int(1.5, 2));
+        // 没有库, 调试输出中也不再有 lib 字段
+        assert!(!format!("{:?}", func).contains("lib"));
+        funcall::self_test().unwrap();
+    }
+}
+
+mod cstring_array {
+    use super::*;
+
+    fn join(func: &mut Func) -> (usize, String) {
+        let mut out = [0u8; 64];
+        func.push(b' ').push(out.as_mut_ptr());
+        unsafe { func.try_call(Convention::Cdecl).unwrap() };
+        let s = CStr::from_bytes_until_nul(&out).unwrap();
+        (func.ret_as_usize(), s.to_str().unwrap().to_owned())
+    }
+
+    #[test]
+    fn walk() {
+        let mut func = Func::from_raw(cdecl_func::join_argv as *const fn());
+        let items = vec![String::from("ls"), String::from("-l"), String::from("/tmp")];
+        func.push_cstring_array(&items.iter().map(|s| s.as_str()).collect::<Vec<_>>())
+            .unwrap();
+        // 字符串已被复制, 原来的可以立即释放
+        drop(items);
+        assert_eq!(join(&mut func), (3, "ls -l /tmp".to_owned()));
+
+        let mut func = Func::from_raw(cdecl_func::join_argv as *const fn());
+        func.push_cstring_array(&[]).unwrap();
+        assert_eq!(join(&mut func), (0, String::new()));
+    }
+
+    #[test]
+    fn clone_copies_strings() {
+        let mut func = Func::from_raw(cdecl_func::join_argv as *const fn());
+        func.push_cstring_array(&["PATH=/bin", "HOME=/root"])
+            .unwrap();
+        let mut copy = func.clone();
+        let strings = |func: &Func| {
+            let argv = func.arg_views().next().unwrap().bits() as usize;
+            unsafe { std::slice::from_raw_parts(argv as *const *const u8, 3) }.to_vec()
+        };
+        let (original, copied) = (strings(&func), strings(&copy));
+        // 副本中的数组指向复制出的字符串, 原来的 Func 被 drop 后仍然有效
+        assert_ne!(original[0], copied[0]);
+        assert_ne!(original[1], copied[1]);
+        assert!(copied[2].is_null());
+        drop(func);
+        assert_eq!(join(&mut copy), (2, "PATH=/bin HOME=/root".to_owned()));
+    }
+
+    #[test]
+    fn interior_nul() {
+        let mut func = Func::from_raw(cdecl_func::join_argv as *const fn());
+        assert!(func.push_cstring_array(&["a", "b\0c"]).is_err());
+        assert_eq!(func.arg_views().count(), 0);
+    }
+
+    #[test]
+    fn borrowed() {
+        let items = [
+            CStr::from_bytes_with_nul(b"echo\0").unwrap(),
+            CStr::from_bytes_with_nul(b"hi\0").unwrap(),
+        ];
+        let mut func = Func::from_raw(cdecl_func::join_argv as *const fn());
+        func.push_cstr_array(&items);
+        assert_eq!(join(&mut func), (2, "echo hi".to_owned()));
+    }
+}
+
+mod supported_conventions {
+    use super::*;
+
+    #[test]
+    fn matches_is_supported() {
+        let supported = Convention::ALL
+            .iter()
+            .copied()
+            .filter(|conv| conv.is_supported())
+            .collect::<Vec<_>>();
+        assert_eq!(funcall::supported_conventions(), &supported[..]);
+    }
+
+    #[test]
+    fn unsupported_target() {
+        let e = CallError::unsupported_target();
+        assert_eq!(
+            e,
+            CallError::UnsupportedTarget {
+                arch: std::env::consts::ARCH,
+                os: std::env::consts::OS,
+            }
+        );
+        assert_eq!(
+            e.to_string(),
+            format!(
+                "no calling convention is supported on {}-{}, try enabling the `libffi` feature",
+                std::env::consts::ARCH,
+                std::env::consts::OS
+            )
+        );
+    }
+
+    #[test]
+    fn call() {
+        for &conv in Convention::ALL {
+            let mut func = Func::from_raw(cdecl_func::no_args as *const fn());
+            let ret = unsafe { func.call(conv) };
+            if funcall::supported_conventions().is_empty() {
+                assert_eq!(ret, Err(CallError::unsupported_target()));
+            } else if !conv.is_supported() {
+                assert_eq!(ret, Err(CallError::UnsupportedConvention(conv)));
+            } else if conv == Convention::Cdecl || conv == Convention::System {
+                assert_eq!(ret, Ok(()));
+                assert_eq!(func.ret_as_i32(), cdecl_func::no_args());
+            }
+        }
+    }
+}
+
+#[cfg(all(feature = "regcheck", target_arch = "x86_64", target_os = "linux"))]
+mod regcheck {
+    use super::*;
+
+    #[test]
+    #[should_panic(expected = "r12 (")]
+    fn clobbered() {
+        let mut func = Func::from_raw(cdecl_func::clobber_r12 as *const fn());
+        unsafe { func.cdecl() };
+    }
+
+    #[test]
+    fn well_behaved() {
+        let mut func = Func::from_raw(cdecl_func::float_then_int as *const fn());
+        func.push_float(1.5).push(2i32);
+        unsafe { func.cdecl() };
+        assert_eq!(func.ret_as_i32(), cdecl_func::float_then_int(1.5, 2));
+
+        // 参数通过栈传递时同样能找到保存区
+        let mut func = Func::from_raw(cdecl_func::more_than_6_args as *const fn());
+        for i in 1..=8 {
+            func.push(i);
+        }
+        unsafe { func.cdecl() };
+        assert_eq!(func.ret_as_i32(), 36);
+    }
+}
+
+mod call_x64 {
+    use super::*;
+
+    #[test]
+    fn unsupported() {
+        // 只有 x64 Windows 的 WOW64 中才能找到 64 位的 ntdll, 其他环境下都不支持
+        if funcall::ntdll64_export("NtClose").is_some() {
+            return;
+        }
+        let mut func = Func::from_raw(cdecl_func::no_args as *const fn());
+        let ret = unsafe { func.call_x64() };
+        assert_eq!(ret, Err(CallError::unsupported_target()));
+        let ret = unsafe { Func::null().call_x64() };
+        assert_eq!(ret, Err(CallError::unsupported_target()));
+    }
+
+    #[test]
+    #[cfg(all(target_arch = "x86", windows))]
+    fn nt_query_information_process() {
+        let target = match funcall::ntdll64_export("NtQueryInformationProcess") {
+            Some(target) => target,
+            None => return,
+        };
+        // PROCESS_BASIC_INFORMATION 的 64 位版本, UniqueProcessId 在偏移 32 处
+        let mut info = [0u64; 6];
+        let mut len = 0u32;
+        let mut func = Func::from_raw(cdecl_func::no_args as *const fn());
+        // 当前进程的伪句柄 -1 需要符号扩展为 64 位
+        func.push(-1isize)
+            .push(0u32)
+            .push(info.as_mut_ptr())
+            .push(48u32)
+            .push(&mut len as *mut u32);
+        unsafe { func.call_x64_at(target) }.unwrap();
+        assert_eq!(func.ret_as_u64(), 0);
+        assert_eq!(len, 48);
+        assert_eq!(info[4], u64::from(std::process::id()));
+    }
+}
+
+#[cfg(all(feature = "std", unix))]
+mod call_in_fork {
+    use super::*;
+    use funcall::{ForkError, ForkOutcome};
+
+    extern "C" {
+        fn close(fd: i32) -> i32;
+    }
+
+    #[test]
+    fn returned() {
+        let n = 7;
+        let mut func = Func::from_raw(cdecl_func::read_i32 as *const fn());
+        func.push(&n as *const i32);
+        let outcome = unsafe { func.call_in_fork(Convention::Cdecl) }.unwrap();
+        match outcome {
+            ForkOutcome::Returned { ret, errno } => {
+                assert_eq!(ret.as_i32(), 7);
+                assert_eq!(errno, 0);
+            }
+            _ => panic!("unexpected outcome {:?}", outcome),
+        }
+        // 返回值同时写回 `Func`
+        assert_eq!(func.ret_as_i32(), 7);
+    }
+
+    #[test]
+    fn errno() {
+        let mut func = Func::from_raw(close as *const fn());
+        func.push(-1i32);
+        let outcome = unsafe { func.call_in_fork(Convention::Cdecl) }.unwrap();
+        // EBADF
+        assert!(matches!(outcome, ForkOutcome::Returned { errno: 9, .. }));
+        assert_eq!(func.ret_as_i32(), -1);
+    }
+
+    #[test]
+    fn crashed() {
+        let mut func = Func::from_raw(cdecl_func::read_i32 as *const fn());
+        func.push(std::ptr::null::<i32>());
+        let outcome = unsafe { func.call_in_fork(Convention::Cdecl) }.unwrap();
+        // 大多数平台上访问空指针得到 SIGSEGV, macOS 上也可能是 SIGBUS
+        let signal = match outcome {
+            ForkOutcome::Crashed { signal } => signal,
+            _ => panic!("unexpected outcome {:?}", outcome),
+        };
+        assert!(signal == 11 || signal == 10 || signal == 7, "{}", signal);
+
+        // 当前进程不受影响, 之后的调用照常进行
+        let n = 3;
+        let mut func = Func::from_raw(cdecl_func::read_i32 as *const fn());
+        func.push(&n as *const i32);
+        unsafe { func.cdecl() };
+        assert_eq!(func.ret_as_i32(), 3);
+    }
+
+    #[test]
+    fn side_effects_stay_in_child() {
+        let mut x = 1i64;
+        let mut y = 0.0f64;
+        let mut func = Func::from_raw(cdecl_func::split_double as *const fn());
+        func.push(2.5f64)
+            .push(&mut x as *mut i64)
+            .push(&mut y as *mut f64);
+        unsafe { func.call_in_fork(Convention::Cdecl) }.unwrap();
+        assert_eq!((x, y), (1, 0.0));
+    }
+
+    #[test]
+    fn checked_before_fork() {
+        let mut func = Func::from_raw(std::ptr::null());
+        let e = unsafe { func.call_in_fork(Convention::Cdecl) }.unwrap_err();
+        assert!(matches!(e, ForkError::Call(CallError::NullTarget)));
+        assert_eq!(e.to_string(), CallError::NullTarget.to_string());
+    }
+}
+
+#[cfg(feature = "std")]
+mod syscall_result {
+    use super::*;
+
+    #[test]
+    fn error_range() {
+        extern "C" fn ret(n: isize) -> isize {
+            n
+        }
+        for (n, errno) in [
+            (-1, Some(1)),
+            (-4095, Some(4095)),
+            (-4096, None),
+            (0, None),
+            (5, None),
+        ] {
+            let mut func = Func::from_raw(ret as *const fn());
+            func.push(n);
+            unsafe { func.cdecl() };
+            match func.ret_as_syscall_result() {
+                Ok(v) => assert_eq!((v as isize, errno), (n, None)),
+                Err(e) => assert_eq!(e.raw_os_error(), errno),
+            }
+        }
+    }
+
+    #[cfg(all(target_arch = "x86_64", target_os = "linux"))]
+    #[test]
+    fn raw_syscalls() {
+        const SYS_OPEN: usize = 2;
+        const SYS_GETPID: usize = 39;
+
+        let path = b"/nonexistent/funcall\0";
+        let mut func = Func::from_raw(cdecl_func::raw_syscall3 as *const fn());
+        func.push(SYS_OPEN)
+            .push(path.as_ptr())
+            .push(0usize)
+            .push(0usize);
+        unsafe { func.cdecl() };
+        let e = func.ret_as_syscall_result().unwrap_err();
+        assert_eq!(e.kind(), std::io::ErrorKind::NotFound);
+        assert_eq!(e.raw_os_error(), Some(2));
+
+        let mut func = Func::from_raw(cdecl_func::raw_syscall3 as *const fn());
+        func.push(SYS_GETPID).push(0usize).push(0usize).push(0usize);
+        unsafe { func.cdecl() };
+        assert_eq!(
+            func.ret_as_syscall_result().unwrap(),
+            std::process::id() as usize
+        );
+    }
+}
+
+mod owned_ptr {
+    use super::*;
+    use std::os::raw::c_void;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static FREED: AtomicUsize = AtomicUsize::new(0);
+
+    extern "C" fn make_box(n: i32) -> *mut i32 {
+        Box::into_raw(Box::new(n))
+    }
+
+    unsafe extern "C" fn free_box(p: *mut i32) {
+        FREED.fetch_add(*p as usize, Ordering::SeqCst);
+        drop(Box::from_raw(p));
+    }
+
+    fn make(n: i32) -> funcall::OwnedPtr {
+        let mut func = Func::from_raw(make_box as *const fn());
+        func.push(n);
+        unsafe { func.try_call(Convention::Cdecl).unwrap() };
+        func.ret_as_owned_ptr(Func::from_raw(free_box as *const fn()))
+    }
+
+    #[test]
+    fn freed_once() {
+        let owned = make(1);
+        assert_eq!(unsafe { *(owned.as_ptr() as *const i32) }, 1);
+        drop(owned);
+        assert_eq!(FREED.load(Ordering::SeqCst), 1);
+
+        // 释放时清空释放函数中已压入的参数
+        let mut free = Func::from_raw(free_box as *const fn());
+        free.push(0usize);
+        let mut func = Func::from_raw(make_box as *const fn());
+        func.push(10i32);
+        unsafe { func.try_call(Convention::Cdecl).unwrap() };
+        drop(func.ret_as_owned_ptr(free));
+        assert_eq!(FREED.load(Ordering::SeqCst), 11);
+
+        // 放弃所有权后不再释放
+        let p = make(100).into_raw();
+        assert_eq!(FREED.load(Ordering::SeqCst), 11);
+        unsafe { free_box(p as *mut i32) };
+        assert_eq!(FREED.load(Ordering::SeqCst), 111);
+    }
+
+    #[test]
+    fn null() {
+        extern "C" fn null() -> *mut c_void {
+            std::ptr::null_mut()
+        }
+        let mut func = Func::from_raw(null as *const fn());
+        unsafe { func.try_call(Convention::Cdecl).unwrap() };
+        let owned = func.ret_as_owned_ptr(Func::from_raw(std::ptr::null()));
+        assert!(owned.is_null());
+        // 不会调用空的释放函数
+        drop(owned);
+    }
+
+    #[cfg(feature = "loader")]
+    #[test]
+    fn strdup_free() {
+        let mut strdup =
+            funcall::libc_func(if cfg!(windows) { "_strdup" } else { "strdup" }).unwrap();
+        strdup.push(b"funcall\0".as_ptr());
+        unsafe { strdup.try_call(Convention::Cdecl).unwrap() };
+        let owned = strdup.ret_as_owned_ptr(funcall::libc_func("free").unwrap());
+        let s = unsafe { CStr::from_ptr(owned.as_ptr() as *const std::os::raw::c_char) };
+        assert_eq!(s.to_str(), Ok("funcall"));
+    }
+}
+
+#[cfg(unix)]
+mod va_list {
+    use super::*;
+    use std::os::raw::c_char;
+
+    extern "C" {
+        fn vsnprintf(buf: *mut c_char, size: usize, fmt: *const c_char, ap: *mut u8) -> i32;
+    }
+
+    fn format(func: &mut Func, buf: &[u8]) -> String {
+        unsafe { func.try_call(Convention::Cdecl).unwrap() };
+        String::from_utf8(buf[..func.ret_as_usize()].to_vec()).unwrap()
+    }
+
+    #[test]
+    fn vsnprintf_mixed() {
+        // 整数与浮点数都超出寄存器保存区, 中间夹有 64 位整数与字符串
+        let fmt = "%d %u %hhd %lld %s %ld %d %d %d %d %.3f %f %.2f %g %g %g %g %g %g %g %llu %s\0";
+        let args = [
+            Arg::I32(-1),
+            Arg::U32(4_000_000_000),
+            Arg::I8(-8),
+            Arg::I64(-1 << 40),
+            Arg::Str("str".to_string()),
+            Arg::Isize(-5),
+            Arg::I16(-16),
+            Arg::U16(65535),
+            Arg::U8(255),
+            Arg::I32(9),
+            Arg::F64(1.0 / 3.0),
+            Arg::F32(2.5),
+            Arg::F64(-0.125),
+            Arg::F64(4.0),
+            Arg::F64(5.0),
+            Arg::F64(6.0),
+            Arg::F64(7.0),
+            Arg::F64(8.0),
+            Arg::F64(9.0),
+            Arg::F64(10.0),
+            Arg::U64(u64::MAX),
+            Arg::Bytes(b"bytes\0".to_vec()),
+        ];
+        let expected = format!(
+            "{} {} {} {} {} {} {} {} {} {} {:.3} {:.6} {:.2} 4 5 6 7 8 9 10 {} bytes",
+            -1,
+            4_000_000_000u32,
+            -8,
+            -1i64 << 40,
+            "str",
+            -5,
+            -16,
+            65535,
+            255,
+            9,
+            1.0 / 3.0,
+            2.5,
+            -0.125,
+            u64::MAX
+        );
+        let mut buf = [0u8; 256];
+        let mut func = Func::from_raw(vsnprintf as *const fn());
+        func.push(buf.as_mut_ptr())
+            .push(buf.len())
+            .push(fmt.as_ptr());
+        func.push_va_list(&args).unwrap();
+        assert_eq!(format(&mut func, &buf), expected);
+    }
+
+    #[test]
+    fn cloned() {
+        let mut buf = [0u8; 64];
+        let mut func = Func::from_raw(vsnprintf as *const fn());
+        func.push(buf.as_mut_ptr())
+            .push(buf.len())
+            .push(b"%s %d %s\0".as_ptr());
+        func.push_va_list(&[
+            Arg::Str("a".to_string()),
+            Arg::I32(1),
+            Arg::Str("b".to_string()),
+        ])
+        .unwrap();
+        // 副本中的 va_list 指向复制出的字符串, 原来的 Func 释放后仍然有效
+        let mut cloned = func.clone();
+        drop(func);
+        assert_eq!(format(&mut cloned, &buf), "a 1 b");
+    }
+
+    #[test]
+    #[cfg(all(target_arch = "x86_64", target_os = "linux"))]
+    fn cloned_self_pointers() {
+        let mut func = Func::from_raw(vsnprintf as *const fn());
+        func.push_va_list(&[Arg::I32(1), Arg::F64(2.0)]).unwrap();
+        let cloned = func.clone();
+        let base = |func: &Func| func.arg_views().last().unwrap().bits() as usize;
+        let words =
+            |func: &Func| unsafe { std::slice::from_raw_parts(base(func) as *const usize, 3) };
+        // va_list 中指向自身缓冲区的指针在副本中指向副本的同一位置
+        let mut relocated = 0;
+        for (&orig, &copy) in words(&func).iter().zip(words(&cloned)) {
+            let offset = orig.wrapping_sub(base(&func));
+            if offset <= 4096 {
+                assert_eq!(copy.wrapping_sub(base(&cloned)), offset);
+                relocated += 1;
+            }
+        }
+        assert_eq!(relocated, 2);
+    }
+}
+
+#[cfg(feature = "provenance")]
+mod provenance {
+    use super::*;
+
+    /// 执行压入并返回所在的行, 宏中的 `line!` 与压入方法记下的位置都是宏调用的那一行
+    macro_rules! at {
+        ($push:expr) => {{
+            $push;
+            line!()
+        }};
+    }
+
+    fn lines(func: &Func) -> Vec<u32> {
+        func.arg_views()
+            .map(|arg| {
+                let origin = arg.origin().unwrap();
+                assert_eq!(origin.file(), file!());
+                origin.line()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn push_sites() {
+        let mut func = Func::from_raw(std::ptr::null());
+        let expected = vec![
+            at!(func.push(1i32)),
+            at!(func.push_float(2.0)),
+            at!(func.push_arg(Arg::Str("s".to_string()))),
+            at!(func.push_c_long(3)),
+            at!(func.push_aligned(4u64, 16)),
+        ];
+        let last = at!(func.push_cstring_array(&["a", "b"]).unwrap());
+        let mut expected = expected;
+        expected.push(last);
+        assert_eq!(lines(&func), expected);
+    }
+
+    #[test]
+    fn checked_pushes() {
+        let mut func = Func::from_raw(std::ptr::null());
+        func.set_signature(Signature::parse("int f(short, double, ...)").unwrap());
+        let expected = vec![
+            at!(func.push(1i32)),
+            at!(func.try_push(2.0f32).unwrap()),
+            at!(func.push_arg(Arg::I8(3))),
+        ];
+        assert_eq!(lines(&func), expected);
+        // 以 push_fmt 压入的参数都记为同一行
+        let mut func = Func::from_raw(std::ptr::null());
+        let args = [Arg::I32(1), Arg::Str("s".to_string())];
+        let line = at!(func.push_fmt("iz", &args).unwrap());
+        assert_eq!(lines(&func), vec![line, line]);
+    }
+
+    #[test]
+    fn debug_output() {
+        let mut func = Func::from_raw(std::ptr::null());
+        let line = at!(func.push(7i32));
+        let debug = format!("{:?}", func);
+        assert!(
+            debug.contains(&format!("i32 7 at {}:{}:", file!(), line)),
+            "{}",
+            debug
+        );
+    }
+}
+
+#[cfg(feature = "std")]
+mod memoize {
+    use super::*;
+    use funcall::{Arg, CacheStats};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn repeated_args() {
+        static CALLS: AtomicUsize = AtomicUsize::new(0);
+        extern "C" fn add(a: i32, b: f64) -> f64 {
+            CALLS.fetch_add(1, Ordering::SeqCst);
+            a as f64 + b
+        }
+
+        let mut func = Func::from_raw(add as *const fn());
+        func.memoize(true);
+        func.push(1i32).push_arg(Arg::F64(0.5));
+        for _ in 0..3 {
+            unsafe { func.try_call(Convention::Cdecl).unwrap() };
+            assert_eq!(func.ret_as_f64(), 1.5);
+        }
+        assert_eq!(CALLS.load(Ordering::SeqCst), 1);
+        assert_eq!(
+            func.cache_stats(),
+            CacheStats {
+                hits: 2,
+                misses: 1,
+                entries: 1
+            }
+        );
+
+        func.clear_cache();
+        unsafe { func.try_call(Convention::Cdecl).unwrap() };
+        assert_eq!(CALLS.load(Ordering::SeqCst), 2);
+        func.memoize(false);
+        assert_eq!(func.cache_stats(), CacheStats::default());
+        unsafe { func.try_call(Convention::Cdecl).unwrap() };
+        assert_eq!(CALLS.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn changed_contents() {
+        static CALLS: AtomicUsize = AtomicUsize::new(0);
+        unsafe extern "C" fn bump(p: *mut u8) -> u8 {
+            CALLS.fetch_add(1, Ordering::SeqCst);
+            *p += 1;
+            *p
+        }
+
+        // 被调用者修改了缓冲区, 下一次调用的参数随之不同
+        let mut func = Func::from_raw(bump as *const fn());
+        func.memoize(true);
+        func.push_arg(Arg::Bytes(vec![0]));
+        for n in 1..=3 {
+            unsafe { func.try_call(Convention::Cdecl).unwrap() };
+            assert_eq!(func.ret_as_u8(), n);
+        }
+        assert_eq!(CALLS.load(Ordering::SeqCst), 3);
+        assert_eq!(func.cache_stats().hits, 0);
+    }
+
+    #[test]
+    fn cloned_hits() {
+        static CALLS: AtomicUsize = AtomicUsize::new(0);
+        unsafe extern "C" fn len(s: *const u8) -> usize {
+            CALLS.fetch_add(1, Ordering::SeqCst);
+            std::ffi::CStr::from_ptr(s.cast()).to_bytes().len()
+        }
+
+        let mut func = Func::from_raw(len as *const fn());
+        func.memoize(true);
+        func.push_arg(Arg::Str("hello".into()));
+        unsafe { func.try_call(Convention::Cdecl).unwrap() };
+
+        // 克隆中的字符串复制到了新的地址, 按内容查找仍然命中
+        let mut cloned = func.clone();
+        assert_eq!(cloned.cache_stats().hits, 0);
+        unsafe { cloned.try_call(Convention::Cdecl).unwrap() };
+        assert_eq!(cloned.ret_as_usize(), 5);
+        assert_eq!(cloned.cache_stats().hits, 1);
+        assert_eq!(CALLS.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn out_param_restored() {
+        static CALLS: AtomicUsize = AtomicUsize::new(0);
+        unsafe extern "C" fn fill(out: *mut u8, n: u8) {
+            CALLS.fetch_add(1, Ordering::SeqCst);
+            *out = n;
+        }
+
+        let mut func = Func::from_raw(fill as *const fn());
+        func.memoize(true);
+        let out = func.push_out_param::<u8>();
+        func.push(7u8);
+        let mut calls = Vec::new();
+        for _ in 0..3 {
+            unsafe { func.try_call(Convention::Cdecl).unwrap() };
+            calls.push(CALLS.load(Ordering::SeqCst));
+            assert_eq!(func.read_out(out), 7);
+        }
+        // 第二次调用时输出参数已被写入, 与第一次的输入不同
+        assert_eq!(calls, [1, 2, 2]);
+    }
+}
+
+#[cfg(all(feature = "loader", target_os = "linux"))]
+mod resolver {
+    use super::*;
+    use funcall::{DefaultResolver, Error, Library, LibraryResolver, ResolvedLib};
+    use std::ffi::OsStr;
+
+    /// 只处理测试用的库名, 其他库名交给默认的解析器, 以免影响同时运行的其他测试
+    struct Manifest;
+
+    impl LibraryResolver for Manifest {
+        fn resolve(&self, requested: &OsStr) -> funcall::Result<ResolvedLib> {
+            match requested.to_str() {
+                Some("plugin:libc") => Ok(ResolvedLib::Path("libc.so.6".into())),
+                Some("plugin:handle") => Ok(ResolvedLib::Handle(Library::libc()?)),
+                Some("plugin:bytes") => Ok(ResolvedLib::Bytes(std::fs::read(libc_path())?)),
+                Some(name) if name.starts_with("plugin:") => {
+                    Err(Error::NotFound(format!("{} is not in the manifest", name)))
+                }
+                _ => DefaultResolver.resolve(requested),
+            }
+        }
+    }
+
+    /// 从 /proc/self/maps 中找到已映射的 libc 文件
+    fn libc_path() -> String {
+        let maps = std::fs::read_to_string("/proc/self/maps").unwrap();
+        maps.lines()
+            .filter_map(|line| line.split_whitespace().nth(5))
+            .find(|path| path.contains("/libc.so") || path.contains("/libc-"))
+            .unwrap()
+            .to_owned()
+    }
+
+    fn abs(lib: &str) -> i32 {
+        let mut func = Func::new(lib, b"abs").unwrap();
+        func.push(-3i32);
+        unsafe { func.try_call(Convention::Cdecl).unwrap() };
+        func.ret_as_i32()
+    }
+
+    #[test]
+    fn redirect_and_deny() {
+        funcall::set_resolver(Box::new(Manifest));
+        assert_eq!(abs("plugin:libc"), 3);
+        assert_eq!(abs("plugin:handle"), 3);
+        assert_eq!(abs("plugin:bytes"), 3);
+        assert_eq!(
+            Library::new("plugin:libc").unwrap().path().unwrap(),
+            std::path::Path::new("libc.so.6")
+        );
+
+        let err = Library::new("plugin:evil").unwrap_err();
+        assert!(
+            matches!(&err, Error::NotFound(msg) if msg == "plugin:evil is not in the manifest")
+        );
+
+        funcall::clear_resolver();
+        assert!(Library::new("plugin:libc").is_err());
+    }
+}
+
+#[cfg(all(
+    feature = "unwind",
+    target_arch = "x86_64",
+    any(target_os = "linux", windows)
+))]
+mod unwind_policy {
+    use super::*;
+    use funcall::UnwindPolicy;
+    use std::panic::{self, AssertUnwindSafe};
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    static FAIL: AtomicBool = AtomicBool::new(true);
+
+    extern "C-unwind" fn checked(x: i32) -> i32 {
+        if FAIL.load(Ordering::SeqCst) {
+            panic!("callback failed on {}", x);
+        }
+        x * 2
+    }
+
+    #[test]
+    fn panic_propagates() {
+        let canary = [0x5au8; 64];
+        let mut func = Func::from_raw(cdecl_func::apply_callback as *const fn());
+        func.set_unwind_policy(UnwindPolicy::Catch);
+        func.push(checked as *const ()).push(21i32);
+
+        let payload =
+            panic::catch_unwind(AssertUnwindSafe(|| unsafe { func.cdecl() })).unwrap_err();
+        assert_eq!(
+            payload.downcast_ref::<String>().unwrap(),
+            "callback failed on 21"
+        );
+        assert_eq!(canary, [0x5au8; 64]);
+
+        // 展开之后同一个 Func 仍可以正常调用
+        FAIL.store(false, Ordering::SeqCst);
+        unsafe { func.try_call(Convention::Cdecl).unwrap() };
+        assert_eq!(func.ret_as_i32(), 43);
+        FAIL.store(true, Ordering::SeqCst);
+        let caught = panic::catch_unwind(AssertUnwindSafe(|| unsafe {
+            func.try_call(Convention::Cdecl).unwrap()
+        }));
+        assert!(caught.is_err());
+    }
+
+    #[test]
+    fn same_results() {
+        let mut abort = Func::from_raw(cdecl_func::floats_then_int as *const fn());
+        for i in 0..9 {
+            abort.push(i as f64 + 0.5);
+        }
+        abort.push(2i32);
+        let mut catch = abort.clone();
+        catch.set_unwind_policy(UnwindPolicy::Catch);
+        unsafe {
+            abort.try_call(Convention::Cdecl).unwrap();
+            catch.try_call(Convention::Cdecl).unwrap();
+        }
+        assert_eq!(catch.ret_as_f64(), abort.ret_as_f64());
+
+        let mut func = Func::from_raw(cdecl_func::more_than_6_args as *const fn());
+        func.set_unwind_policy(UnwindPolicy::Catch);
+        func.push_args((1i32, 2i32, 3i32, 4i32, 5i32, 6i32, 7i32, 8i32));
+        unsafe { func.try_call(Convention::Cdecl).unwrap() };
+        assert_eq!(func.ret_as_i32(), 36);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn panic_in_timeout() {
+        extern "C-unwind" fn always(x: i32) -> i32 {
+            panic!("callback failed on {}", x);
+        }
+        let mut func = Func::from_raw(cdecl_func::apply_callback as *const fn());
+        func.set_unwind_policy(UnwindPolicy::Catch);
+        func.push(always as *const ()).push(3i32);
+
+        // panic 在调用者的线程中继续, 而不是报告为超时
+        let timeout = std::time::Duration::from_secs(10);
+        let payload = panic::catch_unwind(AssertUnwindSafe(|| unsafe {
+            func.call_with_timeout(Convention::Cdecl, timeout)
+        }))
+        .unwrap_err();
+        assert_eq!(
+            payload.downcast_ref::<String>().unwrap(),
+            "callback failed on 3"
+        );
+        // 调用已经结束, 不会被标记为中毒
+        assert!(!func.is_poisoned());
+    }
+
+    #[cfg(feature = "libffi")]
+    #[test]
+    fn unsupported_backend() {
+        let mut func = Func::from_raw(cdecl_func::apply_callback as *const fn());
+        func.set_unwind_policy(UnwindPolicy::Catch);
+        func.push(checked as *const ()).push(1i32);
+        assert_eq!(
+            unsafe { func.try_call_with(Convention::Cdecl, funcall::Backend::Libffi) },
+            Err(CallError::UnsupportedUnwind(funcall::Backend::Libffi))
+        );
+    }
+}
+
+#[cfg(all(feature = "loader", target_os = "linux"))]
+mod open_first {
+    use super::*;
+    use funcall::Library;
+    use std::path::Path;
+
+    #[test]
+    fn first_success_wins() {
+        let (lib, name) =
+            Library::open_first(&["libfuncall_no_such.so", "libc.so.6", "libm.so.6"]).unwrap();
+        assert_eq!(name, "libc.so.6");
+        assert!(lib.get("strlen").is_ok());
+    }
+
+    #[test]
+    fn all_candidates_reported() {
+        let err =
+            Library::open_first(&["libfuncall_no_such.so", "libfuncall_no_such.so.1"]).unwrap_err();
+        let failed = err
+            .failed
+            .iter()
+            .map(|(name, _)| name.to_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(failed, ["libfuncall_no_such.so", "libfuncall_no_such.so.1"]);
+        assert!(err.to_string().contains("libfuncall_no_such.so.1: "));
+    }
+
+    #[test]
+    fn func_skips_missing_symbol() {
+        let path = cdylib::build(
+            "open_first_fixture",
+            "#[no_mangle] pub extern \"C\" fn open_first_answer() -> i32 { 42 }",
+        );
+        let libs = [
+            Path::new("libfuncall_no_such.so"),
+            Path::new("libc.so.6"),
+            &path,
+        ];
+        let (mut func, name) = Func::new_first(&libs, b"open_first_answer\0").unwrap();
+        assert_eq!(name, path.as_os_str());
+        unsafe { func.try_call(Convention::Cdecl).unwrap() };
+        assert_eq!(func.ret_as_i32(), 42);
+
+        let err = Func::new_first(&libs[..2], b"open_first_answer\0").unwrap_err();
+        assert_eq!(err.failed.len(), 2);
+    }
+}
+
+mod f16 {
+    use super::*;
+    use funcall::F16;
+
+    #[test]
+    fn conversions() {
+        let cases: &[(f32, u16)] = &[
+            (0.0, 0x0000),
+            (-0.0, 0x8000),
+            (1.0, 0x3c00),
+            (-2.5, 0xc100),
+            (65504.0, 0x7bff),
+            // 超出最大的有限值一半以上的进位为无穷大
+            (65520.0, 0x7c00),
+            (f32::INFINITY, 0x7c00),
+            (2.0f32.powi(-14), 0x0400),
+            (2.0f32.powi(-24), 0x0001),
+            // 恰在 0 与最小的非规格化数中间, 舍入到偶数 0
+            (2.0f32.powi(-25), 0x0000),
+            (3.0 * 2.0f32.powi(-26), 0x0001),
+            // 1 + 2^-11 恰在 1 与下一个数中间, 舍入到偶数
+            (1.0 + 2.0f32.powi(-11), 0x3c00),
+            (1.0 + 3.0 * 2.0f32.powi(-11), 0x3c02),
+        ];
+        for &(value, bits) in cases {
+            assert_eq!(F16::from_f32(value).to_bits(), bits, "{}", value);
+        }
+        assert_eq!(F16::from_f32(f32::NAN).to_bits() & 0x7e00, 0x7e00);
+
+        // 所有非 NaN 的半精度数经 f32 往返后不变
+        for bits in 0..=u16::MAX {
+            let half = F16::from_bits(bits);
+            if bits & 0x7c00 == 0x7c00 && bits & 0x3ff != 0 {
+                assert!(half.to_f32().is_nan());
+                continue;
+            }
+            assert_eq!(F16::from_f32(half.to_f32()), half, "{:#06x}", bits);
+        }
+        assert_eq!(F16::from_bits(0x0001).to_f32(), 2.0f32.powi(-24));
+        assert_eq!(F16::from_bits(0x3555).to_string(), "0.33325195");
+    }
+
+    #[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+    #[test]
+    fn echo_bits() {
+        // 含非规格化数, 负零, 无穷大与带负载的 NaN
+        for &bits in &[0x3c00u16, 0x0001, 0x8000, 0xfc00, 0x7c01, 0xfe55, 0x7bff] {
+            let mut func = Func::from_raw(cdecl_func::echo_f16 as *const fn());
+            func.push(F16::from_bits(bits));
+            assert_eq!(
+                format!("{}", func.arg_views().next().unwrap()).get(..4),
+                Some("f16 ")
+            );
+            unsafe { func.try_call(Convention::Cdecl).unwrap() };
+            assert_eq!(func.ret_as_f16().to_bits(), bits);
+            assert_eq!(func.ret().get::<F16>().to_bits(), bits);
+        }
+    }
+
+    #[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+    #[test]
+    fn float_registers() {
+        let mut func = Func::from_raw(cdecl_func::f16_pair as *const fn());
+        func.push(7i32)
+            .push(F16::from_f32(1.5))
+            .push(2.5f64)
+            .push(F16::from_f32(-0.25));
+        let plan = func.dry_run(Convention::Cdecl);
+        assert!(matches!(
+            plan.args[1].locations[0],
+            funcall::ArgLocation::FloatReg(_)
+        ));
+        unsafe { func.try_call(Convention::Cdecl).unwrap() };
+        assert_eq!(func.ret_as_u32(), 0xb400_3e00);
+    }
+
+    #[cfg(all(
+        feature = "libffi",
+        any(target_arch = "x86_64", target_arch = "aarch64")
+    ))]
+    #[test]
+    fn libffi_backend() {
+        let mut func = Func::from_raw(cdecl_func::echo_f16 as *const fn());
+        func.set_ret_kind(funcall::ArgKind::F16);
+        func.push(F16::from_bits(0x7c01));
+        unsafe {
+            func.try_call_with(Convention::Cdecl, funcall::Backend::Libffi)
+                .unwrap()
+        };
+        assert_eq!(func.ret_as_f16().to_bits(), 0x7c01);
+    }
+
+    #[cfg(feature = "half")]
+    #[test]
+    fn half_interop() {
+        let value = half::f16::from_f32(0.1);
+        assert_eq!(F16::from(value).to_bits(), value.to_bits());
+        assert_eq!(half::f16::from(F16::from_f32(0.1)), value);
     }
 }